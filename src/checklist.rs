@@ -0,0 +1,170 @@
+/// A line of an entry that is an item of a checklist like `* [ ] text`.
+#[derive(Debug, Clone, Copy)]
+struct Item {
+    /// Nesting of the item. Both asciidoc (`**`) and markdown (indentation)
+    /// nesting increase it.
+    level: usize,
+    checked: bool,
+}
+
+/// Number of checklist items in the text that are not checked.
+pub(super) fn unchecked_count(text: &str) -> usize {
+    text.lines()
+        .filter_map(parse_item)
+        .filter(|item| !item.checked)
+        .count()
+}
+
+/// Text for a follow-up entry that only keeps the unchecked checklist items
+/// of the text. Checked items are removed together with everything nested
+/// below them unless something nested is still unchecked. Lines that are not
+/// part of a checklist are kept as context. `None` if there are no unchecked
+/// items.
+pub(super) fn carry_over(text: &str) -> Option<String> {
+    if unchecked_count(text) == 0 {
+        return None;
+    }
+
+    let lines = text.lines().collect::<Vec<_>>();
+    let items = lines
+        .iter()
+        .map(|line| parse_item(line))
+        .collect::<Vec<_>>();
+    let mut dropped = vec![false; lines.len()];
+
+    for (index, item) in items.iter().enumerate() {
+        let item = match item {
+            Some(item) if item.checked => item,
+            _ => continue,
+        };
+
+        let end = subtree_end(&lines, &items, index, item.level);
+        let unchecked_below = items[index + 1..end]
+            .iter()
+            .flatten()
+            .any(|nested| !nested.checked);
+
+        if !unchecked_below {
+            dropped[index..end].iter_mut().for_each(|drop| *drop = true);
+        }
+    }
+
+    let mut out = lines
+        .iter()
+        .zip(dropped)
+        .filter(|(_, dropped)| !dropped)
+        .map(|(line, _)| *line)
+        .collect::<Vec<_>>()
+        .join("\n");
+    out.push('\n');
+
+    Some(out)
+}
+
+/// Index after the last line that belongs to the item at `index`. Nested
+/// items and indented lines belong to the item, the next item on the same or
+/// a higher level or a line that is not indented ends it.
+fn subtree_end(lines: &[&str], items: &[Option<Item>], index: usize, level: usize) -> usize {
+    let mut end = index + 1;
+
+    while end < lines.len() {
+        let belongs = match items[end] {
+            Some(nested) => nested.level > level,
+            None => lines[end].starts_with(char::is_whitespace) || lines[end] == "+",
+        };
+
+        if !belongs {
+            break;
+        }
+
+        end += 1;
+    }
+
+    end
+}
+
+fn parse_item(line: &str) -> Option<Item> {
+    let trimmed = line.trim_start();
+    let indent = line.len() - trimmed.len();
+
+    let marker = trimmed
+        .chars()
+        .next()
+        .filter(|c| matches!(c, '*' | '-' | '+'))?;
+    let rest = trimmed.trim_start_matches(marker);
+    let depth = trimmed.len() - rest.len();
+
+    let rest = rest.strip_prefix(' ')?.trim_start();
+
+    let checked = if rest.starts_with("[ ]") {
+        false
+    } else if rest.starts_with("[x]") || rest.starts_with("[X]") || rest.starts_with("[*]") {
+        true
+    } else {
+        return None;
+    };
+
+    Some(Item {
+        level: indent + depth,
+        checked,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_unchecked_items() {
+        let text = "* [ ] one\n* [x] two\n** [ ] three\n- [X] four\n  - [ ] five\n* six\n";
+
+        assert_eq!(unchecked_count(text), 3);
+        assert_eq!(unchecked_count("no checklist\n* plain item\n"), 0);
+    }
+
+    #[test]
+    fn without_unchecked_items_nothing_is_carried_over() {
+        assert_eq!(carry_over("== Notes\n\nno checklist here\n"), None);
+        assert_eq!(carry_over("* [x] done\n* [*] also done\n"), None);
+        assert_eq!(carry_over(""), None);
+    }
+
+    #[test]
+    fn keeps_unchecked_items_and_context() {
+        let text = "== Release\n\nSteps:\n\n* [x] tag\n* [ ] publish\n* [x] announce\n\nDone when \
+                    published.\n";
+
+        assert_eq!(
+            carry_over(text).unwrap(),
+            "== Release\n\nSteps:\n\n* [ ] publish\n\nDone when published.\n"
+        );
+    }
+
+    #[test]
+    fn nested_asciidoc_items() {
+        let text =
+            "* [x] backend\n** [x] api\n** [ ] tests\n* [x] frontend\n** [x] page\n* [ ] docs\n";
+
+        assert_eq!(
+            carry_over(text).unwrap(),
+            "* [x] backend\n** [ ] tests\n* [ ] docs\n"
+        );
+    }
+
+    #[test]
+    fn nested_markdown_items() {
+        let text = "- [x] backend\n  - [ ] tests\n    - [x] unit\n- [x] frontend\n  - [x] page\n";
+
+        assert_eq!(carry_over(text).unwrap(), "- [x] backend\n  - [ ] tests\n");
+    }
+
+    #[test]
+    fn continuation_lines_belong_to_their_item() {
+        let text = "* [x] tag\n+\n  git tag v1.0\n* [ ] publish\n  cargo publish\n";
+
+        assert_eq!(
+            carry_over(text).unwrap(),
+            "* [ ] publish\n  cargo publish\n"
+        );
+    }
+}
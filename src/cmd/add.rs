@@ -0,0 +1,214 @@
+use crate::{
+    cmd::{
+        check_new_project,
+        open_store,
+        read_path_or_stdin,
+    },
+    config::Config,
+    entry::{
+        Entry,
+        EntrySource,
+    },
+    helper,
+    helper::string_from_editor,
+    interactivity::Interactivity,
+    opt::*,
+    porcelain,
+    store::Store,
+    templating,
+    time_context::TimeContext,
+};
+use anyhow::{
+    bail,
+    Context,
+    Error,
+};
+use std::collections::{
+    BTreeMap,
+    BTreeSet,
+};
+
+pub(crate) fn run_add(opt: AddSubCommandOpts, config: Config) -> Result<(), Error> {
+    if opt.from_file.is_some() {
+        return run_add_from_file(opt, config);
+    }
+
+    let time = TimeContext::from_config(&config);
+    let yes = opt.yes || config.auto_create_projects;
+    let quiet = config.quiet;
+    let store = open_store(&opt.datadir_opt.datadir, config)?;
+
+    // Ask before the editor runs so the text is not lost when declining.
+    check_new_project(&store, &opt.project_opt.project, yes)?;
+
+    let text = if let Some(template) = &opt.template {
+        let variables = opt.vars.into_iter().collect();
+        let rendered = templating::render_entry_template(
+            &opt.datadir_opt.datadir,
+            template,
+            &opt.project_opt.project,
+            &variables,
+            &time,
+        )?;
+
+        if opt.edit {
+            text_from_editor(Some(&rendered), store.interactivity())?
+        } else {
+            rendered
+        }
+    } else if let Some(opt_text) = &opt.text {
+        opt_text.clone()
+    } else {
+        text_from_editor(None, store.interactivity())?
+    };
+
+    let entry = Entry::builder(text, opt.project_opt.project, EntrySource::Cli)
+        .tags(opt.tags.into_iter().collect())
+        .build()?;
+
+    store
+        .add_entry(entry.clone())
+        .context("can not add entry to store")?;
+
+    print_added_entries(&store, &[entry], quiet, opt.porcelain)
+}
+
+/// Text for a new entry from the editor. Empty texts are not added.
+pub(crate) fn text_from_editor(
+    prepopulate: Option<&str>,
+    interactivity: Interactivity,
+) -> Result<String, Error> {
+    let text = string_from_editor(prepopulate, interactivity)
+        .context("can not get message from editor")?;
+
+    non_empty_text(text)
+}
+
+fn non_empty_text(text: String) -> Result<String, Error> {
+    if text.trim().is_empty() {
+        bail!("the text from the editor is empty, not adding entry then")
+    }
+
+    Ok(text)
+}
+
+/// Print a confirmation with the id, uuid and project of the added entries so
+/// they can be referenced right away.
+pub(crate) fn print_added_entries(
+    store: &Store,
+    added: &[Entry],
+    quiet: bool,
+    porcelain: bool,
+) -> Result<(), Error> {
+    if quiet {
+        return Ok(());
+    }
+
+    if porcelain {
+        for entry in added {
+            println!("{}", porcelain::added_record(entry));
+        }
+
+        return Ok(());
+    }
+
+    let mut active_entries = BTreeMap::new();
+
+    for entry in added {
+        let project = &entry.metadata.project;
+
+        if !active_entries.contains_key(project) {
+            let entries = store
+                .get_active_entries(project)
+                .context("can not get entries from store")?;
+
+            active_entries.insert(project.clone(), entries);
+        }
+
+        let id = active_entries[project]
+            .id_of(&entry.metadata.uuid)
+            .map_or_else(|| "-".to_string(), |id| id.to_string());
+
+        println!(
+            "added entry {} ({}) to project {}",
+            id, entry.metadata.uuid, project
+        );
+    }
+
+    Ok(())
+}
+
+pub(crate) fn run_add_from_file(opt: AddSubCommandOpts, config: Config) -> Result<(), Error> {
+    let path = opt.from_file.expect("from_file is checked by the caller");
+    let project = opt.project_opt.project;
+
+    let content = read_path_or_stdin(&path)?;
+
+    let texts = match opt.split.unwrap_or_default() {
+        SplitMode::Lines => helper::split_lines(&content),
+        SplitMode::Sections => helper::split_sections(&content),
+    };
+
+    if texts.is_empty() {
+        bail!("no entries found in {:?}", path)
+    }
+
+    let yes = opt.yes || config.auto_create_projects;
+    let quiet = config.quiet;
+    let store = open_store(&opt.datadir_opt.datadir, config)?;
+    check_new_project(&store, &project, yes)?;
+
+    let tags = opt.tags.into_iter().collect::<BTreeSet<_>>();
+    let entries = texts
+        .into_iter()
+        .map(|text| {
+            Entry::builder(text, project.clone(), EntrySource::Import)
+                .tags(tags.clone())
+                .build()
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    store
+        .add_entries(&entries)
+        .context("can not add entries to store")?;
+
+    print_added_entries(&store, &entries, quiet, opt.porcelain)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::tests::edit_with;
+
+    fn add(script: &str, prepopulate: Option<&str>) -> Result<String, Error> {
+        non_empty_text(edit_with(script, prepopulate)?)
+    }
+
+    #[test]
+    fn saved_text_is_added() {
+        assert_eq!(add("printf 'Buy milk' > \"$1\"", None).unwrap(), "Buy milk");
+    }
+
+    #[test]
+    fn unchanged_template_is_added() {
+        assert_eq!(add("true", Some("== Standup\n")).unwrap(), "== Standup\n");
+    }
+
+    #[test]
+    fn empty_text_is_not_added() {
+        let err = add(": > \"$1\"", None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "the text from the editor is empty, not adding entry then"
+        );
+
+        assert!(add("printf ' \\n\\n' > \"$1\"", None).is_err());
+        assert!(add("true", None).is_err());
+    }
+
+    #[test]
+    fn crashed_editor_adds_nothing() {
+        assert!(add("printf 'partial' > \"$1\"\nexit 1", None).is_err());
+        assert!(add("rm \"$1\"", Some("== Standup\n")).is_err());
+    }
+}
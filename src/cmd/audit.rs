@@ -0,0 +1,62 @@
+use crate::{
+    cmd::open_store_for_reading,
+    config::Config,
+    opt::*,
+    store::audit::AuditFilter,
+    table,
+};
+use anyhow::{
+    Context,
+    Error,
+};
+use chrono::{
+    SecondsFormat,
+    Utc,
+};
+
+pub(crate) fn run_audit(opt: AuditSubCommandOpts, config: Config) -> Result<(), Error> {
+    let table_style = config.table_style;
+    let interactivity = config.interactivity;
+    let store = open_store_for_reading(&opt.datadir_opt.datadir, config)?;
+
+    let filter = AuditFilter {
+        uuid: opt.uuid,
+        project: opt.project,
+        since: opt.since.map(|since| Utc::now() - since),
+    };
+
+    let records = store
+        .audit_log()
+        .read(&filter)
+        .context("can not read audit log")?;
+
+    if records.is_empty() {
+        println!(
+            "no matching changes in the audit log in {:?}",
+            store.audit_log().directory()
+        );
+        return Ok(());
+    }
+
+    let mut table = table::new_table(
+        &["Time", "Token", "Action", "Project", "UUID"],
+        None,
+        false,
+        table_style,
+        interactivity,
+    );
+
+    for record in records {
+        table.add_row(vec![
+            record.timestamp.to_rfc3339_opts(SecondsFormat::Secs, true),
+            record.principal,
+            record.action,
+            record.project,
+            record.uuid.to_string(),
+        ]);
+    }
+
+    println!("{}", table);
+
+    Ok(())
+}
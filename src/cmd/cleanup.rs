@@ -0,0 +1,65 @@
+use crate::{
+    cmd::open_store,
+    config::Config,
+    opt::*,
+};
+use anyhow::{
+    Context,
+    Error,
+};
+
+pub(crate) fn run_cleanup(opt: CleanupSubCommandOpts, config: Config) -> Result<(), Error> {
+    let store = open_store(&opt.datadir_opt.datadir, config)?;
+    let report = store.run_cleanup(opt.keep_days, !opt.no_backup)?;
+
+    if opt.verbose {
+        print!("{:#}", report);
+    } else {
+        print!("{}", report);
+    }
+
+    if !opt.merge_similar_projects {
+        return Ok(());
+    }
+
+    for similar in &report.similar_projects {
+        let projects = similar
+            .projects
+            .iter()
+            .map(|project| format!("{:?}", project))
+            .collect::<Vec<_>>();
+
+        let message = format!(
+            "do you want to merge the projects {} into {:?}?",
+            projects.join(", "),
+            similar.canonical
+        );
+
+        let hint =
+            "run cleanup --merge_similar_projects in a terminal to pick the projects to merge";
+        if !store.interactivity().confirm(&message, false, hint)? {
+            continue;
+        }
+
+        let moved = store
+            .merge_projects(similar)
+            .with_context(|| format!("can not merge projects into {:?}", similar.canonical))?;
+
+        println!("moved {} entries to {:?}", moved, similar.canonical);
+    }
+
+    Ok(())
+}
+
+pub(crate) fn run_archive(opt: ArchiveSubCommandOpts, config: Config) -> Result<(), Error> {
+    let store = open_store(&opt.datadir_opt.datadir, config)?;
+    let report = store.archive_done_before(opt.before, !opt.no_backup)?;
+
+    if opt.verbose {
+        print!("{:#}", report);
+    } else {
+        print!("{}", report);
+    }
+
+    Ok(())
+}
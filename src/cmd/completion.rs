@@ -0,0 +1,11 @@
+use crate::opt::*;
+use anyhow::Error;
+use std::env;
+use structopt::StructOpt;
+
+pub(crate) fn run_completion(opt: CompletionSubCommandOpts) -> Result<(), Error> {
+    std::fs::create_dir_all(&opt.directory)?;
+    Opt::clap().gen_completions(env!("CARGO_PKG_NAME"), opt.shell, opt.directory);
+
+    Ok(())
+}
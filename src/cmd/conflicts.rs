@@ -0,0 +1,113 @@
+use crate::{
+    cmd::open_store,
+    config::Config,
+    helper,
+    opt::*,
+    store::conflicts,
+    table,
+};
+use anyhow::{
+    Context,
+    Error,
+};
+
+pub(crate) fn run_conflicts(opt: ConflictsSubCommandOpts, config: Config) -> Result<(), Error> {
+    let table_style = config.table_style;
+    let interactivity = config.interactivity;
+    let store = open_store(&opt.datadir_opt.datadir, config)?;
+
+    if opt.history {
+        let mut table = table::new_table(
+            &["UUID", "Revision", "Fields", "Detected", "Resolved"],
+            None,
+            false,
+            table_style,
+            interactivity,
+        );
+
+        for record in store.conflict_records()? {
+            table.add_row(vec![
+                record.uuid.to_string(),
+                record.revision.to_string(),
+                record.fields.join(", "),
+                record.detected.to_rfc3339(),
+                record
+                    .resolved
+                    .map(|resolved| resolved.to_rfc3339())
+                    .unwrap_or_else(|| "-".to_string()),
+            ]);
+        }
+
+        println!("{}", table);
+    }
+
+    let changes = store
+        .concurrent_changes()
+        .context("can not get concurrent changes")?;
+
+    if changes.is_empty() {
+        println!("no conflicting changes");
+        return Ok(());
+    }
+
+    for change in changes {
+        let mut header = vec!["Field".to_string()];
+        header.extend(change.rows.iter().map(|(identifier, _)| identifier.clone()));
+
+        let header = header.iter().map(String::as_str).collect::<Vec<_>>();
+        let mut table = table::new_table(&header, None, false, table_style, interactivity);
+
+        for field in &change.fields {
+            let mut row = vec![field.to_string()];
+            row.extend(
+                change
+                    .rows
+                    .iter()
+                    .map(|(_, metadata)| conflicts::field_value(metadata, *field)),
+            );
+            table.add_row(row);
+        }
+
+        println!(
+            "entry {} was changed on {} machines at the same time, the change of {} is used",
+            change.uuid,
+            change.rows.len(),
+            change.rows[0].0
+        );
+        println!("{}", table);
+
+        if !opt.resolve {
+            continue;
+        }
+
+        store
+            .interactivity()
+            .check_prompt("run conflicts --resolve in a terminal to pick the values to keep")?;
+
+        let mut merged = change.winner().clone();
+        for field in &change.fields {
+            let options = change
+                .rows
+                .iter()
+                .map(|(identifier, metadata)| {
+                    format!(
+                        "{} (from {})",
+                        conflicts::field_value(metadata, *field),
+                        identifier
+                    )
+                })
+                .collect::<Vec<_>>();
+
+            let picked = helper::choose(&format!("which {} should be kept?", field), &options)?;
+            conflicts::take_field(&mut merged, &change.rows[picked].1, *field);
+        }
+
+        store
+            .resolve_concurrent_change(&change, merged)
+            .context("can not merge concurrent changes")?;
+
+        println!("merged changes of entry {}", change.uuid);
+    }
+
+    Ok(())
+}
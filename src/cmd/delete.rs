@@ -0,0 +1,47 @@
+use crate::{
+    cmd::{
+        get_entry_by_ref,
+        open_store,
+    },
+    config::Config,
+    helper,
+    opt::*,
+};
+use anyhow::{
+    bail,
+    Context,
+    Error,
+};
+
+pub(crate) fn run_delete(opt: DeleteSubCommandOpts, config: Config) -> Result<(), Error> {
+    let store = open_store(&opt.datadir_opt.datadir, config)?;
+
+    if let EntryRef::Uuid(uuid) = &opt.entry_ref {
+        if let Some(deleted) = store.deleted_at(uuid)? {
+            bail!("entry {} was already deleted at {}", uuid, deleted)
+        }
+    }
+
+    let entry = get_entry_by_ref(&store, opt.entry_ref, &opt.project_opt.project)?;
+
+    let message = format!(
+        "do you want to delete this entry? This can not be undone:\n{:.*}",
+        helper::PROMPT_SUMMARY_WIDTH,
+        entry
+    );
+    if !opt.yes
+        && !store
+            .interactivity()
+            .confirm(&message, false, "pass --yes to delete the entry")?
+    {
+        bail!("not deleting entry then")
+    }
+
+    let report = store
+        .delete_entry(&entry, !opt.no_backup)
+        .with_context(|| format!("can not delete entry {}", entry.metadata.uuid))?;
+
+    print!("{}", report);
+
+    Ok(())
+}
@@ -0,0 +1,41 @@
+use crate::{
+    cmd::open_store_for_reading,
+    config::Config,
+    locale::Translations,
+    opt::*,
+    stats::Digest,
+    time_context::TimeContext,
+};
+use anyhow::{
+    Context,
+    Error,
+};
+
+pub(crate) fn run_digest(opt: DigestSubCommandOpts, config: Config) -> Result<(), Error> {
+    let time = TimeContext::from_config(&config).frozen();
+    let locale = config.locale;
+    let store = open_store_for_reading(&opt.datadir_opt.datadir, config)?;
+
+    let entries = store
+        .get_all_entries(false)
+        .context("can not get entries from store")?;
+
+    let digest = Digest::new(entries, &time, opt.period);
+
+    let rendered = match opt.format {
+        DigestFormat::Asciidoc => digest.to_string(),
+        DigestFormat::Html => crate::webservice::render_digest(
+            &digest,
+            Translations::load(store.datadir(), locale)?,
+            true,
+        )?,
+    };
+
+    match opt.output {
+        Some(path) => std::fs::write(&path, rendered)
+            .with_context(|| format!("can not write digest to {:?}", path))?,
+        None => print!("{}", rendered),
+    }
+
+    Ok(())
+}
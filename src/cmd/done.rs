@@ -0,0 +1,143 @@
+use crate::{
+    cmd::{
+        open_store,
+        print_changed_entries,
+    },
+    config::Config,
+    entry::Entry,
+    helper,
+    helper::ConfirmDone,
+    locale::Translations,
+    opt::*,
+};
+use anyhow::{
+    bail,
+    Context,
+    Error,
+};
+
+pub(crate) fn run_done(opt: DoneSubCommandOpts, config: Config) -> Result<(), Error> {
+    let store = open_store(&opt.datadir_opt.datadir, config)?;
+    store.entry_done(
+        opt.entry_id,
+        &opt.project_opt.project,
+        opt.carry_over,
+        opt.yes,
+    )?;
+
+    Ok(())
+}
+
+pub(crate) fn run_reopen(opt: ReopenSubCommandOpts, config: Config) -> Result<(), Error> {
+    let table_style = config.table_style;
+    let interactivity = config.interactivity;
+    let locale = config.locale;
+    let store = open_store(&opt.datadir_opt.datadir, config)?;
+
+    let old_entries = store.get_done_entries_by_ids(&opt.entry_ids, &opt.project_opt.project)?;
+
+    let new_entries = old_entries
+        .into_iter()
+        .map(|(id, old_entry)| {
+            let new_entry = Entry {
+                metadata: old_entry.metadata.changed().reactivate().build()?,
+                ..old_entry
+            };
+
+            Ok((id, new_entry))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    store
+        .update_entries(new_entries.iter().map(|(_, entry)| entry.clone()).collect())
+        .context("can not reopen entries")?;
+
+    let active = store
+        .get_active_entries(&opt.project_opt.project)
+        .context("can not get project entries")?;
+
+    // Show the ids the entries have now that they are active again.
+    let translations = Translations::load(store.datadir(), locale)?;
+    print_changed_entries(
+        &new_entries,
+        "new_id",
+        table_style,
+        interactivity,
+        &translations,
+        |entry| {
+            active
+                .id_of(&entry.metadata.uuid)
+                .map(|id| id.to_string())
+                .unwrap_or_default()
+        },
+    );
+
+    Ok(())
+}
+
+pub(crate) fn run_undone(opt: UndoneSubCommandOpts, config: Config) -> Result<(), Error> {
+    let mode = if opt.yes {
+        ConfirmDone::Off
+    } else {
+        config.confirm_done
+    };
+    let store = open_store(&opt.datadir_opt.datadir, config)?;
+
+    let old_entry = match opt.entry_ref {
+        EntryRef::Id(entry_id) => store
+            .get_done_entries_by_ids(&[entry_id], &opt.project_opt.project)?
+            .into_iter()
+            .map(|(_, entry)| entry)
+            .next()
+            .context("can not get done entry")?,
+
+        EntryRef::Uuid(uuid) => store
+            .get_entry_by_uuid(&uuid)
+            .with_context(|| format!("can not get entry with uuid {}", uuid))?,
+    };
+
+    if old_entry.is_active() {
+        bail!("entry {} is not done", old_entry.metadata.uuid)
+    }
+
+    let message = format!(
+        "do you want to mark this entry as active again?:\n{:.*}",
+        helper::PROMPT_SUMMARY_WIDTH,
+        old_entry
+    );
+    if !helper::confirm_done(
+        mode,
+        store.interactivity(),
+        &message,
+        &old_entry.text,
+        old_entry.metadata.uuid,
+    )? {
+        bail!("not marking entry as active then")
+    }
+
+    let uuid = old_entry.metadata.uuid;
+    let project = old_entry.metadata.project.clone();
+    let new_entry = Entry {
+        metadata: old_entry.metadata.changed().reactivate().build()?,
+        ..old_entry
+    };
+
+    store
+        .update_entry(new_entry)
+        .context("can not mark entry as active")?;
+
+    let id = store
+        .get_active_entries(&project)
+        .context("can not get project entries")?
+        .id_of(&uuid);
+
+    match id {
+        Some(id) => println!(
+            "entry {} is active again as entry {} of project {}",
+            uuid, id, project
+        ),
+        None => println!("entry {} is active again in project {}", uuid, project),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use crate::{
+    cmd::{
+        check_due_date,
+        open_store,
+        print_changed_entries,
+    },
+    config::Config,
+    entry::Entry,
+    helper::format_due,
+    locale::Translations,
+    opt::*,
+    time_context::TimeContext,
+};
+use anyhow::{
+    Context,
+    Error,
+};
+
+pub(crate) fn run_due(opt: DueSubCommandOpts, config: Config) -> Result<(), Error> {
+    let today = TimeContext::from_config(&config).today();
+    let due_date = opt.due_date()?.date(today);
+    check_due_date(
+        due_date,
+        today,
+        &config.due_date_limits,
+        opt.yes,
+        config.interactivity,
+    )?;
+
+    let table_style = config.table_style;
+    let interactivity = config.interactivity;
+    let locale = config.locale;
+    let store = open_store(&opt.datadir_opt.datadir, config)?;
+
+    let old_entries = store.get_entries_by_ids(&opt.entry_ids()?, &opt.project_opt.project)?;
+
+    let new_entries = old_entries
+        .into_iter()
+        .map(|(id, old_entry)| {
+            let new_entry = Entry {
+                metadata: old_entry.metadata.changed().due(Some(due_date)).build()?,
+                ..old_entry
+            };
+
+            Ok((id, new_entry))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    store
+        .update_entries(new_entries.iter().map(|(_, entry)| entry.clone()).collect())
+        .context("can not set due date of entries")?;
+
+    let translations = Translations::load(store.datadir(), locale)?;
+    print_changed_entries(
+        &new_entries,
+        "due",
+        table_style,
+        interactivity,
+        &translations,
+        |entry| format_due(entry.metadata.due, today, locale),
+    );
+
+    Ok(())
+}
@@ -0,0 +1,229 @@
+use crate::{
+    cmd::{
+        check_due_date,
+        open_store,
+    },
+    config::Config,
+    diff,
+    entry::{
+        EditableMetadata,
+        Entry,
+    },
+    helper::{
+        format_duration,
+        string_from_editor,
+    },
+    interactivity::Interactivity,
+    opt::*,
+    time_context::TimeContext,
+};
+use anyhow::{
+    bail,
+    Context,
+    Error,
+};
+use chrono::Utc;
+
+pub(crate) fn run_edit(opt: EditSubCommandOpts, config: Config, color: bool) -> Result<(), Error> {
+    if opt.entry_id < 1 {
+        bail!("entry id can not be smaller than 1")
+    }
+
+    let skip_confirmation = opt.yes || config.skip_edit_confirmation;
+    let today = TimeContext::from_config(&config).today();
+    let due_date_limits = config.due_date_limits.clone();
+
+    let store = open_store(&opt.datadir_opt.datadir, config)?;
+
+    let old_entry = store
+        .get_entry_by_id(opt.entry_id, &opt.project_opt.project)
+        .context("can not get entry")?;
+
+    store.record_view(&old_entry.metadata.uuid);
+
+    let new_entry = if opt.metadata {
+        let old_metadata = toml::to_string_pretty(&EditableMetadata::from(&old_entry.metadata))
+            .context("can not serialize metadata")?;
+
+        let prepopulate = format!(
+            "# started = \"2019-12-24T08:00:00Z\"\n# due = \"2019-12-24\"\n{}",
+            old_metadata
+        );
+
+        let edited = string_from_editor(Some(&prepopulate), store.interactivity())
+            .context("can not edit metadata with editor")?;
+
+        let new_metadata: EditableMetadata =
+            toml::from_str(&edited).context("can not parse edited metadata")?;
+
+        if let Some(due) = new_metadata.due {
+            if Some(due) != old_entry.metadata.due {
+                check_due_date(
+                    due,
+                    today,
+                    &due_date_limits,
+                    skip_confirmation,
+                    store.interactivity(),
+                )?;
+            }
+        }
+
+        Entry {
+            metadata: old_entry
+                .metadata
+                .changed()
+                .started(new_metadata.started)
+                .due(new_metadata.due)
+                .build()?,
+            text: old_entry.text,
+        }
+    } else {
+        let new_text = string_from_editor(Some(&old_entry.text), store.interactivity()).context(
+            "can not edit entry with
+editor",
+        )?;
+
+        let new_text = match edited_text(
+            &old_entry.text,
+            new_text,
+            opt.restart,
+            store.interactivity(),
+        )? {
+            Some(new_text) => new_text,
+            None => {
+                println!("entry was not changed, nothing to save");
+                return Ok(());
+            }
+        };
+
+        let diff = diff::unified_diff("before", &old_entry.text, "after", &new_text);
+        if !diff.is_empty() {
+            if color {
+                print!("{}", diff::colorize(&diff));
+            } else {
+                print!("{}", diff);
+            }
+
+            if !skip_confirmation
+                && !store.interactivity().confirm(
+                    "do you want to save these changes?",
+                    false,
+                    "pass --yes to save them",
+                )?
+            {
+                bail!("not saving changes then")
+            }
+        }
+
+        let started = if opt.restart {
+            let message = format!(
+                "this entry was started {} ago, do you want to reset it to now?",
+                format_duration(old_entry.active_duration())
+            );
+
+            if !skip_confirmation
+                && !store
+                    .interactivity()
+                    .confirm(&message, false, "pass --yes to restart it")?
+            {
+                bail!("not restarting entry then")
+            }
+
+            Utc::now()
+        } else {
+            old_entry.metadata.started
+        };
+
+        Entry {
+            text: new_text,
+            metadata: old_entry.metadata.changed().started(started).build()?,
+        }
+    };
+
+    store
+        .update_entry(new_entry)
+        .context("can not update entry")?;
+
+    Ok(())
+}
+
+/// Text to save after the entry was edited. Nothing is saved if the text is
+/// unchanged unless the entry is restarted. Replacing a text with an empty
+/// one is always confirmed, even when the changes are saved without
+/// confirmation otherwise.
+fn edited_text(
+    old_text: &str,
+    new_text: String,
+    restart: bool,
+    interactivity: Interactivity,
+) -> Result<Option<String>, Error> {
+    if new_text == old_text && !restart {
+        return Ok(None);
+    }
+
+    if new_text.trim().is_empty()
+        && !old_text.trim().is_empty()
+        && !interactivity.confirm(
+            "the edited text is empty, do you want to remove the text of the entry?",
+            false,
+            "edit the entry in a terminal to remove its text",
+        )?
+    {
+        bail!("not removing the text then")
+    }
+
+    Ok(Some(new_text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helper::tests::edit_with;
+
+    const OLD_TEXT: &str = "== Entry\n\nnotes\n";
+
+    fn edit(script: &str, restart: bool) -> Result<Option<String>, Error> {
+        let new_text = edit_with(script, Some(OLD_TEXT))?;
+
+        edited_text(OLD_TEXT, new_text, restart, Interactivity::default())
+    }
+
+    #[test]
+    fn changed_text_is_saved() {
+        assert_eq!(
+            edit("printf 'new notes' > \"$1\"", false)
+                .unwrap()
+                .as_deref(),
+            Some("new notes")
+        );
+    }
+
+    #[test]
+    fn unchanged_text_is_not_saved() {
+        assert_eq!(edit("true", false).unwrap(), None);
+        assert_eq!(edit("true", true).unwrap().as_deref(), Some(OLD_TEXT));
+    }
+
+    #[test]
+    fn empty_text_is_never_saved_without_confirmation() {
+        let err = edit(": > \"$1\"", false).unwrap_err();
+
+        assert!(
+            err.to_string().starts_with("can not ask for confirmation"),
+            "{}",
+            err
+        );
+        assert_eq!(
+            edited_text("", String::new(), true, Interactivity::default())
+                .unwrap()
+                .as_deref(),
+            Some("")
+        );
+    }
+
+    #[test]
+    fn crashed_editor_saves_nothing() {
+        assert!(edit("printf 'partial' > \"$1\"\nexit 1", false).is_err());
+        assert!(edit("rm \"$1\"", false).is_err());
+    }
+}
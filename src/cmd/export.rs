@@ -0,0 +1,61 @@
+use crate::{
+    cmd::open_store_for_reading,
+    config::Config,
+    locale::Translations,
+    markdown,
+    opt::*,
+    time_context::TimeContext,
+};
+use anyhow::{
+    Context,
+    Error,
+};
+
+pub(crate) fn run_export(opt: ExportSubCommandOpts, config: Config) -> Result<(), Error> {
+    let time = TimeContext::from_config(&config).frozen();
+    let locale = config.locale;
+    let store = open_store_for_reading(&opt.datadir_opt.datadir, config)?;
+
+    let entries = if opt.all_projects {
+        store
+            .get_all_entries(opt.include_ignored)
+            .context("can not get entries from store")?
+    } else {
+        store
+            .get_entries(&opt.project_opt.project)
+            .context("can not get entries from store")?
+    };
+
+    let entries = if opt.no_done {
+        entries.get_active()
+    } else {
+        entries
+    };
+
+    let rendered = match opt.format {
+        ExportFormat::TaskList(TaskListFormat::Markdown) => markdown::export(&entries.entries),
+        ExportFormat::HtmlBundle => {
+            let title = if opt.all_projects {
+                "all projects"
+            } else {
+                &opt.project_opt.project
+            };
+
+            crate::webservice::render_export_bundle(
+                &store,
+                title,
+                &entries,
+                time.today(),
+                Translations::load(store.datadir(), locale)?,
+            )?
+        }
+    };
+
+    match opt.output {
+        Some(path) => std::fs::write(&path, rendered)
+            .with_context(|| format!("can not write export to {:?}", path))?,
+        None => print!("{}", rendered),
+    }
+
+    Ok(())
+}
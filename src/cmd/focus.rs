@@ -0,0 +1,53 @@
+use crate::{
+    cmd::{
+        get_entry_by_ref,
+        open_store,
+    },
+    config::Config,
+    opt::*,
+};
+use anyhow::Error;
+
+pub(crate) fn run_focus(opt: FocusSubCommandOpts, config: Config) -> Result<(), Error> {
+    let store = open_store(&opt.datadir_opt.datadir, config)?;
+
+    let project = opt.project_opt.project;
+
+    if opt.clear {
+        if store.clear_focus(&project)? {
+            println!("cleared focus of project {}", project);
+        } else {
+            println!("project {} has no focus", project);
+        }
+
+        return Ok(());
+    }
+
+    match opt.entry_ref {
+        Some(entry_ref) => {
+            let entry = get_entry_by_ref(&store, entry_ref, &project)?;
+
+            store.set_focus(&entry)?;
+
+            println!(
+                "focused entry {} in project {}: {:.80}",
+                entry.metadata.uuid, entry.metadata.project, entry
+            );
+        }
+
+        None => match store.get_focus(&project)? {
+            Some(entry) => {
+                store.record_view(&entry.metadata.uuid);
+
+                println!("{}", entry.text);
+            }
+
+            None => {
+                eprintln!("project {} has no focus", project);
+                ::std::process::exit(1);
+            }
+        },
+    }
+
+    Ok(())
+}
@@ -0,0 +1,193 @@
+use crate::{
+    cmd::{
+        open_store,
+        read_path_or_stdin,
+    },
+    config::Config,
+    entry::{
+        Entry,
+        EntrySource,
+    },
+    markdown,
+    opt::*,
+    store::Store,
+    time_context::TimeContext,
+};
+use anyhow::{
+    bail,
+    Context,
+    Error,
+};
+use log::warn;
+use std::collections::{
+    BTreeSet,
+    HashMap,
+};
+
+/// Create the tasks that do not exist yet and finish or reopen the existing
+/// entries whose checkbox changed. Existing entries are matched by the uuid of
+/// the task. Nothing else of existing entries is changed.
+pub(crate) fn run_import(opt: ImportSubCommandOpts, config: Config) -> Result<(), Error> {
+    let format = match opt.format {
+        ImportFormat::TaskList(format) => format,
+        ImportFormat::Datadir => return run_import_datadir(opt, config),
+    };
+
+    let from_path = opt.from_path;
+    let project = opt.project_opt.project;
+    let import_all = opt.import_all;
+
+    let content = read_path_or_stdin(&from_path)?;
+
+    let tasks = match format {
+        TaskListFormat::Markdown => markdown::parse(&content)?,
+    };
+
+    let tasks = tasks
+        .into_iter()
+        .filter(|task| {
+            import_all
+                || task
+                    .project
+                    .as_ref()
+                    .is_none_or(|task_project| *task_project == project)
+        })
+        .collect::<Vec<_>>();
+
+    if tasks.is_empty() {
+        bail!("no tasks found in {:?}", from_path)
+    }
+
+    let today = TimeContext::from_config(&config).today();
+    for due in tasks.iter().filter_map(|task| task.due) {
+        config.due_date_limits.validate(due, today)?;
+    }
+
+    let store = open_store(&opt.datadir_opt.datadir, config)?;
+
+    let existing = store
+        .get_all_entries(true)
+        .context("can not get entries from store")?
+        .into_iter()
+        .map(|entry| (entry.metadata.uuid, entry))
+        .collect::<HashMap<_, _>>();
+
+    let mut seen = BTreeSet::new();
+    let mut new_entries = Vec::new();
+    let mut changed_entries = Vec::new();
+
+    for task in tasks {
+        if let Some(uuid) = task.uuid {
+            if !seen.insert(uuid) {
+                continue;
+            }
+        }
+
+        match task.uuid.and_then(|uuid| existing.get(&uuid)) {
+            Some(entry) => {
+                if entry.is_done() == task.done {
+                    continue;
+                }
+
+                let change = entry.metadata.changed();
+                let change = if task.done {
+                    change.finish()
+                } else {
+                    change.reactivate()
+                };
+
+                changed_entries.push(Entry {
+                    metadata: change.build()?,
+                    text: entry.text.clone(),
+                });
+            }
+
+            None => {
+                if let Some(uuid) = task.uuid {
+                    warn!("entry {} does not exist, creating a new entry", uuid);
+                }
+
+                let task_project = task.project.unwrap_or_else(|| project.clone());
+
+                let mut entry = Entry::builder(task.text, task_project, EntrySource::Import)
+                    .due(task.due)
+                    .build()?;
+
+                if task.done {
+                    entry.metadata = entry.metadata.changed().finish().build()?;
+                }
+
+                new_entries.push(entry);
+            }
+        }
+    }
+
+    if !new_entries.is_empty() {
+        store
+            .add_entries(&new_entries)
+            .context("can not add entries to store")?;
+    }
+
+    let finished = changed_entries
+        .iter()
+        .filter(|entry| entry.is_done())
+        .count();
+    let reopened = changed_entries.len() - finished;
+
+    if !changed_entries.is_empty() {
+        store
+            .update_entries(changed_entries)
+            .context("can not update entries")?;
+    }
+
+    println!(
+        "created {} entries, finished {} entries and reopened {} entries",
+        new_entries.len(),
+        finished,
+        reopened
+    );
+
+    Ok(())
+}
+
+/// Merge the entries of another datadir into the store. Only the entries of
+/// the current project are merged unless --import_all is given.
+pub(crate) fn run_import_datadir(opt: ImportSubCommandOpts, config: Config) -> Result<(), Error> {
+    let from_path = opt.from_path;
+    let project = opt.project_opt.project;
+    let import_all = opt.import_all;
+
+    if !Store::exists(&from_path) {
+        bail!("{:?} is not a todust datadir", from_path)
+    }
+
+    let source = Store::open(&from_path, config.clone())
+        .with_context(|| format!("can not open datadir {:?}", from_path))?;
+    let store = open_store(&opt.datadir_opt.datadir, config)?;
+
+    if source.datadir().canonicalize()? == store.datadir().canonicalize()? {
+        bail!("can not import the datadir into itself")
+    }
+
+    let entries = source
+        .get_all_entries(true)
+        .with_context(|| format!("can not get entries from {:?}", from_path))?
+        .into_iter()
+        .filter(|entry| import_all || entry.metadata.project == project)
+        .collect::<Vec<_>>();
+
+    if entries.is_empty() {
+        bail!("no entries found in {:?}", from_path)
+    }
+
+    let counts = store
+        .merge_entries(entries)
+        .context("can not add entries to store")?;
+
+    println!(
+        "imported {} entries, merged {} entries and skipped {} entries",
+        counts.imported, counts.merged, counts.skipped
+    );
+
+    Ok(())
+}
@@ -0,0 +1,66 @@
+use crate::{
+    cmd::open_store,
+    config::Config,
+    opt::*,
+    table,
+};
+use anyhow::{
+    Context,
+    Error,
+};
+
+pub(crate) fn run_info(opt: InfoSubCommandOpts, config: Config) -> Result<(), Error> {
+    let table_style = config.table_style;
+    let interactivity = config.interactivity;
+    let store = open_store(&opt.datadir_opt.datadir, config)?;
+
+    let info = store.info().context("can not get store info")?;
+
+    match opt.format {
+        InfoFormat::Json => println!("{}", serde_json::to_string_pretty(&info)?),
+        InfoFormat::Table => {
+            let or_dash = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+
+            let mut table = table::new_table(
+                &["Name", "Value"],
+                Some(1),
+                false,
+                table_style,
+                interactivity,
+            );
+            table.add_row(vec!["Version", info.version]);
+            table.add_row(vec![
+                "Store Version".to_string(),
+                info.store_version.to_string(),
+            ]);
+            table.add_row(vec!["Identifier".to_string(), info.identifier]);
+            table.add_row(vec![
+                "Datadir".to_string(),
+                or_dash(info.datadir.map(|path| path.display().to_string())),
+            ]);
+            table.add_row(vec!["VCS".to_string(), or_dash(info.vcs)]);
+            table.add_row(vec!["Head".to_string(), or_dash(info.head)]);
+            table.add_row(vec!["Entries".to_string(), info.entry_count.to_string()]);
+            table.add_row(vec!["Projects".to_string(), info.project_count.to_string()]);
+            table.add_row(vec![
+                "Index Fragments".to_string(),
+                info.index_fragment_count.to_string(),
+            ]);
+            for usage in info.quotas {
+                let count = match usage.project {
+                    Some(project) => format!("{} ({})", usage.count, project),
+                    None => usage.count.to_string(),
+                };
+
+                table.add_row(vec![
+                    format!("Quota {}", usage.quota),
+                    format!("{} / {}", count, usage.limit),
+                ]);
+            }
+
+            println!("{}", table);
+        }
+    }
+
+    Ok(())
+}
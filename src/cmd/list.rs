@@ -0,0 +1,462 @@
+use crate::{
+    cmd::open_store_for_reading,
+    config::Config,
+    entry::{
+        Entry,
+        EntrySource,
+    },
+    helper::{
+        format_due,
+        format_duration,
+        format_timestamp,
+    },
+    locale::Translations,
+    opt::*,
+    output,
+    porcelain,
+    stats::FORGOTTEN_DAYS,
+    store::Store,
+    table,
+    tags,
+    time_context::TimeContext,
+    watch,
+};
+use anyhow::{
+    bail,
+    Context,
+    Error,
+};
+use chrono::{
+    Duration,
+    NaiveDate,
+    Utc,
+};
+use comfy_table::{
+    Cell,
+    Color,
+};
+use std::collections::{
+    HashMap,
+    HashSet,
+};
+
+/// Minimum width of the description column in the list table so very narrow
+/// terminals still show something useful.
+const MIN_DESCRIPTION_WIDTH: usize = 20;
+
+pub(crate) fn run_list(opt: ListSubCommandOpts, config: Config) -> Result<(), Error> {
+    let pager = output::pager_command(
+        config.pager.as_deref(),
+        opt.pager_opt.no_pager,
+        config.interactivity,
+    );
+    let store = open_store_for_reading(&opt.datadir_opt.datadir, config.clone())?;
+
+    if opt.watch {
+        if !config.interactivity.is_styled() {
+            bail!("--watch needs a terminal to redraw the list in")
+        }
+
+        return watch::watch(
+            std::time::Duration::from_secs(config.watch_interval_seconds.max(1)),
+            || store.fingerprint(),
+            || render_list(&opt, &store, &config),
+        );
+    }
+
+    output::print_paged(&render_list(&opt, &store, &config)?, pager.as_deref())
+}
+
+/// Render the entries for `list` the way they are printed.
+pub(crate) fn render_list(
+    opt: &ListSubCommandOpts,
+    store: &Store,
+    config: &Config,
+) -> Result<String, Error> {
+    let time = TimeContext::from_config(config);
+    let today = time.today();
+    let table_style = config.table_style;
+    let interactivity = config.interactivity;
+    let locale = config.locale;
+    let translations = Translations::load(store.datadir(), locale)?;
+
+    let projects = opt.projects_opt.projects();
+    let show_project = projects.len() > 1;
+    let state = opt.state();
+
+    let entries = store
+        .get_entry_previews_of_projects(&projects)
+        .context("can not get entries from store")?;
+
+    // The ids are assigned per project before filtering so they stay the same
+    // as in the unfiltered list of each project and can be used with done,
+    // edit and move. Done entries have their own ids for reopen.
+    let mut entries_with_ids = Vec::new();
+    if state != ListState::Done {
+        entries_with_ids.extend(number_per_project(entries.clone().get_active()));
+    }
+    if state != ListState::Active {
+        entries_with_ids.extend(number_per_project(entries.get_done_by_finished()));
+    }
+
+    if entries_with_ids.is_empty() {
+        // A store that was just created is explained by `open_store`.
+        if opt.porcelain || store.new_store().is_some() {
+            return Ok(String::new());
+        }
+
+        let message = match state {
+            ListState::Active => "no active todos",
+            ListState::Done => "no done todos",
+            ListState::All => "no todos",
+        };
+
+        return Ok(format!("{}\n", message));
+    }
+
+    let last_viewed = store.last_viewed();
+
+    let focused = projects
+        .iter()
+        .filter_map(|project| store.focused_uuid(project))
+        .collect::<HashSet<_>>();
+
+    let forgotten_days = opt
+        .forgotten
+        .map_or(FORGOTTEN_DAYS, |days| days.unwrap_or(FORGOTTEN_DAYS));
+    let forgotten_since = Utc::now() - Duration::days(forgotten_days);
+
+    // Done entries show when they were finished instead of when they are due.
+    let date_column = if state == ListState::Done {
+        "finished"
+    } else {
+        "due"
+    };
+
+    let mut header = vec!["id"];
+    if show_project {
+        header.push("project");
+    }
+    if state == ListState::All {
+        header.push("state");
+    }
+    header.extend(&[
+        "age",
+        date_column,
+        "stale",
+        "focus",
+        "lines",
+        "tags",
+        "description",
+    ]);
+    let header = header
+        .into_iter()
+        .map(|key| translations.get(key))
+        .collect::<Vec<_>>();
+
+    let mut table = table::new_table(
+        &header,
+        Some(header.len() - 1),
+        opt.table_opt.wide,
+        table_style,
+        interactivity,
+    );
+
+    let mut rows = entries_with_ids
+        .into_iter()
+        .map(|(id, entry)| {
+            let forgotten = entry.is_active()
+                && entry
+                    .metadata
+                    .last_seen(last_viewed.get(&entry.metadata.uuid).copied())
+                    < forgotten_since;
+
+            // Only entries with more text than the summary get a line count.
+            let lines = entry
+                .metadata
+                .line_count
+                .filter(|line_count| *line_count > 1)
+                .map(|line_count| line_count.to_string())
+                .unwrap_or_default();
+
+            let date = match (state, entry.metadata.finished) {
+                (ListState::Done, Some(finished)) if opt.table_opt.raw_dates => {
+                    format_timestamp(Some(time.date(finished)), locale)
+                }
+                (ListState::Done, Some(finished)) => {
+                    format!("{} ago", format_duration(Utc::now() - finished))
+                }
+                _ if opt.table_opt.raw_dates => format_timestamp(entry.metadata.due, locale),
+                _ => format_due(entry.metadata.due, today, locale),
+            };
+
+            (
+                id.to_string(),
+                format_duration(entry.age()),
+                date,
+                if forgotten { "!" } else { "" }.to_string(),
+                if focused.contains(&entry.metadata.uuid) {
+                    "*"
+                } else {
+                    ""
+                }
+                .to_string(),
+                lines,
+                forgotten,
+                entry,
+            )
+        })
+        .filter(|row| opt.forgotten.is_none() || row.6)
+        .filter(|row| tags::has_all(&row.7.metadata.tags, &opt.tags))
+        .filter(|row| !opt.due_only || row.7.metadata.due.is_some())
+        .filter(|row| {
+            opt.source
+                .as_ref()
+                .is_none_or(|source| EntrySource::matches(row.7.metadata.source.as_deref(), source))
+        })
+        .collect::<Vec<_>>();
+
+    // Entries are listed grouped by project in the order the projects were
+    // given. The sort is stable so entries keep their order in a project.
+    rows.sort_by_key(|row| {
+        projects
+            .iter()
+            .position(|project| project == &row.7.metadata.project)
+    });
+
+    if opt.due_only {
+        rows.sort_by_key(|row| row.7.due_order());
+    }
+
+    if opt.porcelain {
+        return Ok(rows
+            .iter()
+            .map(|row| format!("{}\n", porcelain::entry_record(&row.7)))
+            .collect());
+    }
+
+    if rows.is_empty() {
+        return Ok("no matching todos\n".to_string());
+    }
+
+    let state_active = translations.get("state_active").to_string();
+    let state_done = translations.get("state_done").to_string();
+
+    let row_tags = rows
+        .iter()
+        .map(|row| tags::join(&row.7.metadata.tags))
+        .collect::<Vec<_>>();
+
+    let description_width =
+        table::terminal_width(opt.table_opt.wide, interactivity).map(|terminal_width| {
+            let mut column_widths = vec![
+                table::column_width(translations.get("id"), rows.iter().map(|row| &row.0)),
+                table::column_width(translations.get("age"), rows.iter().map(|row| &row.1)),
+                table::column_width(translations.get(date_column), rows.iter().map(|row| &row.2)),
+                table::column_width(translations.get("stale"), rows.iter().map(|row| &row.3)),
+                table::column_width(translations.get("focus"), rows.iter().map(|row| &row.4)),
+                table::column_width(translations.get("lines"), rows.iter().map(|row| &row.5)),
+                table::column_width(translations.get("tags"), row_tags.iter()),
+            ];
+
+            if show_project {
+                column_widths.push(table::column_width(
+                    translations.get("project"),
+                    rows.iter().map(|row| &row.7.metadata.project),
+                ));
+            }
+
+            if state == ListState::All {
+                column_widths.push(table::column_width(
+                    translations.get("state"),
+                    [&state_active, &state_done].iter().copied(),
+                ));
+            }
+
+            table::remaining_width(terminal_width, &column_widths).max(MIN_DESCRIPTION_WIDTH)
+        });
+
+    for ((id, age, date, stale, focus, lines, _, entry), entry_tags) in
+        rows.into_iter().zip(row_tags)
+    {
+        let description = match description_width {
+            Some(width) => format!("{:.*}", width, entry),
+            None => format!("{}", entry),
+        };
+
+        let mut date = Cell::new(date);
+        if let Some(color) = due_color(&entry, today) {
+            date = date.fg(color);
+        }
+
+        let mut row = vec![Cell::new(id)];
+        if show_project {
+            row.push(Cell::new(&entry.metadata.project));
+        }
+        if state == ListState::All {
+            row.push(Cell::new(if entry.is_done() {
+                &state_done
+            } else {
+                &state_active
+            }));
+        }
+        row.extend(vec![
+            Cell::new(age),
+            date,
+            Cell::new(stale),
+            Cell::new(focus),
+            Cell::new(lines),
+            Cell::new(entry_tags),
+            Cell::new(description),
+        ]);
+
+        table.add_row(row);
+    }
+
+    Ok(format!("{}\n", table))
+}
+
+/// Active entries that are overdue are shown in red and the ones due today in
+/// yellow.
+pub(crate) fn due_color(entry: &Entry, today: NaiveDate) -> Option<Color> {
+    if entry.is_done() {
+        return None;
+    }
+
+    if entry.metadata.days_overdue(today).is_some() {
+        Some(Color::Red)
+    } else if entry.metadata.due == Some(today) {
+        Some(Color::Yellow)
+    } else {
+        None
+    }
+}
+
+/// Number the entries per project in the order they are given, starting at 1.
+pub(crate) fn number_per_project<I>(entries: I) -> Vec<(usize, Entry)>
+where
+    I: IntoIterator<Item = Entry>,
+{
+    let mut next_ids: HashMap<String, usize> = HashMap::new();
+
+    entries
+        .into_iter()
+        .map(|entry| {
+            let id = next_ids.entry(entry.metadata.project.clone()).or_default();
+            *id += 1;
+
+            (*id, entry)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        store::tests::{
+            entry,
+            open_with,
+            temporary,
+        },
+        watch::ChangeDetector,
+    };
+    use structopt::StructOpt;
+
+    fn list_opts(args: &[&str]) -> ListSubCommandOpts {
+        let args = ["todust", "list"].iter().chain(args);
+
+        match Opt::from_iter_safe(args).unwrap().cmd {
+            Some(SubCommand::List(opt)) => opt,
+            cmd => panic!("parsed wrong subcommand: {:?}", cmd),
+        }
+    }
+
+    #[test]
+    fn new_store_is_not_listed_as_empty() {
+        let directory = tempfile::tempdir().unwrap();
+        let datadir = directory.path().join("datadir");
+        let state_home = directory.path().join("state");
+        std::fs::create_dir_all(&datadir).unwrap();
+        let config = Config::default();
+        let opt = list_opts(&[]);
+
+        let store = open_with(&datadir, &state_home, config.clone());
+        assert_eq!(render_list(&opt, &store, &config).unwrap(), "");
+
+        let store = open_with(&datadir, &state_home, config.clone());
+        assert_eq!(
+            render_list(&opt, &store, &config).unwrap(),
+            "no active todos\n"
+        );
+        assert_eq!(
+            render_list(&list_opts(&["--done"]), &store, &config).unwrap(),
+            "no done todos\n"
+        );
+
+        store.add_entry(entry("first entry")).unwrap();
+        assert!(render_list(&opt, &store, &config)
+            .unwrap()
+            .contains("first entry"));
+    }
+
+    #[test]
+    fn watch_renders_again_when_the_store_changes() {
+        let (_directory, store) = temporary();
+        let config = Config::default();
+        let opt = list_opts(&[]);
+        store.add_entry(entry("first entry")).unwrap();
+
+        let mut detector = ChangeDetector::default();
+        assert!(detector.changed(store.fingerprint().unwrap()));
+        assert!(!detector.changed(store.fingerprint().unwrap()));
+
+        // Reading the store does not count as a change.
+        render_list(&opt, &store, &config).unwrap();
+        assert!(!detector.changed(store.fingerprint().unwrap()));
+
+        store.add_entry(entry("second entry")).unwrap();
+        assert!(detector.changed(store.fingerprint().unwrap()));
+        assert!(render_list(&opt, &store, &config)
+            .unwrap()
+            .contains("second entry"));
+        assert!(!detector.changed(store.fingerprint().unwrap()));
+    }
+
+    #[test]
+    fn done_ids_are_the_ones_undone_accepts() {
+        let (_directory, store) = temporary();
+        let config = Config::default();
+        let finished = Utc::now() - Duration::days(3);
+
+        for (text, days) in &[("finished first", 0), ("finished last", 2)] {
+            let mut done = entry(text);
+            done.metadata.finished = Some(finished + Duration::days(*days));
+            store.add_entry(done).unwrap();
+        }
+        store.add_entry(entry("still active")).unwrap();
+
+        let listed = render_list(&list_opts(&["--done"]), &store, &config).unwrap();
+        assert!(!listed.contains("still active"));
+
+        for text in &["finished first", "finished last"] {
+            let id = listed
+                .lines()
+                .find(|line| line.contains(text))
+                .and_then(|line| {
+                    line.split(|c: char| !c.is_ascii_digit())
+                        .find(|part| !part.is_empty())
+                })
+                .unwrap()
+                .parse::<usize>()
+                .unwrap();
+
+            let (resolved_id, resolved) = store
+                .get_done_entries_by_ids(&[id], "default")
+                .unwrap()
+                .remove(0);
+            assert_eq!(resolved_id, id);
+            assert_eq!(&resolved.text, text);
+        }
+    }
+}
@@ -0,0 +1,344 @@
+pub(crate) mod add;
+pub(crate) mod audit;
+pub(crate) mod cleanup;
+pub(crate) mod completion;
+pub(crate) mod conflicts;
+pub(crate) mod delete;
+pub(crate) mod digest;
+pub(crate) mod done;
+pub(crate) mod due;
+pub(crate) mod edit;
+pub(crate) mod export;
+pub(crate) mod focus;
+pub(crate) mod import;
+pub(crate) mod info;
+pub(crate) mod list;
+pub(crate) mod move_entry;
+pub(crate) mod print;
+pub(crate) mod project;
+pub(crate) mod projects;
+pub(crate) mod recent;
+pub(crate) mod stats;
+pub(crate) mod sync;
+pub(crate) mod templates;
+pub(crate) mod tidy;
+pub(crate) mod triage;
+pub(crate) mod web;
+pub(crate) mod worklog;
+
+use crate::{
+    config::Config,
+    due_date,
+    due_date::DueDateLimits,
+    entry::Entry,
+    helper,
+    interactivity::Interactivity,
+    locale::Translations,
+    opt::*,
+    store::{
+        NewStore,
+        Store,
+    },
+    table,
+    table::TableStyle,
+};
+use anyhow::{
+    bail,
+    Context,
+    Error,
+};
+use chrono::{
+    Datelike,
+    NaiveDate,
+};
+use std::{
+    io::{
+        self,
+        Read,
+    },
+    path::Path,
+};
+
+/// Open the store in the datadir. Explains how to get started if the store
+/// was just created. The explanation goes to stderr so it does not mix with
+/// output that is meant for other programs.
+pub(super) fn open_store(datadir: &Path, config: Config) -> Result<Store, Error> {
+    let store = Store::open(datadir, config)?;
+    store.record_stats_snapshot();
+
+    let new_store = match store.new_store() {
+        Some(new_store) if store.interactivity().shows_notices() => new_store,
+        _ => return Ok(store),
+    };
+
+    eprint!("{}", new_store_message(store.datadir(), new_store));
+
+    Ok(store)
+}
+
+/// Explanation of how to get started with a store that was just created.
+fn new_store_message(datadir: &Path, new_store: NewStore) -> String {
+    let mut message = format!("created a new todust store in {}\n", datadir.display());
+
+    match new_store {
+        NewStore::RepositoryInitialized => {
+            message.push_str("initialized a git repository in it, every change is committed\n")
+        }
+        NewStore::ExistingRepository => {
+            message.push_str("it is a git repository already, every change is committed\n")
+        }
+        NewStore::RepositoryFailed => message.push_str(
+            "no git repository could be initialized in it, run git init in the datadir or disable \
+             autocommit in the config\n",
+        ),
+        NewStore::NotCommitted => {}
+    }
+
+    message.push_str("add the first entry with: todust add \"text of the entry\"\n");
+    message.push_str(
+        "entries go to the project \"default\", pick another one with --project or set \
+         TODUST_PROJECT\n",
+    );
+    message.push('\n');
+
+    message
+}
+
+/// Open the store for a command that only reads entries and warn if the
+/// datadir looks stale.
+pub(super) fn open_store_for_reading(datadir: &Path, config: Config) -> Result<Store, Error> {
+    let store = open_store(datadir, config)?;
+    store.warn_if_stale();
+
+    Ok(store)
+}
+
+/// Ask before entries end up in a project that does not exist yet as that is
+/// usually a typo and the entries would be missing from the usual views. The
+/// closest existing project is suggested. Nothing is asked with `yes` or when
+/// the store has no projects at all.
+pub(super) fn check_new_project(store: &Store, project: &str, yes: bool) -> Result<(), Error> {
+    if yes {
+        return Ok(());
+    }
+
+    let projects = store.get_projects()?;
+    if projects.is_empty() || projects.iter().any(|existing| existing == project) {
+        return Ok(());
+    }
+
+    let mut message = format!("project '{}' does not exist", project);
+    if let Some(suggestion) = helper::closest_match(project, &projects) {
+        message.push_str(&format!(" (did you mean '{}'?)", suggestion));
+    }
+    message.push_str(" - create it?");
+
+    let hint = format!("pass --yes to create the project {}", project);
+    if !store.interactivity().confirm(&message, false, &hint)? {
+        bail!("not creating project {} then", project)
+    }
+
+    Ok(())
+}
+
+/// Read the whole file or stdin if the path is `-`. Windows line endings are
+/// replaced.
+pub(super) fn read_path_or_stdin(path: &Path) -> Result<String, Error> {
+    let content = if path == Path::new("-") {
+        let mut content = String::new();
+        io::stdin()
+            .read_to_string(&mut content)
+            .context("can not read entries from stdin")?;
+
+        content
+    } else {
+        std::fs::read_to_string(path)
+            .with_context(|| format!("can not read entries from {:?}", path))?
+    };
+
+    Ok(content.replace("\r\n", "\n"))
+}
+
+/// Get the entry by its id in the project or by its uuid.
+pub(super) fn get_entry_by_ref(
+    store: &Store,
+    entry_ref: EntryRef,
+    project: &str,
+) -> Result<Entry, Error> {
+    match entry_ref {
+        EntryRef::Id(entry_id) => store
+            .get_entry_by_id(entry_id, project)
+            .context("can not get entry"),
+
+        EntryRef::Uuid(uuid) => store
+            .get_entry_by_uuid(&uuid)
+            .with_context(|| format!("can not get entry with uuid {}", uuid)),
+    }
+}
+
+/// Print a table with the old id, the changed value and the description of
+/// each changed entry. `column` is the translation key of the header of the
+/// changed value.
+pub(super) fn print_changed_entries<F>(
+    entries: &[(usize, Entry)],
+    column: &str,
+    table_style: TableStyle,
+    interactivity: Interactivity,
+    translations: &Translations,
+    value: F,
+) where
+    F: Fn(&Entry) -> String,
+{
+    let mut table = table::new_table(
+        &[
+            translations.get("id"),
+            translations.get(column),
+            translations.get("description"),
+        ],
+        Some(2),
+        false,
+        table_style,
+        interactivity,
+    );
+
+    for (id, entry) in entries {
+        table.add_row(vec![id.to_string(), value(entry), format!("{:.50}", entry)]);
+    }
+
+    println!("{}", table);
+}
+
+/// Check a due date entered by the user. Dates outside of the configured
+/// limits are rejected and dates more than a year in the future have to be
+/// confirmed unless `yes` is set.
+pub(super) fn check_due_date(
+    due: NaiveDate,
+    today: NaiveDate,
+    limits: &DueDateLimits,
+    yes: bool,
+    interactivity: Interactivity,
+) -> Result<(), Error> {
+    limits.validate(due, today)?;
+
+    if !yes
+        && due_date::is_far_future(due, today)
+        && !interactivity.confirm(
+            &format!("due in {} - really?", due.year()),
+            false,
+            "pass --yes to set it",
+        )?
+    {
+        bail!("not setting due date then")
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store;
+
+    #[test]
+    fn new_store_message_explains_the_first_steps() {
+        let datadir = Path::new("/home/user/.local/share/todust");
+
+        assert_eq!(
+            new_store_message(datadir, NewStore::NotCommitted),
+            "created a new todust store in /home/user/.local/share/todust\nadd the first entry \
+             with: todust add \"text of the entry\"\nentries go to the project \"default\", pick \
+             another one with --project or set TODUST_PROJECT\n\n"
+        );
+    }
+
+    #[test]
+    fn new_store_message_names_the_repository() {
+        let datadir = Path::new("/tmp/todust");
+        let second_line = |new_store| {
+            new_store_message(datadir, new_store)
+                .lines()
+                .nth(1)
+                .unwrap()
+                .to_string()
+        };
+
+        assert_eq!(
+            second_line(NewStore::RepositoryInitialized),
+            "initialized a git repository in it, every change is committed"
+        );
+        assert_eq!(
+            second_line(NewStore::ExistingRepository),
+            "it is a git repository already, every change is committed"
+        );
+        assert!(second_line(NewStore::RepositoryFailed).starts_with("no git repository"));
+    }
+
+    #[test]
+    fn existing_projects_are_not_confirmed() {
+        let (_directory, store) = store::tests::temporary();
+        assert!(check_new_project(&store, "wrok", false).is_ok());
+
+        store.add_entry(store::tests::entry("first entry")).unwrap();
+        assert!(check_new_project(&store, "default", false).is_ok());
+    }
+
+    #[test]
+    fn new_projects_are_confirmed_unless_yes() {
+        let (_directory, store) = store::tests::temporary();
+        let mut work = store::tests::entry("first entry");
+        work.metadata.project = "work".to_string();
+        store.add_entry(work).unwrap();
+
+        let err = check_new_project(&store, "wrok", false).unwrap_err();
+        assert!(
+            err.to_string()
+                .ends_with("pass --yes to create the project wrok"),
+            "{}",
+            err
+        );
+
+        assert!(check_new_project(&store, "wrok", true).is_ok());
+    }
+
+    #[test]
+    fn far_future_due_dates_need_a_confirmation() {
+        let today = NaiveDate::from_ymd_opt(2026, 10, 16).unwrap();
+        let due = NaiveDate::from_ymd_opt(2031, 1, 1).unwrap();
+        let limits = DueDateLimits::default();
+
+        let err = check_due_date(due, today, &limits, false, Interactivity::default()).unwrap_err();
+        assert!(err.to_string().ends_with("pass --yes to set it"), "{}", err);
+
+        assert!(check_due_date(due, today, &limits, true, Interactivity::default()).is_ok());
+    }
+
+    #[test]
+    fn near_due_dates_are_not_confirmed() {
+        let today = NaiveDate::from_ymd_opt(2026, 10, 16).unwrap();
+        let due = NaiveDate::from_ymd_opt(2027, 3, 1).unwrap();
+
+        assert!(check_due_date(
+            due,
+            today,
+            &DueDateLimits::default(),
+            false,
+            Interactivity::default()
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn yes_does_not_bypass_the_limits() {
+        let today = NaiveDate::from_ymd_opt(2026, 10, 16).unwrap();
+        let due = NaiveDate::from_ymd_opt(20025, 1, 1).unwrap();
+
+        assert!(check_due_date(
+            due,
+            today,
+            &DueDateLimits::default(),
+            true,
+            Interactivity::default()
+        )
+        .is_err());
+    }
+}
@@ -0,0 +1,149 @@
+use crate::{
+    cmd::{
+        check_new_project,
+        open_store,
+        print_changed_entries,
+    },
+    config::Config,
+    entry::Entry,
+    locale::Translations,
+    opt::*,
+};
+use anyhow::{
+    bail,
+    Context,
+    Error,
+};
+use log::warn;
+
+pub(crate) fn run_move(opt: MoveSubCommandOpts, config: Config) -> Result<(), Error> {
+    if opt.to_store.is_some() {
+        return run_move_to_store(opt, config);
+    }
+
+    let table_style = config.table_style;
+    let interactivity = config.interactivity;
+    let locale = config.locale;
+    let yes = opt.yes || config.auto_create_projects;
+    let store = open_store(&opt.datadir_opt.datadir, config)?;
+
+    let old_entries = store.get_entries_by_ids(&opt.entry_ids()?, &opt.project_opt.project)?;
+    check_new_project(&store, opt.target_project(), yes)?;
+
+    let new_entries = old_entries
+        .into_iter()
+        .map(|(id, old_entry)| {
+            let new_entry = Entry {
+                metadata: old_entry
+                    .metadata
+                    .changed()
+                    .project(opt.target_project().to_string())
+                    .build()?,
+                ..old_entry
+            };
+
+            Ok((id, new_entry))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    store
+        .update_entries(new_entries.iter().map(|(_, entry)| entry.clone()).collect())
+        .context("can not move entries")?;
+
+    let translations = Translations::load(store.datadir(), locale)?;
+    print_changed_entries(
+        &new_entries,
+        "project",
+        table_style,
+        interactivity,
+        &translations,
+        |entry| entry.metadata.project.clone(),
+    );
+
+    Ok(())
+}
+
+/// Move the entries to a project in another store. The entries are added to
+/// the other store first and then removed from this one. When the removal
+/// fails the entries are in both stores and running the move again only
+/// removes them here as entries that are already in the other store are not
+/// added again.
+pub(crate) fn run_move_to_store(opt: MoveSubCommandOpts, config: Config) -> Result<(), Error> {
+    let table_style = config.table_style;
+    let interactivity = config.interactivity;
+    let locale = config.locale;
+    let yes = opt.yes || config.auto_create_projects;
+
+    let name = opt.to_store.clone().unwrap_or_default();
+    let target_datadir = match config.stores.get(&name) {
+        Some(datadir) => datadir.clone(),
+        None if config.stores.is_empty() => {
+            bail!(
+                "unknown store {}, add it to the stores table of the config",
+                name
+            )
+        }
+        None => bail!(
+            "unknown store {}, known stores are {}",
+            name,
+            config.stores.keys().cloned().collect::<Vec<_>>().join(", ")
+        ),
+    };
+
+    let store = open_store(&opt.datadir_opt.datadir, config.clone())?;
+    let target = open_store(&target_datadir, config)?;
+
+    if target.datadir().canonicalize()? == store.datadir().canonicalize()? {
+        bail!("store {} is the datadir the entries are in already", name)
+    }
+
+    let old_entries = store.get_entries_by_ids(&opt.entry_ids()?, &opt.project_opt.project)?;
+    check_new_project(&target, opt.target_project(), yes)?;
+
+    let mut new_entries = Vec::new();
+    for (id, old_entry) in old_entries {
+        let new_entry = Entry {
+            metadata: old_entry
+                .metadata
+                .changed()
+                .project(opt.target_project().to_string())
+                .build()?,
+            ..old_entry.clone()
+        };
+
+        if target.has_entry(&new_entry.metadata.uuid)? {
+            warn!(
+                "entry {} is in store {} already, only removing it from this store",
+                new_entry.metadata.uuid, name
+            );
+        } else {
+            target
+                .add_entry(new_entry.clone())
+                .with_context(|| format!("can not add entry to store {}", name))?;
+        }
+
+        store
+            .remove_moved_entry(&old_entry, &name)
+            .with_context(|| {
+                format!(
+                    "entry {} was added to store {} but can not be removed from this store, run \
+                     the move again to finish it",
+                    old_entry.metadata.uuid, name
+                )
+            })?;
+
+        new_entries.push((id, new_entry));
+    }
+
+    let translations = Translations::load(store.datadir(), locale)?;
+    print_changed_entries(
+        &new_entries,
+        "project",
+        table_style,
+        interactivity,
+        &translations,
+        |entry| format!("{}: {}", name, entry.metadata.project),
+    );
+
+    Ok(())
+}
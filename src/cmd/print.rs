@@ -0,0 +1,109 @@
+use crate::{
+    cmd::{
+        get_entry_by_ref,
+        open_store_for_reading,
+    },
+    config::Config,
+    entry::Entries,
+    opt::*,
+    output,
+    tags,
+    time_context::TimeContext,
+};
+use anyhow::{
+    bail,
+    Context,
+    Error,
+};
+
+pub(crate) fn run_print(opt: PrintSubCommandOpts, config: Config) -> Result<(), Error> {
+    let time = TimeContext::from_config(&config);
+    let pager = output::pager_command(
+        config.pager.as_deref(),
+        opt.pager_opt.no_pager,
+        config.interactivity,
+    );
+    let store = open_store_for_reading(&opt.datadir_opt.datadir, config)?;
+
+    let projects = opt.projects_opt.projects();
+
+    // Name of the printed document so the html of the last print of the same
+    // selection can be reused.
+    let (entries, document) = match opt.entry_ref {
+        Some(entry_ref) => {
+            if matches!(entry_ref, EntryRef::Id(_)) && projects.len() > 1 {
+                bail!("an entry id can only be used with a single project, use the uuid instead")
+            }
+
+            let entry = get_entry_by_ref(&store, entry_ref, &projects[0])?;
+
+            store.record_view(&entry.metadata.uuid);
+
+            let document = format!("print entry {}", entry.metadata.uuid);
+
+            (Entries::from(entry), document)
+        }
+
+        None => {
+            let (entries, document) = if opt.all_projects {
+                (
+                    store
+                        .get_all_entries(opt.include_ignored)
+                        .context("can not get entries from store")?,
+                    format!("print all {}", opt.include_ignored),
+                )
+            } else {
+                (
+                    store
+                        .get_entries_of_projects(&projects)
+                        .context("can not get entries from store")?,
+                    format!("print projects {}", projects.join("\n")),
+                )
+            };
+
+            let entries = if opt.no_done {
+                entries.get_active()
+            } else {
+                entries
+            };
+
+            let entries = if opt.include_archived {
+                let archived = if opt.all_projects {
+                    store.get_all_archived_entries(opt.include_ignored)
+                } else {
+                    store.get_archived_entries_of_projects(&projects)
+                }
+                .context("can not get archived entries from store")?;
+
+                entries.into_iter().chain(archived).collect()
+            } else {
+                entries
+            };
+
+            let entries = entries
+                .into_iter()
+                .filter(|entry| tags::has_all(&entry.metadata.tags, &opt.tags))
+                .collect::<Entries>();
+
+            (
+                entries,
+                format!(
+                    "{} {} {} {}",
+                    document,
+                    opt.no_done,
+                    opt.include_archived,
+                    opt.tags.join(",")
+                ),
+            )
+        }
+    };
+
+    let asciidoc = entries.to_asciidoc(&time)?;
+
+    let output = match opt.format {
+        PrintFormat::Asciidoc => asciidoc,
+        PrintFormat::Html => store.document_html(&document, &asciidoc, !opt.no_cache)?,
+    };
+
+    output::print_paged(&format!("{}\n", output), pager.as_deref())
+}
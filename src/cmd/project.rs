@@ -0,0 +1,33 @@
+use crate::{
+    cmd::open_store,
+    config::Config,
+    opt::*,
+    project_name,
+};
+use anyhow::Error;
+
+pub(crate) fn run_project(opt: ProjectSubCommandOpts, config: Config) -> Result<(), Error> {
+    match opt.cmd {
+        ProjectCommand::Favorite(opt) => {
+            project_name::validate_project_name(&opt.project)?;
+
+            let store = open_store(&opt.datadir_opt.datadir, config)?;
+            if store.favorite_project(&opt.project)? {
+                println!("project {} is a favorite now", opt.project);
+            } else {
+                println!("project {} already is a favorite", opt.project);
+            }
+        }
+
+        ProjectCommand::Unfavorite(opt) => {
+            let store = open_store(&opt.datadir_opt.datadir, config)?;
+            if store.unfavorite_project(&opt.project)? {
+                println!("project {} is not a favorite anymore", opt.project);
+            } else {
+                println!("project {} is not a favorite", opt.project);
+            }
+        }
+    }
+
+    Ok(())
+}
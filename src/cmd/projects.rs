@@ -0,0 +1,275 @@
+use crate::{
+    cmd::open_store_for_reading,
+    config::Config,
+    entry::ProjectCount,
+    helper,
+    locale::Translations,
+    opt::*,
+    porcelain,
+    store::{
+        favorites,
+        Store,
+    },
+    table,
+    time_context::TimeContext,
+};
+use anyhow::{
+    Context,
+    Error,
+};
+use comfy_table::{
+    Attribute,
+    Cell,
+};
+use log::trace;
+use std::io::{
+    self,
+    Write,
+};
+
+pub(crate) fn run_projects(
+    opt: ProjectsSubCommandOpts,
+    config: Config,
+    color: bool,
+) -> Result<(), Error> {
+    if opt.simple {
+        run_projects_simple(opt, config)
+    } else if opt.porcelain {
+        run_projects_porcelain(opt, config)
+    } else {
+        run_projects_normal(opt, config, color)
+    }
+}
+
+pub(crate) fn run_projects_porcelain(
+    opt: ProjectsSubCommandOpts,
+    config: Config,
+) -> Result<(), Error> {
+    let time = TimeContext::from_config(&config);
+    let store = open_store_for_reading(&opt.datadir_opt.datadir, config)?;
+
+    let mut projects_count = store
+        .get_projects_count(opt.include_ignored, &time)
+        .context("can not get projects count from store")?;
+
+    projects_count.sort();
+
+    // Favorites are shown even without open entries as they are pinned.
+    let favorites = favorites::take_favorites(
+        &mut projects_count,
+        &store.favorite_projects(opt.include_ignored),
+    );
+    projects_count.retain(|entry| entry.has_open_entries() || opt.print_inactive);
+
+    for count in favorites.iter().chain(&projects_count) {
+        println!("{}", porcelain::project_record(count));
+    }
+
+    Ok(())
+}
+
+pub(crate) fn run_projects_simple(
+    opt: ProjectsSubCommandOpts,
+    config: Config,
+) -> Result<(), Error> {
+    let time = TimeContext::from_config(&config);
+    let store = open_store_for_reading(&opt.datadir_opt.datadir, config)?;
+
+    let mut projects_count = store
+        .get_projects_count(opt.include_ignored, &time)
+        .context("can not get projects count from store")?;
+
+    projects_count.sort();
+
+    // Favorites are shown even without open entries as they are pinned.
+    let favorites = favorites::take_favorites(
+        &mut projects_count,
+        &store.favorite_projects(opt.include_ignored),
+    );
+    projects_count.retain(|entry| entry.has_open_entries() || opt.print_inactive);
+
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+
+    for entry in favorites.into_iter().chain(projects_count) {
+        handle.write_all(entry.project.as_bytes())?;
+        handle.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
+
+pub(crate) fn run_projects_normal(
+    opt: ProjectsSubCommandOpts,
+    config: Config,
+    color: bool,
+) -> Result<(), Error> {
+    let store = open_store_for_reading(&opt.datadir_opt.datadir, config.clone())?;
+
+    println!("{}", render_projects_table(&opt, &store, &config, color)?);
+
+    Ok(())
+}
+
+/// Render the projects table the way it is printed.
+fn render_projects_table(
+    opt: &ProjectsSubCommandOpts,
+    store: &Store,
+    config: &Config,
+    color: bool,
+) -> Result<String, Error> {
+    let time = TimeContext::from_config(config);
+    let table_style = config.table_style;
+    let interactivity = config.interactivity;
+    let thresholds = &config.projects_active_thresholds;
+    let translations = Translations::load(store.datadir(), config.locale)?;
+
+    let mut projects_count = store
+        .get_projects_count(opt.include_ignored, &time)
+        .context("can not get projects count from store")?;
+
+    projects_count.sort();
+
+    // Favorites are shown even without open entries as they are pinned.
+    let favorites = favorites::take_favorites(
+        &mut projects_count,
+        &store.favorite_projects(opt.include_ignored),
+    );
+    projects_count.retain(|entry| entry.has_open_entries() || opt.print_inactive);
+
+    let header = [
+        translations.get("project"),
+        translations.get("active"),
+        translations.get("done"),
+        translations.get("total"),
+        translations.get("on_time"),
+    ];
+
+    let mut table = table::new_table(
+        &header,
+        Some(0),
+        opt.table_opt.wide,
+        table_style,
+        interactivity,
+    );
+    table::set_color(&mut table, color);
+
+    let row = |entry: &ProjectCount| {
+        trace!("entry written to table: {:#?}", entry);
+
+        vec![
+            Cell::new(&entry.project),
+            Cell::new(entry.active_count).fg(thresholds.color(entry.active_count)),
+            Cell::new(entry.done_count),
+            Cell::new(entry.total_count),
+            Cell::new(helper::format_ratio(entry.on_time_ratio())),
+        ]
+    };
+
+    for row in table::shade_rows(favorites.iter().map(row).collect()) {
+        table.add_row(row);
+    }
+
+    if !favorites.is_empty() && !projects_count.is_empty() {
+        table::add_separator(&mut table, table_style);
+    }
+
+    for row in table::shade_rows(projects_count.iter().map(row).collect()) {
+        table.add_row(row);
+    }
+
+    if !favorites.is_empty() || !projects_count.is_empty() {
+        table::add_separator(&mut table, table_style);
+    }
+
+    let total = store
+        .get_projects_count(opt.include_ignored, &time)
+        .context("can not get projects count from store")?
+        .into_iter()
+        .fold(ProjectCount::default(), |acc, x| acc + x);
+
+    table.add_row(
+        vec![
+            translations.get("total").to_string(),
+            total.active_count.to_string(),
+            total.done_count.to_string(),
+            total.total_count.to_string(),
+            helper::format_ratio(total.on_time_ratio()),
+        ]
+        .into_iter()
+        .map(|value| Cell::new(value).add_attribute(Attribute::Bold))
+        .collect::<Vec<_>>(),
+    );
+
+    Ok(table.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::{
+        entry,
+        temporary,
+    };
+    use structopt::StructOpt;
+
+    fn projects_opts(args: &[&str]) -> ProjectsSubCommandOpts {
+        let args = ["todust", "projects"].iter().chain(args);
+
+        match Opt::from_iter_safe(args).unwrap().cmd {
+            Some(SubCommand::Projects(opt)) => opt,
+            cmd => panic!("parsed wrong subcommand: {:?}", cmd),
+        }
+    }
+
+    #[test]
+    fn projects_table_without_colors() {
+        let (_directory, store) = temporary();
+        for (project, text) in [
+            ("default", "first"),
+            ("default", "second"),
+            ("work", "third"),
+            ("empty", "finished"),
+        ] {
+            let mut entry = entry(text);
+            entry.metadata.project = project.to_string();
+            store.add_entry(entry.clone()).unwrap();
+
+            if project == "empty" {
+                store
+                    .entry_done_by_uuid(entry.metadata.uuid, false)
+                    .unwrap();
+            }
+        }
+
+        let table =
+            render_projects_table(&projects_opts(&[]), &store, &Config::default(), false).unwrap();
+
+        assert_eq!(
+            table,
+            " Project  Active  Done  Total  On Time \n\x20default  2       0     2      -       \
+             \n\x20work     1       0     1      -       \n\x20         ------  ----  -----  \
+             ------- \n\x20Total    3       1     4      -       "
+        );
+    }
+
+    #[test]
+    fn inactive_projects_are_listed_on_request() {
+        let (_directory, store) = temporary();
+        let finished = entry("finished");
+        store.add_entry(finished.clone()).unwrap();
+        store
+            .entry_done_by_uuid(finished.metadata.uuid, false)
+            .unwrap();
+
+        let table = render_projects_table(
+            &projects_opts(&["--print_inactive"]),
+            &store,
+            &Config::default(),
+            false,
+        )
+        .unwrap();
+
+        assert!(table.contains("\n default  0       1     1 "), "{}", table);
+    }
+}
@@ -0,0 +1,115 @@
+use crate::{
+    config::Config,
+    helper,
+    helper::format_duration,
+    opt::*,
+    project_name,
+    store::recent::{
+        self,
+        RecentProjects,
+    },
+    table,
+    PROJECT_ENV,
+};
+use anyhow::{
+    bail,
+    Error,
+};
+use chrono::Utc;
+use std::env;
+
+pub(crate) fn run_recent(opt: RecentSubCommandOpts, config: Config) -> Result<(), Error> {
+    let recent = RecentProjects::open(&opt.datadir_opt.datadir, config.changes.clone())?;
+    let projects = recent.list();
+
+    if projects.is_empty() {
+        println!("no recently used projects");
+        return Ok(());
+    }
+
+    let session_project =
+        recent::current_session().and_then(|session| recent.session_project(&session));
+
+    let mut table = table::new_table(
+        &["#", "Project", "Last Used", "Session"],
+        Some(1),
+        false,
+        config.table_style,
+        config.interactivity,
+    );
+
+    for (index, project) in projects.into_iter().enumerate() {
+        let session = if session_project.as_ref() == Some(&project.project) {
+            "*"
+        } else {
+            ""
+        };
+
+        table.add_row(vec![
+            (index + 1).to_string(),
+            project.project,
+            format!("{} ago", format_duration(Utc::now() - project.last_used)),
+            session.to_string(),
+        ]);
+    }
+
+    println!("{}", table);
+
+    Ok(())
+}
+
+pub(crate) fn run_switch(opt: SwitchSubCommandOpts, config: Config) -> Result<(), Error> {
+    let session = match recent::current_session() {
+        Some(session) => session,
+        None => {
+            bail!("can not find out the terminal session, use --project or TODUST_PROJECT instead")
+        }
+    };
+
+    let recent = RecentProjects::open(&opt.datadir_opt.datadir, config.changes.clone())?;
+
+    if opt.clear {
+        recent.switch(&session, None)?;
+        println!("this terminal session uses the default project again");
+
+        return Ok(());
+    }
+
+    let project = match opt.name {
+        Some(project) => project,
+        None => {
+            let projects = recent
+                .list()
+                .into_iter()
+                .map(|recent| recent.project)
+                .collect::<Vec<_>>();
+
+            if projects.is_empty() {
+                bail!("no recently used projects, pass the name of the project to switch to")
+            }
+
+            config
+                .interactivity
+                .check_prompt("pass the name of the project to switch to")?;
+
+            let index = helper::choose("switch to which project?", &projects)?;
+            projects[index].clone()
+        }
+    };
+
+    project_name::validate_project_name(&project)?;
+
+    recent.switch(&session, Some(&project))?;
+    recent.record(std::slice::from_ref(&project), Some(&session))?;
+
+    println!("this terminal session uses project {} now", project);
+
+    if env::var_os(PROJECT_ENV).is_some() {
+        println!(
+            "{} is set and takes precedence until it is unset",
+            PROJECT_ENV
+        );
+    }
+
+    Ok(())
+}
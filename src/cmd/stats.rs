@@ -0,0 +1,137 @@
+use crate::{
+    cmd::open_store_for_reading,
+    config::Config,
+    helper,
+    locale::Translations,
+    opt::*,
+    table,
+};
+use anyhow::{
+    Context,
+    Error,
+};
+use comfy_table::{
+    Attribute,
+    Cell,
+};
+
+pub(crate) fn run_stats(opt: StatsSubCommandOpts, config: Config) -> Result<(), Error> {
+    let table_style = config.table_style;
+    let interactivity = config.interactivity;
+    let locale = config.locale;
+    let store = open_store_for_reading(&opt.datadir_opt.datadir, config)?;
+    let translations = Translations::load(store.datadir(), locale)?;
+
+    let limit = if opt.history { opt.limit } else { 1 };
+    let snapshots = store
+        .stats_history(opt.project.as_deref(), |_| true, limit)
+        .context("can not read statistics history")?;
+
+    let latest = match snapshots.last() {
+        Some(latest) => latest,
+        None => {
+            println!("no snapshots recorded yet");
+            return Ok(());
+        }
+    };
+
+    if !opt.history {
+        let header = [
+            translations.get("project"),
+            translations.get("active"),
+            translations.get("done"),
+        ];
+
+        let mut table = table::new_table(
+            &header,
+            Some(0),
+            opt.table_opt.wide,
+            table_style,
+            interactivity,
+        );
+        let rows = latest
+            .projects
+            .iter()
+            .map(|(project, counts)| {
+                vec![
+                    Cell::new(project),
+                    Cell::new(counts.active),
+                    Cell::new(counts.done),
+                ]
+            })
+            .collect();
+
+        for row in table::shade_rows(rows) {
+            table.add_row(row);
+        }
+
+        table::add_separator(&mut table, table_style);
+        table.add_row(
+            vec![
+                translations.get("total").to_string(),
+                latest.active.to_string(),
+                latest.done.to_string(),
+            ]
+            .into_iter()
+            .map(|value| Cell::new(value).add_attribute(Attribute::Bold))
+            .collect::<Vec<_>>(),
+        );
+
+        println!("{}", locale.format_day(latest.day));
+        println!("{}", table);
+
+        return Ok(());
+    }
+
+    let header = [
+        translations.get("day"),
+        translations.get("active"),
+        translations.get("done"),
+    ];
+
+    let mut table = table::new_table(
+        &header,
+        None,
+        opt.table_opt.wide,
+        table_style,
+        interactivity,
+    );
+    let rows = snapshots
+        .iter()
+        .map(|snapshot| {
+            vec![
+                Cell::new(locale.format_date(snapshot.day)),
+                Cell::new(snapshot.active),
+                Cell::new(snapshot.done),
+            ]
+        })
+        .collect();
+
+    for row in table::shade_rows(rows) {
+        table.add_row(row);
+    }
+
+    println!("{}", table);
+
+    let active = snapshots
+        .iter()
+        .map(|snapshot| snapshot.active)
+        .collect::<Vec<_>>();
+    let done = snapshots
+        .iter()
+        .map(|snapshot| snapshot.done)
+        .collect::<Vec<_>>();
+
+    println!(
+        "{}: {}",
+        translations.get("active"),
+        helper::format_sparkline(&active)
+    );
+    println!(
+        "{}: {}",
+        translations.get("done"),
+        helper::format_sparkline(&done)
+    );
+
+    Ok(())
+}
@@ -0,0 +1,12 @@
+use crate::{
+    cmd::open_store,
+    config::Config,
+    opt::*,
+};
+use anyhow::Error;
+
+pub(crate) fn run_sync(opt: SyncSubCommandOpts, config: Config) -> Result<(), Error> {
+    let store = open_store(&opt.datadir_opt.datadir, config)?;
+
+    store.sync()
+}
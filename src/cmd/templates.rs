@@ -0,0 +1,27 @@
+use crate::{
+    opt::*,
+    templating,
+};
+use anyhow::{
+    Context,
+    Error,
+};
+use std::io::{
+    self,
+    Write,
+};
+
+pub(crate) fn run_templates(opt: TemplatesSubCommandOpts) -> Result<(), Error> {
+    let templates = templating::entry_templates(&opt.datadir_opt.datadir)
+        .context("can not get entry templates")?;
+
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+
+    for template in templates {
+        handle.write_all(template.as_bytes())?;
+        handle.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
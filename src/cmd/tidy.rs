@@ -0,0 +1,67 @@
+use crate::{
+    cmd::open_store,
+    config::Config,
+    entry::Entry,
+    opt::*,
+    tidy,
+};
+use anyhow::{
+    Context,
+    Error,
+};
+
+pub(crate) fn run_tidy(opt: TidySubCommandOpts, config: Config) -> Result<(), Error> {
+    let formatter = config.tidy_formatter.clone();
+    let store = open_store(&opt.datadir_opt.datadir, config)?;
+
+    let entries = if opt.all_projects {
+        store
+            .get_all_entries(opt.include_ignored)
+            .context("can not get entries from store")?
+    } else {
+        store
+            .get_entries(&opt.project_opt.project)
+            .context("can not get entries from store")?
+    };
+
+    let mut untidy = Vec::new();
+    for entry in entries {
+        let text = match &formatter {
+            Some(formatter) => tidy::run_formatter(formatter, &entry.text)
+                .with_context(|| format!("can not format entry {}", entry.metadata.uuid))?,
+            None => entry.text.clone(),
+        };
+
+        let text = tidy::normalize(&text);
+        if text != entry.text {
+            untidy.push(Entry { text, ..entry });
+        }
+    }
+
+    if opt.check {
+        if untidy.is_empty() {
+            return Ok(());
+        }
+
+        for entry in &untidy {
+            match store.entry_file(&entry.metadata.uuid) {
+                Some(path) => println!("{}", path.display()),
+                None => println!("{}", entry.metadata.uuid),
+            }
+        }
+
+        ::std::process::exit(1);
+    }
+
+    let tidied = store
+        .tidy_entries(untidy)
+        .context("can not write tidied entries")?;
+
+    match tidied {
+        0 => println!("all entries are tidy"),
+        1 => println!("tidied 1 entry"),
+        tidied => println!("tidied {} entries", tidied),
+    }
+
+    Ok(())
+}
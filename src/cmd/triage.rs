@@ -0,0 +1,205 @@
+use crate::{
+    cmd::{
+        check_new_project,
+        open_store,
+    },
+    config::Config,
+    entry::Entry,
+    helper,
+    opt::*,
+    project_name,
+    store::Store,
+    DEFAULT_PROJECT,
+};
+use anyhow::{
+    Context,
+    Error,
+};
+
+/// Ask for every active entry of the default project which project it
+/// belongs to and move it there. Every move is committed on its own so
+/// stopping halfway keeps the entries that were moved already.
+pub(crate) fn run_triage(opt: TriageSubCommandOpts, config: Config) -> Result<(), Error> {
+    let yes = opt.yes || config.auto_create_projects;
+    let store = open_store(&opt.datadir_opt.datadir, config)?;
+
+    let entries = store
+        .get_active_entries(DEFAULT_PROJECT)
+        .context("can not get project entries")?
+        .into_iter()
+        .collect::<Vec<_>>();
+
+    if entries.is_empty() {
+        println!("no active todos in project {}", DEFAULT_PROJECT);
+        return Ok(());
+    }
+
+    store
+        .interactivity()
+        .check_prompt("move the todos with todust move instead")?;
+
+    let moved = triage_entries(&store, &entries, yes, helper::ask)?;
+
+    println!();
+    println!(
+        "moved {} of {} todos out of project {}",
+        moved,
+        entries.len(),
+        DEFAULT_PROJECT
+    );
+
+    Ok(())
+}
+
+/// Move each entry to the project `ask` answers with. Returns how many
+/// entries were moved.
+fn triage_entries<F>(
+    store: &Store,
+    entries: &[Entry],
+    yes: bool,
+    mut ask: F,
+) -> Result<usize, Error>
+where
+    F: FnMut(&str) -> Result<String, Error>,
+{
+    let mut moved = 0;
+    'entries: for (index, entry) in entries.iter().enumerate() {
+        println!();
+        println!(
+            "{}/{}: {}",
+            index + 1,
+            entries.len(),
+            helper::summarize(&entry.text, None)
+        );
+
+        let project = loop {
+            let project = ask("move to which project? (empty to skip, q to stop): ")?;
+
+            match project.as_str() {
+                "" => continue 'entries,
+                "q" => break 'entries,
+                _ => {}
+            }
+
+            // Ask again instead of failing so the todos that are left can
+            // still be triaged.
+            let checked = project_name::validate_project_name(&project)
+                .map_err(Error::from)
+                .and_then(|()| check_new_project(store, &project, yes));
+
+            match checked {
+                Ok(()) => break project,
+                Err(err) => println!("{:#}", err),
+            }
+        };
+
+        if project == DEFAULT_PROJECT {
+            continue;
+        }
+
+        let new_entry = Entry {
+            metadata: entry.metadata.changed().project(project.clone()).build()?,
+            ..entry.clone()
+        };
+
+        store
+            .update_entry(new_entry)
+            .context("can not move entry")?;
+
+        println!("moved to project {}", project);
+        moved += 1;
+    }
+
+    Ok(moved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::{
+        entry,
+        temporary,
+    };
+
+    /// Triage the active entries of the default project with the answers.
+    /// Returns how many entries were moved and the answers that are left.
+    fn triage(store: &Store, answers: &[&str]) -> (usize, Vec<String>) {
+        let entries = store
+            .get_active_entries(DEFAULT_PROJECT)
+            .unwrap()
+            .into_iter()
+            .collect::<Vec<_>>();
+        let mut answers = answers
+            .iter()
+            .map(|answer| answer.to_string())
+            .collect::<Vec<_>>();
+        answers.reverse();
+
+        let moved = triage_entries(store, &entries, true, |_| Ok(answers.pop().unwrap())).unwrap();
+        answers.reverse();
+
+        (moved, answers)
+    }
+
+    fn projects_of(store: &Store) -> Vec<(String, String)> {
+        let mut projects = store
+            .get_projects()
+            .unwrap()
+            .into_iter()
+            .flat_map(|project| {
+                store
+                    .get_active_entries(&project)
+                    .unwrap()
+                    .into_iter()
+                    .map(move |entry| (entry.text, project.clone()))
+            })
+            .collect::<Vec<_>>();
+        projects.sort();
+
+        projects
+    }
+
+    #[test]
+    fn entries_are_moved_skipped_or_asked_again() {
+        let (_directory, store) = temporary();
+        let entries = ["first", "second", "third", "fourth"]
+            .iter()
+            .map(|text| entry(text))
+            .collect::<Vec<_>>();
+        store.add_entries(&entries).unwrap();
+        let order = store
+            .get_active_entries(DEFAULT_PROJECT)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.text)
+            .collect::<Vec<_>>();
+
+        let (moved, left) = triage(&store, &["work", "", "a/b", "home", "default"]);
+
+        assert_eq!(moved, 2);
+        assert!(left.is_empty(), "{:?}", left);
+        let mut expected = vec![
+            (order[0].clone(), "work".to_string()),
+            (order[1].clone(), "default".to_string()),
+            (order[2].clone(), "home".to_string()),
+            (order[3].clone(), "default".to_string()),
+        ];
+        expected.sort();
+        assert_eq!(projects_of(&store), expected);
+    }
+
+    #[test]
+    fn q_stops_and_keeps_the_moved_entries() {
+        let (_directory, store) = temporary();
+        store
+            .add_entries(&[entry("first"), entry("second")])
+            .unwrap();
+
+        let (moved, left) = triage(&store, &["work", "q", "unused"]);
+
+        assert_eq!(moved, 1);
+        assert_eq!(left, vec!["unused"]);
+        assert_eq!(store.get_active_entries("work").unwrap().len(), 1);
+        assert_eq!(store.get_active_entries(DEFAULT_PROJECT).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,46 @@
+use crate::{
+    cmd::open_store,
+    config::Config,
+    opt::*,
+};
+use anyhow::{
+    Context,
+    Error,
+};
+use async_std::channel;
+
+pub(crate) async fn run_web(opt: WebSubCommandOpts, config: Config) -> Result<(), Error> {
+    let store = open_store(&opt.datadir_opt.datadir, config.clone())?;
+
+    let lock = store.lock_web_instance(&opt.binding.to_string(), opt.force)?;
+
+    // Stopping with ctrl-c or a termination signal ends the webservice like
+    // an error does so the lock is removed and queued webhook events are
+    // delivered before the process exits.
+    let (stop, stopped) = channel::bounded(1);
+    let handler_stop = stop.clone();
+    ctrlc::set_handler(move || {
+        let _ = handler_stop.try_send(Ok(()));
+    })
+    .context("can not set handler for stopping the webservice")?;
+
+    let webservice = crate::webservice::WebService::open(store.clone(), &config, !opt.no_cache);
+    let result = match webservice {
+        Ok(webservice) => {
+            async_std::task::spawn(async move {
+                let _ = stop.send(webservice.run(opt.binding).await).await;
+            });
+
+            stopped
+                .recv()
+                .await
+                .context("webservice stopped unexpectedly")?
+        }
+        Err(err) => Err(err),
+    };
+
+    lock.release();
+    store.flush_webhooks();
+
+    result
+}
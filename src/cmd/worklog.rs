@@ -0,0 +1,48 @@
+use crate::{
+    cmd::open_store_for_reading,
+    config::Config,
+    opt::*,
+    stats::Worklog,
+    time_context::TimeContext,
+};
+use anyhow::{
+    Context,
+    Error,
+};
+
+pub(crate) fn run_worklog(opt: WorklogSubCommandOpts, config: Config) -> Result<(), Error> {
+    let time = TimeContext::from_config(&config).frozen();
+    let locale = config.locale;
+    let store = open_store_for_reading(&opt.datadir_opt.datadir, config)?;
+
+    let entries = if opt.all_projects {
+        store.get_all_entries(false)
+    } else {
+        store.get_entries(&opt.project_opt.project)
+    }
+    .context("can not get entries from store")?;
+
+    let (from, to) = if opt.week {
+        time.week_bounds(time.today())
+    } else {
+        (
+            opt.from.expect("clap requires --from without --week"),
+            opt.to.unwrap_or_else(|| time.today()),
+        )
+    };
+    let worklog = Worklog::new(entries, from, to, &time, locale);
+
+    let rendered = match opt.format {
+        WorklogFormat::Asciidoc => worklog.to_string(),
+        WorklogFormat::Json => serde_json::to_string_pretty(&worklog)? + "\n",
+        WorklogFormat::Text => worklog.to_text(),
+    };
+
+    match opt.output {
+        Some(path) => std::fs::write(&path, rendered)
+            .with_context(|| format!("can not write worklog to {:?}", path))?,
+        None => print!("{}", rendered),
+    }
+
+    Ok(())
+}
@@ -1,30 +1,358 @@
-use crate::store::vcs::VcsConfig;
+use crate::{
+    due_date::DueDateLimits,
+    helper::ConfirmDone,
+    interactivity::Interactivity,
+    locale::Locale,
+    store::{
+        backup::BackupConfig,
+        changes::Changes,
+        hooks::HooksConfig,
+        index::IndexRotation,
+        quota::Quotas,
+        vcs::VcsConfig,
+        webhooks::Webhook,
+    },
+    table::{
+        ActiveThresholds,
+        TableStyle,
+    },
+    time_context::WeekStart,
+    webservice::{
+        auth::WebToken,
+        index_columns::IndexPreferences,
+        preferences::ViewPreferences,
+        rate_limit::RateLimitConfig,
+        webhook::InboundWebhook,
+    },
+};
+use chrono_tz::Tz;
 use serde::{
     Deserialize,
     Serialize,
 };
 use std::{
+    collections::BTreeMap,
     fs,
     io::Write,
-    path::Path,
+    path::{
+        Path,
+        PathBuf,
+    },
 };
 use uuid::Uuid;
 
-#[derive(Serialize, Deserialize)]
-pub(super) struct Config {
+/// Entries listed in the sitemap by default.
+const DEFAULT_SITEMAP_LIMIT: usize = 1000;
+
+/// Bytes of entry texts read for views that show many entries by default.
+const DEFAULT_ENTRY_PREVIEW_BYTES: usize = 64 * 1024;
+
+/// How far apart concurrent changes of an entry can be by default.
+const DEFAULT_CONFLICT_WINDOW_HOURS: u64 = 7 * 24;
+
+/// How often `list --watch` checks for changes by default.
+const DEFAULT_WATCH_INTERVAL_SECONDS: u64 = 2;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Config {
     pub(super) identifier: String,
+
+    /// Projects that are hidden from views spanning multiple projects. Can be
+    /// overwritten per store in the store settings.
+    #[serde(default)]
+    pub(super) ignored_projects: Vec<String>,
+
+    /// Projects that are listed first in the projects table and on the index
+    /// page in this order, even without entries. Can be changed per machine
+    /// with `todust project favorite` and `todust project unfavorite`.
+    #[serde(default)]
+    pub(super) favorite_projects: Vec<String>,
+
+    /// Timezone used to decide which day a timestamp belongs to, for example
+    /// "Europe/Berlin". Defaults to the timezone of the system.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) timezone: Option<Tz>,
+
+    /// Project used by the webservice when a request does not name one.
+    /// Defaults to "default".
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) default_project: Option<String>,
+
+    /// Command that `tidy` runs for every entry. It gets the text on stdin
+    /// and has to print the formatted text. Arguments are split on
+    /// whitespace.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) tidy_formatter: Option<String>,
+
+    /// Save edited entry texts without asking after the diff was shown. Same
+    /// as always passing --yes to edit.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(super) skip_edit_confirmation: bool,
+
+    /// Add and move entries to projects that do not exist yet without asking.
+    /// Same as always passing --yes to add and move.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(super) auto_create_projects: bool,
+
+    /// Refuse to add entries without a project instead of adding them to the
+    /// default project, both with `add` and the quick add of the webservice.
+    /// Entries that are in the default project already can be moved with
+    /// `todust triage`.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(super) require_explicit_project: bool,
+
+    /// How finishing an entry is confirmed, either "prompt" to answer yes or
+    /// no, "text" to retype the first word of the entry or "off" to not ask
+    /// like passing --yes to done.
+    #[serde(default)]
+    pub(super) confirm_done: ConfirmDone,
+
+    /// Day weeks start on in reports, either "monday" or "sunday".
+    #[serde(default)]
+    pub(super) week_starts_on: WeekStart,
+
+    /// Include filesystem paths like the datadir in /api/v1/info. Off by
+    /// default as the webservice might be reachable from the internet.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(super) expose_paths: bool,
+
+    /// Allow crawlers to index the webservice in its robots.txt. Only set
+    /// this if the instance is meant to be public.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(super) public: bool,
+
+    /// Url the webservice is reachable under, for example
+    /// "https://example.com/todust". Used for the links in the sitemap.
+    /// Defaults to the host the request was sent to.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) web_base_url: Option<String>,
+
+    /// Maximum number of entries in the sitemap. The most recently changed
+    /// entries are listed first.
+    #[serde(default = "default_sitemap_limit")]
+    pub(super) sitemap_limit: usize,
+
+    /// How often a new index file is started, either "day" or "month".
+    /// Fewer files are created with "month".
+    #[serde(default)]
+    pub(super) index_rotation: IndexRotation,
+
+    /// Hours the changes of two machines to the same entry can be apart to
+    /// still be reported as a conflict when both were based on the same
+    /// previous change. Set to 0 to disable the detection.
+    #[serde(default = "default_conflict_window_hours")]
+    pub(super) conflict_window_hours: u64,
+
+    /// How the borders of tables are drawn, either "plain", "borders" or
+    /// "ascii".
+    #[serde(default)]
+    pub(super) table_style: TableStyle,
+
+    /// Pager that long output of print and list is shown in when it does not
+    /// fit on the terminal. Defaults to $PAGER or "less -FRX", set to "" to
+    /// disable paging.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) pager: Option<String>,
+
+    /// Language of dates and labels in tables and on the web pages, either
+    /// "en" or "de". Labels can be changed with a locale/<locale>.toml file
+    /// in the datadir.
+    #[serde(default)]
+    pub(super) locale: Locale,
+
+    /// Bytes of the text of each entry that are read for views that show
+    /// many entries like the project page, `list` and the json api. Longer
+    /// texts are cut off there and only shown fully on their own.
+    #[serde(default = "default_entry_preview_bytes")]
+    pub(super) entry_preview_bytes: usize,
+
+    /// Warn before reading entries when the datadir was not synced for longer
+    /// than this duration like "1d". The last pull on this machine and the
+    /// last commit count as sync. Disabled by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) warn_stale_after: Option<String>,
+
+    /// Do not print the header naming the datadir and project when they come
+    /// from an environment variable or a flag. Same as always passing
+    /// --quiet.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub(super) hide_origin_header: bool,
+
+    /// Seconds between the checks for changes of the entries with `list
+    /// --watch`. Only the size and modification time of the index files are
+    /// checked so short intervals are cheap.
+    #[serde(default = "default_watch_interval_seconds")]
+    pub(super) watch_interval_seconds: u64,
+
+    /// Directory of the audit log that records which token changed which
+    /// entry through the webservice. Relative paths are relative to the
+    /// datadir. Defaults to the state directory of the store.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) audit_directory: Option<PathBuf>,
+
+    // Tables have to come after plain values when serializing to toml so all
+    // new fields that are tables have to go below this line.
     pub(super) vcs_config: VcsConfig,
+
+    /// Other datadirs by name that entries can be moved to with `move
+    /// --to_store`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub(super) stores: BTreeMap<String, PathBuf>,
+
+    /// Tokens that grant access to the webservice. If no tokens are
+    /// configured the webservice can be accessed without authentication.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(super) web_tokens: Vec<WebToken>,
+
+    /// Webhooks that create entries from json payloads posted to the
+    /// webservice.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(super) inbound_webhooks: Vec<InboundWebhook>,
+
+    /// Webhooks that get notified when entries are created, finished or
+    /// moved.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub(super) webhooks: Vec<Webhook>,
+
+    /// Defaults for how projects are shown in the web ui.
+    #[serde(default)]
+    pub(super) web_view: ViewPreferences,
+
+    /// Default columns and sort of the project index in the web ui.
+    #[serde(default)]
+    pub(super) web_index: IndexPreferences,
+
+    /// Limits for the web endpoints that change entries.
+    #[serde(default)]
+    pub(super) web_rate_limit: RateLimitConfig,
+
+    /// Range of due dates that are accepted.
+    #[serde(default)]
+    pub(super) due_date_limits: DueDateLimits,
+
+    /// Active entry counts from which `todust projects` shows projects in
+    /// yellow or red.
+    #[serde(default)]
+    pub(super) projects_active_thresholds: ActiveThresholds,
+
+    /// Commands that are run after entries were added, finished or moved.
+    #[serde(default)]
+    pub(super) hooks: HooksConfig,
+
+    /// Limits for the number of projects and entries in the store.
+    #[serde(default)]
+    pub(super) quotas: Quotas,
+
+    /// Backups of the datadir before operations that remove data.
+    #[serde(default)]
+    pub(super) backups: BackupConfig,
+
+    /// Changes of the stores opened with this config. Only planned with the
+    /// global --dry_run flag and never read from or written to the config
+    /// file.
+    #[serde(skip)]
+    pub(super) changes: Changes,
+
+    /// Set from the global --no_hooks flag and never read from or written to
+    /// the config file.
+    #[serde(skip)]
+    pub(super) no_hooks: bool,
+
+    /// Set from the global --no_index_cache flag and never read from or
+    /// written to the config file.
+    #[serde(skip)]
+    pub(super) no_index_cache: bool,
+
+    /// Set from the global --no_stale_check flag and never read from or
+    /// written to the config file.
+    #[serde(skip)]
+    pub(super) no_stale_check: bool,
+
+    /// Set from the global --repair flag and never read from or written to
+    /// the config file.
+    #[serde(skip)]
+    pub(super) repair: bool,
+
+    /// Set from the global --quiet flag and never read from or written to
+    /// the config file.
+    #[serde(skip)]
+    pub(super) quiet: bool,
+
+    /// Detected in main from the terminal, the global --quiet flag and the
+    /// --porcelain flag of the subcommand. Never read from or written to the
+    /// config file.
+    #[serde(skip)]
+    pub(super) interactivity: Interactivity,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             identifier: Uuid::new_v4().to_string(),
+            ignored_projects: Vec::new(),
+            favorite_projects: Vec::new(),
+            timezone: None,
+            default_project: None,
+            tidy_formatter: None,
+            skip_edit_confirmation: false,
+            auto_create_projects: false,
+            require_explicit_project: false,
+            confirm_done: ConfirmDone::default(),
+            week_starts_on: WeekStart::default(),
+            expose_paths: false,
+            public: false,
+            web_base_url: None,
+            sitemap_limit: default_sitemap_limit(),
+            index_rotation: IndexRotation::default(),
+            conflict_window_hours: default_conflict_window_hours(),
+            table_style: TableStyle::default(),
+            pager: None,
+            locale: Locale::default(),
+            entry_preview_bytes: default_entry_preview_bytes(),
+            warn_stale_after: None,
+            hide_origin_header: false,
+            watch_interval_seconds: default_watch_interval_seconds(),
+            audit_directory: None,
+            stores: BTreeMap::new(),
             vcs_config: VcsConfig::default(),
+            web_tokens: Vec::new(),
+            inbound_webhooks: Vec::new(),
+            webhooks: Vec::new(),
+            web_view: ViewPreferences::default(),
+            web_index: IndexPreferences::default(),
+            web_rate_limit: RateLimitConfig::default(),
+            due_date_limits: DueDateLimits::default(),
+            projects_active_thresholds: ActiveThresholds::default(),
+            hooks: HooksConfig::default(),
+            quotas: Quotas::default(),
+            backups: BackupConfig::default(),
+            changes: Changes::default(),
+            no_hooks: false,
+            no_index_cache: false,
+            no_stale_check: false,
+            repair: false,
+            quiet: false,
+            interactivity: Interactivity::default(),
         }
     }
 }
 
+fn default_entry_preview_bytes() -> usize {
+    DEFAULT_ENTRY_PREVIEW_BYTES
+}
+
+fn default_sitemap_limit() -> usize {
+    DEFAULT_SITEMAP_LIMIT
+}
+
+fn default_conflict_window_hours() -> u64 {
+    DEFAULT_CONFLICT_WINDOW_HOURS
+}
+
+fn default_watch_interval_seconds() -> u64 {
+    DEFAULT_WATCH_INTERVAL_SECONDS
+}
+
 impl Config {
     pub(super) fn read_path<P: AsRef<Path>>(file_path: P) -> Result<Self, Error> {
         if !file_path.as_ref().exists() {
@@ -0,0 +1,95 @@
+use crossterm::style::{
+    style,
+    Color,
+    Stylize,
+};
+use similar::TextDiff;
+
+/// Lines of unchanged text shown around every change.
+const CONTEXT_LINES: usize = 3;
+
+/// Unified diff from the old to the new text with the names in the header.
+/// Empty if the texts are the same.
+pub(super) fn unified_diff(old_name: &str, old: &str, new_name: &str, new: &str) -> String {
+    if old == new {
+        return String::new();
+    }
+
+    TextDiff::from_lines(old, new)
+        .unified_diff()
+        .context_radius(CONTEXT_LINES)
+        .header(old_name, new_name)
+        .to_string()
+}
+
+/// Color the lines of a unified diff for the terminal. Removed lines are red,
+/// added lines green and hunk headers cyan.
+pub(super) fn colorize(diff: &str) -> String {
+    diff.lines()
+        .map(|line| {
+            let color = if line.starts_with("---") || line.starts_with("+++") {
+                None
+            } else if line.starts_with('-') {
+                Some(Color::Red)
+            } else if line.starts_with('+') {
+                Some(Color::Green)
+            } else if line.starts_with("@@") {
+                Some(Color::Cyan)
+            } else {
+                None
+            };
+
+            match color {
+                Some(color) => format!("{}\n", style(line).with(color)),
+                None => format!("{}\n", line),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unified_diff_shows_the_changed_lines_with_context() {
+        let old = "== Title\n\none\ntwo\nthree\nfour\nfive\nsix\nseven\n";
+        let new = "== Title\n\none\ntwo\nthree\nfour\nfive\nsix\neight\n";
+
+        assert_eq!(
+            unified_diff("before", old, "after", new),
+            "--- before\n+++ after\n@@ -6,4 +6,4 @@\n four\n five\n six\n-seven\n+eight\n"
+        );
+    }
+
+    #[test]
+    fn unified_diff_of_equal_texts_is_empty() {
+        assert_eq!(unified_diff("before", "text\n", "after", "text\n"), "");
+        assert_eq!(unified_diff("before", "", "after", ""), "");
+    }
+
+    #[test]
+    fn unified_diff_marks_a_missing_line_break() {
+        assert_eq!(
+            unified_diff("before", "text\n", "after", "text"),
+            "--- before\n+++ after\n@@ -1 +1 @@\n-text\n+text\n\\ No newline at end of file\n"
+        );
+    }
+
+    #[test]
+    fn colorize_only_colors_changes_and_hunks() {
+        let diff = unified_diff("before", "same\nold\n", "after", "same\nnew\n");
+        let colored = colorize(&diff);
+        let lines = colored.lines().collect::<Vec<_>>();
+
+        assert_eq!(lines[0], "--- before");
+        assert_eq!(lines[1], "+++ after");
+        assert_eq!(
+            lines[2],
+            format!("{}", style("@@ -1,2 +1,2 @@").with(Color::Cyan))
+        );
+        assert_eq!(lines[3], " same");
+        assert_eq!(lines[4], format!("{}", style("-old").with(Color::Red)));
+        assert_eq!(lines[5], format!("{}", style("+new").with(Color::Green)));
+    }
+}
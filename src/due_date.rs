@@ -0,0 +1,239 @@
+use anyhow::{
+    bail,
+    Error,
+};
+use chrono::{
+    naive::{
+        MAX_DATE,
+        MIN_DATE,
+    },
+    Datelike,
+    Duration,
+    NaiveDate,
+    Weekday,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::str::FromStr;
+
+/// Due dates further in the future than this many years need a confirmation.
+const CONFIRM_AFTER_YEARS: i32 = 1;
+
+/// Range of due dates that are accepted so typos in the year like 0225 or
+/// 20025 are caught.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct DueDateLimits {
+    /// How many years due dates can be in the past.
+    pub(super) max_years_past: u32,
+
+    /// How many years due dates can be in the future.
+    pub(super) max_years_future: u32,
+}
+
+impl Default for DueDateLimits {
+    fn default() -> Self {
+        Self {
+            max_years_past: 100,
+            max_years_future: 20,
+        }
+    }
+}
+
+impl DueDateLimits {
+    /// Fail if the due date is outside of the configured range around today.
+    pub(super) fn validate(&self, due: NaiveDate, today: NaiveDate) -> Result<(), Error> {
+        let earliest = years_from(today, -(self.max_years_past as i32));
+        let latest = years_from(today, self.max_years_future as i32);
+
+        if due < earliest {
+            bail!(
+                "due date {} is more than {} years in the past, check the year",
+                due,
+                self.max_years_past
+            )
+        }
+
+        if due > latest {
+            bail!(
+                "due date {} is more than {} years in the future, check the year",
+                due,
+                self.max_years_future
+            )
+        }
+
+        Ok(())
+    }
+}
+
+/// Due date as it is given on the command line. Either a date like 2019-12-24
+/// or the name of a weekday like friday.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DueDateArg {
+    Date(NaiveDate),
+    Weekday(Weekday),
+}
+
+impl DueDateArg {
+    /// The date the argument names. A weekday is the next such day after
+    /// today so friday on a friday is a week later.
+    pub(super) fn date(self, today: NaiveDate) -> NaiveDate {
+        match self {
+            DueDateArg::Date(date) => date,
+            DueDateArg::Weekday(weekday) => {
+                let days = (7 + weekday.num_days_from_monday()
+                    - today.weekday().num_days_from_monday())
+                    % 7;
+
+                today + Duration::days(if days == 0 { 7 } else { i64::from(days) })
+            }
+        }
+    }
+}
+
+impl FromStr for DueDateArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(date) = NaiveDate::from_str(s) {
+            return Ok(DueDateArg::Date(date));
+        }
+
+        Weekday::from_str(s).map(DueDateArg::Weekday).map_err(|_| {
+            format!(
+                "{:?} is neither a date like 2019-12-24 nor a weekday like friday",
+                s
+            )
+        })
+    }
+}
+
+/// If the due date is far enough in the future that it might be a typo and
+/// should be confirmed.
+pub(super) fn is_far_future(due: NaiveDate, today: NaiveDate) -> bool {
+    due > years_from(today, CONFIRM_AFTER_YEARS)
+}
+
+/// The same day the given number of years later or earlier. The 29th of
+/// February becomes the 28th in years that are not leap years.
+fn years_from(date: NaiveDate, years: i32) -> NaiveDate {
+    let year = date.year().saturating_add(years);
+
+    NaiveDate::from_ymd_opt(year, date.month(), date.day())
+        .or_else(|| NaiveDate::from_ymd_opt(year, date.month(), date.day() - 1))
+        .unwrap_or(if years < 0 { MIN_DATE } else { MAX_DATE })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn dates_at_the_limits_are_accepted() {
+        let limits = DueDateLimits::default();
+        let today = day(2026, 10, 16);
+
+        assert!(limits.validate(today, today).is_ok());
+        assert!(limits.validate(day(1926, 10, 16), today).is_ok());
+        assert!(limits.validate(day(2046, 10, 16), today).is_ok());
+    }
+
+    #[test]
+    fn dates_past_the_limits_are_rejected() {
+        let limits = DueDateLimits::default();
+        let today = day(2026, 10, 16);
+
+        assert_eq!(
+            limits
+                .validate(day(1926, 10, 15), today)
+                .unwrap_err()
+                .to_string(),
+            "due date 1926-10-15 is more than 100 years in the past, check the year"
+        );
+        assert_eq!(
+            limits
+                .validate(day(2046, 10, 17), today)
+                .unwrap_err()
+                .to_string(),
+            "due date 2046-10-17 is more than 20 years in the future, check the year"
+        );
+    }
+
+    #[test]
+    fn typos_in_the_year_are_rejected() {
+        let limits = DueDateLimits::default();
+        let today = day(2026, 10, 16);
+
+        assert!(limits.validate(day(225, 1, 1), today).is_err());
+        assert!(limits.validate(day(20025, 1, 1), today).is_err());
+    }
+
+    #[test]
+    fn limits_are_configurable() {
+        let limits: DueDateLimits = toml::from_str("max_years_future = 2").unwrap();
+        let today = day(2026, 10, 16);
+
+        assert_eq!(limits.max_years_past, 100);
+        assert!(limits.validate(day(2028, 10, 16), today).is_ok());
+        assert!(limits.validate(day(2028, 10, 17), today).is_err());
+    }
+
+    #[test]
+    fn leap_days_are_moved_to_the_28th() {
+        assert_eq!(years_from(day(2024, 2, 29), 1), day(2025, 2, 28));
+        assert_eq!(years_from(day(2024, 2, 29), 4), day(2028, 2, 29));
+        assert_eq!(years_from(day(2024, 2, 29), -100), day(1924, 2, 29));
+        assert_eq!(years_from(day(2024, 2, 29), -101), day(1923, 2, 28));
+    }
+
+    #[test]
+    fn due_date_args_are_dates_or_weekdays() {
+        assert_eq!("2026-12-01".parse(), Ok(DueDateArg::Date(day(2026, 12, 1))));
+        assert_eq!("friday".parse(), Ok(DueDateArg::Weekday(Weekday::Fri)));
+        assert_eq!("Fri".parse(), Ok(DueDateArg::Weekday(Weekday::Fri)));
+        assert!("someday".parse::<DueDateArg>().is_err());
+        assert!("2026-13-01".parse::<DueDateArg>().is_err());
+    }
+
+    #[test]
+    fn weekdays_are_the_next_such_day() {
+        // 2026-10-16 is a friday.
+        let today = day(2026, 10, 16);
+
+        assert_eq!(
+            DueDateArg::Weekday(Weekday::Sat).date(today),
+            day(2026, 10, 17)
+        );
+        assert_eq!(
+            DueDateArg::Weekday(Weekday::Mon).date(today),
+            day(2026, 10, 19)
+        );
+        assert_eq!(
+            DueDateArg::Weekday(Weekday::Thu).date(today),
+            day(2026, 10, 22)
+        );
+        assert_eq!(
+            DueDateArg::Weekday(Weekday::Fri).date(today),
+            day(2026, 10, 23)
+        );
+        assert_eq!(
+            DueDateArg::Date(day(2026, 1, 1)).date(today),
+            day(2026, 1, 1)
+        );
+    }
+
+    #[test]
+    fn more_than_a_year_out_is_far_future() {
+        let today = day(2026, 10, 16);
+
+        assert!(!is_far_future(day(2027, 10, 16), today));
+        assert!(is_far_future(day(2027, 10, 17), today));
+        assert!(!is_far_future(day(2020, 1, 1), today));
+    }
+}
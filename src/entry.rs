@@ -1,4 +1,12 @@
-use crate::templating;
+use crate::{
+    helper,
+    project_name::{
+        validate_project_name,
+        ProjectNameError,
+    },
+    templating,
+    time_context::TimeContext,
+};
 use anyhow::{
     bail,
     Error,
@@ -9,6 +17,7 @@ use chrono::{
     Utc,
 };
 use core::ops::AddAssign;
+use log::warn;
 use serde::{
     Deserialize,
     Serialize,
@@ -36,47 +45,373 @@ pub(super) struct Metadata {
     pub(super) project: String,
     pub(super) finished: Option<DateTime<Utc>>,
     pub(super) uuid: Uuid,
+
+    /// Key used by webhooks to avoid creating the same entry multiple times.
+    #[serde(default)]
+    pub(super) dedup_key: Option<String>,
+
+    /// Counts the updates of the entry. Used to decide which row is the most
+    /// recent one when two rows have the same last change.
+    #[serde(default)]
+    pub(super) revision: u64,
+
+    /// How the entry was created. See `EntrySource`. Entries created before
+    /// this was recorded have no source.
+    #[serde(default)]
+    pub(super) source: Option<String>,
+
+    /// Number of lines of the entry text when the metadata was written so
+    /// listings can show that an entry has more than its summary without
+    /// reading the text. Missing for rows written before it was recorded
+    /// until the cleanup fills it in.
+    #[serde(default)]
+    pub(super) line_count: Option<usize>,
+
+    /// Name of the store the entry was moved to with `move --to_store`.
+    /// Entries that were moved to another store are not shown anymore.
+    #[serde(default)]
+    pub(super) moved_to: Option<String>,
+
+    /// When the entry was added. Unlike the start it is never changed. Rows
+    /// written before it was recorded have none until the cleanup fills it
+    /// in with the start. Use `Metadata::created` to read it.
+    #[serde(default)]
+    pub(super) created: Option<DateTime<Utc>>,
+
+    /// Tags like "urgent" the entry was added with. Rows written before
+    /// entries had tags have none.
+    #[serde(default, with = "crate::tags")]
+    pub(super) tags: BTreeSet<String>,
+
+    /// When the entry was deleted with `delete`. Deleted entries are not
+    /// shown anymore and their rows are dropped by the next cleanup.
+    #[serde(default)]
+    pub(super) deleted: Option<DateTime<Utc>>,
 }
 
 impl Default for Metadata {
     fn default() -> Self {
+        let now = Utc::now();
+
         Self {
-            last_change: Utc::now(),
+            last_change: now,
             project: "default".to_owned(),
-            started: Utc::now(),
+            started: now,
             finished: None,
             due: None,
             uuid: Uuid::new_v4(),
+            dedup_key: None,
+            revision: 0,
+            source: None,
+            line_count: None,
+            moved_to: None,
+            created: Some(now),
+            tags: BTreeSet::new(),
+            deleted: None,
+        }
+    }
+}
+
+/// How an entry was created.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum EntrySource {
+    Cli,
+    Web,
+    Api,
+    Import,
+
+    /// Created by the inbound webhook with the name.
+    Webhook(String),
+}
+
+impl EntrySource {
+    /// Check if the recorded source of an entry matches the filter. The
+    /// filter `webhook` matches all webhooks.
+    pub(super) fn matches(source: Option<&str>, filter: &str) -> bool {
+        match source {
+            None => false,
+            Some(source) => {
+                source == filter
+                    || source
+                        .strip_prefix(filter)
+                        .is_some_and(|rest| rest.starts_with(':'))
+            }
+        }
+    }
+}
+
+impl fmt::Display for EntrySource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EntrySource::Cli => write!(f, "cli"),
+            EntrySource::Web => write!(f, "web"),
+            EntrySource::Api => write!(f, "api"),
+            EntrySource::Import => write!(f, "import"),
+            EntrySource::Webhook(name) => write!(f, "webhook:{}", name),
+        }
+    }
+}
+
+/// Metadata fields that can be changed by hand when editing an entry.
+#[derive(Serialize, Deserialize, Debug)]
+pub(super) struct EditableMetadata {
+    pub(super) started: DateTime<Utc>,
+
+    #[serde(default)]
+    pub(super) due: Option<NaiveDate>,
+}
+
+impl From<&Metadata> for EditableMetadata {
+    fn from(metadata: &Metadata) -> Self {
+        Self {
+            started: metadata.started,
+            due: metadata.due,
         }
     }
 }
 
 impl Metadata {
+    /// Key that decides which of two metadata rows of the same entry is the
+    /// most recent one. Rows with the same last change are ordered by their
+    /// revision and then by all other fields so every machine picks the same
+    /// row.
+    pub(super) fn recency(&self) -> (DateTime<Utc>, u64, &Self) {
+        (self.last_change, self.revision, self)
+    }
+
     pub(super) fn is_active(&self) -> bool {
         self.finished.is_none()
     }
 
+    /// When the entry was added. Entries from before this was recorded were
+    /// added when they were started as the start could not be changed back
+    /// then.
+    pub(super) fn created(&self) -> DateTime<Utc> {
+        self.created.unwrap_or(self.started)
+    }
+
+    /// Start changing the metadata. The uuid, the start and everything that
+    /// is not changed explicitly is kept. The last change is set to now and
+    /// the revision is increased when the change is built. The creation time
+    /// can not be changed and is recorded for entries that do not have it
+    /// yet before the start can be changed.
+    pub(super) fn changed(&self) -> MetadataChange {
+        MetadataChange {
+            metadata: Metadata {
+                created: Some(self.created()),
+                ..self.clone()
+            },
+            project: None,
+            keep_last_change: false,
+        }
+    }
+
+    /// When the entry was last viewed or changed, whatever happened later.
+    pub(super) fn last_seen(&self, last_viewed: Option<DateTime<Utc>>) -> DateTime<Utc> {
+        last_viewed.map_or(self.last_change, |last_viewed| {
+            last_viewed.max(self.last_change)
+        })
+    }
+
     pub(super) fn is_done(&self) -> bool {
         self.finished.is_some()
     }
+
+    /// Days the entry is past its due date on the given day. `None` if the
+    /// entry has no due date or the day is not after it.
+    pub(super) fn days_overdue(&self, day: NaiveDate) -> Option<i64> {
+        let days = day.signed_duration_since(self.due?).num_days();
+
+        if days > 0 {
+            Some(days)
+        } else {
+            None
+        }
+    }
+
+    /// If the entry was finished on or before its due date. Finishing on the
+    /// due date counts as on time. `None` if the entry is not finished or has
+    /// no due date.
+    pub(super) fn finished_on_time(&self, time: &TimeContext) -> Option<bool> {
+        self.due?;
+        let finished = time.date(self.finished?);
+
+        Some(self.days_overdue(finished).is_none())
+    }
 }
 
-#[derive(Serialize, Deserialize, Debug, Ord, Eq, PartialOrd, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, Debug, Default, Ord, Eq, PartialOrd, PartialEq, Clone)]
 pub(super) struct Entry {
     pub(super) metadata: Metadata,
     pub(super) text: String,
 }
 
-impl Default for Entry {
-    fn default() -> Self {
-        Self {
-            metadata: Metadata::default(),
-            text: String::new(),
+/// Changes to the metadata of an existing entry. Created with
+/// `Metadata::changed`.
+#[derive(Debug)]
+pub(super) struct MetadataChange {
+    metadata: Metadata,
+
+    /// New project, validated when the change is built.
+    project: Option<String>,
+
+    /// Keep the last change of the metadata instead of setting it to now.
+    keep_last_change: bool,
+}
+
+impl MetadataChange {
+    pub(super) fn project(mut self, project: String) -> Self {
+        self.project = Some(project);
+        self
+    }
+
+    pub(super) fn due(mut self, due: Option<NaiveDate>) -> Self {
+        self.metadata.due = due;
+        self
+    }
+
+    pub(super) fn started(mut self, started: DateTime<Utc>) -> Self {
+        self.metadata.started = started;
+        self
+    }
+
+    /// Mark the entry as done now.
+    pub(super) fn finish(mut self) -> Self {
+        self.metadata.finished = Some(Utc::now());
+        self
+    }
+
+    /// Mark the entry as active again.
+    pub(super) fn reactivate(mut self) -> Self {
+        self.metadata.finished = None;
+        self
+    }
+
+    /// Mark the entry as deleted now.
+    pub(super) fn delete(mut self) -> Self {
+        self.metadata.deleted = Some(Utc::now());
+        self
+    }
+
+    /// Record that the entry was moved to the store with the name.
+    pub(super) fn moved_to(mut self, store: String) -> Self {
+        self.metadata.moved_to = Some(store);
+        self
+    }
+
+    pub(super) fn line_count(mut self, line_count: usize) -> Self {
+        self.metadata.line_count = Some(line_count);
+        self
+    }
+
+    /// Keep the last change for rows that only fill in what was not recorded
+    /// before so the entry does not look like it was just changed.
+    pub(super) fn keep_last_change(mut self) -> Self {
+        self.keep_last_change = true;
+        self
+    }
+
+    /// The changed metadata. The revision is always increased so the row
+    /// follows the row it was changed from, even when that row was written on
+    /// another machine and is not seen as a concurrent change.
+    pub(super) fn build(self) -> Result<Metadata, ProjectNameError> {
+        let mut metadata = self.metadata;
+
+        if let Some(project) = self.project {
+            validate_project_name(&project)?;
+            metadata.project = project;
         }
+
+        if !self.keep_last_change {
+            metadata.last_change = next_change(&metadata);
+        }
+        metadata.revision += 1;
+
+        Ok(metadata)
+    }
+}
+
+/// Time of a change that follows the given metadata row. This is now unless
+/// the clock is behind the last change of the row. Then the last change plus
+/// one millisecond is used so the new row still wins over the old one when
+/// the most recent row is picked.
+pub(super) fn next_change(previous: &Metadata) -> DateTime<Utc> {
+    let now = Utc::now();
+
+    if now > previous.last_change {
+        return now;
+    }
+
+    warn!(
+        "clock is behind the last change of entry {} ({} <= {}), check the system clock",
+        previous.uuid, now, previous.last_change
+    );
+
+    previous.last_change + chrono::Duration::milliseconds(1)
+}
+
+/// Builder for new entries. Created with `Entry::builder`.
+#[derive(Debug)]
+pub(super) struct EntryBuilder {
+    text: String,
+    project: String,
+    source: EntrySource,
+    due: Option<NaiveDate>,
+    dedup_key: Option<String>,
+    tags: BTreeSet<String>,
+}
+
+impl EntryBuilder {
+    pub(super) fn due(mut self, due: Option<NaiveDate>) -> Self {
+        self.due = due;
+        self
+    }
+
+    pub(super) fn dedup_key(mut self, dedup_key: Option<String>) -> Self {
+        self.dedup_key = dedup_key;
+        self
+    }
+
+    pub(super) fn tags(mut self, tags: BTreeSet<String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    /// Create the entry with a new uuid that is started now. Fails if the
+    /// project name is not valid.
+    pub(super) fn build(self) -> Result<Entry, ProjectNameError> {
+        validate_project_name(&self.project)?;
+
+        Ok(Entry {
+            metadata: Metadata {
+                project: self.project,
+                due: self.due,
+                dedup_key: self.dedup_key,
+                tags: self.tags,
+                source: Some(self.source.to_string()),
+                line_count: Some(helper::line_count(&self.text)),
+                ..Metadata::default()
+            },
+            text: self.text,
+        })
     }
 }
 
 impl Entry {
+    /// Start creating a new entry in the project. All entries should be
+    /// created through here so the project is validated and the source is
+    /// always recorded.
+    pub(super) fn builder(text: String, project: String, source: EntrySource) -> EntryBuilder {
+        EntryBuilder {
+            text,
+            project,
+            source,
+            due: None,
+            dedup_key: None,
+            tags: BTreeSet::new(),
+        }
+    }
+
     pub(super) fn is_active(&self) -> bool {
         self.metadata.is_active()
     }
@@ -85,21 +420,32 @@ impl Entry {
         self.metadata.is_done()
     }
 
+    /// Key for sorting entries by their due date, the earliest first.
+    /// Entries without a due date come last.
+    pub(super) fn due_order(&self) -> (bool, Option<NaiveDate>) {
+        (self.metadata.due.is_none(), self.metadata.due)
+    }
+
+    /// How long the entry exists.
     pub(super) fn age(&self) -> ::chrono::Duration {
+        Utc::now().signed_duration_since(self.metadata.created())
+    }
+
+    /// How long the entry is worked on.
+    pub(super) fn active_duration(&self) -> ::chrono::Duration {
         Utc::now().signed_duration_since(self.metadata.started)
     }
+
+    pub(super) fn line_count(&self) -> usize {
+        helper::line_count(&self.text)
+    }
 }
 
+/// Writes a single line summary of the entry text. The precision of the
+/// formatter (`{:.50}`) limits the width of the summary.
 impl fmt::Display for Entry {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let line = self
-            .text
-            .replace("\n", " ")
-            .chars()
-            .take(100)
-            .fold(String::new(), |acc, x| format!("{}{}", acc, x));
-
-        write!(f, "{}", line)
+        write!(f, "{}", helper::summarize(&self.text, f.precision()))
     }
 }
 
@@ -132,6 +478,16 @@ impl Entries {
         self.into_iter().filter(Entry::is_active).collect()
     }
 
+    /// Done entries with the most recently finished first. Done entries are
+    /// numbered in this order.
+    pub(super) fn get_done_by_finished(self) -> Vec<Entry> {
+        let mut done = self.into_iter().filter(Entry::is_done).collect::<Vec<_>>();
+
+        done.sort_by_key(|entry| std::cmp::Reverse(entry.metadata.finished));
+
+        done
+    }
+
     pub(super) fn entry_by_id(self, id: usize) -> Result<Entry, Error> {
         let active_entries: Entries = self.get_active();
 
@@ -144,8 +500,14 @@ impl Entries {
         Ok(entry)
     }
 
-    pub(super) fn is_empty(&self) -> bool {
-        self.entries.is_empty()
+    /// Id of the entry with the uuid in the list of active entries. This is
+    /// the id that `entry_by_id` resolves back to the entry.
+    pub(super) fn id_of(&self, uuid: &Uuid) -> Option<usize> {
+        self.entries
+            .iter()
+            .filter(|entry| entry.is_active())
+            .position(|entry| entry.metadata.uuid == *uuid)
+            .map(|index| index + 1)
     }
 
     pub(super) fn latest_entries(self) -> Self {
@@ -155,39 +517,68 @@ impl Entries {
             latest.insert(entry.metadata.uuid, entry);
         }
 
-        let entries = latest
-            .into_iter()
-            .map(|(_, entry)| entry)
-            .collect::<BTreeSet<Entry>>();
+        let entries = latest.into_values().collect::<BTreeSet<Entry>>();
 
         entries.into()
     }
 
-    pub(super) fn into_inner(self) -> BTreeSet<Entry> {
-        self.entries
+    /// Summary of the entries like how many are active and overdue.
+    pub(super) fn summary(&self, time: &TimeContext) -> EntriesSummary {
+        EntriesSummary::from_metadata(
+            self.entries.iter().map(|entry| &entry.metadata),
+            time.today(),
+        )
     }
-}
 
-impl fmt::Display for Entries {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    /// Render the entries grouped by project and state as an asciidoc
+    /// document. This is the document `print` writes.
+    pub(super) fn to_asciidoc(&self, time: &TimeContext) -> Result<String, tera::Error> {
         let mut active: BTreeMap<&str, BTreeSet<&Entry>> = BTreeMap::default();
         let mut done: BTreeMap<&str, BTreeSet<&Entry>> = BTreeMap::default();
 
+        // Days done entries were finished after their due date by uuid.
+        let mut late: BTreeMap<String, i64> = BTreeMap::default();
+
         for entry in &self.entries {
             if entry.metadata.finished.is_none() {
                 active
                     .entry(&entry.metadata.project)
-                    .or_insert_with(BTreeSet::default)
+                    .or_default()
                     .insert(entry);
             } else {
                 done.entry(&entry.metadata.project)
-                    .or_insert_with(BTreeSet::default)
+                    .or_default()
                     .insert(entry);
+
+                if let Some(days) = entry
+                    .metadata
+                    .finished
+                    .and_then(|finished| entry.metadata.days_overdue(time.date(finished)))
+                {
+                    late.insert(entry.metadata.uuid.to_string(), days);
+                }
             }
         }
 
+        let mut entries_by_project: BTreeMap<&str, Vec<&Metadata>> = BTreeMap::default();
+        for entry in &self.entries {
+            entries_by_project
+                .entry(&entry.metadata.project)
+                .or_default()
+                .push(&entry.metadata);
+        }
+
+        let today = time.today();
+        let summaries = entries_by_project
+            .into_iter()
+            .map(|(project, metadata)| (project, EntriesSummary::from_metadata(metadata, today)))
+            .collect::<BTreeMap<_, _>>();
+
         let mut context = Context::new();
         context.insert("active", &active);
+        context.insert("late", &late);
+        context.insert("summary", &self.summary(time));
+        context.insert("summaries", &summaries);
 
         if !done.is_empty() {
             context.insert("done", &done);
@@ -201,11 +592,18 @@ impl fmt::Display for Entries {
         .expect("can not compile entries.asciidoc template");
         tera.register_filter("single_line", templating::single_line);
         tera.register_filter("lines", templating::lines);
+        tera.register_filter("example_block", templating::example_block);
         tera.register_filter("format_duration_since", templating::format_duration_since);
         tera.register_filter("some_or_dash", templating::some_or_dash);
 
-        let rendered = tera
-            .render("entries.asciidoc", &context)
+        tera.render("entries.asciidoc", &context)
+    }
+}
+
+impl fmt::Display for Entries {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let rendered = self
+            .to_asciidoc(&TimeContext::new(None))
             .expect("can not render remplate for entries");
 
         write!(f, "{}", rendered)
@@ -244,6 +642,24 @@ pub(super) struct ProjectCount {
     pub(super) active_count: usize,
     pub(super) done_count: usize,
     pub(super) total_count: usize,
+
+    /// Active entries with a due date in the past.
+    pub(super) overdue_count: usize,
+
+    /// Active entries that are due in the next days.
+    pub(super) due_soon_count: usize,
+
+    /// Active entries that were not viewed or changed in a long time.
+    pub(super) forgotten_count: usize,
+
+    /// Most recent change of an entry in the project.
+    pub(super) last_activity: Option<DateTime<Utc>>,
+
+    /// Done entries with a due date that were finished on or before it.
+    pub(super) on_time_count: usize,
+
+    /// Done entries that were finished after their due date.
+    pub(super) late_count: usize,
 }
 
 impl Add for ProjectCount {
@@ -255,17 +671,558 @@ impl Add for ProjectCount {
             active_count: self.active_count + other.active_count,
             done_count: self.done_count + other.done_count,
             total_count: self.total_count + other.total_count,
+            overdue_count: self.overdue_count + other.overdue_count,
+            due_soon_count: self.due_soon_count + other.due_soon_count,
+            forgotten_count: self.forgotten_count + other.forgotten_count,
+            last_activity: self.last_activity.max(other.last_activity),
+            on_time_count: self.on_time_count + other.on_time_count,
+            late_count: self.late_count + other.late_count,
         }
     }
 }
 
 impl AddAssign for ProjectCount {
     fn add_assign(&mut self, other: ProjectCount) {
-        *self = Self {
-            project: other.project,
-            active_count: self.active_count + other.active_count,
-            done_count: self.done_count + other.done_count,
-            total_count: self.total_count + other.total_count,
+        *self = std::mem::take(self) + other
+    }
+}
+
+impl ProjectCount {
+    /// A project stays visible as long as it has entries that are not done.
+    /// This is based on the done count instead of the active count so entries
+    /// in states other than active and done keep their project visible.
+    pub(super) fn has_open_entries(&self) -> bool {
+        self.total_count > self.done_count
+    }
+
+    /// Share of the done entries with a due date that were finished on time.
+    /// `None` if no done entry had a due date.
+    pub(super) fn on_time_ratio(&self) -> Option<f64> {
+        let finished_with_due = self.on_time_count + self.late_count;
+        if finished_with_due == 0 {
+            return None;
+        }
+
+        Some(self.on_time_count as f64 / finished_with_due as f64)
+    }
+}
+
+/// Summary of a set of entries for headers like "12 active, 3 overdue, oldest
+/// 45d". Only uses the metadata so it can be computed without reading texts.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub(super) struct EntriesSummary {
+    pub(super) active_count: usize,
+    pub(super) done_count: usize,
+
+    /// Active entries with a due date in the past.
+    pub(super) overdue_count: usize,
+
+    /// Start of the oldest active entry.
+    pub(super) oldest_started: Option<DateTime<Utc>>,
+
+    /// Earliest due date of the active entries.
+    pub(super) nearest_due: Option<NaiveDate>,
+}
+
+impl EntriesSummary {
+    pub(super) fn from_metadata<'a, I>(metadata: I, today: NaiveDate) -> Self
+    where
+        I: IntoIterator<Item = &'a Metadata>,
+    {
+        let mut summary = Self::default();
+
+        for metadata in metadata {
+            if !metadata.is_active() {
+                summary.done_count += 1;
+                continue;
+            }
+
+            summary.active_count += 1;
+
+            if metadata.days_overdue(today).is_some() {
+                summary.overdue_count += 1;
+            }
+
+            summary.oldest_started = Some(match summary.oldest_started {
+                Some(oldest) => oldest.min(metadata.started),
+                None => metadata.started,
+            });
+
+            summary.nearest_due = match (summary.nearest_due, metadata.due) {
+                (Some(nearest), Some(due)) => Some(nearest.min(due)),
+                (nearest, due) => nearest.or(due),
+            };
+        }
+
+        summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(text: &str) -> Entry {
+        Entry {
+            metadata: Metadata::default(),
+            text: text.to_string(),
+        }
+    }
+
+    fn summary_entry(started: &str, due: Option<NaiveDate>, finished: Option<&str>) -> Metadata {
+        Metadata {
+            started: started.parse().unwrap(),
+            due,
+            finished: finished.map(|finished| finished.parse().unwrap()),
+            ..Metadata::default()
+        }
+    }
+
+    #[test]
+    fn summary_of_no_entries_is_empty() {
+        let today = NaiveDate::from_ymd_opt(2024, 5, 10).unwrap();
+
+        assert_eq!(
+            EntriesSummary::from_metadata(&[], today),
+            EntriesSummary::default()
+        );
+    }
+
+    #[test]
+    fn summary_of_done_entries_only_counts_them() {
+        let today = NaiveDate::from_ymd_opt(2024, 5, 10).unwrap();
+        let metadata = [
+            summary_entry(
+                "2024-01-01T00:00:00Z",
+                NaiveDate::from_ymd_opt(2024, 2, 1),
+                Some("2024-03-01T00:00:00Z"),
+            ),
+            summary_entry("2024-04-01T00:00:00Z", None, Some("2024-04-02T00:00:00Z")),
+        ];
+
+        assert_eq!(
+            EntriesSummary::from_metadata(&metadata, today),
+            EntriesSummary {
+                done_count: 2,
+                ..EntriesSummary::default()
+            }
+        );
+    }
+
+    #[test]
+    fn summary_only_looks_at_active_entries_for_dates() {
+        let today = NaiveDate::from_ymd_opt(2024, 5, 10).unwrap();
+        let metadata = [
+            summary_entry(
+                "2024-05-01T00:00:00Z",
+                NaiveDate::from_ymd_opt(2024, 5, 9),
+                None,
+            ),
+            summary_entry("2024-03-01T00:00:00Z", None, None),
+            summary_entry(
+                "2024-04-01T00:00:00Z",
+                NaiveDate::from_ymd_opt(2024, 5, 10),
+                None,
+            ),
+            summary_entry(
+                "2023-01-01T00:00:00Z",
+                NaiveDate::from_ymd_opt(2024, 1, 1),
+                Some("2024-01-01T00:00:00Z"),
+            ),
+        ];
+
+        assert_eq!(
+            EntriesSummary::from_metadata(&metadata, today),
+            EntriesSummary {
+                active_count: 3,
+                done_count: 1,
+                overdue_count: 1,
+                oldest_started: Some("2024-03-01T00:00:00Z".parse().unwrap()),
+                nearest_due: NaiveDate::from_ymd_opt(2024, 5, 9),
+            }
+        );
+    }
+
+    #[test]
+    fn display_is_a_single_line() {
+        let entry = entry("= Title\n\nsome   text\nmore text");
+
+        assert_eq!(format!("{}", entry), "Title some text more text");
+    }
+
+    #[test]
+    fn display_precision_limits_the_width() {
+        let entry = entry(&"word ".repeat(100));
+
+        let summary = format!("{:.*}", helper::PROMPT_SUMMARY_WIDTH, entry);
+        assert!(summary.chars().count() <= helper::PROMPT_SUMMARY_WIDTH);
+        assert!(summary.starts_with("word word"));
+
+        assert!(format!("{}", entry).chars().count() > helper::PROMPT_SUMMARY_WIDTH);
+    }
+
+    #[test]
+    fn builder_records_project_source_and_line_count() {
+        let entry = Entry::builder(
+            "= Title\n\nline".to_string(),
+            "work".to_string(),
+            EntrySource::Webhook("github".to_string()),
+        )
+        .due(NaiveDate::from_ymd_opt(2026, 12, 1))
+        .dedup_key(Some("issue-1".to_string()))
+        .build()
+        .unwrap();
+
+        assert_eq!(entry.metadata.project, "work");
+        assert_eq!(entry.metadata.source.as_deref(), Some("webhook:github"));
+        assert_eq!(entry.metadata.line_count, Some(3));
+        assert_eq!(entry.metadata.due, NaiveDate::from_ymd_opt(2026, 12, 1));
+        assert_eq!(entry.metadata.dedup_key.as_deref(), Some("issue-1"));
+        assert_eq!(entry.metadata.created, Some(entry.metadata.started));
+        assert!(entry.is_active());
+    }
+
+    #[test]
+    fn builder_creates_a_new_uuid_for_every_entry() {
+        let build = || {
+            Entry::builder("text".to_string(), "work".to_string(), EntrySource::Cli)
+                .build()
+                .unwrap()
+        };
+
+        assert_ne!(build().metadata.uuid, build().metadata.uuid);
+    }
+
+    #[test]
+    fn builder_rejects_invalid_project() {
+        let result =
+            Entry::builder("text".to_string(), "a/b".to_string(), EntrySource::Cli).build();
+
+        assert_eq!(result.unwrap_err(), ProjectNameError::PathSeparator);
+    }
+
+    #[test]
+    fn changed_keeps_uuid_start_and_creation() {
+        let metadata = Metadata {
+            due: NaiveDate::from_ymd_opt(2026, 12, 1),
+            ..Metadata::default()
+        };
+
+        let changed = metadata
+            .changed()
+            .project("other".to_string())
+            .build()
+            .unwrap();
+
+        assert_eq!(changed.uuid, metadata.uuid);
+        assert_eq!(changed.started, metadata.started);
+        assert_eq!(changed.created, metadata.created);
+        assert_eq!(changed.due, metadata.due);
+        assert_eq!(changed.project, "other");
+        assert!(changed.last_change >= metadata.last_change);
+    }
+
+    #[test]
+    fn changed_bumps_last_change_past_a_future_row() {
+        let metadata = Metadata {
+            last_change: Utc::now() + chrono::Duration::hours(1),
+            ..Metadata::default()
+        };
+
+        let changed = metadata.changed().build().unwrap();
+
+        assert!(changed.last_change > metadata.last_change);
+        assert!(changed.recency() > metadata.recency());
+    }
+
+    #[test]
+    fn next_change_is_now_when_the_clock_is_ahead() {
+        let before = Utc::now();
+        let metadata = Metadata {
+            last_change: before - chrono::Duration::hours(1),
+            ..Metadata::default()
+        };
+
+        let next = next_change(&metadata);
+
+        assert!(next >= before);
+        assert!(next <= Utc::now());
+    }
+
+    #[test]
+    fn next_change_follows_a_row_from_the_future() {
+        let metadata = Metadata {
+            last_change: Utc::now() + chrono::Duration::hours(1),
+            ..Metadata::default()
+        };
+
+        assert_eq!(
+            next_change(&metadata),
+            metadata.last_change + chrono::Duration::milliseconds(1)
+        );
+    }
+
+    #[test]
+    fn changed_records_creation_before_changing_the_start() {
+        let metadata = Metadata {
+            created: None,
+            ..Metadata::default()
+        };
+        let started = metadata.started - chrono::Duration::days(2);
+
+        let changed = metadata.changed().started(started).build().unwrap();
+
+        assert_eq!(changed.started, started);
+        assert_eq!(changed.created, Some(metadata.started));
+    }
+
+    #[test]
+    fn every_change_follows_the_previous_row() {
+        let metadata = Metadata {
+            last_change: Utc::now() - chrono::Duration::minutes(1),
+            revision: 3,
+            ..Metadata::default()
+        };
+        type Change = fn(MetadataChange) -> MetadataChange;
+
+        let changes: Vec<(&str, Change)> = vec![
+            ("nothing", |change| change),
+            ("project", |change| change.project("work".to_string())),
+            ("due", |change| {
+                change.due(NaiveDate::from_ymd_opt(2026, 12, 1))
+            }),
+            ("finish", MetadataChange::finish),
+            ("reactivate", MetadataChange::reactivate),
+            ("delete", MetadataChange::delete),
+            ("moved_to", |change| change.moved_to("other".to_string())),
+            ("line_count", |change| change.line_count(7)),
+        ];
+
+        for (name, change) in changes {
+            let changed = change(metadata.changed()).build().unwrap();
+
+            assert_eq!(changed.uuid, metadata.uuid, "{}", name);
+            assert_eq!(changed.started, metadata.started, "{}", name);
+            assert_eq!(changed.created, metadata.created, "{}", name);
+            assert_eq!(changed.revision, 4, "{}", name);
+            assert!(changed.last_change > metadata.last_change, "{}", name);
+        }
+    }
+
+    #[test]
+    fn kept_last_change_still_increases_the_revision() {
+        let metadata = Metadata {
+            line_count: None,
+            ..Metadata::default()
+        };
+
+        let changed = metadata
+            .changed()
+            .line_count(2)
+            .keep_last_change()
+            .build()
+            .unwrap();
+
+        assert_eq!(changed.last_change, metadata.last_change);
+        assert_eq!(changed.revision, metadata.revision + 1);
+        assert_eq!(changed.line_count, Some(2));
+    }
+
+    #[test]
+    fn changed_rejects_invalid_project() {
+        let result = Metadata::default()
+            .changed()
+            .project("..".to_string())
+            .build();
+
+        assert_eq!(result.unwrap_err(), ProjectNameError::RelativePath);
+    }
+
+    #[test]
+    fn changed_finishes_reactivates_and_deletes() {
+        let metadata = Metadata::default();
+
+        let finished = metadata.changed().finish().build().unwrap();
+        assert!(finished.is_done());
+
+        let reactivated = finished.changed().reactivate().build().unwrap();
+        assert!(reactivated.is_active());
+
+        let deleted = reactivated.changed().delete().build().unwrap();
+        assert!(deleted.deleted.is_some());
+
+        let moved = metadata
+            .changed()
+            .moved_to("other".to_string())
+            .build()
+            .unwrap();
+        assert_eq!(moved.moved_to.as_deref(), Some("other"));
+    }
+
+    #[test]
+    fn asciidoc_has_a_section_per_project() {
+        let entry = |text: &str, project: &str| {
+            Entry::builder(text.to_string(), project.to_string(), EntrySource::Cli)
+                .build()
+                .unwrap()
+        };
+        let mut finished = entry("finished entry", "work");
+        finished.metadata.finished = Some(Utc::now());
+
+        let entries = vec![
+            entry("home entry", "home"),
+            entry("work entry", "work"),
+            finished,
+        ]
+        .into_iter()
+        .collect::<Entries>();
+
+        let asciidoc = entries.to_asciidoc(&TimeContext::new(None)).unwrap();
+        let position = |line: &str| {
+            asciidoc
+                .lines()
+                .position(|candidate| candidate == line)
+                .unwrap_or_else(|| panic!("{:?} missing in\n{}", line, asciidoc))
+        };
+
+        assert!(position("== Active") < position("=== home"));
+        assert!(position("=== home") < position("==== home entry"));
+        assert!(position("==== home entry") < position("=== work"));
+        assert!(position("=== work") < position("==== work entry"));
+        assert!(position("==== work entry") < position("== Done"));
+        assert!(position("== Done") < position("==== finished entry"));
+        assert_eq!(asciidoc.matches("\n=== work\n").count(), 2);
+    }
+
+    #[test]
+    fn asciidoc_keeps_template_syntax_of_the_text() {
+        let text = "{{ 7*7 }}\n{% raw %}\ninclude::/etc/passwd[]\n====\nafter";
+        let entries = vec![
+            Entry::builder(text.to_string(), "work".to_string(), EntrySource::Cli)
+                .build()
+                .unwrap(),
+        ]
+        .into_iter()
+        .collect::<Entries>();
+
+        let asciidoc = entries.to_asciidoc(&TimeContext::new(None)).unwrap();
+
+        assert!(
+            asciidoc.contains("{{ 7*7 }}\n\n{% raw %}\n\n"),
+            "{}",
+            asciidoc
+        );
+        assert!(!asciidoc.contains("\n49\n"), "{}", asciidoc);
+        assert!(
+            asciidoc.contains("\n\\include::/etc/passwd[]\n"),
+            "{}",
+            asciidoc
+        );
+        assert!(
+            asciidoc.contains("=====\n{{ 7*7 }}") && asciidoc.contains("after\n====="),
+            "{}",
+            asciidoc
+        );
+    }
+
+    #[test]
+    fn entries_in_other_states_keep_a_project_open() {
+        // Entries that are neither active nor done, like snoozed ones, only
+        // show up in the total count.
+        let snoozed_only = ProjectCount {
+            project: "someday".to_string(),
+            total_count: 2,
+            ..ProjectCount::default()
+        };
+        let done_only = ProjectCount {
+            project: "finished".to_string(),
+            done_count: 2,
+            total_count: 2,
+            ..ProjectCount::default()
+        };
+        let active = ProjectCount {
+            project: "work".to_string(),
+            active_count: 1,
+            done_count: 1,
+            total_count: 2,
+            ..ProjectCount::default()
+        };
+
+        assert!(snoozed_only.has_open_entries());
+        assert!(!done_only.has_open_entries());
+        assert!(active.has_open_entries());
+        assert!(!ProjectCount::default().has_open_entries());
+    }
+
+    fn due_and_finished(due: Option<(i32, u32, u32)>, finished: Option<&str>) -> Metadata {
+        Metadata {
+            due: due.and_then(|(year, month, day)| NaiveDate::from_ymd_opt(year, month, day)),
+            finished: finished.map(|finished| finished.parse().unwrap()),
+            ..Metadata::default()
         }
     }
+
+    #[test]
+    fn finishing_on_the_due_date_is_on_time() {
+        let time = TimeContext::new(Some(chrono_tz::Tz::UTC));
+
+        let same_day = due_and_finished(Some((2024, 6, 1)), Some("2024-06-01T23:59:00Z"));
+        let day_before = due_and_finished(Some((2024, 6, 1)), Some("2024-05-20T12:00:00Z"));
+        let day_after = due_and_finished(Some((2024, 6, 1)), Some("2024-06-02T00:00:00Z"));
+
+        assert_eq!(same_day.finished_on_time(&time), Some(true));
+        assert_eq!(day_before.finished_on_time(&time), Some(true));
+        assert_eq!(day_after.finished_on_time(&time), Some(false));
+    }
+
+    #[test]
+    fn finishing_late_uses_the_local_day() {
+        let finished = due_and_finished(Some((2024, 6, 1)), Some("2024-06-01T12:00:00Z"));
+
+        assert_eq!(
+            finished.finished_on_time(&TimeContext::new(Some(chrono_tz::Tz::UTC))),
+            Some(true)
+        );
+        assert_eq!(
+            finished.finished_on_time(&TimeContext::new(Some(chrono_tz::Tz::Pacific__Tongatapu))),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn on_time_needs_a_due_date_and_a_finish() {
+        let time = TimeContext::new(Some(chrono_tz::Tz::UTC));
+
+        assert_eq!(
+            due_and_finished(None, Some("2024-06-01T12:00:00Z")).finished_on_time(&time),
+            None
+        );
+        assert_eq!(
+            due_and_finished(Some((2024, 6, 1)), None).finished_on_time(&time),
+            None
+        );
+    }
+
+    #[test]
+    fn days_overdue_after_the_due_date() {
+        let due = due_and_finished(Some((2024, 6, 1)), None);
+        let day = |day| NaiveDate::from_ymd_opt(2024, 6, day).unwrap();
+
+        assert_eq!(due.days_overdue(day(1)), None);
+        assert_eq!(due.days_overdue(day(6)), Some(5));
+        assert_eq!(due_and_finished(None, None).days_overdue(day(6)), None);
+    }
+
+    #[test]
+    fn on_time_ratio_without_due_dates_is_none() {
+        let count = |on_time_count, late_count| ProjectCount {
+            on_time_count,
+            late_count,
+            ..ProjectCount::default()
+        };
+
+        assert_eq!(count(0, 0).on_time_ratio(), None);
+        assert_eq!(count(3, 1).on_time_ratio(), Some(0.75));
+        assert_eq!(count(0, 2).on_time_ratio(), Some(0.0));
+    }
 }
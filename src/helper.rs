@@ -1,5 +1,10 @@
+use crate::{
+    interactivity::Interactivity,
+    locale::Locale,
+};
 use anyhow::{
     bail,
+    format_err,
     Context,
     Error,
 };
@@ -7,18 +12,76 @@ use chrono::{
     Duration,
     NaiveDate,
 };
-use std::fs::File;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    env,
+    fs::{
+        self,
+        File,
+    },
+    io::{
+        self,
+        BufRead,
+        Write,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+    process::Command,
+};
 use tempfile::tempdir;
 use text_io::read;
+use unicode_normalization::UnicodeNormalization;
+use uuid::Uuid;
+
+/// Longest slug of an entry in characters.
+const MAX_SLUG_LENGTH: usize = 60;
+
+/// Slug of entries whose first line has no letters or digits.
+const EMPTY_SLUG: &str = "entry";
+
+/// How often the confirmation text can be typed wrong before giving up.
+const CONFIRM_TEXT_ATTEMPTS: usize = 3;
+
+/// Characters of the uuid that can be typed instead of the first word of an
+/// entry to confirm finishing it.
+const SHORT_ID_LENGTH: usize = 8;
+
+/// Characters of the entry text shown in prompts so long entries do not push
+/// the question out of view.
+pub(super) const PROMPT_SUMMARY_WIDTH: usize = 100;
+
+/// How finishing an entry is confirmed.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum ConfirmDone {
+    /// Answer yes or no.
+    #[default]
+    Prompt,
+
+    /// Retype the first word of the entry or the start of its uuid, which
+    /// catches picking the wrong id.
+    Text,
+
+    /// Do not ask, same as always passing --yes.
+    Off,
+}
 
 pub(super) fn confirm(message: &str, default: bool) -> Result<bool, Error> {
+    confirm_from(&mut io::stdin().lock(), message, default)
+}
+
+/// Like `confirm` but reads the answer from the input.
+fn confirm_from<R: BufRead>(input: &mut R, message: &str, default: bool) -> Result<bool, Error> {
     let default_text = if default { "Y/n" } else { "N/y" };
 
     println!("{}\n({}): ", message, default_text);
 
-    // TODO: Remove this when upstream is fixed
-    #[allow(clippy::try_err)]
-    let input: String = read!("{}\n");
+    let input = read_answer(input)?;
 
     match input.trim().to_uppercase().as_str() {
         "Y" | "YES" => Ok(true),
@@ -27,15 +90,195 @@ pub(super) fn confirm(message: &str, default: bool) -> Result<bool, Error> {
     }
 }
 
-pub(super) fn string_from_editor(prepoluate: Option<&str>) -> Result<String, Error> {
-    use std::{
-        env,
-        io::Write,
-        process::Command,
-    };
+/// Ask for confirmation before finishing the entry as configured. Fails when
+/// there is nobody to answer.
+pub(super) fn confirm_done(
+    mode: ConfirmDone,
+    interactivity: Interactivity,
+    message: &str,
+    text: &str,
+    uuid: Uuid,
+) -> Result<bool, Error> {
+    if mode == ConfirmDone::Off {
+        return Ok(true);
+    }
 
-    let tmpdir = tempdir().context("can not create tempdir")?;
-    let tmppath = tmpdir.path().join("todo.asciidoc");
+    interactivity.check_prompt("pass --yes to skip it")?;
+
+    let mut input = io::stdin().lock();
+
+    match mode {
+        ConfirmDone::Prompt => confirm_from(&mut input, message, false),
+        ConfirmDone::Text => confirm_text(&mut input, message, text, uuid),
+        ConfirmDone::Off => unreachable!(),
+    }
+}
+
+/// Read the answer to a prompt up to the end of the line.
+fn read_answer<R: BufRead>(input: &mut R) -> Result<String, Error> {
+    let mut answer = String::new();
+    input
+        .read_line(&mut answer)
+        .context("can not read answer")?;
+
+    Ok(answer.trim_end_matches(&['\r', '\n'][..]).to_string())
+}
+
+/// Let the user retype the first word of the text or the start of the uuid.
+/// Wrong input can be corrected a few times, empty input cancels.
+fn confirm_text<R: BufRead>(
+    input: &mut R,
+    message: &str,
+    text: &str,
+    uuid: Uuid,
+) -> Result<bool, Error> {
+    let short_id = uuid.to_string()[..SHORT_ID_LENGTH].to_string();
+    let word = first_word(text);
+
+    println!("{}", message);
+
+    for attempt in 1..=CONFIRM_TEXT_ATTEMPTS {
+        // The word is not shown as reading it in the entry is the point.
+        match word {
+            Some(_) => println!(
+                "type the first word of the entry or its short id ({}) to confirm: ",
+                short_id
+            ),
+            None => println!("type the short id of the entry ({}) to confirm: ", short_id),
+        }
+
+        let answer = read_answer(input)?.trim().to_lowercase();
+
+        if answer.is_empty() {
+            return Ok(false);
+        }
+
+        if answer == short_id || word.as_ref() == Some(&answer) {
+            return Ok(true);
+        }
+
+        if attempt < CONFIRM_TEXT_ATTEMPTS {
+            println!(
+                "{} does not match, try again or leave empty to cancel",
+                answer
+            );
+        }
+    }
+
+    Ok(false)
+}
+
+/// First word of the first line in lowercase without surrounding punctuation.
+fn first_word(text: &str) -> Option<String> {
+    first_line(text)
+        .split_whitespace()
+        .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()))
+        .find(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+/// Command that runs the command line with the shell of the system.
+#[cfg(unix)]
+pub(super) fn shell_command(command_line: &str) -> Command {
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(command_line);
+    command
+}
+
+/// Command that runs the command line with the shell of the system.
+#[cfg(not(unix))]
+pub(super) fn shell_command(command_line: &str) -> Command {
+    let mut command = Command::new("cmd");
+    command.arg("/C").arg(command_line);
+    command
+}
+
+/// Let the user pick one of the options by its number. Returns the index of
+/// the picked option.
+pub(super) fn choose(message: &str, options: &[String]) -> Result<usize, Error> {
+    println!("{}", message);
+    for (index, option) in options.iter().enumerate() {
+        println!("  {}) {}", index + 1, option);
+    }
+    println!("(1-{}): ", options.len());
+
+    // TODO: Remove this when upstream is fixed
+    #[allow(clippy::try_err)]
+    let input: String = read!("{}\n");
+
+    match input.trim().parse::<usize>() {
+        Ok(number) if number >= 1 && number <= options.len() => Ok(number - 1),
+        _ => bail!("do not know what to do with {}", input),
+    }
+}
+
+/// Ask for a line of text. Returns the answer without surrounding
+/// whitespace.
+pub(super) fn ask(message: &str) -> Result<String, Error> {
+    println!("{}", message);
+
+    Ok(read_answer(&mut io::stdin().lock())?.trim().to_string())
+}
+
+/// Name of the candidate that is closest to the input, for suggesting what
+/// might have been meant when the input contains a typo. Only candidates that
+/// are at most a third of the input length (and at least one edit) away are
+/// suggested.
+pub(super) fn closest_match<'a>(input: &str, candidates: &'a [String]) -> Option<&'a str> {
+    let limit = (input.chars().count() / 3).max(1);
+
+    candidates
+        .iter()
+        .filter(|candidate| candidate.as_str() != input)
+        .map(|candidate| (edit_distance(input, candidate), candidate))
+        .filter(|(distance, _)| *distance <= limit)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate.as_str())
+}
+
+/// Number of insertions, deletions, substitutions and transpositions of
+/// neighbouring characters that turn one string into the other. Case is
+/// ignored.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.to_lowercase().chars().collect::<Vec<_>>();
+    let b = b.to_lowercase().chars().collect::<Vec<_>>();
+
+    // Distances of the last three rows.
+    let mut before = vec![0; b.len() + 1];
+    let mut previous = (0..=b.len()).collect::<Vec<_>>();
+    let mut current = vec![0; b.len() + 1];
+
+    for i in 1..=a.len() {
+        current[0] = i;
+
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+
+            current[j] = (previous[j] + 1)
+                .min(current[j - 1] + 1)
+                .min(previous[j - 1] + cost);
+
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                current[j] = current[j].min(before[j - 2] + 1);
+            }
+        }
+
+        std::mem::swap(&mut before, &mut previous);
+        std::mem::swap(&mut previous, &mut current);
+    }
+
+    previous[b.len()]
+}
+
+/// Let the user write a text with the editor from `$VISUAL` or `$EDITOR`. The
+/// editor starts with the prepopulated text. If the editor fails or the file
+/// is gone after it exits the user can edit again, starting with what was
+/// saved before the failure if the file is still there. Without anybody to
+/// answer the error is returned right away.
+pub(super) fn string_from_editor(
+    prepoluate: Option<&str>,
+    interactivity: Interactivity,
+) -> Result<String, Error> {
     let editor = {
         match env::var("VISUAL") {
             Ok(editor) => editor,
@@ -48,26 +291,320 @@ pub(super) fn string_from_editor(prepoluate: Option<&str>) -> Result<String, Err
         }
     };
 
-    if let Some(content) = prepoluate {
-        let mut file = File::create(tmppath.display().to_string())
-            .context("can not open tmp editor file to prepoluate with string")?;
+    string_from_editor_command(&editor, prepoluate, interactivity)
+}
+
+/// Like `string_from_editor` but with the given editor command.
+pub(super) fn string_from_editor_command(
+    editor: &str,
+    prepoluate: Option<&str>,
+    interactivity: Interactivity,
+) -> Result<String, Error> {
+    let mut buffer = prepoluate.unwrap_or_default().to_string();
+
+    loop {
+        let (result, saved) = run_editor(editor, &buffer)?;
+
+        let err = match result {
+            Ok(()) => match saved {
+                Some(text) => return Ok(text),
+                None => format_err!("the file of the editor was removed while editing"),
+            },
+            Err(err) => err,
+        };
+
+        if !interactivity.can_prompt() {
+            return Err(err);
+        }
+
+        println!("{:#}", err);
+
+        if !confirm("do you want to edit again?", true)? {
+            return Err(err);
+        }
 
-        file.write_all(content.as_bytes())
-            .context("can not prepoluate editor tmp file")?;
+        if let Some(saved) = saved {
+            buffer = saved;
+        }
     }
+}
 
-    let mut editor_command = Command::new(editor);
-    editor_command.arg(tmppath.display().to_string());
+/// Run the editor on a temporary file with the given text. Returns if the
+/// editor exited successfully together with the content of the file if it
+/// still exists.
+fn run_editor(editor: &str, text: &str) -> Result<(Result<(), Error>, Option<String>), Error> {
+    let tmpdir = tempdir().context("can not create tempdir")?;
+    let tmppath = tmpdir.path().join("todo.asciidoc");
 
-    editor_command
+    // Always create the file so a file that is missing after the editor exits
+    // can be told apart from one that was never written.
+    let mut file = File::create(&tmppath).context("can not create tmp editor file")?;
+    file.write_all(text.as_bytes())
+        .context("can not prepoluate editor tmp file")?;
+    drop(file);
+
+    let status = Command::new(editor)
+        .arg(tmppath.display().to_string())
         .spawn()
         .context("couldn not launch editor")?
         .wait()
         .context("problem while running editor")?;
 
-    let string = std::fs::read_to_string(tmppath).context("can not read tmpfile to string")?;
+    let saved = if tmppath.exists() {
+        Some(fs::read_to_string(&tmppath).context("can not read tmpfile to string")?)
+    } else {
+        None
+    };
+
+    let result = if status.success() {
+        Ok(())
+    } else {
+        Err(format_err!("editor exited with {}", status))
+    };
+
+    Ok((result, saved))
+}
+
+/// Split text into one entry per non-empty line.
+pub(super) fn split_lines(text: &str) -> Vec<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Split text into one entry per asciidoc section. A section starts with a
+/// `== ` heading and contains everything up to the next one. Text before the
+/// first heading becomes its own entry if it is not empty.
+pub(super) fn split_sections(text: &str) -> Vec<String> {
+    let mut sections = Vec::new();
+    let mut current: Vec<&str> = Vec::new();
+
+    for line in text.lines() {
+        if line.starts_with("== ") && !current.is_empty() {
+            sections.push(current.join("\n"));
+            current.clear();
+        }
+
+        current.push(line.trim_end());
+    }
+
+    sections.push(current.join("\n"));
+
+    sections
+        .into_iter()
+        .map(|section| section.trim().to_string())
+        .filter(|section| !section.is_empty())
+        .collect()
+}
+
+/// Number of lines of the text of an entry.
+pub(super) fn line_count(text: &str) -> usize {
+    text.lines().count()
+}
+
+/// Summarize the text of an entry into a single line. Leading asciidoc
+/// attribute lines and heading markers are removed and whitespace is collapsed.
+/// If a width is given the summary is truncated at a word boundary and an
+/// ellipsis is appended.
+pub(super) fn summarize(text: &str, width: Option<usize>) -> String {
+    let summary = text
+        .lines()
+        .map(str::trim)
+        .skip_while(|line| line.is_empty() || is_asciidoc_attribute_line(line))
+        .map(strip_asciidoc_heading)
+        .flat_map(str::split_whitespace)
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    match width {
+        Some(width) => truncate_words(&summary, width),
+        None => summary,
+    }
+}
+
+/// First line of the text that is not an asciidoc attribute with heading
+/// markers removed.
+pub(super) fn first_line(text: &str) -> String {
+    text.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !is_asciidoc_attribute_line(line))
+        .map(strip_asciidoc_heading)
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn is_asciidoc_attribute_line(line: &str) -> bool {
+    // Document attributes like ":toc: right" or ":!numbered:".
+    let is_document_attribute = line.starts_with(':')
+        && line[1..]
+            .find(':')
+            .map(|end| end > 0 && !line[1..=end].contains(char::is_whitespace))
+            .unwrap_or(false);
+
+    // Block attributes like "[source,rust]" or "[NOTE]".
+    let is_block_attribute = line.starts_with('[') && line.ends_with(']');
+
+    is_document_attribute || is_block_attribute
+}
+
+fn strip_asciidoc_heading(line: &str) -> &str {
+    let stripped = line.trim_start_matches('=');
 
-    Ok(string)
+    if stripped.len() != line.len() && stripped.starts_with(' ') {
+        stripped.trim_start()
+    } else {
+        line
+    }
+}
+
+fn truncate_words(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+
+    // Leave room for the ellipsis.
+    let limit = width.saturating_sub(1);
+    let cut = text
+        .char_indices()
+        .nth(limit)
+        .map(|(index, _)| index)
+        .unwrap_or_else(|| text.len());
+
+    // Prefer cutting at the last word boundary. A single word that is longer
+    // than the width has to be cut in the middle.
+    let head = &text[..cut];
+    let truncated = if text[cut..].starts_with(' ') {
+        head
+    } else {
+        match head.rfind(' ') {
+            Some(index) if index > 0 => &head[..index],
+            _ => head,
+        }
+    };
+
+    format!("{}…", truncated.trim_end())
+}
+
+/// Readable part of the permalink of an entry made from the first line of its
+/// text. Letters and digits are lowercased, everything else becomes a single
+/// hyphen and the slug is cut at a hyphen after `MAX_SLUG_LENGTH` characters.
+/// Letters outside of ascii are kept and have to be percent encoded in urls.
+pub(super) fn slug(text: &str) -> String {
+    let mut slug = String::new();
+
+    for character in first_line(text).nfc().flat_map(char::to_lowercase) {
+        if character.is_alphanumeric() {
+            slug.push(character);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+
+    if let Some((cut, _)) = slug.char_indices().nth(MAX_SLUG_LENGTH) {
+        let head = &slug[..cut];
+
+        let end = if slug[cut..].starts_with('-') {
+            cut
+        } else {
+            match head.rfind('-') {
+                Some(index) if index > 0 => index,
+                _ => cut,
+            }
+        };
+
+        slug.truncate(end);
+    }
+
+    let slug = slug.trim_end_matches('-');
+
+    if slug.is_empty() {
+        EMPTY_SLUG.to_string()
+    } else {
+        slug.to_string()
+    }
+}
+
+/// Percent encode everything but ascii letters, digits and `-._~` so the
+/// input can be used as a single segment of an url path.
+pub(super) fn percent_encode(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+
+    for byte in input.bytes() {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            out.push(char::from(byte));
+        } else {
+            out.push_str(&format!("%{:02X}", byte));
+        }
+    }
+
+    out
+}
+
+/// Parse a duration like `30m`, `12h`, `7d` or `2w`.
+pub(super) fn parse_duration(input: &str) -> Result<Duration, Error> {
+    let input = input.trim();
+
+    let unit_index = input
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(|| format_err!("duration {} is missing a unit (s, m, h, d, w)", input))?;
+
+    let (amount, unit) = input.split_at(unit_index);
+    let amount: i64 = amount
+        .parse()
+        .with_context(|| format!("can not parse amount of duration {}", input))?;
+
+    let duration = match unit {
+        "s" => Duration::seconds(amount),
+        "m" => Duration::minutes(amount),
+        "h" => Duration::hours(amount),
+        "d" => Duration::days(amount),
+        "w" => Duration::weeks(amount),
+        _ => bail!("unknown unit {} in duration {}", unit, input),
+    };
+
+    Ok(duration)
+}
+
+/// Parse a uuid in any of the forms users paste around: upper or lowercase,
+/// with or without hyphens and optionally wrapped in braces, which may also be
+/// percent encoded when they come from an url. Uuids are always written back
+/// in the lowercase hyphenated form.
+pub(super) fn parse_uuid(input: &str) -> Result<Uuid, Error> {
+    let trimmed = input.trim();
+
+    let unwrapped = strip_wrapping(trimmed, "{", "}")
+        .or_else(|| strip_wrapping(trimmed, "%7B", "%7D"))
+        .or_else(|| strip_wrapping(trimmed, "%7b", "%7d"))
+        .unwrap_or(trimmed);
+
+    Uuid::parse_str(unwrapped).map_err(|err| format_err!("{} is not a valid uuid: {}", input, err))
+}
+
+fn strip_wrapping<'a>(input: &'a str, prefix: &str, suffix: &str) -> Option<&'a str> {
+    input.strip_prefix(prefix)?.strip_suffix(suffix)
+}
+
+/// Decode percent encoded characters like `%2F`. Returns `None` if the input
+/// is not validly encoded.
+pub(super) fn percent_decode(input: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(input.len());
+    let mut iter = input.bytes();
+
+    while let Some(byte) = iter.next() {
+        if byte == b'%' {
+            let high = iter.next()?;
+            let low = iter.next()?;
+            let hex = [high, low];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+
+    String::from_utf8(bytes).ok()
 }
 
 pub(super) fn format_duration(duration: Duration) -> String {
@@ -86,11 +623,406 @@ pub(super) fn format_duration(duration: Duration) -> String {
     format!("{}d", duration.num_days())
 }
 
-pub(super) fn format_timestamp(time_stamp: Option<NaiveDate>) -> String {
-    if time_stamp.is_none() {
-        return "-".to_string();
+/// Format the due date together with how far it is from today, like
+/// `2024-06-01 (in 5d)` or `2024-06-01 (3d overdue)`.
+pub(super) fn format_due(due: Option<NaiveDate>, today: NaiveDate, locale: Locale) -> String {
+    let due = match due {
+        Some(due) => due,
+        None => return "-".to_string(),
+    };
+
+    let days = due.signed_duration_since(today).num_days();
+
+    format!(
+        "{} ({})",
+        locale.format_date(due),
+        locale.format_due_distance(days)
+    )
+}
+
+/// Format a ratio as a whole percentage like `80%` or `-` if there is none.
+pub(super) fn format_ratio(ratio: Option<f64>) -> String {
+    match ratio {
+        Some(ratio) => format!("{:.0}%", ratio * 100.0),
+        None => "-".to_string(),
+    }
+}
+
+/// Values as a line of block characters for the terminal like `▁▃▅█`. The
+/// highest value gets the full block, zero the lowest one.
+pub(super) fn format_sparkline(values: &[usize]) -> String {
+    const BLOCKS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+    let max = values.iter().copied().max().unwrap_or_default().max(1);
+
+    values
+        .iter()
+        .map(|value| BLOCKS[value * (BLOCKS.len() - 1) / max])
+        .collect()
+}
+
+pub(super) fn format_timestamp(time_stamp: Option<NaiveDate>, locale: Locale) -> String {
+    match time_stamp {
+        Some(time_stamp) => locale.format_date(time_stamp),
+        None => "-".to_string(),
+    }
+}
+
+/// Paths in the folder sorted by name. A folder that does not exist has no
+/// paths. The paths are never converted to strings so folders with names
+/// that are not valid UTF-8 work as well.
+pub(super) fn folder_paths(folder: &Path) -> io::Result<Vec<PathBuf>> {
+    let dir_entries = match fs::read_dir(folder) {
+        Ok(dir_entries) => dir_entries,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => return Err(err),
+    };
+
+    let mut paths = dir_entries
+        .map(|dir_entry| dir_entry.map(|dir_entry| dir_entry.path()))
+        .collect::<io::Result<Vec<_>>>()?;
+    paths.sort();
+
+    Ok(paths)
+}
+
+/// Files with the extension in the folder and all its subfolders sorted by
+/// path.
+pub(super) fn files_with_extension(folder: &Path, extension: &str) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+
+    for path in folder_paths(folder)? {
+        if path.is_dir() {
+            files.extend(files_with_extension(&path, extension)?);
+        } else if path.extension().is_some_and(|found| found == extension) {
+            files.push(path);
+        }
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// Editor that runs the shell script with the path of the file to edit
+    /// as `$1`.
+    pub(crate) fn fake_editor(directory: &Path, script: &str) -> PathBuf {
+        let path = directory.join("editor");
+        fs::write(&path, format!("#!/bin/sh\n{}\n", script)).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+
+        path
+    }
+
+    /// Text of the editor running the script on the prepopulated text.
+    pub(crate) fn edit_with(script: &str, prepopulate: Option<&str>) -> Result<String, Error> {
+        let directory = tempdir().unwrap();
+        let editor = fake_editor(directory.path(), script);
+
+        string_from_editor_command(
+            &editor.display().to_string(),
+            prepopulate,
+            Interactivity::default(),
+        )
+    }
+
+    #[test]
+    fn edit_distance_counts_transpositions_once() {
+        assert_eq!(edit_distance("work", "work"), 0);
+        assert_eq!(edit_distance("wrok", "work"), 1);
+        assert_eq!(edit_distance("owrk", "work"), 1);
+        assert_eq!(edit_distance("Work", "work"), 0);
+        assert_eq!(edit_distance("wor", "work"), 1);
+        assert_eq!(edit_distance("", "work"), 4);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+    }
+
+    #[test]
+    fn closest_match_suggests_typos() {
+        let projects = vec![
+            "default".to_string(),
+            "work".to_string(),
+            "homework".to_string(),
+        ];
+
+        assert_eq!(closest_match("wrok", &projects), Some("work"));
+        assert_eq!(closest_match("defualt", &projects), Some("default"));
+        assert_eq!(closest_match("homwork", &projects), Some("homework"));
+    }
+
+    #[test]
+    fn closest_match_ignores_unrelated_names() {
+        let projects = vec!["default".to_string(), "work".to_string()];
+
+        assert_eq!(closest_match("garden", &projects), None);
+        assert_eq!(closest_match("work", &projects), None);
+        assert_eq!(closest_match("wrok", &[]), None);
     }
-    let time_stamp = time_stamp.unwrap();
 
-    format!("{}", time_stamp)
+    const UUID: &str = "6b3f3f0e-6b0a-4a4e-9c4a-1f0e0c3a2b10";
+
+    fn confirm_text_with(answers: &str, text: &str) -> Result<bool, Error> {
+        confirm_text(
+            &mut answers.as_bytes(),
+            "finish?",
+            text,
+            Uuid::parse_str(UUID).unwrap(),
+        )
+    }
+
+    #[test]
+    fn prompt_takes_yes_or_no() {
+        for (answer, expected) in [
+            ("y\n", true),
+            ("YES\r\n", true),
+            ("n\n", false),
+            ("No\n", false),
+        ] {
+            assert_eq!(
+                confirm_from(&mut answer.as_bytes(), "finish?", false).unwrap(),
+                expected,
+                "{:?}",
+                answer
+            );
+        }
+
+        assert!(confirm_from(&mut "maybe\n".as_bytes(), "finish?", false).is_err());
+    }
+
+    #[test]
+    fn text_mode_takes_the_first_word_or_short_id() {
+        assert!(confirm_text_with("call\n", "== Call the vendor").unwrap());
+        assert!(confirm_text_with("CALL\n", "== Call the vendor").unwrap());
+        assert!(confirm_text_with(&format!("{}\n", &UUID[..8]), "== Call the vendor").unwrap());
+        assert!(confirm_text_with(&format!("{}\n", &UUID[..8]), "").unwrap());
+    }
+
+    #[test]
+    fn text_mode_retries_a_wrong_word() {
+        assert!(confirm_text_with("buy\ncall\n", "Call the vendor").unwrap());
+        assert!(!confirm_text_with("buy\nmilk\neggs\ncall\n", "Call the vendor").unwrap());
+    }
+
+    #[test]
+    fn text_mode_is_cancelled_by_an_empty_answer() {
+        assert!(!confirm_text_with("\ncall\n", "Call the vendor").unwrap());
+        assert!(!confirm_text_with("", "Call the vendor").unwrap());
+    }
+
+    #[test]
+    fn off_mode_never_asks() {
+        let uuid = Uuid::parse_str(UUID).unwrap();
+
+        assert!(confirm_done(
+            ConfirmDone::Off,
+            Interactivity::default(),
+            "finish?",
+            "text",
+            uuid
+        )
+        .unwrap());
+
+        for mode in [ConfirmDone::Prompt, ConfirmDone::Text] {
+            let err =
+                confirm_done(mode, Interactivity::default(), "finish?", "text", uuid).unwrap_err();
+
+            assert!(
+                err.to_string().ends_with("pass --yes to skip it"),
+                "{}",
+                err
+            );
+        }
+    }
+
+    #[test]
+    fn editor_returns_the_saved_text() {
+        assert_eq!(
+            edit_with("printf 'new text' > \"$1\"", Some("old text")).unwrap(),
+            "new text"
+        );
+        assert_eq!(edit_with("true", Some("old text")).unwrap(), "old text");
+        assert_eq!(edit_with(": > \"$1\"", Some("old text")).unwrap(), "");
+    }
+
+    #[test]
+    fn crashed_editor_is_an_error() {
+        let err = edit_with("printf 'partial' > \"$1\"\nexit 3", Some("old text")).unwrap_err();
+
+        assert!(err.to_string().starts_with("editor exited with"), "{}", err);
+    }
+
+    #[test]
+    fn crashed_editor_keeps_what_was_saved() {
+        let directory = tempdir().unwrap();
+        let editor = fake_editor(directory.path(), "printf 'partial' > \"$1\"\nexit 3");
+
+        let (result, saved) = run_editor(&editor.display().to_string(), "old text").unwrap();
+
+        assert!(result.is_err());
+        assert_eq!(saved.as_deref(), Some("partial"));
+    }
+
+    #[test]
+    fn removed_editor_file_is_an_error() {
+        let err = edit_with("rm \"$1\"", Some("old text")).unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "the file of the editor was removed while editing"
+        );
+    }
+
+    #[test]
+    fn summarize_strips_heading_markers() {
+        assert_eq!(
+            summarize("== Call the vendor\n\nAsk about   the invoice.", None),
+            "Call the vendor Ask about the invoice."
+        );
+        assert_eq!(summarize("==not a heading", None), "==not a heading");
+    }
+
+    #[test]
+    fn summarize_skips_attribute_only_lines() {
+        assert_eq!(
+            summarize(":toc: right\n[NOTE]\n=== Renew the domain\n", None),
+            "Renew the domain"
+        );
+        assert_eq!(first_line(":toc: right\n\n== Renew\nlater"), "Renew");
+    }
+
+    #[test]
+    fn summarize_truncates_at_a_word_boundary() {
+        assert_eq!(
+            summarize("Call the vendor about the invoice", Some(16)),
+            "Call the vendor…"
+        );
+        assert_eq!(summarize("short", Some(16)), "short");
+    }
+
+    #[test]
+    fn summarize_cuts_very_long_words() {
+        let word = "a".repeat(30);
+
+        assert_eq!(summarize(&word, Some(10)), format!("{}…", "a".repeat(9)));
+    }
+
+    #[test]
+    fn split_lines_skips_blank_lines() {
+        assert_eq!(
+            split_lines("first\n\n  second  \n\n\n"),
+            vec!["first", "second"]
+        );
+        assert!(split_lines("\n \n").is_empty());
+    }
+
+    #[test]
+    fn split_lines_handles_crlf() {
+        assert_eq!(split_lines("first\r\nsecond\r\n"), vec!["first", "second"]);
+    }
+
+    #[test]
+    fn split_sections_keeps_heading_and_body() {
+        assert_eq!(
+            split_sections("intro\n\n== First\nbody\n\n== Second\n\n\n"),
+            vec!["intro", "== First\nbody", "== Second"]
+        );
+    }
+
+    #[test]
+    fn split_sections_of_only_headings() {
+        assert_eq!(
+            split_sections("== First\n== Second\n== Third"),
+            vec!["== First", "== Second", "== Third"]
+        );
+    }
+
+    #[test]
+    fn split_sections_handles_crlf() {
+        assert_eq!(
+            split_sections("== First\r\nbody\r\n== Second\r\n"),
+            vec!["== First\nbody", "== Second"]
+        );
+    }
+
+    #[test]
+    fn parse_uuid_accepts_pasted_forms() {
+        let uuid = Uuid::parse_str("0e3b9f6c-5c3a-4d9e-8a0f-2b7c1d4e5f60").unwrap();
+
+        for input in &[
+            "0e3b9f6c-5c3a-4d9e-8a0f-2b7c1d4e5f60",
+            "0E3B9F6C-5C3A-4D9E-8A0F-2B7C1D4E5F60",
+            "0e3b9f6c5c3a4d9e8a0f2b7c1d4e5f60",
+            "{0e3b9f6c-5c3a-4d9e-8a0f-2b7c1d4e5f60}",
+            "%7B0E3B9F6C-5C3A-4D9E-8A0F-2B7C1D4E5F60%7D",
+            "%7b0e3b9f6c5c3a4d9e8a0f2b7c1d4e5f60%7d",
+            " 0e3b9f6c-5c3a-4d9e-8a0f-2b7c1d4e5f60\n",
+        ] {
+            let parsed = parse_uuid(input).unwrap();
+
+            assert_eq!(parsed, uuid, "{:?}", input);
+            assert_eq!(parsed.to_string(), "0e3b9f6c-5c3a-4d9e-8a0f-2b7c1d4e5f60");
+        }
+    }
+
+    #[test]
+    fn parse_uuid_rejects_invalid_input() {
+        for input in &[
+            "",
+            "not-a-uuid",
+            "{0e3b9f6c-5c3a-4d9e-8a0f-2b7c1d4e5f60",
+            "0e3b9f6c-5c3a-4d9e-8a0f-2b7c1d4e5f6",
+            "0e3b9f6c-5c3a-4d9e-8a0f-2b7c1d4e5f6z",
+        ] {
+            let err = parse_uuid(input).unwrap_err().to_string();
+
+            assert!(
+                err.starts_with(&format!("{} is not a valid uuid", input)),
+                "{}",
+                err
+            );
+        }
+    }
+
+    fn day(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn format_due_relative_to_today() {
+        let today = day(2024, 6, 1);
+
+        assert_eq!(
+            format_due(Some(day(2024, 6, 1)), today, Locale::En),
+            "2024-06-01 (today)"
+        );
+        assert_eq!(
+            format_due(Some(day(2024, 5, 29)), today, Locale::En),
+            "2024-05-29 (3d overdue)"
+        );
+        assert_eq!(
+            format_due(Some(day(2024, 6, 6)), today, Locale::En),
+            "2024-06-06 (in 5d)"
+        );
+        assert_eq!(format_due(None, today, Locale::En), "-");
+    }
+
+    #[test]
+    fn format_due_in_german() {
+        assert_eq!(
+            format_due(Some(day(2024, 5, 29)), day(2024, 6, 1), Locale::De),
+            "29.05.2024 (3d überfällig)"
+        );
+    }
+
+    #[test]
+    fn format_timestamp_is_only_the_date() {
+        assert_eq!(
+            format_timestamp(Some(day(2024, 6, 1)), Locale::En),
+            "2024-06-01"
+        );
+        assert_eq!(format_timestamp(None, Locale::En), "-");
+    }
 }
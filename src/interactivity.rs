@@ -0,0 +1,108 @@
+use crate::helper;
+use anyhow::{
+    bail,
+    Error,
+};
+use std::env;
+
+/// Environment variable that turns off prompts and decorations even when
+/// todust runs in a terminal, for scripts and editor integrations. Any value
+/// except an empty one and "0" counts.
+const NONINTERACTIVE_ENV: &str = "TODUST_NONINTERACTIVE";
+
+/// What todust can do with the terminal it runs in. Detected once at the start
+/// and passed to everything that asks questions or decorates its output.
+///
+/// | Situation                    | Prompts            | Styling | Notices |
+/// |------------------------------|--------------------|---------|---------|
+/// | stdin and stdout terminals   | asked              | yes     | yes     |
+/// | stdin is not a terminal      | fail naming a flag | yes     | yes     |
+/// | stdout is not a terminal     | asked              | no      | no      |
+/// | TODUST_NONINTERACTIVE is set | fail naming a flag | no      | no      |
+/// | --quiet                      | asked              | yes     | no      |
+/// | --porcelain                  | asked              | no      | no      |
+///
+/// Prompts that `--yes` or a similar flag answers are not asked at all.
+/// Styling is the pager, colors with `--color auto` and limiting tables to the
+/// width of the terminal. Notices are the header naming the datadir and
+/// project and the explanation after a new store was created.
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct Interactivity {
+    stdin_terminal: bool,
+    stdout_terminal: bool,
+    noninteractive: bool,
+    quiet: bool,
+}
+
+impl Interactivity {
+    pub(crate) fn detect(quiet: bool) -> Self {
+        let noninteractive =
+            env::var_os(NONINTERACTIVE_ENV).is_some_and(|value| !value.is_empty() && value != "0");
+
+        Self {
+            stdin_terminal: atty::is(atty::Stream::Stdin),
+            stdout_terminal: atty::is(atty::Stream::Stdout),
+            noninteractive,
+            quiet,
+        }
+    }
+
+    /// Interactivity of somebody sitting at a terminal.
+    #[cfg(test)]
+    pub(crate) fn terminal() -> Self {
+        Self {
+            stdin_terminal: true,
+            stdout_terminal: true,
+            noninteractive: false,
+            quiet: false,
+        }
+    }
+
+    /// Output of --porcelain is parsed by other programs so it is never
+    /// styled and has no notices.
+    pub(crate) fn porcelain(self) -> Self {
+        Self {
+            stdout_terminal: false,
+            quiet: true,
+            ..self
+        }
+    }
+
+    /// If somebody can answer questions on stdin.
+    pub(crate) fn can_prompt(&self) -> bool {
+        self.stdin_terminal && !self.noninteractive
+    }
+
+    /// If stdout can be paged, colored and limited to the terminal width.
+    pub(crate) fn is_styled(&self) -> bool {
+        self.stdout_terminal && !self.noninteractive
+    }
+
+    /// If informational messages that are not part of the output are shown.
+    pub(crate) fn shows_notices(&self) -> bool {
+        self.is_styled() && !self.quiet
+    }
+
+    /// Fail if nobody can answer a prompt. `hint` tells how to do without
+    /// the prompt like "pass --yes to create the project".
+    pub(crate) fn check_prompt(&self, hint: &str) -> Result<(), Error> {
+        if self.can_prompt() {
+            return Ok(());
+        }
+
+        let reason = if self.noninteractive {
+            format!("{} is set", NONINTERACTIVE_ENV)
+        } else {
+            "stdin is not a terminal".to_string()
+        };
+
+        bail!("can not ask for confirmation as {}, {}", reason, hint)
+    }
+
+    /// Ask a yes or no question. Fails with the hint if nobody can answer it.
+    pub(crate) fn confirm(&self, message: &str, default: bool, hint: &str) -> Result<bool, Error> {
+        self.check_prompt(hint)?;
+
+        helper::confirm(message, default)
+    }
+}
@@ -0,0 +1,179 @@
+use anyhow::{
+    Context,
+    Error,
+};
+use chrono::{
+    Datelike,
+    NaiveDate,
+    Weekday,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::Path,
+    sync::OnceLock,
+};
+
+/// Folder in the datadir with files that override the built-in translations.
+/// The files are named after the locale like `de.toml`.
+const TRANSLATIONS_FOLDER: &str = "locale";
+
+/// Language dates and labels are shown in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum Locale {
+    #[default]
+    En,
+    De,
+}
+
+impl Locale {
+    fn name(self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::De => "de",
+        }
+    }
+
+    /// Chrono format of a date, `2024-12-24` or `24.12.2024`.
+    fn date_format(self) -> &'static str {
+        match self {
+            Locale::En => "%Y-%m-%d",
+            Locale::De => "%d.%m.%Y",
+        }
+    }
+
+    pub(crate) fn format_date(self, date: NaiveDate) -> String {
+        date.format(self.date_format()).to_string()
+    }
+
+    /// How far a due date is from today like `today`, `in 5d` or `3d
+    /// overdue`.
+    pub(crate) fn format_due_distance(self, days: i64) -> String {
+        match (self, days) {
+            (Locale::En, 0) => "today".to_string(),
+            (Locale::En, days) if days > 0 => format!("in {}d", days),
+            (Locale::En, days) => format!("{}d overdue", -days),
+            (Locale::De, 0) => "heute".to_string(),
+            (Locale::De, days) if days > 0 => format!("in {}d", days),
+            (Locale::De, days) => format!("{}d überfällig", -days),
+        }
+    }
+
+    /// Date together with the name of its weekday like `2024-12-24
+    /// (Tuesday)`.
+    pub(crate) fn format_day(self, date: NaiveDate) -> String {
+        format!(
+            "{} ({})",
+            self.format_date(date),
+            self.weekday_name(date.weekday())
+        )
+    }
+
+    pub(crate) fn weekday_name(self, weekday: Weekday) -> &'static str {
+        let names = match self {
+            Locale::En => [
+                "Monday",
+                "Tuesday",
+                "Wednesday",
+                "Thursday",
+                "Friday",
+                "Saturday",
+                "Sunday",
+            ],
+            Locale::De => [
+                "Montag",
+                "Dienstag",
+                "Mittwoch",
+                "Donnerstag",
+                "Freitag",
+                "Samstag",
+                "Sonntag",
+            ],
+        };
+
+        names[weekday.num_days_from_monday() as usize]
+    }
+
+    /// Built-in translations of the locale.
+    fn builtin(self) -> &'static BTreeMap<String, String> {
+        static EN: OnceLock<BTreeMap<String, String>> = OnceLock::new();
+        static DE: OnceLock<BTreeMap<String, String>> = OnceLock::new();
+
+        let (cell, raw) = match self {
+            Locale::En => (&EN, include_str!("../resources/locale/en.toml")),
+            Locale::De => (&DE, include_str!("../resources/locale/de.toml")),
+        };
+
+        cell.get_or_init(|| {
+            toml::from_str(raw)
+                .unwrap_or_else(|err| panic!("built-in {} translations are invalid: {}", self, err))
+        })
+    }
+}
+
+impl std::fmt::Display for Locale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl std::str::FromStr for Locale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "en" => Ok(Locale::En),
+            "de" => Ok(Locale::De),
+            _ => Err(format!("unknown locale {}", s)),
+        }
+    }
+}
+
+/// Labels of the tables and web pages in the configured locale. Labels are
+/// looked up in the override file of the datadir first, then in the built-in
+/// translations of the locale and then in the English ones.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Translations {
+    locale: Locale,
+    overrides: BTreeMap<String, String>,
+}
+
+impl Translations {
+    pub(crate) fn load<P: AsRef<Path>>(datadir: P, locale: Locale) -> Result<Self, Error> {
+        let path = datadir
+            .as_ref()
+            .join(TRANSLATIONS_FOLDER)
+            .join(format!("{}.toml", locale));
+
+        let overrides = if path.exists() {
+            let raw = fs::read_to_string(&path)
+                .with_context(|| format!("can not read translations from {:?}", path))?;
+
+            toml::from_str(&raw)
+                .with_context(|| format!("can not parse translations from {:?}", path))?
+        } else {
+            BTreeMap::new()
+        };
+
+        Ok(Self { locale, overrides })
+    }
+
+    pub(crate) fn locale(&self) -> Locale {
+        self.locale
+    }
+
+    /// Label for the key. Unknown keys are returned as they are so a missing
+    /// translation is visible but does not break the output.
+    pub(crate) fn get<'a>(&'a self, key: &'a str) -> &'a str {
+        self.overrides
+            .get(key)
+            .or_else(|| self.locale.builtin().get(key))
+            .or_else(|| Locale::En.builtin().get(key))
+            .map_or(key, String::as_str)
+    }
+}
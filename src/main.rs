@@ -1,60 +1,95 @@
+mod checklist;
+mod cmd;
 mod config;
+mod diff;
+mod due_date;
 mod entry;
 mod helper;
+mod interactivity;
+mod locale;
+mod markdown;
 mod opt;
+mod output;
+mod porcelain;
+mod project_name;
+mod setting_source;
+mod stats;
 mod store;
+mod table;
+mod tags;
 mod templating;
+mod tidy;
+mod time_context;
+mod watch;
 mod webservice;
 
 use crate::{
     config::Config,
-    entry::{
-        Entries,
-        Entry,
-        Metadata,
-        ProjectCount,
+    interactivity::Interactivity,
+    opt::*,
+    setting_source::{
+        EffectiveSettings,
+        SettingSource,
     },
-    helper::{
-        format_duration,
-        format_timestamp,
-        string_from_editor,
+    store::{
+        changes::Changes,
+        quota::QuotaExceeded,
+        recent::{
+            self,
+            RecentProjects,
+        },
+        Store,
     },
-    opt::*,
-    store::Store,
 };
 use anyhow::{
     bail,
-    Context,
     Error,
 };
-use chrono::Utc;
-use comfy_table::{
-    Attribute,
-    Cell,
-    Table,
-};
+use crossterm::style::Stylize;
 use log::{
     error,
     trace,
+    warn,
+};
+use std::{
+    env,
+    path::PathBuf,
 };
-use std::io::{
-    self,
-    Write,
+use structopt::{
+    clap::ArgMatches,
+    StructOpt,
 };
-use structopt::StructOpt;
+
+/// Environment variable clap takes the project from when --project is not
+/// given.
+const PROJECT_ENV: &str = "TODUST_PROJECT";
+
+/// Project entries are added to when no project is given.
+const DEFAULT_PROJECT: &str = "default";
 
 #[async_std::main]
 async fn main() {
     if let Err(err) = run().await {
-        error!("{}", err)
+        // Only the outermost context is shown so the quota has to be named
+        // explicitly to tell why the change was refused.
+        match err.downcast_ref::<QuotaExceeded>() {
+            Some(exceeded) => error!("{}: {}", err, exceeded),
+            None => error!("{}", err),
+        }
     }
 }
 
 async fn run() -> Result<(), Error> {
-    let opt = Opt::from_args();
+    let (matches, session_project) = with_session_project(Opt::clap().get_matches());
+    let opt = Opt::from_clap(&matches);
+
+    if opt.porcelain_version {
+        println!("{}", porcelain::PORCELAIN_VERSION);
+        return Ok(());
+    }
 
     // setup logging
-    if matches!(opt.cmd, SubCommand::Web(_)) {
+    if matches!(opt.cmd, Some(SubCommand::Web(_))) {
         use tide::log::LevelFilter;
 
         let tide_log_level = match opt.log_level {
@@ -78,355 +113,253 @@ async fn run() -> Result<(), Error> {
 
     trace!("opt: {:#?}", opt);
 
-    let config = Config::read_path(opt.config_path)?;
-
-    match opt.cmd {
-        SubCommand::Add(sub_opt) => run_add(sub_opt, config),
-        SubCommand::Cleanup(sub_opt) => run_cleanup(sub_opt, config),
-        SubCommand::Completion(sub_opt) => run_completion(sub_opt),
-        SubCommand::Done(sub_opt) => run_done(sub_opt, config),
-        SubCommand::Due(sub_opt) => run_due(sub_opt, config),
-        SubCommand::Edit(sub_opt) => run_edit(sub_opt, config),
-        SubCommand::List(sub_opt) => run_list(sub_opt, config),
-        SubCommand::Move(sub_opt) => run_move(sub_opt, config),
-        SubCommand::Print(sub_opt) => run_print(sub_opt, config),
-        SubCommand::Projects(sub_opt) => run_projects(sub_opt, config),
-        SubCommand::Web(sub_opt) => run_web(sub_opt, config).await,
-    }
-}
-
-fn run_add(opt: AddSubCommandOpts, config: Config) -> Result<(), Error> {
-    let store = Store::open(
-        &opt.datadir_opt.datadir,
-        config.identifier,
-        config.vcs_config,
-    )?;
-
-    let text = if let Some(opt_text) = &opt.text {
-        opt_text.clone()
-    } else {
-        string_from_editor(None).context("can not get message from editor")?
-    };
-
-    let entry = Entry {
-        text,
-        metadata: Metadata {
-            project: opt.project_opt.project,
-            ..Metadata::default()
-        },
-    };
-
-    store
-        .add_entry(entry)
-        .context("can not add entry to store")?;
-
-    Ok(())
-}
-
-fn run_cleanup(opt: CleanupSubCommandOpts, config: Config) -> Result<(), Error> {
-    Store::open(
-        &opt.datadir_opt.datadir,
-        config.identifier,
-        config.vcs_config,
-    )?
-    .run_cleanup()
-}
-
-fn run_completion(opt: CompletionSubCommandOpts) -> Result<(), Error> {
-    std::fs::create_dir_all(&opt.directory)?;
-    Opt::clap().gen_completions(env!("CARGO_PKG_NAME"), opt.shell, opt.directory);
-
-    Ok(())
-}
-
-fn run_done(opt: DoneSubCommandOpts, config: Config) -> Result<(), Error> {
-    let store = Store::open(
-        &opt.datadir_opt.datadir,
-        config.identifier,
-        config.vcs_config,
-    )?;
-    store.entry_done(opt.entry_id, &opt.project_opt.project)?;
-
-    Ok(())
-}
-
-fn run_edit(opt: EditSubCommandOpts, config: Config) -> Result<(), Error> {
-    if opt.entry_id < 1 {
-        bail!("entry id can not be smaller than 1")
-    }
-
-    let store = Store::open(
-        &opt.datadir_opt.datadir,
-        config.identifier,
-        config.vcs_config,
-    )?;
-
-    let old_entry = store
-        .get_entry_by_id(opt.entry_id, &opt.project_opt.project)
-        .context("can not get entry")?;
-
-    let new_text = string_from_editor(Some(&old_entry.text)).context(
-        "can not edit entry with
-editor",
-    )?;
-
-    let new_entry = if opt.update_time {
-        Entry {
-            text: new_text,
-            metadata: Metadata {
-                started: Utc::now(),
-                last_change: Utc::now(),
-                ..old_entry.metadata
-            },
-        }
-    } else {
-        Entry {
-            text: new_text,
-            ..old_entry
+    let cmd = match opt.cmd {
+        Some(cmd) => cmd,
+        None => {
+            Opt::clap().print_help()?;
+            println!();
+            return Ok(());
         }
     };
 
-    store
-        .update_entry(new_entry)
-        .context("can not update entry")?;
-
-    Ok(())
-}
-
-fn run_list(opt: ListSubCommandOpts, config: Config) -> Result<(), Error> {
-    let store = Store::open(
-        &opt.datadir_opt.datadir,
-        config.identifier,
-        config.vcs_config,
-    )?;
+    let mut config = Config::read_path(opt.config_path)?;
+    if opt.dry_run {
+        config.changes = Changes::dry_run();
+    }
+    let changes = config.changes.clone();
+    config.no_hooks = opt.no_hooks;
+    config.no_index_cache = opt.no_index_cache;
+    config.no_stale_check = opt.no_stale_check;
+    config.repair = opt.repair;
+    config.quiet = opt.quiet;
+    config.interactivity = Interactivity::detect(opt.quiet);
+    if matches
+        .subcommand()
+        .1
+        .is_some_and(|sub_matches| sub_matches.is_present("porcelain"))
+    {
+        config.interactivity = config.interactivity.porcelain();
+    }
+    if let Some(table_style) = opt.table_style {
+        config.table_style = table_style;
+    }
 
-    let entries = store
-        .get_active_entries(&opt.project_opt.project)
-        .context("can not get entries from store")?;
+    let color = opt.color.enabled(config.interactivity);
 
-    if entries.is_empty() {
-        println!("no active todos");
-        return Ok(());
+    let mut settings = EffectiveSettings::from_matches(&matches);
+    if session_project {
+        settings = settings.with_session_project();
     }
 
-    let mut table = Table::new();
-    table.load_preset("                   ");
-    table.set_content_arrangement(comfy_table::ContentArrangement::Dynamic);
-    table.set_header(vec![
-        Cell::new("ID").add_attribute(Attribute::Bold),
-        Cell::new("Age").add_attribute(Attribute::Bold),
-        Cell::new("Due").add_attribute(Attribute::Bold),
-        Cell::new("Description").add_attribute(Attribute::Bold),
-    ]);
-
-    for (index, entry) in entries.into_iter().enumerate() {
-        table.add_row(vec![
-            format!("{}", index + 1),
-            format_duration(entry.age()),
-            format_timestamp(entry.metadata.due),
-            format!("{}", entry),
-        ]);
+    if !matches!(cmd, SubCommand::Web(_)) {
+        print_origin_header(&settings, &config, color);
+        record_recent_projects(&settings, &config);
     }
 
-    println!("{}", table);
-
-    Ok(())
-}
+    if matches!(cmd, SubCommand::Add(_)) && config.require_explicit_project {
+        check_explicit_project(&settings)?;
+    }
 
-fn run_move(opt: MoveSubCommandOpts, config: Config) -> Result<(), Error> {
-    let store = Store::open(
-        &opt.datadir_opt.datadir,
-        config.identifier,
-        config.vcs_config,
-    )?;
-
-    let old_entry = store
-        .get_entry_by_id(opt.entry_id, &opt.project_opt.project)
-        .context("can not get entry")?;
-
-    let new_entry = Entry {
-        text: old_entry.text.clone(),
-        metadata: Metadata {
-            project: opt.target_project,
-            last_change: Utc::now(),
-            ..old_entry.metadata
-        },
+    let result = match cmd {
+        SubCommand::Add(sub_opt) => cmd::add::run_add(sub_opt, config),
+        SubCommand::Archive(sub_opt) => cmd::cleanup::run_archive(sub_opt, config),
+        SubCommand::Cleanup(sub_opt) => cmd::cleanup::run_cleanup(sub_opt, config),
+        SubCommand::Completion(sub_opt) => cmd::completion::run_completion(sub_opt),
+        SubCommand::Done(sub_opt) => cmd::done::run_done(sub_opt, config),
+        SubCommand::Digest(sub_opt) => cmd::digest::run_digest(sub_opt, config),
+        SubCommand::Due(sub_opt) => cmd::due::run_due(sub_opt, config),
+        SubCommand::Edit(sub_opt) => cmd::edit::run_edit(sub_opt, config, color),
+        SubCommand::Export(sub_opt) => cmd::export::run_export(sub_opt, config),
+        SubCommand::Focus(sub_opt) => cmd::focus::run_focus(sub_opt, config),
+        SubCommand::Import(sub_opt) => cmd::import::run_import(sub_opt, config),
+        SubCommand::Info(sub_opt) => cmd::info::run_info(sub_opt, config),
+        SubCommand::Conflicts(sub_opt) => cmd::conflicts::run_conflicts(sub_opt, config),
+        SubCommand::List(sub_opt) => cmd::list::run_list(sub_opt, config),
+        SubCommand::Move(sub_opt) => cmd::move_entry::run_move(sub_opt, config),
+        SubCommand::Print(sub_opt) => cmd::print::run_print(sub_opt, config),
+        SubCommand::Reopen(sub_opt) => cmd::done::run_reopen(sub_opt, config),
+        SubCommand::Undone(sub_opt) => cmd::done::run_undone(sub_opt, config),
+        SubCommand::Recent(sub_opt) => cmd::recent::run_recent(sub_opt, config),
+        SubCommand::Switch(sub_opt) => cmd::recent::run_switch(sub_opt, config),
+        SubCommand::Delete(sub_opt) => cmd::delete::run_delete(sub_opt, config),
+        SubCommand::Audit(sub_opt) => cmd::audit::run_audit(sub_opt, config),
+        SubCommand::Project(sub_opt) => cmd::project::run_project(sub_opt, config),
+        SubCommand::Sync(sub_opt) => cmd::sync::run_sync(sub_opt, config),
+        SubCommand::Stats(sub_opt) => cmd::stats::run_stats(sub_opt, config),
+        SubCommand::Projects(sub_opt) => cmd::projects::run_projects(sub_opt, config, color),
+        SubCommand::Templates(sub_opt) => cmd::templates::run_templates(sub_opt),
+        SubCommand::Tidy(sub_opt) => cmd::tidy::run_tidy(sub_opt, config),
+        SubCommand::Triage(sub_opt) => cmd::triage::run_triage(sub_opt, config),
+        SubCommand::Web(sub_opt) => cmd::web::run_web(sub_opt, config).await,
+        SubCommand::Worklog(sub_opt) => cmd::worklog::run_worklog(sub_opt, config),
     };
 
-    store.add_entry(new_entry).context("can not add entry")?;
+    // The plan is shown even if the command failed halfway so it is clear
+    // how far it got.
+    for change in changes.take_plan() {
+        println!("dry run: would {}", change);
+    }
 
-    Ok(())
+    result
 }
 
-fn run_print(opt: PrintSubCommandOpts, config: Config) -> Result<(), Error> {
-    let store = Store::open(
-        &opt.datadir_opt.datadir,
-        config.identifier,
-        config.vcs_config,
-    )?;
-
-    let project = opt.project_opt.project;
-
-    match opt.entry_id {
-        Some(entry_id) => {
-            let entry = store
-                .get_entry_by_id(entry_id, &project)
-                .context("can not get entry")?;
+/// Parse the command line again with the project the terminal session
+/// switched to when neither --project nor TODUST_PROJECT is given. Returns if
+/// the project of the session is used.
+fn with_session_project(matches: ArgMatches<'static>) -> (ArgMatches<'static>, bool) {
+    let settings = EffectiveSettings::from_matches(&matches);
 
-            let entries: Entries = entry.into();
-
-            println!("{}", entries);
+    let datadir = match (&settings.datadir, &settings.project) {
+        (Some(datadir), Some(project)) if project.source == SettingSource::Default => {
+            PathBuf::from(&datadir.value)
         }
+        _ => return (matches, false),
+    };
 
-        None => {
-            if opt.no_done {
-                let entries = store
-                    .get_active_entries(&project)
-                    .context("can not get entries from store")?;
-
-                println!("{}", entries);
-            } else {
-                let entries = store
-                    .get_entries(&project)
-                    .context("can not get entries from store")?;
-
-                println!("{}", entries);
-            }
+    let project = recent::current_session().and_then(|session| {
+        RecentProjects::open(&datadir, Changes::default())
+            .ok()?
+            .session_project(&session)
+    });
+
+    match project {
+        Some(project) => {
+            // Clap only falls back to the environment so the project is
+            // passed through it and removed again so editors and hooks do
+            // not see it.
+            env::set_var(PROJECT_ENV, project);
+            let matches = Opt::clap().get_matches();
+            env::remove_var(PROJECT_ENV);
+
+            (matches, true)
         }
+        None => (matches, false),
     }
-
-    Ok(())
 }
 
-fn run_projects(opt: ProjectsSubCommandOpts, config: Config) -> Result<(), Error> {
-    if opt.simple {
-        run_projects_simple(opt, config)
-    } else {
-        run_projects_normal(opt, config)
+/// Fail if the project comes from the built-in default as entries that are
+/// added without thinking about the project pile up in the default project.
+/// A project picked with `todust switch` counts as given.
+fn check_explicit_project(settings: &EffectiveSettings) -> Result<(), Error> {
+    let from_default = settings
+        .project
+        .as_ref()
+        .is_some_and(|project| project.source == SettingSource::Default);
+
+    if from_default {
+        bail!(
+            "no project given and require_explicit_project is set, pass --project, set {} or pick \
+             a project for this terminal with todust switch",
+            PROJECT_ENV
+        )
     }
-}
 
-fn run_projects_simple(opt: ProjectsSubCommandOpts, config: Config) -> Result<(), Error> {
-    let store = Store::open(
-        &opt.datadir_opt.datadir,
-        config.identifier,
-        config.vcs_config,
-    )?;
+    Ok(())
+}
 
-    let mut projects_count = store
-        .get_projects_count()
-        .context("can not get projects count from store")?
-        .into_iter()
-        .filter(|entry| entry.active_count != 0 || opt.print_inactive)
-        .collect::<Vec<_>>();
+/// Remember the projects the command uses for `todust recent` and `todust
+/// switch`. Failing to do so only warns.
+fn record_recent_projects(settings: &EffectiveSettings, config: &Config) {
+    let (datadir, project) = match (&settings.datadir, &settings.project) {
+        (Some(datadir), Some(project)) => (datadir, project),
+        _ => return,
+    };
 
-    projects_count.sort();
+    // Commands on a datadir that is not a store yet do not use a project.
+    if !Store::exists(&datadir.value) {
+        return;
+    }
 
-    let stdout = io::stdout();
-    let mut handle = stdout.lock();
+    let session = recent::current_session();
+    let recorded = RecentProjects::open(&datadir.value, config.changes.clone())
+        .and_then(|recent| recent.record(&project.values, session.as_deref()));
 
-    for entry in projects_count {
-        handle.write_all(entry.project.as_bytes())?;
-        handle.write_all(b"\n")?;
+    if let Err(err) = recorded {
+        warn!("can not remember the recently used projects: {:#}", err);
     }
-
-    Ok(())
 }
 
-fn run_projects_normal(opt: ProjectsSubCommandOpts, config: Config) -> Result<(), Error> {
-    let store = Store::open(
-        &opt.datadir_opt.datadir,
-        config.identifier,
-        config.vcs_config,
-    )?;
-
-    let mut projects_count = store
-        .get_projects_count()
-        .context("can not get projects count from store")?
-        .into_iter()
-        .filter(|entry| entry.active_count != 0 || opt.print_inactive)
-        .collect::<Vec<_>>();
-
-    projects_count.sort();
-
-    let mut table = Table::new();
-    table.load_preset("                   ");
-    table.set_content_arrangement(comfy_table::ContentArrangement::Dynamic);
-    table.set_header(vec![
-        Cell::new("Project").add_attribute(Attribute::Bold),
-        Cell::new("Active").add_attribute(Attribute::Bold),
-        Cell::new("Done").add_attribute(Attribute::Bold),
-        Cell::new("Total").add_attribute(Attribute::Bold),
-    ]);
-
-    for entry in &projects_count {
-        trace!("entry written to table: {:#?}", entry);
-
-        table.add_row(vec![
-            entry.project.to_string(),
-            entry.active_count.to_string(),
-            entry.done_count.to_string(),
-            entry.total_count.to_string(),
-        ]);
+/// Print which datadir and project the command uses when one of them does
+/// not come from the built-in default, so an exported TODUST_DATADIR is
+/// noticed before entries end up in the wrong place.
+fn print_origin_header(settings: &EffectiveSettings, config: &Config, color: bool) {
+    if let Some(header) = origin_header(settings, config, color) {
+        println!("{}", header);
     }
+}
 
-    if !projects_count.is_empty() {
-        table.add_row(vec!["", "------", "----", "-----"]);
+fn origin_header(settings: &EffectiveSettings, config: &Config, color: bool) -> Option<String> {
+    if config.hide_origin_header || !config.interactivity.shows_notices() {
+        return None;
     }
 
-    let total = store
-        .get_projects_count()
-        .context("can not get projects count from store")?
-        .into_iter()
-        .fold(ProjectCount::default(), |acc, x| acc + x);
+    let header = settings.header()?;
 
-    table.add_row(vec![
-        "Total".to_string(),
-        total.active_count.to_string(),
-        total.done_count.to_string(),
-        total.total_count.to_string(),
-    ]);
-
-    println!("{}", table);
-
-    Ok(())
+    if color {
+        Some(header.dim().to_string())
+    } else {
+        Some(header)
+    }
 }
 
-fn run_due(opt: DueSubCommandOpts, config: Config) -> Result<(), Error> {
-    let store = Store::open(
-        &opt.datadir_opt.datadir,
-        config.identifier,
-        config.vcs_config,
-    )?;
-
-    let old_entry = store
-        .get_entry_by_id(opt.entry_id, &opt.project_opt.project)
-        .context("can not get entry")?;
-
-    let new_entry = Entry {
-        text: old_entry.text,
-        metadata: Metadata {
-            due: Some(opt.due_date),
-            last_change: Utc::now(),
-            ..old_entry.metadata
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        interactivity::Interactivity,
+        setting_source::{
+            ResolvedSetting,
+            SettingSource,
         },
     };
 
-    store.add_entry(new_entry).context("can not add entry")?;
+    fn from_environment() -> EffectiveSettings {
+        EffectiveSettings {
+            datadir: Some(ResolvedSetting {
+                value: "/srv/todos".to_string(),
+                source: SettingSource::Environment("TODUST_DATADIR"),
+                values: vec!["/srv/todos".to_string()],
+            }),
+            project: None,
+        }
+    }
 
-    Ok(())
-}
+    #[test]
+    fn origin_header_is_dim_with_colors() {
+        let config = Config {
+            interactivity: Interactivity::terminal(),
+            ..Config::default()
+        };
 
-async fn run_web(opt: WebSubCommandOpts, config: Config) -> Result<(), Error> {
-    let store = Store::open(
-        &opt.datadir_opt.datadir,
-        config.identifier,
-        config.vcs_config,
-    )?;
+        assert_eq!(
+            origin_header(&from_environment(), &config, false).unwrap(),
+            "# store: /srv/todos  (from TODUST_DATADIR)"
+        );
+        assert_eq!(
+            origin_header(&from_environment(), &config, true).unwrap(),
+            "# store: /srv/todos  (from TODUST_DATADIR)"
+                .dim()
+                .to_string()
+        );
+    }
 
-    crate::webservice::WebService::open(store)?
-        .run(opt.binding)
-        .await?;
+    #[test]
+    fn origin_header_can_be_hidden() {
+        let terminal = Interactivity::terminal();
 
-    Ok(())
+        for config in [
+            Config {
+                interactivity: terminal,
+                hide_origin_header: true,
+                ..Config::default()
+            },
+            Config {
+                interactivity: terminal.porcelain(),
+                ..Config::default()
+            },
+            Config::default(),
+        ] {
+            assert_eq!(origin_header(&from_environment(), &config, false), None);
+        }
+    }
 }
@@ -0,0 +1,290 @@
+use crate::{
+    entry::Entry,
+    helper,
+};
+use anyhow::{
+    bail,
+    Error,
+};
+use chrono::NaiveDate;
+use std::collections::{
+    BTreeMap,
+    HashMap,
+};
+use uuid::Uuid;
+
+/// A task of a markdown task list like `- [x] text (due 2024-06-01) <!-- uuid
+/// -->`.
+#[derive(Debug, PartialEq, Eq)]
+pub(super) struct MarkdownTask {
+    /// Project from the `## project` heading the task is listed under.
+    /// Missing for tasks before the first heading.
+    pub(super) project: Option<String>,
+    pub(super) done: bool,
+    pub(super) text: String,
+    pub(super) due: Option<NaiveDate>,
+
+    /// Uuid of the entry the task was exported from.
+    pub(super) uuid: Option<Uuid>,
+}
+
+/// Write the entries as markdown task lists with one `## project` section per
+/// project. Only the first line of each entry is written. Active entries come
+/// before done entries and both are sorted by when they were started.
+pub(super) fn export<'a, I>(entries: I) -> String
+where
+    I: IntoIterator<Item = &'a Entry>,
+{
+    let mut projects: BTreeMap<&str, Vec<&Entry>> = BTreeMap::new();
+    for entry in entries {
+        projects
+            .entry(entry.metadata.project.as_str())
+            .or_default()
+            .push(entry);
+    }
+
+    let mut out = String::new();
+
+    for (project, mut entries) in projects {
+        entries.sort_by_key(|entry| (entry.is_done(), entry.metadata.started));
+
+        if !out.is_empty() {
+            out.push('\n');
+        }
+
+        out.push_str(&format!("## {}\n\n", project));
+
+        for entry in entries {
+            let checkbox = if entry.is_done() { "x" } else { " " };
+
+            let due = entry
+                .metadata
+                .due
+                .map(|due| format!(" (due {})", due))
+                .unwrap_or_default();
+
+            out.push_str(&format!(
+                "- [{}] {}{} <!-- {} -->\n",
+                checkbox,
+                helper::first_line(&entry.text),
+                due,
+                entry.metadata.uuid
+            ));
+        }
+    }
+
+    out
+}
+
+/// Parse the tasks from a markdown file in the shape written by `export`.
+/// Indentation, the list marker (`-`, `*` or `+`) and the case of the `x` do
+/// not matter and lines that are not tasks are skipped. Fails if the same uuid
+/// is listed more than once with different checkbox states.
+pub(super) fn parse(input: &str) -> Result<Vec<MarkdownTask>, Error> {
+    let mut project = None;
+    let mut tasks = Vec::new();
+
+    for line in input.lines() {
+        let line = line.trim();
+
+        if let Some(heading) = line.strip_prefix("## ") {
+            project = Some(heading.trim().to_string());
+            continue;
+        }
+
+        if let Some(task) = parse_task(line, project.as_ref()) {
+            tasks.push(task);
+        }
+    }
+
+    let mut states: HashMap<Uuid, bool> = HashMap::new();
+    for task in &tasks {
+        let uuid = match task.uuid {
+            Some(uuid) => uuid,
+            None => continue,
+        };
+
+        match states.insert(uuid, task.done) {
+            Some(done) if done != task.done => bail!(
+                "entry {} is listed as done and as not done at the same time",
+                uuid
+            ),
+            _ => {}
+        }
+    }
+
+    Ok(tasks)
+}
+
+fn parse_task(line: &str, project: Option<&String>) -> Option<MarkdownTask> {
+    let rest = line
+        .strip_prefix("- ")
+        .or_else(|| line.strip_prefix("* "))
+        .or_else(|| line.strip_prefix("+ "))?
+        .trim_start();
+
+    let (done, rest) = if let Some(rest) = rest.strip_prefix("[ ]") {
+        (false, rest)
+    } else if let Some(rest) = rest
+        .strip_prefix("[x]")
+        .or_else(|| rest.strip_prefix("[X]"))
+    {
+        (true, rest)
+    } else {
+        return None;
+    };
+
+    let mut text = rest.trim();
+
+    let mut uuid = None;
+    if let Some(comment_start) = text.rfind("<!--") {
+        if let Some(comment) = text[comment_start + 4..].trim().strip_suffix("-->") {
+            if let Ok(parsed) = helper::parse_uuid(comment.trim()) {
+                uuid = Some(parsed);
+                text = text[..comment_start].trim_end();
+            }
+        }
+    }
+
+    let mut due = None;
+    if let Some(due_start) = text.rfind("(due ") {
+        if let Some(date) = text[due_start + 5..].strip_suffix(')') {
+            if let Ok(parsed) = date.trim().parse() {
+                due = Some(parsed);
+                text = text[..due_start].trim_end();
+            }
+        }
+    }
+
+    if text.is_empty() {
+        return None;
+    }
+
+    Some(MarkdownTask {
+        project: project.cloned(),
+        done,
+        text: text.to_string(),
+        due,
+        uuid,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::entry;
+
+    const UUID: &str = "0e3b9f6c-5c3a-4d9e-8a0f-2b7c1d4e5f60";
+
+    fn task(project: Option<&str>, done: bool, text: &str) -> MarkdownTask {
+        MarkdownTask {
+            project: project.map(str::to_string),
+            done,
+            text: text.to_string(),
+            due: None,
+            uuid: None,
+        }
+    }
+
+    #[test]
+    fn parses_indentation_and_list_markers() {
+        let tasks = parse(
+            "- [ ] first\n    * [x] second\n\t+ [X] third\n-   [ ]   fourth  \n- [] not a \
+             task\n-[ ] no space\nplain line\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            tasks,
+            vec![
+                task(None, false, "first"),
+                task(None, true, "second"),
+                task(None, true, "third"),
+                task(None, false, "fourth"),
+            ]
+        );
+    }
+
+    #[test]
+    fn tasks_belong_to_the_heading_above() {
+        let tasks =
+            parse("- [ ] before\n\n## work\n\n- [ ] first\n##  home \n- [x] second\n").unwrap();
+
+        assert_eq!(
+            tasks,
+            vec![
+                task(None, false, "before"),
+                task(Some("work"), false, "first"),
+                task(Some("home"), true, "second"),
+            ]
+        );
+    }
+
+    #[test]
+    fn due_date_and_uuid_comment_are_optional() {
+        let tasks = parse(&format!(
+            "- [ ] with both (due 2024-06-01) <!-- {uuid} -->\n- [ ] only due (due 2024-06-01)\n- \
+             [ ] only uuid <!--{uuid}-->\n- [ ] broken (due someday) <!-- not a uuid -->\n",
+            uuid = UUID.to_uppercase()
+        ))
+        .unwrap();
+
+        let uuid = Some(Uuid::parse_str(UUID).unwrap());
+        let due = NaiveDate::from_ymd_opt(2024, 6, 1);
+
+        assert_eq!(
+            tasks
+                .iter()
+                .map(|task| (task.text.as_str(), task.due, task.uuid))
+                .collect::<Vec<_>>(),
+            vec![
+                ("with both", due, uuid),
+                ("only due", due, None),
+                ("only uuid", None, uuid),
+                ("broken (due someday) <!-- not a uuid -->", None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn conflicting_states_of_one_entry_fail() {
+        let repeated = format!("- [ ] a <!-- {0} -->\n- [ ] a <!-- {0} -->\n", UUID);
+        assert_eq!(parse(&repeated).unwrap().len(), 2);
+
+        let conflicting = format!("- [ ] a <!-- {0} -->\n- [x] a <!-- {0} -->\n", UUID);
+        assert_eq!(
+            parse(&conflicting).unwrap_err().to_string(),
+            format!(
+                "entry {} is listed as done and as not done at the same time",
+                UUID
+            )
+        );
+    }
+
+    #[test]
+    fn export_round_trips() {
+        let mut active = entry("== Call the vendor\nabout the invoice");
+        active.metadata.project = "work".to_string();
+        active.metadata.due = NaiveDate::from_ymd_opt(2024, 6, 1);
+        let mut done = entry("Renew the domain");
+        done.metadata.finished = Some(chrono::Utc::now());
+
+        let exported = export(&[active.clone(), done.clone()]);
+
+        assert_eq!(
+            exported,
+            format!(
+                "## default\n\n- [x] Renew the domain <!-- {} -->\n\n## work\n\n- [ ] Call the \
+                 vendor (due 2024-06-01) <!-- {} -->\n",
+                done.metadata.uuid, active.metadata.uuid
+            )
+        );
+
+        let tasks = parse(&exported).unwrap();
+        assert_eq!(tasks[0].uuid, Some(done.metadata.uuid));
+        assert!(tasks[0].done);
+        assert_eq!(tasks[1].uuid, Some(active.metadata.uuid));
+        assert_eq!(tasks[1].due, active.metadata.due);
+        assert_eq!(tasks[1].project.as_deref(), Some("work"));
+    }
+}
@@ -1,38 +1,48 @@
-use chrono::NaiveDate;
+use crate::{
+    due_date::DueDateArg,
+    helper::{
+        parse_duration,
+        parse_uuid,
+    },
+    interactivity::Interactivity,
+    table::TableStyle,
+    tags,
+};
+use chrono::{
+    Duration,
+    NaiveDate,
+};
 use lazy_static::lazy_static;
 use simplelog::LevelFilter;
 use std::{
     net::SocketAddr,
     path::PathBuf,
+    str::FromStr,
 };
 use structopt::{
     clap::{
+        self,
         AppSettings::*,
         Shell,
     },
     StructOpt,
 };
+use uuid::Uuid;
 
 lazy_static! {
     static ref DEFAULT_DATADIR: PathBuf = xdg::BaseDirectories::with_prefix("todust")
         .expect("can not read xdg base directories")
         .get_data_home();
-    static ref DEFAULT_DATADIR_STRING: &'static str = DEFAULT_DATADIR
-        .to_str()
-        .expect("can not convert xdg data home to string");
     static ref DEFAULT_CONFIG_PATH: PathBuf = xdg::BaseDirectories::with_prefix("todust")
         .expect("can not read xdg base directories")
         .place_config_file("config.toml")
         .expect("can not place config file");
-    static ref DEFAULT_CONFIG_PATH_STRING: &'static str = DEFAULT_CONFIG_PATH
-        .to_str()
-        .expect("can not convert xdg config file path to string");
 }
 
 /// Very basic todo cli tool that supports multiline todos.
 #[derive(StructOpt, Debug)]
 #[structopt(
-    settings = &[SubcommandRequiredElseHelp],
+    settings = &[ArgRequiredElseHelp],
     global_settings = &[ColoredHelp, VersionlessSubcommands, NextLineHelp, GlobalVersion]
 )]
 pub(super) struct Opt {
@@ -54,14 +64,69 @@ pub(super) struct Opt {
         long = "config_path",
         global = true,
         value_name = "path",
-        default_value = &DEFAULT_CONFIG_PATH_STRING,
+        default_value_os = DEFAULT_CONFIG_PATH.as_os_str(),
+        required = false,
+        parse(from_os_str),
         env = "TODUST_CONFIG_PATH"
     )]
     pub(super) config_path: PathBuf,
 
+    /// Only print what would be changed without writing to the datadir,
+    /// running git or sending webhooks
+    #[structopt(long = "dry_run", global = true)]
+    pub(super) dry_run: bool,
+
+    /// Do not run the hooks configured for added, finished and moved entries
+    #[structopt(long = "no_hooks", global = true)]
+    pub(super) no_hooks: bool,
+
+    /// Parse the index files every time they are needed instead of keeping
+    /// them in memory, for debugging
+    #[structopt(long = "no_index_cache", global = true)]
+    pub(super) no_index_cache: bool,
+
+    /// Do not warn when the datadir was not synced for longer than
+    /// warn_stale_after
+    #[structopt(long = "no_stale_check", global = true)]
+    pub(super) no_stale_check: bool,
+
+    /// Move broken store settings aside and write the default settings
+    /// without asking
+    #[structopt(long = "repair", global = true)]
+    pub(super) repair: bool,
+
+    /// Do not print the header naming the datadir and project when they
+    /// are not the defaults, and the id and uuid of entries created with add
+    #[structopt(short = "q", long = "quiet", global = true)]
+    pub(super) quiet: bool,
+
+    /// When to color the output
+    #[structopt(
+        long = "color",
+        global = true,
+        value_name = "when",
+        default_value = "auto",
+        possible_values = &["auto", "always", "never"]
+    )]
+    pub(super) color: ColorChoice,
+
+    /// How the borders of tables are drawn. Overrides the table_style from
+    /// the config
+    #[structopt(
+        long = "table_style",
+        global = true,
+        value_name = "style",
+        possible_values = &["plain", "borders", "ascii"]
+    )]
+    pub(super) table_style: Option<TableStyle>,
+
+    /// Print the version of the --porcelain output formats and exit
+    #[structopt(long = "porcelain_version")]
+    pub(super) porcelain_version: bool,
+
     /// Subcommand to run
     #[structopt(subcommand)]
-    pub(super) cmd: SubCommand,
+    pub(super) cmd: Option<SubCommand>,
 }
 
 #[derive(StructOpt, Debug)]
@@ -71,7 +136,9 @@ pub(super) struct DatadirOpt {
         short = "d",
         long = "datadir",
         value_name = "path",
-        default_value = &DEFAULT_DATADIR_STRING,
+        default_value_os = DEFAULT_DATADIR.as_os_str(),
+        required = false,
+        parse(from_os_str),
         env = "TODUST_DATADIR"
     )]
     pub(super) datadir: PathBuf,
@@ -90,6 +157,87 @@ pub(super) struct ProjectOpt {
     pub(super) project: String,
 }
 
+#[derive(StructOpt, Debug)]
+pub(super) struct MultiProjectOpt {
+    /// Which project to show the entries of. Can be given multiple times to
+    /// show the entries of multiple projects together
+    #[structopt(
+        short = "p",
+        long = "project",
+        value_name = "project",
+        default_value = "default",
+        env = "TODUST_PROJECT",
+        number_of_values = 1
+    )]
+    pub(super) projects: Vec<String>,
+}
+
+impl MultiProjectOpt {
+    /// The selected projects in the order they were given without
+    /// duplicates.
+    pub(super) fn projects(&self) -> Vec<String> {
+        let mut projects: Vec<String> = Vec::new();
+        for project in &self.projects {
+            if !projects.contains(project) {
+                projects.push(project.clone());
+            }
+        }
+
+        projects
+    }
+}
+
+#[derive(StructOpt, Debug)]
+pub(super) struct PagerOpt {
+    /// Print long output directly instead of showing it in a pager
+    #[structopt(long = "no_pager")]
+    pub(super) no_pager: bool,
+}
+
+#[derive(StructOpt, Debug)]
+pub(super) struct TableOpt {
+    /// Dont limit the table to the width of the terminal. Useful when piping
+    /// the output to a pager.
+    #[structopt(short = "w", long = "wide")]
+    pub(super) wide: bool,
+
+    /// Only show dates without how far they are from today. Useful for
+    /// scripts that parse the output.
+    #[structopt(long = "raw_dates")]
+    pub(super) raw_dates: bool,
+}
+
+/// Reference to an entry either by its id in the list of active entries of a
+/// project or directly by its uuid.
+#[derive(Debug)]
+pub(super) enum EntryRef {
+    Id(usize),
+    Uuid(Uuid),
+}
+
+impl FromStr for EntryRef {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Ok(id) = s.parse() {
+            return Ok(EntryRef::Id(id));
+        }
+
+        parse_uuid(s)
+            .map(EntryRef::Uuid)
+            .map_err(|_| format!("{} is neither a valid id nor a valid uuid", s))
+    }
+}
+
+fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    let mut split = s.splitn(2, '=');
+
+    match (split.next(), split.next()) {
+        (Some(key), Some(value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("{} is not in the format key=value", s)),
+    }
+}
+
 /// Available subcommands in the application
 #[derive(StructOpt, Debug)]
 pub(super) enum SubCommand {
@@ -101,6 +249,11 @@ pub(super) enum SubCommand {
     #[structopt(name = "cleanup")]
     Cleanup(CleanupSubCommandOpts),
 
+    /// Move done todos that were finished before a day out of the index into
+    /// the archive so listing todos stays fast
+    #[structopt(name = "archive")]
+    Archive(ArchiveSubCommandOpts),
+
     /// Print formatted todos
     #[structopt(name = "print")]
     Print(PrintSubCommandOpts),
@@ -118,7 +271,10 @@ pub(super) enum SubCommand {
     Edit(EditSubCommandOpts),
 
     /// Move entry from current project to target project
-    #[structopt(name = "move")]
+    #[structopt(
+        name = "move",
+        usage = "todust move [FLAGS] [OPTIONS] <ids>... <project>"
+    )]
     Move(MoveSubCommandOpts),
 
     // FIXME: Disable project flag in this subcommand as it doesnt make sense here.
@@ -127,13 +283,104 @@ pub(super) enum SubCommand {
     Projects(ProjectsSubCommandOpts),
 
     /// Set due date for entry
-    #[structopt(name = "due")]
+    #[structopt(
+        name = "due",
+        usage = "todust due [FLAGS] [OPTIONS] <ids>... <due_date>"
+    )]
     Due(DueSubCommandOpts),
 
+    /// Focus an entry of the project or print the focused entry
+    #[structopt(name = "focus")]
+    Focus(FocusSubCommandOpts),
+
+    /// Export entries as a task list
+    #[structopt(name = "export")]
+    Export(ExportSubCommandOpts),
+
+    /// Import entries from a task list that was exported before or written by
+    /// hand
+    #[structopt(name = "import")]
+    Import(ImportSubCommandOpts),
+
+    /// Normalize the line endings and whitespace of entry texts and run the
+    /// configured formatter over them
+    #[structopt(name = "tidy")]
+    Tidy(TidySubCommandOpts),
+
     /// Generate shell completion for todust
     #[structopt(name = "completion")]
     Completion(CompletionSubCommandOpts),
 
+    /// Generate a digest of the last period
+    #[structopt(name = "digest")]
+    Digest(DigestSubCommandOpts),
+
+    /// Print the entries finished in a date range grouped by day
+    #[structopt(name = "worklog")]
+    Worklog(WorklogSubCommandOpts),
+
+    /// List available entry templates
+    #[structopt(name = "templates")]
+    Templates(TemplatesSubCommandOpts),
+
+    /// Print the version of todust and information about the store
+    #[structopt(name = "info")]
+    Info(InfoSubCommandOpts),
+
+    /// List and merge entries that were changed on multiple machines at the
+    /// same time
+    #[structopt(name = "conflicts")]
+    Conflicts(ConflictsSubCommandOpts),
+
+    /// Change settings of a single project
+    #[structopt(name = "project")]
+    Project(ProjectSubCommandOpts),
+
+    /// Pull the changes of the other machines into the datadir and push the
+    /// local ones
+    #[structopt(name = "sync")]
+    Sync(SyncSubCommandOpts),
+
+    /// Print the active and done entries per project from the daily
+    /// snapshots
+    #[structopt(name = "stats")]
+    Stats(StatsSubCommandOpts),
+
+    /// Mark done entries as active again. The ids are the ones of list
+    /// --done
+    #[structopt(name = "reopen")]
+    Reopen(ReopenSubCommandOpts),
+
+    /// Mark a single done entry as active again after showing it and asking
+    /// for confirmation like done does. The id is the one of list --done
+    #[structopt(name = "undone")]
+    Undone(UndoneSubCommandOpts),
+
+    /// List the projects recently used on this machine, the most recent
+    /// first
+    #[structopt(name = "recent")]
+    Recent(RecentSubCommandOpts),
+
+    /// Use a project in this terminal session when neither --project nor
+    /// TODUST_PROJECT is given. Without a name one of the recent projects is
+    /// picked
+    #[structopt(name = "switch")]
+    Switch(SwitchSubCommandOpts),
+
+    /// Go through the active todos of the default project and move each to
+    /// the project it belongs to
+    #[structopt(name = "triage")]
+    Triage(TriageSubCommandOpts),
+
+    /// Show which web token changed which entry through the webservice
+    #[structopt(name = "audit")]
+    Audit(AuditSubCommandOpts),
+
+    /// Delete an entry for good instead of marking it as done. The text is
+    /// removed and the entry is not shown anymore
+    #[structopt(name = "delete")]
+    Delete(DeleteSubCommandOpts),
+
     /// Launch webservice
     #[structopt(name = "web")]
     Web(WebSubCommandOpts),
@@ -149,8 +396,204 @@ pub(super) struct AddSubCommandOpts {
     pub(super) project_opt: ProjectOpt,
 
     /// Text of the entry
-    #[structopt(index = 1, value_name = "text")]
+    #[structopt(
+        index = 1,
+        value_name = "text",
+        conflicts_with_all = &["template", "from-file"]
+    )]
     pub(super) text: Option<String>,
+
+    /// Create the entry from the template with the given name. Templates are
+    /// stored in the templates folder of the datadir.
+    #[structopt(
+        short = "t",
+        long = "template",
+        value_name = "name",
+        conflicts_with = "from-file"
+    )]
+    pub(super) template: Option<String>,
+
+    /// Variable that is passed to the template. Can be given multiple times.
+    #[structopt(
+        long = "var",
+        value_name = "key=value",
+        number_of_values = 1,
+        requires = "template",
+        parse(try_from_str = parse_key_value)
+    )]
+    pub(super) vars: Vec<(String, String)>,
+
+    /// Open the rendered template in the editor before creating the entry
+    #[structopt(short = "e", long = "edit", requires = "template")]
+    pub(super) edit: bool,
+
+    /// Tag the entry, for example with urgent. Can be given multiple times.
+    /// Entries created from a file all get the tags
+    #[structopt(
+        long = "tag",
+        value_name = "tag",
+        number_of_values = 1,
+        parse(try_from_str = tags::parse_tag)
+    )]
+    pub(super) tags: Vec<String>,
+
+    /// Create multiple entries from the given file. Use - to read from stdin
+    #[structopt(long = "from_file", value_name = "path", parse(from_os_str))]
+    pub(super) from_file: Option<PathBuf>,
+
+    /// How the file is split into entries. Either one entry per non-empty
+    /// line (default) or one entry per asciidoc section (== heading)
+    #[structopt(
+        long = "split",
+        value_name = "mode",
+        possible_values = &["lines", "sections"],
+        requires = "from-file"
+    )]
+    pub(super) split: Option<SplitMode>,
+
+    /// Only print the uuid of each created entry for use in scripts
+    #[structopt(long = "porcelain", conflicts_with = "quiet")]
+    pub(super) porcelain: bool,
+
+    /// Add the entry to a project that does not exist yet without asking
+    #[structopt(short = "y", long = "yes")]
+    pub(super) yes: bool,
+}
+
+/// When the output is colored
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) enum ColorChoice {
+    /// Only when writing to a terminal and `NO_COLOR` is not set.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(format!("unknown color choice {}", s)),
+        }
+    }
+}
+
+impl ColorChoice {
+    pub(super) fn enabled(self, interactivity: Interactivity) -> bool {
+        match self {
+            ColorChoice::Auto => {
+                interactivity.is_styled() && std::env::var_os("NO_COLOR").is_none()
+            }
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+/// Which entries are listed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(super) enum ListState {
+    #[default]
+    Active,
+    Done,
+    All,
+}
+
+impl FromStr for ListState {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "active" => Ok(ListState::Active),
+            "done" => Ok(ListState::Done),
+            "all" => Ok(ListState::All),
+            _ => Err(format!("unknown list state {}", s)),
+        }
+    }
+}
+
+/// How a file is split into entries
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) enum SplitMode {
+    #[default]
+    Lines,
+    Sections,
+}
+
+impl FromStr for SplitMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lines" => Ok(SplitMode::Lines),
+            "sections" => Ok(SplitMode::Sections),
+            _ => Err(format!("unknown split mode {}", s)),
+        }
+    }
+}
+
+/// Options for the templates subcommand
+#[derive(StructOpt, Debug)]
+pub(super) struct TemplatesSubCommandOpts {
+    #[structopt(flatten)]
+    pub(super) datadir_opt: DatadirOpt,
+}
+
+/// Output formats of the info subcommand
+#[derive(Debug)]
+pub(super) enum InfoFormat {
+    Table,
+    Json,
+}
+
+impl FromStr for InfoFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "table" => Ok(InfoFormat::Table),
+            "json" => Ok(InfoFormat::Json),
+            _ => Err(format!("unknown info format {}", s)),
+        }
+    }
+}
+
+/// Options for the conflicts subcommand
+#[derive(StructOpt, Debug)]
+pub(super) struct ConflictsSubCommandOpts {
+    #[structopt(flatten)]
+    pub(super) datadir_opt: DatadirOpt,
+
+    /// Ask which value to keep for every field that differs and write the
+    /// merged metadata
+    #[structopt(short = "r", long = "resolve")]
+    pub(super) resolve: bool,
+
+    /// Also list the conflicts that were detected on this machine before
+    #[structopt(long = "history")]
+    pub(super) history: bool,
+}
+
+/// Options for the info subcommand
+#[derive(StructOpt, Debug)]
+pub(super) struct InfoSubCommandOpts {
+    #[structopt(flatten)]
+    pub(super) datadir_opt: DatadirOpt,
+
+    /// Format of the printed information
+    #[structopt(
+        short = "f",
+        long = "format",
+        value_name = "format",
+        default_value = "table",
+        possible_values = &["table", "json"]
+    )]
+    pub(super) format: InfoFormat,
 }
 
 /// Options for the cleanup subcommand
@@ -159,8 +602,46 @@ pub(super) struct CleanupSubCommandOpts {
     #[structopt(flatten)]
     pub(super) datadir_opt: DatadirOpt,
 
+    /// List every affected uuid and path
+    #[structopt(short = "v", long = "verbose")]
+    pub(super) verbose: bool,
+
+    /// Ask for every group of projects whose names only differ by whitespace
+    /// or invisible characters if it should be merged into one project
+    #[structopt(long = "merge_similar_projects")]
+    pub(super) merge_similar_projects: bool,
+
+    /// Only merge index fragments that are older than this many days and
+    /// keep the recent ones. Syncing multiple machines over git conflicts
+    /// less if recent fragments are kept
+    #[structopt(long = "keep_days", value_name = "days")]
+    pub(super) keep_days: Option<u32>,
+
+    /// Do not write a backup of the datadir before the cleanup even if
+    /// auto_backup_before_destructive is set
+    #[structopt(long = "no_backup")]
+    pub(super) no_backup: bool,
+}
+
+/// Options for the archive subcommand
+#[derive(StructOpt, Debug)]
+pub(super) struct ArchiveSubCommandOpts {
     #[structopt(flatten)]
-    pub(super) project_opt: ProjectOpt,
+    pub(super) datadir_opt: DatadirOpt,
+
+    /// Archive the todos finished before this day. Has to be a date in
+    /// format 2019-12-24
+    #[structopt(long = "before", value_name = "date")]
+    pub(super) before: NaiveDate,
+
+    /// List every archived uuid
+    #[structopt(short = "v", long = "verbose")]
+    pub(super) verbose: bool,
+
+    /// Do not write a backup of the datadir before archiving even if
+    /// auto_backup_before_destructive is set
+    #[structopt(long = "no_backup")]
+    pub(super) no_backup: bool,
 }
 
 /// Options for done subcommand
@@ -175,6 +656,182 @@ pub(super) struct DoneSubCommandOpts {
     /// Id of the task that should be marked as done
     #[structopt(index = 1, value_name = "id")]
     pub(super) entry_id: usize,
+
+    /// Carry unchecked checklist items over to a new entry without asking
+    #[structopt(long = "carry_over")]
+    pub(super) carry_over: bool,
+
+    /// Finish the entry without asking for confirmation
+    #[structopt(short = "y", long = "yes")]
+    pub(super) yes: bool,
+}
+
+/// Options for reopen subcommand
+#[derive(StructOpt, Debug)]
+pub(super) struct ReopenSubCommandOpts {
+    #[structopt(flatten)]
+    pub(super) datadir_opt: DatadirOpt,
+
+    #[structopt(flatten)]
+    pub(super) project_opt: ProjectOpt,
+
+    /// Ids of the done tasks as listed by list --done
+    #[structopt(index = 1, value_name = "id", required = true, min_values = 1)]
+    pub(super) entry_ids: Vec<usize>,
+}
+
+/// Options for undone subcommand
+#[derive(StructOpt, Debug)]
+pub(super) struct UndoneSubCommandOpts {
+    #[structopt(flatten)]
+    pub(super) datadir_opt: DatadirOpt,
+
+    #[structopt(flatten)]
+    pub(super) project_opt: ProjectOpt,
+
+    /// Id of the done task as listed by list --done or its uuid. When a uuid
+    /// is given the project is ignored
+    #[structopt(index = 1, value_name = "id|uuid")]
+    pub(super) entry_ref: EntryRef,
+
+    /// Mark the entry as active without asking for confirmation
+    #[structopt(short = "y", long = "yes")]
+    pub(super) yes: bool,
+}
+
+/// Options for triage subcommand
+#[derive(StructOpt, Debug)]
+pub(super) struct TriageSubCommandOpts {
+    #[structopt(flatten)]
+    pub(super) datadir_opt: DatadirOpt,
+
+    /// Move todos to projects that do not exist yet without asking
+    #[structopt(short = "y", long = "yes")]
+    pub(super) yes: bool,
+}
+
+/// Options for audit subcommand
+#[derive(StructOpt, Debug)]
+pub(super) struct AuditSubCommandOpts {
+    #[structopt(flatten)]
+    pub(super) datadir_opt: DatadirOpt,
+
+    /// Only show changes of the entry with this uuid
+    #[structopt(long = "uuid", value_name = "uuid", parse(try_from_str = parse_uuid))]
+    pub(super) uuid: Option<Uuid>,
+
+    /// Only show changes of entries in this project
+    #[structopt(long = "project", value_name = "project")]
+    pub(super) project: Option<String>,
+
+    /// Only show changes from the last duration, for example 7d or 2w
+    #[structopt(long = "since", value_name = "duration", parse(try_from_str = parse_duration))]
+    pub(super) since: Option<Duration>,
+}
+
+/// Options for delete subcommand
+#[derive(StructOpt, Debug)]
+pub(super) struct DeleteSubCommandOpts {
+    #[structopt(flatten)]
+    pub(super) datadir_opt: DatadirOpt,
+
+    #[structopt(flatten)]
+    pub(super) project_opt: ProjectOpt,
+
+    /// Id of the active task or its uuid. When a uuid is given the project
+    /// is ignored and done tasks can be deleted too
+    #[structopt(index = 1, value_name = "id|uuid")]
+    pub(super) entry_ref: EntryRef,
+
+    /// Delete the entry without asking for confirmation
+    #[structopt(short = "y", long = "yes")]
+    pub(super) yes: bool,
+
+    /// Do not write a backup of the datadir before deleting even if
+    /// auto_backup_before_destructive is set
+    #[structopt(long = "no_backup")]
+    pub(super) no_backup: bool,
+}
+
+/// Options for recent subcommand
+#[derive(StructOpt, Debug)]
+pub(super) struct RecentSubCommandOpts {
+    #[structopt(flatten)]
+    pub(super) datadir_opt: DatadirOpt,
+}
+
+/// Options for switch subcommand
+#[derive(StructOpt, Debug)]
+pub(super) struct SwitchSubCommandOpts {
+    #[structopt(flatten)]
+    pub(super) datadir_opt: DatadirOpt,
+
+    /// Project to switch to
+    #[structopt(index = 1, value_name = "project")]
+    pub(super) name: Option<String>,
+
+    /// Go back to the default project in this terminal session
+    #[structopt(long = "clear", conflicts_with = "name")]
+    pub(super) clear: bool,
+}
+
+/// Options for project subcommand
+#[derive(StructOpt, Debug)]
+pub(super) struct ProjectSubCommandOpts {
+    #[structopt(subcommand)]
+    pub(super) cmd: ProjectCommand,
+}
+
+#[derive(StructOpt, Debug)]
+pub(super) enum ProjectCommand {
+    /// List the project before all others in projects and on the index page
+    /// on this machine
+    #[structopt(name = "favorite")]
+    Favorite(ProjectNameOpts),
+
+    /// Stop listing the project before all others on this machine
+    #[structopt(name = "unfavorite")]
+    Unfavorite(ProjectNameOpts),
+}
+
+#[derive(StructOpt, Debug)]
+pub(super) struct ProjectNameOpts {
+    #[structopt(flatten)]
+    pub(super) datadir_opt: DatadirOpt,
+
+    /// Name of the project
+    #[structopt(name = "name", index = 1, value_name = "project")]
+    pub(super) project: String,
+}
+
+/// Options for sync subcommand
+#[derive(StructOpt, Debug)]
+pub(super) struct SyncSubCommandOpts {
+    #[structopt(flatten)]
+    pub(super) datadir_opt: DatadirOpt,
+}
+
+/// Options for stats subcommand
+#[derive(StructOpt, Debug)]
+pub(super) struct StatsSubCommandOpts {
+    #[structopt(flatten)]
+    pub(super) datadir_opt: DatadirOpt,
+
+    /// Print the totals of every snapshot instead of the projects of the
+    /// latest one
+    #[structopt(long = "history")]
+    pub(super) history: bool,
+
+    /// Only count the entries of this project
+    #[structopt(short = "p", long = "project", value_name = "project")]
+    pub(super) project: Option<String>,
+
+    /// How many of the latest snapshots are printed with --history
+    #[structopt(long = "limit", value_name = "days", default_value = "30")]
+    pub(super) limit: usize,
+
+    #[structopt(flatten)]
+    pub(super) table_opt: TableOpt,
 }
 
 /// Options for edit subcommand
@@ -190,9 +847,20 @@ pub(super) struct EditSubCommandOpts {
     #[structopt(index = 1, value_name = "id")]
     pub(super) entry_id: usize,
 
-    /// Update started time of todo to current time if specified
-    #[structopt(short = "u", long = "update_time")]
-    pub(super) update_time: bool,
+    /// Reset the started time of the todo to the current time. The age of the
+    /// todo counts from when it was created and is kept. A confirmation is
+    /// asked for unless --yes is given
+    #[structopt(long = "restart")]
+    pub(super) restart: bool,
+
+    /// Edit the metadata (started time and due date) as toml instead of the
+    /// text of the todo
+    #[structopt(short = "m", long = "metadata", conflicts_with = "restart")]
+    pub(super) metadata: bool,
+
+    /// Save the changed text without asking after showing the diff
+    #[structopt(short = "y", long = "yes")]
+    pub(super) yes: bool,
 }
 
 /// Options for list subcommand
@@ -202,27 +870,74 @@ pub(super) struct ListSubCommandOpts {
     pub(super) datadir_opt: DatadirOpt,
 
     #[structopt(flatten)]
-    pub(super) project_opt: ProjectOpt,
-}
+    pub(super) projects_opt: MultiProjectOpt,
 
-/// Options for merge subcommand
-#[derive(StructOpt, Debug)]
-pub(super) struct MergeIndexFilesSubCommandOpts {
-    /// Path to the first input index file
-    #[structopt(index = 1, value_name = "index_file_path")]
-    pub(super) input_first: PathBuf,
+    /// Only list entries that were not viewed or changed in the given number
+    /// of days (default 30)
+    #[structopt(long = "forgotten", value_name = "days")]
+    pub(super) forgotten: Option<Option<i64>>,
 
-    /// Path to the second input index file
-    #[structopt(index = 2, value_name = "index_file_path")]
-    pub(super) input_second: PathBuf,
+    /// Only list entries that were created by the given source (cli, web,
+    /// api, import or webhook). A single webhook can be selected with
+    /// webhook:name
+    #[structopt(long = "source", value_name = "source")]
+    pub(super) source: Option<String>,
 
-    /// Path to the output index file
-    #[structopt(index = 3, value_name = "index_file_path")]
-    pub(super) output: PathBuf,
+    /// Only list entries that have the tag. Can be given multiple times to
+    /// only list entries that have all of the tags
+    #[structopt(
+        long = "tag",
+        value_name = "tag",
+        number_of_values = 1,
+        parse(try_from_str = tags::parse_tag)
+    )]
+    pub(super) tags: Vec<String>,
 
-    /// Force overwriting output file
-    #[structopt(short = "f", long = "force")]
-    pub(super) force: bool,
+    /// Only list entries that have a due date, the earliest due date first
+    /// instead of grouped by project
+    #[structopt(long = "due_only")]
+    pub(super) due_only: bool,
+
+    /// Print one tab separated record per entry in a stable format for
+    /// scripts. See --porcelain_version.
+    #[structopt(long = "porcelain")]
+    pub(super) porcelain: bool,
+
+    /// Which entries to list. Done entries are listed with the most recently
+    /// finished first and have their own ids that reopen uses
+    #[structopt(
+        long = "state",
+        value_name = "state",
+        default_value = "active",
+        possible_values = &["active", "done", "all"]
+    )]
+    pub(super) state: ListState,
+
+    /// Only list done entries. Same as --state done
+    #[structopt(long = "done", conflicts_with = "state")]
+    pub(super) done: bool,
+
+    /// Keep showing the list and update it whenever the entries change, for
+    /// example by another todust or a sync. How often the store is checked
+    /// is set with watch_interval_seconds in the config. Stop with ctrl-c
+    #[structopt(long = "watch", conflicts_with = "porcelain")]
+    pub(super) watch: bool,
+
+    #[structopt(flatten)]
+    pub(super) table_opt: TableOpt,
+
+    #[structopt(flatten)]
+    pub(super) pager_opt: PagerOpt,
+}
+
+impl ListSubCommandOpts {
+    pub(super) fn state(&self) -> ListState {
+        if self.done {
+            ListState::Done
+        } else {
+            self.state
+        }
+    }
 }
 
 /// Options for move subcommand
@@ -234,13 +949,33 @@ pub(super) struct MoveSubCommandOpts {
     #[structopt(flatten)]
     pub(super) project_opt: ProjectOpt,
 
-    /// Id of the task
-    #[structopt(index = 1, value_name = "id")]
-    pub(super) entry_id: usize,
+    /// Ids of the tasks to move in the order they are shown followed by the
+    /// target project name
+    #[structopt(index = 1, value_name = "ids", required = true, min_values = 2)]
+    pub(super) ids_and_project: Vec<String>,
+
+    /// Move the entries to the project in another store from the stores
+    /// table of the config instead
+    #[structopt(long = "to_store", value_name = "name")]
+    pub(super) to_store: Option<String>,
 
-    /// Target project name
-    #[structopt(index = 2, value_name = "project")]
-    pub(super) target_project: String,
+    /// Move the entries to a project that does not exist yet without asking
+    #[structopt(short = "y", long = "yes")]
+    pub(super) yes: bool,
+}
+
+impl MoveSubCommandOpts {
+    /// The ids of all tasks to move in the order they were given.
+    pub(super) fn entry_ids(&self) -> Result<Vec<usize>, clap::Error> {
+        leading_ids(&self.ids_and_project)
+    }
+
+    /// The project the tasks are moved to.
+    pub(super) fn target_project(&self) -> &str {
+        self.ids_and_project
+            .last()
+            .expect("clap requires the target project")
+    }
 }
 
 /// Options for print subcommand
@@ -250,15 +985,79 @@ pub(super) struct PrintSubCommandOpts {
     pub(super) datadir_opt: DatadirOpt,
 
     #[structopt(flatten)]
-    pub(super) project_opt: ProjectOpt,
+    pub(super) projects_opt: MultiProjectOpt,
 
-    /// Id of the task. If none is given all tasks will be printed
-    #[structopt(index = 1, value_name = "id")]
-    pub(super) entry_id: Option<usize>,
+    /// Id or uuid of the task. If none is given all tasks will be printed.
+    /// When a uuid is given the project is ignored. An id can only be used
+    /// with a single project.
+    #[structopt(index = 1, value_name = "id|uuid")]
+    pub(super) entry_ref: Option<EntryRef>,
 
     /// Dont print done tasks if specified
     #[structopt(short = "n", long = "no_done")]
     pub(super) no_done: bool,
+
+    /// Print the tasks of all projects instead of just the current project
+    #[structopt(short = "a", long = "all_projects", conflicts_with = "entry-ref")]
+    pub(super) all_projects: bool,
+
+    /// Also print tasks of ignored projects when printing all projects
+    #[structopt(long = "include_ignored", requires = "all-projects")]
+    pub(super) include_ignored: bool,
+
+    /// Also print done tasks that were moved to the archive
+    #[structopt(
+        long = "include_archived",
+        conflicts_with_all = &["entry-ref", "no-done"]
+    )]
+    pub(super) include_archived: bool,
+
+    /// Only print tasks that have the tag. Can be given multiple times to
+    /// only print tasks that have all of the tags
+    #[structopt(
+        long = "tag",
+        value_name = "tag",
+        number_of_values = 1,
+        conflicts_with = "entry-ref",
+        parse(try_from_str = tags::parse_tag)
+    )]
+    pub(super) tags: Vec<String>,
+
+    /// Format of the printed tasks. Html is rendered with asciidoctor
+    #[structopt(
+        long = "format",
+        value_name = "format",
+        default_value = "asciidoc",
+        possible_values = &["asciidoc", "html"]
+    )]
+    pub(super) format: PrintFormat,
+
+    /// Render html with asciidoctor instead of using the html cached in the
+    /// state directory
+    #[structopt(long = "no_cache")]
+    pub(super) no_cache: bool,
+
+    #[structopt(flatten)]
+    pub(super) pager_opt: PagerOpt,
+}
+
+/// Output formats of print
+#[derive(Debug, Clone, Copy)]
+pub(super) enum PrintFormat {
+    Asciidoc,
+    Html,
+}
+
+impl FromStr for PrintFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "asciidoc" => Ok(PrintFormat::Asciidoc),
+            "html" => Ok(PrintFormat::Html),
+            _ => Err(format!("unknown print format {}", s)),
+        }
+    }
 }
 
 /// Options for projects subcommand
@@ -268,7 +1067,7 @@ pub(super) struct ProjectsSubCommandOpts {
     pub(super) datadir_opt: DatadirOpt,
 
     #[structopt(flatten)]
-    pub(super) project_opt: ProjectOpt,
+    pub(super) table_opt: TableOpt,
 
     /// Also print out projects without active todos. If not specified inactive
     /// projects will not be listed.
@@ -279,6 +1078,131 @@ pub(super) struct ProjectsSubCommandOpts {
     /// Usefully for scripts.
     #[structopt(long = "simple")]
     pub(super) simple: bool,
+
+    /// Print one tab separated record per project in a stable format for
+    /// scripts. See --porcelain_version.
+    #[structopt(long = "porcelain", conflicts_with = "simple")]
+    pub(super) porcelain: bool,
+
+    /// Also list projects that are ignored in the config or store settings
+    #[structopt(long = "include_ignored")]
+    pub(super) include_ignored: bool,
+}
+
+/// Format of exported and imported task lists
+#[derive(Debug, Clone, Copy)]
+pub(super) enum TaskListFormat {
+    Markdown,
+}
+
+impl FromStr for TaskListFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "markdown" => Ok(TaskListFormat::Markdown),
+            _ => Err(format!("unknown task list format {}", s)),
+        }
+    }
+}
+
+/// Formats of the export. Task lists can also be imported again.
+#[derive(Debug, Clone, Copy)]
+pub(super) enum ExportFormat {
+    TaskList(TaskListFormat),
+
+    /// Single html page with the rendered entries for archiving a project.
+    HtmlBundle,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "html-bundle" => Ok(ExportFormat::HtmlBundle),
+            _ => s.parse().map(ExportFormat::TaskList),
+        }
+    }
+}
+
+/// Options for export subcommand
+#[derive(StructOpt, Debug)]
+pub(super) struct ExportSubCommandOpts {
+    #[structopt(flatten)]
+    pub(super) datadir_opt: DatadirOpt,
+
+    #[structopt(flatten)]
+    pub(super) project_opt: ProjectOpt,
+
+    /// Format of the export. html-bundle is a single html page with the
+    /// rendered entries and the stylesheet that needs nothing else to be
+    /// viewed
+    #[structopt(
+        long = "format",
+        value_name = "format",
+        possible_values = &["markdown", "html-bundle"]
+    )]
+    pub(super) format: ExportFormat,
+
+    /// Write the export to this file instead of stdout
+    #[structopt(short = "o", long = "output", value_name = "path", parse(from_os_str))]
+    pub(super) output: Option<PathBuf>,
+
+    /// Dont export done tasks if specified
+    #[structopt(short = "n", long = "no_done")]
+    pub(super) no_done: bool,
+
+    /// Export the tasks of all projects instead of just the current project
+    #[structopt(short = "a", long = "all_projects")]
+    pub(super) all_projects: bool,
+
+    /// Also export tasks of ignored projects when exporting all projects
+    #[structopt(long = "include_ignored", requires = "all-projects")]
+    pub(super) include_ignored: bool,
+}
+
+/// Options for tidy subcommand
+#[derive(StructOpt, Debug)]
+pub(super) struct TidySubCommandOpts {
+    #[structopt(flatten)]
+    pub(super) datadir_opt: DatadirOpt,
+
+    #[structopt(flatten)]
+    pub(super) project_opt: ProjectOpt,
+
+    /// Tidy the entries of all projects instead of just the current project
+    #[structopt(short = "a", long = "all_projects")]
+    pub(super) all_projects: bool,
+
+    /// Also tidy entries of ignored projects when tidying all projects
+    #[structopt(long = "include_ignored", requires = "all-projects")]
+    pub(super) include_ignored: bool,
+
+    /// Only list the entry files that would change and exit with 1 if there
+    /// are any. Useful in a pre-push hook
+    #[structopt(long = "check")]
+    pub(super) check: bool,
+}
+
+/// Formats of the import.
+#[derive(Debug, Clone, Copy)]
+pub(super) enum ImportFormat {
+    TaskList(TaskListFormat),
+
+    /// Another datadir whose entries are merged into the store.
+    Datadir,
+}
+
+impl FromStr for ImportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "datadir" => Ok(ImportFormat::Datadir),
+            _ => s.parse().map(ImportFormat::TaskList),
+        }
+    }
 }
 
 /// Options for import subcommand
@@ -290,11 +1214,24 @@ pub(super) struct ImportSubCommandOpts {
     #[structopt(flatten)]
     pub(super) project_opt: ProjectOpt,
 
-    /// Path of the file/folder from which to import from
-    #[structopt(index = 1, value_name = "path")]
+    /// Path of the file from which to import from. Use - to read from stdin.
+    /// The path of another datadir with --format datadir
+    #[structopt(index = 1, value_name = "path", parse(from_os_str))]
     pub(super) from_path: PathBuf,
 
-    /// Import all projects instead of just the current project
+    /// Format of the import. datadir merges the entries of another datadir
+    /// into this one with their ids and timestamps. Entries that are in both
+    /// are taken from the datadir that changed them last
+    #[structopt(
+        long = "format",
+        value_name = "format",
+        possible_values = &["markdown", "datadir"]
+    )]
+    pub(super) format: ImportFormat,
+
+    /// Import all projects instead of just the current project. Tasks that
+    /// are not listed under a project heading always belong to the current
+    /// project.
     #[structopt(short = "a", long = "import_all")]
     pub(super) import_all: bool,
 }
@@ -308,13 +1245,67 @@ pub(super) struct DueSubCommandOpts {
     #[structopt(flatten)]
     pub(super) project_opt: ProjectOpt,
 
-    /// Id of the task for which the due date should be set
-    #[structopt(index = 1, value_name = "id")]
-    pub(super) entry_id: usize,
+    /// Ids of the tasks for which the due date should be set followed by when
+    /// they are due. Either a date like 2019-12-24 or a weekday like friday
+    /// for the next such day
+    #[structopt(index = 1, value_name = "ids", required = true, min_values = 2)]
+    pub(super) ids_and_due_date: Vec<String>,
+
+    /// Set due dates that are more than a year in the future without asking
+    #[structopt(short = "y", long = "yes")]
+    pub(super) yes: bool,
+}
+
+impl DueSubCommandOpts {
+    /// The ids of all tasks to set the due date for in the order they were
+    /// given.
+    pub(super) fn entry_ids(&self) -> Result<Vec<usize>, clap::Error> {
+        leading_ids(&self.ids_and_due_date)
+    }
+
+    /// When the tasks are due.
+    pub(super) fn due_date(&self) -> Result<DueDateArg, clap::Error> {
+        self.ids_and_due_date
+            .last()
+            .expect("clap requires the due date")
+            .parse()
+            .map_err(clap::Error::value_validation_auto)
+    }
+}
+
+/// Parse all but the last of the positional values as ids. The ids and the
+/// value after them are taken as a single list as clap stops filling a list
+/// in front of another positional after one value when an option like
+/// --project comes first.
+fn leading_ids(values: &[String]) -> Result<Vec<usize>, clap::Error> {
+    values[..values.len().saturating_sub(1)]
+        .iter()
+        .map(|id| {
+            id.parse().map_err(|err| {
+                clap::Error::value_validation_auto(format!("invalid id {:?}: {}", id, err))
+            })
+        })
+        .collect()
+}
 
-    /// When the task is due. Has to be date in format 2019-12-24
-    #[structopt(index = 2, value_name = "due_date")]
-    pub(super) due_date: NaiveDate,
+/// Options for focus subcommand
+#[derive(StructOpt, Debug)]
+pub(super) struct FocusSubCommandOpts {
+    #[structopt(flatten)]
+    pub(super) datadir_opt: DatadirOpt,
+
+    #[structopt(flatten)]
+    pub(super) project_opt: ProjectOpt,
+
+    /// Id or uuid of the task that should be focused. If none is given the
+    /// focused task is printed. When a uuid is given the task is focused in
+    /// its own project.
+    #[structopt(index = 1, value_name = "id|uuid")]
+    pub(super) entry_ref: Option<EntryRef>,
+
+    /// Remove the focus of the project
+    #[structopt(long = "clear", conflicts_with = "entry-ref")]
+    pub(super) clear: bool,
 }
 
 /// Options for completion subcommand
@@ -330,10 +1321,128 @@ pub(super) struct CompletionSubCommandOpts {
     pub(super) shell: Shell,
 
     /// Folder to where to save the generated file to
-    #[structopt(short = "d", long = "directory", value_name = "path")]
+    #[structopt(
+        short = "d",
+        long = "directory",
+        value_name = "path",
+        parse(from_os_str)
+    )]
     pub(super) directory: PathBuf,
 }
 
+/// Output formats of the digest
+#[derive(Debug)]
+pub(super) enum DigestFormat {
+    Asciidoc,
+    Html,
+}
+
+impl FromStr for DigestFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "asciidoc" => Ok(DigestFormat::Asciidoc),
+            "html" => Ok(DigestFormat::Html),
+            _ => Err(format!("unknown digest format {}", s)),
+        }
+    }
+}
+
+/// Options for the digest subcommand
+#[derive(StructOpt, Debug)]
+pub(super) struct DigestSubCommandOpts {
+    #[structopt(flatten)]
+    pub(super) datadir_opt: DatadirOpt,
+
+    /// Period the digest covers, for example 7d or 2w. The same period is
+    /// used to look ahead for upcoming due dates.
+    #[structopt(
+        long = "period",
+        value_name = "duration",
+        default_value = "7d",
+        parse(try_from_str = parse_duration)
+    )]
+    pub(super) period: Duration,
+
+    /// Format of the generated digest
+    #[structopt(
+        short = "f",
+        long = "format",
+        value_name = "format",
+        default_value = "html",
+        possible_values = &["asciidoc", "html"]
+    )]
+    pub(super) format: DigestFormat,
+
+    /// Write the digest to this file instead of stdout
+    #[structopt(short = "o", long = "output", value_name = "path", parse(from_os_str))]
+    pub(super) output: Option<PathBuf>,
+}
+
+/// Output formats of the worklog
+#[derive(Debug)]
+pub(super) enum WorklogFormat {
+    Asciidoc,
+    Json,
+    Text,
+}
+
+impl FromStr for WorklogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "asciidoc" => Ok(WorklogFormat::Asciidoc),
+            "json" => Ok(WorklogFormat::Json),
+            "text" => Ok(WorklogFormat::Text),
+            _ => Err(format!("unknown worklog format {}", s)),
+        }
+    }
+}
+
+/// Options for the worklog subcommand
+#[derive(StructOpt, Debug)]
+pub(super) struct WorklogSubCommandOpts {
+    #[structopt(flatten)]
+    pub(super) datadir_opt: DatadirOpt,
+
+    #[structopt(flatten)]
+    pub(super) project_opt: ProjectOpt,
+
+    /// Include the entries of all projects instead of just the current
+    /// project
+    #[structopt(short = "a", long = "all_projects")]
+    pub(super) all_projects: bool,
+
+    /// First day of the worklog. Has to be a date in format 2019-12-24
+    #[structopt(long = "from", value_name = "date", required_unless = "week")]
+    pub(super) from: Option<NaiveDate>,
+
+    /// Last day of the worklog. Defaults to today
+    #[structopt(long = "to", value_name = "date")]
+    pub(super) to: Option<NaiveDate>,
+
+    /// Cover the current week instead of --from and --to. The first day of
+    /// the week is set with week_starts_on in the config
+    #[structopt(short = "w", long = "week", conflicts_with_all = &["from", "to"])]
+    pub(super) week: bool,
+
+    /// Format of the generated worklog
+    #[structopt(
+        short = "f",
+        long = "format",
+        value_name = "format",
+        default_value = "asciidoc",
+        possible_values = &["asciidoc", "json", "text"]
+    )]
+    pub(super) format: WorklogFormat,
+
+    /// Write the worklog to this file instead of stdout
+    #[structopt(short = "o", long = "output", value_name = "path", parse(from_os_str))]
+    pub(super) output: Option<PathBuf>,
+}
+
 /// Options for the web subcommand
 #[derive(StructOpt, Debug)]
 pub(super) struct WebSubCommandOpts {
@@ -348,4 +1457,181 @@ pub(super) struct WebSubCommandOpts {
         default_value = "127.0.0.1:9216"
     )]
     pub(super) binding: SocketAddr,
+
+    /// Render entries and exports with asciidoctor on every request instead
+    /// of using the html cached in the state directory
+    #[structopt(long = "no_cache")]
+    pub(super) no_cache: bool,
+
+    /// Start even if another webservice is running for the same datadir, for
+    /// example to replace it without downtime
+    #[structopt(long = "force")]
+    pub(super) force: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<SubCommand, structopt::clap::Error> {
+        let args = std::iter::once("todust").chain(args.iter().copied());
+        Opt::from_iter_safe(args).map(|opt| opt.cmd.expect("no subcommand parsed"))
+    }
+
+    fn due(args: &[&str]) -> Result<(Vec<usize>, DueDateArg), structopt::clap::Error> {
+        let args = std::iter::once("due").chain(args.iter().copied());
+        match parse(&args.collect::<Vec<_>>())? {
+            SubCommand::Due(opt) => Ok((opt.entry_ids()?, opt.due_date()?)),
+            cmd => panic!("parsed wrong subcommand: {:?}", cmd),
+        }
+    }
+
+    fn move_entries(args: &[&str]) -> Result<(Vec<usize>, String), structopt::clap::Error> {
+        let args = std::iter::once("move").chain(args.iter().copied());
+        match parse(&args.collect::<Vec<_>>())? {
+            SubCommand::Move(opt) => Ok((opt.entry_ids()?, opt.target_project().to_string())),
+            cmd => panic!("parsed wrong subcommand: {:?}", cmd),
+        }
+    }
+
+    #[test]
+    fn due_single_id() {
+        let date = DueDateArg::Date(NaiveDate::from_ymd_opt(2026, 12, 1).unwrap());
+
+        assert_eq!(due(&["1", "2026-12-01"]).unwrap(), (vec![1], date));
+        assert_eq!(
+            due(&["-p", "batch", "1", "2026-12-01"]).unwrap(),
+            (vec![1], date)
+        );
+    }
+
+    #[test]
+    fn due_multiple_ids_keep_order() {
+        let friday = DueDateArg::Weekday(chrono::Weekday::Fri);
+
+        assert_eq!(
+            due(&["2", "5", "9", "friday"]).unwrap(),
+            (vec![2, 5, 9], friday)
+        );
+        assert_eq!(
+            due(&["-p", "batch", "2", "5", "9", "friday"]).unwrap(),
+            (vec![2, 5, 9], friday)
+        );
+    }
+
+    #[test]
+    fn due_invalid_date() {
+        assert!(due(&["1", "someday"]).is_err());
+        assert!(due(&["1", "2026-13-01"]).is_err());
+        assert!(due(&["2026-12-01", "1"]).is_err());
+        assert!(due(&["2026-12-01"]).is_err());
+        assert!(due(&["1"]).is_err());
+    }
+
+    #[test]
+    fn move_single_id() {
+        assert_eq!(
+            move_entries(&["1", "other"]).unwrap(),
+            (vec![1], "other".to_string())
+        );
+        assert_eq!(
+            move_entries(&["-p", "batch", "1", "other"]).unwrap(),
+            (vec![1], "other".to_string())
+        );
+    }
+
+    #[test]
+    fn move_multiple_ids_keep_order() {
+        assert_eq!(
+            move_entries(&["1", "3", "4", "target-project"]).unwrap(),
+            (vec![1, 3, 4], "target-project".to_string())
+        );
+        assert_eq!(
+            move_entries(&["-p", "batch", "1", "3", "4", "target-project"]).unwrap(),
+            (vec![1, 3, 4], "target-project".to_string())
+        );
+    }
+
+    #[test]
+    fn move_invalid_id() {
+        assert!(move_entries(&["one", "other"]).is_err());
+        assert!(move_entries(&["1", "two", "other"]).is_err());
+        assert!(move_entries(&["other"]).is_err());
+    }
+
+    #[test]
+    fn print_uuid() {
+        let uuid = Uuid::new_v4();
+
+        match parse(&["print", &uuid.to_string()]).unwrap() {
+            SubCommand::Print(opt) => match opt.entry_ref {
+                Some(EntryRef::Uuid(parsed)) => assert_eq!(parsed, uuid),
+                entry_ref => panic!("parsed wrong entry reference: {:?}", entry_ref),
+            },
+            cmd => panic!("parsed wrong subcommand: {:?}", cmd),
+        }
+    }
+
+    #[test]
+    fn print_id() {
+        match parse(&["print", "3"]).unwrap() {
+            SubCommand::Print(opt) => assert!(matches!(opt.entry_ref, Some(EntryRef::Id(3)))),
+            cmd => panic!("parsed wrong subcommand: {:?}", cmd),
+        }
+    }
+
+    #[test]
+    fn print_invalid_entry_ref() {
+        assert!(parse(&["print", "not-a-uuid"]).is_err());
+    }
+
+    #[test]
+    fn print_all_projects_without_entry_ref() {
+        match parse(&["print", "--all_projects"]).unwrap() {
+            SubCommand::Print(opt) => {
+                assert!(opt.all_projects);
+                assert!(opt.entry_ref.is_none());
+            }
+            cmd => panic!("parsed wrong subcommand: {:?}", cmd),
+        }
+
+        assert!(parse(&["print", "--all_projects", "3"]).is_err());
+    }
+
+    #[test]
+    fn list_and_print_take_multiple_projects() {
+        match parse(&["list", "-p", "work", "-p", "oncall", "--project", "work"]).unwrap() {
+            SubCommand::List(opt) => {
+                assert_eq!(opt.projects_opt.projects(), vec!["work", "oncall"])
+            }
+            cmd => panic!("parsed wrong subcommand: {:?}", cmd),
+        }
+
+        match parse(&["print", "-p", "work", "-p", "oncall"]).unwrap() {
+            SubCommand::Print(opt) => {
+                assert_eq!(opt.projects_opt.projects(), vec!["work", "oncall"])
+            }
+            cmd => panic!("parsed wrong subcommand: {:?}", cmd),
+        }
+    }
+
+    #[test]
+    fn mutators_take_a_single_project() {
+        for args in &[
+            vec!["add", "-p", "work", "-p", "oncall", "text"],
+            vec!["done", "-p", "work", "-p", "oncall", "1"],
+            vec!["move", "-p", "work", "-p", "oncall", "1", "other"],
+            vec!["due", "-p", "work", "-p", "oncall", "1", "2026-12-01"],
+        ] {
+            let err = parse(args).unwrap_err();
+
+            assert_eq!(
+                err.kind,
+                structopt::clap::ErrorKind::UnexpectedMultipleUsage,
+                "{:?}",
+                args
+            );
+            assert!(err.message.contains("--project"), "{}", err.message);
+        }
+    }
 }
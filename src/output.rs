@@ -0,0 +1,195 @@
+use crate::{
+    helper,
+    interactivity::Interactivity,
+};
+use anyhow::{
+    bail,
+    Context,
+    Error,
+};
+use log::warn;
+use std::{
+    env,
+    io::{
+        self,
+        Write,
+    },
+    process::Stdio,
+};
+
+/// Pager that is used when neither the config nor `$PAGER` name one. Quits
+/// right away if the output fits on one screen, keeps colors and does not
+/// clear the screen when quitting.
+const DEFAULT_PAGER: &str = "less -FRX";
+
+/// Pager command to use. Paging is disabled with `no_pager`, an empty `pager`
+/// in the config or `$PAGER` and when the output is not styled.
+pub(super) fn pager_command(
+    configured: Option<&str>,
+    no_pager: bool,
+    interactivity: Interactivity,
+) -> Option<String> {
+    if no_pager || !interactivity.is_styled() {
+        return None;
+    }
+
+    let command = match configured {
+        Some(command) => command.to_string(),
+        None => env::var("PAGER").unwrap_or_else(|_| DEFAULT_PAGER.to_string()),
+    };
+
+    Some(command).filter(|command| !command.trim().is_empty())
+}
+
+/// Print the output through the pager if there is one and the output does not
+/// fit on the terminal, like git does. Quitting the pager early or closing the
+/// pipe stdout is connected to is not an error.
+pub(super) fn print_paged(output: &str, pager: Option<&str>) -> Result<(), Error> {
+    let pager = pager.filter(|_| !fits_terminal(output));
+
+    if let Some(pager) = pager {
+        match run_pager(pager, output) {
+            Ok(()) => return Ok(()),
+            Err(err) => warn!("can not run pager {:?}: {:#}", pager, err),
+        }
+    }
+
+    print_ignoring_closed_pipe(output)
+}
+
+fn fits_terminal(output: &str) -> bool {
+    match crossterm::terminal::size() {
+        Ok((_, height)) => output.lines().count() < usize::from(height),
+        Err(_) => true,
+    }
+}
+
+fn run_pager(pager: &str, output: &str) -> Result<(), Error> {
+    let mut child = helper::shell_command(pager)
+        .stdin(Stdio::piped())
+        .spawn()
+        .context("can not start pager")?;
+
+    // The pager handles ctrl-c on its own and todust has to keep running until
+    // the pager quits so the terminal is not left to both of them.
+    let _interrupt = IgnoreInterrupt::new();
+
+    if let Some(mut stdin) = child.stdin.take() {
+        if let Err(err) = stdin.write_all(output.as_bytes()) {
+            if err.kind() != io::ErrorKind::BrokenPipe {
+                return Err(err).context("can not write to pager");
+            }
+        }
+    }
+
+    let status = child.wait().context("can not wait for pager")?;
+
+    // The shell could not find the pager so nothing was shown.
+    if status.code() == Some(127) {
+        bail!("pager not found")
+    }
+
+    Ok(())
+}
+
+fn print_ignoring_closed_pipe(output: &str) -> Result<(), Error> {
+    let stdout = io::stdout();
+    let mut handle = stdout.lock();
+
+    match handle
+        .write_all(output.as_bytes())
+        .and_then(|_| handle.flush())
+    {
+        Err(err) if err.kind() != io::ErrorKind::BrokenPipe => {
+            Err(err).context("can not write to stdout")
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Ignores SIGINT while it exists and restores the previous handler after.
+struct IgnoreInterrupt {
+    #[cfg(unix)]
+    previous: libc::sighandler_t,
+}
+
+impl IgnoreInterrupt {
+    #[cfg(unix)]
+    fn new() -> Self {
+        // SAFETY: Ignoring a signal does not run any code in the signal
+        // handler.
+        let previous = unsafe { libc::signal(libc::SIGINT, libc::SIG_IGN) };
+
+        Self { previous }
+    }
+
+    #[cfg(not(unix))]
+    fn new() -> Self {
+        Self {}
+    }
+}
+
+#[cfg(unix)]
+impl Drop for IgnoreInterrupt {
+    fn drop(&mut self) {
+        // SAFETY: Restores the handler that was installed before.
+        unsafe {
+            libc::signal(libc::SIGINT, self.previous);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn output_that_is_not_styled_is_not_paged() {
+        let interactivity = Interactivity::default();
+
+        assert_eq!(pager_command(Some("less"), false, interactivity), None);
+        assert_eq!(
+            pager_command(Some("less"), false, Interactivity::terminal().porcelain()),
+            None
+        );
+    }
+
+    #[test]
+    fn pager_can_be_disabled() {
+        let terminal = Interactivity::terminal();
+
+        assert_eq!(pager_command(Some("less"), true, terminal), None);
+        assert_eq!(pager_command(Some(""), false, terminal), None);
+        assert_eq!(pager_command(Some("  "), false, terminal), None);
+        assert_eq!(
+            pager_command(Some("more"), false, terminal),
+            Some("more".to_string())
+        );
+    }
+
+    #[test]
+    fn pager_gets_the_whole_output() {
+        let directory = tempfile::tempdir().unwrap();
+        let paged = directory.path().join("paged");
+        let output = "line\n".repeat(10_000);
+
+        run_pager(&format!("cat > '{}'", paged.display()), &output).unwrap();
+
+        assert_eq!(std::fs::read_to_string(paged).unwrap(), output);
+    }
+
+    #[test]
+    fn quitting_the_pager_early_is_not_an_error() {
+        let output = "line\n".repeat(100_000);
+
+        run_pager("true", &output).unwrap();
+        run_pager("head -n 1 > /dev/null", &output).unwrap();
+    }
+
+    #[test]
+    fn missing_pager_is_an_error() {
+        let err = run_pager("does-not-exist-todust-pager", "output").unwrap_err();
+
+        assert_eq!(err.to_string(), "pager not found");
+    }
+}
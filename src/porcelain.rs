@@ -0,0 +1,133 @@
+use crate::{
+    entry::{
+        Entry,
+        ProjectCount,
+    },
+    helper,
+};
+
+/// Version of the stable output formats for scripts that are selected with
+/// `--porcelain`. Printed by `todust --porcelain_version`. Any change to the
+/// formats has to increase the version.
+///
+/// Every record is one line with tab separated fields. Fields that are not
+/// set are empty. Tabs and line breaks in fields are replaced with spaces.
+///
+/// * `list`: `uuid project started due state title` where `started` is a RFC
+///   3339 timestamp, `due` a date like `2024-06-01`, `state` either `active` or
+///   `done` and `title` the first line of the entry.
+/// * `projects`: `project active done total`.
+/// * `add`: `uuid`.
+pub(super) const PORCELAIN_VERSION: u32 = 1;
+
+/// Record of an entry for `list`.
+pub(super) fn entry_record(entry: &Entry) -> String {
+    record(&[
+        entry.metadata.uuid.to_string(),
+        entry.metadata.project.clone(),
+        entry.metadata.started.to_rfc3339(),
+        entry
+            .metadata
+            .due
+            .map(|due| due.to_string())
+            .unwrap_or_default(),
+        if entry.is_done() { "done" } else { "active" }.to_string(),
+        helper::first_line(&entry.text),
+    ])
+}
+
+/// Record of a project for `projects`.
+pub(super) fn project_record(count: &ProjectCount) -> String {
+    record(&[
+        count.project.clone(),
+        count.active_count.to_string(),
+        count.done_count.to_string(),
+        count.total_count.to_string(),
+    ])
+}
+
+/// Record of an added entry for `add`.
+pub(super) fn added_record(entry: &Entry) -> String {
+    record(&[entry.metadata.uuid.to_string()])
+}
+
+fn record(fields: &[String]) -> String {
+    fields
+        .iter()
+        .map(|field| field.replace(['\t', '\r', '\n'], " "))
+        .collect::<Vec<_>>()
+        .join("\t")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::entry;
+    use uuid::Uuid;
+
+    /// The golden files have to change together with the version.
+    #[test]
+    fn version_matches_the_golden_files() {
+        assert_eq!(PORCELAIN_VERSION, 1);
+    }
+
+    fn golden_entries() -> Vec<Entry> {
+        let mut active = entry("== Call the vendor\nabout the invoice");
+        active.metadata.uuid = Uuid::parse_str("0e3b9f6c-5c3a-4d9e-8a0f-2b7c1d4e5f60").unwrap();
+        active.metadata.project = "work".to_string();
+        active.metadata.started = "2024-05-01T08:30:00Z".parse().unwrap();
+        active.metadata.due = chrono::NaiveDate::from_ymd_opt(2024, 6, 1);
+
+        let mut done = entry("Renew the domain\twith tabs\rand line breaks");
+        done.metadata.uuid = Uuid::parse_str("7a1c2d3e-4f50-4a6b-9c7d-8e9f0a1b2c3d").unwrap();
+        done.metadata.project = "home".to_string();
+        done.metadata.started = "2024-04-02T17:00:00Z".parse().unwrap();
+        done.metadata.finished = Some("2024-04-03T09:00:00Z".parse().unwrap());
+
+        vec![active, done]
+    }
+
+    fn lines(records: impl Iterator<Item = String>) -> String {
+        records.map(|record| record + "\n").collect()
+    }
+
+    #[test]
+    fn list_records() {
+        assert_eq!(
+            lines(golden_entries().iter().map(entry_record)),
+            include_str!("../resources/porcelain/list.tsv")
+        );
+    }
+
+    #[test]
+    fn projects_records() {
+        let counts = [
+            ProjectCount {
+                project: "work".to_string(),
+                active_count: 3,
+                done_count: 1,
+                total_count: 4,
+                ..ProjectCount::default()
+            },
+            ProjectCount {
+                project: "home".to_string(),
+                done_count: 2,
+                total_count: 2,
+                ..ProjectCount::default()
+            },
+        ];
+
+        assert_eq!(
+            lines(counts.iter().map(project_record)),
+            include_str!("../resources/porcelain/projects.tsv")
+        );
+    }
+
+    #[test]
+    fn add_records() {
+        assert_eq!(
+            lines(golden_entries().iter().take(1).map(added_record)),
+            include_str!("../resources/porcelain/add.tsv")
+        );
+    }
+}
@@ -0,0 +1,342 @@
+use std::{
+    collections::BTreeMap,
+    fmt,
+};
+use unicode_normalization::UnicodeNormalization;
+
+/// Longest project name that is accepted.
+const MAX_PROJECT_NAME_LENGTH: usize = 200;
+
+/// Characters that do not render but are not whitespace or control
+/// characters: zero width space, word joiner, zero width no-break space and
+/// soft hyphen. They are dropped when normalizing project names.
+const INVISIBLE_CHARACTERS: &[char] = &['\u{200b}', '\u{2060}', '\u{feff}', '\u{ad}'];
+
+/// Reasons why a string can not be used as a project name.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) enum ProjectNameError {
+    Empty,
+    TooLong(usize),
+    SurroundingWhitespace,
+    PathSeparator,
+    RelativePath,
+    ControlCharacter,
+
+    /// The name renders like its normalized form which has to be used
+    /// instead.
+    NotNormalized(String),
+}
+
+impl fmt::Display for ProjectNameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProjectNameError::Empty => write!(f, "project name can not be empty"),
+
+            ProjectNameError::TooLong(length) => write!(
+                f,
+                "project name is {} characters long but can only be {} characters long",
+                length, MAX_PROJECT_NAME_LENGTH
+            ),
+
+            ProjectNameError::SurroundingWhitespace => {
+                write!(f, "project name can not start or end with whitespace")
+            }
+
+            ProjectNameError::PathSeparator => {
+                write!(f, "project name can not contain / or \\")
+            }
+
+            ProjectNameError::RelativePath => write!(f, "project name can not be . or .."),
+
+            ProjectNameError::ControlCharacter => {
+                write!(f, "project name can not contain control characters")
+            }
+
+            ProjectNameError::NotNormalized(normalized) => write!(
+                f,
+                "project name contains invisible characters or unusual whitespace, use {:?} \
+                 instead",
+                normalized
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ProjectNameError {
+    fn description(&self) -> &str {
+        ""
+    }
+}
+
+/// Check that the name can be used as a project name. Project names end up in
+/// urls, cookies and later maybe in file paths so anything that could be read
+/// as a path or that is invisible is rejected.
+pub(crate) fn validate_project_name(name: &str) -> Result<(), ProjectNameError> {
+    if name.is_empty() {
+        return Err(ProjectNameError::Empty);
+    }
+
+    let length = name.chars().count();
+    if length > MAX_PROJECT_NAME_LENGTH {
+        return Err(ProjectNameError::TooLong(length));
+    }
+
+    if name.trim() != name {
+        return Err(ProjectNameError::SurroundingWhitespace);
+    }
+
+    if name.contains('/') || name.contains('\\') {
+        return Err(ProjectNameError::PathSeparator);
+    }
+
+    if name == "." || name == ".." {
+        return Err(ProjectNameError::RelativePath);
+    }
+
+    if name.chars().any(char::is_control) {
+        return Err(ProjectNameError::ControlCharacter);
+    }
+
+    let normalized = normalize_project_name(name);
+    if normalized != name {
+        return Err(ProjectNameError::NotNormalized(normalized));
+    }
+
+    Ok(())
+}
+
+/// Form of the project name that two names have in common if they render the
+/// same. The name is composed to unicode NFC, invisible characters are
+/// dropped, all whitespace becomes a plain space and the name is trimmed.
+pub(crate) fn normalize_project_name(name: &str) -> String {
+    name.nfc()
+        .filter(|character| !INVISIBLE_CHARACTERS.contains(character))
+        .map(|character| {
+            if character.is_whitespace() {
+                ' '
+            } else {
+                character
+            }
+        })
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+/// Projects whose names are the same after normalization.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct SimilarProjects {
+    /// Normalized name the projects can be merged into.
+    pub(crate) canonical: String,
+
+    /// Names of the projects as they are stored.
+    pub(crate) projects: Vec<String>,
+}
+
+/// Group the project names that collide after normalization. Names that are
+/// different after normalization are never grouped together.
+pub(crate) fn similar_projects<I>(projects: I) -> Vec<SimilarProjects>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut groups: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+    for project in projects {
+        let group = groups.entry(normalize_project_name(&project)).or_default();
+
+        if !group.contains(&project) {
+            group.push(project);
+        }
+    }
+
+    groups
+        .into_iter()
+        .filter(|(_, projects)| projects.len() > 1)
+        .map(|(canonical, mut projects)| {
+            projects.sort();
+
+            SimilarProjects {
+                canonical,
+                projects,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn usual_names_are_valid() {
+        for name in &[
+            "work",
+            "side project",
+            "2026-q4",
+            "café",
+            "日本語",
+            ".hidden",
+            "a..b",
+        ] {
+            assert_eq!(validate_project_name(name), Ok(()), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn relative_paths_are_rejected() {
+        assert_eq!(
+            validate_project_name("."),
+            Err(ProjectNameError::RelativePath)
+        );
+        assert_eq!(
+            validate_project_name(".."),
+            Err(ProjectNameError::RelativePath)
+        );
+    }
+
+    #[test]
+    fn path_separators_are_rejected() {
+        for name in &["a/b", "/work", "work/", "a\\b", "\\work", "../work"] {
+            assert_eq!(
+                validate_project_name(name),
+                Err(ProjectNameError::PathSeparator),
+                "{:?}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn control_characters_are_rejected() {
+        for name in &["a\tb", "a\nb", "a\u{0}b", "a\u{7f}b", "a\u{1b}[31mb"] {
+            assert_eq!(
+                validate_project_name(name),
+                Err(ProjectNameError::ControlCharacter),
+                "{:?}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn empty_and_blank_names_are_rejected() {
+        assert_eq!(validate_project_name(""), Err(ProjectNameError::Empty));
+
+        for name in &[" ", "   ", "\t", "\u{a0}", " work", "work "] {
+            assert_eq!(
+                validate_project_name(name),
+                Err(ProjectNameError::SurroundingWhitespace),
+                "{:?}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn long_names_are_rejected() {
+        let longest = "a".repeat(MAX_PROJECT_NAME_LENGTH);
+        assert_eq!(validate_project_name(&longest), Ok(()));
+
+        // Characters are counted, not bytes.
+        let longest = "ä".repeat(MAX_PROJECT_NAME_LENGTH);
+        assert_eq!(validate_project_name(&longest), Ok(()));
+
+        let too_long = "a".repeat(MAX_PROJECT_NAME_LENGTH + 1);
+        assert_eq!(
+            validate_project_name(&too_long),
+            Err(ProjectNameError::TooLong(MAX_PROJECT_NAME_LENGTH + 1))
+        );
+    }
+
+    #[test]
+    fn names_that_are_not_normalized_are_rejected_with_the_normalized_name() {
+        for (name, normalized) in &[
+            ("side\u{a0}project", "side project"),
+            ("side\u{2003}project", "side project"),
+            ("wo\u{200b}rk", "work"),
+            ("work\u{feff}", "work"),
+            ("cafe\u{301}", "café"),
+        ] {
+            assert_eq!(
+                validate_project_name(name),
+                Err(ProjectNameError::NotNormalized(normalized.to_string())),
+                "{:?}",
+                name
+            );
+            assert_eq!(validate_project_name(normalized), Ok(()));
+        }
+    }
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn unusual_whitespace_becomes_a_space() {
+        assert_eq!(normalize_project_name("side\u{a0}project"), "side project");
+        assert_eq!(
+            normalize_project_name("side\u{202f}project"),
+            "side project"
+        );
+        assert_eq!(normalize_project_name("side\tproject"), "side project");
+        assert_eq!(normalize_project_name("\u{a0}work\u{a0}"), "work");
+    }
+
+    #[test]
+    fn composed_and_decomposed_names_are_the_same() {
+        let composed = "d\u{e9}fault";
+        let decomposed = "de\u{301}fault";
+        assert_ne!(composed, decomposed);
+
+        assert_eq!(normalize_project_name(composed), composed);
+        assert_eq!(normalize_project_name(decomposed), composed);
+    }
+
+    #[test]
+    fn colliding_names_are_grouped() {
+        let similar = similar_projects(names(&[
+            "work",
+            "work ",
+            "work\u{a0}",
+            "d\u{e9}fault",
+            "de\u{301}fault",
+            "home",
+        ]));
+
+        assert_eq!(
+            similar,
+            vec![
+                SimilarProjects {
+                    canonical: "d\u{e9}fault".to_string(),
+                    projects: names(&["de\u{301}fault", "d\u{e9}fault"]),
+                },
+                SimilarProjects {
+                    canonical: "work".to_string(),
+                    projects: names(&["work", "work ", "work\u{a0}"]),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn distinct_names_are_never_grouped() {
+        let similar = similar_projects(names(&[
+            "work",
+            "Work",
+            "WORK",
+            "work1",
+            "work 1",
+            "work  1",
+            "resume",
+            "r\u{e9}sum\u{e9}",
+            "\u{212b}ngstr\u{f6}m",
+        ]));
+
+        assert_eq!(similar, Vec::new());
+    }
+
+    #[test]
+    fn the_same_name_twice_is_no_collision() {
+        assert_eq!(similar_projects(names(&["work", "work"])), Vec::new());
+    }
+}
@@ -0,0 +1,245 @@
+use std::{
+    env,
+    fmt,
+};
+use structopt::clap::ArgMatches;
+
+/// Where the value of an option that can be given in multiple ways came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum SettingSource {
+    /// Built-in default of the option.
+    Default,
+
+    /// Environment variable with the name.
+    Environment(&'static str),
+
+    /// Command line flag with the name.
+    Flag(&'static str),
+
+    /// Project picked with `todust switch` in the terminal session.
+    Session,
+}
+
+impl fmt::Display for SettingSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingSource::Default => write!(f, "default"),
+            SettingSource::Environment(name) | SettingSource::Flag(name) => write!(f, "{}", name),
+            SettingSource::Session => write!(f, "todust switch"),
+        }
+    }
+}
+
+/// Value of an option together with where it came from.
+#[derive(Debug, Clone)]
+pub(super) struct ResolvedSetting {
+    pub(super) value: String,
+    pub(super) source: SettingSource,
+
+    /// Each value if the option can be given multiple times.
+    pub(super) values: Vec<String>,
+}
+
+impl ResolvedSetting {
+    /// Resolve the option `name` the way clap does: the flag wins over the
+    /// environment variable which wins over the default. Multiple values are
+    /// joined with commas.
+    fn from_matches(
+        matches: &ArgMatches,
+        name: &str,
+        flag: &'static str,
+        env_var: &'static str,
+    ) -> Option<Self> {
+        let values = matches.values_of_lossy(name)?;
+        let value = values.join(", ");
+
+        // Values from the environment are not counted as occurrences.
+        let source = if matches.occurrences_of(name) > 0 {
+            SettingSource::Flag(flag)
+        } else if env::var_os(env_var).is_some() {
+            SettingSource::Environment(env_var)
+        } else {
+            SettingSource::Default
+        };
+
+        Some(Self {
+            value,
+            source,
+            values,
+        })
+    }
+}
+
+/// Datadir and project a command runs with and where they came from.
+#[derive(Debug, Clone, Default)]
+pub(super) struct EffectiveSettings {
+    pub(super) datadir: Option<ResolvedSetting>,
+    pub(super) project: Option<ResolvedSetting>,
+}
+
+impl EffectiveSettings {
+    /// Resolve the settings from the innermost subcommand that has a datadir
+    /// option. Commands without one have no settings.
+    pub(super) fn from_matches(matches: &ArgMatches) -> Self {
+        let mut settings = Self::default();
+        let mut current = Some(matches);
+
+        while let Some(matches) = current {
+            if matches.is_present("datadir") {
+                settings = Self {
+                    datadir: ResolvedSetting::from_matches(
+                        matches,
+                        "datadir",
+                        "--datadir",
+                        "TODUST_DATADIR",
+                    ),
+                    project: ResolvedSetting::from_matches(
+                        matches,
+                        "project",
+                        "--project",
+                        "TODUST_PROJECT",
+                    )
+                    .or_else(|| {
+                        ResolvedSetting::from_matches(
+                            matches,
+                            "projects",
+                            "--project",
+                            "TODUST_PROJECT",
+                        )
+                    }),
+                };
+            }
+
+            current = matches.subcommand().1;
+        }
+
+        settings
+    }
+
+    /// The project was taken from the terminal session as it was not given
+    /// otherwise.
+    pub(super) fn with_session_project(mut self) -> Self {
+        if let Some(project) = &mut self.project {
+            project.source = SettingSource::Session;
+        }
+
+        self
+    }
+
+    /// Header like `# store: /srv/todos  project: oncall  (from
+    /// TODUST_DATADIR, --project)` naming the sources that are not the
+    /// built-in default. None if all settings are the default ones.
+    pub(super) fn header(&self) -> Option<String> {
+        let settings = [("store", &self.datadir), ("project", &self.project)];
+
+        let sources = settings
+            .iter()
+            .filter_map(|(_, setting)| setting.as_ref())
+            .filter(|setting| setting.source != SettingSource::Default)
+            .map(|setting| setting.source.to_string())
+            .collect::<Vec<_>>();
+
+        if sources.is_empty() {
+            return None;
+        }
+
+        let values = settings
+            .iter()
+            .filter_map(|(label, setting)| {
+                setting
+                    .as_ref()
+                    .map(|setting| format!("{}: {}", label, setting.value))
+            })
+            .collect::<Vec<_>>();
+
+        Some(format!(
+            "# {}  (from {})",
+            values.join("  "),
+            sources.join(", ")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::opt::Opt;
+    use structopt::StructOpt;
+
+    fn resolve(args: &[&str]) -> EffectiveSettings {
+        let args = std::iter::once("todust").chain(args.iter().copied());
+
+        EffectiveSettings::from_matches(&Opt::clap().get_matches_from_safe(args).unwrap())
+    }
+
+    fn setting(value: &str, source: SettingSource) -> Option<ResolvedSetting> {
+        Some(ResolvedSetting {
+            value: value.to_string(),
+            source,
+            values: vec![value.to_string()],
+        })
+    }
+
+    #[test]
+    fn flags_are_named_in_the_header() {
+        let settings = resolve(&[
+            "list",
+            "--datadir",
+            "/srv/todos",
+            "-p",
+            "oncall",
+            "-p",
+            "home",
+        ]);
+
+        let project = settings.project.as_ref().unwrap();
+        assert_eq!(project.source, SettingSource::Flag("--project"));
+        assert_eq!(project.values, vec!["oncall", "home"]);
+        assert_eq!(
+            settings.header().unwrap(),
+            "# store: /srv/todos  project: oncall, home  (from --datadir, --project)"
+        );
+    }
+
+    #[test]
+    fn defaults_have_no_header() {
+        if env::var_os("TODUST_DATADIR").is_some() || env::var_os("TODUST_PROJECT").is_some() {
+            return;
+        }
+
+        let settings = resolve(&["list"]);
+
+        assert_eq!(
+            settings.datadir.as_ref().unwrap().source,
+            SettingSource::Default
+        );
+        assert_eq!(settings.header(), None);
+        assert!(
+            resolve(&["completion", "--shell", "bash", "--directory", "/tmp"])
+                .datadir
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn only_sources_other_than_the_default_are_named() {
+        let settings = EffectiveSettings {
+            datadir: setting("/srv/todos", SettingSource::Environment("TODUST_DATADIR")),
+            project: setting("default", SettingSource::Default),
+        };
+        assert_eq!(
+            settings.header().unwrap(),
+            "# store: /srv/todos  project: default  (from TODUST_DATADIR)"
+        );
+
+        let settings = EffectiveSettings {
+            datadir: setting("/srv/todos", SettingSource::Default),
+            project: setting("oncall", SettingSource::Default),
+        }
+        .with_session_project();
+        assert_eq!(
+            settings.header().unwrap(),
+            "# store: /srv/todos  project: oncall  (from todust switch)"
+        );
+    }
+}
@@ -0,0 +1,602 @@
+use crate::{
+    entry::{
+        Entries,
+        Entry,
+        Metadata,
+    },
+    helper,
+    locale::Locale,
+    templating,
+    time_context::TimeContext,
+};
+use chrono::{
+    DateTime,
+    Duration,
+    NaiveDate,
+    Utc,
+};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fmt,
+};
+use tera::{
+    Context,
+    Tera,
+};
+use uuid::Uuid;
+
+/// How many of the stalest active entries are listed per project in the
+/// digest.
+const DIGEST_STALEST_PER_PROJECT: usize = 5;
+
+/// How many days ahead entries count as due soon on the board and the project
+/// index.
+pub(crate) const DUE_SOON_DAYS: i64 = 7;
+
+/// After how many days without being viewed or changed active entries count
+/// as forgotten.
+pub(crate) const FORGOTTEN_DAYS: i64 = 30;
+
+/// How many days finished entries stay on the board.
+const BOARD_DONE_DAYS: i64 = 7;
+
+/// Summary of what happened in a period and what is coming up in the next
+/// one.
+#[derive(Debug, Serialize)]
+pub(super) struct Digest {
+    pub(super) generated: DateTime<Utc>,
+    pub(super) period_start: DateTime<Utc>,
+    pub(super) period_end: DateTime<Utc>,
+
+    /// Days of the timestamps above in the configured timezone.
+    pub(super) today: NaiveDate,
+    pub(super) first_day: NaiveDate,
+    pub(super) last_day: NaiveDate,
+
+    /// Entries finished in the last period.
+    pub(super) finished: Vec<Entry>,
+
+    /// Active entries that are past their due date.
+    pub(super) overdue: Vec<Entry>,
+
+    /// Active entries that are due in the next period.
+    pub(super) due_soon: Vec<Entry>,
+
+    /// Active entries per project that have not been changed for the longest
+    /// time.
+    pub(super) stalest: BTreeMap<String, Vec<Entry>>,
+}
+
+impl Digest {
+    /// Aggregate the digest from the given entries. Now and the days are
+    /// taken from the time context so the aggregation does not depend on the
+    /// system clock.
+    pub(super) fn new(entries: Entries, time: &TimeContext, period: Duration) -> Self {
+        let now = time.now();
+        let period_start = now - period;
+        let period_end = now + period;
+        let today = time.today();
+        let last_day = time.date(period_end);
+
+        let mut finished = Vec::new();
+        let mut overdue = Vec::new();
+        let mut due_soon = Vec::new();
+        let mut active: BTreeMap<String, Vec<Entry>> = BTreeMap::new();
+
+        for entry in entries {
+            if let Some(finished_at) = entry.metadata.finished {
+                if finished_at >= period_start && finished_at <= now {
+                    finished.push(entry);
+                }
+
+                continue;
+            }
+
+            match entry.metadata.due {
+                Some(due) if due < today => overdue.push(entry.clone()),
+                Some(due) if due <= last_day => due_soon.push(entry.clone()),
+                _ => {}
+            }
+
+            active
+                .entry(entry.metadata.project.clone())
+                .or_default()
+                .push(entry);
+        }
+
+        finished.sort_by_key(|entry| entry.metadata.finished);
+        overdue.sort_by_key(|entry| entry.metadata.due);
+        due_soon.sort_by_key(|entry| entry.metadata.due);
+
+        let stalest = active
+            .into_iter()
+            .map(|(project, mut entries)| {
+                entries.sort_by_key(|entry| entry.metadata.last_change);
+                entries.truncate(DIGEST_STALEST_PER_PROJECT);
+
+                (project, entries)
+            })
+            .collect();
+
+        Self {
+            generated: now,
+            period_start,
+            period_end,
+            today,
+            first_day: time.date(period_start),
+            last_day,
+            finished,
+            overdue,
+            due_soon,
+            stalest,
+        }
+    }
+}
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut context = Context::new();
+        context.insert("digest", self);
+
+        let mut tera = Tera::default();
+        tera.add_raw_template(
+            "digest.asciidoc",
+            include_str!("../resources/templates/digest.asciidoc"),
+        )
+        .expect("can not compile digest.asciidoc template");
+        tera.register_filter("single_line", templating::single_line);
+        tera.register_filter("format_duration_since", templating::format_duration_since);
+        tera.register_filter("some_or_dash", templating::some_or_dash);
+
+        let rendered = tera
+            .render("digest.asciidoc", &context)
+            .expect("can not render template for digest");
+
+        write!(f, "{}", rendered)
+    }
+}
+
+/// Entries of a project sorted into the columns of the board. Every entry is
+/// in at most one column.
+#[derive(Debug, Default, Serialize)]
+pub(super) struct Board {
+    /// Active entries that are past their due date.
+    pub(super) overdue: Vec<Entry>,
+
+    /// Active entries that are due in the next days.
+    pub(super) due_soon: Vec<Entry>,
+
+    /// All other active entries.
+    pub(super) active: Vec<Entry>,
+
+    /// Active entries that are waiting on something else. Stays empty until
+    /// entries can be marked as waiting.
+    pub(super) waiting: Vec<Entry>,
+
+    /// Entries that were finished in the last days.
+    pub(super) done: Vec<Entry>,
+}
+
+impl Board {
+    pub(super) fn new(entries: Entries, time: &TimeContext) -> Self {
+        let today = time.today();
+        let due_soon_until = today + Duration::days(DUE_SOON_DAYS);
+        let done_since = time.now() - Duration::days(BOARD_DONE_DAYS);
+
+        let mut board = Board::default();
+
+        for entry in entries {
+            if let Some(finished) = entry.metadata.finished {
+                if finished >= done_since {
+                    board.done.push(entry);
+                }
+
+                continue;
+            }
+
+            match entry.metadata.due {
+                Some(due) if due < today => board.overdue.push(entry),
+                Some(due) if due <= due_soon_until => board.due_soon.push(entry),
+                _ => board.active.push(entry),
+            }
+        }
+
+        board.overdue.sort_by_key(|entry| entry.metadata.due);
+        board.due_soon.sort_by_key(|entry| entry.metadata.due);
+        board
+            .done
+            .sort_by_key(|entry| std::cmp::Reverse(entry.metadata.finished));
+
+        board
+    }
+}
+
+/// Finished entries grouped by the day they were finished on.
+#[derive(Debug, Serialize)]
+pub(super) struct Worklog {
+    pub(super) from: NaiveDate,
+    pub(super) to: NaiveDate,
+    pub(super) days: Vec<WorklogDay>,
+    pub(super) total: usize,
+
+    /// Locale the days are shown in. The json output always uses iso dates.
+    #[serde(skip)]
+    pub(super) locale: Locale,
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct WorklogDay {
+    pub(super) date: NaiveDate,
+    pub(super) count: usize,
+    pub(super) entries: Vec<WorklogEntry>,
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct WorklogEntry {
+    pub(super) uuid: Uuid,
+    pub(super) project: String,
+    pub(super) finished: DateTime<Utc>,
+    pub(super) summary: String,
+}
+
+impl Worklog {
+    /// Group the entries finished between `from` and `to` (both inclusive) by
+    /// the day they were finished on in the configured timezone.
+    pub(super) fn new(
+        entries: Entries,
+        from: NaiveDate,
+        to: NaiveDate,
+        time: &TimeContext,
+        locale: Locale,
+    ) -> Self {
+        let mut days: BTreeMap<NaiveDate, Vec<WorklogEntry>> = BTreeMap::new();
+
+        for entry in entries {
+            let finished = match entry.metadata.finished {
+                Some(finished) => finished,
+                None => continue,
+            };
+
+            let date = time.date(finished);
+            if date < from || date > to {
+                continue;
+            }
+
+            days.entry(date).or_default().push(WorklogEntry {
+                uuid: entry.metadata.uuid,
+                project: entry.metadata.project,
+                finished,
+                summary: helper::first_line(&entry.text),
+            });
+        }
+
+        let days: Vec<_> = days
+            .into_iter()
+            .map(|(date, mut entries)| {
+                entries.sort_by_key(|entry| entry.finished);
+
+                WorklogDay {
+                    date,
+                    count: entries.len(),
+                    entries,
+                }
+            })
+            .collect();
+
+        let total = days.iter().map(|day| day.count).sum();
+
+        Self {
+            from,
+            to,
+            days,
+            total,
+            locale,
+        }
+    }
+
+    /// Render the worklog as plain text with one line per entry.
+    pub(super) fn to_text(&self) -> String {
+        let mut out = String::new();
+
+        for day in &self.days {
+            out.push_str(&format!(
+                "{} ({})\n",
+                self.locale.format_date(day.date),
+                day.count
+            ));
+
+            for entry in &day.entries {
+                out.push_str(&format!("  {} ({})\n", entry.summary, entry.project));
+            }
+
+            out.push('\n');
+        }
+
+        out.push_str(&format!("Total: {}\n", self.total));
+
+        out
+    }
+}
+
+impl fmt::Display for Worklog {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut context = Context::new();
+        context.insert("worklog", self);
+
+        let mut tera = Tera::default();
+        tera.add_raw_template(
+            "worklog.asciidoc",
+            include_str!("../resources/templates/worklog.asciidoc"),
+        )
+        .expect("can not compile worklog.asciidoc template");
+        tera.register_filter("format_date", templating::format_date(self.locale));
+        tera.register_filter("format_day", templating::format_day(self.locale));
+
+        let rendered = tera
+            .render("worklog.asciidoc", &context)
+            .expect("can not render template for worklog");
+
+        write!(f, "{}", rendered)
+    }
+}
+
+/// Number of entries finished per week and project for the last `weeks`
+/// weeks. The last week is the current one and the oldest week comes first.
+/// Weeks start on the day configured in the time context. Projects without
+/// finished entries in that time are missing.
+pub(crate) fn finished_per_week<'a, I>(
+    metadata: I,
+    weeks: usize,
+    time: &TimeContext,
+) -> BTreeMap<String, Vec<usize>>
+where
+    I: IntoIterator<Item = &'a Metadata>,
+{
+    let (current_week, _) = time.week_bounds(time.today());
+    let mut counts: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+
+    for metadata in metadata {
+        let finished = match metadata.finished {
+            Some(finished) => time.date(finished),
+            None => continue,
+        };
+
+        let (week, _) = time.week_bounds(finished);
+        let weeks_ago = current_week.signed_duration_since(week).num_weeks();
+        if weeks_ago < 0 || weeks_ago as usize >= weeks {
+            continue;
+        }
+
+        counts
+            .entry(metadata.project.clone())
+            .or_insert_with(|| vec![0; weeks])[weeks - 1 - weeks_ago as usize] += 1;
+    }
+
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::entry;
+    use chrono_tz::Tz;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    fn day(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    fn finished(text: &str, project: &str, time: &str) -> Entry {
+        let mut finished = entry(text);
+        finished.metadata.project = project.to_string();
+        finished.metadata.finished = Some(at(time));
+
+        finished
+    }
+
+    fn worklog(entries: Vec<Entry>, from: NaiveDate, to: NaiveDate, timezone: Tz) -> Worklog {
+        Worklog::new(
+            entries.into_iter().collect(),
+            from,
+            to,
+            &TimeContext::new(Some(timezone)),
+            Locale::default(),
+        )
+    }
+
+    fn days(worklog: &Worklog) -> Vec<(NaiveDate, Vec<&str>)> {
+        worklog
+            .days
+            .iter()
+            .map(|day| {
+                (
+                    day.date,
+                    day.entries
+                        .iter()
+                        .map(|entry| entry.summary.as_str())
+                        .collect(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn worklog_range_includes_both_days_until_midnight() {
+        let worklog = worklog(
+            vec![
+                finished("before", "work", "2024-04-30T23:59:59Z"),
+                finished("first", "work", "2024-05-01T00:00:00Z"),
+                finished("last", "work", "2024-05-31T23:59:59Z"),
+                finished("after", "work", "2024-06-01T00:00:00Z"),
+                entry("active"),
+            ],
+            day(2024, 5, 1),
+            day(2024, 5, 31),
+            Tz::UTC,
+        );
+
+        assert_eq!(
+            days(&worklog),
+            vec![
+                (day(2024, 5, 1), vec!["first"]),
+                (day(2024, 5, 31), vec!["last"]),
+            ]
+        );
+        assert_eq!(worklog.total, 2);
+    }
+
+    #[test]
+    fn worklog_groups_by_the_local_day() {
+        let worklog = worklog(
+            vec![
+                finished("late evening", "work", "2024-05-01T11:30:00Z"),
+                finished("morning", "work", "2024-05-01T12:30:00Z"),
+            ],
+            day(2024, 5, 1),
+            day(2024, 5, 31),
+            Tz::Pacific__Auckland,
+        );
+
+        assert_eq!(
+            days(&worklog),
+            vec![
+                (day(2024, 5, 1), vec!["late evening"]),
+                (day(2024, 5, 2), vec!["morning"]),
+            ]
+        );
+    }
+
+    #[test]
+    fn worklog_follows_daylight_saving_transitions() {
+        // Berlin switches from UTC+1 to UTC+2 on 2024-03-31 at 01:00 UTC, so
+        // the same UTC time of day falls on the next day only after the switch.
+        let worklog = worklog(
+            vec![
+                finished("before switch", "work", "2024-03-30T22:30:00Z"),
+                finished("after switch", "work", "2024-03-31T22:30:00Z"),
+                finished("autumn", "work", "2024-10-27T22:30:00Z"),
+            ],
+            day(2024, 3, 1),
+            day(2024, 10, 31),
+            Tz::Europe__Berlin,
+        );
+
+        assert_eq!(
+            days(&worklog),
+            vec![
+                (day(2024, 3, 30), vec!["before switch"]),
+                (day(2024, 4, 1), vec!["after switch"]),
+                (day(2024, 10, 27), vec!["autumn"]),
+            ]
+        );
+    }
+
+    fn texts(entries: &[Entry]) -> Vec<&str> {
+        entries.iter().map(|entry| entry.text.as_str()).collect()
+    }
+
+    #[test]
+    fn board_sorts_entries_into_columns() {
+        let due = |text: &str, due: NaiveDate| {
+            let mut due_entry = entry(text);
+            due_entry.metadata.due = Some(due);
+
+            due_entry
+        };
+
+        let mut finished_late = finished("finished overdue", "default", "2024-05-09T12:00:00Z");
+        finished_late.metadata.due = Some(day(2024, 5, 1));
+
+        let entries = vec![
+            due("overdue yesterday", day(2024, 5, 9)),
+            due("overdue long ago", day(2024, 4, 1)),
+            due("due in a week", day(2024, 5, 17)),
+            due("due today", day(2024, 5, 10)),
+            due("due in eight days", day(2024, 5, 18)),
+            entry("without due date"),
+            finished("finished today", "default", "2024-05-10T09:00:00Z"),
+            finished_late,
+            finished("finished a week ago", "default", "2024-05-03T13:00:00Z"),
+            finished("finished too long ago", "default", "2024-05-03T11:00:00Z"),
+        ];
+
+        let time = TimeContext::new(None).with_now(at("2024-05-10T12:00:00Z"));
+        let board = Board::new(entries.into_iter().collect(), &time);
+
+        assert_eq!(
+            texts(&board.overdue),
+            vec!["overdue long ago", "overdue yesterday"]
+        );
+        assert_eq!(texts(&board.due_soon), vec!["due today", "due in a week"]);
+        assert_eq!(
+            texts(&board.active),
+            vec!["due in eight days", "without due date"]
+        );
+        assert!(board.waiting.is_empty());
+        assert_eq!(
+            texts(&board.done),
+            vec!["finished today", "finished overdue", "finished a week ago"]
+        );
+    }
+
+    #[test]
+    fn board_columns_follow_the_local_day() {
+        let mut due_today = entry("due today in berlin");
+        due_today.metadata.due = Some(day(2024, 5, 10));
+
+        // Already the next day in Berlin while it is still the 10th in UTC.
+        let time = TimeContext::new(Some(Tz::Europe__Berlin)).with_now(at("2024-05-10T23:00:00Z"));
+        let board = Board::new(vec![due_today].into_iter().collect(), &time);
+
+        assert_eq!(texts(&board.overdue), vec!["due today in berlin"]);
+        assert!(board.due_soon.is_empty());
+    }
+
+    #[test]
+    fn worklog_asciidoc() {
+        let worklog = worklog(
+            vec![
+                finished(
+                    "== Ship the release\nwith notes",
+                    "work",
+                    "2024-05-02T09:00:00Z",
+                ),
+                finished("Renew the domain", "home", "2024-05-02T08:00:00Z"),
+                finished("Call the vendor", "work", "2024-05-06T15:00:00Z"),
+            ],
+            day(2024, 5, 1),
+            day(2024, 5, 31),
+            Tz::UTC,
+        );
+
+        assert_eq!(
+            worklog.to_string(),
+            ":toc: right
+:toclevels: 2
+:icons: font
+:doctype: book
+
+= Worklog 2024-05-01 - 2024-05-31
+
+== 2024-05-02 (Thursday)
+
+* Renew the domain (home)
+* Ship the release (work)
+
+Finished: 2
+
+== 2024-05-06 (Monday)
+
+* Call the vendor (work)
+
+Finished: 1
+
+Total: 3
+"
+        );
+    }
+}
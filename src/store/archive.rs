@@ -0,0 +1,259 @@
+use crate::{
+    entry::{
+        Entries,
+        Entry,
+        Metadata,
+    },
+    store::{
+        index::{
+            CompactionReport,
+            Index,
+        },
+        Store,
+    },
+    time_context::TimeContext,
+};
+use anyhow::{
+    Context,
+    Error,
+};
+use chrono::NaiveDate;
+use std::{
+    collections::{
+        BTreeMap,
+        BTreeSet,
+    },
+    fmt,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+use uuid::Uuid;
+
+/// Folder in the index folder the archive index is written to. It has its
+/// own identifier folders and compacted index file like the live index.
+pub(super) const ARCHIVE_FOLDER_NAME: &str = "archive";
+
+/// What archiving the done entries of a store did or would do in a dry run.
+#[derive(Debug)]
+pub(crate) struct ArchiveReport {
+    pub(crate) dry_run: bool,
+
+    /// Entries finished before this day were archived.
+    pub(crate) before: NaiveDate,
+
+    /// Entries whose metadata was moved to the archive index.
+    pub(crate) archived: Vec<Uuid>,
+
+    /// Compaction of the live index that dropped the archived entries. Not
+    /// set if there was nothing to archive.
+    pub(crate) compaction: Option<CompactionReport>,
+
+    /// Backup of the datadir that was written before archiving.
+    pub(crate) backup: Option<PathBuf>,
+
+    /// Id of the commit that recorded the archiving.
+    pub(crate) commit: Option<String>,
+}
+
+/// Writes a summary of the archiving. The alternate form (`{:#}`) also lists
+/// every archived uuid.
+impl fmt::Display for ArchiveReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.dry_run {
+            writeln!(f, "Dry run, nothing was changed.")?;
+        }
+
+        if let Some(backup) = &self.backup {
+            if self.dry_run {
+                writeln!(f, "Backup (destructive operation): {:?}", backup)?;
+            } else {
+                writeln!(f, "Backup written to: {:?}", backup)?;
+            }
+        }
+
+        writeln!(
+            f,
+            "Entries finished before {} archived: {}",
+            self.before,
+            self.archived.len()
+        )?;
+        if f.alternate() {
+            for uuid in &self.archived {
+                writeln!(f, "  {}", uuid)?;
+            }
+        }
+
+        if let Some(compaction) = &self.compaction {
+            writeln!(
+                f,
+                "Index rows: {} -> {}",
+                compaction.rows_before, compaction.rows_after
+            )?;
+
+            writeln!(
+                f,
+                "Index bytes: {} -> {}",
+                compaction.bytes_before, compaction.bytes_after
+            )?;
+        }
+
+        match &self.commit {
+            Some(commit) => writeln!(f, "Commit: {}", commit),
+            None => writeln!(f, "Commit: -"),
+        }
+    }
+}
+
+impl Store {
+    fn archive_folder<P: AsRef<Path>>(datadir: P) -> PathBuf {
+        Store::index_folder(datadir).join(ARCHIVE_FOLDER_NAME)
+    }
+
+    /// Index of the archived entries. Creates the archive folder so it is
+    /// only opened when entries are archived or the folder already exists.
+    fn archive_index(&self) -> Result<Index, Error> {
+        Ok(Index::new(
+            Store::archive_folder(&self.datadir),
+            self.config.identifier.clone(),
+            self.config.index_rotation,
+            self.conflicts.clone(),
+            self.changes.clone(),
+            false,
+        )?)
+    }
+
+    /// Most recent metadata of the archived entries. Empty if nothing was
+    /// archived yet.
+    pub(super) fn archived_metadata(&self) -> Result<BTreeSet<Metadata>, Error> {
+        if !Store::archive_folder(&self.datadir).exists() {
+            return Ok(BTreeSet::new());
+        }
+
+        self.archive_index()?
+            .metadata_most_recent()
+            .context("can not get metadata from archive index")
+    }
+
+    /// Entries that still have rows in the live index although they were
+    /// archived, for example because the rows were synced from another
+    /// machine after archiving. Entries that were changed after they were
+    /// archived stay in the live index.
+    pub(super) fn superseded_by_archive(&self) -> Result<BTreeSet<Uuid>, Error> {
+        let live = self
+            .index
+            .metadata_most_recent()?
+            .into_iter()
+            .map(|metadata| (metadata.uuid, metadata))
+            .collect::<BTreeMap<_, _>>();
+
+        Ok(self
+            .archived_metadata()?
+            .into_iter()
+            .filter(|archived| {
+                live.get(&archived.uuid)
+                    .is_some_and(|metadata| metadata.recency() <= archived.recency())
+            })
+            .map(|archived| archived.uuid)
+            .collect())
+    }
+
+    /// Archived entries of all the given projects. Entries that are in the
+    /// live index again are left out as the other getters return them.
+    pub(crate) fn get_archived_entries_of_projects(
+        &self,
+        projects: &[String],
+    ) -> Result<Entries, Error> {
+        self.get_archived_entries_filtered(|metadata| projects.contains(&metadata.project))
+    }
+
+    /// Archived entries of all projects. Entries of ignored projects are only
+    /// included when `include_ignored` is set.
+    pub(crate) fn get_all_archived_entries(&self, include_ignored: bool) -> Result<Entries, Error> {
+        self.get_archived_entries_filtered(|metadata| {
+            include_ignored || !self.is_ignored_project(&metadata.project)
+        })
+    }
+
+    fn get_archived_entries_filtered<F>(&self, filter: F) -> Result<Entries, Error>
+    where
+        F: Fn(&Metadata) -> bool,
+    {
+        let live = self
+            .index
+            .metadata_most_recent()?
+            .into_iter()
+            .map(|metadata| metadata.uuid)
+            .collect::<BTreeSet<_>>();
+
+        let entries = self
+            .archived_metadata()?
+            .into_iter()
+            .filter(|metadata| !live.contains(&metadata.uuid) && filter(metadata))
+            .map(|metadata| self.get_entry_for_metadata(metadata))
+            .collect::<Result<BTreeSet<Entry>, Error>>()
+            .context("can not get entry for archived metadata")?
+            .into();
+
+        Ok(entries)
+    }
+
+    /// Move the metadata of the entries that were finished before the day to
+    /// the archive index and compact the live index without them, so commands
+    /// that read the live index do not have to read them anymore. The entry
+    /// files stay where they are. All index fragments are merged like a
+    /// cleanup without `keep_days`. A backup is written first unless `backup`
+    /// is false. Only reports what would be done in a dry run.
+    pub(crate) fn archive_done_before(
+        &self,
+        before: NaiveDate,
+        backup: bool,
+    ) -> Result<ArchiveReport, Error> {
+        let time = TimeContext::from_config(&self.config);
+
+        let archived = self
+            .index
+            .metadata_most_recent()?
+            .into_iter()
+            .filter(|metadata| {
+                metadata
+                    .finished
+                    .is_some_and(|finished| time.date(finished) < before)
+            })
+            .collect::<Vec<_>>();
+
+        let mut report = ArchiveReport {
+            dry_run: self.changes.is_dry_run(),
+            before,
+            archived: archived.iter().map(|metadata| metadata.uuid).collect(),
+            compaction: None,
+            backup: None,
+            commit: None,
+        };
+
+        if archived.is_empty() {
+            return Ok(report);
+        }
+
+        if backup {
+            report.backup = self.backup_before("archive")?;
+        }
+
+        // The rows are written to the archive before they are dropped from
+        // the live index so an interrupted run never loses an entry.
+        let archive = self.archive_index()?;
+        for metadata in &archived {
+            archive
+                .metadata_add(metadata)
+                .context("can not add metadata to archive index")?;
+        }
+
+        let uuids = report.archived.iter().copied().collect::<BTreeSet<_>>();
+        report.compaction = Some(self.index.compact(|_| false, &uuids, None)?);
+
+        report.commit = self.commit(&format!("archived {} done entries", archived.len()))?;
+
+        Ok(report)
+    }
+}
@@ -0,0 +1,420 @@
+use crate::store::{
+    changes::Changes,
+    Store,
+};
+use anyhow::{
+    Context,
+    Error,
+};
+use chrono::{
+    DateTime,
+    Utc,
+};
+use log::warn;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    self,
+    convert::Infallible,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::mpsc::{
+        self,
+        Receiver,
+        SyncSender,
+        TrySendError,
+    },
+    thread::JoinHandle,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+use uuid::Uuid;
+
+/// How many records can wait to be written before new records get dropped.
+const QUEUE_SIZE: usize = 256;
+
+/// How long to wait for queued records to be written when the webservice
+/// stops.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Principal of changes made while no tokens are configured.
+pub(crate) const ANONYMOUS: &str = "anonymous";
+
+/// A change to an entry made through the webservice.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct AuditRecord {
+    pub(crate) timestamp: DateTime<Utc>,
+
+    /// Name of the token the change was made with.
+    pub(crate) principal: String,
+
+    /// What was done like "add", "done" or "move".
+    pub(crate) action: String,
+
+    pub(crate) uuid: Uuid,
+
+    /// Project of the entry after the change.
+    pub(crate) project: String,
+}
+
+/// Which audit records to return. Records match if they match every filter
+/// that is set.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct AuditFilter {
+    pub(crate) uuid: Option<Uuid>,
+    pub(crate) project: Option<String>,
+    pub(crate) since: Option<DateTime<Utc>>,
+}
+
+impl AuditFilter {
+    fn matches(&self, record: &AuditRecord) -> bool {
+        self.uuid.is_none_or(|uuid| record.uuid == uuid)
+            && self
+                .project
+                .as_ref()
+                .is_none_or(|project| &record.project == project)
+            && self.since.is_none_or(|since| record.timestamp >= since)
+    }
+}
+
+/// Append only log of the changes made through the webservice. Records are
+/// written to one csv file per month.
+#[derive(Debug, Clone)]
+pub(crate) struct AuditLog {
+    directory: PathBuf,
+    changes: Changes,
+}
+
+impl AuditLog {
+    pub(super) fn new(directory: PathBuf, changes: Changes) -> Self {
+        Self { directory, changes }
+    }
+
+    pub(crate) fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Records that match the filter, the oldest first.
+    pub(crate) fn read(&self, filter: &AuditFilter) -> Result<Vec<AuditRecord>, Error> {
+        if !self.directory.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut paths = fs::read_dir(&self.directory)
+            .with_context(|| format!("can not read audit directory {:?}", self.directory))?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .map(|name| name.starts_with("audit-") && name.ends_with(".csv"))
+                    .unwrap_or(false)
+            })
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        let mut records = Vec::new();
+        for path in paths {
+            let mut reader = csv::Reader::from_path(&path)
+                .with_context(|| format!("can not open audit file {:?}", path))?;
+
+            for record in reader.deserialize() {
+                let record: AuditRecord =
+                    record.with_context(|| format!("can not read audit file {:?}", path))?;
+
+                if filter.matches(&record) {
+                    records.push(record);
+                }
+            }
+        }
+
+        records.sort_by_key(|record| record.timestamp);
+
+        Ok(records)
+    }
+
+    /// Start writing records from a background thread.
+    pub(crate) fn writer(&self) -> AuditWriter {
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_SIZE);
+
+        let directory = self.directory.clone();
+        let worker = std::thread::spawn(move || AuditWriter::write_all(&directory, receiver));
+
+        AuditWriter {
+            sender: Some(sender),
+            worker: Some(worker),
+            changes: self.changes.clone(),
+        }
+    }
+}
+
+/// Writes audit records from a background thread so writing the log can never
+/// block or fail the change that is recorded. Records that can not be written
+/// are logged and dropped.
+#[derive(Debug)]
+pub(crate) struct AuditWriter {
+    sender: Option<SyncSender<AuditRecord>>,
+    worker: Option<JoinHandle<()>>,
+    changes: Changes,
+}
+
+impl AuditWriter {
+    pub(crate) fn record(&self, record: AuditRecord) {
+        self.changes
+            .apply(
+                || {
+                    format!(
+                        "record the {} of entry {} in the audit log",
+                        record.action, record.uuid
+                    )
+                },
+                || {
+                    self.send(record.clone());
+                    Ok::<_, Infallible>(())
+                },
+            )
+            .unwrap_or_else(|never| match never {});
+    }
+
+    fn send(&self, record: AuditRecord) {
+        let sender = match &self.sender {
+            Some(sender) => sender,
+            None => return,
+        };
+
+        match sender.try_send(record) {
+            Ok(()) => {}
+            Err(TrySendError::Full(record)) => warn!(
+                "audit queue is full, dropping {} of entry {}",
+                record.action, record.uuid
+            ),
+            Err(TrySendError::Disconnected(record)) => warn!(
+                "audit writer stopped, dropping {} of entry {}",
+                record.action, record.uuid
+            ),
+        }
+    }
+
+    fn write_all(directory: &Path, receiver: Receiver<AuditRecord>) {
+        for record in receiver {
+            if let Err(err) = AuditWriter::append(directory, &record) {
+                warn!(
+                    "can not write {} of entry {} to the audit log: {:#}",
+                    record.action, record.uuid, err
+                );
+            }
+        }
+    }
+
+    /// Append the record to the file of the month it was made in.
+    fn append(directory: &Path, record: &AuditRecord) -> Result<(), Error> {
+        fs::create_dir_all(directory)
+            .with_context(|| format!("can not create audit directory {:?}", directory))?;
+
+        let path = directory.join(format!("audit-{}.csv", record.timestamp.format("%Y-%m")));
+
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .with_context(|| format!("can not open audit file {:?}", path))?;
+
+        // Only new files get a header so the records can be appended.
+        let is_new = file.metadata().map(|metadata| metadata.len() == 0)?;
+
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(is_new)
+            .from_writer(file);
+        writer.serialize(record)?;
+        writer.flush()?;
+
+        Ok(())
+    }
+}
+
+impl Drop for AuditWriter {
+    /// Give queued records a chance to be written before the process exits
+    /// but never wait longer than `SHUTDOWN_TIMEOUT`.
+    fn drop(&mut self) {
+        self.sender.take();
+
+        let worker = match self.worker.take() {
+            Some(worker) => worker,
+            None => return,
+        };
+
+        let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+        while !worker.is_finished() {
+            if Instant::now() >= deadline {
+                warn!("giving up on writing the remaining audit records");
+                return;
+            }
+
+            std::thread::sleep(Duration::from_millis(50))
+        }
+    }
+}
+
+impl Store {
+    /// Log of the changes made through the webservice.
+    pub(crate) fn audit_log(&self) -> &AuditLog {
+        &self.audit
+    }
+
+    /// When the entry with the uuid was deleted if it was deleted.
+    pub(crate) fn deleted_at(&self, uuid: &Uuid) -> Result<Option<DateTime<Utc>>, Error> {
+        Ok(self.index.deleted_at(uuid)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(timestamp: DateTime<Utc>, action: &str, project: &str) -> AuditRecord {
+        AuditRecord {
+            timestamp,
+            principal: "alice".to_string(),
+            action: action.to_string(),
+            uuid: Uuid::new_v4(),
+            project: project.to_string(),
+        }
+    }
+
+    fn day(month: u32, day: u32) -> DateTime<Utc> {
+        format!("2020-{:02}-{:02}T12:00:00Z", month, day)
+            .parse()
+            .unwrap()
+    }
+
+    /// Write the records and wait until the writer is done with them.
+    fn write(log: &AuditLog, records: &[AuditRecord]) {
+        let writer = log.writer();
+        for record in records {
+            writer.record(record.clone());
+        }
+    }
+
+    #[test]
+    fn records_are_written_to_a_file_per_month() {
+        let directory = tempfile::tempdir().unwrap();
+        let log = AuditLog::new(directory.path().join("audit"), Changes::default());
+        let records = vec![
+            record(day(5, 2), "add", "work"),
+            record(day(5, 1), "done", "work"),
+            record(day(6, 1), "move", "home"),
+        ];
+
+        write(&log, &records);
+        write(&log, &[record(day(6, 2), "due", "home")]);
+
+        let mut files = fs::read_dir(log.directory())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>();
+        files.sort();
+        assert_eq!(files, vec!["audit-2020-05.csv", "audit-2020-06.csv"]);
+
+        let june = fs::read_to_string(log.directory().join("audit-2020-06.csv")).unwrap();
+        let lines = june.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "timestamp,principal,action,uuid,project");
+        assert_eq!(
+            lines[1],
+            format!("2020-06-01T12:00:00Z,alice,move,{},home", records[2].uuid)
+        );
+
+        let read = log.read(&AuditFilter::default()).unwrap();
+        assert_eq!(
+            read.iter()
+                .map(|record| record.action.as_str())
+                .collect::<Vec<_>>(),
+            vec!["done", "add", "move", "due"]
+        );
+        assert_eq!(read[0], records[1]);
+    }
+
+    #[test]
+    fn filters_are_combined() {
+        let directory = tempfile::tempdir().unwrap();
+        let log = AuditLog::new(directory.path().join("audit"), Changes::default());
+        let records = vec![
+            record(day(5, 1), "add", "work"),
+            record(day(5, 2), "add", "home"),
+            record(day(5, 3), "done", "work"),
+        ];
+        write(&log, &records);
+
+        let actions = |filter: AuditFilter| {
+            log.read(&filter)
+                .unwrap()
+                .into_iter()
+                .map(|record| record.action)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            actions(AuditFilter {
+                project: Some("work".to_string()),
+                ..AuditFilter::default()
+            }),
+            vec!["add", "done"]
+        );
+        assert_eq!(
+            actions(AuditFilter {
+                uuid: Some(records[1].uuid),
+                ..AuditFilter::default()
+            }),
+            vec!["add"]
+        );
+        assert_eq!(
+            actions(AuditFilter {
+                project: Some("work".to_string()),
+                since: Some(day(5, 2)),
+                ..AuditFilter::default()
+            }),
+            vec!["done"]
+        );
+        assert!(actions(AuditFilter {
+            uuid: Some(records[1].uuid),
+            project: Some("work".to_string()),
+            ..AuditFilter::default()
+        })
+        .is_empty());
+    }
+
+    #[test]
+    fn failing_writes_do_not_fail_the_change() {
+        let directory = tempfile::tempdir().unwrap();
+        // A file where the directory should be makes every write fail.
+        let path = directory.path().join("audit");
+        fs::write(&path, "not a directory").unwrap();
+        let log = AuditLog::new(path.clone(), Changes::default());
+
+        let writer = log.writer();
+        for _ in 0..QUEUE_SIZE * 2 {
+            writer.record(record(day(5, 1), "add", "work"));
+        }
+        drop(writer);
+
+        assert_eq!(fs::read_to_string(&path).unwrap(), "not a directory");
+    }
+
+    #[test]
+    fn dry_run_records_nothing() {
+        let directory = tempfile::tempdir().unwrap();
+        let log = AuditLog::new(directory.path().join("audit"), Changes::dry_run());
+
+        write(&log, &[record(day(5, 1), "add", "work")]);
+
+        assert!(!log.directory().exists());
+        assert!(log.read(&AuditFilter::default()).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,187 @@
+use crate::store::{
+    state::StateStore,
+    Store,
+};
+use anyhow::{
+    Context,
+    Error,
+};
+use chrono::Utc;
+use flate2::{
+    write::GzEncoder,
+    Compression,
+};
+use log::debug;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+/// How many backups of a store are kept by default.
+const DEFAULT_KEEP: usize = 5;
+
+/// Extension of finished backups. Backups that are still written have an
+/// additional `.partial` extension so they are never pruned or restored.
+const BACKUP_EXTENSION: &str = "tar.gz";
+
+/// Backups of the datadir that are written before operations that remove
+/// data, like `cleanup` removing entry files and index rows.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct BackupConfig {
+    /// Write a backup before destructive operations. Can be skipped for a
+    /// single command with --no_backup.
+    pub(super) auto_backup_before_destructive: bool,
+
+    /// Directory the backups are written to. Every store gets its own
+    /// directory in it. Defaults to the state directory of the store.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) directory: Option<PathBuf>,
+
+    /// How many backups of each store are kept. Older backups are removed
+    /// after a new one was written.
+    pub(super) keep: usize,
+}
+
+impl Default for BackupConfig {
+    fn default() -> Self {
+        Self {
+            auto_backup_before_destructive: false,
+            directory: None,
+            keep: DEFAULT_KEEP,
+        }
+    }
+}
+
+/// The backups of one store.
+#[derive(Debug, Clone)]
+pub(super) struct Backups {
+    directory: PathBuf,
+    keep: usize,
+}
+
+impl Backups {
+    pub(super) fn new(config: &BackupConfig, state: &StateStore) -> Self {
+        let directory = match &config.directory {
+            Some(directory) => directory.join(state.name()),
+            None => state.directory().join("backups"),
+        };
+
+        Self {
+            directory,
+            // The backup that was just written is always kept.
+            keep: config.keep.max(1),
+        }
+    }
+
+    /// Path of a new backup that is written before the operation.
+    pub(super) fn next_path(&self, operation: &str) -> PathBuf {
+        self.directory.join(format!(
+            "{}-{}.{}",
+            Utc::now().format("%Y%m%dT%H%M%S%3fZ"),
+            operation,
+            BACKUP_EXTENSION
+        ))
+    }
+
+    /// Write the datadir as a gzipped tarball to `path` and remove the
+    /// backups that are not kept anymore. The datadir is stored under its
+    /// directory name so unpacking the backup restores the datadir next to
+    /// the current one.
+    pub(super) fn create(&self, datadir: &Path, path: &Path) -> Result<(), Error> {
+        fs::create_dir_all(&self.directory)
+            .with_context(|| format!("can not create backup directory {:?}", self.directory))?;
+
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+        let partial = PathBuf::from(partial);
+
+        let name = datadir
+            .canonicalize()
+            .ok()
+            .and_then(|datadir| datadir.file_name().map(PathBuf::from))
+            .unwrap_or_else(|| PathBuf::from("datadir"));
+
+        let file = fs::File::create(&partial)
+            .with_context(|| format!("can not create backup file {:?}", partial))?;
+
+        let mut archive = tar::Builder::new(GzEncoder::new(file, Compression::default()));
+        archive.follow_symlinks(false);
+        archive
+            .append_dir_all(&name, datadir)
+            .with_context(|| format!("can not write datadir {:?} to backup", datadir))?;
+        archive
+            .into_inner()
+            .and_then(GzEncoder::finish)
+            .and_then(|file| file.sync_all())
+            .with_context(|| format!("can not write backup file {:?}", partial))?;
+
+        fs::rename(&partial, path)
+            .with_context(|| format!("can not move backup file to {:?}", path))?;
+
+        self.prune()
+    }
+
+    /// Remove the oldest backups until only `keep` are left. The names start
+    /// with the time they were written so they sort from oldest to newest.
+    fn prune(&self) -> Result<(), Error> {
+        let suffix = format!(".{}", BACKUP_EXTENSION);
+
+        let mut backups = fs::read_dir(&self.directory)
+            .with_context(|| format!("can not read backup directory {:?}", self.directory))?
+            .filter_map(Result::ok)
+            .map(|entry| entry.path())
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|name| name.to_str())
+                    .map(|name| name.ends_with(&suffix))
+                    .unwrap_or(false)
+            })
+            .collect::<Vec<_>>();
+        backups.sort();
+
+        let remove = backups.len().saturating_sub(self.keep);
+        for backup in backups.into_iter().take(remove) {
+            debug!("removing old backup {:?}", backup);
+
+            fs::remove_file(&backup)
+                .with_context(|| format!("can not remove old backup {:?}", backup))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Store {
+    /// Write a backup of the datadir before an operation that removes data
+    /// if `auto_backup_before_destructive` is set. Returns where the backup
+    /// was written or would be written in a dry run.
+    pub(crate) fn backup_before(&self, operation: &str) -> Result<Option<PathBuf>, Error> {
+        if !self.config.backups.auto_backup_before_destructive {
+            return Ok(None);
+        }
+
+        let path = self.backups.next_path(operation);
+        self.changes.apply(
+            || format!("back up the datadir to {:?}", path),
+            || {
+                self.backups.create(&self.datadir, &path).with_context(|| {
+                    format!(
+                        "can not back up the datadir before {}, pass --no_backup to skip the \
+                         backup",
+                        operation
+                    )
+                })
+            },
+        )?;
+
+        Ok(Some(path))
+    }
+}
@@ -0,0 +1,89 @@
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+/// Every write of the store to the datadir, the state directory or the
+/// version control and every hook or webhook goes through here. In a dry run
+/// the changes are not made but described so the command can show the plan
+/// once it is done. The plan is shared between clones so every part of the
+/// store adds to the same plan.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Changes {
+    dry_run: bool,
+    plan: Arc<Mutex<Vec<String>>>,
+}
+
+impl Changes {
+    /// Changes that are only described and never made.
+    pub(crate) fn dry_run() -> Self {
+        Self {
+            dry_run: true,
+            plan: Arc::default(),
+        }
+    }
+
+    pub(crate) fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Make the change and return its result. In a dry run the change is
+    /// only added to the plan with its description and nothing is returned.
+    /// The description completes "would" like "commit with message ...".
+    pub(crate) fn apply<T, E, D, F>(&self, description: D, change: F) -> Result<Option<T>, E>
+    where
+        D: FnOnce() -> String,
+        F: FnOnce() -> Result<T, E>,
+    {
+        if !self.dry_run {
+            return change().map(Some);
+        }
+
+        self.plan
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(description());
+
+        Ok(None)
+    }
+
+    /// The changes that were planned so far in the order they were planned.
+    /// They are removed from the plan.
+    pub(crate) fn take_plan(&self) -> Vec<String> {
+        std::mem::take(&mut *self.plan.lock().unwrap_or_else(|err| err.into_inner()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Error;
+
+    #[test]
+    fn applies_changes() {
+        let changes = Changes::default();
+
+        let result = changes.apply(|| "write".to_string(), || Ok::<_, Error>(1));
+
+        assert_eq!(result.unwrap(), Some(1));
+        assert!(changes.take_plan().is_empty());
+    }
+
+    #[test]
+    fn dry_run_only_plans_changes() {
+        let changes = Changes::dry_run();
+        let shared = changes.clone();
+
+        let result = changes.apply(
+            || "write".to_string(),
+            || -> Result<(), Error> { panic!("change was made in a dry run") },
+        );
+        shared
+            .apply(|| "commit".to_string(), || Ok::<_, Error>(()))
+            .unwrap();
+
+        assert_eq!(result.unwrap(), None);
+        assert_eq!(changes.take_plan(), vec!["write", "commit"]);
+        assert!(changes.take_plan().is_empty());
+    }
+}
@@ -0,0 +1,997 @@
+use crate::{
+    helper,
+    project_name,
+    project_name::SimilarProjects,
+    store::{
+        changes::Changes,
+        index::CompactionReport,
+        Store,
+    },
+};
+use anyhow::{
+    Context,
+    Error,
+};
+use chrono::Utc;
+use log::{
+    info,
+    trace,
+    warn,
+};
+use std::{
+    self,
+    collections::BTreeSet,
+    fmt,
+    fs,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+use uuid::Uuid;
+
+/// Entries with larger texts than this are reported as they are slow to
+/// render. The webservice does not accept texts of this size anymore.
+pub(crate) const LARGE_ENTRY_BYTES: u64 = 1024 * 1024;
+
+/// What the cleanup of a store did or would do in a dry run.
+#[derive(Debug, Default)]
+pub(crate) struct CleanupReport {
+    pub(crate) dry_run: bool,
+    pub(crate) compaction: CompactionReport,
+
+    /// Entry files that are not referenced by the index anymore.
+    pub(crate) unreferenced: Vec<RemovedFile>,
+
+    /// Entry files that were not stored under the path derived from their
+    /// uuid.
+    pub(crate) misplaced: Vec<MovedFile>,
+
+    /// Directories under `entries/` that were left empty.
+    pub(crate) empty_directories: Vec<PathBuf>,
+
+    /// Last viewed records of entries that do not exist anymore.
+    pub(crate) pruned_views: usize,
+
+    /// Focus records of entries that are not active anymore.
+    pub(crate) pruned_focus: usize,
+
+    /// Cached html of entries that do not exist anymore and of documents
+    /// that were not rendered in a long time.
+    pub(crate) pruned_renders: usize,
+
+    /// Entries whose line count was filled in.
+    pub(crate) line_counts: usize,
+
+    /// Entries whose creation time was filled in.
+    pub(crate) created_timestamps: usize,
+
+    /// Projects whose names only differ by whitespace, invisible characters
+    /// or unicode normalization.
+    pub(crate) similar_projects: Vec<SimilarProjects>,
+
+    /// Entries whose index rows show that a clock was behind when they were
+    /// changed.
+    pub(crate) clock_skew: Vec<Uuid>,
+
+    /// Entries whose text is larger than `LARGE_ENTRY_BYTES`. They are only
+    /// reported, splitting them up is left to the user.
+    pub(crate) large_entries: Vec<LargeEntry>,
+
+    /// Rows of the statistics history before and after rows that were
+    /// recorded twice were removed.
+    pub(crate) stats_history_rows: (usize, usize),
+
+    /// Backup of the datadir that was written before the cleanup.
+    pub(crate) backup: Option<PathBuf>,
+
+    /// Id of the commit that recorded the cleanup.
+    pub(crate) commit: Option<String>,
+}
+
+#[derive(Debug)]
+pub(crate) struct RemovedFile {
+    pub(crate) uuid: Uuid,
+    pub(crate) path: PathBuf,
+    pub(crate) bytes: u64,
+}
+
+#[derive(Debug)]
+pub(crate) struct LargeEntry {
+    pub(crate) uuid: Uuid,
+    pub(crate) path: PathBuf,
+    pub(crate) bytes: u64,
+}
+
+#[derive(Debug)]
+pub(crate) struct MovedFile {
+    pub(crate) uuid: Uuid,
+    pub(crate) from: PathBuf,
+    pub(crate) to: PathBuf,
+}
+
+impl CleanupReport {
+    /// Bytes that were or would be freed by the cleanup.
+    pub(crate) fn bytes_reclaimed(&self) -> u64 {
+        let index = self
+            .compaction
+            .bytes_before
+            .saturating_sub(self.compaction.bytes_after);
+
+        let files: u64 = self.unreferenced.iter().map(|file| file.bytes).sum();
+
+        index + files
+    }
+}
+
+/// Writes a summary of the cleanup. The alternate form (`{:#}`) also lists
+/// every affected uuid and path.
+impl fmt::Display for CleanupReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let verbose = f.alternate();
+        let compaction = &self.compaction;
+
+        if self.dry_run {
+            writeln!(f, "Dry run, nothing was changed.")?;
+        }
+
+        if let Some(backup) = &self.backup {
+            if self.dry_run {
+                writeln!(f, "Backup (destructive operation): {:?}", backup)?;
+            } else {
+                writeln!(f, "Backup written to: {:?}", backup)?;
+            }
+        }
+
+        writeln!(f, "Index fragments merged: {}", compaction.fragments)?;
+        if compaction.fragments_kept > 0 {
+            writeln!(f, "Index fragments kept: {}", compaction.fragments_kept)?;
+        }
+        writeln!(
+            f,
+            "Index rows: {} -> {}",
+            compaction.rows_before, compaction.rows_after
+        )?;
+        writeln!(
+            f,
+            "Duplicate uuids collapsed: {}",
+            compaction.duplicate_uuids
+        )?;
+
+        writeln!(
+            f,
+            "Stale index rows removed: {}",
+            compaction.stale_uuids.len()
+        )?;
+        if verbose {
+            for uuid in &compaction.stale_uuids {
+                writeln!(f, "  {}", uuid)?;
+            }
+        }
+
+        writeln!(
+            f,
+            "Deleted entries reduced to their deletion row: {}",
+            compaction.deleted_uuids.len()
+        )?;
+        if verbose {
+            for uuid in &compaction.deleted_uuids {
+                writeln!(f, "  {}", uuid)?;
+            }
+        }
+
+        writeln!(
+            f,
+            "Archived entries removed from the index: {}",
+            compaction.archived_uuids.len()
+        )?;
+        if verbose {
+            for uuid in &compaction.archived_uuids {
+                writeln!(f, "  {}", uuid)?;
+            }
+        }
+
+        writeln!(f, "Unreferenced files deleted: {}", self.unreferenced.len())?;
+        for file in &self.unreferenced {
+            if verbose {
+                writeln!(f, "  {} ({:?}, {} bytes)", file.uuid, file.path, file.bytes)?;
+            } else {
+                writeln!(f, "  {}", file.uuid)?;
+            }
+        }
+
+        writeln!(f, "Misplaced files moved: {}", self.misplaced.len())?;
+        if verbose {
+            for file in &self.misplaced {
+                writeln!(f, "  {} ({:?} -> {:?})", file.uuid, file.from, file.to)?;
+            }
+        }
+
+        writeln!(
+            f,
+            "Empty directories removed: {}",
+            self.empty_directories.len()
+        )?;
+        if verbose {
+            for directory in &self.empty_directories {
+                writeln!(f, "  {:?}", directory)?;
+            }
+        }
+
+        writeln!(f, "Stale view records removed: {}", self.pruned_views)?;
+
+        writeln!(f, "Stale focus records removed: {}", self.pruned_focus)?;
+
+        writeln!(f, "Stale rendered html removed: {}", self.pruned_renders)?;
+
+        writeln!(f, "Line counts filled in: {}", self.line_counts)?;
+
+        writeln!(f, "Creation times filled in: {}", self.created_timestamps)?;
+
+        writeln!(f, "Similar project names: {}", self.similar_projects.len())?;
+        for similar in &self.similar_projects {
+            let projects = similar
+                .projects
+                .iter()
+                .map(|project| format!("{:?}", project))
+                .collect::<Vec<_>>();
+
+            writeln!(f, "  {} -> {:?}", projects.join(", "), similar.canonical)?;
+        }
+
+        writeln!(
+            f,
+            "Entries changed with clock skew: {}",
+            self.clock_skew.len()
+        )?;
+        for uuid in &self.clock_skew {
+            writeln!(f, "  {}", uuid)?;
+        }
+
+        writeln!(
+            f,
+            "Entries larger than {} bytes: {}",
+            LARGE_ENTRY_BYTES,
+            self.large_entries.len()
+        )?;
+        for entry in &self.large_entries {
+            if verbose {
+                writeln!(
+                    f,
+                    "  {} ({:?}, {} bytes)",
+                    entry.uuid, entry.path, entry.bytes
+                )?;
+            } else {
+                writeln!(f, "  {} ({} bytes)", entry.uuid, entry.bytes)?;
+            }
+        }
+
+        writeln!(
+            f,
+            "Statistics history rows: {} -> {}",
+            self.stats_history_rows.0, self.stats_history_rows.1
+        )?;
+
+        writeln!(f, "Bytes reclaimed: {}", self.bytes_reclaimed())?;
+
+        match &self.commit {
+            Some(commit) => writeln!(f, "Commit: {}", commit),
+            None => writeln!(f, "Commit: -"),
+        }
+    }
+}
+
+/// Remove all directories below `folder` that are empty or only contain
+/// paths from `vacated`, which are files the cleanup removed or moved away.
+/// Directories in `occupied` received moved files and are always kept.
+/// `folder` itself is never removed. Returns the removed directories deepest
+/// first.
+pub(crate) fn remove_empty_directories(
+    folder: &Path,
+    vacated: &BTreeSet<PathBuf>,
+    occupied: &BTreeSet<PathBuf>,
+    changes: &Changes,
+) -> Result<Vec<PathBuf>, Error> {
+    let filter = DirectoryFilter { vacated, occupied };
+
+    let mut removed = Vec::new();
+
+    for entry in fs::read_dir(folder).with_context(|| format!("can not read {:?}", folder))? {
+        let path = entry?.path();
+
+        if path.is_dir() {
+            collect_empty_directory(&path, &filter, changes, &mut removed)?;
+        }
+    }
+
+    Ok(removed)
+}
+
+struct DirectoryFilter<'a> {
+    vacated: &'a BTreeSet<PathBuf>,
+    occupied: &'a BTreeSet<PathBuf>,
+}
+
+/// Returns true if `folder` is empty after the cleanup.
+fn collect_empty_directory(
+    folder: &Path,
+    filter: &DirectoryFilter<'_>,
+    changes: &Changes,
+    removed: &mut Vec<PathBuf>,
+) -> Result<bool, Error> {
+    let mut empty = !filter.occupied.contains(folder);
+
+    for entry in fs::read_dir(folder).with_context(|| format!("can not read {:?}", folder))? {
+        let path = entry?.path();
+
+        let path_empty = if path.is_dir() {
+            collect_empty_directory(&path, filter, changes, removed)?
+        } else {
+            filter.vacated.contains(&path)
+        };
+
+        empty &= path_empty;
+    }
+
+    if empty {
+        changes.apply(
+            || format!("remove empty directory {:?}", folder),
+            || {
+                info!("remove empty directory: {:?}", folder);
+                fs::remove_dir(folder).with_context(|| format!("can not remove {:?}", folder))
+            },
+        )?;
+
+        removed.push(folder.to_path_buf());
+    }
+
+    Ok(empty)
+}
+
+/// Check if `path` exists with exactly this file name. Unlike
+/// [`Path::exists`] this does not match files whose names only differ in case
+/// on case insensitive filesystems.
+pub(crate) fn exists_exactly(path: &Path) -> Result<bool, Error> {
+    let (parent, file_name) = match (path.parent(), path.file_name()) {
+        (Some(parent), Some(file_name)) => (parent, file_name),
+        _ => return Ok(false),
+    };
+
+    if !parent.is_dir() {
+        return Ok(false);
+    }
+
+    for entry in fs::read_dir(parent).with_context(|| format!("can not read {:?}", parent))? {
+        if entry?.file_name() == file_name {
+            return Ok(true);
+        }
+    }
+
+    Ok(false)
+}
+
+impl Store {
+    /// Uuids of the entries the index references. Archived entries are still
+    /// referenced by the archive index.
+    fn referenced_uuids(&self) -> Result<BTreeSet<Uuid>, Error> {
+        Ok(self
+            .index
+            .metadata_most_recent()?
+            .iter()
+            .chain(self.archived_metadata()?.iter())
+            .map(|metadata| metadata.uuid)
+            .collect())
+    }
+
+    /// Find entry files that are not referenced by the index and remove them.
+    fn cleanup_unreferenced_entry(&self) -> Result<Vec<RemovedFile>, Error> {
+        let store_uuids = self.referenced_uuids()?;
+
+        let mut removed = Vec::new();
+
+        for (path, uuid) in self.entry_files_with_uuid()? {
+            trace!("uuid from file entry: {:?}", uuid);
+
+            if store_uuids.contains(&uuid) {
+                continue;
+            }
+
+            let bytes = fs::metadata(&path)
+                .with_context(|| format!("can not read metadata of {:?}", path))?
+                .len();
+
+            self.changes.apply(
+                || format!("remove unreferenced entry file {:?}", path),
+                || {
+                    info!("remove unreferenced entry: {:?}", path);
+                    fs::remove_file(&path)
+                },
+            )?;
+
+            removed.push(RemovedFile { uuid, path, bytes });
+        }
+
+        Ok(removed)
+    }
+
+    /// Move entry files that are not stored under the path derived from their
+    /// uuid to that path. File names are compared after parsing the uuid so
+    /// uppercase file names from other tools are normalized to the lowercase
+    /// form the store writes. Files that are not referenced are left where
+    /// they are as they are removed afterwards.
+    fn normalize_entry_paths(&self) -> Result<Vec<MovedFile>, Error> {
+        let store_uuids = self.referenced_uuids()?;
+
+        let mut moved = Vec::new();
+
+        for (from, uuid) in self.entry_files_with_uuid()? {
+            let to = self.get_entry_filename_for_uuid(&uuid);
+            if from == to || !store_uuids.contains(&uuid) {
+                continue;
+            }
+
+            // Only compare against the exact file name so a file whose name
+            // only differs in case is not mistaken for the target on case
+            // insensitive filesystems. Existing files are never overwritten.
+            if exists_exactly(&to)? {
+                warn!(
+                    "can not move {:?} to {:?} as the target already exists",
+                    from, to
+                );
+                continue;
+            }
+
+            self.changes.apply(
+                || format!("move misplaced entry file {:?} to {:?}", from, to),
+                || {
+                    info!("move misplaced entry: {:?} -> {:?}", from, to);
+                    fs::create_dir_all(self.get_entry_foldername_for_uuid(&uuid))
+                        .context("can not create entry folder")?;
+
+                    // Go through a temporary name so the rename also changes
+                    // the case on case insensitive filesystems.
+                    let temporary = from.with_extension("adoc.move");
+                    fs::rename(&from, &temporary)
+                        .with_context(|| format!("can not move {:?}", from))?;
+                    fs::rename(&temporary, &to).with_context(|| format!("can not move {:?}", from))
+                },
+            )?;
+
+            moved.push(MovedFile { uuid, from, to });
+        }
+
+        Ok(moved)
+    }
+
+    /// Compact the index, drop index rows of entries without an entry file
+    /// and remove entry files that are not referenced anymore. With
+    /// `keep_days` index fragments from the last days are not merged. The
+    /// cleanup is destructive so a backup is written first unless `backup`
+    /// is false. Only reports what would be done in a dry run.
+    pub(crate) fn run_cleanup(
+        &self,
+        keep_days: Option<u32>,
+        backup: bool,
+    ) -> Result<CleanupReport, Error> {
+        let backup = if backup {
+            self.backup_before("cleanup")?
+        } else {
+            None
+        };
+
+        // Misplaced files have to be moved first as their index rows would be
+        // removed as stale otherwise.
+        let misplaced = self.normalize_entry_paths()?;
+        let moved_uuids = misplaced
+            .iter()
+            .map(|file| file.uuid)
+            .collect::<BTreeSet<_>>();
+
+        let (line_counts, created_timestamps) = self.backfill_metadata()?;
+
+        // Has to run before the compaction drops the older rows.
+        let clock_skew = self.index.clock_skewed_uuids()?;
+
+        // Index fragments are named after the UTC day they were started on.
+        let merge_before = keep_days
+            .map(|days| Utc::now().date().naive_utc() - chrono::Duration::days(i64::from(days)));
+
+        let archived = self.superseded_by_archive()?;
+
+        let compaction = self.index.compact(
+            |metadata| {
+                !moved_uuids.contains(&metadata.uuid)
+                    && self.find_entry_file(&metadata.uuid).is_none()
+            },
+            &archived,
+            merge_before,
+        )?;
+
+        let unreferenced = self.cleanup_unreferenced_entry()?;
+
+        let existing = self
+            .index
+            .metadata_most_recent()?
+            .iter()
+            .map(|metadata| metadata.uuid)
+            .filter(|uuid| !compaction.stale_uuids.contains(uuid))
+            .collect::<BTreeSet<_>>();
+
+        let pruned_views = self.views.prune(&existing)?;
+
+        let pruned_renders = self.render_cache.prune(&existing)?;
+
+        let active = self
+            .index
+            .metadata_most_recent()?
+            .iter()
+            .filter(|metadata| metadata.is_active() && existing.contains(&metadata.uuid))
+            .map(|metadata| metadata.uuid)
+            .collect::<BTreeSet<_>>();
+
+        let pruned_focus = self.focus.prune(&active)?;
+
+        let similar_projects = project_name::similar_projects(self.get_projects()?);
+
+        let stats_history_rows = self.stats_history.compact()?;
+
+        let large_entries = existing
+            .iter()
+            .filter_map(|uuid| {
+                let path = self.find_entry_file(uuid)?;
+                let bytes = fs::metadata(&path).ok()?.len();
+
+                Some(LargeEntry {
+                    uuid: *uuid,
+                    path,
+                    bytes,
+                })
+            })
+            .filter(|entry| entry.bytes > LARGE_ENTRY_BYTES)
+            .collect();
+
+        let vacated = unreferenced
+            .iter()
+            .map(|file| file.path.clone())
+            .chain(misplaced.iter().map(|file| file.from.clone()))
+            .collect::<BTreeSet<_>>();
+
+        let mut entries_folder = self.datadir.clone();
+        entries_folder.push("entries");
+
+        let empty_directories = if entries_folder.exists() {
+            let occupied = misplaced
+                .iter()
+                .filter_map(|file| file.to.parent())
+                .map(Path::to_path_buf)
+                .collect::<BTreeSet<_>>();
+
+            remove_empty_directories(&entries_folder, &vacated, &occupied, &self.changes)?
+        } else {
+            Vec::new()
+        };
+
+        let mut report = CleanupReport {
+            dry_run: self.changes.is_dry_run(),
+            compaction,
+            unreferenced,
+            misplaced,
+            empty_directories,
+            pruned_views,
+            pruned_focus,
+            pruned_renders,
+            line_counts,
+            created_timestamps,
+            similar_projects,
+            clock_skew,
+            large_entries,
+            stats_history_rows,
+            backup,
+            commit: None,
+        };
+
+        report.commit = self.commit("ran cleanup")?;
+
+        Ok(report)
+    }
+
+    /// Record the line count and the creation time of entries whose metadata
+    /// was written before they were recorded. The creation time is the start
+    /// of the entry. The new rows keep the last change so the entries do not
+    /// look like they were just changed. Returns how many line counts and
+    /// creation times were or would be filled in.
+    fn backfill_metadata(&self) -> Result<(usize, usize), Error> {
+        let mut line_counts = 0;
+        let mut created_timestamps = 0;
+
+        for metadata in self.index.metadata_most_recent()? {
+            if metadata.line_count.is_some() && metadata.created.is_some() {
+                continue;
+            }
+
+            let entry_file = match self.find_entry_file(&metadata.uuid) {
+                Some(entry_file) => entry_file,
+                None => continue,
+            };
+
+            let line_count = match metadata.line_count {
+                Some(line_count) => line_count,
+                None => {
+                    line_counts += 1;
+
+                    let text = fs::read_to_string(entry_file)
+                        .with_context(|| format!("can not read text of entry {}", metadata.uuid))?;
+
+                    helper::line_count(&text)
+                }
+            };
+
+            if metadata.created.is_none() {
+                created_timestamps += 1;
+            }
+
+            let metadata = metadata
+                .changed()
+                .line_count(line_count)
+                .keep_last_change()
+                .build()?;
+
+            self.append_metadata(&metadata)?;
+        }
+
+        Ok((line_counts, created_timestamps))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        helper,
+        store::{
+            changes::Changes,
+            tests::{
+                entry,
+                open,
+                temporary,
+            },
+            Store,
+        },
+    };
+    use chrono::Duration;
+    use std::{
+        fs,
+        path::{
+            Path,
+            PathBuf,
+        },
+    };
+    use uuid::Uuid;
+
+    /// Add an entry whose file is gone so its index rows are stale and an
+    /// entry file the index does not know about. Returns both uuids. The line
+    /// counts are filled in so the cleanup does not append rows for them.
+    fn break_store(store: &Store) -> (Uuid, Uuid) {
+        let mut kept = entry("kept entry");
+        kept.metadata.line_count = Some(1);
+        store.add_entry(kept).unwrap();
+
+        let mut stale = entry("stale entry");
+        stale.metadata.line_count = Some(1);
+        store.add_entry(stale.clone()).unwrap();
+        fs::remove_file(store.get_entry_filename_for_uuid(&stale.metadata.uuid)).unwrap();
+
+        let unreferenced = Uuid::new_v4();
+        let path = store.get_entry_filename_for_uuid(&unreferenced);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "unreferenced").unwrap();
+
+        (stale.metadata.uuid, unreferenced)
+    }
+
+    fn datadir_files(datadir: &Path) -> Vec<(String, Vec<u8>)> {
+        let mut files = Vec::new();
+        let mut folders = vec![datadir.to_path_buf()];
+
+        while let Some(folder) = folders.pop() {
+            for entry in fs::read_dir(folder).unwrap() {
+                let path = entry.unwrap().path();
+                if path.ends_with(".git") {
+                    continue;
+                }
+
+                if path.is_dir() {
+                    folders.push(path);
+                } else {
+                    let name = path.strip_prefix(datadir).unwrap().display().to_string();
+                    files.push((name, fs::read(&path).unwrap()));
+                }
+            }
+        }
+
+        files.sort();
+        files
+    }
+
+    #[test]
+    fn dry_run_reports_what_the_cleanup_would_do() {
+        let directory = tempfile::tempdir().unwrap();
+        let datadir = directory.path().join("datadir");
+        let state_home = directory.path().join("state");
+        fs::create_dir_all(&datadir).unwrap();
+
+        let store = open(&datadir, &state_home, &Changes::default());
+        let (stale, unreferenced) = break_store(&store);
+        let before = datadir_files(&datadir);
+
+        let store = open(&datadir, &state_home, &Changes::dry_run());
+        let report = store.run_cleanup(None, false).unwrap();
+        assert_eq!(datadir_files(&datadir), before);
+
+        assert!(report.dry_run);
+        assert_eq!(report.compaction.stale_uuids, vec![stale]);
+        assert_eq!(
+            report
+                .unreferenced
+                .iter()
+                .map(|file| file.uuid)
+                .collect::<Vec<_>>(),
+            vec![unreferenced]
+        );
+        assert_eq!(report.unreferenced[0].bytes, "unreferenced".len() as u64);
+        assert_eq!(report.commit, None);
+
+        let summary = report.to_string();
+        assert!(summary.starts_with("Dry run, nothing was changed.\n"));
+        assert!(summary.contains("Stale index rows removed: 1\n"));
+        assert!(summary.contains(&format!(
+            "Unreferenced files deleted: 1\n  {}\n",
+            unreferenced
+        )));
+        assert!(summary.ends_with("Commit: -\n"));
+        // Stale rows are only listed one by one in the verbose summary.
+        assert!(!summary.contains(&stale.to_string()));
+
+        let verbose = format!("{:#}", report);
+        assert!(verbose.contains(&format!("Stale index rows removed: 1\n  {}\n", stale)));
+        assert!(verbose.contains(&format!(
+            "  {} ({:?}, 12 bytes)\n",
+            unreferenced, report.unreferenced[0].path
+        )));
+    }
+
+    #[test]
+    fn dry_run_reports_the_same_as_the_cleanup() {
+        let directory = tempfile::tempdir().unwrap();
+        let datadir = directory.path().join("datadir");
+        let state_home = directory.path().join("state");
+        fs::create_dir_all(&datadir).unwrap();
+
+        let store = open(&datadir, &state_home, &Changes::default());
+        break_store(&store);
+
+        let planned = open(&datadir, &state_home, &Changes::dry_run())
+            .run_cleanup(None, false)
+            .unwrap();
+        let done = store.run_cleanup(None, false).unwrap();
+        assert!(!done.dry_run);
+
+        // Only the first line and the commit differ.
+        let planned = planned.to_string();
+        let done = done.to_string();
+        assert_eq!(
+            planned.lines().skip(1).collect::<Vec<_>>(),
+            done.lines()
+                .filter(|line| !line.starts_with("Commit: "))
+                .chain(Some("Commit: -"))
+                .collect::<Vec<_>>()
+        );
+    }
+
+    /// Move the file of the entry to `relative` below the entries folder.
+    fn misplace(store: &Store, uuid: &Uuid, relative: &str) -> PathBuf {
+        let to = store.datadir.join("entries").join(relative);
+        fs::create_dir_all(to.parent().unwrap()).unwrap();
+        fs::rename(store.get_entry_filename_for_uuid(uuid), &to).unwrap();
+
+        to
+    }
+
+    #[test]
+    fn cleanup_repairs_a_messed_up_entries_tree() {
+        let (_directory, store) = temporary();
+        let entries = store.datadir.join("entries");
+
+        let mut uuids = Vec::new();
+        for text in &["wrong prefix", "uppercase name", "in place"] {
+            let mut added = entry(text);
+            added.metadata.line_count = Some(1);
+            uuids.push(added.metadata.uuid);
+            store.add_entry(added).unwrap();
+        }
+
+        // Prefixes are two hex digits so "zz" is never the right one.
+        let wrong_prefix = misplace(&store, &uuids[0], &format!("zz/{}.adoc", uuids[0]));
+        let uppercase = uuids[1].to_string().to_uppercase();
+        let uppercase_name = misplace(
+            &store,
+            &uuids[1],
+            &format!("{}/{}.adoc", &uppercase[..2], uppercase),
+        );
+
+        let orphaned = Uuid::new_v4();
+        let orphaned_file = entries.join(format!("zz/nested/{}.adoc", orphaned));
+        fs::create_dir_all(orphaned_file.parent().unwrap()).unwrap();
+        fs::write(&orphaned_file, "orphaned").unwrap();
+
+        let stray_files = [
+            entries.join("zz/notes.txt"),
+            entries.join("yy/not-a-uuid.adoc"),
+        ];
+        for stray in &stray_files {
+            fs::create_dir_all(stray.parent().unwrap()).unwrap();
+            fs::write(stray, "stray").unwrap();
+        }
+        fs::create_dir_all(entries.join("xx/empty")).unwrap();
+
+        let report = store.run_cleanup(None, false).unwrap();
+
+        let mut misplaced = report
+            .misplaced
+            .iter()
+            .map(|file| (file.uuid, file.from.clone()))
+            .collect::<Vec<_>>();
+        misplaced.sort();
+        let mut expected = vec![(uuids[0], wrong_prefix), (uuids[1], uppercase_name)];
+        expected.sort();
+        assert_eq!(misplaced, expected);
+
+        assert_eq!(
+            report
+                .unreferenced
+                .iter()
+                .map(|file| (file.uuid, file.path.clone()))
+                .collect::<Vec<_>>(),
+            vec![(orphaned, orphaned_file)]
+        );
+        assert!(report.compaction.stale_uuids.is_empty());
+
+        // Directories with stray files are kept and the others are removed
+        // deepest first.
+        let removed = &report.empty_directories;
+        let position = |relative: &str| {
+            removed
+                .iter()
+                .position(|directory| directory == &entries.join(relative))
+        };
+        assert_eq!(position("zz"), None);
+        assert_eq!(position("yy"), None);
+        assert!(position("zz/nested").is_some());
+        assert!(position("xx/empty").is_some());
+        assert!(position("xx/empty") < position("xx"));
+
+        for (uuid, text) in uuids
+            .iter()
+            .zip(&["wrong prefix", "uppercase name", "in place"])
+        {
+            assert_eq!(
+                store.find_entry_file(uuid),
+                Some(store.get_entry_filename_for_uuid(uuid))
+            );
+            assert_eq!(&store.get_entry_by_uuid(uuid).unwrap().text, text);
+        }
+        for stray in &stray_files {
+            assert_eq!(fs::read_to_string(stray).unwrap(), "stray");
+        }
+
+        let again = store.run_cleanup(None, false).unwrap();
+        assert!(again.misplaced.is_empty());
+        assert!(again.unreferenced.is_empty());
+        assert!(again.empty_directories.is_empty());
+    }
+
+    #[test]
+    fn cleanup_stops_at_a_broken_index_row() {
+        let (_directory, store) = temporary();
+        let mut added = entry("kept entry");
+        added.metadata.line_count = Some(1);
+        let uuid = added.metadata.uuid;
+        store.add_entry(added).unwrap();
+        let misplaced = misplace(&store, &uuid, &format!("zz/{}.adoc", uuid));
+
+        let fragment = helper::files_with_extension(&store.datadir.join("index"), "csv")
+            .unwrap()
+            .remove(0);
+        let mut rows = fs::read_to_string(&fragment).unwrap();
+        rows.push_str("not,a,valid,row\n");
+        fs::write(&fragment, &rows).unwrap();
+
+        let err = store.run_cleanup(None, false).unwrap_err();
+        assert!(format!("{:#}", err).contains(&fragment.display().to_string()));
+        assert_eq!(fs::read_to_string(&fragment).unwrap(), rows);
+        assert_eq!(fs::read_to_string(&misplaced).unwrap(), "kept entry");
+    }
+
+    #[test]
+    fn backfill_fills_in_missing_line_counts() {
+        let (_directory, store) = temporary();
+        let old = entry("first\nsecond");
+        let uuid = old.metadata.uuid;
+        store.add_entry(old).unwrap();
+        assert_eq!(
+            store.get_entry_by_uuid(&uuid).unwrap().metadata.line_count,
+            None
+        );
+
+        assert_eq!(store.backfill_metadata().unwrap(), (1, 0));
+
+        let current = store.get_entry_by_uuid(&uuid).unwrap().metadata;
+        assert_eq!(current.line_count, Some(2));
+        assert_eq!(current.revision, 1);
+        assert_eq!(store.backfill_metadata().unwrap(), (0, 0));
+    }
+
+    #[test]
+    fn backfill_fills_in_the_creation_of_legacy_entries() {
+        let (_directory, store) = temporary();
+        let mut old = entry("legacy entry");
+        old.metadata.line_count = Some(1);
+        old.metadata.created = None;
+        old.metadata.started = old.metadata.started - Duration::days(3);
+        let (uuid, started, last_change) = (
+            old.metadata.uuid,
+            old.metadata.started,
+            old.metadata.last_change,
+        );
+        store.add_entry(old).unwrap();
+        assert_eq!(
+            store.get_entry_by_uuid(&uuid).unwrap().metadata.created(),
+            started
+        );
+
+        assert_eq!(store.backfill_metadata().unwrap(), (0, 1));
+
+        let current = store.get_entry_by_uuid(&uuid).unwrap().metadata;
+        assert_eq!(current.created, Some(started));
+        assert_eq!(current.started, started);
+        assert_eq!(current.last_change, last_change);
+        assert_eq!(store.backfill_metadata().unwrap(), (0, 0));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn datadir_that_is_not_utf8_can_be_cleaned_up() {
+        use std::{
+            ffi::OsStr,
+            os::unix::ffi::OsStrExt,
+        };
+
+        let directory = tempfile::tempdir().unwrap();
+        let datadir = directory.path().join(OsStr::from_bytes(b"todos-\xff\xfe"));
+        assert!(datadir.to_str().is_none());
+        fs::create_dir_all(&datadir).unwrap();
+
+        let store = open(
+            &datadir,
+            &directory.path().join("state"),
+            &Changes::default(),
+        );
+        let kept = entry("kept entry");
+        store.add_entry(kept.clone()).unwrap();
+
+        let unreferenced = store.get_entry_filename_for_uuid(&uuid::Uuid::new_v4());
+        fs::create_dir_all(unreferenced.parent().unwrap()).unwrap();
+        fs::write(&unreferenced, "unreferenced").unwrap();
+
+        let report = store.run_cleanup(None, false).unwrap();
+        assert_eq!(report.unreferenced.len(), 1);
+        assert!(!unreferenced.exists());
+        assert!(format!("{:#}", report).contains("todos-"));
+
+        let active = store.get_active_entries("default").unwrap();
+        assert_eq!(active.len(), 1);
+        assert_eq!(
+            store.get_entry_by_uuid(&kept.metadata.uuid).unwrap().text,
+            "kept entry"
+        );
+    }
+}
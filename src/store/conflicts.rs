@@ -0,0 +1,762 @@
+use crate::{
+    entry::{
+        Entry,
+        Metadata,
+    },
+    store::{
+        changes::Changes,
+        index::FileFingerprint,
+        Store,
+    },
+    tags,
+};
+use anyhow::{
+    Context,
+    Error,
+};
+use chrono::{
+    DateTime,
+    Duration,
+    Utc,
+};
+use log::warn;
+use serde::{
+    Deserialize,
+    Deserializer,
+    Serialize,
+    Serializer,
+};
+use std::{
+    collections::{
+        BTreeMap,
+        BTreeSet,
+    },
+    fmt,
+    fs,
+    io::ErrorKind,
+    path::PathBuf,
+    sync::{
+        Arc,
+        Mutex,
+    },
+};
+use uuid::Uuid;
+
+/// Name of the file in the state directory that records the detected
+/// concurrent changes.
+pub(super) const CONFLICTS_FILE_NAME: &str = "conflicts.csv";
+
+/// Largest window in hours that fits into a chrono duration.
+const MAX_WINDOW_HOURS: u64 = i64::MAX as u64 / (60 * 60 * 1000);
+
+/// Metadata fields that are compared between concurrent rows. The last change,
+/// the revision and the line count always differ or follow the text which git
+/// merges on its own.
+pub(crate) const CONFLICT_FIELDS: &[ConflictField] = &[
+    ConflictField::Project,
+    ConflictField::Due,
+    ConflictField::Started,
+    ConflictField::Finished,
+    ConflictField::DedupKey,
+    ConflictField::Source,
+    ConflictField::Tags,
+    ConflictField::Deleted,
+    ConflictField::MovedTo,
+    ConflictField::Created,
+];
+
+/// Metadata field that can differ between concurrent rows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ConflictField {
+    Project,
+    Due,
+    Started,
+    Finished,
+    DedupKey,
+    Source,
+    Tags,
+    Deleted,
+    MovedTo,
+    Created,
+}
+
+impl ConflictField {
+    /// Name of the column of the field in the index.
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            ConflictField::Project => "project",
+            ConflictField::Due => "due",
+            ConflictField::Started => "started",
+            ConflictField::Finished => "finished",
+            ConflictField::DedupKey => "dedup_key",
+            ConflictField::Source => "source",
+            ConflictField::Tags => "tags",
+            ConflictField::Deleted => "deleted",
+            ConflictField::MovedTo => "moved_to",
+            ConflictField::Created => "created",
+        }
+    }
+}
+
+impl fmt::Display for ConflictField {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Rows of an entry that were written on different machines based on the same
+/// previous row. Only one of them wins when the most recent row is picked so
+/// the changes of the others are lost until the rows are merged.
+#[derive(Debug, Clone)]
+pub(crate) struct ConcurrentChange {
+    pub(crate) uuid: Uuid,
+    pub(crate) revision: u64,
+
+    /// The rows with the identifier they were written with, the one that
+    /// wins first.
+    pub(crate) rows: Vec<(String, Metadata)>,
+
+    /// Fields of `CONFLICT_FIELDS` that differ between the rows.
+    pub(crate) fields: Vec<ConflictField>,
+}
+
+impl ConcurrentChange {
+    /// The row that is currently used for the entry.
+    pub(crate) fn winner(&self) -> &Metadata {
+        &self.rows[0].1
+    }
+}
+
+/// Find entries whose most recent revision was written on more than one
+/// machine with different metadata and last changes that are at most `window`
+/// apart. Rows of the same machine follow each other and never conflict.
+pub(super) fn detect(rows: &[(String, Metadata)], window: Duration) -> Vec<ConcurrentChange> {
+    if window <= Duration::zero() {
+        return Vec::new();
+    }
+
+    let mut by_uuid: BTreeMap<Uuid, Vec<&(String, Metadata)>> = BTreeMap::new();
+    for row in rows {
+        by_uuid.entry(row.1.uuid).or_default().push(row);
+    }
+
+    let mut changes = Vec::new();
+
+    for (uuid, rows) in by_uuid {
+        let revision = match rows.iter().map(|(_, metadata)| metadata.revision).max() {
+            Some(revision) => revision,
+            None => continue,
+        };
+
+        // Newest row of every machine at the most recent revision.
+        let mut latest: BTreeMap<&str, &Metadata> = BTreeMap::new();
+        for (identifier, metadata) in rows
+            .iter()
+            .filter(|(_, metadata)| metadata.revision == revision)
+        {
+            match latest.get(identifier.as_str()) {
+                Some(current) if current.recency() >= metadata.recency() => {}
+                _ => {
+                    latest.insert(identifier, metadata);
+                }
+            }
+        }
+
+        if latest.len() < 2 {
+            continue;
+        }
+
+        let mut rows = latest
+            .into_iter()
+            .map(|(identifier, metadata)| (identifier.to_string(), metadata.clone()))
+            .collect::<Vec<_>>();
+        rows.sort_by(|a, b| b.1.recency().cmp(&a.1.recency()));
+
+        let newest = rows[0].1.last_change;
+        let oldest = rows.iter().map(|(_, metadata)| metadata.last_change).min();
+        if oldest.is_some_and(|oldest| newest - oldest > window) {
+            continue;
+        }
+
+        let fields = CONFLICT_FIELDS
+            .iter()
+            .copied()
+            .filter(|field| {
+                rows.iter().any(|(_, metadata)| {
+                    field_value(metadata, *field) != field_value(&rows[0].1, *field)
+                })
+            })
+            .collect::<Vec<_>>();
+
+        if fields.is_empty() {
+            continue;
+        }
+
+        changes.push(ConcurrentChange {
+            uuid,
+            revision,
+            rows,
+            fields,
+        });
+    }
+
+    changes
+}
+
+/// Value of the field for showing and comparing it.
+pub(crate) fn field_value(metadata: &Metadata, field: ConflictField) -> String {
+    fn optional<T: ToString>(value: &Option<T>) -> String {
+        value
+            .as_ref()
+            .map(ToString::to_string)
+            .unwrap_or_else(|| "-".to_string())
+    }
+
+    match field {
+        ConflictField::Project => metadata.project.clone(),
+        ConflictField::Due => optional(&metadata.due),
+        ConflictField::Started => metadata.started.to_string(),
+        ConflictField::Finished => optional(&metadata.finished),
+        ConflictField::DedupKey => optional(&metadata.dedup_key),
+        ConflictField::Source => optional(&metadata.source),
+        ConflictField::Tags if metadata.tags.is_empty() => "-".to_string(),
+        ConflictField::Tags => tags::join(&metadata.tags),
+        ConflictField::Deleted => optional(&metadata.deleted),
+        ConflictField::MovedTo => optional(&metadata.moved_to),
+        ConflictField::Created => optional(&metadata.created),
+    }
+}
+
+/// Copy the field from one row to another.
+pub(crate) fn take_field(target: &mut Metadata, source: &Metadata, field: ConflictField) {
+    match field {
+        ConflictField::Project => target.project = source.project.clone(),
+        ConflictField::Due => target.due = source.due,
+        ConflictField::Started => target.started = source.started,
+        ConflictField::Finished => target.finished = source.finished,
+        ConflictField::DedupKey => target.dedup_key = source.dedup_key.clone(),
+        ConflictField::Source => target.source = source.source.clone(),
+        ConflictField::Tags => target.tags = source.tags.clone(),
+        ConflictField::Deleted => target.deleted = source.deleted,
+        ConflictField::MovedTo => target.moved_to = source.moved_to.clone(),
+        ConflictField::Created => target.created = source.created,
+    }
+}
+
+/// A concurrent change that was detected on this machine.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct ConflictRecord {
+    pub(crate) uuid: Uuid,
+    pub(crate) revision: u64,
+    #[serde(with = "field_list")]
+    pub(crate) fields: Vec<String>,
+    pub(crate) detected: DateTime<Utc>,
+
+    /// When the rows were merged with `todust conflicts --resolve`. Conflicts
+    /// that disappear because the entry was changed again stay unresolved.
+    #[serde(default)]
+    pub(crate) resolved: Option<DateTime<Utc>>,
+}
+
+/// Write the fields as one comma separated value so every record is a single
+/// csv row.
+mod field_list {
+    use super::*;
+
+    pub(super) fn serialize<S: Serializer>(
+        fields: &[String],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&fields.join(","))
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<String>, D::Error> {
+        let field = String::deserialize(deserializer)?;
+
+        Ok(field
+            .split(',')
+            .filter(|name| !name.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+}
+
+/// Warns about concurrent changes once and records them in `conflicts.csv` so
+/// they can be looked at later.
+#[derive(Debug, Clone)]
+pub(crate) struct ConflictLog {
+    path: PathBuf,
+    window: Duration,
+    changes: Changes,
+
+    /// Concurrent changes that were already warned about by this process.
+    seen: Arc<Mutex<BTreeSet<(Uuid, u64)>>>,
+
+    /// Fingerprint of the index files the last detection ran on.
+    checked: Arc<Mutex<Option<Vec<FileFingerprint>>>>,
+
+    /// Serializes the read-modify-write cycles of concurrent web requests.
+    lock: Arc<Mutex<()>>,
+}
+
+impl ConflictLog {
+    pub(super) fn new(path: PathBuf, window_hours: u64, changes: Changes) -> Self {
+        Self {
+            path,
+            // Larger windows than chrono can represent cover everything anyway.
+            window: Duration::hours(window_hours.min(MAX_WINDOW_HOURS) as i64),
+            changes,
+            seen: Arc::new(Mutex::new(BTreeSet::new())),
+            checked: Arc::new(Mutex::new(None)),
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// How far apart the last changes of concurrent rows can be.
+    pub(super) fn window(&self) -> Duration {
+        self.window
+    }
+
+    /// If the index files changed since the last detection. Remembers the
+    /// fingerprint so unchanged reads of the index skip the detection.
+    pub(super) fn index_changed(&self, fingerprint: &[FileFingerprint]) -> bool {
+        let mut checked = self.checked.lock().unwrap_or_else(|err| err.into_inner());
+
+        if checked.as_deref() == Some(fingerprint) {
+            return false;
+        }
+
+        *checked = Some(fingerprint.to_vec());
+        true
+    }
+
+    /// Warn about and record the concurrent changes that were not seen
+    /// before. Without `warn` they are only recorded, for when they are shown
+    /// to the user anyway. Failing to record them is only a warning as this
+    /// runs whenever the index changed.
+    pub(super) fn report(&self, changes: &[ConcurrentChange], warn: bool) {
+        let mut seen = self.seen.lock().unwrap_or_else(|err| err.into_inner());
+
+        let new = changes
+            .iter()
+            .filter(|change| seen.insert((change.uuid, change.revision)))
+            .collect::<Vec<_>>();
+
+        if new.is_empty() {
+            return;
+        }
+
+        for change in new.iter().filter(|_| warn) {
+            warn!(
+                "entry {} was changed on {} at the same time, the {} of {} is used, run `todust \
+                 conflicts --resolve` to merge the changes",
+                change.uuid,
+                change
+                    .rows
+                    .iter()
+                    .map(|(identifier, _)| identifier.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" and "),
+                change
+                    .fields
+                    .iter()
+                    .map(|field| field.name())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                change.rows[0].0,
+            );
+        }
+
+        if let Err(err) = self.record(&new) {
+            warn!("can not record concurrent changes: {:#}", err);
+        }
+    }
+
+    fn record(&self, changes: &[&ConcurrentChange]) -> Result<(), Error> {
+        let _guard = self.lock.lock().unwrap_or_else(|err| err.into_inner());
+
+        let mut records = self.load()?;
+        let before = records.len();
+
+        for change in changes {
+            let known = records
+                .iter()
+                .any(|record| record.uuid == change.uuid && record.revision == change.revision);
+
+            if !known {
+                records.push(ConflictRecord {
+                    uuid: change.uuid,
+                    revision: change.revision,
+                    fields: change.fields.iter().map(ToString::to_string).collect(),
+                    detected: Utc::now(),
+                    resolved: None,
+                });
+            }
+        }
+
+        if records.len() == before {
+            return Ok(());
+        }
+
+        self.write(&records)
+    }
+
+    /// Mark the concurrent change as merged.
+    pub(super) fn resolve(&self, change: &ConcurrentChange) -> Result<(), Error> {
+        let _guard = self.lock.lock().unwrap_or_else(|err| err.into_inner());
+
+        let mut records = self.load()?;
+
+        match records
+            .iter_mut()
+            .find(|record| record.uuid == change.uuid && record.revision == change.revision)
+        {
+            Some(record) => record.resolved = Some(Utc::now()),
+            None => records.push(ConflictRecord {
+                uuid: change.uuid,
+                revision: change.revision,
+                fields: change.fields.iter().map(ToString::to_string).collect(),
+                detected: Utc::now(),
+                resolved: Some(Utc::now()),
+            }),
+        }
+
+        self.write(&records)
+    }
+
+    /// All concurrent changes that were detected on this machine. A missing
+    /// file has no records.
+    pub(crate) fn load(&self) -> Result<Vec<ConflictRecord>, Error> {
+        let mut reader = match csv::Reader::from_path(&self.path) {
+            Ok(reader) => reader,
+            Err(err) => match err.kind() {
+                csv::ErrorKind::Io(io) if io.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+                _ => {
+                    return Err(err)
+                        .with_context(|| format!("can not open conflicts file {:?}", self.path))
+                }
+            },
+        };
+
+        reader
+            .deserialize()
+            .collect::<Result<Vec<ConflictRecord>, _>>()
+            .with_context(|| format!("can not read conflicts file {:?}", self.path))
+    }
+
+    /// Replace the conflicts file with the records. The file is written to a
+    /// temporary file first so a crash never leaves half written records.
+    fn write(&self, records: &[ConflictRecord]) -> Result<(), Error> {
+        self.changes.apply(
+            || {
+                format!(
+                    "write {} conflict records to {:?}",
+                    records.len(),
+                    self.path
+                )
+            },
+            || self.replace(records),
+        )?;
+
+        Ok(())
+    }
+
+    fn replace(&self, records: &[ConflictRecord]) -> Result<(), Error> {
+        if let Some(directory) = self.path.parent() {
+            fs::create_dir_all(directory)
+                .with_context(|| format!("can not create directory {:?}", directory))?;
+        }
+
+        let temporary = self.path.with_extension("csv.tmp");
+
+        let mut writer = csv::Writer::from_path(&temporary)
+            .with_context(|| format!("can not create conflicts file {:?}", temporary))?;
+        for record in records {
+            writer
+                .serialize(record)
+                .context("can not serialize conflict record")?;
+        }
+        writer
+            .flush()
+            .with_context(|| format!("can not write conflicts file {:?}", temporary))?;
+
+        fs::rename(&temporary, &self.path)
+            .with_context(|| format!("can not replace conflicts file {:?}", self.path))
+    }
+}
+
+impl Store {
+    /// Entries that were changed on multiple machines at the same time.
+    pub(crate) fn concurrent_changes(&self) -> Result<Vec<ConcurrentChange>, Error> {
+        Ok(self.index.concurrent_changes()?)
+    }
+
+    /// Concurrent changes that were detected on this machine so far.
+    pub(crate) fn conflict_records(&self) -> Result<Vec<ConflictRecord>, Error> {
+        self.conflicts.load()
+    }
+
+    /// Write the merged metadata of a concurrent change as a new row that
+    /// follows all of the concurrent rows. The row is written even if the
+    /// merge equals the row that already wins so the conflict is gone.
+    pub(crate) fn resolve_concurrent_change(
+        &self,
+        change: &ConcurrentChange,
+        merged: Metadata,
+    ) -> Result<(), Error> {
+        let winner = change.winner();
+
+        // The merge follows all of the concurrent rows.
+        let project = merged.project.clone();
+        let metadata = Metadata {
+            uuid: change.uuid,
+            last_change: winner.last_change,
+            revision: change.revision,
+            ..merged
+        }
+        .changed()
+        .project(project)
+        .build()?;
+
+        // The path of the entry file depends on the metadata so the text is
+        // written again in case it has to move.
+        let entry = Entry {
+            text: self.get_entry_by_uuid(&change.uuid)?.text,
+            metadata: metadata.clone(),
+        };
+        self.write_entry_text(&entry)
+            .context("can not write entry text to file")?;
+
+        self.append_metadata(&metadata)?;
+        self.commit(&format!(
+            "merged concurrent changes of entry with id {}",
+            change.uuid
+        ))?;
+
+        self.conflicts.resolve(change)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{
+        index::{
+            Index,
+            IndexRotation,
+        },
+        tests::{
+            entry,
+            temporary,
+        },
+    };
+
+    fn row(identifier: &str, metadata: &Metadata) -> (String, Metadata) {
+        (identifier.to_string(), metadata.clone())
+    }
+
+    #[test]
+    fn detects_every_conflict_field() {
+        let base = Metadata::default();
+
+        let mut other = base.clone();
+        other.project = "other".to_string();
+        other.tags.insert("urgent".to_string());
+        other.deleted = Some(base.last_change);
+        other.moved_to = Some("work".to_string());
+        other.created = None;
+
+        let changes = detect(
+            &[row("laptop", &base), row("desktop", &other)],
+            Duration::hours(1),
+        );
+
+        assert_eq!(changes.len(), 1);
+        assert_eq!(
+            changes[0].fields,
+            vec![
+                ConflictField::Project,
+                ConflictField::Tags,
+                ConflictField::Deleted,
+                ConflictField::MovedTo,
+                ConflictField::Created,
+            ]
+        );
+    }
+
+    #[test]
+    fn same_machine_does_not_conflict() {
+        let base = Metadata::default();
+
+        let mut other = base.clone();
+        other.project = "other".to_string();
+
+        let changes = detect(
+            &[row("laptop", &base), row("laptop", &other)],
+            Duration::hours(1),
+        );
+
+        assert!(changes.is_empty());
+    }
+
+    #[test]
+    fn records_every_conflict_once() {
+        let directory = tempfile::tempdir().unwrap();
+        let log = ConflictLog::new(
+            directory.path().join(CONFLICTS_FILE_NAME),
+            1,
+            Changes::default(),
+        );
+
+        let base = Metadata::default();
+        let mut other = base.clone();
+        other.due = Some(base.last_change.naive_utc().date());
+        other.tags.insert("urgent".to_string());
+
+        let changes = detect(
+            &[row("laptop", &base), row("desktop", &other)],
+            log.window(),
+        );
+        log.report(&changes, false);
+        log.report(&changes, false);
+
+        let records = log.load().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].uuid, base.uuid);
+        assert_eq!(records[0].fields, vec!["due", "tags"]);
+        assert!(records[0].resolved.is_none());
+
+        log.resolve(&changes[0]).unwrap();
+
+        let records = log.load().unwrap();
+        assert_eq!(records.len(), 1);
+        assert!(records[0].resolved.is_some());
+    }
+
+    #[test]
+    fn missing_conflicts_file_has_no_records() {
+        let directory = tempfile::tempdir().unwrap();
+        let log = ConflictLog::new(
+            directory.path().join(CONFLICTS_FILE_NAME),
+            1,
+            Changes::default(),
+        );
+
+        assert!(log.load().unwrap().is_empty());
+    }
+
+    #[test]
+    fn index_changed_only_for_new_fingerprints() {
+        let log = ConflictLog::new(PathBuf::from(CONFLICTS_FILE_NAME), 1, Changes::default());
+
+        let first = vec![(PathBuf::from("laptop.csv"), Some((1, None)))];
+        let second = vec![(PathBuf::from("laptop.csv"), Some((2, None)))];
+
+        assert!(log.index_changed(&first));
+        assert!(!log.index_changed(&first));
+        assert!(log.index_changed(&second));
+    }
+
+    #[test]
+    fn take_field_copies_every_field() {
+        let target = Metadata::default();
+
+        let mut source = target.clone();
+        source.project = "other".to_string();
+        source.tags.insert("urgent".to_string());
+        source.deleted = Some(source.last_change);
+        source.moved_to = Some("work".to_string());
+        source.created = None;
+
+        let mut merged = target.clone();
+        for field in CONFLICT_FIELDS {
+            take_field(&mut merged, &source, *field);
+        }
+
+        for field in CONFLICT_FIELDS {
+            assert_eq!(
+                field_value(&merged, *field),
+                field_value(&source, *field),
+                "{} was not copied",
+                field
+            );
+        }
+    }
+
+    #[test]
+    fn rows_outside_the_window_do_not_conflict() {
+        let base = Metadata::default();
+
+        let mut other = base.clone();
+        other.project = "other".to_string();
+        other.last_change = base.last_change + Duration::hours(2);
+
+        let rows = [row("laptop", &base), row("desktop", &other)];
+
+        assert!(detect(&rows, Duration::hours(1)).is_empty());
+        assert_eq!(detect(&rows, Duration::hours(3)).len(), 1);
+        assert!(detect(&rows, Duration::zero()).is_empty());
+    }
+
+    #[test]
+    fn resolving_writes_the_merged_row() {
+        let (_directory, store) = temporary();
+        let added = entry("text");
+        store.add_entry(added.clone()).unwrap();
+        let base = store
+            .get_entry_by_uuid(&added.metadata.uuid)
+            .unwrap()
+            .metadata;
+
+        let mut moved = base.clone();
+        moved.revision = 1;
+        moved.project = "work".to_string();
+        moved.last_change = base.last_change + Duration::minutes(1);
+        store.index.metadata_add(&moved).unwrap();
+
+        let mut due = base.clone();
+        due.revision = 1;
+        due.due = Some(base.last_change.naive_utc().date());
+        due.last_change = base.last_change + Duration::minutes(2);
+        let desktop = Index::new(
+            Store::index_folder(store.datadir()),
+            "desktop".to_string(),
+            IndexRotation::Month,
+            store.conflicts.clone(),
+            Changes::default(),
+            false,
+        )
+        .unwrap();
+        desktop.metadata_add(&due).unwrap();
+
+        let changes = store.concurrent_changes().unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(
+            changes[0].fields,
+            vec![ConflictField::Project, ConflictField::Due]
+        );
+
+        // Keep the due date of the winner and the project of the other row.
+        let mut merged = changes[0].winner().clone();
+        take_field(&mut merged, &changes[0].rows[1].1, ConflictField::Project);
+        store
+            .resolve_concurrent_change(&changes[0], merged)
+            .unwrap();
+
+        let resolved = store.get_entry_by_uuid(&added.metadata.uuid).unwrap();
+        assert_eq!(resolved.text, "text");
+        assert_eq!(resolved.metadata.project, "work");
+        assert_eq!(resolved.metadata.due, due.due);
+        assert_eq!(resolved.metadata.revision, 2);
+
+        assert!(store.concurrent_changes().unwrap().is_empty());
+
+        let records = store.conflict_records().unwrap();
+        assert_eq!(records.len(), 1);
+        assert!(records[0].resolved.is_some());
+    }
+}
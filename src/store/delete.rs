@@ -0,0 +1,202 @@
+use crate::{
+    entry::Entry,
+    store::Store,
+};
+use anyhow::Error;
+use std::{
+    fmt,
+    path::PathBuf,
+};
+use uuid::Uuid;
+
+/// What deleting an entry did or would do in a dry run.
+#[derive(Debug)]
+pub(crate) struct DeleteReport {
+    pub(crate) dry_run: bool,
+
+    /// Uuid of the deleted entry.
+    pub(crate) uuid: Uuid,
+
+    /// Backup of the datadir that was written before deleting.
+    pub(crate) backup: Option<PathBuf>,
+}
+
+impl fmt::Display for DeleteReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(backup) = &self.backup {
+            if self.dry_run {
+                writeln!(f, "Backup (destructive operation): {:?}", backup)?;
+            } else {
+                writeln!(f, "Backup written to: {:?}", backup)?;
+            }
+        }
+
+        if self.dry_run {
+            writeln!(f, "dry run: would delete entry {}", self.uuid)
+        } else {
+            writeln!(f, "deleted entry {}", self.uuid)
+        }
+    }
+}
+
+impl Store {
+    /// Delete the entry for good. The text is removed and a row records the
+    /// deletion so the entry is not shown anymore on any machine. Cleanups
+    /// keep that row so a sync can not bring the entry back.
+    pub(crate) fn delete_entry(&self, entry: &Entry, backup: bool) -> Result<DeleteReport, Error> {
+        let metadata = entry.metadata.changed().delete().build()?;
+
+        let backup = if backup {
+            self.backup_before("delete")?
+        } else {
+            None
+        };
+
+        let report = DeleteReport {
+            dry_run: self.changes.is_dry_run(),
+            uuid: metadata.uuid,
+            backup,
+        };
+
+        self.append_metadata(&metadata)?;
+        self.remove_entry_file(&metadata.uuid)?;
+
+        self.commit(&format!("deleted entry with id {}", metadata.uuid))?;
+
+        let deleted = Entry {
+            metadata,
+            text: entry.text.clone(),
+        };
+        self.notify_changed(Some(&entry.metadata), &deleted);
+
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::store::{
+        changes::Changes,
+        index::{
+            Index,
+            IndexRotation,
+        },
+        tests::{
+            entry,
+            open,
+            temporary,
+        },
+        Store,
+    };
+    use std::{
+        collections::BTreeSet,
+        fs,
+    };
+    use uuid::Uuid;
+
+    #[test]
+    fn deleted_entry_is_gone() {
+        let (_directory, store) = temporary();
+        let added = entry("text");
+        store.add_entry(added.clone()).unwrap();
+        let uuid = added.metadata.uuid;
+        let entry_file = store.find_entry_file(&uuid).unwrap();
+
+        let report = store.delete_entry(&added, true).unwrap();
+
+        assert_eq!(report.uuid, uuid);
+        assert!(!report.dry_run);
+        assert!(report.backup.is_none());
+        assert!(!entry_file.exists());
+        assert!(!store.has_entry(&uuid).unwrap());
+        assert!(store.find_entry_by_uuid(&uuid).unwrap().is_none());
+        assert!(store.deleted_at(&uuid).unwrap().is_some());
+    }
+
+    #[test]
+    fn unknown_entry_was_not_deleted() {
+        let (_directory, store) = temporary();
+        let added = entry("text");
+        store.add_entry(added.clone()).unwrap();
+
+        assert!(store.deleted_at(&added.metadata.uuid).unwrap().is_none());
+        assert!(store.deleted_at(&Uuid::new_v4()).unwrap().is_none());
+    }
+
+    #[test]
+    fn dry_run_does_not_delete() {
+        let directory = tempfile::tempdir().unwrap();
+        let datadir = directory.path().join("datadir");
+        let state_home = directory.path().join("state");
+        fs::create_dir_all(&datadir).unwrap();
+
+        let store = open(&datadir, &state_home, &Changes::default());
+        let added = entry("text");
+        store.add_entry(added.clone()).unwrap();
+
+        let changes = Changes::dry_run();
+        let report = open(&datadir, &state_home, &changes)
+            .delete_entry(&added, false)
+            .unwrap();
+
+        assert!(report.dry_run);
+        assert_eq!(
+            report.to_string(),
+            format!("dry run: would delete entry {}\n", added.metadata.uuid)
+        );
+        assert!(store.has_entry(&added.metadata.uuid).unwrap());
+        assert!(store.find_entry_file(&added.metadata.uuid).is_some());
+        assert!(!changes.take_plan().is_empty());
+    }
+
+    #[test]
+    fn compaction_keeps_the_tombstone() {
+        let (_directory, store) = temporary();
+        let added = entry("text");
+        store.add_entry(added.clone()).unwrap();
+        let uuid = added.metadata.uuid;
+        let row = store.get_entry_by_uuid(&uuid).unwrap().metadata;
+
+        store.delete_entry(&added, false).unwrap();
+        let report = store
+            .index
+            .compact(|_| false, &BTreeSet::new(), None)
+            .unwrap();
+        assert_eq!(report.deleted_uuids, vec![uuid]);
+        assert_eq!(report.rows_after, 1);
+
+        // Another machine that did not pull the deletion yet still has the
+        // row from before.
+        let desktop = Index::new(
+            Store::index_folder(store.datadir()),
+            "desktop".to_string(),
+            IndexRotation::Month,
+            store.conflicts.clone(),
+            Changes::default(),
+            false,
+        )
+        .unwrap();
+        desktop.metadata_add(&row).unwrap();
+
+        assert!(!store.has_entry(&uuid).unwrap());
+        assert!(store.deleted_at(&uuid).unwrap().is_some());
+    }
+
+    #[test]
+    fn cleanup_removes_text_of_deleted_entry() {
+        let (_directory, store) = temporary();
+        let added = entry("text");
+        store.add_entry(added.clone()).unwrap();
+        store.delete_entry(&added, false).unwrap();
+
+        // The text comes back with a sync from a machine that still has it.
+        let entry_file = store.get_entry_filename_for_uuid(&added.metadata.uuid);
+        fs::create_dir_all(entry_file.parent().unwrap()).unwrap();
+        fs::write(&entry_file, "text").unwrap();
+
+        store.run_cleanup(None, false).unwrap();
+
+        assert!(!entry_file.exists());
+        assert!(store.deleted_at(&added.metadata.uuid).unwrap().is_some());
+    }
+}
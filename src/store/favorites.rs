@@ -0,0 +1,275 @@
+use crate::{
+    entry::ProjectCount,
+    store::{
+        state::StateStore,
+        Store,
+    },
+};
+use anyhow::{
+    Context,
+    Error,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::sync::{
+    Arc,
+    Mutex,
+};
+
+/// Name of the state that records the favorites changed on this machine.
+const FAVORITES_STATE_NAME: &str = "favorite_projects";
+
+/// Changes of this machine to the favorite projects from the config.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub(crate) struct FavoriteOverrides {
+    /// Projects that are favorites on this machine in the order they were
+    /// added.
+    #[serde(default)]
+    added: Vec<String>,
+
+    /// Favorites from the config that are not favorites on this machine.
+    #[serde(default)]
+    removed: Vec<String>,
+}
+
+impl FavoriteOverrides {
+    /// Favorites from the config without the removed ones followed by the
+    /// added ones.
+    pub(super) fn merge(&self, configured: &[String]) -> Vec<String> {
+        let mut favorites = Vec::new();
+
+        for project in configured.iter().chain(&self.added) {
+            if !self.removed.contains(project) && !favorites.contains(project) {
+                favorites.push(project.clone());
+            }
+        }
+
+        favorites
+    }
+}
+
+/// Records which projects are favorites on this machine on top of the ones
+/// from the config.
+#[derive(Debug, Clone)]
+pub(crate) struct Favorites {
+    state: StateStore,
+
+    /// Serializes the read-modify-write cycles of concurrent commands.
+    lock: Arc<Mutex<()>>,
+}
+
+impl Favorites {
+    pub(super) fn new(state: StateStore) -> Self {
+        Self {
+            state,
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    pub(super) fn load(&self) -> FavoriteOverrides {
+        self.state.load(FAVORITES_STATE_NAME)
+    }
+
+    /// Make the project a favorite on this machine. Returns if it was not a
+    /// favorite before.
+    pub(super) fn add(&self, project: &str, configured: &[String]) -> Result<bool, Error> {
+        let _guard = self.lock.lock().unwrap_or_else(|err| err.into_inner());
+
+        let mut overrides = self.load();
+        if overrides
+            .merge(configured)
+            .iter()
+            .any(|name| name == project)
+        {
+            return Ok(false);
+        }
+
+        overrides.removed.retain(|name| name != project);
+        if !configured.iter().any(|name| name == project) {
+            overrides.added.push(project.to_string());
+        }
+
+        self.state.save(FAVORITES_STATE_NAME, &overrides)?;
+
+        Ok(true)
+    }
+
+    /// Remove the project from the favorites on this machine. Returns if it
+    /// was a favorite before.
+    pub(super) fn remove(&self, project: &str, configured: &[String]) -> Result<bool, Error> {
+        let _guard = self.lock.lock().unwrap_or_else(|err| err.into_inner());
+
+        let mut overrides = self.load();
+        if !overrides
+            .merge(configured)
+            .iter()
+            .any(|name| name == project)
+        {
+            return Ok(false);
+        }
+
+        overrides.added.retain(|name| name != project);
+        if configured.iter().any(|name| name == project) {
+            overrides.removed.push(project.to_string());
+        }
+
+        self.state.save(FAVORITES_STATE_NAME, &overrides)?;
+
+        Ok(true)
+    }
+}
+
+/// Take the counts of the favorite projects out of `counts` in the order of
+/// `favorites`. Favorites without entries get an empty count as they are
+/// pinned explicitly. The other counts are left in `counts`.
+pub(crate) fn take_favorites(
+    counts: &mut Vec<ProjectCount>,
+    favorites: &[String],
+) -> Vec<ProjectCount> {
+    favorites
+        .iter()
+        .map(
+            |favorite| match counts.iter().position(|count| &count.project == favorite) {
+                Some(index) => counts.remove(index),
+                None => ProjectCount {
+                    project: favorite.clone(),
+                    ..ProjectCount::default()
+                },
+            },
+        )
+        .collect()
+}
+
+impl Store {
+    /// Favorite projects from the config with the changes of this machine.
+    /// Ignored favorites are left out unless `include_ignored` is set.
+    pub(crate) fn favorite_projects(&self, include_ignored: bool) -> Vec<String> {
+        self.favorites
+            .load()
+            .merge(&self.config.favorite_projects)
+            .into_iter()
+            .filter(|project| include_ignored || !self.is_ignored_project(project))
+            .collect()
+    }
+
+    /// Make the project a favorite on this machine. Returns if it was not a
+    /// favorite before.
+    pub(crate) fn favorite_project(&self, project: &str) -> Result<bool, Error> {
+        self.favorites
+            .add(project, &self.config.favorite_projects)
+            .context("can not save favorite projects")
+    }
+
+    /// Remove the project from the favorites on this machine. Returns if it
+    /// was a favorite before.
+    pub(crate) fn unfavorite_project(&self, project: &str) -> Result<bool, Error> {
+        self.favorites
+            .remove(project, &self.config.favorite_projects)
+            .context("can not save favorite projects")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        config::Config,
+        store::tests::open_with,
+    };
+
+    fn names(projects: &[&str]) -> Vec<String> {
+        projects.iter().map(|project| project.to_string()).collect()
+    }
+
+    fn overrides(added: &[&str], removed: &[&str]) -> FavoriteOverrides {
+        FavoriteOverrides {
+            added: names(added),
+            removed: names(removed),
+        }
+    }
+
+    #[test]
+    fn merge_keeps_the_configured_order_first() {
+        let configured = names(&["work", "home", "garden"]);
+
+        assert_eq!(
+            overrides(&[], &[]).merge(&configured),
+            names(&["work", "home", "garden"])
+        );
+        assert_eq!(
+            overrides(&["music", "home"], &["work"]).merge(&configured),
+            names(&["home", "garden", "music"])
+        );
+        assert_eq!(overrides(&["music"], &[]).merge(&[]), names(&["music"]));
+    }
+
+    #[test]
+    fn favorites_are_taken_out_in_their_order() {
+        let count = |project: &str, active_count| ProjectCount {
+            project: project.to_string(),
+            active_count,
+            ..ProjectCount::default()
+        };
+        let mut counts = vec![
+            count("a", 1),
+            count("home", 2),
+            count("b", 3),
+            count("work", 4),
+        ];
+
+        let favorites = take_favorites(&mut counts, &names(&["work", "pinned", "home"]));
+
+        let summary = |counts: &[ProjectCount]| {
+            counts
+                .iter()
+                .map(|count| (count.project.clone(), count.active_count))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            summary(&favorites),
+            vec![
+                ("work".to_string(), 4),
+                ("pinned".to_string(), 0),
+                ("home".to_string(), 2),
+            ]
+        );
+        assert_eq!(
+            summary(&counts),
+            vec![("a".to_string(), 1), ("b".to_string(), 3)]
+        );
+    }
+
+    #[test]
+    fn favorites_round_trip_through_the_state() {
+        let directory = tempfile::tempdir().unwrap();
+        let datadir = directory.path().join("datadir");
+        let state_home = directory.path().join("state");
+        std::fs::create_dir_all(&datadir).unwrap();
+        let config = Config {
+            favorite_projects: names(&["work", "home"]),
+            ..Config::default()
+        };
+
+        let store = open_with(&datadir, &state_home, config.clone());
+        assert!(store.favorite_project("music").unwrap());
+        assert!(!store.favorite_project("music").unwrap());
+        assert!(!store.favorite_project("work").unwrap());
+        assert!(store.unfavorite_project("work").unwrap());
+        assert!(!store.unfavorite_project("work").unwrap());
+        assert!(!store.unfavorite_project("unknown").unwrap());
+
+        let store = open_with(&datadir, &state_home, config.clone());
+        assert_eq!(store.favorite_projects(true), names(&["home", "music"]));
+
+        // A configured favorite that was removed can be added back.
+        assert!(store.favorite_project("work").unwrap());
+        assert!(store.unfavorite_project("music").unwrap());
+
+        let store = open_with(&datadir, &state_home, config);
+        assert_eq!(store.favorite_projects(true), names(&["work", "home"]));
+        assert!(store.favorites.load().added.is_empty());
+        assert!(store.favorites.load().removed.is_empty());
+    }
+}
@@ -0,0 +1,382 @@
+use crate::{
+    entry::{
+        Entry,
+        Metadata,
+    },
+    helper,
+    store::Store,
+};
+use anyhow::{
+    format_err,
+    Context,
+    Error,
+};
+use log::{
+    debug,
+    warn,
+};
+use std::{
+    fs,
+    io,
+    io::{
+        Read,
+        Write,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+use uuid::Uuid;
+
+/// Parse the uuid from the stem of an entry file name.
+fn uuid_from_path(path: &Path) -> Result<Uuid, Error> {
+    let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(|| format_err!("entry file {:?} has no valid file name", path))?;
+
+    helper::parse_uuid(stem).with_context(|| format!("can not parse uuid from {:?}", path))
+}
+
+/// Text of the entry file cut off after `limit` bytes with a note how large
+/// the whole text is. Only the first `limit` bytes are read so large entries
+/// do not slow down views that show many entries.
+fn read_text_preview(path: &Path, limit: usize) -> io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let size = file.metadata()?.len();
+
+    if size <= limit as u64 {
+        let mut text = String::new();
+        file.read_to_string(&mut text)?;
+
+        return Ok(text);
+    }
+
+    let mut bytes = Vec::with_capacity(limit);
+    file.take(limit as u64).read_to_end(&mut bytes)?;
+
+    // The limit can end in the middle of a character which is dropped.
+    let mut text = match String::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(err) if err.utf8_error().error_len().is_none() => {
+            let valid = err.utf8_error().valid_up_to();
+            let mut bytes = err.into_bytes();
+            bytes.truncate(valid);
+
+            String::from_utf8(bytes).expect("bytes up to the first error are valid utf-8")
+        }
+        Err(err) => return Err(io::Error::new(io::ErrorKind::InvalidData, err)),
+    };
+
+    text.push_str(&format!(
+        "\n\n[text cut off after {} of {} bytes, open the entry to see all of it]\n",
+        text.len(),
+        size
+    ));
+
+    Ok(text)
+}
+
+impl Store {
+    fn get_entry_foldername(&self, entry: &Metadata) -> PathBuf {
+        self.get_entry_foldername_for_uuid(&entry.uuid)
+    }
+
+    pub(super) fn get_entry_foldername_for_uuid(&self, uuid: &Uuid) -> PathBuf {
+        let uuid = uuid.to_string();
+        debug!("uuid: {}", uuid);
+
+        // Gets the first two characters of the uuid. This should never fail so the
+        // unwrap is safe.
+        let uuid_prefix = &uuid[..uuid.char_indices().nth(2).unwrap().0];
+        debug!("uuid_prefix: {}", uuid_prefix);
+
+        // {{ datadir }}/entries/{{ uuid_prefix }}
+        let mut folder = PathBuf::new();
+        folder.push(&self.datadir);
+        folder.push("entries");
+        folder.push(uuid_prefix);
+
+        debug!("folder: {:?}", folder);
+
+        folder
+    }
+
+    fn get_entry_filename(&self, entry: &Metadata) -> PathBuf {
+        self.get_entry_filename_for_uuid(&entry.uuid)
+    }
+
+    pub(super) fn get_entry_filename_for_uuid(&self, uuid: &Uuid) -> PathBuf {
+        let entry_folder = self.get_entry_foldername_for_uuid(uuid);
+
+        let mut entry_file = PathBuf::new();
+        entry_file.push(entry_folder);
+        entry_file.push(format!("{}.adoc", uuid));
+
+        entry_file
+    }
+
+    /// Find the file of the entry. Falls back to files that were saved with an
+    /// uppercase or otherwise non canonical uuid by external tools when the
+    /// file at the canonical path does not exist.
+    pub(super) fn find_entry_file(&self, uuid: &Uuid) -> Option<PathBuf> {
+        let entry_file = self.get_entry_filename_for_uuid(uuid);
+        if entry_file.exists() {
+            return Some(entry_file);
+        }
+
+        let entry_folder = self.get_entry_foldername_for_uuid(uuid);
+        let uppercase_folder =
+            entry_folder.with_file_name(entry_folder.file_name()?.to_string_lossy().to_uppercase());
+
+        vec![entry_folder, uppercase_folder]
+            .into_iter()
+            .filter_map(|folder| fs::read_dir(folder).ok())
+            .flatten()
+            .flatten()
+            .map(|dir_entry| dir_entry.path())
+            .filter(|path| {
+                path.extension()
+                    .is_some_and(|extension| extension == "adoc")
+            })
+            .find(|path| uuid_from_path(path).is_ok_and(|found| found == *uuid))
+    }
+
+    pub(super) fn write_entry_text(&self, entry: &Entry) -> Result<(), Error> {
+        self.changes.apply(
+            || {
+                format!(
+                    "write entry text to {:?}: {:.60}",
+                    self.get_entry_filename(&entry.metadata),
+                    entry
+                )
+            },
+            || self.write_entry_file(entry),
+        )?;
+
+        Ok(())
+    }
+
+    fn write_entry_file(&self, entry: &Entry) -> Result<(), Error> {
+        let entry_folder = self.get_entry_foldername(&entry.metadata);
+        fs::create_dir_all(&entry_folder).context("can not create entry folder")?;
+
+        let entry_file = self.get_entry_filename(&entry.metadata);
+
+        // The text is always written to the canonical path so a file with a
+        // non canonical name has to be removed afterwards to not leave a stale
+        // copy behind.
+        let stray_file = self
+            .find_entry_file(&entry.metadata.uuid)
+            .filter(|path| path != &entry_file);
+
+        let mut file = fs::File::create(entry_file).context("can not create entry file")?;
+        file.write(entry.text.as_bytes())
+            .context("can not write entry text to file")?;
+
+        if let Some(stray_file) = stray_file {
+            fs::remove_file(&stray_file)
+                .with_context(|| format!("can not remove old entry file {:?}", stray_file))?;
+        }
+
+        Ok(())
+    }
+
+    /// Append the metadata to the index of this machine.
+    pub(super) fn append_metadata(&self, metadata: &Metadata) -> Result<(), Error> {
+        Ok(self.index.metadata_add(metadata)?)
+    }
+
+    /// Remove the text of the entry if it has a file.
+    pub(super) fn remove_entry_file(&self, uuid: &Uuid) -> Result<(), Error> {
+        let entry_file = match self.find_entry_file(uuid) {
+            Some(entry_file) => entry_file,
+            None => return Ok(()),
+        };
+
+        self.changes.apply(
+            || format!("remove entry file {:?}", entry_file),
+            || {
+                fs::remove_file(&entry_file)
+                    .with_context(|| format!("can not remove entry file {:?}", entry_file))
+            },
+        )?;
+
+        Ok(())
+    }
+
+    pub(super) fn get_entry_for_metadata(&self, metadata: Metadata) -> Result<Entry, Error> {
+        self.read_entry(metadata, None)
+    }
+
+    /// Read the entry. With `preview` only that many bytes of the text are
+    /// read and longer texts are cut off.
+    pub(super) fn read_entry(
+        &self,
+        metadata: Metadata,
+        preview: Option<usize>,
+    ) -> Result<Entry, Error> {
+        let entry_file = self
+            .find_entry_file(&metadata.uuid)
+            .ok_or_else(|| format_err!("can not find entry file for uuid {}", metadata.uuid))?;
+
+        let text = match preview {
+            Some(limit) => read_text_preview(&entry_file, limit),
+            None => fs::read_to_string(entry_file),
+        }
+        .context("can not read entry file text")?;
+
+        Ok(Entry { metadata, text })
+    }
+
+    /// All entry files in the entries folder and its subfolders.
+    fn entry_files(&self) -> Result<Vec<PathBuf>, Error> {
+        let entries_folder = self.datadir.join("entries");
+
+        helper::files_with_extension(&entries_folder, "adoc")
+            .with_context(|| format!("can not list entry files in {:?}", entries_folder))
+    }
+
+    /// Entry files with the uuid their name is parsed as. Files whose name is
+    /// not a uuid are skipped with a warning so a stray file does not stop a
+    /// cleanup.
+    pub(super) fn entry_files_with_uuid(&self) -> Result<Vec<(PathBuf, Uuid)>, Error> {
+        Ok(self
+            .entry_files()?
+            .into_iter()
+            .filter_map(|path| match uuid_from_path(&path) {
+                Ok(uuid) => Some((path, uuid)),
+                Err(err) => {
+                    warn!("skipping entry file: {:#}", err);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    /// Path of the file that holds the text of the entry.
+    pub(crate) fn entry_file(&self, uuid: &Uuid) -> Option<PathBuf> {
+        self.find_entry_file(uuid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::read_text_preview;
+    use crate::{
+        config::Config,
+        store::tests::{
+            entry,
+            temporary,
+            temporary_with,
+        },
+    };
+    use std::fs;
+
+    #[test]
+    fn short_text_is_read_whole() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("entry.adoc");
+        fs::write(&path, "short text\n").unwrap();
+
+        assert_eq!(read_text_preview(&path, 11).unwrap(), "short text\n");
+        assert_eq!(read_text_preview(&path, 64).unwrap(), "short text\n");
+    }
+
+    #[test]
+    fn large_text_is_cut_off_with_a_note() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("entry.adoc");
+        let text = "line of a large entry\n".repeat(200_000);
+        fs::write(&path, &text).unwrap();
+
+        let preview = read_text_preview(&path, 64 * 1024).unwrap();
+
+        assert!(preview.starts_with(&text[..64 * 1024]));
+        assert!(preview.ends_with(&format!(
+            "\n\n[text cut off after 65536 of {} bytes, open the entry to see all of it]\n",
+            text.len()
+        )));
+        assert!(preview.len() < 64 * 1024 + 100);
+    }
+
+    #[test]
+    fn cut_in_the_middle_of_a_character_drops_the_character() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("entry.adoc");
+        fs::write(&path, "ab\u{e4}cd").unwrap();
+
+        let preview = read_text_preview(&path, 3).unwrap();
+
+        assert_eq!(
+            preview,
+            "ab\n\n[text cut off after 2 of 6 bytes, open the entry to see all of it]\n"
+        );
+    }
+
+    #[test]
+    fn previews_are_cut_off_but_the_entry_is_whole() {
+        let (_directory, store) = temporary_with(Config {
+            entry_preview_bytes: 1024,
+            ..Config::default()
+        });
+        let text = "x".repeat(3 * 1024 * 1024);
+        let large = entry(&text);
+        let uuid = large.metadata.uuid;
+        store.add_entry(large).unwrap();
+        store.add_entry(entry("small")).unwrap();
+
+        let previews: Vec<_> = store
+            .get_entry_previews_of_projects(&["default".to_string()])
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.text)
+            .collect();
+
+        assert_eq!(previews.len(), 2);
+        assert!(previews.contains(&"small".to_string()));
+        let preview = previews
+            .iter()
+            .find(|text| text.as_str() != "small")
+            .unwrap();
+        assert!(preview.starts_with(&"x".repeat(1024)));
+        assert!(preview.contains(&format!("[text cut off after 1024 of {} bytes", text.len())));
+
+        assert_eq!(store.get_entry_by_uuid(&uuid).unwrap().text, text);
+    }
+
+    #[test]
+    fn uppercase_entry_file_is_found_and_replaced() {
+        let (_directory, store) = temporary();
+        let added = entry("text");
+        let uuid = added.metadata.uuid;
+        store.add_entry(added).unwrap();
+
+        let canonical = store.get_entry_filename_for_uuid(&uuid);
+        let uppercase =
+            canonical.with_file_name(format!("{}.adoc", uuid.to_string().to_uppercase()));
+        fs::rename(&canonical, &uppercase).unwrap();
+
+        assert_eq!(store.find_entry_file(&uuid), Some(uppercase.clone()));
+
+        let mut found = store.get_entry_by_uuid(&uuid).unwrap();
+        assert_eq!(found.text, "text");
+
+        found.text = "changed".to_string();
+        store.update_entry(found).unwrap();
+
+        assert_eq!(fs::read_to_string(&canonical).unwrap(), "changed");
+        assert!(!uppercase.exists());
+    }
+
+    #[test]
+    fn files_of_other_uuids_are_not_found() {
+        let (_directory, store) = temporary();
+        let added = entry("text");
+        let other = entry("other").metadata.uuid;
+        store.add_entry(added).unwrap();
+
+        assert_eq!(store.find_entry_file(&other), None);
+    }
+}
@@ -0,0 +1,321 @@
+use crate::{
+    entry::Entry,
+    store::{
+        state::{
+            self,
+            StateStore,
+        },
+        Store,
+    },
+};
+use anyhow::{
+    bail,
+    Context,
+    Error,
+};
+use log::{
+    debug,
+    info,
+    warn,
+};
+use serde::Deserialize;
+use std::{
+    collections::{
+        BTreeMap,
+        BTreeSet,
+    },
+    path::Path,
+    sync::{
+        Arc,
+        Mutex,
+    },
+};
+use uuid::Uuid;
+
+/// Name of the state that records the focused entry of each project.
+const FOCUS_STATE_NAME: &str = "focus";
+
+/// File in the datadir in which older versions recorded the focus.
+const LEGACY_FOCUS_FILE_NAME: &str = ".focus.csv";
+
+/// Records the single entry of each project that is worked on right now.
+#[derive(Debug, Clone)]
+pub(crate) struct Focus {
+    state: StateStore,
+
+    /// Serializes the read-modify-write cycles of concurrent web requests.
+    lock: Arc<Mutex<()>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LegacyFocusRecord {
+    project: String,
+    uuid: Uuid,
+}
+
+impl Focus {
+    pub(super) fn new(state: StateStore) -> Self {
+        Self {
+            state,
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Move the focus that older versions recorded in the datadir to the
+    /// state store. The focus in the state store wins if both have one for
+    /// the same project.
+    pub(super) fn migrate(&self, datadir: &Path) -> Result<(), Error> {
+        let _guard = self.lock.lock().unwrap_or_else(|err| err.into_inner());
+
+        let records =
+            match state::read_legacy_csv::<LegacyFocusRecord>(datadir, LEGACY_FOCUS_FILE_NAME)? {
+                Some(records) => records,
+                None => return Ok(()),
+            };
+
+        let mut focus = self.load();
+        for record in records {
+            focus.entry(record.project).or_insert(record.uuid);
+        }
+
+        self.state.save(FOCUS_STATE_NAME, &focus)?;
+        self.state
+            .remove_legacy_file(datadir, LEGACY_FOCUS_FILE_NAME)?;
+
+        info!("moved focus from the datadir to the state directory");
+
+        Ok(())
+    }
+
+    /// Load the focused entries by project.
+    pub(crate) fn load(&self) -> BTreeMap<String, Uuid> {
+        self.state.load(FOCUS_STATE_NAME)
+    }
+
+    /// Focus the entry in the project. Replaces the previous focus.
+    pub(super) fn set(&self, project: &str, uuid: Uuid) -> Result<(), Error> {
+        let _guard = self.lock.lock().unwrap_or_else(|err| err.into_inner());
+
+        let mut focus = self.load();
+        focus.insert(project.to_string(), uuid);
+
+        self.state.save(FOCUS_STATE_NAME, &focus)
+    }
+
+    /// Remove the focus of the project. When `uuid` is given the focus is
+    /// only removed if it is on that entry. Returns if a focus was removed.
+    pub(super) fn clear(&self, project: &str, uuid: Option<Uuid>) -> Result<bool, Error> {
+        let _guard = self.lock.lock().unwrap_or_else(|err| err.into_inner());
+
+        let mut focus = self.load();
+
+        let matches = match (focus.get(project), uuid) {
+            (None, _) => false,
+            (Some(_), None) => true,
+            (Some(focused), Some(uuid)) => *focused == uuid,
+        };
+
+        if matches {
+            focus.remove(project);
+            self.state.save(FOCUS_STATE_NAME, &focus)?;
+        }
+
+        Ok(matches)
+    }
+
+    /// Remove the focus on entries that are not in `active` anymore. Returns
+    /// how many records were or would be removed.
+    pub(super) fn prune(&self, active: &BTreeSet<Uuid>) -> Result<usize, Error> {
+        let _guard = self.lock.lock().unwrap_or_else(|err| err.into_inner());
+
+        let mut focus = self.load();
+        let before = focus.len();
+        focus.retain(|_, uuid| active.contains(uuid));
+
+        let pruned = before - focus.len();
+        if pruned > 0 {
+            self.state.save(FOCUS_STATE_NAME, &focus)?;
+        }
+
+        Ok(pruned)
+    }
+}
+
+impl Store {
+    /// Focus the entry in its project. Only active entries can be focused.
+    pub(crate) fn set_focus(&self, entry: &Entry) -> Result<(), Error> {
+        if entry.is_done() {
+            bail!("entry {} is already done", entry.metadata.uuid)
+        }
+
+        self.focus
+            .set(&entry.metadata.project, entry.metadata.uuid)
+            .context("can not save focus")
+    }
+
+    /// Remove the focus of the project. Returns if the project had a focus.
+    pub(crate) fn clear_focus(&self, project: &str) -> Result<bool, Error> {
+        self.focus
+            .clear(project, None)
+            .context("can not clear focus")
+    }
+
+    /// Uuid of the focused entry of the project without checking that the
+    /// entry still exists.
+    pub(crate) fn focused_uuid(&self, project: &str) -> Option<Uuid> {
+        self.focus.load().remove(project)
+    }
+
+    /// The focused entry of the project. A focus on an entry that does not
+    /// exist anymore, is done or was moved to another project is removed.
+    pub(crate) fn get_focus(&self, project: &str) -> Result<Option<Entry>, Error> {
+        let uuid = match self.focused_uuid(project) {
+            Some(uuid) => uuid,
+            None => return Ok(None),
+        };
+
+        match self.get_entry_by_uuid(&uuid) {
+            Ok(entry) if entry.is_active() && entry.metadata.project == project => Ok(Some(entry)),
+            _ => {
+                debug!("removing stale focus on {} from project {}", uuid, project);
+                self.focus
+                    .clear(project, Some(uuid))
+                    .context("can not clear stale focus")?;
+
+                Ok(None)
+            }
+        }
+    }
+
+    /// Remove the focus from the entry after it was finished. Failing to do so
+    /// is only logged as the focus is removed on the next lookup anyway.
+    pub(super) fn clear_focus_of(&self, entry: &Entry) {
+        if let Err(err) = self
+            .focus
+            .clear(&entry.metadata.project, Some(entry.metadata.uuid))
+        {
+            warn!(
+                "can not clear focus of entry {}: {:#}",
+                entry.metadata.uuid, err
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::store::tests::{
+        entry,
+        temporary,
+    };
+
+    #[test]
+    fn focus_is_set_replaced_and_cleared_per_project() {
+        let (_directory, store) = temporary();
+        let first = entry("first entry");
+        let second = entry("second entry");
+        let mut other = entry("other project");
+        other.metadata.project = "other".to_string();
+        for added in &[&first, &second, &other] {
+            store.add_entry((*added).clone()).unwrap();
+        }
+
+        assert!(store.get_focus("default").unwrap().is_none());
+
+        store.set_focus(&first).unwrap();
+        store.set_focus(&other).unwrap();
+        assert_eq!(
+            store.get_focus("default").unwrap().unwrap().text,
+            "first entry"
+        );
+
+        store.set_focus(&second).unwrap();
+        assert_eq!(
+            store.get_focus("default").unwrap().unwrap().text,
+            "second entry"
+        );
+
+        assert!(store.clear_focus("default").unwrap());
+        assert!(!store.clear_focus("default").unwrap());
+        assert!(store.get_focus("default").unwrap().is_none());
+        assert_eq!(
+            store.get_focus("other").unwrap().unwrap().text,
+            "other project"
+        );
+    }
+
+    #[test]
+    fn done_entries_can_not_be_focused() {
+        let (_directory, store) = temporary();
+        let mut done = entry("done entry");
+        done.metadata.finished = Some(chrono::Utc::now());
+        store.add_entry(done.clone()).unwrap();
+
+        assert!(store.set_focus(&done).is_err());
+        assert_eq!(store.focused_uuid("default"), None);
+    }
+
+    #[test]
+    fn finishing_the_focused_entry_clears_the_focus() {
+        let (_directory, store) = temporary();
+        let focused = entry("focused entry");
+        let other = entry("other entry");
+        store.add_entry(focused.clone()).unwrap();
+        store.add_entry(other.clone()).unwrap();
+        store.set_focus(&focused).unwrap();
+
+        // Finishing another entry keeps the focus.
+        store
+            .entry_done_by_uuid(other.metadata.uuid, false)
+            .unwrap();
+        assert_eq!(store.focused_uuid("default"), Some(focused.metadata.uuid));
+
+        store
+            .entry_done_by_uuid(focused.metadata.uuid, false)
+            .unwrap();
+        assert_eq!(store.focused_uuid("default"), None);
+    }
+
+    #[test]
+    fn stale_focus_is_removed_on_lookup() {
+        let (_directory, store) = temporary();
+
+        // The entry can be finished, deleted or moved without going through
+        // this store, for example on another machine before a sync.
+        let finished = entry("finished elsewhere");
+        let deleted = entry("deleted");
+        let moved = entry("moved to another project");
+        for (project, focused) in [
+            ("finished", &finished),
+            ("deleted", &deleted),
+            ("moved", &moved),
+        ] {
+            let mut focused = focused.clone();
+            focused.metadata.project = project.to_string();
+            store.add_entry(focused.clone()).unwrap();
+            store.set_focus(&focused).unwrap();
+        }
+
+        let mut changed = store.get_entry_by_uuid(&finished.metadata.uuid).unwrap();
+        changed.metadata = changed.metadata.changed().finish().build().unwrap();
+        store.update_entry(changed).unwrap();
+
+        let removed = store.get_entry_by_uuid(&deleted.metadata.uuid).unwrap();
+        store.delete_entry(&removed, false).unwrap();
+
+        let mut changed = store.get_entry_by_uuid(&moved.metadata.uuid).unwrap();
+        changed.metadata = changed
+            .metadata
+            .changed()
+            .project("elsewhere".to_string())
+            .build()
+            .unwrap();
+        store.update_entry(changed).unwrap();
+
+        for project in &["finished", "deleted", "moved"] {
+            assert!(store.focused_uuid(project).is_some(), "{}", project);
+            assert!(store.get_focus(project).unwrap().is_none(), "{}", project);
+            assert_eq!(store.focused_uuid(project), None, "{}", project);
+        }
+    }
+}
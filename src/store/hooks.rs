@@ -0,0 +1,474 @@
+use crate::{
+    entry::{
+        Entry,
+        Metadata,
+    },
+    helper,
+    store::webhooks::{
+        self,
+        EventKind,
+    },
+};
+use log::{
+    debug,
+    warn,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    path::{
+        Path,
+        PathBuf,
+    },
+    process::Stdio,
+    sync::mpsc::{
+        self,
+        Receiver,
+        SyncSender,
+        TrySendError,
+    },
+    thread::JoinHandle,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+use uuid::Uuid;
+
+/// Seconds a hook can run by default before it is killed.
+const DEFAULT_TIMEOUT_SECONDS: u64 = 30;
+
+/// How often a running hook is checked for having exited.
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+/// How many hooks can wait to be run before new ones get dropped.
+const QUEUE_SIZE: usize = 64;
+
+/// Shell commands that are run after an entry was changed and the change was
+/// committed. The commands get the entry in the environment variables
+/// `TODUST_EVENT`, `TODUST_UUID`, `TODUST_PROJECT`, `TODUST_TITLE` and
+/// `TODUST_DATADIR`. Moved entries also get `TODUST_PREVIOUS_PROJECT`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct HooksConfig {
+    /// Run after an entry was added.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    post_add: Option<String>,
+
+    /// Run after an entry was marked as done.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    post_done: Option<String>,
+
+    /// Run after an entry was moved to another project.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    post_move: Option<String>,
+
+    /// Run after an entry was deleted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    post_delete: Option<String>,
+
+    /// Seconds a hook can run before it is killed.
+    timeout_seconds: u64,
+}
+
+impl Default for HooksConfig {
+    fn default() -> Self {
+        Self {
+            post_add: None,
+            post_done: None,
+            post_move: None,
+            post_delete: None,
+            timeout_seconds: DEFAULT_TIMEOUT_SECONDS,
+        }
+    }
+}
+
+impl HooksConfig {
+    fn command(&self, event: EventKind) -> Option<&str> {
+        match event {
+            EventKind::Created => self.post_add.as_deref(),
+            EventKind::Finished => self.post_done.as_deref(),
+            EventKind::Moved => self.post_move.as_deref(),
+            EventKind::Deleted => self.post_delete.as_deref(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.post_add.is_none()
+            && self.post_done.is_none()
+            && self.post_move.is_none()
+            && self.post_delete.is_none()
+    }
+}
+
+/// Hook process waiting to be run by the worker thread.
+struct Run {
+    event: EventKind,
+    command: String,
+    uuid: Uuid,
+    env: Vec<(&'static str, String)>,
+}
+
+/// Runs the configured hooks from a background thread so slow hooks do not
+/// block changes to the store or requests to the webservice. Failing hooks
+/// are only logged as the change they are run for already happened.
+#[derive(Debug)]
+pub(super) struct Hooks {
+    config: HooksConfig,
+    datadir: PathBuf,
+    sender: Option<SyncSender<Run>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl Hooks {
+    pub(super) fn new(config: HooksConfig, datadir: &Path, enabled: bool) -> Self {
+        if !enabled || config.is_empty() {
+            return Self {
+                config,
+                datadir: datadir.to_path_buf(),
+                sender: None,
+                worker: None,
+            };
+        }
+
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_SIZE);
+        let timeout = Duration::from_secs(config.timeout_seconds);
+        let worker = std::thread::spawn(move || Hooks::run_all(receiver, timeout));
+
+        Self {
+            config,
+            datadir: datadir.to_path_buf(),
+            sender: Some(sender),
+            worker: Some(worker),
+        }
+    }
+
+    pub(super) fn is_enabled(&self) -> bool {
+        self.sender.is_some()
+    }
+
+    /// Queue the hooks for the change from `previous` to `entry`. `previous`
+    /// is `None` if the entry did not exist before.
+    pub(super) fn entry_changed(&self, previous: Option<&Metadata>, entry: &Entry) {
+        let sender = match &self.sender {
+            Some(sender) => sender,
+            None => return,
+        };
+
+        for event in webhooks::events(previous, entry) {
+            let command = match self.config.command(event) {
+                Some(command) => command,
+                None => continue,
+            };
+
+            let mut env = vec![
+                ("TODUST_EVENT", event.name().to_string()),
+                ("TODUST_UUID", entry.metadata.uuid.to_string()),
+                ("TODUST_PROJECT", entry.metadata.project.clone()),
+                ("TODUST_TITLE", helper::first_line(&entry.text)),
+                (
+                    "TODUST_DATADIR",
+                    self.datadir.to_string_lossy().into_owned(),
+                ),
+            ];
+
+            if let (EventKind::Moved, Some(previous)) = (event, previous) {
+                env.push(("TODUST_PREVIOUS_PROJECT", previous.project.clone()));
+            }
+
+            let run = Run {
+                event,
+                command: command.to_string(),
+                uuid: entry.metadata.uuid,
+                env,
+            };
+
+            match sender.try_send(run) {
+                Ok(()) => {}
+                Err(TrySendError::Full(run)) => {
+                    warn!("hook queue is full, not running {} hook", run.event.name())
+                }
+                Err(TrySendError::Disconnected(run)) => {
+                    warn!("hook worker stopped, not running {} hook", run.event.name())
+                }
+            }
+        }
+    }
+
+    fn run_all(receiver: Receiver<Run>, timeout: Duration) {
+        for run in receiver {
+            Hooks::run(&run, timeout)
+        }
+    }
+
+    fn run(run: &Run, timeout: Duration) {
+        let mut process = helper::shell_command(&run.command);
+        process
+            .envs(run.env.iter().map(|(key, value)| (key, value)))
+            .stdin(Stdio::null())
+            // Keep the output of todust itself parseable.
+            .stdout(Stdio::from(std::io::stderr()));
+
+        let mut child = match process.spawn() {
+            Ok(child) => child,
+            Err(err) => {
+                warn!(
+                    "can not run {} hook {:?}: {}",
+                    run.event.name(),
+                    run.command,
+                    err
+                );
+                return;
+            }
+        };
+
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            match child.try_wait() {
+                Ok(Some(status)) if status.success() => {
+                    debug!("ran {} hook for entry {}", run.event.name(), run.uuid);
+                    return;
+                }
+                Ok(Some(status)) => {
+                    warn!(
+                        "{} hook for entry {} failed with {}",
+                        run.event.name(),
+                        run.uuid,
+                        status
+                    );
+                    return;
+                }
+                Ok(None) if Instant::now() >= deadline => {
+                    let _ = child.kill();
+                    let _ = child.wait();
+                    warn!(
+                        "{} hook for entry {} did not finish within {} seconds and was killed",
+                        run.event.name(),
+                        run.uuid,
+                        timeout.as_secs()
+                    );
+                    return;
+                }
+                Ok(None) => std::thread::sleep(POLL_INTERVAL),
+                Err(err) => {
+                    warn!("can not wait for {} hook: {}", run.event.name(), err);
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl Drop for Hooks {
+    /// Give queued hooks a chance to run before the process exits but never
+    /// wait longer than the timeout of a single hook.
+    fn drop(&mut self) {
+        self.sender.take();
+
+        let worker = match self.worker.take() {
+            Some(worker) => worker,
+            None => return,
+        };
+
+        let deadline = Instant::now() + Duration::from_secs(self.config.timeout_seconds);
+        while !worker.is_finished() {
+            if Instant::now() >= deadline {
+                warn!("giving up on running the remaining hooks");
+                return;
+            }
+
+            std::thread::sleep(POLL_INTERVAL)
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{
+        config::Config,
+        store::tests::{
+            entry,
+            temporary_with,
+        },
+    };
+    use std::{
+        collections::BTreeMap,
+        fs,
+        os::unix::fs::PermissionsExt,
+    };
+
+    /// Hook that appends its `TODUST_` environment variables to the file
+    /// `environment` next to it, followed by a line with `---`.
+    pub(crate) fn recorder(directory: &Path) -> String {
+        let path = directory.join("hook");
+        fs::write(
+            &path,
+            "#!/bin/sh\nrecord=\"$(dirname \"$0\")/environment\"\nenv | grep '^TODUST_' | sort >> \
+             \"$record\"\necho --- >> \"$record\"\n",
+        )
+        .unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+
+        format!("'{}'", path.display())
+    }
+
+    /// Environment of every run of the `recorder` hook in the order they ran.
+    pub(crate) fn recorded(directory: &Path) -> Vec<BTreeMap<String, String>> {
+        let record = match fs::read_to_string(directory.join("environment")) {
+            Ok(record) => record,
+            Err(_) => return Vec::new(),
+        };
+
+        record
+            .split_terminator("---\n")
+            .map(|run| {
+                run.lines()
+                    .filter_map(|line| line.split_once('='))
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn config(hooks: &str) -> HooksConfig {
+        toml::from_str(hooks).unwrap()
+    }
+
+    #[test]
+    fn hooks_get_the_entry_in_the_environment() {
+        let directory = tempfile::tempdir().unwrap();
+        let hook = recorder(directory.path());
+        let added = entry("== Call the vendor\nabout the invoice");
+
+        let hooks = Hooks::new(
+            config(&format!("post_add = {:?}", hook)),
+            Path::new("/data"),
+            true,
+        );
+        hooks.entry_changed(None, &added);
+        drop(hooks);
+
+        let runs = recorded(directory.path());
+        assert_eq!(runs.len(), 1);
+        assert_eq!(runs[0]["TODUST_EVENT"], "created");
+        assert_eq!(runs[0]["TODUST_UUID"], added.metadata.uuid.to_string());
+        assert_eq!(runs[0]["TODUST_PROJECT"], "default");
+        assert_eq!(runs[0]["TODUST_TITLE"], "Call the vendor");
+        assert_eq!(runs[0]["TODUST_DATADIR"], "/data");
+        assert!(!runs[0].contains_key("TODUST_PREVIOUS_PROJECT"));
+    }
+
+    #[test]
+    fn every_event_of_a_change_runs_its_hook() {
+        let directory = tempfile::tempdir().unwrap();
+        let hook = recorder(directory.path());
+        let previous = entry("entry").metadata;
+        let mut changed = entry("entry");
+        changed.metadata.uuid = previous.uuid;
+        changed.metadata.project = "work".to_string();
+        changed.metadata.finished = Some(chrono::Utc::now());
+
+        let hooks = Hooks::new(
+            config(&format!(
+                "post_move = {:?}\npost_done = {:?}\npost_add = \"exit 1\"",
+                hook, hook
+            )),
+            directory.path(),
+            true,
+        );
+        hooks.entry_changed(Some(&previous), &changed);
+        drop(hooks);
+
+        let runs = recorded(directory.path());
+        let events = runs
+            .iter()
+            .map(|run| run["TODUST_EVENT"].as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(events, vec!["moved", "finished"]);
+        assert_eq!(runs[0]["TODUST_PREVIOUS_PROJECT"], "default");
+        assert_eq!(runs[0]["TODUST_PROJECT"], "work");
+    }
+
+    #[test]
+    fn disabled_hooks_never_run() {
+        let directory = tempfile::tempdir().unwrap();
+        let hook = recorder(directory.path());
+
+        let hooks = Hooks::new(
+            config(&format!("post_add = {:?}", hook)),
+            directory.path(),
+            false,
+        );
+        assert!(!hooks.is_enabled());
+        hooks.entry_changed(None, &entry("entry"));
+        drop(hooks);
+
+        assert!(!Hooks::new(HooksConfig::default(), directory.path(), true).is_enabled());
+        assert!(recorded(directory.path()).is_empty());
+    }
+
+    #[test]
+    fn failing_and_slow_hooks_are_not_fatal() {
+        let directory = tempfile::tempdir().unwrap();
+        let marker = directory.path().join("marker");
+        let run = |command: String| Run {
+            event: EventKind::Created,
+            command,
+            uuid: Uuid::new_v4(),
+            env: Vec::new(),
+        };
+
+        Hooks::run(&run("exit 3".to_string()), Duration::from_secs(5));
+        Hooks::run(
+            &run("does-not-exist-todust-hook".to_string()),
+            Duration::from_secs(5),
+        );
+
+        let started = Instant::now();
+        Hooks::run(
+            &run(format!("sleep 10; touch '{}'", marker.display())),
+            Duration::from_millis(100),
+        );
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert!(!marker.exists());
+    }
+
+    #[test]
+    fn store_runs_hooks_after_changes_unless_disabled() {
+        let hooks = tempfile::tempdir().unwrap();
+        let hook = recorder(hooks.path());
+        let hooks_config = config(&format!("post_add = {:?}\npost_done = {:?}", hook, hook));
+
+        let (_directory, store) = temporary_with(Config {
+            hooks: hooks_config.clone(),
+            ..Config::default()
+        });
+        let added = entry("entry");
+        store.add_entry(added.clone()).unwrap();
+        store
+            .entry_done_by_uuid(added.metadata.uuid, false)
+            .unwrap();
+        drop(store);
+
+        let events = recorded(hooks.path())
+            .iter()
+            .map(|run| run["TODUST_EVENT"].clone())
+            .collect::<Vec<_>>();
+        assert_eq!(events, vec!["created", "finished"]);
+
+        fs::remove_file(hooks.path().join("environment")).unwrap();
+        let (_directory, store) = temporary_with(Config {
+            hooks: hooks_config,
+            no_hooks: true,
+            ..Config::default()
+        });
+        store.add_entry(entry("entry")).unwrap();
+        drop(store);
+
+        assert!(recorded(hooks.path()).is_empty());
+    }
+}
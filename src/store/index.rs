@@ -1,5 +1,29 @@
-use crate::entry::Metadata;
-use log::trace;
+use crate::{
+    entry::Metadata,
+    helper,
+    store::{
+        changes::Changes,
+        conflicts::{
+            self,
+            ConcurrentChange,
+            ConflictLog,
+        },
+    },
+};
+use chrono::{
+    DateTime,
+    Datelike,
+    NaiveDate,
+    Utc,
+};
+use log::{
+    debug,
+    trace,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
 use std::{
     collections::{
         BTreeMap,
@@ -10,12 +34,52 @@ use std::{
         Path,
         PathBuf,
     },
+    sync::{
+        Arc,
+        Mutex,
+    },
+    time::SystemTime,
 };
+use uuid::Uuid;
 
 #[derive(Debug, Clone)]
 pub(crate) struct Index {
     folder_path: PathBuf,
     identifier: String,
+    rotation: IndexRotation,
+    conflicts: ConflictLog,
+    changes: Changes,
+
+    /// Rows that were read before. `None` if caching is disabled. Shared by
+    /// all clones so the webservice reads the index once for all requests.
+    cache: Option<Arc<Mutex<Option<CachedRows>>>>,
+}
+
+/// Rows of the index files as they were when they were read so commands that
+/// look at the index more than once only parse it once.
+#[derive(Debug)]
+struct CachedRows {
+    fingerprint: Vec<FileFingerprint>,
+    rows: Vec<(String, Metadata)>,
+}
+
+/// Path, size and modification time of an index file. The cached rows are
+/// only used while the fingerprints of all index files stay the same, so
+/// changes by other processes like a git pull are picked up.
+pub(crate) type FileFingerprint = (PathBuf, Option<(u64, Option<SystemTime>)>);
+
+/// Metadata rows with the identifier of the machine that wrote them.
+type Rows = Vec<(String, Metadata)>;
+
+/// How often a new index file is started for the identifier of this machine.
+/// Files of both granularities are always read so the setting can be changed
+/// at any time.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum IndexRotation {
+    Day,
+    #[default]
+    Month,
 }
 
 const IDENTIFIER_FILE_EXTENTION: &str = "csv";
@@ -24,23 +88,63 @@ const INDEX_FILE_NAME: &str = "index.csv";
 
 impl Index {
     /// Create new index from given folder path and use given identifier to
-    /// split up the index.
-    pub(crate) fn new<P: AsRef<Path>>(folder_path: P, identifier: String) -> Result<Self, Error> {
-        fs::create_dir_all(&folder_path)
-            .map_err(|err| Error::CreateIndexFolder(folder_path.as_ref().to_path_buf(), err))?;
+    /// split up the index. With `cache` the rows are kept in memory between
+    /// reads.
+    pub(crate) fn new<P: AsRef<Path>>(
+        folder_path: P,
+        identifier: String,
+        rotation: IndexRotation,
+        conflicts: ConflictLog,
+        changes: Changes,
+        cache: bool,
+    ) -> Result<Self, Error> {
+        if !folder_path.as_ref().exists() {
+            changes
+                .apply(
+                    || format!("create index folder {:?}", folder_path.as_ref()),
+                    || fs::create_dir_all(&folder_path),
+                )
+                .map_err(|err| Error::CreateIndexFolder(folder_path.as_ref().to_path_buf(), err))?;
+        }
 
         Ok(Self {
             folder_path: folder_path.as_ref().to_path_buf(),
             identifier,
+            rotation,
+            conflicts,
+            changes,
+            cache: cache.then(|| Arc::new(Mutex::new(None))),
         })
     }
 
     /// Add metadata to index.
     pub(crate) fn metadata_add(&self, metadata: &Metadata) -> Result<(), Error> {
+        self.changes.apply(
+            || {
+                format!(
+                    "add metadata row for entry {} in project {} ({})",
+                    metadata.uuid,
+                    metadata.project,
+                    if metadata.is_done() { "done" } else { "active" }
+                )
+            },
+            || self.append(metadata),
+        )?;
+
+        Ok(())
+    }
+
+    fn append(&self, metadata: &Metadata) -> Result<(), Error> {
+        self.invalidate_cache();
+
         fs::create_dir_all(self.identifier_folder_path())
             .map_err(|err| Error::CreateIdentifierFolder(self.identifier_folder_path(), err))?;
 
-        let index_path = self.todays_index_path();
+        let index_path = self.current_index_path();
+
+        if index_path.exists() {
+            Index::upgrade_index_file(&index_path)?;
+        }
 
         let mut builder = csv::WriterBuilder::new();
 
@@ -58,56 +162,235 @@ impl Index {
         let mut writer = builder.from_writer(index_file);
 
         writer
-            .serialize(&metadata)
+            .serialize(metadata)
             .map_err(Error::SerializeMetadata)?;
 
         Ok(())
     }
 
     /// Return only most recent metadata. This will be determined based on the
-    /// uuid of the entry and the last_change field.
+    /// uuid of the entry and the recency of the metadata rows. Rows that were
+    /// written concurrently on different machines are reported to the
+    /// conflict log whenever the index files changed.
     pub(crate) fn metadata_most_recent(&self) -> Result<BTreeSet<Metadata>, Error> {
-        let metadata = self
-            .metadata()?
-            .into_iter()
-            .map(|metadata| (metadata.uuid, metadata))
-            .collect::<BTreeMap<_, _>>()
+        // Entries that were moved to another store or deleted only leave their
+        // last row.
+        Ok(self
+            .latest_rows()?
+            .into_values()
+            .filter(|metadata| metadata.moved_to.is_none() && metadata.deleted.is_none())
+            .collect())
+    }
+
+    /// Most recent rows of the entries that were moved to another store or
+    /// deleted after `since`.
+    pub(crate) fn metadata_removed_since(
+        &self,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<Metadata>, Error> {
+        Ok(self
+            .latest_rows()?
+            .into_values()
+            .filter(|metadata| metadata.moved_to.is_some() || metadata.deleted.is_some())
+            .filter(|metadata| metadata.last_change > since)
+            .collect())
+    }
+
+    /// Most recent row of every entry including the moved and deleted ones.
+    fn latest_rows(&self) -> Result<BTreeMap<Uuid, Metadata>, Error> {
+        let (fingerprint, rows) = self.fingerprinted_rows()?;
+
+        if self.conflicts.index_changed(&fingerprint) {
+            let changes = conflicts::detect(&rows, self.conflicts.window());
+            if !changes.is_empty() {
+                self.conflicts.report(&changes, true);
+            }
+        }
+
+        let mut latest: BTreeMap<Uuid, Metadata> = BTreeMap::new();
+
+        for (_, metadata) in rows {
+            match latest.get(&metadata.uuid) {
+                Some(current) if current.recency() >= metadata.recency() => {}
+                _ => {
+                    latest.insert(metadata.uuid, metadata);
+                }
+            }
+        }
+
+        Ok(latest)
+    }
+
+    /// When the entry was deleted if its most recent row is a deletion.
+    pub(crate) fn deleted_at(&self, uuid: &Uuid) -> Result<Option<DateTime<Utc>>, Error> {
+        let latest = self
+            .rows()?
             .into_iter()
             .map(|(_, metadata)| metadata)
-            .collect();
+            .filter(|metadata| metadata.uuid == *uuid)
+            .max_by(|a, b| a.recency().cmp(&b.recency()));
 
-        Ok(metadata)
+        Ok(latest.and_then(|metadata| metadata.deleted))
     }
 
     /// Compact files into singular index file and only keep latest state of
-    /// entries.
-    pub(crate) fn compact(&self) -> Result<(), Error> {
-        let metadata = self.metadata_most_recent()?;
+    /// entries. Entries for which `is_stale` returns true and entries in
+    /// `archived` are dropped. Deleted entries only keep the row recording
+    /// the deletion. With
+    /// `merge_before` only fragments that end before that day are merged and
+    /// newer fragments are left untouched, otherwise all fragments are merged.
+    /// Nothing is written in a dry run but the report is the same.
+    pub(crate) fn compact<F>(
+        &self,
+        is_stale: F,
+        archived: &BTreeSet<Uuid>,
+        merge_before: Option<NaiveDate>,
+    ) -> Result<CompactionReport, Error>
+    where
+        F: Fn(&Metadata) -> bool,
+    {
+        self.invalidate_cache();
 
-        let tmp_dir = tempfile::tempdir().map_err(Error::CompactTempDir)?;
-        let tmp_path = tmp_dir.path().join(INDEX_FILE_NAME);
+        let index_file_path = self.folder_path.join(INDEX_FILE_NAME);
 
-        // In its own scope so the file will be flushed when the scope is closed.
-        {
-            let tmp_file = std::fs::OpenOptions::new()
-                .append(true)
-                .create(true)
-                .open(&tmp_path)
-                .map_err(Error::CompactTempFile)?;
+        // The compacted index file is always merged as it is the file the
+        // result is written to.
+        let (index_paths, kept_paths): (Vec<_>, Vec<_>) =
+            self.index_paths()?.into_iter().partition(|path| {
+                match (merge_before, Index::fragment_last_day(path)) {
+                    (None, _) => true,
+                    _ if *path == index_file_path => true,
+                    (Some(merge_before), Some(last_day)) => last_day < merge_before,
+                    // Files that are not named after a date are never old
+                    // enough.
+                    (Some(_), None) => false,
+                }
+            });
+
+        let mut bytes_before = 0;
+        let mut rows = Vec::new();
+        for path in &index_paths {
+            bytes_before += fs::metadata(path)
+                .map_err(|err| Error::OpenIndexFile(path.to_path_buf(), err))?
+                .len();
+
+            rows.extend(Index::read_metadata_file(path)?);
+        }
+
+        let rows_before = rows.len();
+
+        let mut rows_per_uuid: BTreeMap<Uuid, usize> = BTreeMap::new();
+        for row in &rows {
+            *rows_per_uuid.entry(row.uuid).or_default() += 1;
+        }
+        let duplicate_uuids = rows_per_uuid.values().filter(|count| **count > 1).count();
 
-            let builder = csv::WriterBuilder::new();
-            let mut writer = builder.from_writer(tmp_file);
+        // Whether an entry is stale depends on its most recent row which can
+        // also be in one of the kept fragments.
+        let stale = self
+            .metadata_most_recent()?
+            .into_iter()
+            .filter(|metadata| is_stale(metadata))
+            .map(|metadata| metadata.uuid)
+            .collect::<BTreeSet<_>>();
 
-            for entry in metadata {
-                writer.serialize(&entry).map_err(Error::SerializeMetadata)?;
+        let mut latest: BTreeMap<Uuid, Metadata> = BTreeMap::new();
+        for metadata in rows {
+            match latest.get(&metadata.uuid) {
+                Some(current) if current.recency() >= metadata.recency() => {}
+                _ => {
+                    latest.insert(metadata.uuid, metadata);
+                }
             }
         }
 
-        let index_file_path = self.folder_path.join(INDEX_FILE_NAME);
+        // Deleted entries keep the row that records the deletion as a
+        // tombstone. Without it a sync with a clone that still has older rows
+        // of the entry would bring it back.
+        let deleted = latest
+            .values()
+            .filter(|metadata| metadata.deleted.is_some())
+            .map(|metadata| metadata.uuid)
+            .collect();
+
+        let (archived, metadata): (Vec<_>, Vec<_>) = latest
+            .into_values()
+            .filter(|metadata| !stale.contains(&metadata.uuid))
+            .partition(|metadata| archived.contains(&metadata.uuid));
+
+        let mut data = Vec::new();
+
+        // In its own scope so the writer releases the buffer when the scope is
+        // closed.
+        {
+            let mut writer = csv::WriterBuilder::new().from_writer(&mut data);
+
+            for entry in &metadata {
+                writer.serialize(entry).map_err(Error::SerializeMetadata)?;
+            }
+
+            writer
+                .flush()
+                .map_err(|err| Error::SerializeMetadata(err.into()))?;
+        }
+
+        let report = CompactionReport {
+            fragments: index_paths.len(),
+            fragments_kept: kept_paths.len(),
+            rows_before,
+            rows_after: metadata.len(),
+            duplicate_uuids,
+            stale_uuids: stale.into_iter().collect(),
+            deleted_uuids: deleted,
+            archived_uuids: archived.into_iter().map(|metadata| metadata.uuid).collect(),
+            bytes_before,
+            bytes_after: data.len() as u64,
+        };
+
+        self.changes.apply(
+            || {
+                format!(
+                    "write {} compacted rows to {:?} and remove {} merged fragments",
+                    report.rows_after,
+                    index_file_path,
+                    index_paths
+                        .iter()
+                        .filter(|path| **path != index_file_path)
+                        .count()
+                )
+            },
+            || self.replace_fragments(&index_file_path, &index_paths, &data, merge_before),
+        )?;
+
+        Ok(report)
+    }
+
+    /// Write the compacted rows to the index file and remove the fragments
+    /// that were merged into it.
+    fn replace_fragments(
+        &self,
+        index_file_path: &Path,
+        index_paths: &[PathBuf],
+        data: &[u8],
+        merge_before: Option<NaiveDate>,
+    ) -> Result<(), Error> {
+        let tmp_dir = tempfile::tempdir().map_err(Error::CompactTempDir)?;
+        let tmp_path = tmp_dir.path().join(INDEX_FILE_NAME);
+        fs::write(&tmp_path, data).map_err(Error::CompactTempFile)?;
+
         std::fs::copy(tmp_path, index_file_path).map_err(Error::MoveCompactTempFile)?;
 
-        std::fs::remove_dir_all(self.folder_path.join(IDENTIFIER_FOLDER_NAME))
-            .map_err(Error::CleanupIdentifierFolder)?;
+        let identifier_folder = self.folder_path.join(IDENTIFIER_FOLDER_NAME);
+        if merge_before.is_none() {
+            if identifier_folder.exists() {
+                std::fs::remove_dir_all(identifier_folder)
+                    .map_err(Error::CleanupIdentifierFolder)?;
+            }
+        } else {
+            for path in index_paths.iter().filter(|path| *path != index_file_path) {
+                std::fs::remove_file(path).map_err(Error::CleanupIdentifierFolder)?;
+            }
+        }
 
         Ok(())
     }
@@ -126,21 +409,174 @@ impl Index {
         Ok(projects)
     }
 
+    /// Entries with a row that has a higher revision but an earlier last
+    /// change than another row of the same entry. The revision follows the
+    /// order the rows were written in, so this happens when a clock was
+    /// behind while the entry was changed and the most recent row might be an
+    /// outdated one.
+    pub(crate) fn clock_skewed_uuids(&self) -> Result<Vec<Uuid>, Error> {
+        let mut rows: BTreeMap<Uuid, Vec<(u64, DateTime<Utc>)>> = BTreeMap::new();
+
+        for metadata in self.metadata()? {
+            rows.entry(metadata.uuid)
+                .or_default()
+                .push((metadata.revision, metadata.last_change));
+        }
+
+        let skewed = rows
+            .into_iter()
+            .filter(|(_, rows)| {
+                rows.iter().any(|(revision, last_change)| {
+                    rows.iter().any(|(other_revision, other_last_change)| {
+                        other_revision > revision && other_last_change < last_change
+                    })
+                })
+            })
+            .map(|(uuid, _)| uuid)
+            .collect();
+
+        Ok(skewed)
+    }
+
+    /// Entries with rows that were written concurrently on different machines
+    /// and differ in their metadata. The caller shows them to the user so
+    /// they are not warned about again.
+    pub(crate) fn concurrent_changes(&self) -> Result<Vec<ConcurrentChange>, Error> {
+        let changes = conflicts::detect(&self.rows()?, self.conflicts.window());
+        self.conflicts.report(&changes, false);
+
+        Ok(changes)
+    }
+
+    /// Number of files the index is split up into.
+    pub(crate) fn fragment_count(&self) -> Result<usize, Error> {
+        Ok(self.index_paths()?.len())
+    }
+
     /// Get all metadata stored in the index.
     /// The index is stored by identifier and current date to make it easier to
     /// sync over git and compact old entries in the future.
     fn metadata(&self) -> Result<BTreeSet<Metadata>, Error> {
-        let glob_string = self
-            .folder_path
-            .join(IDENTIFIER_FOLDER_NAME)
-            .join("*")
-            .join(format!("*.{}", IDENTIFIER_FILE_EXTENTION));
+        let metadata = self
+            .rows()?
+            .into_iter()
+            .map(|(_, metadata)| metadata)
+            .collect();
 
-        let glob = glob::glob(&glob_string.to_string_lossy()).map_err(Error::InvalidGlob)?;
+        Ok(metadata)
+    }
+
+    /// All metadata rows with the identifier of the machine that wrote them.
+    /// Rows of the compacted index file have the identifier "index". The rows
+    /// are read from the cache while no index file changed.
+    fn rows(&self) -> Result<Vec<(String, Metadata)>, Error> {
+        Ok(self.fingerprinted_rows()?.1)
+    }
+
+    /// All metadata rows together with the fingerprint of the index files
+    /// they were read from.
+    fn fingerprinted_rows(&self) -> Result<(Vec<FileFingerprint>, Rows), Error> {
+        let paths = self.index_paths()?;
+        let fingerprint = Index::fingerprint_paths(&paths);
+
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return Ok((fingerprint, self.read_rows(paths)?)),
+        };
+
+        let mut cache = cache.lock().unwrap_or_else(|err| err.into_inner());
+
+        if let Some(cached) = cache
+            .as_ref()
+            .filter(|cached| cached.fingerprint == fingerprint)
+        {
+            trace!("read {} index rows from the cache", cached.rows.len());
+            return Ok((fingerprint, cached.rows.clone()));
+        }
 
-        let mut index_paths = glob
-            .collect::<Result<Vec<PathBuf>, glob::GlobError>>()
-            .map_err(Error::GlobIteration)?;
+        let rows = self.read_rows(paths)?;
+        *cache = Some(CachedRows {
+            fingerprint: fingerprint.clone(),
+            rows: rows.clone(),
+        });
+
+        Ok((fingerprint, rows))
+    }
+
+    /// Fingerprints of all index files. Changes whenever a row is added, the
+    /// index is compacted or other processes change the index files.
+    pub(crate) fn fingerprint(&self) -> Result<Vec<FileFingerprint>, Error> {
+        Ok(Index::fingerprint_paths(&self.index_paths()?))
+    }
+
+    fn fingerprint_paths(paths: &[PathBuf]) -> Vec<FileFingerprint> {
+        paths
+            .iter()
+            .map(|path| {
+                let metadata = fs::metadata(path)
+                    .ok()
+                    .map(|metadata| (metadata.len(), metadata.modified().ok()));
+
+                (path.clone(), metadata)
+            })
+            .collect()
+    }
+
+    /// Forget the cached rows before the index is written to.
+    fn invalidate_cache(&self) {
+        if let Some(cache) = &self.cache {
+            cache.lock().unwrap_or_else(|err| err.into_inner()).take();
+        }
+    }
+
+    fn read_rows(&self, paths: Vec<PathBuf>) -> Result<Vec<(String, Metadata)>, Error> {
+        debug!("reading {} index files", paths.len());
+
+        let mut rows = Vec::new();
+
+        for path in paths {
+            let identifier = if path == self.folder_path.join(INDEX_FILE_NAME) {
+                "index".to_string()
+            } else {
+                path.parent()
+                    .and_then(Path::file_name)
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default()
+            };
+
+            rows.extend(
+                Index::read_metadata_file(&path)?
+                    .into_iter()
+                    .map(|metadata| (identifier.clone(), metadata)),
+            );
+        }
+
+        Ok(rows)
+    }
+
+    /// Paths of all index files. These are the files of all identifiers and
+    /// the compacted index file if it exists.
+    fn index_paths(&self) -> Result<Vec<PathBuf>, Error> {
+        let identifier_folder = self.folder_path.join(IDENTIFIER_FOLDER_NAME);
+
+        let mut index_paths = Vec::new();
+        for folder in helper::folder_paths(&identifier_folder)
+            .map_err(|err| Error::ReadIdentifierFolder(identifier_folder.clone(), err))?
+            .into_iter()
+            .filter(|path| path.is_dir())
+        {
+            index_paths.extend(
+                helper::folder_paths(&folder)
+                    .map_err(|err| Error::ReadIdentifierFolder(folder.clone(), err))?
+                    .into_iter()
+                    .filter(|path| {
+                        path.is_file()
+                            && path
+                                .extension()
+                                .is_some_and(|extension| extension == IDENTIFIER_FILE_EXTENTION)
+                    }),
+            );
+        }
 
         let index_file_path = self.folder_path.join(INDEX_FILE_NAME);
         if index_file_path.exists() {
@@ -149,15 +585,61 @@ impl Index {
 
         trace!("index_paths: {:?}", index_paths);
 
-        let metadata = index_paths
-            .into_iter()
-            .map(Index::read_metadata_file)
-            .collect::<Result<Vec<Vec<_>>, Error>>()?
-            .into_iter()
-            .flatten()
-            .collect();
+        Ok(index_paths)
+    }
 
-        Ok(metadata)
+    /// Rewrite an index file that was written by an older version with fewer
+    /// metadata fields so new rows can be appended with the current header.
+    /// Missing fields are filled in with their defaults.
+    fn upgrade_index_file<P: AsRef<Path>>(file_path: P) -> Result<(), Error> {
+        let file_path = file_path.as_ref();
+
+        let file = std::fs::File::open(file_path)
+            .map_err(|err| Error::OpenIndexFile(file_path.to_path_buf(), err))?;
+
+        let header = csv::ReaderBuilder::new()
+            .from_reader(file)
+            .headers()
+            .map_err(|err| Error::ReadIndexFile(file_path.to_path_buf(), err))?
+            .clone();
+
+        if header == Index::current_header()? {
+            return Ok(());
+        }
+
+        debug!("upgrading header of index file {:?}", file_path);
+
+        let metadata = Index::read_metadata_file(file_path)?;
+
+        let file = std::fs::File::create(file_path)
+            .map_err(|err| Error::OpenIndexFile(file_path.to_path_buf(), err))?;
+
+        let mut writer = csv::WriterBuilder::new().from_writer(file);
+        for entry in metadata {
+            writer.serialize(entry).map_err(Error::SerializeMetadata)?;
+        }
+
+        Ok(())
+    }
+
+    /// Header of index files written by this version.
+    fn current_header() -> Result<csv::StringRecord, Error> {
+        let mut writer = csv::WriterBuilder::new().from_writer(Vec::new());
+        writer
+            .serialize(Metadata::default())
+            .map_err(Error::SerializeMetadata)?;
+
+        let data = writer.into_inner().map_err(|err| {
+            Error::SerializeMetadata(std::io::Error::other(err.to_string()).into())
+        })?;
+
+        let header = csv::ReaderBuilder::new()
+            .from_reader(data.as_slice())
+            .headers()
+            .map_err(Error::SerializeMetadata)?
+            .clone();
+
+        Ok(header)
     }
 
     /// Deserialize metadata from given path.
@@ -180,18 +662,43 @@ impl Index {
             .collect::<Result<Vec<Metadata>, csv::Error>>()
     }
 
-    /// Get todays file to store the index.
-    /// Will live under {identifier_file_path}/{Year}-{Month}-{Day}.csv.
-    fn todays_index_path(&self) -> PathBuf {
-        let mut index_path = self
-            .identifier_folder_path()
-            .join(chrono::Utc::now().date().to_string());
+    /// Get the file new metadata is appended to. Will live under
+    /// {identifier_file_path}/{Year}-{Month}-{Day}UTC.csv when rotating daily
+    /// and {identifier_file_path}/{Year}-{Month}.csv when rotating monthly.
+    fn current_index_path(&self) -> PathBuf {
+        let today = chrono::Utc::now().date();
+
+        let name = match self.rotation {
+            IndexRotation::Day => today.to_string(),
+            IndexRotation::Month => today.format("%Y-%m").to_string(),
+        };
 
+        let mut index_path = self.identifier_folder_path().join(name);
         index_path.set_extension(IDENTIFIER_FILE_EXTENTION);
 
         index_path
     }
 
+    /// Last day an index fragment can contain rows from, taken from its file
+    /// name. `None` if the file is not named after a day or a month.
+    fn fragment_last_day(path: &Path) -> Option<NaiveDate> {
+        let stem = path.file_stem()?.to_str()?;
+        let stem = stem.strip_suffix("UTC").unwrap_or(stem);
+
+        if let Ok(day) = NaiveDate::parse_from_str(stem, "%Y-%m-%d") {
+            return Some(day);
+        }
+
+        let first = NaiveDate::parse_from_str(&format!("{}-01", stem), "%Y-%m-%d").ok()?;
+        let next_month = if first.month() == 12 {
+            NaiveDate::from_ymd_opt(first.year() + 1, 1, 1)
+        } else {
+            NaiveDate::from_ymd_opt(first.year(), first.month() + 1, 1)
+        }?;
+
+        next_month.pred_opt()
+    }
+
     /// Get path to identifier folder.
     fn identifier_folder_path(&self) -> PathBuf {
         self.folder_path
@@ -200,6 +707,38 @@ impl Index {
     }
 }
 
+/// What compacting the index did or would do.
+#[derive(Debug, Default)]
+pub(crate) struct CompactionReport {
+    /// Number of index files that were merged.
+    pub(crate) fragments: usize,
+
+    /// Number of recent index files that were left untouched by an
+    /// incremental compaction.
+    pub(crate) fragments_kept: usize,
+
+    pub(crate) rows_before: usize,
+    pub(crate) rows_after: usize,
+
+    /// Number of entries that had more than one row.
+    pub(crate) duplicate_uuids: usize,
+
+    /// Entries that were dropped from the index because their entry file is
+    /// missing.
+    pub(crate) stale_uuids: Vec<Uuid>,
+
+    /// Deleted entries of which only the row recording the deletion was
+    /// kept.
+    pub(crate) deleted_uuids: Vec<Uuid>,
+
+    /// Entries that were dropped from the index because they were moved to
+    /// the archive.
+    pub(crate) archived_uuids: Vec<Uuid>,
+
+    pub(crate) bytes_before: u64,
+    pub(crate) bytes_after: u64,
+}
+
 #[derive(Debug)]
 pub(crate) enum Error {
     CleanupIdentifierFolder(std::io::Error),
@@ -207,10 +746,9 @@ pub(crate) enum Error {
     CompactTempFile(std::io::Error),
     CreateIdentifierFolder(PathBuf, std::io::Error),
     CreateIndexFolder(PathBuf, std::io::Error),
-    GlobIteration(glob::GlobError),
-    InvalidGlob(glob::PatternError),
     MoveCompactTempFile(std::io::Error),
     OpenIndexFile(PathBuf, std::io::Error),
+    ReadIdentifierFolder(PathBuf, std::io::Error),
     ReadIndexFile(PathBuf, csv::Error),
     SerializeMetadata(csv::Error),
 }
@@ -237,8 +775,6 @@ impl std::fmt::Display for Error {
                 "cant not create index folder at path {:?}: {}",
                 path, err
             ),
-            Error::GlobIteration(err) => write!(f, "can not create glob iterator: {}", err),
-            Error::InvalidGlob(err) => write!(f, "got invalid glob iterator: {}", err),
             Error::MoveCompactTempFile(err) => write!(
                 f,
                 "can not replace index file with compacted tmp file: {}",
@@ -247,6 +783,11 @@ impl std::fmt::Display for Error {
             Error::OpenIndexFile(path, err) => {
                 write!(f, "can not open index file at path {:?}: {}", path, err)
             }
+            Error::ReadIdentifierFolder(path, err) => write!(
+                f,
+                "can not read identifier folder at path {:?}: {}",
+                path, err
+            ),
             Error::SerializeMetadata(err) => write!(f, "cant not generate metadata: {}", err),
             Error::ReadIndexFile(path, err) => {
                 write!(f, "can not read index file from path {:?}: {}", path, err)
@@ -260,3 +801,480 @@ impl std::error::Error for Error {
         unimplemented!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::conflicts::CONFLICTS_FILE_NAME;
+    use chrono::Duration;
+
+    /// Index of one machine that shares the index folder and conflict log
+    /// with the other machines like after a git merge.
+    fn machine(folder: &Path, identifier: &str, conflicts: &ConflictLog) -> Index {
+        Index::new(
+            folder,
+            identifier.to_string(),
+            IndexRotation::Month,
+            conflicts.clone(),
+            Changes::default(),
+            true,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn concurrent_rows_of_two_machines_are_recorded() {
+        let directory = tempfile::tempdir().unwrap();
+        let conflicts = ConflictLog::new(
+            directory.path().join(CONFLICTS_FILE_NAME),
+            1,
+            Changes::default(),
+        );
+        let folder = directory.path().join("index");
+
+        let laptop = machine(&folder, "laptop", &conflicts);
+        let desktop = machine(&folder, "desktop", &conflicts);
+
+        let base = Metadata::default();
+        laptop.metadata_add(&base).unwrap();
+
+        let mut due = base.clone();
+        due.revision = 1;
+        due.last_change = base.last_change + Duration::minutes(1);
+        due.due = Some(base.last_change.naive_utc().date());
+        laptop.metadata_add(&due).unwrap();
+
+        let mut moved = base.clone();
+        moved.revision = 1;
+        moved.last_change = base.last_change + Duration::minutes(2);
+        moved.project = "work".to_string();
+        desktop.metadata_add(&moved).unwrap();
+
+        let latest = laptop.metadata_most_recent().unwrap();
+        assert_eq!(latest.len(), 1);
+        assert_eq!(latest.iter().next().unwrap().project, "work");
+
+        let records = conflicts.load().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].uuid, base.uuid);
+        assert_eq!(records[0].revision, 1);
+        assert_eq!(records[0].fields, vec!["project", "due"]);
+
+        let changes = laptop.concurrent_changes().unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].rows[0].0, "desktop");
+    }
+
+    #[test]
+    fn rows_of_one_machine_are_not_recorded() {
+        let directory = tempfile::tempdir().unwrap();
+        let conflicts = ConflictLog::new(
+            directory.path().join(CONFLICTS_FILE_NAME),
+            1,
+            Changes::default(),
+        );
+        let laptop = machine(&directory.path().join("index"), "laptop", &conflicts);
+
+        let base = Metadata::default();
+        laptop.metadata_add(&base).unwrap();
+
+        let mut moved = base.clone();
+        moved.revision = 1;
+        moved.project = "work".to_string();
+        laptop.metadata_add(&moved).unwrap();
+
+        let mut due = moved.clone();
+        due.due = Some(base.last_change.naive_utc().date());
+        laptop.metadata_add(&due).unwrap();
+
+        laptop.metadata_most_recent().unwrap();
+
+        assert!(conflicts.load().unwrap().is_empty());
+    }
+
+    #[test]
+    fn same_last_change_is_decided_by_revision() {
+        let directory = tempfile::tempdir().unwrap();
+        let conflicts = ConflictLog::new(
+            directory.path().join(CONFLICTS_FILE_NAME),
+            1,
+            Changes::default(),
+        );
+        let folder = directory.path().join("index");
+
+        let laptop = machine(&folder, "laptop", &conflicts);
+        let desktop = machine(&folder, "desktop", &conflicts);
+
+        let base = Metadata::default();
+        laptop.metadata_add(&base).unwrap();
+
+        let mut edited = base.clone();
+        edited.revision = 1;
+        edited.line_count = Some(3);
+        desktop.metadata_add(&edited).unwrap();
+
+        for index in &[&laptop, &desktop] {
+            let latest = index.metadata_most_recent().unwrap();
+            assert_eq!(latest.into_iter().collect::<Vec<_>>(), vec![edited.clone()]);
+        }
+    }
+
+    #[test]
+    fn ties_are_decided_the_same_on_every_machine() {
+        let directory = tempfile::tempdir().unwrap();
+        let conflicts = ConflictLog::new(
+            directory.path().join(CONFLICTS_FILE_NAME),
+            1,
+            Changes::default(),
+        );
+        let folder = directory.path().join("index");
+
+        let laptop = machine(&folder, "laptop", &conflicts);
+        let desktop = machine(&folder, "desktop", &conflicts);
+
+        let base = Metadata::default();
+        let mut home = base.clone();
+        home.project = "home".to_string();
+        let mut work = base.clone();
+        work.project = "work".to_string();
+
+        laptop.metadata_add(&work).unwrap();
+        desktop.metadata_add(&home).unwrap();
+
+        let expected = if home.recency() > work.recency() {
+            home
+        } else {
+            work
+        };
+
+        for index in &[&laptop, &desktop] {
+            let latest = index.metadata_most_recent().unwrap();
+            assert_eq!(
+                latest.into_iter().collect::<Vec<_>>(),
+                vec![expected.clone()]
+            );
+        }
+    }
+
+    #[test]
+    fn legacy_rows_have_no_source() {
+        let rows = "last_change,due,started,project,finished,uuid\n2019-01-01T00:00:00Z,,\
+                    2019-01-01T00:00:00Z,work,,0e3b9f6c-5c3a-4d9e-8a0f-2b7c1d4e5f60\n";
+
+        let metadata = Index::read_metadata(rows.as_bytes()).unwrap();
+
+        assert_eq!(metadata.len(), 1);
+        assert_eq!(metadata[0].project, "work");
+        assert_eq!(metadata[0].source, None);
+        assert_eq!(metadata[0].created, None);
+        assert_eq!(metadata[0].line_count, None);
+    }
+
+    #[test]
+    fn rows_written_with_a_clock_behind_are_reported() {
+        let directory = tempfile::tempdir().unwrap();
+        let conflicts = ConflictLog::new(
+            directory.path().join(CONFLICTS_FILE_NAME),
+            1,
+            Changes::default(),
+        );
+        let laptop = machine(&directory.path().join("index"), "laptop", &conflicts);
+
+        let monotonic = Metadata::default();
+        laptop.metadata_add(&monotonic).unwrap();
+        laptop
+            .metadata_add(&Metadata {
+                revision: 1,
+                last_change: monotonic.last_change + Duration::minutes(1),
+                ..monotonic.clone()
+            })
+            .unwrap();
+
+        let skewed = Metadata::default();
+        laptop.metadata_add(&skewed).unwrap();
+        laptop
+            .metadata_add(&Metadata {
+                revision: 1,
+                last_change: skewed.last_change - Duration::hours(1),
+                ..skewed.clone()
+            })
+            .unwrap();
+
+        assert_eq!(laptop.clock_skewed_uuids().unwrap(), vec![skewed.uuid]);
+    }
+
+    fn is_cached(index: &Index) -> bool {
+        index
+            .cache
+            .as_ref()
+            .is_some_and(|cache| cache.lock().unwrap().is_some())
+    }
+
+    fn projects(index: &Index) -> Vec<String> {
+        index
+            .metadata_most_recent()
+            .unwrap()
+            .into_iter()
+            .map(|metadata| metadata.project)
+            .collect()
+    }
+
+    #[test]
+    fn cached_rows_are_dropped_when_the_index_changes() {
+        let directory = tempfile::tempdir().unwrap();
+        let conflicts = ConflictLog::new(
+            directory.path().join(CONFLICTS_FILE_NAME),
+            1,
+            Changes::default(),
+        );
+        let folder = directory.path().join("index");
+        let laptop = machine(&folder, "laptop", &conflicts);
+        let shared = laptop.clone();
+
+        let base = Metadata::default();
+        laptop.metadata_add(&base).unwrap();
+        assert!(!is_cached(&laptop));
+
+        assert_eq!(projects(&laptop), vec!["default"]);
+        assert!(is_cached(&shared));
+
+        // Writes of the same index clear the cache right away.
+        let moved = Metadata {
+            revision: 1,
+            last_change: base.last_change + Duration::minutes(1),
+            project: "work".to_string(),
+            ..base.clone()
+        };
+        shared.metadata_add(&moved).unwrap();
+        assert!(!is_cached(&laptop));
+        assert_eq!(projects(&laptop), vec!["work"]);
+
+        // Writes of another process are noticed by the changed index file.
+        let other_process = machine(&folder, "laptop", &conflicts);
+        other_process
+            .metadata_add(&Metadata {
+                revision: 2,
+                last_change: base.last_change + Duration::minutes(2),
+                project: "home".to_string(),
+                ..base.clone()
+            })
+            .unwrap();
+        assert!(is_cached(&laptop));
+        assert_eq!(projects(&laptop), vec!["home"]);
+    }
+
+    #[test]
+    fn disabled_cache_reads_the_index_every_time() {
+        let directory = tempfile::tempdir().unwrap();
+        let laptop = Index::new(
+            directory.path().join("index"),
+            "laptop".to_string(),
+            IndexRotation::Month,
+            ConflictLog::new(
+                directory.path().join(CONFLICTS_FILE_NAME),
+                1,
+                Changes::default(),
+            ),
+            Changes::default(),
+            false,
+        )
+        .unwrap();
+
+        laptop.metadata_add(&Metadata::default()).unwrap();
+
+        assert_eq!(projects(&laptop), vec!["default"]);
+        assert!(laptop.cache.is_none());
+    }
+
+    /// Write an index fragment like another version or machine would have.
+    fn write_fragment(folder: &Path, name: &str, rows: &[&Metadata]) -> PathBuf {
+        let path = folder.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+        let mut writer = csv::WriterBuilder::new().from_path(&path).unwrap();
+        for row in rows {
+            writer.serialize(row).unwrap();
+        }
+        writer.flush().unwrap();
+
+        path
+    }
+
+    fn in_project(project: &str) -> Metadata {
+        Metadata {
+            project: project.to_string(),
+            ..Metadata::default()
+        }
+    }
+
+    fn changed(metadata: &Metadata, project: &str) -> Metadata {
+        Metadata {
+            project: project.to_string(),
+            revision: metadata.revision + 1,
+            last_change: metadata.last_change + Duration::minutes(1),
+            ..metadata.clone()
+        }
+    }
+
+    fn most_recent(index: &Index) -> BTreeMap<Uuid, Metadata> {
+        index
+            .metadata_most_recent()
+            .unwrap()
+            .into_iter()
+            .map(|metadata| (metadata.uuid, metadata))
+            .collect()
+    }
+
+    #[test]
+    fn fragments_are_named_after_their_last_day() {
+        let last_day = |name: &str| Index::fragment_last_day(Path::new(name));
+        let day = |year, month, day| NaiveDate::from_ymd_opt(year, month, day);
+
+        assert_eq!(last_day("2026-09-14UTC.csv"), day(2026, 9, 14));
+        assert_eq!(last_day("2026-09-14.csv"), day(2026, 9, 14));
+        assert_eq!(last_day("2026-09.csv"), day(2026, 9, 30));
+        assert_eq!(last_day("2026-02.csv"), day(2026, 2, 28));
+        assert_eq!(last_day("2024-02.csv"), day(2024, 2, 29));
+        assert_eq!(last_day("2026-12.csv"), day(2026, 12, 31));
+        assert_eq!(last_day("index.csv"), None);
+        assert_eq!(last_day("notes.csv"), None);
+    }
+
+    #[test]
+    fn daily_and_monthly_fragments_are_read_together() {
+        let directory = tempfile::tempdir().unwrap();
+        let conflicts = ConflictLog::new(
+            directory.path().join(CONFLICTS_FILE_NAME),
+            1,
+            Changes::default(),
+        );
+        let folder = directory.path().join("index");
+
+        let first = in_project("default");
+        let moved = changed(&first, "work");
+        let second = in_project("home");
+        let third = in_project("garden");
+        write_fragment(&folder, "identifier/laptop/2026-09-14UTC.csv", &[&first]);
+        write_fragment(&folder, "identifier/laptop/2026-10.csv", &[&moved]);
+        write_fragment(&folder, "identifier/desktop/2026-10-02UTC.csv", &[&second]);
+        write_fragment(&folder, "index.csv", &[&third]);
+
+        let laptop = machine(&folder, "laptop", &conflicts);
+        let expected = vec![moved, second, third]
+            .into_iter()
+            .map(|metadata| (metadata.uuid, metadata))
+            .collect::<BTreeMap<_, _>>();
+        assert_eq!(most_recent(&laptop), expected);
+
+        // New rows of a machine that rotates daily go next to the monthly
+        // files.
+        let daily = Index::new(
+            &folder,
+            "laptop".to_string(),
+            IndexRotation::Day,
+            conflicts.clone(),
+            Changes::default(),
+            false,
+        )
+        .unwrap();
+        let added = in_project("daily");
+        daily.metadata_add(&added).unwrap();
+        assert_eq!(laptop.fragment_count().unwrap(), 5);
+        assert_eq!(most_recent(&laptop).len(), 4);
+
+        let report = laptop.compact(|_| false, &BTreeSet::new(), None).unwrap();
+        assert_eq!(report.fragments, 5);
+        assert_eq!(report.fragments_kept, 0);
+        assert_eq!(report.rows_before, 5);
+        assert_eq!(report.rows_after, 4);
+
+        assert!(!folder.join(IDENTIFIER_FOLDER_NAME).exists());
+        let mut expected = expected;
+        expected.insert(added.uuid, added);
+        assert_eq!(most_recent(&laptop), expected);
+    }
+
+    #[test]
+    fn incremental_compaction_keeps_fragments_from_the_boundary_on() {
+        let directory = tempfile::tempdir().unwrap();
+        let conflicts = ConflictLog::new(
+            directory.path().join(CONFLICTS_FILE_NAME),
+            1,
+            Changes::default(),
+        );
+        let folder = directory.path().join("index");
+
+        let edited = in_project("default");
+        let edited_later = changed(&edited, "work");
+        let old_month = in_project("old month");
+        let new_month = in_project("new month");
+        let undated = in_project("undated");
+        let compacted = in_project("compacted");
+
+        let merged = [
+            write_fragment(&folder, "identifier/laptop/2026-09-30UTC.csv", &[&edited]),
+            write_fragment(&folder, "identifier/desktop/2026-09.csv", &[&old_month]),
+        ];
+        let kept = [
+            write_fragment(
+                &folder,
+                "identifier/laptop/2026-10-01UTC.csv",
+                &[&edited_later],
+            ),
+            write_fragment(&folder, "identifier/desktop/2026-10.csv", &[&new_month]),
+            write_fragment(&folder, "identifier/desktop/notes.csv", &[&undated]),
+        ];
+        write_fragment(&folder, "index.csv", &[&compacted]);
+
+        let kept_contents = kept
+            .iter()
+            .map(|path| fs::read(path).unwrap())
+            .collect::<Vec<_>>();
+
+        let laptop = machine(&folder, "laptop", &conflicts);
+        let before = most_recent(&laptop);
+        assert_eq!(before.len(), 5);
+
+        let boundary = NaiveDate::from_ymd_opt(2026, 10, 1);
+        let report = laptop
+            .compact(|_| false, &BTreeSet::new(), boundary)
+            .unwrap();
+        assert_eq!(report.fragments, 3);
+        assert_eq!(report.fragments_kept, 3);
+        assert_eq!(report.rows_before, 3);
+        assert_eq!(report.rows_after, 3);
+
+        // Fragments before the boundary are merged into the index file and
+        // the ones from the boundary on are left alone.
+        assert!(merged.iter().all(|path| !path.exists()));
+        for (path, contents) in kept.iter().zip(&kept_contents) {
+            assert_eq!(&fs::read(path).unwrap(), contents, "{:?}", path);
+        }
+
+        let compacted_rows = Index::read_metadata_file(folder.join(INDEX_FILE_NAME))
+            .unwrap()
+            .into_iter()
+            .map(|metadata| metadata.uuid)
+            .collect::<Vec<_>>();
+        assert_eq!(compacted_rows.len(), 3);
+        assert_eq!(
+            compacted_rows.into_iter().collect::<BTreeSet<_>>(),
+            vec![edited.uuid, old_month.uuid, compacted.uuid]
+                .into_iter()
+                .collect()
+        );
+
+        // No entry is lost and the newer row in a kept fragment still wins.
+        assert_eq!(most_recent(&laptop), before);
+        assert_eq!(most_recent(&laptop)[&edited.uuid].project, "work");
+
+        // Compacting again does not duplicate rows.
+        let report = laptop
+            .compact(|_| false, &BTreeSet::new(), boundary)
+            .unwrap();
+        assert_eq!(report.fragments, 1);
+        assert_eq!(report.rows_before, 3);
+        assert_eq!(report.rows_after, 3);
+        assert_eq!(most_recent(&laptop), before);
+    }
+}
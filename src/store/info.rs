@@ -0,0 +1,98 @@
+use crate::store::{
+    quota::QuotaUsage,
+    Store,
+};
+use anyhow::Error;
+use serde::Serialize;
+use std::{
+    collections::BTreeSet,
+    path::PathBuf,
+};
+
+/// Version of todust and facts about a store for debugging setups where the
+/// same datadir is used on multiple machines.
+#[derive(Debug, Serialize)]
+pub(crate) struct StoreInfo {
+    /// Version of todust.
+    pub(crate) version: &'static str,
+
+    /// Version of the store from its settings.
+    pub(crate) store_version: usize,
+
+    /// Identifier of this machine from the config. The index is split up by
+    /// it.
+    pub(crate) identifier: String,
+
+    /// Path of the datadir. Left out when the paths are redacted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) datadir: Option<PathBuf>,
+
+    /// Version control system of the datadir if there is one.
+    pub(crate) vcs: Option<String>,
+
+    /// Commit the datadir is at if it is under version control.
+    pub(crate) head: Option<String>,
+
+    pub(crate) entry_count: usize,
+    pub(crate) project_count: usize,
+
+    /// Number of index files. Grows with every day and machine entries were
+    /// changed on until the index is compacted by the cleanup.
+    pub(crate) index_fragment_count: usize,
+
+    /// Entries and documents asciidoctor failed to render since todust was
+    /// started. Only counts in the webservice as it keeps running.
+    pub(crate) render_failures: usize,
+
+    /// Usage of the quotas that are configured.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub(crate) quotas: Vec<QuotaUsage>,
+}
+
+impl StoreInfo {
+    /// Remove everything that reveals paths on the filesystem.
+    pub(crate) fn redact_paths(self) -> Self {
+        Self {
+            datadir: None,
+            ..self
+        }
+    }
+}
+
+impl Store {
+    /// Version of todust and facts about the store like its identifier and
+    /// how many entries it has.
+    pub(crate) fn info(&self) -> Result<StoreInfo, Error> {
+        let metadata = self.index.metadata_most_recent()?;
+
+        let project_count = metadata
+            .iter()
+            .map(|metadata| &metadata.project)
+            .collect::<BTreeSet<_>>()
+            .len();
+
+        let head = self
+            .settings
+            .vcs
+            .as_ref()
+            .and_then(|vcs| vcs.head(&self.datadir));
+
+        Ok(StoreInfo {
+            version: env!("CARGO_PKG_VERSION"),
+            store_version: self.settings.store_version,
+            identifier: self.config.identifier.clone(),
+            datadir: Some(self.datadir.clone()),
+            vcs: self
+                .settings
+                .vcs
+                .as_ref()
+                .map(|vcs| vcs.vcs_type().to_string()),
+            head,
+            entry_count: metadata.len(),
+            project_count,
+            index_fragment_count: self.index.fragment_count()?,
+            render_failures: self.render_cache.failures(),
+            quotas: self.config.quotas.usage(&metadata),
+        })
+    }
+}
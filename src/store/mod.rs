@@ -1,435 +1,586 @@
+pub(crate) mod archive;
+pub(crate) mod audit;
+pub(crate) mod backup;
+pub(crate) mod changes;
+pub(crate) mod cleanup;
+pub(crate) mod conflicts;
+pub(crate) mod delete;
+pub(crate) mod favorites;
+mod files;
+pub(crate) mod focus;
+pub(crate) mod hooks;
 pub(super) mod index;
+pub(crate) mod info;
+mod projects;
+mod query;
+pub(crate) mod quota;
+pub(crate) mod recent;
+pub(crate) mod render_cache;
+mod settings;
+pub(crate) mod state;
+pub(crate) mod stats_history;
+pub(crate) mod sync_log;
+mod update;
 pub(super) mod vcs;
+pub(crate) mod views;
+pub(crate) mod web_lock;
+pub(crate) mod webhooks;
 
 use crate::{
+    config::Config,
     entry::{
-        Entries,
         Entry,
         Metadata,
-        ProjectCount,
     },
-    helper::confirm,
+    interactivity::Interactivity,
     store::{
-        index::Index,
-        vcs::VcsSettings,
+        audit::AuditLog,
+        backup::Backups,
+        changes::Changes,
+        conflicts::ConflictLog,
+        favorites::Favorites,
+        focus::Focus,
+        hooks::Hooks,
+        index::{
+            FileFingerprint,
+            Index,
+        },
+        render_cache::RenderCache,
+        settings::StoreSettings,
+        state::StateStore,
+        stats_history::StatsHistory,
+        sync_log::SyncLog,
+        views::Views,
+        web_lock::WebInstanceLock,
+        webhooks::Dispatcher,
     },
 };
-use anyhow::{
-    bail,
-    format_err,
-    Context,
-    Error,
-};
-use chrono::Utc;
-use glob::glob;
-use log::{
-    debug,
-    info,
-    trace,
-};
-use serde::{
-    Deserialize,
-    Serialize,
-};
+use anyhow::Error;
+use log::warn;
 use std::{
-    collections::{
-        BTreeSet,
-        HashMap,
-    },
+    convert::Infallible,
     fs,
-    io::Write,
     path::{
         Path,
         PathBuf,
     },
+    sync::Arc,
 };
-use uuid::Uuid;
-use vcs::VcsConfig;
 
 #[derive(Debug, Clone)]
 pub(crate) struct Store {
     datadir: PathBuf,
     index: Index,
     settings: StoreSettings,
-    vcs_config: VcsConfig,
+    config: Config,
+    webhooks: Arc<Dispatcher>,
+    hooks: Arc<Hooks>,
+    views: Views,
+    focus: Focus,
+    favorites: Favorites,
+    conflicts: ConflictLog,
+    render_cache: RenderCache,
+    backups: Backups,
+    audit: AuditLog,
+    sync_log: SyncLog,
+    stats_history: StatsHistory,
+
+    /// Set if the datadir was not a store before it was opened.
+    new_store: Option<NewStore>,
+
+    /// Makes the changes or only plans them in a dry run.
+    changes: Changes,
+}
+
+/// How a store that was created when it was opened is kept under version
+/// control.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum NewStore {
+    /// A new repository was initialized in the datadir.
+    RepositoryInitialized,
+
+    /// The datadir already was a repository.
+    ExistingRepository,
+
+    /// The repository could not be initialized so committing will fail.
+    RepositoryFailed,
+
+    /// Changes are not committed, either because autocommit is disabled or
+    /// in a dry run.
+    NotCommitted,
 }
 
 impl Store {
-    pub(crate) fn open<P: AsRef<Path>>(
+    pub(crate) fn open<P: AsRef<Path>>(datadir: P, config: Config) -> Result<Self, Error> {
+        if !datadir.as_ref().exists() {
+            config.changes.apply(
+                || format!("create the datadir {:?}", datadir.as_ref()),
+                || fs::create_dir_all(&datadir),
+            )?;
+        }
+
+        // The state directory is derived from the path of the datadir so it
+        // has to exist first.
+        let state = StateStore::open(&datadir, config.changes.clone())?;
+
+        Store::with_state(datadir, config, state)
+    }
+
+    /// Open the store with the per machine state kept in `state`.
+    fn with_state<P: AsRef<Path>>(
         datadir: P,
-        identifier: String,
-        vcs_config: VcsConfig,
+        config: Config,
+        state: StateStore,
     ) -> Result<Self, Error> {
-        std::fs::create_dir_all(&datadir)?;
-
-        let settings = Store::get_settings(&datadir)?;
+        let is_new = !Store::settings_path(&datadir).exists();
+        let settings = Store::get_settings(&datadir, &config)?;
+
+        let views = Views::new(state.clone());
+        let focus = Focus::new(state.clone());
+        let favorites = Favorites::new(state.clone());
+        let conflicts = ConflictLog::new(
+            state.directory().join(conflicts::CONFLICTS_FILE_NAME),
+            config.conflict_window_hours,
+            config.changes.clone(),
+        );
+        let render_cache = RenderCache::new(state.clone());
+        let backups = Backups::new(&config.backups, &state);
+        let audit = AuditLog::new(
+            match &config.audit_directory {
+                Some(directory) => datadir.as_ref().join(directory),
+                None => state.directory().join("audit"),
+            },
+            config.changes.clone(),
+        );
+        let sync_log = SyncLog::new(state);
+
+        if let Err(err) = views.migrate(datadir.as_ref()) {
+            warn!("can not move views to the state directory: {:#}", err);
+        }
 
-        if settings.store_version != 1 {
-            bail!("wrong store version")
+        if let Err(err) = focus.migrate(datadir.as_ref()) {
+            warn!("can not move focus to the state directory: {:#}", err);
         }
 
+        let new_store = if is_new {
+            Some(Store::init_vcs(datadir.as_ref(), &settings, &config))
+        } else {
+            None
+        };
+
         Ok(Self {
             datadir: datadir.as_ref().to_path_buf(),
-            index: Index::new(Store::index_folder(&datadir), identifier)?,
+            index: Index::new(
+                Store::index_folder(&datadir),
+                config.identifier.clone(),
+                config.index_rotation,
+                conflicts.clone(),
+                config.changes.clone(),
+                !config.no_index_cache,
+            )?,
             settings,
-            vcs_config,
+            webhooks: Arc::new(Dispatcher::new(config.webhooks.clone())),
+            hooks: Arc::new(Hooks::new(
+                config.hooks.clone(),
+                datadir.as_ref(),
+                !config.no_hooks,
+            )),
+            views,
+            focus,
+            favorites,
+            conflicts,
+            render_cache,
+            backups,
+            audit,
+            sync_log,
+            stats_history: StatsHistory::new(datadir.as_ref(), config.changes.clone()),
+            new_store,
+            changes: config.changes.clone(),
+            config,
         })
     }
 
-    fn index_folder<P: AsRef<Path>>(datadir: P) -> PathBuf {
-        let mut index_file = PathBuf::new();
-        index_file.push(datadir);
-        index_file.push("index");
-
-        index_file
-    }
-
-    fn get_settings<P: AsRef<Path>>(datadir: P) -> Result<StoreSettings, Error> {
-        let path = Store::settings_path(&datadir);
-
-        if !path.exists() {
-            let info = StoreSettings::default();
-            let data = toml::to_string_pretty(&info)?;
-
-            let mut file = fs::File::create(path)?;
-            file.write_all(data.as_bytes())?;
+    /// Put the datadir of a new store under version control so the first
+    /// change can already be committed.
+    fn init_vcs(datadir: &Path, settings: &StoreSettings, config: &Config) -> NewStore {
+        let vcs = match &settings.vcs {
+            Some(vcs) if config.vcs_config.autocommit() => vcs,
+            _ => return NewStore::NotCommitted,
+        };
 
-            return Ok(info);
+        if vcs.is_repository(datadir) {
+            return NewStore::ExistingRepository;
         }
 
-        let data = fs::read(path)?;
-        let info = toml::from_slice(&data)?;
-
-        Ok(info)
+        match config.changes.apply(
+            || format!("initialize a repository in {:?}", datadir),
+            || vcs.init(datadir),
+        ) {
+            Ok(Some(())) => NewStore::RepositoryInitialized,
+            Ok(None) => NewStore::NotCommitted,
+            Err(err) => {
+                warn!("{}", err);
+                NewStore::RepositoryFailed
+            }
+        }
     }
 
-    fn settings_path<P: AsRef<Path>>(datadir: P) -> PathBuf {
-        let mut path = PathBuf::new();
-        path.push(datadir);
-        path.push(".settings.toml");
-
-        path
+    /// Deliver the queued webhook events before the process stops. Events
+    /// of later changes are dropped.
+    pub(crate) fn flush_webhooks(&self) {
+        self.webhooks.flush()
     }
 
-    fn get_entry_foldername(&self, entry: &Metadata) -> PathBuf {
-        let uuid = entry.uuid.to_string();
-        debug!("uuid: {}", uuid);
-
-        // Gets the first two characters of the uuid. This should never fail so the
-        // unwrap is safe.
-        let uuid_prefix = &uuid[..uuid.char_indices().nth(2).unwrap().0];
-        debug!("uuid_prefix: {}", uuid_prefix);
-
-        // {{ datadir }}/entries/{{ uuid_prefix }}
-        let mut folder = PathBuf::new();
-        folder.push(&self.datadir);
-        folder.push("entries");
-        folder.push(uuid_prefix);
-
-        debug!("folder: {:?}", folder);
-
-        folder
+    /// Record that a webservice is serving this datadir on the address. Fails
+    /// if another webservice that is still running did so unless `force` is
+    /// set.
+    pub(crate) fn lock_web_instance(
+        &self,
+        address: &str,
+        force: bool,
+    ) -> Result<WebInstanceLock, Error> {
+        WebInstanceLock::acquire(
+            StateStore::open(&self.datadir, self.changes.clone())?,
+            address,
+            force,
+        )
     }
 
-    fn get_entry_filename(&self, entry: &Metadata) -> PathBuf {
-        let entry_folder = self.get_entry_foldername(entry);
-
-        let mut entry_file = PathBuf::new();
-        entry_file.push(entry_folder);
-        entry_file.push(format!("{}.adoc", entry.uuid));
-
-        entry_file
+    /// If the datadir already is a store. Opening a datadir that is not
+    /// creates a new store in it.
+    pub(crate) fn exists<P: AsRef<Path>>(datadir: P) -> bool {
+        Store::settings_path(datadir).exists()
     }
 
-    fn write_entry_text(&self, entry: &Entry) -> Result<(), Error> {
-        let entry_folder = self.get_entry_foldername(&entry.metadata);
-        fs::create_dir_all(&entry_folder).context("can not create entry folder")?;
-
-        let entry_file = self.get_entry_filename(&entry.metadata);
-
-        let mut file = fs::File::create(entry_file).context("can not create entry file")?;
-        file.write(entry.text.as_bytes())
-            .context("can not write entry text to file")?;
-
-        Ok(())
+    /// How the store was set up if it was created when it was opened.
+    pub(crate) fn new_store(&self) -> Option<NewStore> {
+        self.new_store
     }
 
-    fn get_entry_for_metadata(&self, metadata: Metadata) -> Result<Entry, Error> {
-        let entry_file = self.get_entry_filename(&metadata);
-        let text = fs::read_to_string(entry_file).context("can not read entry file text")?;
-
-        Ok(Entry { metadata, text })
+    pub(crate) fn datadir(&self) -> &Path {
+        &self.datadir
     }
 
-    fn cleanup_unreferenced_entry(&self) -> Result<(), Error> {
-        let glob_text = format!("{}/entries/**/*.adoc", self.datadir.to_str().unwrap());
-
-        let store_uuids = self
-            .index
-            .metadata_most_recent()?
-            .iter()
-            .map(|metadata| metadata.uuid)
-            .collect::<BTreeSet<_>>();
-
-        for path in (glob(&glob_text).context("failed to read glob pattern")?).flatten() {
-            let uuid = path
-                .file_stem()
-                .unwrap()
-                .to_str()
-                .unwrap()
-                .parse::<Uuid>()
-                .context("can not parse uuid from file name")?;
-
-            if !store_uuids.contains(&uuid) {
-                info!("remove unreferenced entry: {:?}", path);
-                fs::remove_file(path)?;
-            }
-
-            trace!("uuid from file entry: {:?}", uuid);
-        }
+    /// Fingerprint of the index that changes whenever an entry is added or
+    /// changed, by this process, another one or a sync.
+    pub(crate) fn fingerprint(&self) -> Result<Vec<FileFingerprint>, Error> {
+        Ok(self.index.fingerprint()?)
+    }
 
-        Ok(())
+    /// What can be asked and shown in the terminal todust runs in.
+    pub(crate) fn interactivity(&self) -> Interactivity {
+        self.config.interactivity
     }
 
-    pub(crate) fn add_entry(&self, entry: Entry) -> Result<(), Error> {
-        self.write_entry_text(&entry)
-            .context("can not write entry text to file")?;
+    fn index_folder<P: AsRef<Path>>(datadir: P) -> PathBuf {
+        let mut index_file = PathBuf::new();
+        index_file.push(datadir);
+        index_file.push("index");
 
-        self.index.metadata_add(&entry.metadata)?;
+        index_file
+    }
 
-        if let Some(vcs) = &self.settings.vcs {
-            let message = format!("added entry with id {}", entry.metadata.uuid);
-            vcs.commit(&self.datadir, &message, &self.vcs_config)?;
+    /// Notify the webhooks and run the hooks for the changed entry. Nothing is
+    /// sent or run in a dry run.
+    fn notify_changed(&self, previous: Option<&Metadata>, entry: &Entry) {
+        if !self.webhooks.is_enabled() && !self.hooks.is_enabled() {
+            return;
         }
 
-        Ok(())
+        self.changes
+            .apply(
+                || {
+                    format!(
+                        "notify the hooks and webhooks about entry {}",
+                        entry.metadata.uuid
+                    )
+                },
+                || {
+                    self.webhooks.entry_changed(previous, entry);
+                    self.hooks.entry_changed(previous, entry);
+
+                    Ok::<_, Infallible>(())
+                },
+            )
+            .unwrap_or_else(|never| match never {});
     }
+}
 
-    pub(crate) fn entry_done(&self, entry_id: usize, project: &str) -> Result<(), Error> {
-        // TODO: Change this to only fetch the metadata as we dont need to touch the
-        // entry text.
-        let entry = self
-            .get_entry_by_id(entry_id, project)
-            .context("can not get entry from id")?;
-
-        // TODO: This should be handled in main not by the store.
-        let message = format!("do you want to finish this entry?:\n{}", entry.to_string());
-        if !confirm(&message, false)? {
-            bail!("not finishing task then")
-        }
+/// What happened to the entries of another store when they were merged into
+/// this one.
+#[derive(Debug, Default)]
+pub(crate) struct MergeCounts {
+    /// Entries that were not in this store.
+    pub(crate) imported: usize,
 
-        let new = Metadata {
-            finished: Some(Utc::now()),
-            last_change: Utc::now(),
-            ..entry.metadata
-        };
+    /// Entries that were replaced by a copy that was changed later.
+    pub(crate) merged: usize,
 
-        trace!("new: {:#?}", new);
+    /// Entries that were not changed later than the copy in this store.
+    pub(crate) skipped: usize,
+}
 
-        self.index
-            .metadata_add(&new)
-            .context("can not add entry to done index")?;
+/// Outcome of an update that checks for concurrent modifications.
+#[derive(Debug)]
+pub(crate) enum UpdateOutcome {
+    Updated,
 
-        if let Some(vcs) = &self.settings.vcs {
-            let message = format!("marked entry with id {} as done", entry.metadata.uuid);
-            vcs.commit(&self.datadir, &message, &self.vcs_config)?;
-        }
+    /// The entry was changed by someone else. Contains the current entry.
+    Conflict(Box<Entry>),
+}
 
-        Ok(())
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{
+        helper,
+        time_context::TimeContext,
+    };
+    use chrono::Duration;
+    use std::collections::BTreeMap;
+
+    /// Store in the datadir that keeps its state below `state_home` and never
+    /// commits.
+    pub(crate) fn open(datadir: &Path, state_home: &Path, changes: &Changes) -> Store {
+        open_with(
+            datadir,
+            state_home,
+            Config {
+                changes: changes.clone(),
+                ..Config::default()
+            },
+        )
     }
 
-    pub(crate) fn entry_done_by_uuid(&self, uuid: Uuid) -> Result<(), Error> {
-        let entry = self
-            .get_entry_by_uuid(&uuid)
-            .context("can not get entry from uuid")?;
-
-        let new = Metadata {
-            finished: Some(Utc::now()),
-            last_change: Utc::now(),
-            ..entry.metadata
+    /// Like `open` but with the other settings of the config.
+    pub(crate) fn open_with(datadir: &Path, state_home: &Path, config: Config) -> Store {
+        let config = Config {
+            vcs_config: toml::from_str("autocommit = false\nautopull = false\nautopush = false")
+                .unwrap(),
+            ..config
         };
 
-        self.index
-            .metadata_add(&new)
-            .context("can not add entry to done index")?;
-
-        if let Some(vcs) = &self.settings.vcs {
-            let message = format!("marked entry with id {} as done", entry.metadata.uuid);
-            vcs.commit(&self.datadir, &message, &self.vcs_config)?;
-        }
+        let state = StateStore::with_state_home(state_home, datadir, config.changes.clone());
 
-        Ok(())
+        Store::with_state(datadir, config, state).unwrap()
     }
 
-    pub(crate) fn entry_active_by_uuid(&self, uuid: Uuid) -> Result<(), Error> {
-        let entry = self
-            .get_entry_by_uuid(&uuid)
-            .context("can not get entry from uuid")?;
-
-        let new = Metadata {
-            finished: None,
-            last_change: Utc::now(),
-            ..entry.metadata
-        };
-
-        self.index
-            .metadata_add(&new)
-            .context("can not add entry to active index")?;
+    /// Every file below the folder with its content.
+    fn snapshot(folder: &Path) -> BTreeMap<PathBuf, Vec<u8>> {
+        let mut files = BTreeMap::new();
 
-        if let Some(vcs) = &self.settings.vcs {
-            let message = format!("marked entry with id {} as done", entry.metadata.uuid);
-            vcs.commit(&self.datadir, &message, &self.vcs_config)?;
+        for path in helper::folder_paths(folder).unwrap() {
+            if path.is_dir() {
+                files.extend(snapshot(&path));
+            } else {
+                files.insert(path.clone(), fs::read(&path).unwrap());
+            }
         }
 
-        Ok(())
+        files
     }
 
-    pub(crate) fn get_active_entries(&self, project: &str) -> Result<Entries, Error> {
-        let entries = self
-            .get_entries(project)?
-            .into_iter()
-            .filter(Entry::is_active)
-            .collect();
-
-        trace!("entries: {:#?}", entries);
-
-        Ok(entries)
+    /// Store in a new temporary directory that is removed with the returned
+    /// directory.
+    pub(crate) fn temporary() -> (tempfile::TempDir, Store) {
+        temporary_with(Config::default())
     }
 
-    pub(crate) fn get_done_entries(&self, project: &str) -> Result<Entries, Error> {
-        let entries = self
-            .get_entries(project)?
-            .into_iter()
-            .filter(Entry::is_done)
-            .collect();
+    /// Like `temporary` but with the settings of the config.
+    pub(crate) fn temporary_with(config: Config) -> (tempfile::TempDir, Store) {
+        let directory = tempfile::tempdir().unwrap();
+        let datadir = directory.path().join("datadir");
+        fs::create_dir_all(&datadir).unwrap();
 
-        trace!("entries: {:#?}", entries);
+        let store = open_with(&datadir, &directory.path().join("state"), config);
 
-        Ok(entries)
+        (directory, store)
     }
 
-    pub(crate) fn get_entries(&self, project: &str) -> Result<Entries, Error> {
-        let metadata_entries = self
-            .index
-            .metadata_most_recent()
-            .context("can not get metadata from active index")?;
-
-        let raw_entries: Entries = metadata_entries
-            .into_iter()
-            .filter(|metadata| metadata.project == project)
-            .map(|metadata| self.get_entry_for_metadata(metadata))
-            .collect::<Result<BTreeSet<Entry>, Error>>()
-            .context("can not get entry for metadata")?
-            .into();
-
-        trace!("raw_entries: {:#?}", raw_entries);
-
-        let entries = raw_entries.latest_entries();
-
-        trace!("entries: {:#?}", entries);
-
-        Ok(entries)
-    }
-
-    pub(crate) fn get_entry_by_uuid(&self, uuid: &Uuid) -> Result<Entry, Error> {
-        let metadata = self
-            .index
-            .metadata_most_recent()?
-            .into_iter()
-            .find(|entry| entry.uuid == *uuid)
-            .ok_or_else(|| format_err!("entry not found"))?;
-
-        let entry = self.get_entry_for_metadata(metadata)?;
-
-        Ok(entry)
+    pub(crate) fn entry(text: &str) -> Entry {
+        Entry {
+            text: text.to_string(),
+            metadata: Metadata::default(),
+        }
     }
 
-    pub(crate) fn get_entry_by_id(&self, entry_id: usize, project: &str) -> Result<Entry, Error> {
-        let entry = self
-            .get_active_entries(project)
-            .context("can not get project entries")?
-            .entry_by_id(entry_id)
-            .context("can not get entry by id")?;
-
-        Ok(entry)
+    #[test]
+    fn dry_run_import_leaves_datadir_unchanged() {
+        let directory = tempfile::tempdir().unwrap();
+        let datadir = directory.path().join("datadir");
+        let state_home = directory.path().join("state");
+        fs::create_dir_all(&datadir).unwrap();
+
+        let store = open(&datadir, &state_home, &Changes::default());
+        let existing = entry("existing entry");
+        store.add_entry(existing.clone()).unwrap();
+        store.set_focus(&existing).unwrap();
+
+        let before = (snapshot(&datadir), snapshot(&state_home));
+
+        let changes = Changes::dry_run();
+        let store = open(&datadir, &state_home, &changes);
+
+        let changed = Entry {
+            text: "changed on another machine".to_string(),
+            metadata: Metadata {
+                last_change: existing.metadata.last_change + Duration::minutes(1),
+                ..existing.metadata.clone()
+            },
+        };
+        let counts = store
+            .merge_entries(vec![entry("imported entry"), changed])
+            .unwrap();
+        store.add_entries(&[entry("imported from csv")]).unwrap();
+        store.run_cleanup(None, false).unwrap();
+
+        assert_eq!(counts.imported, 1);
+        assert_eq!(counts.merged, 1);
+        assert_eq!(before, (snapshot(&datadir), snapshot(&state_home)));
+
+        let plan = changes.take_plan();
+        assert!(plan
+            .iter()
+            .any(|change| change.starts_with("write entry text")));
+        assert!(plan
+            .iter()
+            .any(|change| change.starts_with("add metadata row")));
     }
 
-    pub(crate) fn get_projects_count(&self) -> Result<Vec<ProjectCount>, Error> {
-        let metadata = self.index.metadata_most_recent()?;
-
-        let mut count: HashMap<String, ProjectCount> = HashMap::default();
-
-        for entry in metadata {
-            let old_count = count
-                .entry(entry.project.clone())
-                .or_insert_with(ProjectCount::default);
-
-            let (active_count, done_count) = if entry.is_active() { (1, 0) } else { (0, 1) };
-
-            *old_count += ProjectCount {
-                project: entry.project,
-                active_count,
-                done_count,
-                total_count: 1,
-            }
-        }
-
-        trace!("count: {:#?}", count);
-
-        Ok(count.into_iter().map(|(_, count)| count).collect())
+    #[test]
+    fn dry_run_done_and_archive_leave_datadir_unchanged() {
+        let directory = tempfile::tempdir().unwrap();
+        let datadir = directory.path().join("datadir");
+        let state_home = directory.path().join("state");
+        fs::create_dir_all(&datadir).unwrap();
+
+        let store = open(&datadir, &state_home, &Changes::default());
+        let finished = entry("finished entry");
+        let open_entry = entry("open entry");
+        store.add_entry(finished.clone()).unwrap();
+        store.add_entry(open_entry.clone()).unwrap();
+        store
+            .entry_done_by_uuid(finished.metadata.uuid, false)
+            .unwrap();
+
+        let before = (snapshot(&datadir), snapshot(&state_home));
+
+        let changes = Changes::dry_run();
+        let store = open_with(
+            &datadir,
+            &state_home,
+            Config {
+                changes: changes.clone(),
+                backups: toml::from_str("auto_backup_before_destructive = true").unwrap(),
+                ..Config::default()
+            },
+        );
+
+        store
+            .entry_done_by_uuid(open_entry.metadata.uuid, false)
+            .unwrap();
+        let tomorrow = TimeContext::from_config(&store.config).today() + Duration::days(1);
+        let report = store.archive_done_before(tomorrow, true).unwrap();
+
+        assert!(report.dry_run);
+        assert_eq!(report.archived, vec![finished.metadata.uuid]);
+        assert_eq!(before, (snapshot(&datadir), snapshot(&state_home)));
+
+        let plan = changes.take_plan();
+        assert!(plan
+            .iter()
+            .any(|change| change.starts_with("back up the datadir")));
+        assert!(plan
+            .iter()
+            .any(|change| change.starts_with("add metadata row")));
     }
 
-    pub(crate) fn get_projects(&self) -> Result<Vec<String>, Error> {
-        let projects = self.index.projects().context("can not get projects")?;
+    #[test]
+    fn only_a_created_store_is_new() {
+        let directory = tempfile::tempdir().unwrap();
+        let datadir = directory.path().join("datadir");
+        let state_home = directory.path().join("state");
+        fs::create_dir_all(&datadir).unwrap();
 
-        trace!("projects: {:#?}", projects);
+        let store = open(&datadir, &state_home, &Changes::default());
+        assert_eq!(store.new_store(), Some(NewStore::NotCommitted));
 
-        Ok(projects)
+        let store = open(&datadir, &state_home, &Changes::default());
+        assert_eq!(store.new_store(), None);
     }
 
-    pub(crate) fn run_cleanup(&self) -> Result<(), Error> {
-        self.index.compact()?;
-        // TODO: This should remove index entries that dont have an entry file anymore.
-        // self.cleanup_stale_index_entries()?;
-        self.cleanup_unreferenced_entry()?;
+    #[test]
+    fn new_store_with_autocommit_is_a_repository() {
+        let directory = tempfile::tempdir().unwrap();
+        let datadir = directory.path().join("datadir");
+        fs::create_dir_all(&datadir).unwrap();
+
+        let open_committing = |datadir: &Path, changes: Changes| {
+            let config = Config {
+                vcs_config: toml::from_str("autocommit = true\nautopull = false\nautopush = false")
+                    .unwrap(),
+                changes: changes.clone(),
+                ..Config::default()
+            };
+            let state =
+                StateStore::with_state_home(directory.path().join("state"), datadir, changes);
+
+            Store::with_state(datadir, config, state).unwrap()
+        };
 
-        if let Some(vcs) = &self.settings.vcs {
-            vcs.commit(&self.datadir, "ran cleanup", &self.vcs_config)?;
-        }
+        let store = open_committing(&datadir, Changes::default());
+        assert_eq!(store.new_store(), Some(NewStore::RepositoryInitialized));
+        assert!(datadir.join(".git").is_dir());
 
-        Ok(())
+        let other = directory.path().join("other");
+        fs::create_dir_all(&other).unwrap();
+        let store = open_committing(&other, Changes::dry_run());
+        assert_eq!(store.new_store(), Some(NewStore::NotCommitted));
+        assert!(!other.join(".git").exists());
     }
 
-    pub(crate) fn update_entry(&self, entry: Entry) -> Result<(), Error> {
-        self.write_entry_text(&entry)
-            .context("can not write entry text to file")?;
-
-        let metadata = self.index.metadata_most_recent()?;
-
-        if !metadata.contains(&entry.metadata) {
-            self.index.metadata_add(&entry.metadata)?;
-        }
-
-        if let Some(vcs) = &self.settings.vcs {
-            let message = format!("updated entry with id {}", entry.metadata.uuid);
-            vcs.commit(&self.datadir, &message, &self.vcs_config)?;
-        }
-
-        Ok(())
+    #[test]
+    fn applied_changes_are_written() {
+        let directory = tempfile::tempdir().unwrap();
+        let datadir = directory.path().join("datadir");
+        let state_home = directory.path().join("state");
+        fs::create_dir_all(&datadir).unwrap();
+
+        let changes = Changes::default();
+        let store = open(&datadir, &state_home, &changes);
+        let added = entry("added entry");
+        store.add_entry(added.clone()).unwrap();
+
+        assert_eq!(
+            store.get_entry_by_uuid(&added.metadata.uuid).unwrap().text,
+            "added entry"
+        );
+        assert!(changes.take_plan().is_empty());
     }
-}
-
-#[derive(Debug, Serialize, Deserialize, Clone)]
-struct StoreSettings {
-    store_version: usize,
-    vcs: Option<VcsSettings>,
-}
 
-impl Default for StoreSettings {
-    fn default() -> Self {
-        Self {
-            store_version: 1,
-            vcs: Some(VcsSettings::default()),
+    #[test]
+    fn reads_after_changes_see_the_changes() {
+        for no_index_cache in [false, true] {
+            let (_directory, store) = temporary_with(Config {
+                no_index_cache,
+                ..Config::default()
+            });
+            let listed = entry("listed entry");
+            store.add_entry(listed.clone()).unwrap();
+
+            // Like a command that lists the entries before it changes one.
+            assert_eq!(store.get_active_entries("default").unwrap().len(), 1);
+            store
+                .entry_done_by_uuid(listed.metadata.uuid, false)
+                .unwrap();
+
+            assert_eq!(store.get_active_entries("default").unwrap().len(), 0);
+            assert_eq!(store.get_done_entries("default").unwrap().len(), 1);
+
+            // Clones like the one of the webservice share the cache.
+            let clone = store.clone();
+            clone.add_entry(entry("added by the clone")).unwrap();
+            assert_eq!(store.get_active_entries("default").unwrap().len(), 1);
         }
     }
 }
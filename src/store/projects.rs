@@ -0,0 +1,261 @@
+use crate::{
+    entry::{
+        Entry,
+        ProjectCount,
+    },
+    project_name::SimilarProjects,
+    stats::{
+        DUE_SOON_DAYS,
+        FORGOTTEN_DAYS,
+    },
+    store::Store,
+    time_context::TimeContext,
+};
+use anyhow::{
+    Context,
+    Error,
+};
+use chrono::Duration;
+use log::trace;
+use std::collections::HashMap;
+
+impl Store {
+    pub(crate) fn get_projects_count(
+        &self,
+        include_ignored: bool,
+        time: &TimeContext,
+    ) -> Result<Vec<ProjectCount>, Error> {
+        let metadata = self.index.metadata_most_recent()?;
+
+        let last_viewed = self.last_viewed();
+
+        let today = time.today();
+        let due_soon_until = today + Duration::days(DUE_SOON_DAYS);
+        let forgotten_since = time.now() - Duration::days(FORGOTTEN_DAYS);
+
+        let mut count: HashMap<String, ProjectCount> = HashMap::default();
+
+        for entry in metadata
+            .into_iter()
+            .filter(|entry| include_ignored || !self.is_ignored_project(&entry.project))
+        {
+            let old_count = count.entry(entry.project.clone()).or_default();
+
+            let (active_count, done_count) = if entry.is_active() { (1, 0) } else { (0, 1) };
+
+            let (overdue_count, due_soon_count) = match entry.due {
+                Some(due) if entry.is_active() && due < today => (1, 0),
+                Some(due) if entry.is_active() && due <= due_soon_until => (0, 1),
+                _ => (0, 0),
+            };
+
+            let forgotten_count = usize::from(
+                entry.is_active()
+                    && entry.last_seen(last_viewed.get(&entry.uuid).copied()) < forgotten_since,
+            );
+
+            let (on_time_count, late_count) = match entry.finished_on_time(time) {
+                Some(true) => (1, 0),
+                Some(false) => (0, 1),
+                None => (0, 0),
+            };
+
+            *old_count += ProjectCount {
+                project: entry.project,
+                active_count,
+                done_count,
+                total_count: 1,
+                overdue_count,
+                due_soon_count,
+                forgotten_count,
+                last_activity: Some(entry.last_change),
+                on_time_count,
+                late_count,
+            }
+        }
+
+        trace!("count: {:#?}", count);
+
+        Ok(count.into_values().collect())
+    }
+
+    /// Check if the project should be hidden from views spanning multiple
+    /// projects. The ignored projects from the store settings take precedence
+    /// over the ones from the config.
+    pub(crate) fn is_ignored_project(&self, project: &str) -> bool {
+        self.settings
+            .ignored_projects
+            .as_ref()
+            .unwrap_or(&self.config.ignored_projects)
+            .iter()
+            .any(|ignored| ignored == project)
+    }
+
+    pub(crate) fn get_projects(&self) -> Result<Vec<String>, Error> {
+        let projects = self.index.projects().context("can not get projects")?;
+
+        trace!("projects: {:#?}", projects);
+
+        Ok(projects)
+    }
+
+    /// Move the entries of all projects of the group into the canonical
+    /// project. Returns how many entries were moved.
+    pub(crate) fn merge_projects(&self, similar: &SimilarProjects) -> Result<usize, Error> {
+        let losers = similar
+            .projects
+            .iter()
+            .filter(|project| *project != &similar.canonical)
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let entries = self
+            .get_entries_of_projects(&losers)?
+            .into_iter()
+            .map(|entry| {
+                Ok(Entry {
+                    metadata: entry
+                        .metadata
+                        .changed()
+                        .project(similar.canonical.clone())
+                        .build()?,
+                    ..entry
+                })
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        self.update_entries_as(entries, "moved")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::Config,
+        project_name,
+        store::tests::{
+            entry,
+            temporary_with,
+        },
+        time_context::TimeContext,
+    };
+
+    fn ignoring(projects: &[&str]) -> Config {
+        Config {
+            ignored_projects: projects.iter().map(ToString::to_string).collect(),
+            ..Config::default()
+        }
+    }
+
+    fn add(store: &crate::store::Store, project: &str) {
+        let mut added = entry(project);
+        added.metadata.project = project.to_string();
+        store.add_entry(added).unwrap();
+    }
+
+    fn counted_projects(store: &crate::store::Store, include_ignored: bool) -> Vec<String> {
+        let mut projects = store
+            .get_projects_count(include_ignored, &TimeContext::new(None))
+            .unwrap()
+            .into_iter()
+            .map(|count| count.project)
+            .collect::<Vec<_>>();
+        projects.sort();
+
+        projects
+    }
+
+    #[test]
+    fn ignored_projects_are_hidden_from_global_views() {
+        let (_directory, store) = temporary_with(ignoring(&["someday"]));
+        add(&store, "work");
+        add(&store, "someday");
+
+        assert_eq!(counted_projects(&store, false), vec!["work"]);
+        assert_eq!(counted_projects(&store, true), vec!["someday", "work"]);
+
+        assert_eq!(store.get_all_entries(false).unwrap().len(), 1);
+        assert_eq!(store.get_all_entries(true).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn explicit_queries_still_get_ignored_projects() {
+        let (_directory, store) = temporary_with(ignoring(&["someday"]));
+        add(&store, "someday");
+
+        assert_eq!(store.get_active_entries("someday").unwrap().len(), 1);
+        assert_eq!(
+            store
+                .get_entries_of_projects(&["someday".to_string()])
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(store.get_projects().unwrap(), vec!["someday"]);
+    }
+
+    #[test]
+    fn store_settings_take_precedence_over_config() {
+        let (_directory, mut store) = temporary_with(ignoring(&["someday"]));
+        assert!(store.is_ignored_project("someday"));
+
+        store.settings.ignored_projects = Some(vec!["archive".to_string()]);
+
+        assert!(!store.is_ignored_project("someday"));
+        assert!(store.is_ignored_project("archive"));
+    }
+
+    #[test]
+    fn counts_finished_on_time_and_late() {
+        let (_directory, store) = temporary_with(Config::default());
+        for (due, finished) in &[
+            (Some((2024, 6, 1)), "2024-06-01T12:00:00Z"),
+            (Some((2024, 6, 1)), "2024-06-03T12:00:00Z"),
+            (None, "2024-06-03T12:00:00Z"),
+        ] {
+            let mut done = entry("done");
+            done.metadata.due = due
+                .and_then(|(year, month, day)| chrono::NaiveDate::from_ymd_opt(year, month, day));
+            done.metadata.finished = Some(finished.parse().unwrap());
+            store.add_entry(done).unwrap();
+        }
+
+        let time = TimeContext::new(Some(chrono_tz::Tz::UTC));
+        let count = store.get_projects_count(true, &time).unwrap().remove(0);
+
+        assert_eq!((count.on_time_count, count.late_count), (1, 1));
+        assert_eq!(count.on_time_ratio(), Some(0.5));
+    }
+
+    #[test]
+    fn similar_projects_are_merged_into_the_canonical_name() {
+        let (_directory, store) = temporary_with(Config::default());
+        for project in &[
+            "work",
+            "work ",
+            "work\u{a0}",
+            "d\u{e9}fault",
+            "de\u{301}fault",
+            "home",
+        ] {
+            add(&store, project);
+        }
+
+        let similar = project_name::similar_projects(store.get_projects().unwrap());
+        assert_eq!(similar.len(), 2);
+
+        for similar in &similar {
+            let moved = store.merge_projects(similar).unwrap();
+            assert_eq!(moved, similar.projects.len() - 1);
+        }
+
+        let losers = ["work ", "work\u{a0}", "de\u{301}fault"]
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        assert_eq!(store.get_entries_of_projects(&losers).unwrap().len(), 0);
+        assert_eq!(store.get_active_entries("home").unwrap().len(), 1);
+        assert_eq!(store.get_active_entries("work").unwrap().len(), 3);
+        assert_eq!(store.get_active_entries("d\u{e9}fault").unwrap().len(), 2);
+    }
+}
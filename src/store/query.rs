@@ -0,0 +1,422 @@
+use crate::{
+    entry::{
+        Entries,
+        EntriesSummary,
+        Entry,
+        Metadata,
+    },
+    store::Store,
+    time_context::TimeContext,
+};
+use anyhow::{
+    bail,
+    format_err,
+    Context,
+    Error,
+};
+use chrono::{
+    DateTime,
+    Utc,
+};
+use log::trace;
+use std::collections::{
+    BTreeMap,
+    BTreeSet,
+};
+use uuid::Uuid;
+
+impl Store {
+    /// Whether an entry with the uuid is in the store.
+    pub(crate) fn has_entry(&self, uuid: &Uuid) -> Result<bool, Error> {
+        Ok(self
+            .index
+            .metadata_most_recent()
+            .context("can not get metadata from active index")?
+            .iter()
+            .any(|metadata| metadata.uuid == *uuid))
+    }
+
+    pub(crate) fn get_active_entries(&self, project: &str) -> Result<Entries, Error> {
+        let entries = self
+            .get_entries(project)?
+            .into_iter()
+            .filter(Entry::is_active)
+            .collect();
+
+        trace!("entries: {:#?}", entries);
+
+        Ok(entries)
+    }
+
+    pub(crate) fn get_done_entries(&self, project: &str) -> Result<Entries, Error> {
+        let entries = self
+            .get_entries(project)?
+            .into_iter()
+            .filter(Entry::is_done)
+            .collect();
+
+        trace!("entries: {:#?}", entries);
+
+        Ok(entries)
+    }
+
+    pub(crate) fn get_entries(&self, project: &str) -> Result<Entries, Error> {
+        self.get_entries_filtered(|metadata| metadata.project == project)
+    }
+
+    /// Get the entries of all the given projects.
+    pub(crate) fn get_entries_of_projects(&self, projects: &[String]) -> Result<Entries, Error> {
+        self.get_entries_filtered(|metadata| projects.contains(&metadata.project))
+    }
+
+    /// Get the entries of all projects. Entries of ignored projects are only
+    /// included when `include_ignored` is set.
+    pub(crate) fn get_all_entries(&self, include_ignored: bool) -> Result<Entries, Error> {
+        self.get_entries_filtered(|metadata| {
+            include_ignored || !self.is_ignored_project(&metadata.project)
+        })
+    }
+
+    /// Active entries of the project with their text cut off after the
+    /// configured preview length, for views that show many entries at once.
+    pub(crate) fn get_active_entry_previews(&self, project: &str) -> Result<Entries, Error> {
+        Ok(self
+            .get_entries_filtered_with(|metadata| metadata.project == project, true)?
+            .into_iter()
+            .filter(Entry::is_active)
+            .collect())
+    }
+
+    /// Done entries of the project with their text cut off after the
+    /// configured preview length.
+    pub(crate) fn get_done_entry_previews(&self, project: &str) -> Result<Entries, Error> {
+        Ok(self
+            .get_entries_filtered_with(|metadata| metadata.project == project, true)?
+            .into_iter()
+            .filter(Entry::is_done)
+            .collect())
+    }
+
+    /// Entries of all the given projects with their text cut off after the
+    /// configured preview length.
+    pub(crate) fn get_entry_previews_of_projects(
+        &self,
+        projects: &[String],
+    ) -> Result<Entries, Error> {
+        self.get_entries_filtered_with(|metadata| projects.contains(&metadata.project), true)
+    }
+
+    /// Get the entries of all projects that were changed after the given
+    /// time or all entries without a time with their text cut off after the
+    /// configured preview length. Only the text of the changed entries is
+    /// read.
+    pub(crate) fn get_entry_previews_changed_since(
+        &self,
+        since: Option<DateTime<Utc>>,
+    ) -> Result<Entries, Error> {
+        self.get_entries_filtered_with(
+            |metadata| since.is_none_or(|since| metadata.last_change > since),
+            true,
+        )
+    }
+
+    /// Metadata of the entries that were moved to another store or deleted
+    /// after `since`. Their text is gone.
+    pub(crate) fn get_removed_since(&self, since: DateTime<Utc>) -> Result<Vec<Metadata>, Error> {
+        self.index
+            .metadata_removed_since(since)
+            .context("can not get removed metadata from index")
+    }
+
+    fn get_entries_filtered<F>(&self, filter: F) -> Result<Entries, Error>
+    where
+        F: Fn(&Metadata) -> bool,
+    {
+        self.get_entries_filtered_with(filter, false)
+    }
+
+    fn get_entries_filtered_with<F>(&self, filter: F, preview: bool) -> Result<Entries, Error>
+    where
+        F: Fn(&Metadata) -> bool,
+    {
+        let preview = Some(self.config.entry_preview_bytes).filter(|_| preview);
+
+        let metadata_entries = self
+            .index
+            .metadata_most_recent()
+            .context("can not get metadata from active index")?;
+
+        let raw_entries: Entries = metadata_entries
+            .into_iter()
+            .filter(|metadata| filter(metadata))
+            .map(|metadata| self.read_entry(metadata, preview))
+            .collect::<Result<BTreeSet<Entry>, Error>>()
+            .context("can not get entry for metadata")?
+            .into();
+
+        trace!("raw_entries: {:#?}", raw_entries);
+
+        let entries = raw_entries.latest_entries();
+
+        trace!("entries: {:#?}", entries);
+
+        Ok(entries)
+    }
+
+    pub(crate) fn get_entry_by_uuid(&self, uuid: &Uuid) -> Result<Entry, Error> {
+        self.find_entry_by_uuid(uuid)?
+            .ok_or_else(|| format_err!("entry not found"))
+    }
+
+    /// Get the entry with the uuid if the index has it. Errors are only
+    /// returned if the index or the entry file can not be read.
+    pub(crate) fn find_entry_by_uuid(&self, uuid: &Uuid) -> Result<Option<Entry>, Error> {
+        let metadata = self
+            .index
+            .metadata_most_recent()?
+            .into_iter()
+            .find(|entry| entry.uuid == *uuid);
+
+        metadata
+            .map(|metadata| self.get_entry_for_metadata(metadata))
+            .transpose()
+    }
+
+    /// Get the active entry of the project that was created with the given
+    /// dedup key if there is one.
+    pub(crate) fn get_active_entry_by_dedup_key(
+        &self,
+        project: &str,
+        dedup_key: &str,
+    ) -> Result<Option<Entry>, Error> {
+        let entry = self
+            .get_active_entries(project)
+            .context("can not get project entries")?
+            .into_iter()
+            .find(|entry| entry.metadata.dedup_key.as_deref() == Some(dedup_key));
+
+        Ok(entry)
+    }
+
+    pub(crate) fn get_entry_by_id(&self, entry_id: usize, project: &str) -> Result<Entry, Error> {
+        let entry = self
+            .get_active_entries(project)
+            .context("can not get project entries")?
+            .entry_by_id(entry_id)
+            .context("can not get entry by id")?;
+
+        Ok(entry)
+    }
+
+    /// Resolve the ids against a single snapshot of the active entries of the
+    /// project so the ids can not shift while entries are changed. Ids that
+    /// are given multiple times are only resolved once. Fails without
+    /// resolving anything if any of the ids is invalid.
+    pub(crate) fn get_entries_by_ids(
+        &self,
+        entry_ids: &[usize],
+        project: &str,
+    ) -> Result<Vec<(usize, Entry)>, Error> {
+        let active = self
+            .get_active_entries(project)
+            .context("can not get project entries")?
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        Store::resolve_ids(active, entry_ids, project, "active")
+    }
+
+    /// Resolve the ids against the done entries of the project in the order
+    /// `list --done` shows them, the most recently finished first. Works like
+    /// `get_entries_by_ids` otherwise.
+    pub(crate) fn get_done_entries_by_ids(
+        &self,
+        entry_ids: &[usize],
+        project: &str,
+    ) -> Result<Vec<(usize, Entry)>, Error> {
+        let done = self
+            .get_done_entries(project)
+            .context("can not get project entries")?
+            .get_done_by_finished();
+
+        Store::resolve_ids(done, entry_ids, project, "done")
+    }
+
+    /// Pick the entries with the ids from the list. Ids start at 1.
+    fn resolve_ids(
+        entries: Vec<Entry>,
+        entry_ids: &[usize],
+        project: &str,
+        state: &str,
+    ) -> Result<Vec<(usize, Entry)>, Error> {
+        let mut unique_ids = Vec::new();
+        for id in entry_ids {
+            if !unique_ids.contains(id) {
+                unique_ids.push(*id);
+            }
+        }
+
+        let invalid = unique_ids
+            .iter()
+            .filter(|id| **id < 1 || **id > entries.len())
+            .map(|id| id.to_string())
+            .collect::<Vec<_>>();
+
+        if !invalid.is_empty() {
+            bail!(
+                "no {} entries found with ids {} in project {}",
+                state,
+                invalid.join(", "),
+                project
+            )
+        }
+
+        Ok(unique_ids
+            .into_iter()
+            .map(|id| (id, entries[id - 1].clone()))
+            .collect())
+    }
+
+    /// Most recent metadata of every entry. Only reads the index.
+    pub(crate) fn get_all_metadata(&self) -> Result<BTreeSet<Metadata>, Error> {
+        Ok(self.index.metadata_most_recent()?)
+    }
+
+    /// Summary of the entries of every project. Only reads the index.
+    pub(crate) fn get_summaries(
+        &self,
+        time: &TimeContext,
+    ) -> Result<BTreeMap<String, EntriesSummary>, Error> {
+        let metadata = self.index.metadata_most_recent()?;
+
+        let mut by_project: BTreeMap<String, Vec<Metadata>> = BTreeMap::default();
+        for metadata in metadata {
+            by_project
+                .entry(metadata.project.clone())
+                .or_default()
+                .push(metadata);
+        }
+
+        let today = time.today();
+
+        Ok(by_project
+            .into_iter()
+            .map(|(project, metadata)| (project, EntriesSummary::from_metadata(&metadata, today)))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::store::tests::{
+        entry,
+        temporary,
+    };
+    use uuid::Uuid;
+
+    #[test]
+    fn entry_by_uuid_of_any_project() {
+        let (_directory, store) = temporary();
+        let mut added = entry("entry in another project");
+        added.metadata.project = "other".to_string();
+        store.add_entry(added.clone()).unwrap();
+
+        let found = store.get_entry_by_uuid(&added.metadata.uuid).unwrap();
+
+        assert_eq!(found.text, "entry in another project");
+        assert_eq!(found.metadata.project, "other");
+    }
+
+    #[test]
+    fn unknown_uuid_is_not_found() {
+        let (_directory, store) = temporary();
+        store.add_entry(entry("some entry")).unwrap();
+        let unknown = Uuid::new_v4();
+
+        assert!(store.find_entry_by_uuid(&unknown).unwrap().is_none());
+
+        let err = store.get_entry_by_uuid(&unknown).unwrap_err();
+        assert_eq!(err.to_string(), "entry not found");
+    }
+
+    #[test]
+    fn overlapping_ids_are_resolved_once_in_order() {
+        let (_directory, store) = temporary();
+        for text in &["first", "second", "third"] {
+            store.add_entry(entry(text)).unwrap();
+        }
+        let active = store
+            .get_active_entries("default")
+            .unwrap()
+            .into_iter()
+            .collect::<Vec<_>>();
+
+        let resolved = store.get_entries_by_ids(&[3, 1, 3, 1], "default").unwrap();
+
+        assert_eq!(
+            resolved
+                .iter()
+                .map(|(id, entry)| (*id, entry.metadata.uuid))
+                .collect::<Vec<_>>(),
+            vec![(3, active[2].metadata.uuid), (1, active[0].metadata.uuid),]
+        );
+    }
+
+    #[test]
+    fn invalid_id_aborts_the_batch() {
+        let (_directory, store) = temporary();
+        store.add_entry(entry("first")).unwrap();
+        store.add_entry(entry("second")).unwrap();
+
+        let err = store
+            .get_entries_by_ids(&[1, 5, 0, 2], "default")
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "no active entries found with ids 5, 0 in project default"
+        );
+
+        let err = store.get_done_entries_by_ids(&[1], "default").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "no done entries found with ids 1 in project default"
+        );
+    }
+
+    #[test]
+    fn id_of_an_added_entry_resolves_back_to_it() {
+        let (_directory, store) = temporary();
+
+        // Entries are added in another order than they are listed in and a
+        // done entry is listed in between.
+        let mut added = Vec::new();
+        for (text, days_ago, done) in &[
+            ("new", 1, false),
+            ("old", 30, false),
+            ("done", 10, true),
+            ("middle", 5, false),
+        ] {
+            let mut new = entry(text);
+            new.metadata.started = chrono::Utc::now() - chrono::Duration::days(*days_ago);
+            if *done {
+                new.metadata.finished = Some(chrono::Utc::now());
+            }
+
+            store.add_entry(new.clone()).unwrap();
+            added.push(new);
+        }
+
+        let active = store.get_active_entries("default").unwrap();
+        assert_eq!(active.id_of(&added[2].metadata.uuid), None);
+
+        for new in added.iter().filter(|new| new.metadata.finished.is_none()) {
+            let id = active.id_of(&new.metadata.uuid).unwrap();
+
+            let found = store.get_entry_by_id(id, "default").unwrap();
+            assert_eq!(found.metadata.uuid, new.metadata.uuid, "{}", new.text);
+
+            let found = store.get_entries_by_ids(&[id], "default").unwrap();
+            assert_eq!(found[0].1.metadata.uuid, new.metadata.uuid, "{}", new.text);
+        }
+    }
+}
@@ -0,0 +1,394 @@
+use crate::entry::Metadata;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::{
+        BTreeMap,
+        BTreeSet,
+    },
+    fmt,
+};
+use uuid::Uuid;
+
+/// Limits for stores that are shared through the webservice so one user can
+/// not fill up the disk. Active and done entries count. Every limit is off
+/// unless it is set.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct Quotas {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) max_projects: Option<usize>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) max_entries_per_project: Option<usize>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(super) max_total_entries: Option<usize>,
+}
+
+/// How much of a quota is used. For the quota of entries per project this is
+/// the project with the most entries.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct QuotaUsage {
+    /// Name of the quota in the config.
+    pub(crate) quota: &'static str,
+
+    pub(crate) limit: usize,
+    pub(crate) count: usize,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub(crate) project: Option<String>,
+}
+
+/// Error for a change that would exceed a quota. `count` is how many there
+/// would be after the change.
+#[derive(Debug, Clone, Serialize)]
+#[serde(transparent)]
+pub(crate) struct QuotaExceeded(pub(crate) QuotaUsage);
+
+impl fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let usage = &self.0;
+
+        match (usage.quota, &usage.project) {
+            ("max_projects", _) => write!(
+                f,
+                "can not create another project as the store is limited to {} projects",
+                usage.limit
+            ),
+            (_, Some(project)) => write!(
+                f,
+                "can not add more entries to project {:?} as it is limited to {} entries",
+                project, usage.limit
+            ),
+            _ => write!(
+                f,
+                "can not add more entries as the store is limited to {} entries",
+                usage.limit
+            ),
+        }
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
+
+/// Entry counts of the store per project.
+#[derive(Debug, Default)]
+struct Counts {
+    total: usize,
+    projects: BTreeMap<String, usize>,
+}
+
+impl Counts {
+    fn of<'a>(metadata: impl Iterator<Item = &'a Metadata>) -> Self {
+        let mut counts = Self::default();
+
+        for metadata in metadata {
+            counts.total += 1;
+            *counts.projects.entry(metadata.project.clone()).or_default() += 1;
+        }
+
+        counts
+    }
+
+    fn project(&self, project: &str) -> usize {
+        self.projects.get(project).copied().unwrap_or_default()
+    }
+}
+
+impl Quotas {
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.max_projects.is_some()
+            || self.max_entries_per_project.is_some()
+            || self.max_total_entries.is_some()
+    }
+
+    /// Fail if writing the changed metadata would raise a count over its
+    /// limit. `changes` replaces the current metadata with the same uuid and
+    /// adds the others. Counts that are already over a limit but do not grow
+    /// are accepted so entries can still be edited and finished after a limit
+    /// was lowered.
+    pub(super) fn check(
+        &self,
+        current: &BTreeSet<Metadata>,
+        changes: &[&Metadata],
+    ) -> Result<(), QuotaExceeded> {
+        if !self.is_enabled() {
+            return Ok(());
+        }
+
+        let changed = changes
+            .iter()
+            .map(|metadata| metadata.uuid)
+            .collect::<BTreeSet<Uuid>>();
+
+        let before = Counts::of(current.iter());
+        let after = Counts::of(
+            current
+                .iter()
+                .filter(|metadata| !changed.contains(&metadata.uuid))
+                .chain(changes.iter().copied()),
+        );
+
+        let exceeded = |quota, limit, count, project: Option<&str>| {
+            QuotaExceeded(QuotaUsage {
+                quota,
+                limit,
+                count,
+                project: project.map(str::to_string),
+            })
+        };
+
+        if let Some(limit) = self.max_total_entries {
+            if after.total > limit && after.total > before.total {
+                return Err(exceeded("max_total_entries", limit, after.total, None));
+            }
+        }
+
+        if let Some(limit) = self.max_projects {
+            let count = after.projects.len();
+            if count > limit && count > before.projects.len() {
+                return Err(exceeded("max_projects", limit, count, None));
+            }
+        }
+
+        if let Some(limit) = self.max_entries_per_project {
+            for (project, count) in &after.projects {
+                if *count > limit && *count > before.project(project) {
+                    return Err(exceeded(
+                        "max_entries_per_project",
+                        limit,
+                        *count,
+                        Some(project),
+                    ));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Current usage of every quota that is set.
+    pub(super) fn usage(&self, current: &BTreeSet<Metadata>) -> Vec<QuotaUsage> {
+        let counts = Counts::of(current.iter());
+        let mut usage = Vec::new();
+
+        if let Some(limit) = self.max_projects {
+            usage.push(QuotaUsage {
+                quota: "max_projects",
+                limit,
+                count: counts.projects.len(),
+                project: None,
+            });
+        }
+
+        if let Some(limit) = self.max_entries_per_project {
+            let fullest = counts
+                .projects
+                .iter()
+                .max_by_key(|(_, count)| **count)
+                .map(|(project, count)| (Some(project.clone()), *count));
+            let (project, count) = fullest.unwrap_or((None, 0));
+
+            usage.push(QuotaUsage {
+                quota: "max_entries_per_project",
+                limit,
+                count,
+                project,
+            });
+        }
+
+        if let Some(limit) = self.max_total_entries {
+            usage.push(QuotaUsage {
+                quota: "max_total_entries",
+                limit,
+                count: counts.total,
+                project: None,
+            });
+        }
+
+        usage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata(project: &str) -> Metadata {
+        Metadata {
+            project: project.to_string(),
+            ..Metadata::default()
+        }
+    }
+
+    fn current(projects: &[&str]) -> BTreeSet<Metadata> {
+        projects.iter().map(|project| metadata(project)).collect()
+    }
+
+    fn quotas(toml: &str) -> Quotas {
+        toml::from_str(toml).unwrap()
+    }
+
+    fn exceeded(result: Result<(), QuotaExceeded>) -> QuotaUsage {
+        result.unwrap_err().0
+    }
+
+    #[test]
+    fn quotas_are_off_by_default() {
+        let quotas = Quotas::default();
+        assert!(!quotas.is_enabled());
+
+        let current = current(&["a", "b", "c"]);
+        assert!(quotas.check(&current, &[&metadata("d")]).is_ok());
+        assert!(quotas.usage(&current).is_empty());
+    }
+
+    #[test]
+    fn total_entries_are_limited() {
+        let quotas = quotas("max_total_entries = 2");
+        let full = current(&["a", "b"]);
+
+        assert!(quotas.check(&current(&["a"]), &[&metadata("a")]).is_ok());
+        assert_eq!(
+            exceeded(quotas.check(&full, &[&metadata("a")])),
+            QuotaUsage {
+                quota: "max_total_entries",
+                limit: 2,
+                count: 3,
+                project: None,
+            }
+        );
+    }
+
+    #[test]
+    fn projects_are_limited() {
+        let quotas = quotas("max_projects = 2");
+        let full = current(&["a", "b"]);
+
+        assert!(quotas.check(&full, &[&metadata("b")]).is_ok());
+        assert_eq!(
+            exceeded(quotas.check(&full, &[&metadata("c")])),
+            QuotaUsage {
+                quota: "max_projects",
+                limit: 2,
+                count: 3,
+                project: None,
+            }
+        );
+    }
+
+    #[test]
+    fn entries_per_project_are_limited() {
+        let quotas = quotas("max_entries_per_project = 2");
+        let full = current(&["a", "a", "b"]);
+
+        assert!(quotas.check(&full, &[&metadata("b")]).is_ok());
+        assert_eq!(
+            exceeded(quotas.check(&full, &[&metadata("a")])),
+            QuotaUsage {
+                quota: "max_entries_per_project",
+                limit: 2,
+                count: 3,
+                project: Some("a".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn moving_into_a_full_project_is_refused() {
+        let quotas = quotas("max_entries_per_project = 2");
+        let full = current(&["a", "a", "b"]);
+        let entry = full
+            .iter()
+            .find(|metadata| metadata.project == "b")
+            .unwrap();
+
+        let moved = Metadata {
+            project: "a".to_string(),
+            ..entry.clone()
+        };
+        let usage = exceeded(quotas.check(&full, &[&moved]));
+        assert_eq!(usage.project.as_deref(), Some("a"));
+        assert_eq!(usage.count, 3);
+
+        let moved = Metadata {
+            project: "c".to_string(),
+            ..entry.clone()
+        };
+        assert!(quotas.check(&full, &[&moved]).is_ok());
+    }
+
+    #[test]
+    fn changes_that_do_not_grow_a_count_over_a_lowered_limit_are_accepted() {
+        let quotas = quotas("max_projects = 1\nmax_entries_per_project = 1\nmax_total_entries = 1");
+        let over = current(&["a", "a", "b"]);
+        let entry = over.iter().next().unwrap();
+
+        let edited = Metadata {
+            revision: entry.revision + 1,
+            ..entry.clone()
+        };
+        assert!(quotas.check(&over, &[&edited]).is_ok());
+        assert!(quotas.check(&over, &[&metadata("a")]).is_err());
+    }
+
+    #[test]
+    fn usage_reports_the_fullest_project() {
+        let quotas =
+            quotas("max_projects = 5\nmax_entries_per_project = 3\nmax_total_entries = 10");
+
+        assert_eq!(
+            quotas.usage(&current(&["a", "b", "b"])),
+            vec![
+                QuotaUsage {
+                    quota: "max_projects",
+                    limit: 5,
+                    count: 2,
+                    project: None,
+                },
+                QuotaUsage {
+                    quota: "max_entries_per_project",
+                    limit: 3,
+                    count: 2,
+                    project: Some("b".to_string()),
+                },
+                QuotaUsage {
+                    quota: "max_total_entries",
+                    limit: 10,
+                    count: 3,
+                    project: None,
+                },
+            ]
+        );
+        assert_eq!(quotas.usage(&BTreeSet::new())[1].project, None);
+    }
+
+    #[test]
+    fn messages_name_the_limit() {
+        let message = |quota, project: Option<&str>| {
+            QuotaExceeded(QuotaUsage {
+                quota,
+                limit: 2,
+                count: 3,
+                project: project.map(str::to_string),
+            })
+            .to_string()
+        };
+
+        assert_eq!(
+            message("max_projects", None),
+            "can not create another project as the store is limited to 2 projects"
+        );
+        assert_eq!(
+            message("max_entries_per_project", Some("work")),
+            "can not add more entries to project \"work\" as it is limited to 2 entries"
+        );
+        assert_eq!(
+            message("max_total_entries", None),
+            "can not add more entries as the store is limited to 2 entries"
+        );
+    }
+}
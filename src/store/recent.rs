@@ -0,0 +1,286 @@
+use crate::store::{
+    changes::Changes,
+    state::StateStore,
+};
+use anyhow::Error;
+use chrono::{
+    DateTime,
+    Duration,
+    Utc,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::BTreeMap,
+    path::Path,
+};
+
+/// Name of the state with the recently used projects and the projects of the
+/// terminal sessions.
+const RECENT_STATE_NAME: &str = "recent_projects";
+
+/// How many projects are remembered.
+const MAX_RECENT_PROJECTS: usize = 10;
+
+/// Sessions that did not run a command for this long are forgotten as the
+/// operating system reuses session ids.
+const SESSION_EXPIRY_HOURS: i64 = 24;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct RecentProject {
+    pub(crate) project: String,
+    pub(crate) last_used: DateTime<Utc>,
+}
+
+/// Project picked with `todust switch` in one terminal session.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct SessionProject {
+    project: String,
+    last_used: DateTime<Utc>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct RecentState {
+    /// Most recently used first.
+    #[serde(default)]
+    projects: Vec<RecentProject>,
+
+    /// Project of each session by session id.
+    #[serde(default)]
+    sessions: BTreeMap<String, SessionProject>,
+}
+
+impl RecentState {
+    /// Move the projects to the front of the recent projects. The first one
+    /// ends up first. The project of the session counts as used.
+    fn record(&mut self, projects: &[String], session: Option<&str>, now: DateTime<Utc>) {
+        for project in projects.iter().rev() {
+            self.projects.retain(|recent| &recent.project != project);
+            self.projects.insert(
+                0,
+                RecentProject {
+                    project: project.clone(),
+                    last_used: now,
+                },
+            );
+        }
+        self.projects.truncate(MAX_RECENT_PROJECTS);
+
+        if let Some(session) = session.and_then(|session| self.sessions.get_mut(session)) {
+            session.last_used = now;
+        }
+
+        self.expire_sessions(now);
+    }
+
+    fn set_session(&mut self, session: &str, project: Option<&str>, now: DateTime<Utc>) {
+        match project {
+            Some(project) => {
+                self.sessions.insert(
+                    session.to_string(),
+                    SessionProject {
+                        project: project.to_string(),
+                        last_used: now,
+                    },
+                );
+            }
+            None => {
+                self.sessions.remove(session);
+            }
+        }
+
+        self.expire_sessions(now);
+    }
+
+    fn session_project(&self, session: &str, now: DateTime<Utc>) -> Option<&str> {
+        self.sessions
+            .get(session)
+            .filter(|session| !is_expired(session, now))
+            .map(|session| session.project.as_str())
+    }
+
+    fn expire_sessions(&mut self, now: DateTime<Utc>) {
+        self.sessions.retain(|_, session| !is_expired(session, now));
+    }
+}
+
+fn is_expired(session: &SessionProject, now: DateTime<Utc>) -> bool {
+    now - session.last_used > Duration::hours(SESSION_EXPIRY_HOURS)
+}
+
+/// Projects recently used on this machine and the project each terminal
+/// session switched to. Kept in the state store of the datadir.
+#[derive(Debug, Clone)]
+pub(crate) struct RecentProjects {
+    state: StateStore,
+}
+
+impl RecentProjects {
+    /// Recent projects of the datadir. Can be used before the store is opened
+    /// as the project of the session is needed to parse the command line.
+    pub(crate) fn open<P: AsRef<Path>>(datadir: P, changes: Changes) -> Result<Self, Error> {
+        Ok(Self {
+            state: StateStore::open(datadir, changes)?,
+        })
+    }
+
+    fn load(&self) -> RecentState {
+        self.state.load(RECENT_STATE_NAME)
+    }
+
+    /// Recently used projects, the most recent first.
+    pub(crate) fn list(&self) -> Vec<RecentProject> {
+        self.load().projects
+    }
+
+    /// Remember that the command used the projects. Concurrent commands can
+    /// overwrite each others update which only loses a recent project.
+    pub(crate) fn record(&self, projects: &[String], session: Option<&str>) -> Result<(), Error> {
+        let mut state = self.load();
+        state.record(projects, session, Utc::now());
+
+        self.state.save(RECENT_STATE_NAME, &state)
+    }
+
+    /// Project the session switched to if it did not expire.
+    pub(crate) fn session_project(&self, session: &str) -> Option<String> {
+        self.load()
+            .session_project(session, Utc::now())
+            .map(str::to_string)
+    }
+
+    /// Make the project the default of the session. `None` goes back to the
+    /// built-in default.
+    pub(crate) fn switch(&self, session: &str, project: Option<&str>) -> Result<(), Error> {
+        let mut state = self.load();
+        state.set_session(session, project, Utc::now());
+
+        self.state.save(RECENT_STATE_NAME, &state)
+    }
+}
+
+/// Id of the terminal session todust runs in. Every command started from the
+/// same shell has the same session.
+#[cfg(unix)]
+pub(crate) fn current_session() -> Option<String> {
+    // SAFETY: getsid only reads the session id of the calling process.
+    let session = unsafe { libc::getsid(0) };
+
+    if session < 0 {
+        None
+    } else {
+        Some(session.to_string())
+    }
+}
+
+/// Sessions are only supported on unix.
+#[cfg(not(unix))]
+pub(crate) fn current_session() -> Option<String> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn projects(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    fn recent(state: &RecentState) -> Vec<&str> {
+        state
+            .projects
+            .iter()
+            .map(|recent| recent.project.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn most_recently_used_project_is_first() {
+        let mut state = RecentState::default();
+        let now = Utc::now();
+
+        state.record(&projects(&["a"]), None, now);
+        state.record(&projects(&["b"]), None, now);
+        state.record(&projects(&["c", "d"]), None, now);
+        assert_eq!(recent(&state), vec!["c", "d", "b", "a"]);
+
+        state.record(&projects(&["a"]), None, now + Duration::minutes(1));
+        assert_eq!(recent(&state), vec!["a", "c", "d", "b"]);
+        assert_eq!(state.projects[0].last_used, now + Duration::minutes(1));
+    }
+
+    #[test]
+    fn only_the_latest_projects_are_kept() {
+        let mut state = RecentState::default();
+
+        for index in 0..MAX_RECENT_PROJECTS + 2 {
+            state.record(&[index.to_string()], None, Utc::now());
+        }
+
+        assert_eq!(state.projects.len(), MAX_RECENT_PROJECTS);
+        assert_eq!(
+            state.projects[0].project,
+            (MAX_RECENT_PROJECTS + 1).to_string()
+        );
+        assert!(!recent(&state).contains(&"0"));
+        assert!(!recent(&state).contains(&"1"));
+    }
+
+    #[test]
+    fn sessions_expire_without_commands() {
+        let mut state = RecentState::default();
+        let now = Utc::now();
+
+        state.set_session("1", Some("work"), now);
+        state.set_session("2", Some("home"), now);
+        assert_eq!(state.session_project("1", now), Some("work"));
+        assert_eq!(state.session_project("3", now), None);
+
+        // Commands in session 1 keep it alive.
+        let later = now + Duration::hours(SESSION_EXPIRY_HOURS);
+        state.record(&projects(&["work"]), Some("1"), later);
+
+        let after = now + Duration::hours(SESSION_EXPIRY_HOURS + 1);
+        assert_eq!(state.session_project("1", after), Some("work"));
+        assert_eq!(state.session_project("2", after), None);
+
+        state.record(&projects(&["work"]), None, after);
+        assert!(state.sessions.contains_key("1"));
+        assert!(!state.sessions.contains_key("2"));
+
+        state.set_session("1", None, after);
+        assert_eq!(state.session_project("1", after), None);
+    }
+
+    #[test]
+    fn switch_is_kept_in_the_state() {
+        let directory = tempfile::tempdir().unwrap();
+        let recent = RecentProjects {
+            state: StateStore::with_state_home(
+                directory.path().join("state"),
+                directory.path().join("datadir"),
+                Changes::default(),
+            ),
+        };
+        assert!(recent.list().is_empty());
+
+        recent.switch("1", Some("work")).unwrap();
+        recent
+            .record(&projects(&["home", "work"]), Some("1"))
+            .unwrap();
+
+        assert_eq!(recent.session_project("1").as_deref(), Some("work"));
+        assert_eq!(recent.session_project("2"), None);
+        assert_eq!(
+            recent
+                .list()
+                .into_iter()
+                .map(|recent| recent.project)
+                .collect::<Vec<_>>(),
+            projects(&["home", "work"])
+        );
+    }
+}
@@ -0,0 +1,448 @@
+use crate::{
+    entry::Entry,
+    store::{
+        state::StateStore,
+        Store,
+    },
+    templating,
+};
+use anyhow::{
+    bail,
+    Context,
+    Error,
+};
+use log::{
+    debug,
+    warn,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use sha2::{
+    Digest,
+    Sha256,
+};
+use std::{
+    collections::BTreeSet,
+    fs,
+    path::Path,
+    process::Command,
+    sync::{
+        atomic::{
+            AtomicUsize,
+            Ordering,
+        },
+        Arc,
+        OnceLock,
+    },
+    time::{
+        Duration,
+        SystemTime,
+    },
+};
+use uuid::Uuid;
+
+/// Version of the cached html. Has to be increased whenever todust changes how
+/// it calls asciidoctor so old renders are not served anymore.
+const RENDER_CACHE_VERSION: u32 = 1;
+
+/// Directory in the state store with the html of single entries.
+const ENTRIES_DIRECTORY: &str = "render/entries";
+
+/// Directory in the state store with the html of whole exported documents.
+const DOCUMENTS_DIRECTORY: &str = "render/documents";
+
+/// Rendered documents that were not used for this long are removed by the
+/// cleanup.
+const DOCUMENT_MAX_AGE: Duration = Duration::from_secs(30 * 24 * 60 * 60);
+
+/// Html rendered by asciidoctor together with the key of what was rendered.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct CachedRender {
+    key: String,
+    html: String,
+}
+
+/// Caches the html asciidoctor renders for entries and exported documents in
+/// the state directory. Entries are keyed by their uuid, their last change
+/// and the version of asciidoctor. Documents are keyed by their content.
+#[derive(Debug, Clone)]
+pub(crate) struct RenderCache {
+    state: StateStore,
+
+    /// Renders that failed since todust was started.
+    failures: Arc<AtomicUsize>,
+}
+
+impl RenderCache {
+    pub(super) fn new(state: StateStore) -> Self {
+        Self {
+            state,
+            failures: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+
+    /// How many renders failed since todust was started.
+    pub(super) fn failures(&self) -> usize {
+        self.failures.load(Ordering::Relaxed)
+    }
+
+    /// Html of the text of the entry as it is shown on the entry page.
+    pub(super) fn entry_html(&self, entry: &Entry, use_cache: bool) -> Result<String, Error> {
+        self.count_failure(|| {
+            let name = format!("{}/{}", ENTRIES_DIRECTORY, entry.metadata.uuid);
+            let key = entry_key(entry, renderer_version()?);
+
+            self.get_or_render(&name, key, use_cache, || {
+                templating::render_entry_asciidoc(&entry.metadata.uuid, &entry.text)
+            })
+        })
+    }
+
+    /// Html of a standalone asciidoc document. Only the most recent render of
+    /// each document name is kept.
+    pub(super) fn document_html(
+        &self,
+        document: &str,
+        asciidoc: &str,
+        use_cache: bool,
+    ) -> Result<String, Error> {
+        self.count_failure(|| {
+            let name = format!("{}/{}", DOCUMENTS_DIRECTORY, short_hash(document));
+            let key = format!("{} {}", short_hash(asciidoc), renderer_version()?);
+
+            self.get_or_render(&name, key, use_cache, || {
+                templating::render_asciidoc(asciidoc, true)
+            })
+        })
+    }
+
+    /// Count the render as failed if it returns an error. Also counts when
+    /// asciidoctor can not be run at all.
+    fn count_failure<F>(&self, render: F) -> Result<String, Error>
+    where
+        F: FnOnce() -> Result<String, Error>,
+    {
+        let result = render();
+        if result.is_err() {
+            self.failures.fetch_add(1, Ordering::Relaxed);
+        }
+
+        result
+    }
+
+    fn get_or_render<F>(
+        &self,
+        name: &str,
+        key: String,
+        use_cache: bool,
+        render: F,
+    ) -> Result<String, Error>
+    where
+        F: FnOnce() -> Result<String, Error>,
+    {
+        if use_cache {
+            let cached: CachedRender = self.state.load(name);
+            if cached.key == key {
+                debug!("using cached html for {}", name);
+                return Ok(cached.html);
+            }
+        }
+
+        let html = render()?;
+
+        // Failing to cache only makes the next render slower.
+        if let Err(err) = self.state.save(
+            name,
+            &CachedRender {
+                key,
+                html: html.clone(),
+            },
+        ) {
+            warn!("can not cache rendered html for {}: {:#}", name, err);
+        }
+
+        Ok(html)
+    }
+
+    /// Remove the html of entries that do not exist anymore, documents that
+    /// were not rendered in a long time and cache files that could not be
+    /// read. Returns how many files were or would be removed.
+    pub(super) fn prune(&self, existing: &BTreeSet<Uuid>) -> Result<usize, Error> {
+        let mut stale = Vec::new();
+
+        for path in self.state.files(ENTRIES_DIRECTORY)? {
+            let uuid = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(|name| name.strip_suffix(".json"))
+                .and_then(|stem| Uuid::parse_str(stem).ok());
+
+            if !matches!(uuid, Some(uuid) if existing.contains(&uuid)) {
+                stale.push(path);
+            }
+        }
+
+        let now = SystemTime::now();
+        for path in self.state.files(DOCUMENTS_DIRECTORY)? {
+            let age = fs::metadata(&path)
+                .and_then(|metadata| metadata.modified())
+                .ok()
+                .and_then(|modified| now.duration_since(modified).ok());
+
+            if !is_cache_file(&path) || age.is_none_or(|age| age > DOCUMENT_MAX_AGE) {
+                stale.push(path);
+            }
+        }
+
+        for path in &stale {
+            self.state.remove_file(path)?;
+        }
+
+        Ok(stale.len())
+    }
+}
+
+/// Key of the cached html of the entry. Changes whenever the entry is edited.
+fn entry_key(entry: &Entry, renderer_version: &str) -> String {
+    format!(
+        "{} {}",
+        entry.metadata.last_change.to_rfc3339(),
+        renderer_version
+    )
+}
+
+fn is_cache_file(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "json")
+}
+
+/// Version of asciidoctor and of the cache. Asciidoctor is only asked once
+/// per run.
+fn renderer_version() -> Result<&'static str, Error> {
+    static VERSION: OnceLock<String> = OnceLock::new();
+
+    if let Some(version) = VERSION.get() {
+        return Ok(version);
+    }
+
+    let output = Command::new("asciidoctor")
+        .arg("--version")
+        .output()
+        .context("can not run asciidoctor to get its version")?;
+
+    if !output.status.success() {
+        bail!("asciidoctor --version failed with {}", output.status)
+    }
+
+    let version = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .to_string();
+
+    Ok(VERSION.get_or_init(|| format!("{} cache {}", version, RENDER_CACHE_VERSION)))
+}
+
+fn short_hash(input: &str) -> String {
+    let mut hash = hex::encode(Sha256::digest(input.as_bytes()));
+    hash.truncate(16);
+
+    hash
+}
+
+impl Store {
+    /// Html of the text of the entry rendered by asciidoctor. The html is
+    /// cached in the state directory unless `use_cache` is false.
+    pub(crate) fn entry_html(&self, entry: &Entry, use_cache: bool) -> Result<String, Error> {
+        self.render_cache.entry_html(entry, use_cache)
+    }
+
+    /// Html of a standalone asciidoc document rendered by asciidoctor. Only
+    /// the last render of every document name is cached.
+    pub(crate) fn document_html(
+        &self,
+        document: &str,
+        asciidoc: &str,
+        use_cache: bool,
+    ) -> Result<String, Error> {
+        self.render_cache
+            .document_html(document, asciidoc, use_cache)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{
+        changes::Changes,
+        tests::{
+            entry,
+            temporary,
+        },
+    };
+    use std::cell::Cell;
+
+    fn cache() -> (tempfile::TempDir, RenderCache) {
+        let directory = tempfile::tempdir().unwrap();
+        let state = StateStore::with_state_home(
+            directory.path().join("state"),
+            directory.path().join("datadir"),
+            Changes::default(),
+        );
+
+        (directory, RenderCache::new(state))
+    }
+
+    /// Render through the cache and count how often the renderer ran.
+    fn render(cache: &RenderCache, key: &str, html: &str, renders: &Cell<usize>) -> String {
+        cache
+            .get_or_render("render/entries/entry", key.to_string(), true, || {
+                renders.set(renders.get() + 1);
+                Ok(html.to_string())
+            })
+            .unwrap()
+    }
+
+    #[test]
+    fn cache_hits_are_identical_to_the_render() {
+        let (_directory, cache) = cache();
+        let renders = Cell::new(0);
+        let html = "<div class=\"paragraph\">\n<p>text with ünïcödé</p>\n</div>\n";
+
+        let rendered = render(&cache, "key", html, &renders);
+        let cached = render(&cache, "key", "rendered again", &renders);
+
+        assert_eq!(renders.get(), 1);
+        assert_eq!(rendered, html);
+        assert_eq!(cached.as_bytes(), rendered.as_bytes());
+    }
+
+    #[test]
+    fn changed_key_renders_again() {
+        let (_directory, cache) = cache();
+        let renders = Cell::new(0);
+
+        render(&cache, "before", "<p>old</p>", &renders);
+        let html = render(&cache, "after", "<p>new</p>", &renders);
+
+        assert_eq!(renders.get(), 2);
+        assert_eq!(html, "<p>new</p>");
+        assert_eq!(render(&cache, "after", "unused", &renders), "<p>new</p>");
+        assert_eq!(renders.get(), 2);
+    }
+
+    #[test]
+    fn no_cache_always_renders() {
+        let (_directory, cache) = cache();
+        let renders = Cell::new(0);
+        render(&cache, "key", "<p>cached</p>", &renders);
+
+        let html = cache
+            .get_or_render("render/entries/entry", "key".to_string(), false, || {
+                Ok("<p>fresh</p>".to_string())
+            })
+            .unwrap();
+
+        assert_eq!(html, "<p>fresh</p>");
+        assert_eq!(render(&cache, "key", "unused", &renders), "<p>fresh</p>");
+    }
+
+    #[test]
+    fn corrupt_cache_files_are_rendered_again() {
+        let (_directory, cache) = cache();
+        let renders = Cell::new(0);
+        render(&cache, "key", "<p>html</p>", &renders);
+
+        let files = cache.state.files(ENTRIES_DIRECTORY).unwrap();
+        assert_eq!(files.len(), 1);
+        fs::write(&files[0], "{\"key\": \"key\", \"html\": ").unwrap();
+
+        let html = render(&cache, "key", "<p>html</p>", &renders);
+
+        assert_eq!(renders.get(), 2);
+        assert_eq!(html, "<p>html</p>");
+        assert_eq!(render(&cache, "key", "unused", &renders), "<p>html</p>");
+        assert_eq!(renders.get(), 2);
+    }
+
+    #[test]
+    fn failed_renders_are_counted_and_not_cached() {
+        let (_directory, cache) = cache();
+
+        let result = cache.count_failure(|| {
+            cache.get_or_render("render/entries/entry", "key".to_string(), true, || {
+                bail!("asciidoctor failed to render entry with exit status: 1: include not found")
+            })
+        });
+
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("include not found"));
+        assert_eq!(cache.failures(), 1);
+        assert!(cache.state.files(ENTRIES_DIRECTORY).unwrap().is_empty());
+
+        let renders = Cell::new(0);
+        let html = cache
+            .count_failure(|| Ok(render(&cache, "key", "<p>html</p>", &renders)))
+            .unwrap();
+        assert_eq!(html, "<p>html</p>");
+        assert_eq!(renders.get(), 1);
+        assert_eq!(cache.failures(), 1);
+    }
+
+    #[test]
+    fn edits_change_the_entry_key() {
+        let (_directory, store) = temporary();
+        let added = entry("before the edit");
+        store.add_entry(added.clone()).unwrap();
+        let before = store.get_entry_by_uuid(&added.metadata.uuid).unwrap();
+
+        let mut edited = before.clone();
+        edited.text = "after the edit".to_string();
+        store.update_entry(edited).unwrap();
+        let after = store.get_entry_by_uuid(&added.metadata.uuid).unwrap();
+
+        assert_eq!(entry_key(&before, "version"), entry_key(&before, "version"));
+        assert_ne!(entry_key(&before, "version"), entry_key(&after, "version"));
+        assert_ne!(
+            entry_key(&before, "version"),
+            entry_key(&before, "other version")
+        );
+    }
+
+    #[test]
+    fn prune_removes_renders_of_missing_entries() {
+        let (_directory, cache) = cache();
+        let kept = Uuid::new_v4();
+        let removed = Uuid::new_v4();
+        for uuid in &[kept, removed] {
+            cache
+                .get_or_render(
+                    &format!("{}/{}", ENTRIES_DIRECTORY, uuid),
+                    "key".to_string(),
+                    true,
+                    || Ok(String::new()),
+                )
+                .unwrap();
+        }
+        cache
+            .get_or_render(
+                &format!("{}/{}", DOCUMENTS_DIRECTORY, short_hash("export")),
+                "key".to_string(),
+                true,
+                || Ok(String::new()),
+            )
+            .unwrap();
+
+        let existing = vec![kept].into_iter().collect();
+        assert_eq!(cache.prune(&existing).unwrap(), 1);
+
+        let files = cache.state.files(ENTRIES_DIRECTORY).unwrap();
+        assert_eq!(files.len(), 1);
+        assert!(files[0].ends_with(format!("{}.json", kept)));
+        assert_eq!(cache.state.files(DOCUMENTS_DIRECTORY).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,309 @@
+use crate::{
+    config::Config,
+    helper::confirm,
+    store::{
+        changes::Changes,
+        vcs::VcsSettings,
+        Store,
+    },
+};
+use anyhow::{
+    bail,
+    Context,
+    Error,
+};
+use chrono::Utc;
+use log::warn;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    fs,
+    io::Write,
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+/// Version of the store layout this todust reads and writes.
+pub(super) const STORE_VERSION: usize = 1;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub(super) struct StoreSettings {
+    pub(super) store_version: usize,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub(super) ignored_projects: Option<Vec<String>>,
+
+    pub(super) vcs: Option<VcsSettings>,
+}
+
+impl Default for StoreSettings {
+    fn default() -> Self {
+        Self {
+            store_version: STORE_VERSION,
+            ignored_projects: None,
+            vcs: Some(VcsSettings::default()),
+        }
+    }
+}
+
+impl Store {
+    pub(super) fn get_settings<P: AsRef<Path>>(
+        datadir: P,
+        config: &Config,
+    ) -> Result<StoreSettings, Error> {
+        let path = Store::settings_path(&datadir);
+
+        if !path.exists() {
+            return Store::write_default_settings(&path, &config.changes);
+        }
+
+        let data = fs::read(&path)
+            .with_context(|| format!("can not read store settings from {:?}", path))?;
+
+        // Check the version before parsing everything as the settings of a
+        // newer store can look different and are not broken.
+        if let Some(version) = toml::from_slice::<toml::Value>(&data)
+            .ok()
+            .and_then(|value| value.get("store_version")?.as_integer())
+        {
+            Store::check_store_version(datadir.as_ref(), version)?;
+        }
+
+        let err = match toml::from_slice::<StoreSettings>(&data) {
+            Ok(settings) => {
+                Store::check_store_version(datadir.as_ref(), settings.store_version as i64)?;
+                return Ok(settings);
+            }
+            Err(err) => err,
+        };
+
+        let message = format!("can not parse store settings from {:?}: {}", path, err);
+        let backup = path.with_file_name(format!(
+            ".settings.toml.broken-{}",
+            Utc::now().format("%Y%m%dT%H%M%S")
+        ));
+
+        let repair = if config.repair {
+            true
+        } else if config.interactivity.can_prompt() {
+            eprintln!("{}", message);
+            confirm(
+                &format!(
+                    "Move the broken settings to {:?} and write the default settings? The ignored \
+                     projects and version control settings have to be set again.",
+                    backup
+                ),
+                false,
+            )?
+        } else {
+            false
+        };
+
+        if !repair {
+            bail!(
+                "{}, fix the file or pass --repair to move it to {:?} and write the default \
+                 settings",
+                message,
+                backup
+            )
+        }
+
+        config.changes.apply(
+            || format!("move broken store settings to {:?}", backup),
+            || {
+                fs::rename(&path, &backup).with_context(|| {
+                    format!("can not move broken store settings to {:?}", backup)
+                })?;
+                warn!(
+                    "moved broken store settings to {:?} and wrote the default settings",
+                    backup
+                );
+
+                Ok::<_, Error>(())
+            },
+        )?;
+
+        Store::write_default_settings(&path, &config.changes)
+    }
+
+    fn write_default_settings(path: &Path, changes: &Changes) -> Result<StoreSettings, Error> {
+        let info = StoreSettings::default();
+        let data = toml::to_string_pretty(&info)?;
+
+        changes.apply(
+            || format!("write the default store settings to {:?}", path),
+            || {
+                let mut file = fs::File::create(path)?;
+                file.write_all(data.as_bytes())
+            },
+        )?;
+
+        Ok(info)
+    }
+
+    fn check_store_version(datadir: &Path, version: i64) -> Result<(), Error> {
+        if version > STORE_VERSION as i64 {
+            bail!(
+                "the datadir {:?} was written by a newer todust with store version {} but this \
+                 todust only supports store version {}, update todust or use `todust export` of \
+                 the newer version and import the entries here",
+                datadir,
+                version,
+                STORE_VERSION
+            )
+        }
+
+        if version != STORE_VERSION as i64 {
+            bail!(
+                "the datadir {:?} has the unknown store version {}, this todust only supports \
+                 store version {}",
+                datadir,
+                version,
+                STORE_VERSION
+            )
+        }
+
+        Ok(())
+    }
+
+    pub(super) fn settings_path<P: AsRef<Path>>(datadir: P) -> PathBuf {
+        let mut path = PathBuf::new();
+        path.push(datadir);
+        path.push(".settings.toml");
+
+        path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BROKEN_SETTINGS: &str = "store_version = 1\nignored_projects = [\"a\" \"b\"]\n";
+
+    fn datadir_with_settings(settings: &str) -> (tempfile::TempDir, PathBuf) {
+        let directory = tempfile::tempdir().unwrap();
+        fs::write(Store::settings_path(directory.path()), settings).unwrap();
+        let datadir = directory.path().to_path_buf();
+
+        (directory, datadir)
+    }
+
+    fn broken_backups(datadir: &Path) -> Vec<PathBuf> {
+        fs::read_dir(datadir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| {
+                path.file_name()
+                    .unwrap()
+                    .to_string_lossy()
+                    .starts_with(".settings.toml.broken-")
+            })
+            .collect()
+    }
+
+    fn repair() -> Config {
+        Config {
+            repair: true,
+            ..Config::default()
+        }
+    }
+
+    #[test]
+    fn missing_settings_are_written() {
+        let directory = tempfile::tempdir().unwrap();
+
+        let settings = Store::get_settings(directory.path(), &Config::default()).unwrap();
+        assert_eq!(settings.store_version, STORE_VERSION);
+        assert!(Store::settings_path(directory.path()).exists());
+    }
+
+    #[test]
+    fn broken_settings_are_kept_without_repair() {
+        let (_directory, datadir) = datadir_with_settings(BROKEN_SETTINGS);
+
+        let err = Store::get_settings(&datadir, &Config::default()).unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("at line 2 column"), "{}", message);
+        assert!(message.contains("pass --repair"), "{}", message);
+
+        assert_eq!(
+            fs::read_to_string(Store::settings_path(&datadir)).unwrap(),
+            BROKEN_SETTINGS
+        );
+        assert!(broken_backups(&datadir).is_empty());
+    }
+
+    #[test]
+    fn repair_moves_broken_settings_aside() {
+        let (_directory, datadir) = datadir_with_settings(BROKEN_SETTINGS);
+
+        let settings = Store::get_settings(&datadir, &repair()).unwrap();
+        assert_eq!(settings.store_version, STORE_VERSION);
+        assert_eq!(settings.ignored_projects, None);
+
+        let backups = broken_backups(&datadir);
+        assert_eq!(backups.len(), 1);
+        assert_eq!(fs::read_to_string(&backups[0]).unwrap(), BROKEN_SETTINGS);
+
+        // The written defaults can be read again without a repair.
+        Store::get_settings(&datadir, &Config::default()).unwrap();
+    }
+
+    #[test]
+    fn dry_run_repair_changes_nothing() {
+        let (_directory, datadir) = datadir_with_settings(BROKEN_SETTINGS);
+        let config = Config {
+            changes: Changes::dry_run(),
+            ..repair()
+        };
+
+        Store::get_settings(&datadir, &config).unwrap();
+        assert_eq!(
+            fs::read_to_string(Store::settings_path(&datadir)).unwrap(),
+            BROKEN_SETTINGS
+        );
+        assert!(broken_backups(&datadir).is_empty());
+    }
+
+    #[test]
+    fn settings_of_a_newer_store_name_both_versions() {
+        // A newer store can have settings this version can not parse. They
+        // must not be treated as broken, even when repairing.
+        let newer = "store_version = 2\n\n[vcs]\nbackend = \"jj\"\n";
+        let (_directory, datadir) = datadir_with_settings(newer);
+
+        let message = Store::get_settings(&datadir, &repair())
+            .unwrap_err()
+            .to_string();
+        assert!(
+            message.contains("newer todust with store version 2"),
+            "{}",
+            message
+        );
+        assert!(
+            message.contains("only supports store version 1"),
+            "{}",
+            message
+        );
+        assert_eq!(
+            fs::read_to_string(Store::settings_path(&datadir)).unwrap(),
+            newer
+        );
+        assert!(broken_backups(&datadir).is_empty());
+    }
+
+    #[test]
+    fn unknown_older_store_version_is_refused() {
+        let (_directory, datadir) = datadir_with_settings("store_version = 0\n");
+
+        let message = Store::get_settings(&datadir, &Config::default())
+            .unwrap_err()
+            .to_string();
+        assert!(message.contains("unknown store version 0"), "{}", message);
+    }
+}
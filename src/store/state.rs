@@ -0,0 +1,464 @@
+use crate::store::changes::Changes;
+use anyhow::{
+    Context,
+    Error,
+};
+use log::warn;
+use serde::{
+    de::DeserializeOwned,
+    Serialize,
+};
+use sha2::{
+    Digest,
+    Sha256,
+};
+use std::{
+    ffi::OsStr,
+    fs,
+    io::{
+        ErrorKind,
+        Write,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+};
+
+/// How many hex characters of the hash of the datadir path are used to name
+/// the state directory of a store.
+const STORE_HASH_LENGTH: usize = 16;
+
+/// Per machine state of a store like the focus or when entries were last
+/// viewed. The state is kept in `$XDG_STATE_HOME/todust/<store hash>/` so it
+/// is never committed or synced with the datadir. Every store gets its own
+/// directory derived from the path of its datadir.
+#[derive(Debug, Clone)]
+pub(crate) struct StateStore {
+    directory: PathBuf,
+    changes: Changes,
+}
+
+impl StateStore {
+    /// State store of the datadir under the xdg state home.
+    pub(super) fn open<P: AsRef<Path>>(datadir: P, changes: Changes) -> Result<Self, Error> {
+        let state_home = xdg::BaseDirectories::with_prefix("todust")
+            .context("can not read xdg base directories")?
+            .get_state_home();
+
+        Ok(Self::with_state_home(state_home, datadir, changes))
+    }
+
+    /// State store of the datadir under the given state home.
+    pub(super) fn with_state_home<S: AsRef<Path>, P: AsRef<Path>>(
+        state_home: S,
+        datadir: P,
+        changes: Changes,
+    ) -> Self {
+        Self {
+            directory: state_home.as_ref().join(store_hash(datadir.as_ref())),
+            changes,
+        }
+    }
+
+    /// Load the state with the given name. Missing state is the default. A
+    /// state file that can not be read is moved aside with a warning and the
+    /// default is used so broken state never keeps todust from working.
+    pub(super) fn load<T: DeserializeOwned + Default>(&self, name: &str) -> T {
+        let path = self.path(name);
+
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == ErrorKind::NotFound => return T::default(),
+            Err(err) => {
+                warn!(
+                    "can not read state file {:?}, using defaults: {}",
+                    path, err
+                );
+                return T::default();
+            }
+        };
+
+        match serde_json::from_str(&content) {
+            Ok(state) => state,
+            Err(err) => {
+                let corrupted = path.with_extension("json.corrupted");
+                warn!(
+                    "state file {:?} is corrupted, moving it to {:?} and using defaults: {}",
+                    path, corrupted, err
+                );
+
+                if let Err(err) = fs::rename(&path, &corrupted) {
+                    warn!("can not move corrupted state file {:?}: {}", path, err);
+                }
+
+                T::default()
+            }
+        }
+    }
+
+    /// Save the state with the given name. The file is replaced atomically so
+    /// a crash never leaves half written state behind.
+    pub(super) fn save<T: Serialize>(&self, name: &str, state: &T) -> Result<(), Error> {
+        let path = self.path(name);
+
+        self.changes.apply(
+            || format!("write state file {:?}", path),
+            || self.write(&path, state),
+        )?;
+
+        Ok(())
+    }
+
+    fn write<T: Serialize>(&self, path: &Path, state: &T) -> Result<(), Error> {
+        // Names can contain a directory to group state like the render cache.
+        let directory = path.parent().unwrap_or(&self.directory);
+        fs::create_dir_all(directory)
+            .with_context(|| format!("can not create state directory {:?}", directory))?;
+        let temporary = path.with_extension("json.tmp");
+
+        let content = serde_json::to_string_pretty(state).context("can not serialize state")?;
+
+        fs::write(&temporary, content)
+            .with_context(|| format!("can not write state file {:?}", temporary))?;
+
+        fs::rename(&temporary, path)
+            .with_context(|| format!("can not replace state file {:?}", path))?;
+
+        Ok(())
+    }
+
+    /// Save the state with the given name only if there is none yet. Returns
+    /// false if the state already exists. Checking and creating the file is
+    /// a single step so only one of several processes can create it.
+    pub(super) fn create<T: Serialize>(&self, name: &str, state: &T) -> Result<bool, Error> {
+        let path = self.path(name);
+
+        let created = self.changes.apply(
+            || format!("create state file {:?}", path),
+            || self.write_new(&path, state),
+        )?;
+
+        Ok(created.unwrap_or(true))
+    }
+
+    fn write_new<T: Serialize>(&self, path: &Path, state: &T) -> Result<bool, Error> {
+        fs::create_dir_all(&self.directory)
+            .with_context(|| format!("can not create state directory {:?}", self.directory))?;
+
+        let content = serde_json::to_string_pretty(state).context("can not serialize state")?;
+
+        let mut file = match fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+        {
+            Ok(file) => file,
+            Err(err) if err.kind() == ErrorKind::AlreadyExists => return Ok(false),
+            Err(err) => {
+                return Err(err).with_context(|| format!("can not create state file {:?}", path))
+            }
+        };
+
+        file.write_all(content.as_bytes())
+            .with_context(|| format!("can not write state file {:?}", path))?;
+
+        Ok(true)
+    }
+
+    /// Remove the state with the given name. Missing state is not an error.
+    pub(super) fn remove(&self, name: &str) -> Result<(), Error> {
+        self.remove_file(&self.path(name))
+    }
+
+    /// Remove a file of the state directory like a stale cache file. Missing
+    /// files are not an error.
+    pub(super) fn remove_file(&self, path: &Path) -> Result<(), Error> {
+        self.changes.apply(
+            || format!("remove state file {:?}", path),
+            || match fs::remove_file(path) {
+                Ok(()) => Ok(()),
+                Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+                Err(err) => {
+                    Err(err).with_context(|| format!("can not remove state file {:?}", path))
+                }
+            },
+        )?;
+
+        Ok(())
+    }
+
+    /// All files in the directory of the state store. Missing directories
+    /// have no files.
+    pub(super) fn files(&self, directory: &str) -> Result<Vec<PathBuf>, Error> {
+        let directory = self.directory.join(directory);
+
+        let entries = match fs::read_dir(&directory) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => {
+                return Err(err).with_context(|| format!("can not read {:?}", directory));
+            }
+        };
+
+        let mut files = Vec::new();
+        for entry in entries {
+            let path = entry
+                .with_context(|| format!("can not read {:?}", directory))?
+                .path();
+
+            if path.is_file() {
+                files.push(path);
+            }
+        }
+
+        Ok(files)
+    }
+
+    /// Remove a state file that older versions kept in the datadir once it
+    /// was moved to the state store.
+    pub(super) fn remove_legacy_file(&self, datadir: &Path, file_name: &str) -> Result<(), Error> {
+        self.changes.apply(
+            || format!("remove legacy state file {:?}", datadir.join(file_name)),
+            || remove_legacy_file(datadir, file_name),
+        )?;
+
+        Ok(())
+    }
+
+    /// Directory the state of the store is kept in.
+    pub(super) fn directory(&self) -> &Path {
+        &self.directory
+    }
+
+    /// Name of the state directory that is derived from the datadir. Used to
+    /// keep other per store files apart.
+    pub(super) fn name(&self) -> &OsStr {
+        self.directory.file_name().unwrap_or_default()
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.directory.join(format!("{}.json", name))
+    }
+}
+
+/// Bytes of the path for hashing. Paths that are not valid UTF-8 keep their
+/// bytes on unix so they do not collide after a lossy conversion.
+#[cfg(unix)]
+fn path_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+fn path_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().as_bytes().to_vec()
+}
+
+/// Short hash of the absolute path of the datadir. Falls back to the path as
+/// given if it can not be resolved.
+fn store_hash(datadir: &Path) -> String {
+    let datadir = datadir
+        .canonicalize()
+        .unwrap_or_else(|_| datadir.to_path_buf());
+
+    let hash = Sha256::digest(path_bytes(&datadir));
+
+    let mut hash = hex::encode(hash);
+    hash.truncate(STORE_HASH_LENGTH);
+
+    hash
+}
+
+/// Read the records of a state file that older versions kept in the datadir.
+/// Returns `None` if there is no such file.
+pub(super) fn read_legacy_csv<R: DeserializeOwned>(
+    datadir: &Path,
+    file_name: &str,
+) -> Result<Option<Vec<R>>, Error> {
+    let path = datadir.join(file_name);
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    let mut reader =
+        csv::Reader::from_path(&path).with_context(|| format!("can not open {:?}", path))?;
+
+    let records = reader
+        .deserialize()
+        .collect::<Result<Vec<R>, _>>()
+        .with_context(|| format!("can not read records from {:?}", path))?;
+
+    Ok(Some(records))
+}
+
+/// Remove a state file that older versions kept in the datadir together with
+/// its entry in the `.gitignore` of the datadir.
+fn remove_legacy_file(datadir: &Path, file_name: &str) -> Result<(), Error> {
+    let path = datadir.join(file_name);
+    fs::remove_file(&path).with_context(|| format!("can not remove {:?}", path))?;
+
+    let gitignore = datadir.join(".gitignore");
+    if !gitignore.exists() {
+        return Ok(());
+    }
+
+    let content =
+        fs::read_to_string(&gitignore).with_context(|| format!("can not read {:?}", gitignore))?;
+
+    let lines = content
+        .lines()
+        .filter(|line| line.trim() != file_name)
+        .collect::<Vec<_>>();
+
+    if lines.is_empty() {
+        fs::remove_file(&gitignore).with_context(|| format!("can not remove {:?}", gitignore))?;
+    } else {
+        fs::write(&gitignore, format!("{}\n", lines.join("\n")))
+            .with_context(|| format!("can not write {:?}", gitignore))?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn state(state_home: &Path, datadir: &Path) -> StateStore {
+        StateStore::with_state_home(state_home, datadir, Changes::default())
+    }
+
+    #[test]
+    fn state_directory_is_derived_from_the_datadir() {
+        let directory = tempfile::tempdir().unwrap();
+        let state_home = directory.path().join("state");
+        let datadir = directory.path().join("datadir");
+        fs::create_dir_all(datadir.join("sub")).unwrap();
+
+        let store = state(&state_home, &datadir);
+        assert_eq!(store.directory().parent(), Some(state_home.as_path()));
+        assert_eq!(store.name().len(), STORE_HASH_LENGTH);
+
+        // Other spellings of the same datadir share the state.
+        for spelling in &[
+            datadir.join(""),
+            datadir.join("sub").join(".."),
+            datadir.join(".").join("sub").join(".."),
+        ] {
+            assert_eq!(
+                state(&state_home, spelling).directory(),
+                store.directory(),
+                "{:?}",
+                spelling
+            );
+        }
+
+        let other = directory.path().join("other");
+        fs::create_dir_all(&other).unwrap();
+        assert_ne!(state(&state_home, &other).directory(), store.directory());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_datadir_shares_the_state() {
+        let directory = tempfile::tempdir().unwrap();
+        let state_home = directory.path().join("state");
+        let datadir = directory.path().join("datadir");
+        fs::create_dir_all(&datadir).unwrap();
+        let link = directory.path().join("link");
+        std::os::unix::fs::symlink(&datadir, &link).unwrap();
+
+        assert_eq!(
+            state(&state_home, &link).directory(),
+            state(&state_home, &datadir).directory()
+        );
+    }
+
+    #[test]
+    fn state_is_saved_and_loaded() {
+        let directory = tempfile::tempdir().unwrap();
+        let store = state(directory.path(), Path::new("/todos"));
+
+        let missing: BTreeMap<String, u32> = store.load("counts");
+        assert!(missing.is_empty());
+
+        let counts = vec![("work".to_string(), 3)]
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+        store.save("counts", &counts).unwrap();
+        assert_eq!(store.load::<BTreeMap<String, u32>>("counts"), counts);
+        assert!(store.directory().join("counts.json").exists());
+        assert!(!store.directory().join("counts.json.tmp").exists());
+
+        store.remove("counts").unwrap();
+        assert!(store.load::<BTreeMap<String, u32>>("counts").is_empty());
+        store.remove("counts").unwrap();
+    }
+
+    #[test]
+    fn corrupted_state_is_reset_and_kept_aside() {
+        let directory = tempfile::tempdir().unwrap();
+        let store = state(directory.path(), Path::new("/todos"));
+        fs::create_dir_all(store.directory()).unwrap();
+        let path = store.directory().join("counts.json");
+        fs::write(&path, "{\"work\": ").unwrap();
+
+        let counts: BTreeMap<String, u32> = store.load("counts");
+        assert!(counts.is_empty());
+
+        assert!(!path.exists());
+        assert_eq!(
+            fs::read_to_string(store.directory().join("counts.json.corrupted")).unwrap(),
+            "{\"work\": "
+        );
+
+        // The state works again after it was reset.
+        let counts = vec![("work".to_string(), 1)]
+            .into_iter()
+            .collect::<BTreeMap<_, _>>();
+        store.save("counts", &counts).unwrap();
+        assert_eq!(store.load::<BTreeMap<String, u32>>("counts"), counts);
+    }
+
+    #[test]
+    fn state_of_the_wrong_shape_is_reset() {
+        let directory = tempfile::tempdir().unwrap();
+        let store = state(directory.path(), Path::new("/todos"));
+        store.save("counts", &vec!["not", "a", "map"]).unwrap();
+
+        let counts: BTreeMap<String, u32> = store.load("counts");
+        assert!(counts.is_empty());
+        assert!(store.directory().join("counts.json.corrupted").exists());
+    }
+
+    #[test]
+    fn dry_run_does_not_write_state() {
+        let directory = tempfile::tempdir().unwrap();
+        let store =
+            StateStore::with_state_home(directory.path(), Path::new("/todos"), Changes::dry_run());
+
+        store
+            .save("counts", &BTreeMap::<String, u32>::new())
+            .unwrap();
+        assert!(!store.directory().exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn datadirs_that_are_not_utf8_get_their_own_hash() {
+        use std::{
+            ffi::OsStr,
+            os::unix::ffi::OsStrExt,
+        };
+
+        let first = Path::new(OsStr::from_bytes(b"/todos-\xff"));
+        let second = Path::new(OsStr::from_bytes(b"/todos-\xfe"));
+        assert_eq!(first.to_string_lossy(), second.to_string_lossy());
+
+        assert_ne!(store_hash(first), store_hash(second));
+        assert_eq!(store_hash(first).len(), STORE_HASH_LENGTH);
+    }
+}
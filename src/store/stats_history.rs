@@ -0,0 +1,544 @@
+use crate::{
+    stats,
+    store::{
+        changes::Changes,
+        Store,
+    },
+    time_context::TimeContext,
+};
+use anyhow::{
+    Context,
+    Error,
+};
+use chrono::NaiveDate;
+use log::{
+    debug,
+    warn,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::{
+        self,
+        Read,
+        Seek,
+        SeekFrom,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
+    sync::{
+        Arc,
+        Mutex,
+    },
+};
+
+/// Folder in the datadir with the statistics history.
+const STATS_FOLDER: &str = "stats";
+
+const HISTORY_FILE_NAME: &str = "history.csv";
+
+/// Lets git merge the snapshots two machines appended on the same day by
+/// keeping both sides. Duplicate rows are ignored when reading and removed by
+/// cleanup.
+const GIT_ATTRIBUTES: &str = "history.csv merge=union\n";
+
+/// Bytes at the end of the history that are read to find the day of the last
+/// snapshot. Covers the rows of a few hundred projects.
+const TAIL_BYTES: u64 = 16 * 1024;
+
+/// Counts of one project on one day.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct ProjectSnapshot {
+    pub(crate) day: NaiveDate,
+    pub(crate) project: String,
+    pub(crate) active: usize,
+    pub(crate) done: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub(crate) struct SnapshotCounts {
+    pub(crate) active: usize,
+    pub(crate) done: usize,
+}
+
+/// Counts of all projects on one day.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct DaySnapshot {
+    pub(crate) day: NaiveDate,
+
+    /// Sum of the counts of the projects.
+    pub(crate) active: usize,
+    pub(crate) done: usize,
+
+    pub(crate) projects: BTreeMap<String, SnapshotCounts>,
+}
+
+/// Daily snapshots of the active and done entries of every project in
+/// `stats/history.csv` in the datadir. The file is shared between machines
+/// like the entries so the history is the same everywhere.
+#[derive(Debug, Clone)]
+pub(crate) struct StatsHistory {
+    folder: PathBuf,
+    changes: Changes,
+
+    /// Serializes the check for today's snapshot and appending it.
+    lock: Arc<Mutex<()>>,
+}
+
+impl StatsHistory {
+    pub(super) fn new(datadir: &Path, changes: Changes) -> Self {
+        Self {
+            folder: datadir.join(STATS_FOLDER),
+            changes,
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    fn path(&self) -> PathBuf {
+        self.folder.join(HISTORY_FILE_NAME)
+    }
+
+    /// Day of the newest snapshot. Only the end of the file is read as this
+    /// is checked by every command.
+    pub(super) fn last_day(&self) -> Result<Option<NaiveDate>, Error> {
+        let path = self.path();
+
+        let mut file = match fs::File::open(&path) {
+            Ok(file) => file,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => {
+                return Err(err).with_context(|| format!("can not open {:?}", path));
+            }
+        };
+
+        let length = file
+            .metadata()
+            .with_context(|| format!("can not read metadata of {:?}", path))?
+            .len();
+
+        let mut tail = Vec::new();
+        file.seek(SeekFrom::Start(length.saturating_sub(TAIL_BYTES)))
+            .and_then(|_| file.read_to_end(&mut tail))
+            .with_context(|| format!("can not read {:?}", path))?;
+
+        // The first line can be cut off and rows of merged snapshots are not
+        // in order, so the newest day of all complete looking rows counts.
+        let last_day = String::from_utf8_lossy(&tail)
+            .lines()
+            .filter_map(|line| line.split(',').next()?.parse::<NaiveDate>().ok())
+            .max();
+
+        Ok(last_day)
+    }
+
+    /// Append the counts as the snapshot of the day unless the history
+    /// already has a snapshot of that day or a later one. Returns if the
+    /// snapshot was written.
+    pub(super) fn record(
+        &self,
+        day: NaiveDate,
+        counts: BTreeMap<String, SnapshotCounts>,
+    ) -> Result<bool, Error> {
+        let _guard = self.lock.lock().unwrap_or_else(|err| err.into_inner());
+
+        if counts.is_empty() || self.last_day()?.is_some_and(|last_day| last_day >= day) {
+            return Ok(false);
+        }
+
+        let written = self.changes.apply(
+            || {
+                format!(
+                    "append the statistics snapshot of {} to {:?}",
+                    day,
+                    self.path()
+                )
+            },
+            || self.append(day, counts),
+        )?;
+
+        Ok(written.is_some())
+    }
+
+    fn append(
+        &self,
+        day: NaiveDate,
+        counts: BTreeMap<String, SnapshotCounts>,
+    ) -> Result<(), Error> {
+        fs::create_dir_all(&self.folder)
+            .with_context(|| format!("can not create folder {:?}", self.folder))?;
+
+        let attributes = self.folder.join(".gitattributes");
+        if !attributes.exists() {
+            fs::write(&attributes, GIT_ATTRIBUTES)
+                .with_context(|| format!("can not write {:?}", attributes))?;
+        }
+
+        let path = self.path();
+        let file = fs::OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&path)
+            .with_context(|| format!("can not open {:?}", path))?;
+
+        // Only write the header for a new file so appending keeps one header.
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(file.metadata().map(|metadata| metadata.len() == 0)?)
+            .from_writer(file);
+
+        for (project, counts) in counts {
+            writer
+                .serialize(ProjectSnapshot {
+                    day,
+                    project,
+                    active: counts.active,
+                    done: counts.done,
+                })
+                .with_context(|| format!("can not write snapshot to {:?}", path))?;
+        }
+
+        writer
+            .flush()
+            .with_context(|| format!("can not write snapshot to {:?}", path))?;
+
+        Ok(())
+    }
+
+    /// All rows as they are in the file.
+    fn read_rows(&self) -> Result<Vec<ProjectSnapshot>, Error> {
+        let path = self.path();
+        if !path.exists() {
+            return Ok(Vec::new());
+        }
+
+        csv::ReaderBuilder::new()
+            .from_path(&path)
+            .and_then(|mut reader| reader.deserialize().collect())
+            .with_context(|| format!("can not read statistics history from {:?}", path))
+    }
+
+    /// All snapshots sorted by day and project. Rows that were recorded
+    /// twice for the same day and project are only returned once with the
+    /// counts of the last one.
+    pub(super) fn read(&self) -> Result<Vec<ProjectSnapshot>, Error> {
+        Ok(deduplicate(self.read_rows()?))
+    }
+
+    /// Remove duplicate rows from merged snapshots. Returns the rows before
+    /// and after. Only counts them in a dry run.
+    pub(super) fn compact(&self) -> Result<(usize, usize), Error> {
+        let _guard = self.lock.lock().unwrap_or_else(|err| err.into_inner());
+
+        let rows = self.read_rows()?;
+        let before = rows.len();
+        let rows = deduplicate(rows);
+        let after = rows.len();
+
+        if before != after {
+            self.changes.apply(
+                || {
+                    format!(
+                        "remove {} duplicate rows from {:?}",
+                        before - after,
+                        self.path()
+                    )
+                },
+                || self.write_rows(rows),
+            )?;
+        }
+
+        Ok((before, after))
+    }
+
+    /// Replace the history with the rows.
+    fn write_rows(&self, rows: Vec<ProjectSnapshot>) -> Result<(), Error> {
+        let path = self.path();
+        let temporary = path.with_extension("csv.tmp");
+
+        let mut writer = csv::WriterBuilder::new()
+            .from_path(&temporary)
+            .with_context(|| format!("can not create {:?}", temporary))?;
+        for row in rows {
+            writer
+                .serialize(row)
+                .with_context(|| format!("can not write {:?}", temporary))?;
+        }
+        writer
+            .flush()
+            .with_context(|| format!("can not write {:?}", temporary))?;
+        drop(writer);
+
+        fs::rename(&temporary, &path).with_context(|| format!("can not replace {:?}", path))?;
+
+        Ok(())
+    }
+}
+
+fn deduplicate(rows: Vec<ProjectSnapshot>) -> Vec<ProjectSnapshot> {
+    rows.into_iter()
+        .map(|row| ((row.day, row.project.clone()), row))
+        .collect::<BTreeMap<_, _>>()
+        .into_values()
+        .collect()
+}
+
+/// Group the snapshots of the projects that pass `filter` by day. Only the
+/// last `limit` days are returned, oldest first.
+pub(crate) fn by_day(
+    rows: Vec<ProjectSnapshot>,
+    filter: impl Fn(&str) -> bool,
+    limit: usize,
+) -> Vec<DaySnapshot> {
+    let mut days: BTreeMap<NaiveDate, DaySnapshot> = BTreeMap::new();
+
+    for row in rows.into_iter().filter(|row| filter(&row.project)) {
+        let snapshot = days.entry(row.day).or_insert_with(|| DaySnapshot {
+            day: row.day,
+            active: 0,
+            done: 0,
+            projects: BTreeMap::new(),
+        });
+
+        snapshot.active += row.active;
+        snapshot.done += row.done;
+        snapshot.projects.insert(
+            row.project,
+            SnapshotCounts {
+                active: row.active,
+                done: row.done,
+            },
+        );
+    }
+
+    let skip = days.len().saturating_sub(limit);
+    days.into_values().skip(skip).collect()
+}
+
+impl Store {
+    /// Append the active and done entries of every project to the statistics
+    /// history once per day, so the snapshot has the counts of the first
+    /// command of the day. Only reads the index. The snapshot is committed
+    /// with the next change. Failing is only a warning as this runs for
+    /// every command.
+    pub(crate) fn record_stats_snapshot(&self) {
+        let today = TimeContext::from_config(&self.config).today();
+
+        // Skip reading the index when today was recorded already.
+        if matches!(self.stats_history.last_day(), Ok(Some(day)) if day >= today) {
+            return;
+        }
+
+        let result = self
+            .index
+            .metadata_most_recent()
+            .map_err(Error::from)
+            .and_then(|metadata| {
+                let mut counts: BTreeMap<String, SnapshotCounts> = BTreeMap::new();
+                for metadata in metadata {
+                    let count = counts.entry(metadata.project.clone()).or_default();
+                    if metadata.is_active() {
+                        count.active += 1;
+                    } else {
+                        count.done += 1;
+                    }
+                }
+
+                self.stats_history.record(today, counts)
+            });
+
+        match result {
+            Ok(true) => debug!("recorded statistics snapshot for {}", today),
+            Ok(false) => {}
+            Err(err) => warn!("can not record statistics snapshot: {:#}", err),
+        }
+    }
+
+    /// Daily snapshots of the last `limit` days that were recorded. Without
+    /// a project the counts of all projects that are not ignored are summed
+    /// up. Projects that are not `allowed` are always left out.
+    pub(crate) fn stats_history(
+        &self,
+        project: Option<&str>,
+        allowed: impl Fn(&str) -> bool,
+        limit: usize,
+    ) -> Result<Vec<DaySnapshot>, Error> {
+        let rows = self.stats_history.read()?;
+
+        Ok(by_day(
+            rows,
+            |name| {
+                allowed(name)
+                    && match project {
+                        Some(project) => name == project,
+                        None => !self.is_ignored_project(name),
+                    }
+            },
+            limit,
+        ))
+    }
+
+    /// Number of entries finished per week and project for the last `weeks`
+    /// weeks, oldest week first. Only reads the index.
+    pub(crate) fn finished_per_week(
+        &self,
+        weeks: usize,
+        time: &TimeContext,
+    ) -> Result<BTreeMap<String, Vec<usize>>, Error> {
+        let metadata = self.index.metadata_most_recent()?;
+
+        Ok(stats::finished_per_week(&metadata, weeks, time))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::{
+        entry,
+        temporary,
+    };
+    use chrono::Utc;
+
+    fn day(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2020, 5, day).unwrap()
+    }
+
+    fn counts(projects: &[(&str, usize, usize)]) -> BTreeMap<String, SnapshotCounts> {
+        projects
+            .iter()
+            .map(|(project, active, done)| {
+                (
+                    project.to_string(),
+                    SnapshotCounts {
+                        active: *active,
+                        done: *done,
+                    },
+                )
+            })
+            .collect()
+    }
+
+    fn row(day: NaiveDate, project: &str, active: usize, done: usize) -> ProjectSnapshot {
+        ProjectSnapshot {
+            day,
+            project: project.to_string(),
+            active,
+            done,
+        }
+    }
+
+    #[test]
+    fn snapshot_is_recorded_once_per_day() {
+        let directory = tempfile::tempdir().unwrap();
+        let history = StatsHistory::new(directory.path(), Changes::default());
+        assert_eq!(history.last_day().unwrap(), None);
+
+        assert!(history.record(day(2), counts(&[("work", 2, 1)])).unwrap());
+        assert!(!history.record(day(2), counts(&[("work", 5, 5)])).unwrap());
+        assert!(!history.record(day(1), counts(&[("work", 5, 5)])).unwrap());
+        assert_eq!(history.last_day().unwrap(), Some(day(2)));
+
+        assert!(history
+            .record(day(3), counts(&[("home", 0, 4), ("work", 3, 1)]))
+            .unwrap());
+        assert_eq!(history.last_day().unwrap(), Some(day(3)));
+
+        assert_eq!(
+            history.read().unwrap(),
+            vec![
+                row(day(2), "work", 2, 1),
+                row(day(3), "home", 0, 4),
+                row(day(3), "work", 3, 1),
+            ]
+        );
+
+        let text = fs::read_to_string(history.path()).unwrap();
+        assert_eq!(text.matches("day,project,active,done").count(), 1);
+    }
+
+    #[test]
+    fn nothing_is_recorded_without_projects_or_in_a_dry_run() {
+        let directory = tempfile::tempdir().unwrap();
+
+        let history = StatsHistory::new(directory.path(), Changes::default());
+        assert!(!history.record(day(1), BTreeMap::new()).unwrap());
+
+        let history = StatsHistory::new(directory.path(), Changes::dry_run());
+        assert!(!history.record(day(1), counts(&[("work", 1, 0)])).unwrap());
+
+        assert!(!directory.path().join(STATS_FOLDER).exists());
+    }
+
+    #[test]
+    fn merged_duplicates_are_read_once_and_removed_by_compact() {
+        let directory = tempfile::tempdir().unwrap();
+        let history = StatsHistory::new(directory.path(), Changes::default());
+        history.record(day(1), counts(&[("work", 1, 0)])).unwrap();
+
+        // Both machines appended a snapshot of the same day before git merged
+        // the file.
+        let mut text = fs::read_to_string(history.path()).unwrap();
+        text.push_str("2020-05-02,work,2,0\n2020-05-02,work,3,0\n");
+        fs::write(history.path(), text).unwrap();
+
+        let expected = vec![row(day(1), "work", 1, 0), row(day(2), "work", 3, 0)];
+        assert_eq!(history.read().unwrap(), expected);
+
+        assert_eq!(history.compact().unwrap(), (3, 2));
+        assert_eq!(history.read_rows().unwrap(), expected);
+        assert_eq!(history.compact().unwrap(), (2, 2));
+    }
+
+    #[test]
+    fn days_sum_up_the_filtered_projects() {
+        let rows = vec![
+            row(day(1), "work", 4, 0),
+            row(day(1), "home", 1, 1),
+            row(day(2), "work", 3, 1),
+            row(day(2), "home", 1, 2),
+            row(day(2), "secret", 9, 9),
+            row(day(3), "work", 2, 2),
+        ];
+
+        let days = by_day(rows.clone(), |project| project != "secret", 10);
+        assert_eq!(days.len(), 3);
+        assert_eq!(days[1].day, day(2));
+        assert_eq!((days[1].active, days[1].done), (4, 3));
+        assert_eq!(
+            days[1].projects.keys().collect::<Vec<_>>(),
+            vec!["home", "work"]
+        );
+
+        let days = by_day(rows, |project| project == "work", 2);
+        assert_eq!(
+            days.iter()
+                .map(|snapshot| (snapshot.day, snapshot.active))
+                .collect::<Vec<_>>(),
+            vec![(day(2), 3), (day(3), 2)]
+        );
+    }
+
+    #[test]
+    fn store_snapshot_has_the_counts_of_the_first_command_of_the_day() {
+        let (_directory, store) = temporary();
+        store.add_entry(entry("first")).unwrap();
+        let mut done = entry("done");
+        done.metadata.finished = Some(Utc::now());
+        store.add_entry(done).unwrap();
+
+        store.record_stats_snapshot();
+        store.add_entry(entry("later")).unwrap();
+        store.record_stats_snapshot();
+
+        let today = TimeContext::from_config(&store.config).today();
+        assert_eq!(
+            store.stats_history.read().unwrap(),
+            vec![row(today, "default", 1, 1)]
+        );
+    }
+}
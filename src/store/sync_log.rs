@@ -0,0 +1,280 @@
+use crate::{
+    helper,
+    store::{
+        state::StateStore,
+        Store,
+    },
+};
+use anyhow::{
+    bail,
+    Error,
+};
+use chrono::{
+    DateTime,
+    Duration,
+    Utc,
+};
+use log::warn;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// Name of the state that records when the datadir was last pulled.
+const LAST_SYNC_STATE_NAME: &str = "last_sync";
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+struct LastSync {
+    pulled: Option<DateTime<Utc>>,
+}
+
+/// Records when changes of the other machines were last pulled into the
+/// datadir on this machine.
+#[derive(Debug, Clone)]
+pub(crate) struct SyncLog {
+    state: StateStore,
+}
+
+impl SyncLog {
+    pub(super) fn new(state: StateStore) -> Self {
+        Self { state }
+    }
+
+    /// Record that the datadir was pulled just now.
+    fn record_pull(&self) -> Result<(), Error> {
+        self.state.save(
+            LAST_SYNC_STATE_NAME,
+            &LastSync {
+                pulled: Some(Utc::now()),
+            },
+        )
+    }
+
+    /// When the datadir was last pulled on this machine.
+    pub(super) fn last_pull(&self) -> Option<DateTime<Utc>> {
+        self.state.load::<LastSync>(LAST_SYNC_STATE_NAME).pulled
+    }
+}
+
+/// How long ago the datadir was last synced if that is longer than
+/// `threshold`. The newer of the last pull and the last commit counts as the
+/// last sync. Without either nothing is known and the datadir is not stale.
+pub(super) fn stale_for(
+    last_pull: Option<DateTime<Utc>>,
+    last_commit: Option<DateTime<Utc>>,
+    threshold: Duration,
+    now: DateTime<Utc>,
+) -> Option<Duration> {
+    let last_sync = last_pull.max(last_commit)?;
+    let age = now - last_sync;
+
+    if age > threshold {
+        Some(age)
+    } else {
+        None
+    }
+}
+
+impl Store {
+    /// Commit all changes in the datadir if it is under version control.
+    /// Returns the id of the commit if one was made.
+    pub(super) fn commit(&self, message: &str) -> Result<Option<String>, Error> {
+        let vcs = match &self.settings.vcs {
+            Some(vcs) => vcs,
+            None => return Ok(None),
+        };
+
+        let commit_id = self.changes.apply(
+            || format!("commit with message {:?}", message),
+            || vcs.commit(&self.datadir, message, &self.config.vcs_config),
+        )?;
+
+        if commit_id.is_some()
+            && self.config.vcs_config.autocommit()
+            && self.config.vcs_config.autopull()
+        {
+            self.record_pull();
+        }
+
+        Ok(commit_id.flatten())
+    }
+
+    /// Pull the changes of the other machines into the datadir and push the
+    /// local ones.
+    pub(crate) fn sync(&self) -> Result<(), Error> {
+        let vcs = match &self.settings.vcs {
+            Some(vcs) if vcs.is_repository(&self.datadir) => vcs,
+            _ => bail!("the datadir is not under version control, there is nothing to sync"),
+        };
+
+        let synced = self.changes.apply(
+            || "pull and push the datadir".to_string(),
+            || vcs.sync(&self.datadir),
+        )?;
+
+        if synced.is_some() {
+            self.record_pull();
+        }
+
+        Ok(())
+    }
+
+    /// Failing to record the pull is only a warning as the pull itself
+    /// worked.
+    fn record_pull(&self) {
+        if let Err(err) = self.sync_log.record_pull() {
+            warn!("can not record when the datadir was synced: {:#}", err);
+        }
+    }
+
+    /// Warn if the datadir was not synced for longer than configured in
+    /// `warn_stale_after`. Only looks at the local repository and the state
+    /// of this machine so it is fast and never fails.
+    pub(crate) fn warn_if_stale(&self) {
+        let threshold = match &self.config.warn_stale_after {
+            Some(threshold) if !self.config.no_stale_check => threshold,
+            _ => return,
+        };
+
+        let threshold = match helper::parse_duration(threshold) {
+            Ok(threshold) => threshold,
+            Err(err) => {
+                warn!("can not parse warn_stale_after from the config: {:#}", err);
+                return;
+            }
+        };
+
+        let last_commit = self
+            .settings
+            .vcs
+            .as_ref()
+            .and_then(|vcs| vcs.last_commit_time(&self.datadir));
+
+        if let Some(age) = stale_for(
+            self.sync_log.last_pull(),
+            last_commit,
+            threshold,
+            Utc::now(),
+        ) {
+            warn!(
+                "datadir last synced {} ago, run `todust sync`",
+                helper::format_duration(age)
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{
+        changes::Changes,
+        tests::{
+            entry,
+            open,
+            temporary,
+        },
+    };
+    use std::{
+        path::Path,
+        process::Command,
+    };
+
+    fn at(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    #[test]
+    fn stale_only_after_the_threshold() {
+        let now = at("2024-05-10T12:00:00Z");
+        let day = Duration::days(1);
+
+        assert_eq!(stale_for(None, None, day, now), None);
+        assert_eq!(
+            stale_for(Some(at("2024-05-09T13:00:00Z")), None, day, now),
+            None
+        );
+        assert_eq!(
+            stale_for(Some(at("2024-05-09T12:00:00Z")), None, day, now),
+            None
+        );
+        assert_eq!(
+            stale_for(None, Some(at("2024-05-07T12:00:00Z")), day, now),
+            Some(Duration::days(3))
+        );
+    }
+
+    #[test]
+    fn newer_of_pull_and_commit_counts() {
+        let now = at("2024-05-10T12:00:00Z");
+        let day = Duration::days(1);
+        let old = Some(at("2024-05-01T12:00:00Z"));
+        let recent = Some(at("2024-05-10T06:00:00Z"));
+
+        assert_eq!(stale_for(old, recent, day, now), None);
+        assert_eq!(stale_for(recent, old, day, now), None);
+        assert_eq!(stale_for(old, old, day, now), Some(Duration::days(9)));
+    }
+
+    fn git(directory: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(args)
+            .current_dir(directory)
+            .status()
+            .unwrap();
+        assert!(status.success(), "git {:?}", args);
+    }
+
+    #[test]
+    fn sync_records_the_pull() {
+        let directory = tempfile::tempdir().unwrap();
+        let remote = directory.path().join("remote");
+        let datadir = directory.path().join("datadir");
+        std::fs::create_dir_all(&remote).unwrap();
+        git(&remote, &["init", "--quiet", "--bare"]);
+        git(directory.path(), &["clone", "--quiet", "remote", "datadir"]);
+
+        let store = open(
+            &datadir,
+            &directory.path().join("state"),
+            &Changes::default(),
+        );
+        store.add_entry(entry("first entry")).unwrap();
+
+        // The store of the tests never commits.
+        git(&datadir, &["add", "--all"]);
+        git(
+            &datadir,
+            &[
+                "-c",
+                "user.name=todust",
+                "-c",
+                "user.email=todust@example.com",
+                "commit",
+                "--quiet",
+                "--message",
+                "first entry",
+            ],
+        );
+        git(
+            &datadir,
+            &["push", "--quiet", "--set-upstream", "origin", "HEAD"],
+        );
+        assert_eq!(store.sync_log.last_pull(), None);
+
+        let before = Utc::now();
+        store.sync().unwrap();
+        let pulled = store.sync_log.last_pull().unwrap();
+        assert!(pulled >= before && pulled <= Utc::now());
+    }
+
+    #[test]
+    fn failed_sync_records_nothing() {
+        let (_directory, store) = temporary();
+        store.add_entry(entry("first entry")).unwrap();
+
+        // The datadir is not a repository so there is nothing to pull.
+        assert!(store.sync().is_err());
+        assert_eq!(store.sync_log.last_pull(), None);
+    }
+}
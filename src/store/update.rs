@@ -0,0 +1,1128 @@
+use crate::{
+    checklist,
+    entry::{
+        Entry,
+        EntrySource,
+        Metadata,
+        MetadataChange,
+    },
+    helper,
+    helper::{
+        confirm,
+        ConfirmDone,
+    },
+    store::{
+        MergeCounts,
+        Store,
+        UpdateOutcome,
+    },
+    time_context::TimeContext,
+};
+use anyhow::{
+    bail,
+    Context,
+    Error,
+};
+use chrono::{
+    DateTime,
+    Utc,
+};
+use log::{
+    debug,
+    trace,
+};
+use std::{
+    collections::BTreeMap,
+    fs,
+};
+use uuid::Uuid;
+
+impl Store {
+    pub(crate) fn add_entry(&self, entry: Entry) -> Result<(), Error> {
+        self.check_quotas(&[&entry.metadata])?;
+
+        let previous = self.previous_metadata(&entry)?;
+
+        self.write_entry_text(&entry)
+            .context("can not write entry text to file")?;
+
+        self.append_metadata(&entry.metadata)?;
+
+        self.commit(&format!("added entry with id {}", entry.metadata.uuid))?;
+
+        self.notify_changed(previous.as_ref(), &entry);
+
+        Ok(())
+    }
+
+    /// Take the entry out of this store after it was added to the store with
+    /// the name. The text is removed and a row records where the entry went
+    /// so it is not shown anymore on any machine.
+    pub(crate) fn remove_moved_entry(&self, entry: &Entry, store: &str) -> Result<(), Error> {
+        let metadata = entry
+            .metadata
+            .changed()
+            .moved_to(store.to_string())
+            .build()?;
+
+        self.append_metadata(&metadata)?;
+        self.remove_entry_file(&metadata.uuid)?;
+
+        self.commit(&format!(
+            "moved entry with id {} to store {}",
+            metadata.uuid, store
+        ))?;
+
+        Ok(())
+    }
+
+    /// Add multiple entries with a single commit.
+    pub(crate) fn add_entries(&self, entries: &[Entry]) -> Result<(), Error> {
+        self.check_quotas(
+            &entries
+                .iter()
+                .map(|entry| &entry.metadata)
+                .collect::<Vec<_>>(),
+        )?;
+
+        for entry in entries {
+            self.write_entry_text(entry)
+                .context("can not write entry text to file")?;
+
+            self.append_metadata(&entry.metadata)?;
+        }
+
+        self.commit(&format!("added {} entries", entries.len()))?;
+
+        for entry in entries {
+            self.notify_changed(None, entry);
+        }
+
+        Ok(())
+    }
+
+    /// Write the entries of another store into this one with a single commit.
+    /// Their uuids, timestamps and last change are kept. Entries this store
+    /// does not have yet are added. Entries it has are replaced if the other
+    /// copy was changed later and skipped otherwise.
+    pub(crate) fn merge_entries(&self, entries: Vec<Entry>) -> Result<MergeCounts, Error> {
+        let current = self
+            .index
+            .metadata_most_recent()
+            .context("can not get metadata from active index")?
+            .into_iter()
+            .map(|metadata| (metadata.uuid, metadata))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut counts = MergeCounts::default();
+        let mut changed = Vec::new();
+
+        for entry in entries {
+            match current.get(&entry.metadata.uuid) {
+                None => counts.imported += 1,
+                Some(previous) if entry.metadata.last_change > previous.last_change => {
+                    counts.merged += 1
+                }
+                Some(_) => {
+                    counts.skipped += 1;
+                    continue;
+                }
+            }
+
+            changed.push((current.get(&entry.metadata.uuid), entry));
+        }
+
+        self.check_quotas(
+            &changed
+                .iter()
+                .map(|(_, entry)| &entry.metadata)
+                .collect::<Vec<_>>(),
+        )?;
+
+        for (_, entry) in &changed {
+            self.write_entry_text(entry)
+                .context("can not write entry text to file")?;
+
+            self.append_metadata(&entry.metadata)?;
+        }
+
+        if changed.is_empty() {
+            return Ok(counts);
+        }
+
+        self.commit(&format!("imported {} entries", changed.len()))?;
+
+        for (previous, entry) in &changed {
+            self.notify_changed(*previous, entry);
+        }
+
+        Ok(counts)
+    }
+
+    /// Mark the entry as done after asking for confirmation as configured
+    /// with `confirm_done`. If the entry still has unchecked checklist items
+    /// they are carried over to a new entry when `carry_over` is set or the
+    /// user agrees to it. With `yes` nothing is asked.
+    pub(crate) fn entry_done(
+        &self,
+        entry_id: usize,
+        project: &str,
+        carry_over: bool,
+        yes: bool,
+    ) -> Result<(), Error> {
+        // TODO: Change this to only fetch the metadata as we dont need to touch the
+        // entry text.
+        let entry = self
+            .get_entry_by_id(entry_id, project)
+            .context("can not get entry from id")?;
+
+        let today = TimeContext::from_config(&self.config).today();
+        let overdue = match entry.metadata.days_overdue(today) {
+            Some(1) => "\nthis entry was due 1 day ago".to_string(),
+            Some(days) => format!("\nthis entry was due {} days ago", days),
+            None => String::new(),
+        };
+
+        // TODO: This should be handled in main not by the store.
+        let message = format!(
+            "do you want to finish this entry?:\n{:.*}{}",
+            helper::PROMPT_SUMMARY_WIDTH,
+            entry,
+            overdue
+        );
+        let mode = if yes {
+            ConfirmDone::Off
+        } else {
+            self.config.confirm_done
+        };
+
+        if !helper::confirm_done(
+            mode,
+            self.config.interactivity,
+            &message,
+            &entry.text,
+            entry.metadata.uuid,
+        )? {
+            bail!("not finishing task then")
+        }
+
+        let unchecked = checklist::unchecked_count(&entry.text);
+        let carry_over = carry_over
+            || (unchecked > 0
+                && mode != ConfirmDone::Off
+                && confirm(
+                    &format!(
+                        "this entry has {} unchecked checklist items, do you want to carry them \
+                         over to a new entry?",
+                        unchecked
+                    ),
+                    true,
+                )?);
+
+        self.finish_entry(entry, carry_over, EntrySource::Cli)?;
+
+        Ok(())
+    }
+
+    /// Mark the entry as done. Returns the uuid of the new entry if unchecked
+    /// checklist items were carried over.
+    pub(crate) fn entry_done_by_uuid(
+        &self,
+        uuid: Uuid,
+        carry_over: bool,
+    ) -> Result<Option<Uuid>, Error> {
+        let entry = self
+            .get_entry_by_uuid(&uuid)
+            .context("can not get entry from uuid")?;
+
+        self.finish_entry(entry, carry_over, EntrySource::Web)
+    }
+
+    /// Mark the entry as done and create a follow-up entry with its unchecked
+    /// checklist items if `carry_over` is set and there are any. Both are
+    /// recorded in a single commit.
+    fn finish_entry(
+        &self,
+        entry: Entry,
+        carry_over: bool,
+        source: EntrySource,
+    ) -> Result<Option<Uuid>, Error> {
+        let follow_up = match checklist::carry_over(&entry.text) {
+            Some(text) if carry_over => {
+                let text = format!(
+                    "{}\nCarried over from entry {}.\n",
+                    text, entry.metadata.uuid
+                );
+
+                Some(Entry::builder(text, entry.metadata.project.clone(), source).build()?)
+            }
+            _ => None,
+        };
+
+        if let Some(follow_up) = &follow_up {
+            self.check_quotas(&[&follow_up.metadata])?;
+
+            self.write_entry_text(follow_up)
+                .context("can not write text of follow-up entry to file")?;
+
+            self.append_metadata(&follow_up.metadata)?;
+        }
+
+        let new = entry.metadata.changed().finish().build()?;
+
+        trace!("new: {:#?}", new);
+
+        self.append_metadata(&new)
+            .context("can not add entry to done index")?;
+
+        match &follow_up {
+            Some(follow_up) => self.commit(&format!(
+                "marked entry with id {} as done and carried over its unchecked items to entry \
+                 with id {}",
+                entry.metadata.uuid, follow_up.metadata.uuid
+            ))?,
+            None => self.commit(&format!(
+                "marked entry with id {} as done",
+                entry.metadata.uuid
+            ))?,
+        };
+
+        self.clear_focus_of(&entry);
+
+        if let Some(follow_up) = &follow_up {
+            self.notify_changed(None, follow_up);
+        }
+
+        self.notify_changed(
+            Some(&entry.metadata),
+            &Entry {
+                text: entry.text.clone(),
+                metadata: new,
+            },
+        );
+
+        Ok(follow_up.map(|follow_up| follow_up.metadata.uuid))
+    }
+
+    pub(crate) fn entry_active_by_uuid(&self, uuid: Uuid) -> Result<(), Error> {
+        let entry = self
+            .get_entry_by_uuid(&uuid)
+            .context("can not get entry from uuid")?;
+
+        let new = entry.metadata.changed().reactivate().build()?;
+
+        self.append_metadata(&new)
+            .context("can not add entry to active index")?;
+
+        self.commit(&format!(
+            "marked entry with id {} as active",
+            entry.metadata.uuid
+        ))?;
+
+        self.notify_changed(
+            Some(&entry.metadata),
+            &Entry {
+                text: entry.text.clone(),
+                metadata: new,
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Fail with `QuotaExceeded` if writing the metadata would go over one of
+    /// the configured quotas. The index is only read if a quota is set.
+    fn check_quotas(&self, changes: &[&Metadata]) -> Result<(), Error> {
+        if !self.config.quotas.is_enabled() {
+            return Ok(());
+        }
+
+        let current = self
+            .index
+            .metadata_most_recent()
+            .context("can not get metadata from active index")?;
+
+        self.config.quotas.check(&current, changes)?;
+
+        Ok(())
+    }
+
+    /// Most recent metadata of the entry before it gets changed. Only looked
+    /// up when webhooks or hooks need to know what changed.
+    fn previous_metadata(&self, entry: &Entry) -> Result<Option<Metadata>, Error> {
+        if !self.webhooks.is_enabled() && !self.hooks.is_enabled() {
+            return Ok(None);
+        }
+
+        let metadata = self
+            .index
+            .metadata_most_recent()
+            .context("can not get metadata from active index")?
+            .into_iter()
+            .find(|metadata| metadata.uuid == entry.metadata.uuid);
+
+        Ok(metadata)
+    }
+
+    /// Update the entry only if it was not changed since
+    /// `expected_last_change`. Returns the current entry if it was changed
+    /// in the meantime.
+    pub(crate) fn update_entry_if_unchanged(
+        &self,
+        entry: Entry,
+        expected_last_change: DateTime<Utc>,
+    ) -> Result<UpdateOutcome, Error> {
+        let current = self
+            .get_entry_by_uuid(&entry.metadata.uuid)
+            .context("can not get current entry")?;
+
+        if current.metadata.last_change != expected_last_change {
+            debug!(
+                "entry {} was changed at {} but expected {}",
+                current.metadata.uuid, current.metadata.last_change, expected_last_change
+            );
+
+            return Ok(UpdateOutcome::Conflict(Box::new(current)));
+        }
+
+        self.update_entry(entry)?;
+
+        Ok(UpdateOutcome::Updated)
+    }
+
+    /// Change only the metadata of the entry if it was not changed since
+    /// `expected_last_change`. The change is applied to the current metadata
+    /// and the text is not written so a stale copy of the entry can not bring
+    /// back old text. Returns the current entry if it was changed in the
+    /// meantime.
+    pub(crate) fn update_metadata_if_unchanged<F>(
+        &self,
+        uuid: &Uuid,
+        expected_last_change: DateTime<Utc>,
+        change: F,
+    ) -> Result<UpdateOutcome, Error>
+    where
+        F: FnOnce(MetadataChange) -> MetadataChange,
+    {
+        let current = self
+            .get_entry_by_uuid(uuid)
+            .context("can not get current entry")?;
+
+        if current.metadata.last_change != expected_last_change {
+            debug!(
+                "entry {} was changed at {} but expected {}",
+                uuid, current.metadata.last_change, expected_last_change
+            );
+
+            return Ok(UpdateOutcome::Conflict(Box::new(current)));
+        }
+
+        let metadata = change(current.metadata.changed()).build()?;
+
+        let unchanged_metadata = Metadata {
+            last_change: current.metadata.last_change,
+            revision: current.metadata.revision,
+            ..metadata.clone()
+        };
+        if unchanged_metadata == current.metadata {
+            debug!("metadata of entry {} did not change", uuid);
+            return Ok(UpdateOutcome::Updated);
+        }
+
+        self.check_quotas(&[&metadata])?;
+
+        self.append_metadata(&metadata)?;
+
+        self.commit(&format!("updated metadata of entry with id {}", uuid))?;
+
+        let entry = Entry {
+            metadata,
+            text: current.text.clone(),
+        };
+        self.notify_changed(Some(&current.metadata), &entry);
+
+        Ok(UpdateOutcome::Updated)
+    }
+
+    /// Write the changed entry. The last change of the entry is always set to
+    /// now and the revision is increased so the new metadata row is preferred
+    /// over the old ones. Nothing is written if neither the text nor the
+    /// metadata changed.
+    pub(crate) fn update_entry(&self, entry: Entry) -> Result<(), Error> {
+        self.update_entries(vec![entry]).map(|_| ())
+    }
+
+    /// Write multiple changed entries with a single commit. Works like
+    /// `update_entry` for each entry. Returns how many entries changed.
+    pub(crate) fn update_entries(&self, entries: Vec<Entry>) -> Result<usize, Error> {
+        self.update_entries_as(entries, "updated")
+    }
+
+    /// Write the tidied text of the entries. Works like `update_entries` but
+    /// names the change in the commit message.
+    pub(crate) fn tidy_entries(&self, entries: Vec<Entry>) -> Result<usize, Error> {
+        self.update_entries_as(entries, "tidied")
+    }
+
+    /// Write the entries that changed and commit them with a message that
+    /// starts with `action`.
+    pub(super) fn update_entries_as(
+        &self,
+        entries: Vec<Entry>,
+        action: &str,
+    ) -> Result<usize, Error> {
+        let current = self
+            .index
+            .metadata_most_recent()
+            .context("can not get metadata from active index")?;
+
+        self.config.quotas.check(
+            &current,
+            &entries
+                .iter()
+                .map(|entry| &entry.metadata)
+                .collect::<Vec<_>>(),
+        )?;
+
+        let mut changed = Vec::new();
+
+        for entry in entries {
+            let previous = current
+                .iter()
+                .find(|metadata| metadata.uuid == entry.metadata.uuid)
+                .cloned();
+
+            let previous_text = self
+                .find_entry_file(&entry.metadata.uuid)
+                .and_then(|entry_file| fs::read_to_string(entry_file).ok());
+
+            if let Some(previous) = &previous {
+                let unchanged_metadata = Metadata {
+                    last_change: previous.last_change,
+                    revision: previous.revision,
+                    line_count: previous.line_count,
+                    created: previous.created,
+                    ..entry.metadata.clone()
+                };
+
+                if unchanged_metadata == *previous
+                    && previous_text.as_deref() == Some(entry.text.as_str())
+                {
+                    debug!("entry {} did not change", entry.metadata.uuid);
+                    continue;
+                }
+            }
+
+            let line_count = entry.line_count();
+            let metadata = match &previous {
+                // The row follows the most recent row whether the entry was
+                // changed through `Metadata::changed` or not. The creation time
+                // can not be moved either.
+                Some(previous) => Metadata {
+                    last_change: previous.last_change,
+                    revision: previous.revision,
+                    created: previous.created,
+                    ..entry.metadata
+                }
+                .changed()
+                .line_count(line_count)
+                .build()?,
+                None => Metadata {
+                    last_change: Utc::now(),
+                    revision: 0,
+                    line_count: Some(line_count),
+                    ..entry.metadata
+                },
+            };
+
+            let entry = Entry {
+                metadata,
+                text: entry.text,
+            };
+
+            self.write_entry_text(&entry)
+                .context("can not write entry text to file")?;
+
+            self.append_metadata(&entry.metadata)?;
+
+            changed.push((previous, entry));
+        }
+
+        if changed.is_empty() {
+            return Ok(0);
+        }
+
+        let message = match changed.as_slice() {
+            [(_, entry)] => format!("{} entry with id {}", action, entry.metadata.uuid),
+            _ => format!("{} {} entries", action, changed.len()),
+        };
+        self.commit(&message)?;
+
+        for (previous, entry) in &changed {
+            self.notify_changed(previous.as_ref(), entry);
+        }
+
+        Ok(changed.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        config::Config,
+        entry::{
+            Entry,
+            EntrySource,
+            Metadata,
+        },
+        store::{
+            changes::Changes,
+            quota::QuotaExceeded,
+            tests::{
+                entry,
+                open,
+                temporary,
+                temporary_with,
+            },
+            UpdateOutcome,
+        },
+    };
+    use chrono::{
+        Duration,
+        NaiveDate,
+        Utc,
+    };
+    use std::fs;
+
+    #[test]
+    fn text_only_edit_appends_a_newer_row() {
+        let (_directory, store) = temporary();
+        let added = entry("first text");
+        store.add_entry(added.clone()).unwrap();
+
+        let mut edited = store.get_entry_by_uuid(&added.metadata.uuid).unwrap();
+        edited.text = "second text".to_string();
+        store.update_entry(edited).unwrap();
+
+        let current = store.get_entry_by_uuid(&added.metadata.uuid).unwrap();
+        assert_eq!(current.text, "second text");
+        assert_eq!(current.metadata.revision, 1);
+        assert!(current.metadata.last_change > added.metadata.last_change);
+    }
+
+    #[test]
+    fn moved_entry_is_in_the_target_and_tombstoned_in_the_source() {
+        let (_source_directory, source) = temporary();
+        let (_target_directory, target) = temporary();
+        let before = Utc::now() - Duration::seconds(1);
+        let added = entry("filed in the wrong store");
+        let uuid = added.metadata.uuid;
+        source.add_entry(added.clone()).unwrap();
+        let source_file = source.find_entry_file(&uuid).unwrap();
+
+        let moved = Entry {
+            metadata: added
+                .metadata
+                .changed()
+                .project("inbox".to_string())
+                .build()
+                .unwrap(),
+            ..added.clone()
+        };
+        target.add_entry(moved).unwrap();
+        source.remove_moved_entry(&added, "personal").unwrap();
+
+        let found = target.get_entry_by_uuid(&uuid).unwrap();
+        assert_eq!(found.text, "filed in the wrong store");
+        assert_eq!(found.metadata.project, "inbox");
+        assert_eq!(found.metadata.created, added.metadata.created);
+        assert!(found.metadata.moved_to.is_none());
+
+        assert!(!source.has_entry(&uuid).unwrap());
+        assert!(source.get_entry_by_uuid(&uuid).is_err());
+        assert!(!source_file.exists());
+        assert!(source
+            .get_active_entries("default")
+            .unwrap()
+            .into_iter()
+            .next()
+            .is_none());
+
+        let removed = source.get_removed_since(before).unwrap();
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].uuid, uuid);
+        assert_eq!(removed[0].moved_to.as_deref(), Some("personal"));
+    }
+
+    #[test]
+    fn creation_is_kept_through_done_move_and_restart() {
+        let (_directory, store) = temporary();
+        let mut added = entry("text");
+        let created = Utc::now() - Duration::days(10);
+        added.metadata.created = Some(created);
+        added.metadata.started = created;
+        let uuid = added.metadata.uuid;
+        store.add_entry(added).unwrap();
+
+        store.entry_done_by_uuid(uuid, false).unwrap();
+        store.entry_active_by_uuid(uuid).unwrap();
+        assert_eq!(
+            store.get_entry_by_uuid(&uuid).unwrap().metadata.created,
+            Some(created)
+        );
+
+        let current = store.get_entry_by_uuid(&uuid).unwrap();
+        let moved = Entry {
+            metadata: current
+                .metadata
+                .changed()
+                .project("work".to_string())
+                .build()
+                .unwrap(),
+            ..current
+        };
+        store.update_entry(moved).unwrap();
+
+        let current = store.get_entry_by_uuid(&uuid).unwrap();
+        let restarted = Entry {
+            text: "edited text".to_string(),
+            metadata: current
+                .metadata
+                .changed()
+                .started(Utc::now())
+                .build()
+                .unwrap(),
+        };
+        store.update_entry(restarted).unwrap();
+
+        let current = store.get_entry_by_uuid(&uuid).unwrap();
+        assert_eq!(current.metadata.project, "work");
+        assert_eq!(current.metadata.created, Some(created));
+        assert!(current.metadata.started > created + Duration::days(9));
+        assert!(current.age() >= Duration::days(10));
+        assert!(current.active_duration() < Duration::days(1));
+    }
+
+    #[test]
+    fn adding_over_a_quota_writes_nothing() {
+        let (_directory, store) = temporary_with(Config {
+            quotas: toml::from_str("max_total_entries = 1").unwrap(),
+            ..Config::default()
+        });
+        store.add_entry(entry("first")).unwrap();
+
+        let refused = entry("second");
+        let err = store.add_entry(refused.clone()).unwrap_err();
+        let exceeded = err.downcast_ref::<QuotaExceeded>().unwrap();
+        assert_eq!(exceeded.0.quota, "max_total_entries");
+        assert_eq!(exceeded.0.count, 2);
+
+        assert!(store.add_entries(&[entry("third")]).is_err());
+        assert!(!store.has_entry(&refused.metadata.uuid).unwrap());
+        assert!(store.find_entry_file(&refused.metadata.uuid).is_none());
+        assert_eq!(store.get_active_entries("default").unwrap().len(), 1);
+    }
+
+    #[test]
+    fn moving_into_a_full_project_keeps_the_entry() {
+        let (_directory, store) = temporary_with(Config {
+            quotas: toml::from_str("max_entries_per_project = 1").unwrap(),
+            ..Config::default()
+        });
+        let mut full = entry("in work");
+        full.metadata.project = "work".to_string();
+        store.add_entry(full).unwrap();
+        let moving = entry("in default");
+        let uuid = moving.metadata.uuid;
+        store.add_entry(moving.clone()).unwrap();
+
+        let moved = Entry {
+            metadata: moving
+                .metadata
+                .changed()
+                .project("work".to_string())
+                .build()
+                .unwrap(),
+            ..moving
+        };
+        let err = store.update_entries(vec![moved]).unwrap_err();
+        let exceeded = err.downcast_ref::<QuotaExceeded>().unwrap();
+        assert_eq!(exceeded.0.project.as_deref(), Some("work"));
+
+        let current = store.get_entry_by_uuid(&uuid).unwrap();
+        assert_eq!(current.metadata.project, "default");
+
+        // Changing the entry in its own project is still fine.
+        let edited = Entry {
+            text: "edited".to_string(),
+            ..current
+        };
+        assert_eq!(store.update_entries(vec![edited]).unwrap(), 1);
+    }
+
+    #[test]
+    fn unchanged_entry_is_not_written() {
+        let (_directory, store) = temporary();
+        let added = entry("text");
+        store.add_entry(added.clone()).unwrap();
+
+        let current = store.get_entry_by_uuid(&added.metadata.uuid).unwrap();
+        assert_eq!(store.update_entries(vec![current.clone()]).unwrap(), 0);
+
+        let after = store.get_entry_by_uuid(&added.metadata.uuid).unwrap();
+        assert_eq!(after, current);
+    }
+
+    #[test]
+    fn edits_of_two_machines_agree_on_the_latest_entry() {
+        let directory = tempfile::tempdir().unwrap();
+        let datadir = directory.path().join("datadir");
+        fs::create_dir_all(&datadir).unwrap();
+
+        // Both machines write their own index files into the same datadir
+        // like after merging their changes with git.
+        let laptop = open(
+            &datadir,
+            &directory.path().join("laptop"),
+            &Changes::default(),
+        );
+        let desktop = open(
+            &datadir,
+            &directory.path().join("desktop"),
+            &Changes::default(),
+        );
+
+        let added = entry("first text");
+        laptop.add_entry(added.clone()).unwrap();
+
+        let mut edited = desktop.get_entry_by_uuid(&added.metadata.uuid).unwrap();
+        edited.text = "second text".to_string();
+        desktop.update_entry(edited).unwrap();
+
+        let due = NaiveDate::from_ymd_opt(2026, 12, 1);
+        let current = laptop.get_entry_by_uuid(&added.metadata.uuid).unwrap();
+        let with_due = Entry {
+            metadata: current.metadata.changed().due(due).build().unwrap(),
+            text: current.text,
+        };
+        laptop.update_entry(with_due).unwrap();
+
+        let on_laptop = laptop.get_entry_by_uuid(&added.metadata.uuid).unwrap();
+        let on_desktop = desktop.get_entry_by_uuid(&added.metadata.uuid).unwrap();
+
+        assert_eq!(on_laptop, on_desktop);
+        assert_eq!(on_laptop.text, "second text");
+        assert_eq!(on_laptop.metadata.due, due);
+        assert_eq!(on_laptop.metadata.revision, 2);
+    }
+
+    #[test]
+    fn sequential_changes_on_another_machine_are_not_conflicts() {
+        let directory = tempfile::tempdir().unwrap();
+        let datadir = directory.path().join("datadir");
+        fs::create_dir_all(&datadir).unwrap();
+
+        let laptop = open(
+            &datadir,
+            &directory.path().join("laptop"),
+            &Changes::default(),
+        );
+        let desktop = open(
+            &datadir,
+            &directory.path().join("desktop"),
+            &Changes::default(),
+        );
+
+        let added = entry("text");
+        let uuid = added.metadata.uuid;
+        laptop.add_entry(added).unwrap();
+
+        desktop.entry_done_by_uuid(uuid, false).unwrap();
+        assert!(laptop.index.concurrent_changes().unwrap().is_empty());
+
+        laptop.entry_active_by_uuid(uuid).unwrap();
+        assert!(desktop.index.concurrent_changes().unwrap().is_empty());
+
+        let current = desktop.get_entry_by_uuid(&uuid).unwrap();
+        desktop
+            .update_metadata_if_unchanged(&uuid, current.metadata.last_change, |change| {
+                change.project("work".to_string())
+            })
+            .unwrap();
+
+        let on_laptop = laptop.get_entry_by_uuid(&uuid).unwrap();
+        assert!(laptop.index.concurrent_changes().unwrap().is_empty());
+        assert!(on_laptop.metadata.is_active());
+        assert_eq!(on_laptop.metadata.project, "work");
+        assert_eq!(on_laptop.metadata.revision, 3);
+    }
+
+    #[test]
+    fn unchanged_entry_is_updated() {
+        let (_directory, store) = temporary();
+        let added = entry("first text");
+        store.add_entry(added.clone()).unwrap();
+
+        let current = store.get_entry_by_uuid(&added.metadata.uuid).unwrap();
+        let edited = Entry {
+            text: "second text".to_string(),
+            ..current.clone()
+        };
+
+        let outcome = store
+            .update_entry_if_unchanged(edited, current.metadata.last_change)
+            .unwrap();
+        assert!(matches!(outcome, UpdateOutcome::Updated));
+        assert_eq!(
+            store.get_entry_by_uuid(&added.metadata.uuid).unwrap().text,
+            "second text"
+        );
+    }
+
+    #[test]
+    fn entry_changed_in_the_meantime_is_a_conflict() {
+        let (_directory, store) = temporary();
+        let added = entry("first text");
+        store.add_entry(added.clone()).unwrap();
+
+        let stale = store.get_entry_by_uuid(&added.metadata.uuid).unwrap();
+        store
+            .update_entry(Entry {
+                text: "their text".to_string(),
+                ..stale.clone()
+            })
+            .unwrap();
+
+        let outcome = store
+            .update_entry_if_unchanged(
+                Entry {
+                    text: "my text".to_string(),
+                    ..stale.clone()
+                },
+                stale.metadata.last_change,
+            )
+            .unwrap();
+
+        match outcome {
+            UpdateOutcome::Conflict(current) => assert_eq!(current.text, "their text"),
+            UpdateOutcome::Updated => panic!("stale entry was written"),
+        }
+        assert_eq!(
+            store.get_entry_by_uuid(&added.metadata.uuid).unwrap().text,
+            "their text"
+        );
+    }
+
+    #[test]
+    fn unchanged_metadata_is_updated() {
+        let (_directory, store) = temporary();
+        let added = entry("text");
+        let uuid = added.metadata.uuid;
+        store.add_entry(added).unwrap();
+
+        let current = store.get_entry_by_uuid(&uuid).unwrap();
+        let outcome = store
+            .update_metadata_if_unchanged(&uuid, current.metadata.last_change, |change| {
+                change.project("work".to_string())
+            })
+            .unwrap();
+        assert!(matches!(outcome, UpdateOutcome::Updated));
+
+        let updated = store.get_entry_by_uuid(&uuid).unwrap();
+        assert_eq!(updated.metadata.project, "work");
+        assert_eq!(updated.metadata.revision, current.metadata.revision + 1);
+        assert_eq!(updated.text, "text");
+    }
+
+    #[test]
+    fn metadata_changed_in_the_meantime_is_a_conflict() {
+        let (_directory, store) = temporary();
+        let added = entry("text");
+        let uuid = added.metadata.uuid;
+        store.add_entry(added).unwrap();
+
+        let stale = store.get_entry_by_uuid(&uuid).unwrap();
+        store
+            .update_metadata_if_unchanged(&uuid, stale.metadata.last_change, |change| {
+                change.project("theirs".to_string())
+            })
+            .unwrap();
+
+        let outcome = store
+            .update_metadata_if_unchanged(&uuid, stale.metadata.last_change, |change| {
+                change.project("mine".to_string())
+            })
+            .unwrap();
+
+        match outcome {
+            UpdateOutcome::Conflict(current) => assert_eq!(current.metadata.project, "theirs"),
+            UpdateOutcome::Updated => panic!("stale metadata was written"),
+        }
+        assert_eq!(
+            store.get_entry_by_uuid(&uuid).unwrap().metadata.project,
+            "theirs"
+        );
+    }
+
+    #[test]
+    fn edit_after_a_row_from_the_future_stays_the_most_recent() {
+        let (_directory, store) = temporary();
+        let added = entry("first text");
+        store.add_entry(added.clone()).unwrap();
+
+        // Row written while the clock was an hour ahead.
+        let future = Metadata {
+            last_change: Utc::now() + Duration::hours(1),
+            revision: 1,
+            ..store
+                .get_entry_by_uuid(&added.metadata.uuid)
+                .unwrap()
+                .metadata
+        };
+        store.index.metadata_add(&future).unwrap();
+
+        let mut edited = store.get_entry_by_uuid(&added.metadata.uuid).unwrap();
+        edited.text = "second text".to_string();
+        edited.metadata.due = NaiveDate::from_ymd_opt(2026, 10, 20);
+        store.update_entry(edited).unwrap();
+
+        let current = store.get_entry_by_uuid(&added.metadata.uuid).unwrap();
+        assert_eq!(current.text, "second text");
+        assert_eq!(current.metadata.due, NaiveDate::from_ymd_opt(2026, 10, 20));
+        assert_eq!(current.metadata.revision, 2);
+        assert_eq!(
+            current.metadata.last_change,
+            future.last_change + Duration::milliseconds(1)
+        );
+        assert!(store.index.clock_skewed_uuids().unwrap().is_empty());
+    }
+
+    #[test]
+    fn carried_over_entry_is_from_the_web() {
+        let (_directory, store) = temporary();
+        let added = entry("== Release\n\n* [x] tag\n* [ ] publish\n");
+        store.add_entry(added.clone()).unwrap();
+
+        let follow_up = store
+            .entry_done_by_uuid(added.metadata.uuid, true)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            store
+                .get_entry_by_uuid(&follow_up)
+                .unwrap()
+                .metadata
+                .source
+                .as_deref(),
+            Some("web")
+        );
+    }
+
+    #[test]
+    fn carry_over_keeps_the_unchecked_items() {
+        let (_directory, store) = temporary();
+        let added = entry("== Release\n\n* [x] tag\n* [ ] publish\n");
+        store.add_entry(added.clone()).unwrap();
+
+        let follow_up = store
+            .entry_done_by_uuid(added.metadata.uuid, true)
+            .unwrap()
+            .unwrap();
+
+        assert!(store
+            .get_entry_by_uuid(&added.metadata.uuid)
+            .unwrap()
+            .is_done());
+        assert_eq!(
+            store.get_entry_by_uuid(&follow_up).unwrap().text,
+            format!(
+                "== Release\n\n* [ ] publish\n\nCarried over from entry {}.\n",
+                added.metadata.uuid
+            )
+        );
+    }
+
+    #[test]
+    fn finishing_without_carry_over_adds_no_entry() {
+        let (_directory, store) = temporary();
+        let added = entry("* [ ] publish\n");
+        store.add_entry(added.clone()).unwrap();
+
+        assert_eq!(
+            store
+                .entry_done_by_uuid(added.metadata.uuid, false)
+                .unwrap(),
+            None
+        );
+        assert_eq!(store.get_active_entries("default").unwrap().len(), 0);
+
+        let plain = entry("no checklist");
+        store.add_entry(plain.clone()).unwrap();
+        assert_eq!(
+            store.entry_done_by_uuid(plain.metadata.uuid, true).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn carry_over_commit_names_both_entries() {
+        let directory = tempfile::tempdir().unwrap();
+        let datadir = directory.path().join("datadir");
+        let state_home = directory.path().join("state");
+        fs::create_dir_all(&datadir).unwrap();
+
+        let added = entry("* [ ] publish\n");
+        open(&datadir, &state_home, &Changes::default())
+            .add_entry(added.clone())
+            .unwrap();
+
+        let changes = Changes::dry_run();
+        let follow_up = open(&datadir, &state_home, &changes)
+            .entry_done_by_uuid(added.metadata.uuid, true)
+            .unwrap()
+            .unwrap();
+
+        let commit = changes
+            .take_plan()
+            .into_iter()
+            .find(|change| change.starts_with("commit with message"))
+            .unwrap();
+        assert!(commit.contains(&added.metadata.uuid.to_string()));
+        assert!(commit.contains(&follow_up.to_string()));
+    }
+
+    #[test]
+    fn edits_update_the_line_count() {
+        let (_directory, store) = temporary();
+        let added = Entry::builder(
+            "one line".to_string(),
+            "default".to_string(),
+            EntrySource::Cli,
+        )
+        .build()
+        .unwrap();
+        let uuid = added.metadata.uuid;
+        store.add_entry(added).unwrap();
+        assert_eq!(
+            store.get_entry_by_uuid(&uuid).unwrap().metadata.line_count,
+            Some(1)
+        );
+
+        let mut edited = store.get_entry_by_uuid(&uuid).unwrap();
+        edited.text = "== Heading\n\nnotes\n".to_string();
+        store.update_entry(edited).unwrap();
+        assert_eq!(
+            store.get_entry_by_uuid(&uuid).unwrap().metadata.line_count,
+            Some(3)
+        );
+
+        let mut edited = store.get_entry_by_uuid(&uuid).unwrap();
+        edited.metadata.due = NaiveDate::from_ymd_opt(2026, 10, 20);
+        store.update_entry(edited).unwrap();
+        assert_eq!(
+            store.get_entry_by_uuid(&uuid).unwrap().metadata.line_count,
+            Some(3)
+        );
+    }
+}
@@ -1,3 +1,8 @@
+use chrono::{
+    DateTime,
+    TimeZone,
+    Utc,
+};
 use log::debug;
 use serde::{
     Deserialize,
@@ -6,6 +11,7 @@ use serde::{
 use std::{
     fmt,
     path::Path,
+    process::Command,
 };
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -29,6 +35,16 @@ pub(crate) struct VcsConfig {
     autopush: bool,
 }
 
+impl VcsConfig {
+    pub(super) fn autocommit(&self) -> bool {
+        self.autocommit
+    }
+
+    pub(super) fn autopull(&self) -> bool {
+        self.autopull
+    }
+}
+
 impl Default for VcsConfig {
     fn default() -> Self {
         Self {
@@ -40,17 +56,93 @@ impl Default for VcsConfig {
 }
 
 impl VcsSettings {
+    pub(super) fn vcs_type(&self) -> &VcsType {
+        &self.vcs_type
+    }
+
+    /// Id of the commit the repository is at. `None` if it can not be read,
+    /// for example because nothing was committed yet.
+    pub(super) fn head<P: AsRef<Path>>(&self, repo_path: P) -> Option<String> {
+        match self.vcs_type {
+            VcsType::Git => githelper::get_current_commitid_for_repo(repo_path.as_ref())
+                .ok()
+                .map(|id| id.trim().to_string()),
+        }
+    }
+
+    /// Time of the last commit that touched the datadir. Only reads the local
+    /// repository. `None` if it can not be read, for example because nothing
+    /// was committed yet.
+    pub(super) fn last_commit_time<P: AsRef<Path>>(&self, repo_path: P) -> Option<DateTime<Utc>> {
+        match self.vcs_type {
+            VcsType::Git => {
+                let output = Command::new("git")
+                    .args(["log", "-1", "--format=%ct", "--", "."])
+                    .current_dir(repo_path.as_ref())
+                    .output()
+                    .ok()?;
+
+                if !output.status.success() {
+                    return None;
+                }
+
+                let seconds = String::from_utf8_lossy(&output.stdout)
+                    .trim()
+                    .parse()
+                    .ok()?;
+
+                Utc.timestamp_opt(seconds, 0).single()
+            }
+        }
+    }
+
+    /// Pull the changes of the other machines and push the local ones.
+    pub(super) fn sync<P: AsRef<Path>>(&self, repo_path: P) -> Result<(), VcsSettingsError> {
+        match self.vcs_type {
+            VcsType::Git => {
+                debug!("pulling changes from origin");
+                githelper::pull(repo_path.as_ref()).map_err(VcsSettingsError::Pull)?;
+
+                debug!("pushing changes to origin");
+                githelper::push(repo_path.as_ref()).map_err(VcsSettingsError::Push)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Whether the datadir is the root of a repository.
+    pub(super) fn is_repository<P: AsRef<Path>>(&self, repo_path: P) -> bool {
+        match self.vcs_type {
+            VcsType::Git => repo_path.as_ref().join(".git").exists(),
+        }
+    }
+
+    /// Create a new repository in the datadir.
+    pub(super) fn init<P: AsRef<Path>>(&self, repo_path: P) -> Result<(), VcsSettingsError> {
+        match self.vcs_type {
+            VcsType::Git => {
+                debug!("initializing git repository");
+                githelper::init(repo_path.as_ref()).map_err(VcsSettingsError::Init)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Commit all changes in the repository. Returns the id of the new commit
+    /// if a commit was made and the id could be read.
     pub(super) fn commit<P: AsRef<Path>>(
         &self,
         repo_path: P,
         message: &str,
         config: &VcsConfig,
-    ) -> Result<(), VcsSettingsError> {
+    ) -> Result<Option<String>, VcsSettingsError> {
         if !config.autocommit {
-            return Ok(());
+            return Ok(None);
         }
 
-        match self.vcs_type {
+        let commit_id = match self.vcs_type {
             VcsType::Git => {
                 debug!("staging all changes in the repo");
                 githelper::add(repo_path.as_ref(), &std::path::PathBuf::from("."))
@@ -59,6 +151,8 @@ impl VcsSettings {
                 debug!("commiting changes to repo");
                 githelper::commit(repo_path.as_ref(), message).map_err(VcsSettingsError::Commit)?;
 
+                let commit_id = self.head(repo_path.as_ref());
+
                 if config.autopull {
                     debug!("pulling changes from origin");
                     githelper::pull(repo_path.as_ref()).map_err(VcsSettingsError::Pull)?;
@@ -68,10 +162,12 @@ impl VcsSettings {
                     debug!("pushing changes to origin");
                     githelper::push(repo_path.as_ref()).map_err(VcsSettingsError::Push)?;
                 }
+
+                commit_id
             }
-        }
+        };
 
-        Ok(())
+        Ok(commit_id)
     }
 }
 
@@ -79,6 +175,7 @@ impl VcsSettings {
 pub(super) enum VcsSettingsError {
     Add(std::io::Error),
     Commit(std::io::Error),
+    Init(std::io::Error),
     Pull(std::io::Error),
     Push(std::io::Error),
 }
@@ -92,6 +189,10 @@ impl fmt::Display for VcsSettingsError {
                 write!(f, "can not commit changes to git repository: {}", err)
             }
 
+            VcsSettingsError::Init(err) => {
+                write!(f, "can not initialize git repository: {}", err)
+            }
+
             VcsSettingsError::Pull(err) => {
                 write!(f, "can not pull changes from upstream repository: {}", err)
             }
@@ -113,3 +214,11 @@ impl std::error::Error for VcsSettingsError {
 pub(super) enum VcsType {
     Git,
 }
+
+impl fmt::Display for VcsType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VcsType::Git => write!(f, "git"),
+        }
+    }
+}
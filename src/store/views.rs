@@ -0,0 +1,241 @@
+use crate::store::{
+    state::{
+        self,
+        StateStore,
+    },
+    Store,
+};
+use anyhow::Error;
+use chrono::{
+    DateTime,
+    Utc,
+};
+use log::{
+    info,
+    warn,
+};
+use serde::Deserialize;
+use std::{
+    collections::{
+        BTreeMap,
+        BTreeSet,
+    },
+    path::Path,
+    sync::{
+        Arc,
+        Mutex,
+    },
+};
+use uuid::Uuid;
+
+/// Name of the state that records when entries were last viewed.
+const VIEWS_STATE_NAME: &str = "views";
+
+/// File in the datadir in which older versions recorded the views.
+const LEGACY_VIEWS_FILE_NAME: &str = ".views.csv";
+
+/// Records when entries were last viewed. Entries that are missing from the
+/// records were never viewed.
+#[derive(Debug, Clone)]
+pub(crate) struct Views {
+    state: StateStore,
+
+    /// Serializes the read-modify-write cycles of concurrent web requests.
+    lock: Arc<Mutex<()>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct LegacyViewRecord {
+    uuid: Uuid,
+    last_viewed: DateTime<Utc>,
+}
+
+impl Views {
+    pub(super) fn new(state: StateStore) -> Self {
+        Self {
+            state,
+            lock: Arc::new(Mutex::new(())),
+        }
+    }
+
+    /// Move the views that older versions recorded in the datadir to the
+    /// state store.
+    pub(super) fn migrate(&self, datadir: &Path) -> Result<(), Error> {
+        let _guard = self.lock.lock().unwrap_or_else(|err| err.into_inner());
+
+        let records =
+            match state::read_legacy_csv::<LegacyViewRecord>(datadir, LEGACY_VIEWS_FILE_NAME)? {
+                Some(records) => records,
+                None => return Ok(()),
+            };
+
+        let mut views = self.load();
+        for record in records {
+            let last_viewed = views.entry(record.uuid).or_insert(record.last_viewed);
+            *last_viewed = (*last_viewed).max(record.last_viewed);
+        }
+
+        self.state.save(VIEWS_STATE_NAME, &views)?;
+        self.state
+            .remove_legacy_file(datadir, LEGACY_VIEWS_FILE_NAME)?;
+
+        info!("moved views from the datadir to the state directory");
+
+        Ok(())
+    }
+
+    /// Load the last viewed timestamps.
+    pub(crate) fn load(&self) -> BTreeMap<Uuid, DateTime<Utc>> {
+        self.state.load(VIEWS_STATE_NAME)
+    }
+
+    /// Record that the entry was viewed at the given time.
+    pub(crate) fn record(&self, uuid: Uuid, viewed: DateTime<Utc>) -> Result<(), Error> {
+        let _guard = self.lock.lock().unwrap_or_else(|err| err.into_inner());
+
+        let mut views = self.load();
+        views.insert(uuid, viewed);
+
+        self.state.save(VIEWS_STATE_NAME, &views)
+    }
+
+    /// Remove the records of entries that are not in `existing` anymore.
+    /// Returns how many records were or would be removed.
+    pub(super) fn prune(&self, existing: &BTreeSet<Uuid>) -> Result<usize, Error> {
+        let _guard = self.lock.lock().unwrap_or_else(|err| err.into_inner());
+
+        let mut views = self.load();
+        let before = views.len();
+        views.retain(|uuid, _| existing.contains(uuid));
+
+        let pruned = before - views.len();
+        if pruned > 0 {
+            self.state.save(VIEWS_STATE_NAME, &views)?;
+        }
+
+        Ok(pruned)
+    }
+}
+
+impl Store {
+    /// Record that the entry was viewed. Failing to record the view is only
+    /// logged as it should never keep the entry from being shown.
+    pub(crate) fn record_view(&self, uuid: &Uuid) {
+        if let Err(err) = self.views.record(*uuid, Utc::now()) {
+            warn!("can not record view of entry {}: {:#}", uuid, err);
+        }
+    }
+
+    /// When the entries were last viewed. Entries that were never viewed are
+    /// missing.
+    pub(crate) fn last_viewed(&self) -> BTreeMap<Uuid, DateTime<Utc>> {
+        self.views.load()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        store::tests::{
+            entry,
+            temporary,
+        },
+        time_context::TimeContext,
+    };
+    use chrono::Duration;
+    use std::fs;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    #[test]
+    fn missing_views_mean_never_viewed() {
+        let (_directory, store) = temporary();
+
+        assert!(store.last_viewed().is_empty());
+        assert_eq!(store.views.prune(&BTreeSet::new()).unwrap(), 0);
+    }
+
+    #[test]
+    fn latest_view_is_recorded() {
+        let (_directory, store) = temporary();
+        let uuid = Uuid::new_v4();
+
+        store
+            .views
+            .record(uuid, at("2026-10-01T00:00:00Z"))
+            .unwrap();
+        store
+            .views
+            .record(uuid, at("2026-10-16T00:00:00Z"))
+            .unwrap();
+
+        assert_eq!(
+            store.last_viewed().into_iter().collect::<Vec<_>>(),
+            vec![(uuid, at("2026-10-16T00:00:00Z"))]
+        );
+    }
+
+    #[test]
+    fn prune_removes_views_of_missing_entries() {
+        let (_directory, store) = temporary();
+        let kept = Uuid::new_v4();
+        let removed = Uuid::new_v4();
+        store
+            .views
+            .record(kept, at("2026-10-01T00:00:00Z"))
+            .unwrap();
+        store
+            .views
+            .record(removed, at("2026-10-01T00:00:00Z"))
+            .unwrap();
+
+        let existing = vec![kept].into_iter().collect();
+        assert_eq!(store.views.prune(&existing).unwrap(), 1);
+        assert_eq!(store.views.prune(&existing).unwrap(), 0);
+
+        assert_eq!(store.last_viewed().keys().collect::<Vec<_>>(), vec![&kept]);
+    }
+
+    #[test]
+    fn legacy_views_are_migrated() {
+        let (_directory, store) = temporary();
+        let uuid = Uuid::new_v4();
+        let legacy = store.datadir().join(LEGACY_VIEWS_FILE_NAME);
+        fs::write(
+            &legacy,
+            format!(
+                "uuid,last_viewed\n{uuid},2026-10-01T00:00:00Z\n{uuid},2026-09-01T00:00:00Z\n",
+                uuid = uuid
+            ),
+        )
+        .unwrap();
+
+        store.views.migrate(store.datadir()).unwrap();
+
+        assert_eq!(store.last_viewed()[&uuid], at("2026-10-01T00:00:00Z"));
+        assert!(!legacy.exists());
+    }
+
+    #[test]
+    fn viewing_an_entry_keeps_it_from_being_forgotten() {
+        let (_directory, store) = temporary();
+        let mut old = entry("old");
+        old.metadata.last_change = Utc::now() - Duration::days(60);
+        let uuid = old.metadata.uuid;
+        store.add_entry(old).unwrap();
+
+        let forgotten = || {
+            store
+                .get_projects_count(true, &TimeContext::new(None))
+                .unwrap()[0]
+                .forgotten_count
+        };
+
+        assert_eq!(forgotten(), 1);
+        store.record_view(&uuid);
+        assert_eq!(forgotten(), 0);
+    }
+}
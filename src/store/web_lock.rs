@@ -0,0 +1,283 @@
+use crate::store::state::StateStore;
+use anyhow::{
+    bail,
+    Error,
+};
+use chrono::{
+    DateTime,
+    Utc,
+};
+use log::{
+    info,
+    warn,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// Name of the state that records the webservice serving the datadir.
+const WEB_INSTANCE_STATE_NAME: &str = "web_instance";
+
+/// How often creating the lock is tried when stale locks are removed in
+/// between.
+const ACQUIRE_ATTEMPTS: usize = 3;
+
+/// Webservice that serves a datadir on this machine.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct WebInstance {
+    pid: u32,
+    address: String,
+    started: DateTime<Utc>,
+}
+
+/// Keeps a second webservice from serving the same datadir as both would
+/// write to the index without knowing of each other. The lock is a state file
+/// with the pid and address of the running webservice. A lock whose process
+/// is gone is stale and taken over.
+#[derive(Debug)]
+pub(crate) struct WebInstanceLock {
+    state: StateStore,
+    pid: u32,
+}
+
+impl WebInstanceLock {
+    pub(super) fn acquire(state: StateStore, address: &str, force: bool) -> Result<Self, Error> {
+        let pid = std::process::id();
+        let instance = WebInstance {
+            pid,
+            address: address.to_string(),
+            started: Utc::now(),
+        };
+
+        // The lock file is only created if there is none so two webservices
+        // starting at the same time can not both take it. A stale lock is
+        // removed and creating it is tried again.
+        for _ in 0..ACQUIRE_ATTEMPTS {
+            if state.create(WEB_INSTANCE_STATE_NAME, &instance)? {
+                return Ok(Self { state, pid });
+            }
+
+            // A lock that was removed or could not be read in between is
+            // tried again.
+            let running: Option<WebInstance> = state.load(WEB_INSTANCE_STATE_NAME);
+            let running = match running {
+                Some(running) => running,
+                None => continue,
+            };
+
+            // Already held by this process.
+            if running.pid == pid {
+                return Ok(Self { state, pid });
+            }
+
+            if !is_running(running.pid) {
+                info!(
+                    "webservice on {} (pid {}) is not running anymore, taking over its lock",
+                    running.address, running.pid
+                );
+
+                state.remove(WEB_INSTANCE_STATE_NAME)?;
+                continue;
+            }
+
+            if !force {
+                bail!(
+                    "a webservice is already running for this datadir on {} (pid {}, started {}), \
+                     stop it or pass --force to start anyway",
+                    running.address,
+                    running.pid,
+                    running.started.to_rfc3339()
+                )
+            }
+
+            warn!(
+                "starting although the webservice on {} (pid {}) is still running for this datadir",
+                running.address, running.pid
+            );
+
+            state.save(WEB_INSTANCE_STATE_NAME, &instance)?;
+            return Ok(Self { state, pid });
+        }
+
+        bail!("can not lock the datadir for the webservice as the lock keeps changing")
+    }
+
+    /// Remove the lock when the webservice stops. A lock that another
+    /// webservice took over with --force is left alone.
+    pub(crate) fn release(&self) {
+        let running: Option<WebInstance> = self.state.load(WEB_INSTANCE_STATE_NAME);
+        if !matches!(running, Some(running) if running.pid == self.pid) {
+            return;
+        }
+
+        if let Err(err) = self.state.remove(WEB_INSTANCE_STATE_NAME) {
+            warn!("can not remove webservice lock: {:#}", err);
+        }
+    }
+}
+
+/// Check if a process with the pid exists by sending it no signal.
+#[cfg(unix)]
+fn is_running(pid: u32) -> bool {
+    use std::convert::TryFrom;
+
+    let pid = match libc::pid_t::try_from(pid) {
+        Ok(pid) => pid,
+        Err(_) => return false,
+    };
+
+    // SAFETY: Signal 0 does not do anything to the process, kill only checks
+    // if it exists and if it could be signaled.
+    let result = unsafe { libc::kill(pid, 0) };
+
+    // EPERM means the process exists but belongs to another user.
+    result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Without a way to check for the process the lock is assumed to be held.
+/// --force starts anyway.
+#[cfg(not(unix))]
+fn is_running(_pid: u32) -> bool {
+    true
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::store::changes::Changes;
+
+    fn state(state_home: &tempfile::TempDir) -> StateStore {
+        StateStore::with_state_home(state_home.path(), "/todos", Changes::default())
+    }
+
+    fn held_by(state: &StateStore, pid: u32) {
+        state
+            .save(
+                WEB_INSTANCE_STATE_NAME,
+                &WebInstance {
+                    pid,
+                    address: "127.0.0.1:8080".to_string(),
+                    started: Utc::now(),
+                },
+            )
+            .unwrap();
+    }
+
+    fn holder(state: &StateStore) -> Option<u32> {
+        let running: Option<WebInstance> = state.load(WEB_INSTANCE_STATE_NAME);
+        running.map(|running| running.pid)
+    }
+
+    /// Pid of a process that has exited already.
+    fn exited_pid() -> u32 {
+        let mut child = std::process::Command::new("true").spawn().unwrap();
+        let pid = child.id();
+        child.wait().unwrap();
+
+        pid
+    }
+
+    #[test]
+    fn lock_is_created_and_released() {
+        let state_home = tempfile::tempdir().unwrap();
+        let state = state(&state_home);
+
+        let lock = WebInstanceLock::acquire(state.clone(), "127.0.0.1:8080", false).unwrap();
+        assert_eq!(holder(&state), Some(std::process::id()));
+
+        lock.release();
+        assert_eq!(holder(&state), None);
+    }
+
+    #[test]
+    fn running_webservice_refuses_the_second() {
+        let state_home = tempfile::tempdir().unwrap();
+        let state = state(&state_home);
+
+        // The parent process runs as long as the tests do.
+        let running = std::os::unix::process::parent_id();
+        held_by(&state, running);
+
+        let err = WebInstanceLock::acquire(state.clone(), "127.0.0.1:8081", false).unwrap_err();
+        let message = err.to_string();
+        assert!(message.contains("127.0.0.1:8080"), "{}", message);
+        assert!(message.contains(&format!("pid {}", running)), "{}", message);
+        assert!(message.contains("--force"), "{}", message);
+
+        assert_eq!(holder(&state), Some(running));
+    }
+
+    #[test]
+    fn force_takes_over_a_running_lock() {
+        let state_home = tempfile::tempdir().unwrap();
+        let state = state(&state_home);
+
+        let running = std::os::unix::process::parent_id();
+        held_by(&state, running);
+
+        let lock = WebInstanceLock::acquire(state.clone(), "127.0.0.1:8081", true).unwrap();
+        assert_eq!(holder(&state), Some(std::process::id()));
+
+        lock.release();
+        assert_eq!(holder(&state), None);
+    }
+
+    #[test]
+    fn stale_lock_is_taken_over() {
+        let state_home = tempfile::tempdir().unwrap();
+        let state = state(&state_home);
+
+        held_by(&state, exited_pid());
+
+        let lock = WebInstanceLock::acquire(state.clone(), "127.0.0.1:8081", false).unwrap();
+        assert_eq!(holder(&state), Some(std::process::id()));
+
+        lock.release();
+        assert_eq!(holder(&state), None);
+    }
+
+    #[test]
+    fn release_leaves_a_lock_taken_over_with_force() {
+        let state_home = tempfile::tempdir().unwrap();
+        let state = state(&state_home);
+
+        let lock = WebInstanceLock::acquire(state.clone(), "127.0.0.1:8080", false).unwrap();
+
+        let newer = std::os::unix::process::parent_id();
+        held_by(&state, newer);
+
+        lock.release();
+        assert_eq!(holder(&state), Some(newer));
+    }
+
+    #[test]
+    fn only_one_of_concurrent_creates_wins() {
+        let state_home = tempfile::tempdir().unwrap();
+        let state = state(&state_home);
+
+        let created = (0..8)
+            .map(|pid| {
+                let state = state.clone();
+                std::thread::spawn(move || {
+                    state
+                        .create(
+                            WEB_INSTANCE_STATE_NAME,
+                            &WebInstance {
+                                pid,
+                                address: "127.0.0.1:8080".to_string(),
+                                started: Utc::now(),
+                            },
+                        )
+                        .unwrap()
+                })
+            })
+            .collect::<Vec<_>>()
+            .into_iter()
+            .map(|thread| thread.join().unwrap())
+            .filter(|created| *created)
+            .count();
+
+        assert_eq!(created, 1);
+    }
+}
@@ -0,0 +1,578 @@
+use crate::entry::{
+    Entry,
+    Metadata,
+};
+use chrono::{
+    DateTime,
+    NaiveDate,
+    Utc,
+};
+use hmac::{
+    Hmac,
+    Mac,
+};
+use log::{
+    debug,
+    warn,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use sha2::Sha256;
+use std::{
+    sync::{
+        mpsc::{
+            self,
+            Receiver,
+            SyncSender,
+            TrySendError,
+        },
+        Mutex,
+    },
+    thread::JoinHandle,
+    time::{
+        Duration,
+        Instant,
+    },
+};
+use uuid::Uuid;
+
+/// How many events can wait for delivery before new events get dropped.
+const QUEUE_SIZE: usize = 64;
+
+/// How often the delivery of an event is attempted before giving up.
+const MAX_ATTEMPTS: u32 = 3;
+
+/// Timeout for a single delivery attempt.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How long to wait for queued events to be delivered when the store is
+/// closed.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Header that contains the hex encoded HMAC-SHA256 of the request body.
+const SIGNATURE_HEADER: &str = "X-Todust-Signature";
+
+/// Endpoint that receives events about changed entries.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct Webhook {
+    url: String,
+
+    /// Only send these events. All events are sent when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    events: Vec<EventKind>,
+
+    /// Only send events for entries in these projects. Events of all projects
+    /// are sent when empty.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    projects: Vec<String>,
+
+    /// Secret used to sign the request body.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    secret: Option<String>,
+}
+
+impl Webhook {
+    fn wants(&self, event: &Event) -> bool {
+        (self.events.is_empty() || self.events.contains(&event.event))
+            && (self.projects.is_empty() || self.projects.contains(&event.project))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum EventKind {
+    Created,
+    Finished,
+    Moved,
+    Deleted,
+}
+
+impl EventKind {
+    pub(super) fn name(self) -> &'static str {
+        match self {
+            EventKind::Created => "created",
+            EventKind::Finished => "finished",
+            EventKind::Moved => "moved",
+            EventKind::Deleted => "deleted",
+        }
+    }
+}
+
+/// Events of the change from `previous` to `entry`. `previous` is `None` if
+/// the entry did not exist before.
+pub(super) fn events(previous: Option<&Metadata>, entry: &Entry) -> Vec<EventKind> {
+    let mut events = Vec::new();
+
+    match previous {
+        None => events.push(EventKind::Created),
+        Some(previous) => {
+            if previous.project != entry.metadata.project {
+                events.push(EventKind::Moved)
+            }
+
+            if previous.finished.is_none() && entry.metadata.finished.is_some() {
+                events.push(EventKind::Finished)
+            }
+
+            if previous.deleted.is_none() && entry.metadata.deleted.is_some() {
+                events.push(EventKind::Deleted)
+            }
+        }
+    }
+
+    events
+}
+
+#[derive(Debug, Serialize)]
+struct Event {
+    event: EventKind,
+    timestamp: DateTime<Utc>,
+    uuid: Uuid,
+    project: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    previous_project: Option<String>,
+    due: Option<NaiveDate>,
+    finished: Option<DateTime<Utc>>,
+    text: String,
+}
+
+impl Event {
+    fn new(event: EventKind, entry: &Entry, previous: Option<&Metadata>) -> Self {
+        let previous_project = match event {
+            EventKind::Moved => previous.map(|metadata| metadata.project.clone()),
+            _ => None,
+        };
+
+        Self {
+            event,
+            timestamp: Utc::now(),
+            uuid: entry.metadata.uuid,
+            project: entry.metadata.project.clone(),
+            previous_project,
+            due: entry.metadata.due,
+            finished: entry.metadata.finished,
+            text: entry.text.clone(),
+        }
+    }
+}
+
+struct Delivery {
+    url: String,
+    secret: Option<String>,
+    body: String,
+}
+
+/// Sends events to the configured webhooks from a background thread so slow
+/// or dead endpoints do not block changes to the store.
+#[derive(Debug)]
+pub(super) struct Dispatcher {
+    webhooks: Vec<Webhook>,
+    sender: Mutex<Option<SyncSender<Delivery>>>,
+    worker: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Dispatcher {
+    pub(super) fn new(webhooks: Vec<Webhook>) -> Self {
+        if webhooks.is_empty() {
+            return Self {
+                webhooks,
+                sender: Mutex::new(None),
+                worker: Mutex::new(None),
+            };
+        }
+
+        let (sender, receiver) = mpsc::sync_channel(QUEUE_SIZE);
+        let worker = std::thread::spawn(move || Dispatcher::deliver_all(receiver));
+
+        Self {
+            webhooks,
+            sender: Mutex::new(Some(sender)),
+            worker: Mutex::new(Some(worker)),
+        }
+    }
+
+    pub(super) fn is_enabled(&self) -> bool {
+        self.sender
+            .lock()
+            .expect("webhook sender poisoned")
+            .is_some()
+    }
+
+    /// Queue events for the change from `previous` to `entry`. `previous` is
+    /// `None` if the entry did not exist before.
+    pub(super) fn entry_changed(&self, previous: Option<&Metadata>, entry: &Entry) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        for event in events(previous, entry) {
+            self.dispatch(Event::new(event, entry, previous));
+        }
+    }
+
+    fn dispatch(&self, event: Event) {
+        let sender = self.sender.lock().expect("webhook sender poisoned");
+        let sender = match &*sender {
+            Some(sender) => sender,
+            None => return,
+        };
+
+        let body = match serde_json::to_string(&event) {
+            Ok(body) => body,
+            Err(err) => {
+                warn!("can not serialize webhook event: {}", err);
+                return;
+            }
+        };
+
+        for webhook in self.webhooks.iter().filter(|webhook| webhook.wants(&event)) {
+            let delivery = Delivery {
+                url: webhook.url.clone(),
+                secret: webhook.secret.clone(),
+                body: body.clone(),
+            };
+
+            match sender.try_send(delivery) {
+                Ok(()) => {}
+                Err(TrySendError::Full(delivery)) => {
+                    warn!("webhook queue is full, dropping event for {}", delivery.url)
+                }
+                Err(TrySendError::Disconnected(delivery)) => {
+                    warn!(
+                        "webhook worker stopped, dropping event for {}",
+                        delivery.url
+                    )
+                }
+            }
+        }
+    }
+
+    fn deliver_all(receiver: Receiver<Delivery>) {
+        let agent = ureq::AgentBuilder::new().timeout(REQUEST_TIMEOUT).build();
+
+        for delivery in receiver {
+            Dispatcher::deliver(&agent, &delivery)
+        }
+    }
+
+    fn deliver(agent: &ureq::Agent, delivery: &Delivery) {
+        for attempt in 1..=MAX_ATTEMPTS {
+            let mut request = agent
+                .post(&delivery.url)
+                .set("Content-Type", "application/json");
+
+            if let Some(secret) = &delivery.secret {
+                request = request.set(SIGNATURE_HEADER, &sign(secret, &delivery.body));
+            }
+
+            match request.send_string(&delivery.body) {
+                Ok(_) => {
+                    debug!("delivered webhook event to {}", delivery.url);
+                    return;
+                }
+                Err(err) => warn!(
+                    "can not deliver webhook event to {} (attempt {} of {}): {}",
+                    delivery.url, attempt, MAX_ATTEMPTS, err
+                ),
+            }
+
+            if attempt < MAX_ATTEMPTS {
+                std::thread::sleep(Duration::from_secs(u64::from(attempt)))
+            }
+        }
+    }
+
+    /// Stop taking new events and give queued events a chance to be
+    /// delivered but never wait longer than `SHUTDOWN_TIMEOUT`. Used when
+    /// the process stops while other clones of the store are still around.
+    pub(super) fn flush(&self) {
+        self.sender.lock().expect("webhook sender poisoned").take();
+
+        let worker = match self.worker.lock().expect("webhook worker poisoned").take() {
+            Some(worker) => worker,
+            None => return,
+        };
+
+        let deadline = Instant::now() + SHUTDOWN_TIMEOUT;
+        while !worker.is_finished() {
+            if Instant::now() >= deadline {
+                warn!("giving up on delivering the remaining webhook events");
+                return;
+            }
+
+            std::thread::sleep(Duration::from_millis(50))
+        }
+    }
+}
+
+impl Drop for Dispatcher {
+    /// Give queued events a chance to be delivered before the process exits.
+    fn drop(&mut self) {
+        self.flush()
+    }
+}
+
+/// Signature of the body in the form `sha256=<hex>`.
+fn sign(secret: &str, body: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("hmac can take keys of any size");
+    mac.update(body.as_bytes());
+
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::entry;
+    use serde_json::Value;
+    use std::{
+        io::{
+            BufRead,
+            BufReader,
+            Read,
+            Write,
+        },
+        net::TcpListener,
+        sync::Arc,
+    };
+
+    /// Request received by the local endpoint.
+    #[derive(Debug)]
+    struct Received {
+        signature: Option<String>,
+        body: String,
+    }
+
+    /// Local endpoint that answers with the statuses in order and with 200
+    /// once they are used up. Returns its url and what it received.
+    fn endpoint(statuses: Vec<u16>) -> (String, Arc<Mutex<Vec<Received>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+        let received = Arc::new(Mutex::new(Vec::new()));
+
+        let thread_received = Arc::clone(&received);
+        std::thread::spawn(move || {
+            let mut statuses = statuses.into_iter();
+
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+                let mut signature = None;
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+
+                    if let Some((name, value)) = line.split_once(':') {
+                        match name.to_lowercase().as_str() {
+                            "content-length" => length = value.trim().parse().unwrap(),
+                            "x-todust-signature" => signature = Some(value.trim().to_string()),
+                            _ => {}
+                        }
+                    }
+                }
+
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+
+                thread_received.lock().unwrap().push(Received {
+                    signature,
+                    body: String::from_utf8(body).unwrap(),
+                });
+
+                let status = statuses.next().unwrap_or(200);
+                write!(
+                    stream,
+                    "HTTP/1.1 {} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                )
+                .unwrap();
+            }
+        });
+
+        (url, received)
+    }
+
+    fn webhook(url: &str) -> Webhook {
+        Webhook {
+            url: url.to_string(),
+            events: Vec::new(),
+            projects: Vec::new(),
+            secret: None,
+        }
+    }
+
+    fn entry_in(project: &str, text: &str) -> Entry {
+        let mut entry = entry(text);
+        entry.metadata.project = project.to_string();
+        entry
+    }
+
+    /// Send the events of the changes and wait until they are delivered.
+    fn deliver(webhook: Webhook, changes: &[(Option<&Metadata>, &Entry)]) {
+        let dispatcher = Dispatcher::new(vec![webhook]);
+        for (previous, entry) in changes {
+            dispatcher.entry_changed(*previous, entry);
+        }
+    }
+
+    fn bodies(received: &Mutex<Vec<Received>>) -> Vec<Value> {
+        received
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|received| serde_json::from_str(&received.body).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn signature_is_the_hmac_of_the_body() {
+        // Test vector from the wikipedia article on HMAC.
+        assert_eq!(
+            sign("key", "The quick brown fox jumps over the lazy dog"),
+            "sha256=f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+    }
+
+    #[test]
+    fn events_are_signed_with_the_secret() {
+        let (url, received) = endpoint(Vec::new());
+        let entry = entry_in("work", "signed");
+
+        deliver(
+            Webhook {
+                secret: Some("secret".to_string()),
+                ..webhook(&url)
+            },
+            &[(None, &entry)],
+        );
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(
+            received[0].signature.as_deref(),
+            Some(sign("secret", &received[0].body).as_str())
+        );
+    }
+
+    #[test]
+    fn events_without_secret_are_not_signed() {
+        let (url, received) = endpoint(Vec::new());
+        let entry = entry_in("work", "unsigned");
+
+        deliver(webhook(&url), &[(None, &entry)]);
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].signature, None);
+    }
+
+    #[test]
+    fn payload_describes_the_change() {
+        let (url, received) = endpoint(Vec::new());
+        let created = entry_in("work", "payload");
+        let mut moved = created.clone();
+        moved.metadata.project = "home".to_string();
+
+        deliver(
+            webhook(&url),
+            &[(None, &created), (Some(&created.metadata), &moved)],
+        );
+
+        let bodies = bodies(&received);
+        assert_eq!(bodies.len(), 2);
+
+        assert_eq!(bodies[0]["event"], "created");
+        assert_eq!(bodies[0]["uuid"], created.metadata.uuid.to_string());
+        assert_eq!(bodies[0]["project"], "work");
+        assert_eq!(bodies[0]["text"], "payload");
+        assert_eq!(bodies[0]["due"], Value::Null);
+        assert_eq!(bodies[0]["finished"], Value::Null);
+        assert!(bodies[0].get("previous_project").is_none());
+
+        assert_eq!(bodies[1]["event"], "moved");
+        assert_eq!(bodies[1]["project"], "home");
+        assert_eq!(bodies[1]["previous_project"], "work");
+    }
+
+    #[test]
+    fn only_events_of_the_projects_are_sent() {
+        let (url, received) = endpoint(Vec::new());
+        let work = entry_in("work", "work entry");
+        let home = entry_in("home", "home entry");
+
+        deliver(
+            Webhook {
+                projects: vec!["work".to_string()],
+                ..webhook(&url)
+            },
+            &[(None, &home), (None, &work), (None, &home)],
+        );
+
+        let bodies = bodies(&received);
+        assert_eq!(bodies.len(), 1);
+        assert_eq!(bodies[0]["text"], "work entry");
+    }
+
+    #[test]
+    fn only_the_events_are_sent() {
+        let (url, received) = endpoint(Vec::new());
+        let created = entry_in("work", "finished entry");
+        let mut finished = created.clone();
+        finished.metadata.finished = Some(Utc::now());
+
+        deliver(
+            Webhook {
+                events: vec![EventKind::Finished],
+                ..webhook(&url)
+            },
+            &[(None, &created), (Some(&created.metadata), &finished)],
+        );
+
+        let bodies = bodies(&received);
+        assert_eq!(bodies.len(), 1);
+        assert_eq!(bodies[0]["event"], "finished");
+    }
+
+    #[test]
+    fn failed_deliveries_are_retried() {
+        let (url, received) = endpoint(vec![500]);
+        let entry = entry_in("work", "retried");
+
+        deliver(webhook(&url), &[(None, &entry)]);
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0].body, received[1].body);
+    }
+
+    #[test]
+    fn queued_events_are_delivered_when_flushed() {
+        let (url, received) = endpoint(Vec::new());
+        let entries = (0..5)
+            .map(|index| entry_in("work", &format!("entry {}", index)))
+            .collect::<Vec<_>>();
+
+        let dispatcher = Dispatcher::new(vec![webhook(&url)]);
+        for entry in &entries {
+            dispatcher.entry_changed(None, entry);
+        }
+        dispatcher.flush();
+
+        assert_eq!(received.lock().unwrap().len(), entries.len());
+        assert!(!dispatcher.is_enabled());
+
+        // Events after the flush are dropped.
+        dispatcher.entry_changed(None, &entries[0]);
+        drop(dispatcher);
+        assert_eq!(received.lock().unwrap().len(), entries.len());
+    }
+}
@@ -0,0 +1,379 @@
+use crate::interactivity::Interactivity;
+use comfy_table::{
+    presets,
+    Attribute,
+    Cell,
+    Color,
+    ColumnConstraint,
+    ContentArrangement,
+    Table,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::str::FromStr;
+
+/// Space every column takes up in addition to its content. One space of
+/// padding on each side plus one border character.
+const COLUMN_OVERHEAD: usize = 3;
+
+/// Background of every other row so long tables are easier to follow.
+const SHADED_ROW_BACKGROUND: Color = Color::DarkGrey;
+
+/// How the borders of tables are drawn.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum TableStyle {
+    /// No borders so tables look like aligned plain text.
+    #[default]
+    Plain,
+
+    /// Borders drawn with box drawing characters.
+    Borders,
+
+    /// Borders drawn with ASCII characters only, for example to paste tables
+    /// into plain text emails.
+    Ascii,
+}
+
+impl TableStyle {
+    fn preset(self) -> &'static str {
+        match self {
+            TableStyle::Plain => presets::NOTHING,
+            TableStyle::Borders => presets::UTF8_FULL,
+            TableStyle::Ascii => presets::ASCII_FULL,
+        }
+    }
+}
+
+impl TableStyle {
+    /// If the style draws lines between rows on its own.
+    fn has_row_lines(self) -> bool {
+        !matches!(self, TableStyle::Plain)
+    }
+}
+
+impl FromStr for TableStyle {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "plain" => Ok(TableStyle::Plain),
+            "borders" => Ok(TableStyle::Borders),
+            "ascii" => Ok(TableStyle::Ascii),
+            _ => Err(format!("unknown table style {}", s)),
+        }
+    }
+}
+
+/// Active entry counts from which the Active column of the projects table
+/// changes its color. Projects below `warn` are green, projects below `alert`
+/// yellow and all others red.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub(super) struct ActiveThresholds {
+    /// Active entries from which a project is shown in yellow.
+    pub(super) warn: usize,
+
+    /// Active entries from which a project is shown in red.
+    pub(super) alert: usize,
+}
+
+impl Default for ActiveThresholds {
+    fn default() -> Self {
+        Self { warn: 1, alert: 10 }
+    }
+}
+
+impl ActiveThresholds {
+    pub(super) fn color(&self, active: usize) -> Color {
+        if active >= self.alert {
+            Color::Red
+        } else if active >= self.warn {
+            Color::Yellow
+        } else {
+            Color::Green
+        }
+    }
+}
+
+/// Width of the terminal stdout is attached to. Returns `None` when the output
+/// is not styled or when `wide` is set as the output should not be limited in
+/// that case.
+pub(super) fn terminal_width(wide: bool, interactivity: Interactivity) -> Option<usize> {
+    if wide || !interactivity.is_styled() {
+        return None;
+    }
+
+    crossterm::terminal::size()
+        .ok()
+        .map(|(width, _)| usize::from(width))
+}
+
+/// Create a new table with the given header.
+///
+/// When printing to a terminal the table is limited to the terminal width.
+/// All columns except the flexible column keep the width of their content so
+/// only the flexible column gets wrapped when space runs out. When `wide` is
+/// set or the output is not styled the width of the table is not limited.
+pub(super) fn new_table(
+    header: &[&str],
+    flexible_column: Option<usize>,
+    wide: bool,
+    style: TableStyle,
+    interactivity: Interactivity,
+) -> Table {
+    table_with_width(
+        header,
+        flexible_column,
+        terminal_width(wide, interactivity),
+        style,
+        interactivity,
+    )
+}
+
+/// Create a new table like `new_table` that is limited to the given width
+/// instead of the width of the terminal.
+fn table_with_width(
+    header: &[&str],
+    flexible_column: Option<usize>,
+    width: Option<usize>,
+    style: TableStyle,
+    interactivity: Interactivity,
+) -> Table {
+    let mut table = Table::new();
+    table.load_preset(style.preset());
+    table.set_header(
+        header
+            .iter()
+            .map(|name| Cell::new(name).add_attribute(Attribute::Bold))
+            .collect::<Vec<_>>(),
+    );
+
+    match width {
+        Some(width) => {
+            table.set_table_width(width as u16);
+            table.set_content_arrangement(ContentArrangement::Dynamic);
+
+            for (index, column) in table.column_iter_mut().enumerate() {
+                if Some(index) != flexible_column {
+                    column.set_constraint(ColumnConstraint::ContentWidth);
+                }
+            }
+        }
+
+        None => {
+            table.set_content_arrangement(ContentArrangement::Disabled);
+        }
+    }
+
+    // The table only checks stdout which misses TODUST_NONINTERACTIVE and
+    // --porcelain.
+    if !interactivity.is_styled() {
+        table.force_no_tty();
+    }
+
+    table
+}
+
+/// Width that is left for the flexible column of a table with the given
+/// total width after all other columns took the width of their content.
+pub(super) fn remaining_width(table_width: usize, column_widths: &[usize]) -> usize {
+    let columns = column_widths.len() + 1;
+    let used = column_widths.iter().sum::<usize>() + columns * COLUMN_OVERHEAD + 1;
+
+    table_width.saturating_sub(used)
+}
+
+/// Width of the widest value in a column including its header.
+pub(super) fn column_width<'a>(header: &str, values: impl Iterator<Item = &'a String>) -> usize {
+    values
+        .map(|value| value.chars().count())
+        .fold(header.chars().count(), usize::max)
+}
+
+/// Force colors on or off instead of letting the table check if stdout is a
+/// terminal, so the global --color option and NO_COLOR are respected.
+pub(super) fn set_color(table: &mut Table, color: bool) {
+    if color {
+        table.enforce_styling();
+    } else {
+        table.force_no_tty();
+    }
+}
+
+/// Give every other row a background.
+pub(super) fn shade_rows(rows: Vec<Vec<Cell>>) -> impl Iterator<Item = Vec<Cell>> {
+    rows.into_iter().enumerate().map(|(index, row)| {
+        if index % 2 == 0 {
+            return row;
+        }
+
+        row.into_iter()
+            .map(|cell| cell.bg(SHADED_ROW_BACKGROUND))
+            .collect()
+    })
+}
+
+/// Separate the rows added so far from the ones that follow like a totals
+/// row. Styles with borders already draw a line between all rows, for plain
+/// tables every column except the first is underlined with dashes as wide as
+/// its content.
+pub(super) fn add_separator(table: &mut Table, style: TableStyle) {
+    if style.has_row_lines() {
+        return;
+    }
+
+    let row = table
+        .column_max_content_widths()
+        .into_iter()
+        .enumerate()
+        .map(|(index, width)| match index {
+            0 => String::new(),
+            _ => "-".repeat(usize::from(width)),
+        })
+        .collect::<Vec<_>>();
+
+    table.add_row(row);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DESCRIPTION: &str = "Call the vendor about the invoice that was sent twice last month";
+
+    fn rendered(width: Option<usize>) -> String {
+        let mut table = table_with_width(
+            &["ID", "Description", "Due"],
+            Some(1),
+            width,
+            TableStyle::Plain,
+            Interactivity::default(),
+        );
+        table.add_row(vec!["12", DESCRIPTION, "2026-10-16"]);
+
+        table.to_string()
+    }
+
+    #[test]
+    fn narrow_width_only_wraps_the_flexible_column() {
+        let table = rendered(Some(50));
+
+        assert!(table.lines().all(|line| line.chars().count() <= 50));
+        assert!(table.contains(" 12 "));
+        assert!(table.contains("2026-10-16"));
+        assert!(!table.contains(DESCRIPTION));
+    }
+
+    #[test]
+    fn unbounded_width_does_not_wrap() {
+        let table = rendered(None);
+
+        assert_eq!(table.lines().count(), 2);
+        assert!(table.contains(DESCRIPTION));
+    }
+
+    #[test]
+    fn wide_tables_ignore_the_terminal() {
+        assert_eq!(terminal_width(true, Interactivity::default()), None);
+        assert_eq!(terminal_width(false, Interactivity::default()), None);
+    }
+
+    #[test]
+    fn remaining_width_subtracts_other_columns() {
+        assert_eq!(remaining_width(80, &[2, 10]), 80 - 12 - 3 * 3 - 1);
+        assert_eq!(remaining_width(10, &[20]), 0);
+    }
+
+    /// Small table with a totals row like the projects table in the style.
+    fn fixture(style: TableStyle) -> String {
+        let mut table = table_with_width(
+            &["Project", "Active", "Done"],
+            None,
+            None,
+            style,
+            Interactivity::default(),
+        );
+        table.add_row(vec!["default", "3", "12"]);
+        table.add_row(vec!["work", "10", "4"]);
+        add_separator(&mut table, style);
+        table.add_row(vec!["Total", "13", "16"]);
+
+        format!("{}\n", table)
+    }
+
+    #[test]
+    fn plain_style() {
+        assert_eq!(
+            fixture(TableStyle::Plain),
+            include_str!("../resources/tables/plain.txt")
+        );
+    }
+
+    #[test]
+    fn borders_style() {
+        assert_eq!(
+            fixture(TableStyle::Borders),
+            include_str!("../resources/tables/borders.txt")
+        );
+    }
+
+    #[test]
+    fn ascii_style() {
+        let table = fixture(TableStyle::Ascii);
+
+        assert_eq!(table, include_str!("../resources/tables/ascii.txt"));
+        assert!(table.is_ascii());
+    }
+
+    #[test]
+    fn active_thresholds_classify_counts() {
+        let thresholds = ActiveThresholds::default();
+
+        assert_eq!(thresholds.color(0), Color::Green);
+        assert_eq!(thresholds.color(1), Color::Yellow);
+        assert_eq!(thresholds.color(9), Color::Yellow);
+        assert_eq!(thresholds.color(10), Color::Red);
+        assert_eq!(thresholds.color(100), Color::Red);
+    }
+
+    #[test]
+    fn active_thresholds_are_configurable() {
+        let thresholds: ActiveThresholds = toml::from_str("warn = 5\nalert = 20").unwrap();
+
+        assert_eq!(thresholds.color(4), Color::Green);
+        assert_eq!(thresholds.color(5), Color::Yellow);
+        assert_eq!(thresholds.color(20), Color::Red);
+
+        let thresholds: ActiveThresholds = toml::from_str("alert = 3").unwrap();
+        assert_eq!(thresholds.warn, 1);
+        assert_eq!(thresholds.color(3), Color::Red);
+    }
+
+    #[test]
+    fn shading_skips_every_other_row() {
+        let rows = shade_rows(vec![vec![Cell::new("a")], vec![Cell::new("b")]]).collect::<Vec<_>>();
+        let mut table = Table::new();
+        table.enforce_styling();
+        for row in rows {
+            table.add_row(row);
+        }
+
+        let lines = table.lines().collect::<Vec<_>>();
+        assert!(!lines[1].contains("\u{1b}["), "{:?}", lines[1]);
+        assert!(lines[3].contains("\u{1b}["), "{:?}", lines[3]);
+    }
+
+    #[test]
+    fn style_names() {
+        assert_eq!("plain".parse(), Ok(TableStyle::Plain));
+        assert_eq!("borders".parse(), Ok(TableStyle::Borders));
+        assert_eq!("ascii".parse(), Ok(TableStyle::Ascii));
+        assert_eq!(
+            "fancy".parse::<TableStyle>(),
+            Err("unknown table style fancy".to_string())
+        );
+    }
+}
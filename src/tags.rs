@@ -0,0 +1,69 @@
+use serde::{
+    Deserialize,
+    Deserializer,
+    Serializer,
+};
+use std::collections::BTreeSet;
+
+/// Separates the tags of an entry in its index column.
+const SEPARATOR: char = ',';
+
+/// Check that the tag can be stored in the index and typed on the command
+/// line. Used to parse the --tag options.
+pub(crate) fn parse_tag(tag: &str) -> Result<String, String> {
+    if tag.is_empty() {
+        return Err("tag can not be empty".to_string());
+    }
+
+    if tag
+        .chars()
+        .any(|c| c == SEPARATOR || c.is_whitespace() || c.is_control())
+    {
+        return Err(format!(
+            "tag {:?} can not contain commas, whitespace or control characters",
+            tag
+        ));
+    }
+
+    Ok(tag.to_string())
+}
+
+/// If the entry has all of the wanted tags. No wanted tags match every entry.
+pub(crate) fn has_all(tags: &BTreeSet<String>, wanted: &[String]) -> bool {
+    wanted.iter().all(|tag| tags.contains(tag))
+}
+
+/// Tags joined for showing them in a table.
+pub(crate) fn join(tags: &BTreeSet<String>) -> String {
+    tags.iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Write the tags as one comma separated value as every field of the metadata
+/// is a single column in the index.
+pub(crate) fn serialize<S: Serializer>(
+    tags: &BTreeSet<String>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let field = tags
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>()
+        .join(&SEPARATOR.to_string());
+
+    serializer.serialize_str(&field)
+}
+
+pub(crate) fn deserialize<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeSet<String>, D::Error> {
+    let field = String::deserialize(deserializer)?;
+
+    Ok(field
+        .split(SEPARATOR)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect())
+}
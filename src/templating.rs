@@ -1,6 +1,20 @@
-use crate::helper;
+use crate::{
+    entry::Entry,
+    helper,
+    locale::{
+        Locale,
+        Translations,
+    },
+    time_context::TimeContext,
+};
+use anyhow::{
+    bail,
+    Context as _,
+    Error,
+};
 use chrono::{
     DateTime,
+    NaiveDate,
     Utc,
 };
 use serde_json::value::{
@@ -8,43 +22,139 @@ use serde_json::value::{
     Value,
 };
 use std::{
-    collections::HashMap,
-    io::Write,
+    collections::{
+        BTreeMap,
+        HashMap,
+    },
+    io::{
+        self,
+        BufWriter,
+        Write,
+    },
+    path::{
+        Path,
+        PathBuf,
+    },
 };
 use tempfile::tempdir;
 use tera::{
     try_get_value,
+    Context,
+    Filter,
+    Function,
     Result as TeraResult,
+    Tera,
 };
+use uuid::Uuid;
+
+/// Folder in the datadir that contains the entry templates.
+const ENTRY_TEMPLATES_FOLDER: &str = "templates";
+
+/// File extension of entry templates.
+const ENTRY_TEMPLATE_EXTENSION: &str = "adoc";
+
+/// Asciidoc directives that are processed on every line of a document.
+const PREPROCESSOR_DIRECTIVES: &[&str] =
+    &["include::", "ifdef::", "ifndef::", "ifeval::", "endif::"];
+
+/// Attributes every rendered entry starts with.
+const ASCIIDOC_HEADER: &str = r#"
+:toc: right
+:toclevels: 3
+:sectanchors:
+:sectlink:
+:icons: font
+:linkattrs:
+:numbered:
+:idprefix:
+:idseparator: -
+:doctype: book
+:source-highlighter: pygments
+:listing-caption: Listing
+:hide-uri-scheme:
+"#;
+
+/// Permalink of the entry page with the slug of the text of the entry.
+pub(crate) fn entry_path(uuid: &Uuid, text: &str) -> String {
+    format!(
+        "/entry/{}/{}",
+        uuid,
+        helper::percent_encode(&helper::slug(text))
+    )
+}
+
+pub(super) fn entry_path_filter(value: &Value, _: &HashMap<String, Value>) -> TeraResult<Value> {
+    let entry = try_get_value!("entry_path", "value", Entry, value);
+
+    Ok(to_value(entry_path(&entry.metadata.uuid, &entry.text)).unwrap())
+}
 
-pub(super) fn single_line(value: &Value, _: &HashMap<String, Value>) -> TeraResult<Value> {
+pub(super) fn single_line(value: &Value, args: &HashMap<String, Value>) -> TeraResult<Value> {
     let s = try_get_value!("single_line", "value", String, value);
 
-    let s = s.replace("\n", " ");
+    let width = match args.get("width") {
+        Some(width) => Some(try_get_value!("single_line", "width", usize, width)),
+        None => None,
+    };
 
-    Ok(to_value(&s).unwrap())
+    Ok(to_value(helper::summarize(&s, width)).unwrap())
 }
 
 pub(super) fn lines(value: &Value, _: &HashMap<String, Value>) -> TeraResult<Value> {
-    let mut out = String::new();
-
     let s = try_get_value!("lines", "value", String, value);
-    let lines = s.lines();
+
+    Ok(to_value(separate_lines(&s)).unwrap())
+}
+
+/// Wrap the text into an example block. The delimiter is longer than any
+/// line of the text that only consists of `=` so the text can not end the
+/// block early.
+pub(super) fn example_block(value: &Value, _: &HashMap<String, Value>) -> TeraResult<Value> {
+    let s = try_get_value!("example_block", "value", String, value);
+
+    let longest = s
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.is_empty() && line.chars().all(|c| c == '='))
+        .map(str::len)
+        .max()
+        .unwrap_or(0);
+    let delimiter = "=".repeat(longest.max(3) + 1);
+
+    Ok(to_value(format!("{}\n{}\n{}", delimiter, s, delimiter)).unwrap())
+}
+
+/// Put an empty line after every line outside of code blocks so asciidoc
+/// keeps the line breaks of the text. Preprocessor directives are escaped.
+fn separate_lines(input: &str) -> String {
+    let mut out = Vec::with_capacity(input.len() * 2);
+    write_separated_lines(&mut out, input).expect("writing to a vec can not fail");
+
+    String::from_utf8(out).expect("lines of a string are valid utf-8")
+}
+
+/// Write the lines like `separate_lines` without building the whole output
+/// in memory first.
+fn write_separated_lines<W: Write>(out: &mut W, input: &str) -> io::Result<()> {
     let mut is_codeblock = false;
-    for line in lines {
+    for line in input.lines() {
         if line == "----" {
             is_codeblock = !is_codeblock;
         }
 
-        out.push_str(line);
-        out.push('\n');
+        if is_preprocessor_directive(line) {
+            out.write_all(b"\\")?;
+        }
+
+        out.write_all(line.as_bytes())?;
+        out.write_all(b"\n")?;
 
         if !is_codeblock {
-            out.push('\n');
+            out.write_all(b"\n")?;
         }
     }
 
-    Ok(to_value(&out).unwrap())
+    Ok(())
 }
 
 pub(super) fn format_duration_since(
@@ -54,60 +164,151 @@ pub(super) fn format_duration_since(
     let started = try_get_value!("format_duration_since", "value", DateTime<Utc>, value);
     let duration = Utc::now().signed_duration_since(started);
 
-    Ok(to_value(&helper::format_duration(duration)).unwrap())
+    Ok(to_value(helper::format_duration(duration)).unwrap())
+}
+
+/// Format a due date relative to today like `helper::format_due`. Today can be
+/// given with the `today` argument so it matches the configured timezone.
+pub(super) fn format_due(locale: Locale) -> impl Filter {
+    move |value: &Value, args: &HashMap<String, Value>| {
+        let due = try_get_value!("format_due", "value", Option<NaiveDate>, value);
+
+        let today = match args.get("today") {
+            Some(today) => try_get_value!("format_due", "today", NaiveDate, today),
+            None => TimeContext::new(None).today(),
+        };
+
+        Ok(to_value(helper::format_due(due, today, locale)).unwrap())
+    }
+}
+
+/// Format a date in the format of the locale.
+pub(super) fn format_date(locale: Locale) -> impl Filter {
+    move |value: &Value, _: &HashMap<String, Value>| {
+        let date = try_get_value!("format_date", "value", NaiveDate, value);
+
+        Ok(to_value(locale.format_date(date)).unwrap())
+    }
+}
+
+/// Format a date together with the name of its weekday in the locale.
+pub(super) fn format_day(locale: Locale) -> impl Filter {
+    move |value: &Value, _: &HashMap<String, Value>| {
+        let date = try_get_value!("format_day", "value", NaiveDate, value);
+
+        Ok(to_value(locale.format_day(date)).unwrap())
+    }
 }
 
-pub(super) fn asciidoc_to_html(value: &Value, _: &HashMap<String, Value>) -> TeraResult<Value> {
-    let input = try_get_value!("asciidoc_to_html", "value", String, value);
+/// Tera function `t(key="...")` that looks up a label in the translations.
+/// Every other argument replaces the placeholder of the same name in the
+/// label, so `t(key="page_of", page=2, count=5)` fills `{page}` and `{count}`.
+pub(super) fn translate(translations: Translations) -> impl Function {
+    move |args: &HashMap<String, Value>| {
+        let key = match args.get("key") {
+            Some(key) => try_get_value!("t", "key", String, key),
+            None => return Err("function t is missing the key argument".into()),
+        };
 
-    let tmpdir = tempdir().expect("can not create tempdir");
-    let tmppath = tmpdir.path().join("output.asciidoc");
+        let mut label = translations.get(&key).to_string();
+        for (name, value) in args.iter().filter(|(name, _)| name.as_str() != "key") {
+            let value = match value {
+                Value::String(value) => value.clone(),
+                value => value.to_string(),
+            };
 
-    let mut file =
-        std::fs::File::create(&tmppath).expect("can not create a new file for asciiformatting");
+            label = label.replace(&format!("{{{}}}", name), &value);
+        }
+
+        Ok(to_value(label).unwrap())
+    }
+}
 
-    file.write_all(input.as_bytes())
-        .expect("can not write to asciiformatting file");
+/// If asciidoctor would treat the line as a preprocessor directive. Those are
+/// processed before anything else, even inside of code blocks, so an
+/// `include::` in the text of an entry could pull files into the output.
+/// Escaped directives are shown as they are written.
+fn is_preprocessor_directive(line: &str) -> bool {
+    PREPROCESSOR_DIRECTIVES
+        .iter()
+        .any(|directive| line.starts_with(directive))
+}
 
-    let output = std::process::Command::new("asciidoctor")
-        .arg("--safe-mode")
-        .arg("safe")
-        .arg("--no-header-footer")
+/// Render the text of an entry as it is shown on the entry page. The
+/// asciidoc document is written to the file for asciidoctor line by line so
+/// large entries are not held in memory twice.
+pub(super) fn render_entry_asciidoc(uuid: &Uuid, text: &str) -> Result<String, Error> {
+    render_asciidoc_with(
+        &uuid.to_string(),
+        |file| {
+            file.write_all(ASCIIDOC_HEADER.as_bytes())?;
+            write_separated_lines(file, text)
+        },
+        false,
+    )
+}
+
+/// Convert asciidoc to html with asciidoctor. A `standalone` document
+/// contains the html header and footer so it can be viewed on its own.
+pub(super) fn render_asciidoc(input: &str, standalone: bool) -> Result<String, Error> {
+    render_asciidoc_with(
+        "document",
+        |file| file.write_all(input.as_bytes()),
+        standalone,
+    )
+}
+
+/// Run asciidoctor on the document `write` writes to a temporary file named
+/// after `name`, so the name shows up in the messages of asciidoctor. Fails
+/// with the messages of asciidoctor if it does not succeed.
+fn render_asciidoc_with<F>(name: &str, write: F, standalone: bool) -> Result<String, Error>
+where
+    F: FnOnce(&mut BufWriter<std::fs::File>) -> io::Result<()>,
+{
+    let tmpdir = tempdir().context("can not create tempdir")?;
+    let tmppath = tmpdir.path().join(format!("{}.asciidoc", name));
+
+    let file =
+        std::fs::File::create(&tmppath).context("can not create a new file for asciiformatting")?;
+
+    let mut file = BufWriter::new(file);
+    write(&mut file)
+        .and_then(|_| file.flush())
+        .context("can not write to asciiformatting file")?;
+    drop(file);
+
+    let output = asciidoctor_command(standalone)
         .arg("--out-file")
         .arg("-")
         .arg(tmppath)
         .output()
-        .expect("problems while running asciidoctor");
+        .context("problems while running asciidoctor")?;
 
-    let out = String::from_utf8_lossy(&output.stdout).into_owned();
+    if !output.status.success() {
+        bail!(
+            "asciidoctor failed to render {} with {}: {}",
+            name,
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )
+    }
 
-    Ok(to_value(&out).unwrap())
+    // Only copy the html if it has to be fixed up.
+    Ok(String::from_utf8(output.stdout)
+        .unwrap_or_else(|err| String::from_utf8_lossy(err.as_bytes()).into_owned()))
 }
 
-pub(super) fn asciidoc_header(value: &Value, _: &HashMap<String, Value>) -> TeraResult<Value> {
-    let input = try_get_value!("asciidoc_header", "value", String, value);
-
-    let mut out = String::from(
-        r#"
-:toc: right
-:toclevels: 3
-:sectanchors:
-:sectlink:
-:icons: font
-:linkattrs:
-:numbered:
-:idprefix:
-:idseparator: -
-:doctype: book
-:source-highlighter: pygments
-:listing-caption: Listing
-:hide-uri-scheme:
-"#,
-    );
+/// Asciidoctor in safe mode so documents can not include files from outside
+/// of the folder they are rendered in.
+fn asciidoctor_command(standalone: bool) -> std::process::Command {
+    let mut command = std::process::Command::new("asciidoctor");
+    command.arg("--safe-mode").arg("safe");
 
-    out.push_str(&input);
+    if !standalone {
+        command.arg("--no-header-footer");
+    }
 
-    Ok(to_value(&out).unwrap())
+    command
 }
 
 pub(super) fn some_or_dash(value: &Value, _: &HashMap<String, Value>) -> TeraResult<Value> {
@@ -131,3 +332,370 @@ pub(super) fn some_or_dash(value: &Value, _: &HashMap<String, Value>) -> TeraRes
 pub(super) fn some(value: Option<&Value>, _params: &[Value]) -> TeraResult<bool> {
     Ok(matches!(value, Some(Value::Null)))
 }
+
+/// List the names of all entry templates in the datadir.
+pub(super) fn entry_templates<P: AsRef<Path>>(datadir: P) -> Result<Vec<String>, Error> {
+    let folder = entry_templates_folder(&datadir);
+
+    if !folder.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut templates = std::fs::read_dir(&folder)
+        .with_context(|| format!("can not read templates folder {:?}", folder))?
+        .collect::<Result<Vec<_>, _>>()
+        .context("can not read entry in templates folder")?
+        .into_iter()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            path.extension()
+                .map(|extension| extension == ENTRY_TEMPLATE_EXTENSION)
+                .unwrap_or(false)
+        })
+        .filter_map(|path| {
+            path.file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+        })
+        .collect::<Vec<_>>();
+
+    templates.sort();
+
+    Ok(templates)
+}
+
+/// Render the entry template with the given name. The variables `date` and
+/// `project` are always available, additional variables can be passed in.
+pub(super) fn render_entry_template<P: AsRef<Path>>(
+    datadir: P,
+    name: &str,
+    project: &str,
+    variables: &BTreeMap<String, String>,
+    time: &TimeContext,
+) -> Result<String, Error> {
+    let path = entry_template_path(&datadir, name);
+
+    if !path.exists() {
+        bail!("no template with the name {} found at {:?}", name, path)
+    }
+
+    let source = std::fs::read_to_string(&path)
+        .with_context(|| format!("can not read template {} from {:?}", name, path))?;
+
+    let mut context = Context::new();
+    context.insert("date", &time.today().to_string());
+    context.insert("project", project);
+    for (key, value) in variables {
+        context.insert(key, value);
+    }
+
+    let mut tera = Tera::default();
+
+    if let Err(err) = tera.add_raw_template(name, &source) {
+        bail!(
+            "can not parse template {}: {}",
+            name,
+            tera_error_message(&err)
+        )
+    }
+
+    match tera.render(name, &context) {
+        Ok(rendered) => Ok(rendered),
+        Err(err) => {
+            let message = tera_error_message(&err);
+
+            match template_error_line(&source, &message) {
+                Some(line) => bail!(
+                    "can not render template {} at line {}: {}",
+                    name,
+                    line,
+                    message
+                ),
+                None => bail!("can not render template {}: {}", name, message),
+            }
+        }
+    }
+}
+
+fn entry_templates_folder<P: AsRef<Path>>(datadir: P) -> PathBuf {
+    datadir.as_ref().join(ENTRY_TEMPLATES_FOLDER)
+}
+
+fn entry_template_path<P: AsRef<Path>>(datadir: P, name: &str) -> PathBuf {
+    let mut path = entry_templates_folder(datadir).join(name);
+    path.set_extension(ENTRY_TEMPLATE_EXTENSION);
+
+    path
+}
+
+/// Tera puts the interesting parts of the error into the error sources so
+/// this collects the whole chain into one message.
+fn tera_error_message(err: &tera::Error) -> String {
+    let mut message = err.to_string();
+
+    let mut source = std::error::Error::source(err);
+    while let Some(err) = source {
+        message.push_str(": ");
+        message.push_str(&err.to_string());
+
+        source = err.source();
+    }
+
+    message
+}
+
+/// Find the line of the template that references the variable mentioned in a
+/// render error. Tera only reports line numbers for syntax errors.
+fn template_error_line(source: &str, message: &str) -> Option<usize> {
+    let variable = message.split('`').nth(1)?;
+
+    source
+        .lines()
+        .position(|line| {
+            line.split("{{")
+                .skip(1)
+                .chain(line.split("{%").skip(1))
+                .any(|expression| expression.contains(variable))
+        })
+        .map(|index| index + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call_t(args: &[(&str, Value)]) -> TeraResult<Value> {
+        let args = args
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect();
+
+        translate(Translations::default()).call(&args)
+    }
+
+    #[test]
+    fn single_line_agrees_with_the_list_summary() {
+        let text = "== Call the vendor\nabout the invoice";
+        let mut args = HashMap::new();
+        args.insert("width".to_string(), to_value(16).unwrap());
+
+        assert_eq!(
+            single_line(&to_value(text).unwrap(), &args).unwrap(),
+            to_value(helper::summarize(text, Some(16))).unwrap()
+        );
+    }
+
+    fn slug_of(text: &str) -> String {
+        let uuid = Uuid::nil();
+        let path = entry_path(&uuid, text);
+        let encoded = path
+            .strip_prefix(&format!("/entry/{}/", uuid))
+            .unwrap_or_else(|| panic!("unexpected entry path {}", path));
+
+        helper::percent_decode(encoded).unwrap()
+    }
+
+    #[test]
+    fn entry_path_of_empty_text_has_a_placeholder_slug() {
+        for text in &["", " \n\n", "!!! ???", "== "] {
+            assert_eq!(slug_of(text), "entry", "{:?}", text);
+        }
+
+        // Leading blank lines are skipped.
+        assert_eq!(slug_of("\n\n== Title"), "title");
+    }
+
+    #[test]
+    fn entry_path_keeps_non_ascii_letters() {
+        assert_eq!(
+            entry_path(&Uuid::nil(), "Café über 東京"),
+            format!(
+                "/entry/{}/caf%C3%A9-%C3%BCber-%E6%9D%B1%E4%BA%AC",
+                Uuid::nil()
+            )
+        );
+        assert_eq!(slug_of("Café über 東京"), "café-über-東京");
+
+        // Composed and decomposed accents get the same slug.
+        assert_eq!(slug_of("Cafe\u{301}"), slug_of("Caf\u{e9}"));
+    }
+
+    #[test]
+    fn entry_path_of_long_text_is_cut_at_a_word() {
+        let text =
+            "Prepare the quarterly report for the board meeting next week and send it around";
+        let slug = slug_of(text);
+        assert_eq!(
+            slug,
+            "prepare-the-quarterly-report-for-the-board-meeting-next-week"
+        );
+        assert!(slug.chars().count() <= 60);
+
+        // A single word longer than the limit is cut in the word.
+        let word = "a".repeat(100);
+        assert_eq!(slug_of(&word), "a".repeat(60));
+
+        let word = "ä".repeat(100);
+        assert_eq!(slug_of(&word), "ä".repeat(60));
+    }
+
+    #[test]
+    fn translate_fills_placeholders() {
+        let label = call_t(&[
+            ("key", to_value("page_of").unwrap()),
+            ("page", to_value(2).unwrap()),
+            ("count", to_value(5).unwrap()),
+        ])
+        .unwrap();
+
+        assert_eq!(label, to_value("page 2 of 5").unwrap());
+    }
+
+    #[test]
+    fn translate_keeps_unknown_placeholders() {
+        let label = call_t(&[("key", to_value("page_of").unwrap())]).unwrap();
+
+        assert_eq!(label, to_value("page {page} of {count}").unwrap());
+    }
+
+    #[test]
+    fn translate_needs_key() {
+        assert!(call_t(&[("page", to_value(2).unwrap())]).is_err());
+    }
+
+    fn template_in_datadir(source: &str) -> tempfile::TempDir {
+        let datadir = tempfile::tempdir().unwrap();
+        let folder = entry_templates_folder(datadir.path());
+        std::fs::create_dir_all(&folder).unwrap();
+        std::fs::write(entry_template_path(datadir.path(), "release"), source).unwrap();
+        std::fs::write(folder.join("notes.txt"), "not a template").unwrap();
+
+        datadir
+    }
+
+    fn render_release(datadir: &Path, variables: &[(&str, &str)]) -> Result<String, Error> {
+        let variables = variables
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect();
+        let time = TimeContext::new(None).with_now("2026-10-16T12:00:00Z".parse().unwrap());
+
+        render_entry_template(datadir, "release", "work", &variables, &time)
+    }
+
+    #[test]
+    fn entry_template_fills_variables() {
+        let datadir =
+            template_in_datadir("== Release {{ version }}\n{{ project }} on {{ date }}\n");
+
+        assert_eq!(
+            render_release(datadir.path(), &[("version", "1.2")]).unwrap(),
+            "== Release 1.2\nwork on 2026-10-16\n"
+        );
+        assert_eq!(entry_templates(datadir.path()).unwrap(), vec!["release"]);
+    }
+
+    #[test]
+    fn entry_template_names_the_line_of_a_missing_variable() {
+        let datadir = template_in_datadir("== Release\n\nVersion {{ version }}\n");
+
+        let err = render_release(datadir.path(), &[]).unwrap_err().to_string();
+
+        assert!(
+            err.starts_with("can not render template release at line 3:"),
+            "{}",
+            err
+        );
+        assert!(err.contains("version"), "{}", err);
+    }
+
+    #[test]
+    fn unknown_entry_template_fails() {
+        let datadir = tempfile::tempdir().unwrap();
+
+        assert!(entry_templates(datadir.path()).unwrap().is_empty());
+        assert!(render_release(datadir.path(), &[])
+            .unwrap_err()
+            .to_string()
+            .starts_with("no template with the name release found"));
+    }
+
+    #[test]
+    fn format_due_filter_agrees_with_the_list() {
+        let due = NaiveDate::from_ymd_opt(2024, 6, 6).unwrap();
+        let today = NaiveDate::from_ymd_opt(2024, 6, 1).unwrap();
+
+        let mut args = HashMap::new();
+        args.insert("today".to_string(), to_value(today).unwrap());
+
+        assert_eq!(
+            format_due(Locale::En)
+                .filter(&to_value(Some(due)).unwrap(), &args)
+                .unwrap(),
+            to_value(helper::format_due(Some(due), today, Locale::En)).unwrap()
+        );
+    }
+
+    #[test]
+    fn example_block_outlasts_delimiters_in_the_text() {
+        let block = |text: &str| {
+            example_block(&to_value(text).unwrap(), &HashMap::new())
+                .unwrap()
+                .as_str()
+                .unwrap()
+                .to_string()
+        };
+
+        assert_eq!(block("text"), "====\ntext\n====");
+        assert_eq!(
+            block("before\n====\nafter"),
+            "=====\nbefore\n====\nafter\n====="
+        );
+        assert_eq!(block("======  \n"), "=======\n======  \n\n=======");
+    }
+
+    #[test]
+    fn preprocessor_directives_are_escaped() {
+        let text = "include::/etc/passwd[]\n----\nifdef::env[]\n----\n  include::indented[]";
+
+        assert_eq!(
+            separate_lines(text),
+            "\\include::/etc/passwd[]\n\n----\n\\ifdef::env[]\n----\n\n  include::indented[]\n\n"
+        );
+    }
+
+    #[test]
+    fn asciidoctor_runs_in_safe_mode() {
+        let command = asciidoctor_command(true);
+        let args = command.get_args().collect::<Vec<_>>();
+
+        assert_eq!(args, vec!["--safe-mode", "safe"]);
+    }
+
+    #[test]
+    fn entry_text_can_not_include_files() {
+        if std::process::Command::new("asciidoctor")
+            .arg("--version")
+            .output()
+            .is_err()
+        {
+            return;
+        }
+
+        let directory = tempfile::tempdir().unwrap();
+        let secret = directory.path().join("secret.txt");
+        std::fs::write(&secret, "secret content").unwrap();
+
+        let html = render_entry_asciidoc(
+            &Uuid::new_v4(),
+            &format!("include::{}[]\n\n{{{{ 7*7 }}}}", secret.display()),
+        )
+        .unwrap();
+        assert!(!html.contains("secret content"), "{}", html);
+        assert!(html.contains("include::"), "{}", html);
+        assert!(html.contains("{{ 7*7 }}"), "{}", html);
+
+        // Without the escaping safe mode still keeps the file out.
+        let html = render_asciidoc(&format!("include::{}[]", secret.display()), false).unwrap();
+        assert!(!html.contains("secret content"), "{}", html);
+    }
+}
@@ -0,0 +1,120 @@
+use anyhow::{
+    bail,
+    Context,
+    Error,
+};
+use std::{
+    io::Write,
+    process::{
+        Command,
+        Stdio,
+    },
+};
+
+/// Normalize the text of an entry. Line endings become `\n`, trailing
+/// whitespace is removed from every line and the text ends with exactly one
+/// line break. Text that only consists of whitespace becomes empty.
+pub(super) fn normalize(text: &str) -> String {
+    let text = text.replace("\r\n", "\n").replace('\r', "\n");
+
+    let mut out = text
+        .lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let trimmed = out.trim_end_matches('\n').len();
+    out.truncate(trimmed);
+
+    if !out.is_empty() {
+        out.push('\n');
+    }
+
+    out
+}
+
+/// Run the formatter command with the text on stdin and return what it wrote
+/// to stdout. The command is split on whitespace and the first part is the
+/// program to run.
+pub(super) fn run_formatter(command: &str, text: &str) -> Result<String, Error> {
+    let mut parts = command.split_whitespace();
+    let program = match parts.next() {
+        Some(program) => program,
+        None => bail!("formatter command is empty"),
+    };
+
+    let mut child = Command::new(program)
+        .args(parts)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("can not run formatter {}", command))?;
+
+    child
+        .stdin
+        .take()
+        .expect("stdin of formatter was not piped")
+        .write_all(text.as_bytes())
+        .context("can not write entry text to formatter")?;
+
+    let output = child
+        .wait_with_output()
+        .with_context(|| format!("can not wait for formatter {}", command))?;
+
+    if !output.status.success() {
+        bail!("formatter {} failed with {}", command, output.status)
+    }
+
+    String::from_utf8(output.stdout).context("formatter did not output valid utf-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn trailing_whitespace_is_removed_from_every_line() {
+        assert_eq!(
+            normalize("title  \n\tindented\t\nlast line \t"),
+            "title\n\tindented\nlast line\n"
+        );
+        assert_eq!(normalize("crlf \r\nand\rcr\r\n"), "crlf\nand\ncr\n");
+    }
+
+    #[test]
+    fn trailing_blank_lines_collapse_into_one_line_break() {
+        assert_eq!(normalize("text\n\n  \n\t\n"), "text\n");
+        assert_eq!(normalize("text"), "text\n");
+        assert_eq!(normalize(" \n\r\n\t"), "");
+        assert_eq!(normalize(""), "");
+
+        // Blank lines inside the text can be part of a listing block so they
+        // are only emptied and not collapsed.
+        assert_eq!(
+            normalize("----\nfirst\n\n \nsecond\n----\n"),
+            "----\nfirst\n\n\nsecond\n----\n"
+        );
+    }
+
+    #[test]
+    fn normalize_is_idempotent() {
+        for text in &[
+            "",
+            "clean\n",
+            "title  \r\n\r\n body\t\n\n\n",
+            "\n\nleading blank lines\n",
+            "  \n",
+        ] {
+            let once = normalize(text);
+            assert_eq!(normalize(&once), once, "{:?}", text);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn formatter_gets_the_text_on_stdin() {
+        assert_eq!(run_formatter("tr a-z A-Z", "text\n").unwrap(), "TEXT\n");
+        assert!(run_formatter("false", "text\n").is_err());
+        assert!(run_formatter("  ", "text\n").is_err());
+    }
+}
@@ -0,0 +1,292 @@
+use crate::config::Config;
+use chrono::{
+    DateTime,
+    Datelike,
+    Duration,
+    Local,
+    NaiveDate,
+    Utc,
+    Weekday,
+};
+use chrono_tz::Tz;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+
+/// Day weeks start on in reports that group by week.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum WeekStart {
+    #[default]
+    Monday,
+    Sunday,
+}
+
+/// Converts timestamps into the dates a user sees. All conversions from
+/// `DateTime<Utc>` to days should go through here so they use the configured
+/// timezone instead of mixing UTC and local time.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct TimeContext {
+    /// Configured timezone. The timezone of the system is used if this is
+    /// `None`.
+    timezone: Option<Tz>,
+
+    /// Fixed point in time that is used as now. The system clock is used if
+    /// this is `None`.
+    now: Option<DateTime<Utc>>,
+
+    /// Day weeks start on.
+    week_start: WeekStart,
+}
+
+impl TimeContext {
+    pub(crate) fn new(timezone: Option<Tz>) -> Self {
+        Self {
+            timezone,
+            now: None,
+            week_start: WeekStart::default(),
+        }
+    }
+
+    /// Time context with the timezone and the start of the week from the
+    /// config.
+    pub(crate) fn from_config(config: &Config) -> Self {
+        Self {
+            week_start: config.week_starts_on,
+            ..Self::new(config.timezone)
+        }
+    }
+
+    /// Use the given time as now.
+    pub(crate) fn with_now(self, now: DateTime<Utc>) -> Self {
+        Self {
+            now: Some(now),
+            ..self
+        }
+    }
+
+    /// Pin now to the current time so everything rendered from this context
+    /// agrees on what now is.
+    pub(crate) fn frozen(self) -> Self {
+        let now = self.now();
+        self.with_now(now)
+    }
+
+    pub(crate) fn now(&self) -> DateTime<Utc> {
+        self.now.unwrap_or_else(Utc::now)
+    }
+
+    pub(crate) fn today(&self) -> NaiveDate {
+        self.date(self.now())
+    }
+
+    /// Day the timestamp falls on in the configured timezone.
+    pub(crate) fn date(&self, time: DateTime<Utc>) -> NaiveDate {
+        match self.timezone {
+            Some(timezone) => time.with_timezone(&timezone).date().naive_local(),
+            None => time.with_timezone(&Local).date().naive_local(),
+        }
+    }
+
+    /// First and last day of the week the date falls in. All grouping by week
+    /// should go through here so reports agree on when a week starts.
+    pub(crate) fn week_bounds(&self, date: NaiveDate) -> (NaiveDate, NaiveDate) {
+        let start = match self.week_start {
+            WeekStart::Monday => Weekday::Mon,
+            WeekStart::Sunday => Weekday::Sun,
+        };
+
+        let days_since_start =
+            (7 + date.weekday().num_days_from_monday() - start.num_days_from_monday()) % 7;
+        let first_day = date - Duration::days(i64::from(days_since_start));
+
+        (first_day, first_day + Duration::days(6))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        helper,
+        locale::Locale,
+    };
+
+    fn at(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    fn day(year: i32, month: u32, day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn days_in_utc_plus_13() {
+        let time = TimeContext::new(Some(Tz::Pacific__Tongatapu));
+
+        assert_eq!(time.date(at("2026-01-15T10:59:59Z")), day(2026, 1, 15));
+        assert_eq!(time.date(at("2026-01-15T11:00:00Z")), day(2026, 1, 16));
+    }
+
+    #[test]
+    fn due_today_follows_the_configured_timezone() {
+        let now = at("2026-01-15T11:30:00Z");
+        let utc = TimeContext::new(Some(Tz::UTC)).with_now(now);
+        let tonga = TimeContext::new(Some(Tz::Pacific__Tongatapu)).with_now(now);
+        let due = Some(day(2026, 1, 16));
+
+        assert_eq!(
+            helper::format_due(due, utc.today(), Locale::En),
+            "2026-01-16 (in 1d)"
+        );
+        assert_eq!(
+            helper::format_due(due, tonga.today(), Locale::En),
+            "2026-01-16 (today)"
+        );
+    }
+
+    #[test]
+    fn default_is_the_system_timezone() {
+        let config = Config::default();
+        let time = TimeContext::from_config(&config);
+        let now = at("2026-01-15T11:30:00Z");
+
+        assert_eq!(config.timezone, None);
+        assert_eq!(
+            time.date(now),
+            now.with_timezone(&Local).naive_local().date()
+        );
+    }
+
+    #[test]
+    fn frozen_keeps_now() {
+        let time = TimeContext::new(Some(Tz::UTC)).frozen();
+
+        assert_eq!(time.now(), time.now());
+        assert_eq!(
+            TimeContext::new(None)
+                .with_now(at("2026-01-15T11:30:00Z"))
+                .frozen()
+                .now(),
+            at("2026-01-15T11:30:00Z")
+        );
+    }
+
+    fn weeks_starting_on(week_start: WeekStart) -> TimeContext {
+        TimeContext::from_config(&Config {
+            week_starts_on: week_start,
+            ..Config::default()
+        })
+    }
+
+    #[test]
+    fn weeks_start_on_monday_by_default() {
+        let time = TimeContext::from_config(&Config::default());
+
+        assert_eq!(
+            time.week_bounds(day(2026, 10, 16)),
+            (day(2026, 10, 12), day(2026, 10, 18))
+        );
+        assert_eq!(
+            time.week_bounds(day(2026, 10, 12)),
+            (day(2026, 10, 12), day(2026, 10, 18))
+        );
+        assert_eq!(
+            time.week_bounds(day(2026, 10, 18)),
+            (day(2026, 10, 12), day(2026, 10, 18))
+        );
+    }
+
+    #[test]
+    fn weeks_cross_year_boundaries() {
+        let monday = weeks_starting_on(WeekStart::Monday);
+        let sunday = weeks_starting_on(WeekStart::Sunday);
+
+        // 2026-01-01 is a Thursday.
+        assert_eq!(
+            monday.week_bounds(day(2026, 1, 1)),
+            (day(2025, 12, 29), day(2026, 1, 4))
+        );
+        assert_eq!(
+            sunday.week_bounds(day(2026, 1, 1)),
+            (day(2025, 12, 28), day(2026, 1, 3))
+        );
+
+        // 2026-01-04 is a Sunday, the last day of a Monday week and the
+        // first day of a Sunday week.
+        assert_eq!(
+            monday.week_bounds(day(2026, 1, 4)),
+            (day(2025, 12, 29), day(2026, 1, 4))
+        );
+        assert_eq!(
+            sunday.week_bounds(day(2026, 1, 4)),
+            (day(2026, 1, 4), day(2026, 1, 10))
+        );
+
+        // 2020 has an ISO week 53 that ends in 2021.
+        assert_eq!(
+            monday.week_bounds(day(2021, 1, 3)),
+            (day(2020, 12, 28), day(2021, 1, 3))
+        );
+        assert_eq!(
+            sunday.week_bounds(day(2020, 12, 31)),
+            (day(2020, 12, 27), day(2021, 1, 2))
+        );
+
+        // Leap day.
+        assert_eq!(
+            sunday.week_bounds(day(2024, 2, 29)),
+            (day(2024, 2, 25), day(2024, 3, 2))
+        );
+    }
+
+    #[test]
+    fn every_day_falls_in_its_week() {
+        for week_start in [WeekStart::Monday, WeekStart::Sunday] {
+            let time = weeks_starting_on(week_start);
+            let first_weekday = match week_start {
+                WeekStart::Monday => Weekday::Mon,
+                WeekStart::Sunday => Weekday::Sun,
+            };
+
+            let mut date = day(2019, 12, 1);
+            while date < day(2028, 2, 1) {
+                let (first, last) = time.week_bounds(date);
+
+                assert_eq!(first.weekday(), first_weekday, "{}", date);
+                assert_eq!(last - first, Duration::days(6), "{}", date);
+                assert!(first <= date && date <= last, "{}", date);
+                assert_eq!(time.week_bounds(first), (first, last), "{}", date);
+                assert_eq!(time.week_bounds(last), (first, last), "{}", date);
+
+                // Monday weeks are ISO weeks, Sunday weeks are ISO weeks
+                // shifted by a day.
+                let shift = match week_start {
+                    WeekStart::Monday => Duration::zero(),
+                    WeekStart::Sunday => Duration::days(1),
+                };
+                let iso_week = |date: NaiveDate| {
+                    let week = (date + shift).iso_week();
+                    (week.year(), week.week())
+                };
+                assert_eq!(iso_week(first), iso_week(date), "{}", date);
+
+                date = date.succ_opt().unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn week_start_is_read_from_the_config() {
+        #[derive(Deserialize)]
+        struct Settings {
+            week_starts_on: WeekStart,
+        }
+
+        let settings: Settings = toml::from_str("week_starts_on = \"sunday\"").unwrap();
+        assert_eq!(settings.week_starts_on, WeekStart::Sunday);
+
+        assert!(toml::from_str::<Settings>("week_starts_on = \"friday\"").is_err());
+    }
+}
@@ -0,0 +1,105 @@
+use anyhow::{
+    Context,
+    Error,
+};
+use chrono::Local;
+use crossterm::{
+    cursor::MoveTo,
+    execute,
+    terminal::{
+        Clear,
+        ClearType,
+    },
+};
+use std::{
+    io,
+    sync::mpsc::{
+        self,
+        RecvTimeoutError,
+    },
+    time::Duration,
+};
+
+/// Remembers the last fingerprint of the store to decide if the output has to
+/// be rendered again.
+#[derive(Debug)]
+pub(super) struct ChangeDetector<T> {
+    last: Option<T>,
+}
+
+impl<T> Default for ChangeDetector<T> {
+    fn default() -> Self {
+        Self { last: None }
+    }
+}
+
+impl<T: PartialEq> ChangeDetector<T> {
+    /// If the fingerprint differs from the last one. The first fingerprint
+    /// always counts as a change so the output is rendered once.
+    pub(super) fn changed(&mut self, fingerprint: T) -> bool {
+        if self.last.as_ref() == Some(&fingerprint) {
+            return false;
+        }
+
+        self.last = Some(fingerprint);
+        true
+    }
+}
+
+/// Show the output of `render` and show it again whenever `fingerprint`
+/// changes. The fingerprint is checked every `interval`. The screen is cleared
+/// before each render and a line tells when it was last updated. Returns when
+/// ctrl-c is pressed.
+pub(super) fn watch<T, F, R>(
+    interval: Duration,
+    mut fingerprint: F,
+    mut render: R,
+) -> Result<(), Error>
+where
+    T: PartialEq,
+    F: FnMut() -> Result<T, Error>,
+    R: FnMut() -> Result<String, Error>,
+{
+    let (stop_sender, stop) = mpsc::channel();
+    ctrlc::set_handler(move || {
+        let _ = stop_sender.send(());
+    })
+    .context("can not set handler for stopping the watch")?;
+
+    let mut detector = ChangeDetector::default();
+
+    loop {
+        if detector.changed(fingerprint()?) {
+            let output = render()?;
+
+            execute!(io::stdout(), Clear(ClearType::All), MoveTo(0, 0))
+                .context("can not clear the screen")?;
+            print!("{}", output);
+            println!(
+                "last updated {}, press ctrl-c to stop",
+                Local::now().format("%H:%M:%S")
+            );
+        }
+
+        match stop.recv_timeout(interval) {
+            Err(RecvTimeoutError::Timeout) => continue,
+            Ok(()) | Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_changed_fingerprints_are_rendered() {
+        let mut detector = ChangeDetector::default();
+
+        assert!(detector.changed(1));
+        assert!(!detector.changed(1));
+        assert!(detector.changed(2));
+        assert!(!detector.changed(2));
+        assert!(detector.changed(1));
+    }
+}
@@ -0,0 +1,1025 @@
+use super::{
+    api_entries::read_text_body,
+    audit,
+    auth,
+    auth::{
+        authorize,
+        Access,
+    },
+    project_param,
+    valid_project,
+    webhook::{
+        DEDUP_KEY_FIELD,
+        DEFAULT_WEBHOOK,
+    },
+    WebService,
+    CHANGES_OVERLAP_SECONDS,
+    DEFAULT_HISTORY_DAYS,
+};
+use crate::{
+    entry::{
+        Entry,
+        EntrySource,
+    },
+    helper,
+    store::audit::AuditFilter,
+    templating,
+};
+use chrono::{
+    DateTime,
+    Utc,
+};
+use http_types::mime;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use tide::{
+    Body,
+    Request,
+    Response,
+    StatusCode,
+};
+use uuid::Uuid;
+
+/// Changes made through the webservice to the projects the token has admin
+/// access to, the oldest first. Can be filtered by `uuid`, `project` and
+/// `since`.
+pub(super) async fn handler_api_v1_audit(
+    request: Request<WebService>,
+) -> Result<Response, tide::Error> {
+    #[derive(Deserialize, Debug)]
+    struct Query {
+        uuid: Option<String>,
+        project: Option<String>,
+        since: Option<DateTime<Utc>>,
+    }
+
+    let query: Query = request.query()?;
+
+    let scope = auth::scope(&request);
+
+    if let Some(project) = &query.project {
+        authorize(&request, project, Access::Admin)?;
+    }
+
+    let uuid = query
+        .uuid
+        .map(|uuid| helper::parse_uuid(&uuid))
+        .transpose()
+        .map_err(|err| tide::Error::new(StatusCode::BadRequest, err))?;
+
+    let filter = AuditFilter {
+        uuid,
+        project: query.project,
+        since: query.since,
+    };
+
+    let records = request
+        .state()
+        .store
+        .audit_log()
+        .read(&filter)?
+        .into_iter()
+        .filter(|record| scope.allows(&record.project, Access::Admin))
+        .collect::<Vec<_>>();
+
+    Ok(Response::builder(StatusCode::Ok)
+        .body(Body::from_json(&serde_json::json!({ "records": records }))?)
+        .build())
+}
+
+pub(super) async fn handler_api_v1_info(
+    request: Request<WebService>,
+) -> Result<Response, tide::Error> {
+    let state = request.state();
+
+    let mut info = state.store.info()?;
+    if !state.expose_paths {
+        info = info.redact_paths();
+    }
+
+    let response = Response::builder(200)
+        .body(Body::from_json(&info)?)
+        .content_type(mime::JSON)
+        .build();
+
+    Ok(response)
+}
+
+pub(super) async fn handler_api_v1_project_entries(
+    request: Request<WebService>,
+) -> Result<Response, tide::Error> {
+    let project = &project_param(&request)?;
+
+    let entries = request.state().store.get_active_entry_previews(project)?;
+
+    let response = Response::builder(200)
+        .body(Body::from_json(&entries)?)
+        .content_type(mime::JSON)
+        .build();
+
+    Ok(response)
+}
+
+/// Entries that were changed after `since` for clients that keep a copy of
+/// the entries in sync. Clients pass the returned `server_time` as `since` of
+/// the next request so their own clock does not matter. Entries can show up
+/// in two responses after each other and should be updated by their uuid.
+/// Without `since` all entries are returned. With `since` entries that were
+/// deleted or moved to another store since are returned as deleted.
+pub(super) async fn handler_api_v1_changes(
+    request: Request<WebService>,
+) -> Result<Response, tide::Error> {
+    #[derive(Deserialize, Debug)]
+    struct Query {
+        since: Option<DateTime<Utc>>,
+    }
+
+    #[derive(Serialize, Debug)]
+    struct Change {
+        uuid: Uuid,
+        project: String,
+        last_change: DateTime<Utc>,
+        state: &'static str,
+        title: String,
+
+        /// The entry was deleted or moved to another store and should be
+        /// removed by the client. Its title is empty as the text is gone.
+        deleted: bool,
+    }
+
+    let query: Query = request.query()?;
+
+    // Taken before the index is read so nothing written after it is lost.
+    let server_time = Utc::now() - chrono::Duration::seconds(CHANGES_OVERLAP_SECONDS);
+
+    let scope = auth::scope(&request);
+    let store = &request.state().store;
+
+    let removed = match query.since {
+        Some(since) => store.get_removed_since(since)?,
+        None => Vec::new(),
+    };
+
+    let mut changes = store
+        .get_entry_previews_changed_since(query.since)?
+        .into_iter()
+        .filter(|entry| scope.allows(&entry.metadata.project, Access::Read))
+        .map(|entry| Change {
+            uuid: entry.metadata.uuid,
+            state: if entry.is_done() { "done" } else { "active" },
+            title: helper::first_line(&entry.text),
+            project: entry.metadata.project,
+            last_change: entry.metadata.last_change,
+            deleted: false,
+        })
+        .chain(
+            removed
+                .into_iter()
+                .filter(|metadata| scope.allows(&metadata.project, Access::Read))
+                .map(|metadata| Change {
+                    uuid: metadata.uuid,
+                    state: if metadata.is_done() { "done" } else { "active" },
+                    title: String::new(),
+                    project: metadata.project,
+                    last_change: metadata.last_change,
+                    deleted: true,
+                }),
+        )
+        .collect::<Vec<_>>();
+    changes.sort_by_key(|change| change.last_change);
+
+    Ok(Response::builder(StatusCode::Ok)
+        .body(Body::from_json(&serde_json::json!({
+            "server_time": server_time,
+            "changes": changes,
+        }))?)
+        .build())
+}
+
+pub(super) async fn handler_api_v1_stats_history(
+    request: Request<WebService>,
+) -> Result<Response, tide::Error> {
+    #[derive(Deserialize, Debug)]
+    struct Query {
+        project: Option<String>,
+        limit: Option<usize>,
+    }
+
+    let query: Query = request.query()?;
+
+    if let Some(project) = &query.project {
+        authorize(&request, project, Access::Read)?;
+    }
+
+    let scope = auth::scope(&request);
+    let snapshots = request.state().store.stats_history(
+        query.project.as_deref(),
+        |project| scope.allows(project, Access::Read),
+        query.limit.unwrap_or(DEFAULT_HISTORY_DAYS),
+    )?;
+
+    Ok(Response::builder(StatusCode::Ok)
+        .body(Body::from_json(&serde_json::json!({
+            "snapshots": snapshots,
+        }))?)
+        .build())
+}
+
+pub(super) async fn handler_api_v1_project_add_entry(
+    mut request: Request<WebService>,
+) -> Result<Response, tide::Error> {
+    #[derive(Deserialize, Debug)]
+    struct Message {
+        text: String,
+    }
+
+    let project = project_param(&request)?;
+
+    let message: Message = request.body_form().await?;
+
+    let entry = Entry::builder(message.text.replace("\r", ""), project, EntrySource::Web)
+        .build()
+        .map_err(|err| tide::Error::new(StatusCode::BadRequest, err))?;
+
+    let (uuid, project) = (entry.metadata.uuid, entry.metadata.project.clone());
+    let location = templating::entry_path(&uuid, &entry.text);
+
+    request.state().store.add_entry(entry)?;
+    audit(&request, "add", uuid, &project);
+
+    Ok(Response::builder(StatusCode::SeeOther)
+        .header("Content-Type", "text/plain")
+        .header("Location", location)
+        .body(Body::from("entry updated to be done"))
+        .build())
+}
+
+/// Add an entry from a plain text body for clients that can not send forms
+/// or json.
+pub(super) async fn handler_api_v1_quick_add(
+    mut request: Request<WebService>,
+) -> Result<Response, tide::Error> {
+    #[derive(Deserialize, Debug)]
+    struct Query {
+        project: Option<String>,
+    }
+
+    let query: Query = request.query()?;
+    let project = valid_project(request.state().project_to_add_to(query.project)?)?;
+    authorize(&request, &project, Access::Write)?;
+
+    let is_plain_text = request
+        .content_type()
+        .map(|mime| mime.essence() == mime::PLAIN.essence())
+        .unwrap_or(false);
+
+    if !is_plain_text {
+        return Err(tide::Error::from_str(
+            StatusCode::UnsupportedMediaType,
+            "quick add only accepts text/plain bodies",
+        ));
+    }
+
+    let text = read_text_body(&mut request).await?;
+
+    if text.trim().is_empty() {
+        return Err(tide::Error::from_str(
+            StatusCode::BadRequest,
+            "text of the entry can not be empty",
+        ));
+    }
+
+    let entry = Entry::builder(text, project.clone(), EntrySource::Api)
+        .build()
+        .map_err(|err| tide::Error::new(StatusCode::BadRequest, err))?;
+
+    let uuid = entry.metadata.uuid;
+    let location = templating::entry_path(&uuid, &entry.text);
+
+    request.state().store.add_entry(entry)?;
+    audit(&request, "add", uuid, &project);
+
+    Ok(Response::builder(StatusCode::Created)
+        .header("Location", location)
+        .body(Body::from_json(&serde_json::json!({ "uuid": uuid }))?)
+        .build())
+}
+
+pub(super) async fn handler_api_v1_webhook(
+    mut request: Request<WebService>,
+) -> Result<Response, tide::Error> {
+    #[derive(Deserialize, Debug)]
+    struct Query {
+        name: Option<String>,
+    }
+
+    let project = project_param(&request)?;
+
+    let query: Query = request.query()?;
+    let name = query.name.unwrap_or_else(|| DEFAULT_WEBHOOK.to_string());
+
+    let webhook = request
+        .state()
+        .webhooks
+        .iter()
+        .find(|webhook| webhook.name == name)
+        .cloned()
+        .ok_or_else(|| {
+            tide::Error::from_str(StatusCode::NotFound, format!("webhook {} not found", name))
+        })?;
+
+    let body = request.body_string().await?;
+    let payload: serde_json::Value = serde_json::from_str(&body).map_err(|err| {
+        tide::Error::from_str(
+            StatusCode::BadRequest,
+            format!("can not parse webhook payload: {}", err),
+        )
+    })?;
+
+    let dedup_key = match payload.get(DEDUP_KEY_FIELD) {
+        None | Some(serde_json::Value::Null) => None,
+        Some(serde_json::Value::String(key)) => Some(key.clone()),
+        Some(key) => Some(key.to_string()),
+    };
+
+    if let Some(dedup_key) = &dedup_key {
+        if let Some(entry) = request
+            .state()
+            .store
+            .get_active_entry_by_dedup_key(&project, dedup_key)?
+        {
+            return Ok(Response::builder(StatusCode::Ok)
+                .body(Body::from_json(&serde_json::json!({
+                    "uuid": entry.metadata.uuid,
+                    "created": false,
+                }))?)
+                .build());
+        }
+    }
+
+    let text = webhook
+        .render(&payload)
+        .map_err(|err| tide::Error::from_str(StatusCode::BadRequest, err.to_string()))?;
+
+    let entry = Entry::builder(
+        text,
+        project.clone(),
+        EntrySource::Webhook(name.to_string()),
+    )
+    .dedup_key(dedup_key)
+    .build()
+    .map_err(|err| tide::Error::new(StatusCode::BadRequest, err))?;
+
+    let uuid = entry.metadata.uuid;
+    let location = templating::entry_path(&uuid, &entry.text);
+
+    request.state().store.add_entry(entry)?;
+    audit(&request, "add", uuid, &project);
+
+    Ok(Response::builder(StatusCode::Created)
+        .header("Location", location)
+        .body(Body::from_json(&serde_json::json!({
+            "uuid": uuid,
+            "created": true,
+        }))?)
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        store::tests::entry,
+        webservice::tests::{
+            app,
+            request,
+            respond,
+        },
+    };
+    use http_types::Method;
+    use std::collections::BTreeSet;
+
+    fn quick_add(path: &str, body: Body) -> http_types::Request {
+        let mut request = request(Method::Post, path, None);
+        request.set_body(body);
+
+        request
+    }
+
+    #[async_std::test]
+    async fn quick_add_takes_plain_text() {
+        let (_directory, store, app) = app("");
+
+        let response = respond(
+            &app,
+            quick_add(
+                "/api/v1/quick-add?project=work",
+                Body::from("Buy milk\n& eggs"),
+            ),
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::Created);
+
+        let entries = store.get_active_entries("work").unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries.into_iter().next().unwrap().text, "Buy milk\n& eggs");
+    }
+
+    #[async_std::test]
+    async fn quick_add_uses_the_default_project() {
+        let (_directory, store, app) = app("");
+
+        let response = respond(&app, quick_add("/api/v1/quick-add", Body::from("Buy milk"))).await;
+
+        assert_eq!(response.status(), StatusCode::Created);
+        assert_eq!(store.get_active_entries("default").unwrap().len(), 1);
+    }
+
+    #[async_std::test]
+    async fn quick_add_needs_a_project_when_it_is_required() {
+        let (_directory, store, app) = app("require_explicit_project = true");
+
+        let mut response =
+            respond(&app, quick_add("/api/v1/quick-add", Body::from("Buy milk"))).await;
+        assert_eq!(response.status(), StatusCode::BadRequest);
+        assert_eq!(
+            response.body_string().await.unwrap(),
+            "require_explicit_project is set, name the project with ?project=<name>"
+        );
+        assert!(store.get_projects().unwrap().is_empty());
+
+        let response = respond(
+            &app,
+            quick_add("/api/v1/quick-add?project=work", Body::from("Buy milk")),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::Created);
+        assert_eq!(store.get_active_entries("work").unwrap().len(), 1);
+    }
+
+    #[async_std::test]
+    async fn quick_add_rejects_other_content_types() {
+        let (_directory, store, app) = app("");
+
+        let json = Body::from_json(&serde_json::json!({ "text": "Buy milk" })).unwrap();
+        let response = respond(&app, quick_add("/api/v1/quick-add", json)).await;
+        assert_eq!(response.status(), StatusCode::UnsupportedMediaType);
+
+        let response = respond(&app, quick_add("/api/v1/quick-add", Body::from(" \n"))).await;
+        assert_eq!(response.status(), StatusCode::BadRequest);
+
+        assert_eq!(store.get_active_entries("default").unwrap().len(), 0);
+    }
+
+    fn source_of_the_only_entry(store: &crate::store::Store, project: &str) -> Option<String> {
+        let entries = store.get_active_entries(project).unwrap();
+        assert_eq!(entries.len(), 1);
+
+        entries.into_iter().next().unwrap().metadata.source
+    }
+
+    #[async_std::test]
+    async fn form_add_is_from_the_web() {
+        let (_directory, store, app) = app("");
+
+        let mut request = request(Method::Post, "/api/v1/project/add/entry/work", None);
+        request.set_body("text=Buy+milk");
+        request.set_content_type(mime::FORM);
+        let response = respond(&app, request).await;
+
+        assert_eq!(response.status(), StatusCode::SeeOther);
+        assert_eq!(
+            source_of_the_only_entry(&store, "work").as_deref(),
+            Some("web")
+        );
+    }
+
+    #[async_std::test]
+    async fn add_over_a_quota_is_unprocessable() {
+        let (_directory, store, app) = app("[quotas]\nmax_entries_per_project = 1");
+        let add = |text: &str| {
+            let mut request = request(Method::Post, "/api/v1/project/add/entry/work", None);
+            request.set_body(format!("text={}", text));
+            request.set_content_type(mime::FORM);
+            request
+        };
+
+        let response = respond(&app, add("first")).await;
+        assert_eq!(response.status(), StatusCode::SeeOther);
+
+        let mut response = respond(&app, add("second")).await;
+        assert_eq!(response.status(), StatusCode::UnprocessableEntity);
+        let details: serde_json::Value = response.body_json().await.unwrap();
+        assert_eq!(
+            details,
+            serde_json::json!({
+                "quota": "max_entries_per_project",
+                "limit": 1,
+                "count": 2,
+                "project": "work",
+                "error": "can not add more entries to project \"work\" as it is limited to 1 \
+                          entries",
+            })
+        );
+        assert_eq!(store.get_active_entries("work").unwrap().len(), 1);
+
+        let response = respond(
+            &app,
+            quick_add("/api/v1/quick-add?project=home", Body::from("third")),
+        )
+        .await;
+        assert!(response.status().is_success(), "{}", response.status());
+    }
+
+    #[async_std::test]
+    async fn webhook_add_records_the_webhook() {
+        let (_directory, store, app) = app(r#"
+            [[inbound_webhooks]]
+            name = "alerts"
+            template = "Alert {{ title }}"
+            "#);
+
+        let body = Body::from_json(&serde_json::json!({ "title": "disk full" })).unwrap();
+        let response = respond(&app, quick_add("/api/v1/webhook/work?name=alerts", body)).await;
+
+        assert_eq!(response.status(), StatusCode::Created);
+        assert_eq!(
+            source_of_the_only_entry(&store, "work").as_deref(),
+            Some("webhook:alerts")
+        );
+    }
+
+    const ALERTS_WEBHOOK: &str = r#"
+        [[inbound_webhooks]]
+        name = "alerts"
+        template = "Alert {{ alertname }}: {{ summary }}"
+        "#;
+
+    async fn webhook(
+        app: &tide::Server<WebService>,
+        body: Body,
+    ) -> (StatusCode, Option<serde_json::Value>) {
+        let mut response = respond(app, quick_add("/api/v1/webhook/work?name=alerts", body)).await;
+        let status = response.status();
+
+        (status, response.body_json().await.ok())
+    }
+
+    #[async_std::test]
+    async fn webhook_maps_missing_fields_to_blanks() {
+        let (_directory, store, app) = app(ALERTS_WEBHOOK);
+
+        let body = Body::from_json(&serde_json::json!({ "alertname": "DiskFull" })).unwrap();
+        let (status, _) = webhook(&app, body).await;
+        assert_eq!(status, StatusCode::Created);
+
+        let entries = store.get_active_entries("work").unwrap();
+        assert_eq!(entries.into_iter().next().unwrap().text, "Alert DiskFull: ");
+    }
+
+    #[async_std::test]
+    async fn webhook_dedup_key_creates_a_single_entry() {
+        let (_directory, store, app) = app(ALERTS_WEBHOOK);
+
+        let alert = || {
+            Body::from_json(&serde_json::json!({
+                "alertname": "DiskFull",
+                "summary": "disk is full",
+                "dedup_key": "disk-full-host1",
+            }))
+            .unwrap()
+        };
+
+        let (status, first) = webhook(&app, alert()).await;
+        assert_eq!(status, StatusCode::Created);
+        let first = first.unwrap();
+        assert_eq!(first["created"], true);
+
+        for _ in 0..3 {
+            let (status, again) = webhook(&app, alert()).await;
+            assert_eq!(status, StatusCode::Ok);
+            let again = again.unwrap();
+            assert_eq!(again["created"], false);
+            assert_eq!(again["uuid"], first["uuid"]);
+        }
+
+        let entries = store.get_active_entries("work").unwrap();
+        assert_eq!(entries.len(), 1);
+        let entry = entries.into_iter().next().unwrap();
+        assert_eq!(entry.metadata.dedup_key.as_deref(), Some("disk-full-host1"));
+
+        // Another key is another alert.
+        let other = Body::from_json(&serde_json::json!({
+            "alertname": "DiskFull",
+            "dedup_key": "disk-full-host2",
+        }))
+        .unwrap();
+        let (status, _) = webhook(&app, other).await;
+        assert_eq!(status, StatusCode::Created);
+        assert_eq!(store.get_active_entries("work").unwrap().len(), 2);
+    }
+
+    #[async_std::test]
+    async fn webhook_rejects_malformed_json() {
+        let (_directory, store, app) = app(ALERTS_WEBHOOK);
+
+        for body in &["{\"alertname\": ", "not json", "", "[\"alertname\"]", "42"] {
+            let (status, _) = webhook(&app, Body::from(*body)).await;
+            assert_eq!(status, StatusCode::BadRequest, "{:?}", body);
+        }
+
+        assert_eq!(store.get_active_entries("work").unwrap().len(), 0);
+    }
+
+    #[async_std::test]
+    async fn stats_history_has_the_daily_snapshots() {
+        let (_directory, store, app) = app("");
+        store.add_entry(entry("first")).unwrap();
+        let mut other = entry("second");
+        other.metadata.project = "work".to_string();
+        store.add_entry(other).unwrap();
+        store.record_stats_snapshot();
+
+        let mut response = respond(&app, request(Method::Get, "/api/v1/stats/history", None)).await;
+        assert_eq!(response.status(), StatusCode::Ok);
+        let history: serde_json::Value = response.body_json().await.unwrap();
+
+        assert_eq!(
+            history,
+            serde_json::json!({
+                "snapshots": [{
+                    "day": app.state().time.today().to_string(),
+                    "active": 2,
+                    "done": 0,
+                    "projects": {
+                        "default": {"active": 1, "done": 0},
+                        "work": {"active": 1, "done": 0},
+                    },
+                }],
+            })
+        );
+
+        let mut response = respond(
+            &app,
+            request(Method::Get, "/api/v1/stats/history?project=work", None),
+        )
+        .await;
+        let history: serde_json::Value = response.body_json().await.unwrap();
+        assert_eq!(history["snapshots"][0]["active"], 1);
+        assert_eq!(
+            history["snapshots"][0]["projects"]
+                .as_object()
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    async fn info(app: &tide::Server<WebService>) -> serde_json::Value {
+        let mut response = respond(app, request(Method::Get, "/api/v1/info", None)).await;
+        assert_eq!(response.status(), StatusCode::Ok);
+        assert_eq!(response.content_type(), Some(mime::JSON));
+
+        response.body_json().await.unwrap()
+    }
+
+    #[async_std::test]
+    async fn info_reports_the_quota_usage() {
+        let (_directory, store, app) = app("[quotas]\nmax_projects = 3");
+        store.add_entry(entry("first")).unwrap();
+
+        assert_eq!(
+            info(&app).await["quotas"],
+            serde_json::json!([{"quota": "max_projects", "limit": 3, "count": 1}])
+        );
+    }
+
+    #[async_std::test]
+    async fn info_has_the_documented_fields() {
+        let (_directory, store, app) = app("");
+        store.add_entry(entry("first")).unwrap();
+        let mut other = entry("second");
+        other.metadata.project = "work".to_string();
+        store.add_entry(other).unwrap();
+
+        let info = info(&app).await;
+
+        let fields = info
+            .as_object()
+            .unwrap()
+            .keys()
+            .map(String::as_str)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            fields,
+            vec![
+                "entry_count",
+                "head",
+                "identifier",
+                "index_fragment_count",
+                "project_count",
+                "render_failures",
+                "store_version",
+                "vcs",
+                "version",
+            ]
+        );
+        assert_eq!(info["version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(info["entry_count"], 2);
+        assert_eq!(info["project_count"], 2);
+        assert_eq!(info["head"], serde_json::Value::Null);
+    }
+
+    #[async_std::test]
+    async fn info_redacts_paths_by_default() {
+        let (directory, _store, app) = app("");
+
+        let info = info(&app).await;
+
+        assert!(info.get("datadir").is_none());
+        assert!(!info
+            .to_string()
+            .contains(&directory.path().display().to_string()));
+    }
+
+    #[async_std::test]
+    async fn info_exposes_paths_when_configured() {
+        let (_directory, store, app) = app("expose_paths = true");
+
+        let info = info(&app).await;
+
+        assert_eq!(
+            info["datadir"],
+            store.datadir().display().to_string().as_str()
+        );
+    }
+
+    async fn changes(app: &tide::Server<WebService>, path: &str) -> Vec<serde_json::Value> {
+        let mut response = respond(app, request(Method::Get, path, None)).await;
+        assert_eq!(response.status(), StatusCode::Ok);
+
+        let body: serde_json::Value = response.body_json().await.unwrap();
+        body["changes"].as_array().unwrap().clone()
+    }
+
+    fn changes_since(since: DateTime<Utc>) -> String {
+        format!(
+            "/api/v1/changes?since={}",
+            since.to_rfc3339_opts(chrono::SecondsFormat::Micros, true)
+        )
+    }
+
+    #[async_std::test]
+    async fn changes_report_deleted_and_moved_entries() {
+        let (_directory, store, app) = app("");
+        let deleted = entry("deleted entry");
+        let moved = entry("moved entry");
+        store.add_entry(deleted.clone()).unwrap();
+        store.add_entry(moved.clone()).unwrap();
+        store.add_entry(entry("kept entry")).unwrap();
+        let since = Utc::now();
+
+        store.delete_entry(&deleted, false).unwrap();
+        store.remove_moved_entry(&moved, "other").unwrap();
+
+        let changes = changes(&app, &changes_since(since)).await;
+        let removed = changes
+            .iter()
+            .map(|change| {
+                (
+                    change["uuid"].as_str().unwrap().to_string(),
+                    change["deleted"].as_bool().unwrap(),
+                    change["title"].as_str().unwrap().to_string(),
+                )
+            })
+            .collect::<BTreeSet<_>>();
+        assert_eq!(
+            removed,
+            vec![
+                (deleted.metadata.uuid.to_string(), true, String::new()),
+                (moved.metadata.uuid.to_string(), true, String::new()),
+            ]
+            .into_iter()
+            .collect()
+        );
+
+        // A full sync only has the entries that still exist.
+        let all = self::changes(&app, "/api/v1/changes").await;
+        assert_eq!(all.len(), 1);
+        assert_eq!(all[0]["title"], "kept entry");
+        assert_eq!(all[0]["deleted"], false);
+    }
+
+    fn in_project(project: &str, text: &str) -> Entry {
+        let mut entry = entry(text);
+        entry.metadata.project = project.to_string();
+        entry
+    }
+
+    fn titles(changes: &[serde_json::Value]) -> Vec<&str> {
+        changes
+            .iter()
+            .map(|change| change["title"].as_str().unwrap())
+            .collect()
+    }
+
+    #[async_std::test]
+    async fn changes_are_empty_without_new_changes() {
+        let (_directory, store, app) = app("");
+        store.add_entry(entry("old entry")).unwrap();
+        let since = Utc::now();
+
+        assert_eq!(changes(&app, &changes_since(since)).await.len(), 0);
+    }
+
+    #[async_std::test]
+    async fn changes_exclude_the_since_timestamp() {
+        let (_directory, store, app) = app("");
+        store.add_entry(entry("first")).unwrap();
+
+        let all = changes(&app, "/api/v1/changes").await;
+        let last_change = all[0]["last_change"]
+            .as_str()
+            .unwrap()
+            .parse::<DateTime<Utc>>()
+            .unwrap();
+
+        // A client that synced up to the change does not get it again.
+        let at = format!(
+            "/api/v1/changes?since={}",
+            last_change.to_rfc3339_opts(chrono::SecondsFormat::Nanos, true)
+        );
+        assert_eq!(changes(&app, &at).await.len(), 0);
+
+        let before = format!(
+            "/api/v1/changes?since={}",
+            (last_change - chrono::Duration::nanoseconds(1))
+                .to_rfc3339_opts(chrono::SecondsFormat::Nanos, true)
+        );
+        assert_eq!(titles(&changes(&app, &before).await), vec!["first"]);
+    }
+
+    #[async_std::test]
+    async fn changes_have_every_entry_of_a_large_delta_in_order() {
+        let (_directory, store, app) = app("");
+        store.add_entry(entry("before")).unwrap();
+        let since = Utc::now();
+
+        let count = 300;
+        for index in 0..count {
+            store.add_entry(entry(&format!("entry {}", index))).unwrap();
+        }
+
+        let changes = changes(&app, &changes_since(since)).await;
+        assert_eq!(changes.len(), count);
+
+        let uuids = changes
+            .iter()
+            .map(|change| change["uuid"].as_str().unwrap())
+            .collect::<BTreeSet<_>>();
+        assert_eq!(uuids.len(), count);
+
+        let last_changes = changes
+            .iter()
+            .map(|change| {
+                change["last_change"]
+                    .as_str()
+                    .unwrap()
+                    .parse::<DateTime<Utc>>()
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        assert!(last_changes.windows(2).all(|pair| pair[0] <= pair[1]));
+        assert!(!titles(&changes).contains(&"before"));
+    }
+
+    #[async_std::test]
+    async fn changes_only_have_the_projects_of_the_token() {
+        let (_directory, store, app) = app(r#"
+            [[web_tokens]]
+            name = "work"
+            token = "work"
+            projects = ["work"]
+            access = ["read"]
+            "#);
+        let since = Utc::now();
+        let hidden = in_project("home", "home entry");
+        store.add_entry(in_project("work", "work entry")).unwrap();
+        store.add_entry(hidden.clone()).unwrap();
+        store.delete_entry(&hidden, false).unwrap();
+
+        for path in &["/api/v1/changes".to_string(), changes_since(since)] {
+            let mut response = respond(&app, request(Method::Get, path, Some("work"))).await;
+            assert_eq!(response.status(), StatusCode::Ok);
+
+            let body: serde_json::Value = response.body_json().await.unwrap();
+            let changes = body["changes"].as_array().unwrap();
+            assert_eq!(titles(changes), vec!["work entry"], "{}", path);
+        }
+    }
+
+    const AUDIT_TOKENS: &str = r#"
+        [[web_tokens]]
+        name = "alice"
+        token = "alice-secret"
+        projects = ["work"]
+        access = ["read", "write", "admin"]
+
+        [[web_tokens]]
+        name = "bob"
+        token = "bob-secret"
+        projects = ["home"]
+        access = ["read", "write", "admin"]
+
+        [[web_tokens]]
+        name = "reader"
+        token = "reader-secret"
+        access = ["read"]
+        "#;
+
+    /// Wait until the background writer wrote the number of audit records.
+    async fn audit_records(store: &crate::store::Store, count: usize) {
+        for _ in 0..100 {
+            let records = store
+                .audit_log()
+                .read(&crate::store::audit::AuditFilter::default())
+                .unwrap();
+            if records.len() >= count {
+                return;
+            }
+
+            async_std::task::sleep(std::time::Duration::from_millis(50)).await;
+        }
+
+        panic!("audit records were not written");
+    }
+
+    async fn audit(
+        app: &tide::Server<WebService>,
+        query: &str,
+        token: &str,
+    ) -> (StatusCode, Option<serde_json::Value>) {
+        let mut response = respond(
+            app,
+            request(Method::Get, &format!("/api/v1/audit{}", query), Some(token)),
+        )
+        .await;
+
+        (response.status(), response.body_json().await.ok())
+    }
+
+    #[async_std::test]
+    async fn audit_lists_the_changes_of_the_projects_of_the_token() {
+        let (_directory, store, app) = app(AUDIT_TOKENS);
+        let since = Utc::now();
+
+        for (project, token) in &[("work", "alice-secret"), ("home", "bob-secret")] {
+            let mut request = request(
+                Method::Post,
+                &format!("/api/v1/project/add/entry/{}", project),
+                Some(token),
+            );
+            request.set_body("text=audited");
+            request.set_content_type(mime::FORM);
+            let response = respond(&app, request).await;
+            assert_eq!(response.status(), StatusCode::SeeOther);
+        }
+        audit_records(&store, 2).await;
+
+        let uuid = store
+            .get_active_entries("work")
+            .unwrap()
+            .into_iter()
+            .next()
+            .unwrap()
+            .metadata
+            .uuid;
+        let (status, body) = audit(&app, "", "alice-secret").await;
+        assert_eq!(status, StatusCode::Ok);
+        let records = body.unwrap()["records"].as_array().unwrap().clone();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0]["principal"], "alice");
+        assert_eq!(records[0]["action"], "add");
+        assert_eq!(records[0]["project"], "work");
+        assert_eq!(records[0]["uuid"], uuid.to_string());
+        let timestamp: DateTime<Utc> = records[0]["timestamp"].as_str().unwrap().parse().unwrap();
+        assert!(timestamp >= since);
+
+        let (_, body) = audit(&app, &format!("?uuid={}", uuid), "alice-secret").await;
+        assert_eq!(body.unwrap()["records"].as_array().unwrap().len(), 1);
+        let (_, body) = audit(&app, &format!("?uuid={}", uuid), "bob-secret").await;
+        assert!(body.unwrap()["records"].as_array().unwrap().is_empty());
+        let (_, body) = audit(&app, "?since=2999-01-01T00:00:00Z", "alice-secret").await;
+        assert!(body.unwrap()["records"].as_array().unwrap().is_empty());
+
+        let (status, _) = audit(&app, "?project=work", "bob-secret").await;
+        assert_eq!(status, StatusCode::Forbidden);
+        let (status, _) = audit(&app, "", "reader-secret").await;
+        assert_eq!(status, StatusCode::Forbidden);
+        let (status, _) = audit(&app, "?uuid=not-a-uuid", "alice-secret").await;
+        assert_eq!(status, StatusCode::BadRequest);
+    }
+}
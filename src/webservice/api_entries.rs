@@ -0,0 +1,1028 @@
+use super::{
+    audit,
+    auth::{
+        authorize,
+        authorized_entry,
+        Access,
+    },
+    entry_pages::render_entry_move_project,
+    redirect_location,
+    valid_project,
+    WebService,
+    MAX_TEXT_BODY_SIZE,
+};
+use crate::{
+    diff,
+    entry::Entry,
+    helper,
+    store::UpdateOutcome,
+    templating,
+};
+use async_std::io::ReadExt;
+use chrono::{
+    DateTime,
+    NaiveDate,
+    Utc,
+};
+use http_types::mime;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::collections::BTreeMap;
+use tide::{
+    Body,
+    Request,
+    Response,
+    StatusCode,
+};
+
+pub(super) async fn handler_api_v1_mark_entry_done(
+    request: Request<WebService>,
+) -> Result<Response, tide::Error> {
+    /// Carry the unchecked checklist items over to a new entry.
+    #[derive(Deserialize, Debug)]
+    struct Query {
+        #[serde(default)]
+        carry_over: bool,
+    }
+
+    let entry = authorized_entry(&request);
+    let query: Query = request.query()?;
+
+    let follow_up = request
+        .state()
+        .store
+        .entry_done_by_uuid(entry.metadata.uuid, query.carry_over)?;
+
+    audit(
+        &request,
+        "done",
+        entry.metadata.uuid,
+        &entry.metadata.project,
+    );
+    if let Some(follow_up) = follow_up {
+        audit(&request, "add", follow_up, &entry.metadata.project);
+    }
+
+    // Continue with the follow-up entry as that is where the open work is.
+    let entry = match follow_up {
+        Some(follow_up) => request.state().store.get_entry_by_uuid(&follow_up)?,
+        None => entry,
+    };
+    let location = redirect_location(
+        &request,
+        templating::entry_path(&entry.metadata.uuid, &entry.text),
+    )?;
+
+    Ok(Response::builder(StatusCode::SeeOther)
+        .header("Content-Type", "text/plain")
+        .header("Location", location)
+        .body(Body::from("entry updated to be done"))
+        .build())
+}
+
+pub(super) async fn handler_api_v1_mark_entry_active(
+    request: Request<WebService>,
+) -> Result<Response, tide::Error> {
+    let entry = authorized_entry(&request);
+    let uuid = entry.metadata.uuid;
+
+    request.state().store.entry_active_by_uuid(uuid)?;
+    audit(&request, "active", uuid, &entry.metadata.project);
+
+    let location = redirect_location(&request, templating::entry_path(&uuid, &entry.text))?;
+
+    Ok(Response::builder(StatusCode::SeeOther)
+        .header("Content-Type", "text/plain")
+        .header("Location", location)
+        .body(Body::from("entry updated to be active"))
+        .build())
+}
+
+/// Raw asciidoc text of the entry for tools that edit the text directly. The
+/// ETag changes with every change of the entry and can be sent back with
+/// `If-Match` when updating the text.
+pub(super) async fn handler_api_v1_entry_raw(
+    request: Request<WebService>,
+) -> Result<Response, tide::Error> {
+    let entry = authorized_entry(&request);
+
+    Ok(Response::builder(StatusCode::Ok)
+        .header("Content-Type", "text/asciidoc; charset=utf-8")
+        .header("ETag", entry_etag(&entry))
+        .body(Body::from(entry.text))
+        .build())
+}
+
+/// Replace the text of the entry with the plain text body. The metadata is
+/// kept. Fails with a precondition failed if `If-Match` does not match the
+/// current ETag of the entry.
+pub(super) async fn handler_api_v1_entry_raw_update(
+    mut request: Request<WebService>,
+) -> Result<Response, tide::Error> {
+    let old_entry = authorized_entry(&request);
+    let uuid = old_entry.metadata.uuid;
+
+    let is_text = request
+        .content_type()
+        .map(|mime| mime.essence() == mime::PLAIN.essence() || mime.essence() == "text/asciidoc")
+        .unwrap_or(false);
+
+    if !is_text {
+        return Err(tide::Error::from_str(
+            StatusCode::UnsupportedMediaType,
+            "entry text can only be updated with text/plain or text/asciidoc bodies",
+        ));
+    }
+
+    if let Some(if_match) = request.header("If-Match") {
+        let etag = entry_etag(&old_entry);
+        let matches = if_match
+            .as_str()
+            .split(',')
+            .map(str::trim)
+            .any(|tag| tag == "*" || tag == etag);
+
+        if !matches {
+            return Err(tide::Error::from_str(
+                StatusCode::PreconditionFailed,
+                format!(
+                    "entry was changed in the meantime, current ETag is {}",
+                    etag
+                ),
+            ));
+        }
+    }
+
+    let text = read_text_body(&mut request).await?;
+
+    if text.trim().is_empty() {
+        return Err(tide::Error::from_str(
+            StatusCode::BadRequest,
+            "text of the entry can not be empty",
+        ));
+    }
+
+    let last_change = old_entry.metadata.last_change;
+    let new_entry = Entry {
+        text,
+        metadata: old_entry.metadata.changed().build()?,
+    };
+
+    // The entry can still change between checking the ETag and writing it.
+    if let UpdateOutcome::Conflict(current) = request
+        .state()
+        .store
+        .update_entry_if_unchanged(new_entry, last_change)?
+    {
+        return Err(tide::Error::from_str(
+            StatusCode::PreconditionFailed,
+            format!(
+                "entry was changed in the meantime, current ETag is {}",
+                entry_etag(&current)
+            ),
+        ));
+    }
+
+    audit(&request, "edit", uuid, &old_entry.metadata.project);
+
+    let entry = request.state().store.get_entry_by_uuid(&uuid)?;
+
+    Ok(Response::builder(StatusCode::NoContent)
+        .header("ETag", entry_etag(&entry))
+        .build())
+}
+
+/// Strong ETag of the entry. Every change of the entry moves its last change
+/// forward so the ETag changes with it.
+pub(super) fn entry_etag(entry: &Entry) -> String {
+    format!("\"{}\"", entry.metadata.last_change.timestamp_nanos())
+}
+
+/// Read the body of the request as text. Fails if the body is larger than
+/// `MAX_TEXT_BODY_SIZE` or not valid utf-8. Carriage returns are removed.
+pub(super) async fn read_text_body(
+    request: &mut Request<WebService>,
+) -> Result<String, tide::Error> {
+    let mut body = Vec::new();
+    request
+        .take_body()
+        .into_reader()
+        .take(MAX_TEXT_BODY_SIZE + 1)
+        .read_to_end(&mut body)
+        .await?;
+
+    if body.len() as u64 > MAX_TEXT_BODY_SIZE {
+        return Err(tide::Error::from_str(
+            StatusCode::PayloadTooLarge,
+            format!("body can not be larger than {} bytes", MAX_TEXT_BODY_SIZE),
+        ));
+    }
+
+    let text = String::from_utf8(body)
+        .map_err(|err| tide::Error::new(StatusCode::BadRequest, err))?
+        .replace("\r", "");
+
+    Ok(text)
+}
+
+pub(super) async fn handler_api_v1_entry_edit(
+    mut request: Request<WebService>,
+) -> Result<Response, tide::Error> {
+    #[derive(Deserialize, Debug)]
+    struct Message {
+        text: String,
+        restart: Option<String>,
+        last_change: Option<DateTime<Utc>>,
+    }
+
+    let old_entry = authorized_entry(&request);
+    let uuid = old_entry.metadata.uuid;
+
+    let message: Message = request.body_form().await?;
+
+    let text = message.text.replace("\r", "");
+
+    let started = if message.restart.is_some() {
+        Utc::now()
+    } else {
+        old_entry.metadata.started
+    };
+
+    let new_entry = Entry {
+        text: text.clone(),
+        metadata: old_entry.metadata.changed().started(started).build()?,
+    };
+
+    let last_change = message
+        .last_change
+        .unwrap_or(old_entry.metadata.last_change);
+
+    match request
+        .state()
+        .store
+        .update_entry_if_unchanged(new_entry, last_change)?
+    {
+        UpdateOutcome::Updated => audit(&request, "edit", uuid, &old_entry.metadata.project),
+        UpdateOutcome::Conflict(current) => {
+            let mut template_context = tera::Context::new();
+            template_context.insert("entry", &current);
+            template_context.insert("conflict_text", &text);
+            template_context.insert(
+                "conflict_diff",
+                &diff::unified_diff("current", &current.text, "yours", &text),
+            );
+
+            let output = request
+                .state()
+                .render("entry_edit.html", &template_context)?;
+
+            return Ok(Response::builder(StatusCode::Conflict)
+                .header("Content-Type", "text/html")
+                .body(Body::from(output.as_bytes()))
+                .build());
+        }
+    }
+
+    Ok(Response::builder(StatusCode::SeeOther)
+        .header("Content-Type", "text/plain")
+        .header("Location", templating::entry_path(&uuid, &text))
+        .body(Body::from("entry text updated"))
+        .build())
+}
+
+pub(super) async fn handler_api_v1_entry_move_project(
+    mut request: Request<WebService>,
+) -> Result<Response, tide::Error> {
+    /// The project is either picked from the existing projects or typed in to
+    /// create a new one. The picked project wins if both are given.
+    #[derive(Deserialize, Debug)]
+    struct Message {
+        existing_project: Option<String>,
+        new_project: Option<String>,
+        last_change: Option<DateTime<Utc>>,
+    }
+
+    let old_entry = authorized_entry(&request);
+
+    let message: Message = request.body_form().await?;
+    let new_project = match (message.existing_project, message.new_project) {
+        (Some(project), _) if !project.is_empty() => project,
+        (_, Some(project)) => project,
+        _ => {
+            return Err(tide::Error::from_str(
+                StatusCode::BadRequest,
+                "no project to move the entry to",
+            ))
+        }
+    };
+    let new_project = valid_project(new_project)?;
+    authorize(&request, &new_project, Access::Write)?;
+
+    let last_change = message
+        .last_change
+        .unwrap_or(old_entry.metadata.last_change);
+
+    // Only the project is changed so a tab with an old copy of the entry can
+    // not bring back its old text.
+    match request.state().store.update_metadata_if_unchanged(
+        &old_entry.metadata.uuid,
+        last_change,
+        |change| change.project(new_project.clone()),
+    )? {
+        UpdateOutcome::Updated => audit(&request, "move", old_entry.metadata.uuid, &new_project),
+        UpdateOutcome::Conflict(current) => {
+            return render_entry_move_project(&request, &current, Some(&new_project))
+        }
+    }
+
+    Ok(Response::builder(StatusCode::SeeOther)
+        .header("Content-Type", "text/plain")
+        .header("Location", format!("/project/{}", new_project))
+        .body(Body::from("entry moved"))
+        .build())
+}
+
+/// Set the due date of the entry to a date like 2019-12-24. An empty date
+/// removes the due date.
+pub(super) async fn handler_api_v1_entry_due(
+    mut request: Request<WebService>,
+) -> Result<Response, tide::Error> {
+    #[derive(Deserialize, Debug)]
+    struct Message {
+        due: Option<String>,
+    }
+
+    let message: Message = request.body_form().await?;
+    let due = message.due.filter(|due| !due.is_empty());
+
+    let today = request.state().time.today();
+    let (entry, changed) = entry_with_due(&request, request.param("uuid")?, due.as_deref(), today)?;
+
+    let location = templating::entry_path(&entry.metadata.uuid, &entry.text);
+    if changed {
+        let (uuid, project) = (entry.metadata.uuid, entry.metadata.project.clone());
+        request.state().store.update_entry(entry)?;
+        audit(&request, "due", uuid, &project);
+    }
+
+    Ok(Response::builder(StatusCode::SeeOther)
+        .header("Content-Type", "text/plain")
+        .header("Location", location)
+        .body(Body::from("due date updated"))
+        .build())
+}
+
+/// Outcome of setting the due date of one entry with /api/v1/entries/due.
+#[derive(Debug, Serialize)]
+#[serde(tag = "status", rename_all = "lowercase")]
+pub(super) enum DueUpdate {
+    Updated { due: Option<NaiveDate> },
+    Unchanged { due: Option<NaiveDate> },
+    Failed { error: String },
+}
+
+/// Set the due dates of multiple entries at once. The body maps uuids to due
+/// dates like 2019-12-24, an empty date removes the due date. Entries that
+/// can not be changed or dates that are invalid are reported but do not keep
+/// the other entries from being changed. All changes are written with a
+/// single commit.
+///
+/// Json bodies get the outcome for every uuid back. Forms like the one on the
+/// project page are redirected back with the number of failed entries in
+/// `due_errors`.
+pub(super) async fn handler_api_v1_entries_due(
+    mut request: Request<WebService>,
+) -> Result<Response, tide::Error> {
+    let is_json = request
+        .content_type()
+        .map(|mime| mime.essence() == mime::JSON.essence())
+        .unwrap_or(false);
+
+    let dues: BTreeMap<String, Option<String>> = if is_json {
+        request.body_json().await?
+    } else {
+        request.body_form().await?
+    };
+
+    let today = request.state().time.today();
+    let mut updates = BTreeMap::new();
+    let mut changed = Vec::new();
+
+    for (uuid, due) in dues {
+        let due = due.filter(|due| !due.is_empty());
+
+        let update = match entry_with_due(&request, &uuid, due.as_deref(), today) {
+            Ok((entry, true)) => {
+                let update = DueUpdate::Updated {
+                    due: entry.metadata.due,
+                };
+                changed.push(entry);
+                update
+            }
+            Ok((entry, false)) => DueUpdate::Unchanged {
+                due: entry.metadata.due,
+            },
+            Err(err) => DueUpdate::Failed {
+                error: err.to_string(),
+            },
+        };
+
+        updates.insert(uuid, update);
+    }
+
+    let audited = changed
+        .iter()
+        .map(|entry| (entry.metadata.uuid, entry.metadata.project.clone()))
+        .collect::<Vec<_>>();
+
+    request.state().store.update_entries(changed)?;
+
+    for (uuid, project) in audited {
+        audit(&request, "due", uuid, &project);
+    }
+
+    if is_json {
+        return Ok(Response::builder(StatusCode::Ok)
+            .body(Body::from_json(&serde_json::json!({ "results": updates }))?)
+            .build());
+    }
+
+    let failed = updates
+        .values()
+        .filter(|update| matches!(update, DueUpdate::Failed { .. }))
+        .count();
+
+    let mut location = redirect_location(&request, "/".to_string())?;
+    if failed > 0 {
+        let separator = if location.contains('?') { '&' } else { '?' };
+        location.push_str(&format!("{}due_errors={}", separator, failed));
+    }
+
+    Ok(Response::builder(StatusCode::SeeOther)
+        .header("Content-Type", "text/plain")
+        .header("Location", location)
+        .body(Body::from("due dates updated"))
+        .build())
+}
+
+/// Entry with the due date set after checking that the request can change
+/// the entry and that the date is valid. Also returns if the due date
+/// changed, unchanged due dates are not checked against the limits again.
+pub(super) fn entry_with_due(
+    request: &Request<WebService>,
+    uuid: &str,
+    due: Option<&str>,
+    today: NaiveDate,
+) -> Result<(Entry, bool), tide::Error> {
+    let uuid =
+        helper::parse_uuid(uuid).map_err(|err| tide::Error::new(StatusCode::BadRequest, err))?;
+
+    let entry = request
+        .state()
+        .store
+        .find_entry_by_uuid(&uuid)?
+        .ok_or_else(|| {
+            tide::Error::from_str(StatusCode::NotFound, format!("404 - no entry {}", uuid))
+        })?;
+
+    authorize(request, &entry.metadata.project, Access::Write)?;
+
+    let due = due
+        .map(|due| {
+            due.parse::<NaiveDate>().map_err(|err| {
+                tide::Error::from_str(
+                    StatusCode::BadRequest,
+                    format!("invalid due date {}: {}", due, err),
+                )
+            })
+        })
+        .transpose()?;
+
+    if entry.metadata.due == due {
+        return Ok((entry, false));
+    }
+
+    if let Some(due) = due {
+        request
+            .state()
+            .due_date_limits
+            .validate(due, today)
+            .map_err(|err| tide::Error::new(StatusCode::BadRequest, err))?;
+    }
+
+    let entry = Entry {
+        metadata: entry
+            .metadata
+            .changed()
+            .due(due)
+            .build()
+            .map_err(|err| tide::Error::new(StatusCode::BadRequest, err))?,
+        ..entry
+    };
+
+    Ok((entry, true))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        store::{
+            hooks,
+            tests::entry,
+            Store,
+        },
+        webservice::tests::{
+            app,
+            request,
+            respond,
+        },
+    };
+    use http_types::Method;
+    use uuid::Uuid;
+
+    async fn move_entry(
+        app: &tide::Server<WebService>,
+        uuid: Uuid,
+        form: &str,
+    ) -> http_types::Response {
+        let mut request = request(
+            Method::Post,
+            &format!("/api/v1/entry/move_project/{}", uuid),
+            None,
+        );
+        request.set_body(form);
+        request.set_content_type(mime::FORM);
+
+        respond(app, request).await
+    }
+
+    fn seeded(store: &Store) -> Uuid {
+        let mut added = entry("entry to move");
+        added.metadata.project = "work".to_string();
+        store.add_entry(added.clone()).unwrap();
+        store.add_entry(entry("other entry")).unwrap();
+
+        added.metadata.uuid
+    }
+
+    #[async_std::test]
+    async fn move_to_a_new_project_redirects_to_it() {
+        let (_directory, store, app) = app("");
+        let uuid = seeded(&store);
+
+        let response = move_entry(&app, uuid, "existing_project=&new_project=garden").await;
+
+        assert_eq!(response.status(), StatusCode::SeeOther);
+        assert_eq!(response["Location"], "/project/garden");
+        assert_eq!(
+            store.get_entry_by_uuid(&uuid).unwrap().metadata.project,
+            "garden"
+        );
+    }
+
+    #[async_std::test]
+    async fn existing_project_wins_over_a_new_one() {
+        let (_directory, store, app) = app("");
+        let uuid = seeded(&store);
+
+        let response = move_entry(&app, uuid, "existing_project=default&new_project=garden").await;
+
+        assert_eq!(response.status(), StatusCode::SeeOther);
+        assert_eq!(response["Location"], "/project/default");
+        assert_eq!(
+            store.get_entry_by_uuid(&uuid).unwrap().metadata.project,
+            "default"
+        );
+    }
+
+    #[async_std::test]
+    async fn invalid_new_project_is_rejected() {
+        let (_directory, store, app) = app("");
+        let uuid = seeded(&store);
+
+        for form in &[
+            "existing_project=&new_project=a%2Fb",
+            "existing_project=&new_project=+garden",
+            "existing_project=&new_project=",
+        ] {
+            let response = move_entry(&app, uuid, form).await;
+
+            assert_eq!(response.status(), StatusCode::BadRequest, "{}", form);
+        }
+
+        assert_eq!(
+            store.get_entry_by_uuid(&uuid).unwrap().metadata.project,
+            "work"
+        );
+    }
+
+    #[async_std::test]
+    async fn move_with_a_stale_last_change_shows_the_conflict() {
+        let (_directory, store, app) = app("");
+        let uuid = seeded(&store);
+        let stale = store.get_entry_by_uuid(&uuid).unwrap().metadata.last_change;
+
+        // Another tab moved the entry after this one was opened.
+        let response = move_entry(&app, uuid, "existing_project=default&new_project=").await;
+        assert_eq!(response.status(), StatusCode::SeeOther);
+        let current = store.get_entry_by_uuid(&uuid).unwrap().metadata.last_change;
+
+        let form = |last_change: DateTime<Utc>| {
+            format!(
+                "existing_project=&new_project=garden&last_change={}",
+                last_change.to_rfc3339().replace('+', "%2B")
+            )
+        };
+
+        let mut response = move_entry(&app, uuid, &form(stale)).await;
+        assert_eq!(response.status(), StatusCode::Conflict);
+        let body = response.body_string().await.unwrap();
+        assert!(body.contains("It was not moved to garden."), "{}", body);
+        assert!(body.contains("<a href=\"/project/default\">default</a>"));
+        assert!(body.contains("name=\"new_project\" value=\"garden\""));
+        let hidden = serde_json::to_value(current).unwrap();
+        assert!(body.contains(&format!(
+            "name=\"last_change\" value=\"{}\"",
+            hidden.as_str().unwrap()
+        )));
+        assert_eq!(
+            store.get_entry_by_uuid(&uuid).unwrap().metadata.project,
+            "default"
+        );
+
+        // Submitting the conflict page again moves the entry.
+        let response = move_entry(&app, uuid, &form(current)).await;
+        assert_eq!(response.status(), StatusCode::SeeOther);
+        assert_eq!(
+            store.get_entry_by_uuid(&uuid).unwrap().metadata.project,
+            "garden"
+        );
+    }
+
+    #[async_std::test]
+    async fn move_page_leaves_out_the_current_project() {
+        let (_directory, store, app) = app("");
+        let uuid = seeded(&store);
+
+        let mut response = respond(
+            &app,
+            request(Method::Get, &format!("/entry/move_project/{}", uuid), None),
+        )
+        .await;
+        let body = response.body_string().await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::Ok);
+        assert!(body.contains("<option value=\"default\">"));
+        assert!(!body.contains("<option value=\"work\">"));
+        assert!(body.contains("name=\"new_project\""));
+    }
+
+    async fn set_due(
+        app: &tide::Server<WebService>,
+        uuid: Uuid,
+        due: &str,
+    ) -> http_types::Response {
+        let mut request = request(Method::Post, &format!("/api/v1/entry/due/{}", uuid), None);
+        request.set_body(format!("due={}", due));
+        request.set_content_type(mime::FORM);
+
+        respond(app, request).await
+    }
+
+    #[async_std::test]
+    async fn due_dates_outside_of_the_limits_are_rejected() {
+        let (_directory, store, app) = app("");
+        let uuid = seeded(&store);
+
+        for due in &["0225-01-01", "20025-01-01"] {
+            let response = set_due(&app, uuid, due).await;
+
+            assert_eq!(response.status(), StatusCode::BadRequest, "{}", due);
+        }
+        assert_eq!(store.get_entry_by_uuid(&uuid).unwrap().metadata.due, None);
+
+        let due = chrono::Local::now().naive_local().date() + chrono::Duration::days(7);
+        let response = set_due(&app, uuid, &due.to_string()).await;
+
+        assert_eq!(response.status(), StatusCode::SeeOther);
+        assert_eq!(
+            store.get_entry_by_uuid(&uuid).unwrap().metadata.due,
+            Some(due)
+        );
+    }
+
+    fn raw(method: Method, uuid: Uuid, etag: Option<&str>, text: &str) -> http_types::Request {
+        let mut request = request(method, &format!("/api/v1/entry/raw/{}", uuid), None);
+        if let Some(etag) = etag {
+            request.insert_header("If-Match", etag);
+        }
+        request.set_body(text);
+        request.set_content_type(mime::PLAIN);
+
+        request
+    }
+
+    #[async_std::test]
+    async fn raw_text_round_trips() {
+        let (_directory, store, app) = app("");
+        let uuid = seeded(&store);
+        let due = NaiveDate::from_ymd_opt(2026, 12, 24);
+        let mut entry = store.get_entry_by_uuid(&uuid).unwrap();
+        entry.metadata.due = due;
+        store.update_entry(entry).unwrap();
+
+        let mut response = respond(&app, raw(Method::Get, uuid, None, "")).await;
+        assert_eq!(response.status(), StatusCode::Ok);
+        assert_eq!(response["Content-Type"], "text/asciidoc; charset=utf-8");
+        let etag = response["ETag"].as_str().to_string();
+        assert_eq!(response.body_string().await.unwrap(), "entry to move");
+
+        let response = respond(
+            &app,
+            raw(Method::Put, uuid, Some(&etag), "== Changed\r\n\nnew text\n"),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::NoContent);
+        let new_etag = response["ETag"].as_str().to_string();
+        assert_ne!(new_etag, etag);
+
+        let mut response = respond(&app, raw(Method::Get, uuid, None, "")).await;
+        assert_eq!(response["ETag"], new_etag.as_str());
+        assert_eq!(
+            response.body_string().await.unwrap(),
+            "== Changed\n\nnew text\n"
+        );
+
+        let entry = store.get_entry_by_uuid(&uuid).unwrap();
+        assert_eq!(entry.metadata.project, "work");
+        assert_eq!(entry.metadata.due, due);
+    }
+
+    #[async_std::test]
+    async fn raw_update_with_an_old_etag_fails() {
+        let (_directory, store, app) = app("");
+        let uuid = seeded(&store);
+
+        let response = respond(&app, raw(Method::Get, uuid, None, "")).await;
+        let etag = response["ETag"].as_str().to_string();
+
+        let response = respond(&app, raw(Method::Put, uuid, Some(&etag), "first")).await;
+        assert_eq!(response.status(), StatusCode::NoContent);
+
+        let response = respond(&app, raw(Method::Put, uuid, Some(&etag), "second")).await;
+        assert_eq!(response.status(), StatusCode::PreconditionFailed);
+        assert_eq!(store.get_entry_by_uuid(&uuid).unwrap().text, "first");
+
+        let response = respond(&app, raw(Method::Put, uuid, Some("*"), "third")).await;
+        assert_eq!(response.status(), StatusCode::NoContent);
+        assert_eq!(store.get_entry_by_uuid(&uuid).unwrap().text, "third");
+    }
+
+    #[async_std::test]
+    async fn raw_update_conflict_names_the_current_etag() {
+        let (_directory, store, app) = app("");
+        let uuid = seeded(&store);
+
+        let response = respond(&app, raw(Method::Get, uuid, None, "")).await;
+        let stale = response["ETag"].as_str().to_string();
+
+        let mut current = store.get_entry_by_uuid(&uuid).unwrap();
+        current.text = "their text".to_string();
+        store.update_entry(current).unwrap();
+        let current = entry_etag(&store.get_entry_by_uuid(&uuid).unwrap());
+
+        let mut response = respond(&app, raw(Method::Put, uuid, Some(&stale), "my text")).await;
+        assert_eq!(response.status(), StatusCode::PreconditionFailed);
+        let body = response.body_string().await.unwrap();
+        assert!(body.contains(&current), "{}", body);
+
+        // Retrying with the current ETag works.
+        let response = respond(&app, raw(Method::Put, uuid, Some(&current), "my text")).await;
+        assert_eq!(response.status(), StatusCode::NoContent);
+        assert_eq!(store.get_entry_by_uuid(&uuid).unwrap().text, "my text");
+    }
+
+    async fn edit(
+        app: &tide::Server<WebService>,
+        uuid: Uuid,
+        text: &str,
+        last_change: DateTime<Utc>,
+    ) -> http_types::Response {
+        let mut request = request(Method::Post, &format!("/api/v1/entry/edit/{}", uuid), None);
+        request.set_body(format!(
+            "text={}&last_change={}",
+            text.replace(' ', "+"),
+            last_change.to_rfc3339_opts(chrono::SecondsFormat::Nanos, true)
+        ));
+        request.set_content_type(mime::FORM);
+
+        respond(app, request).await
+    }
+
+    #[async_std::test]
+    async fn stale_edit_shows_the_conflict_page() {
+        let (_directory, store, app) = app("");
+        let uuid = seeded(&store);
+        let stale = store.get_entry_by_uuid(&uuid).unwrap();
+
+        let mut current = stale.clone();
+        current.text = "their text".to_string();
+        store.update_entry(current).unwrap();
+        let current = store.get_entry_by_uuid(&uuid).unwrap();
+
+        let mut response = edit(&app, uuid, "my text", stale.metadata.last_change).await;
+        assert_eq!(response.status(), StatusCode::Conflict);
+        assert_eq!(response["Content-Type"], "text/html");
+
+        let body = response.body_string().await.unwrap();
+        assert!(body.contains("<pre>their text</pre>"), "{}", body);
+        assert!(body.contains("-their text"), "{}", body);
+        assert!(body.contains("+my text"), "{}", body);
+        assert!(body.contains(">my text</textarea>"), "{}", body);
+        assert!(body.contains(&format!(
+            "name=\"last_change\" value=\"{}\"",
+            serde_json::to_value(current.metadata.last_change)
+                .unwrap()
+                .as_str()
+                .unwrap()
+        )));
+        assert_eq!(store.get_entry_by_uuid(&uuid).unwrap().text, "their text");
+
+        // Sending the form of the conflict page again overwrites their text.
+        let response = edit(&app, uuid, "my text", current.metadata.last_change).await;
+        assert_eq!(response.status(), StatusCode::SeeOther);
+        assert_eq!(store.get_entry_by_uuid(&uuid).unwrap().text, "my text");
+    }
+
+    #[async_std::test]
+    async fn raw_update_rejects_invalid_bodies() {
+        let (_directory, store, app) = app("");
+        let uuid = seeded(&store);
+
+        let oversized = "a".repeat(MAX_TEXT_BODY_SIZE as usize + 1);
+        let response = respond(&app, raw(Method::Put, uuid, None, &oversized)).await;
+        assert_eq!(response.status(), StatusCode::PayloadTooLarge);
+
+        let response = respond(&app, raw(Method::Put, uuid, None, " \n")).await;
+        assert_eq!(response.status(), StatusCode::BadRequest);
+
+        let mut request = raw(Method::Put, uuid, None, "text");
+        request.set_content_type(mime::JSON);
+        let response = respond(&app, request).await;
+        assert_eq!(response.status(), StatusCode::UnsupportedMediaType);
+
+        assert_eq!(
+            store.get_entry_by_uuid(&uuid).unwrap().text,
+            "entry to move"
+        );
+    }
+
+    #[async_std::test]
+    async fn raw_text_of_unknown_entries_is_not_found() {
+        let (_directory, _store, app) = app("");
+
+        for method in [Method::Get, Method::Put] {
+            let response = respond(&app, raw(method, Uuid::new_v4(), None, "text")).await;
+
+            assert_eq!(response.status(), StatusCode::NotFound);
+        }
+    }
+
+    async fn set_dues(
+        app: &tide::Server<WebService>,
+        path: &str,
+        body: String,
+        content_type: mime::Mime,
+    ) -> http_types::Response {
+        let mut request = request(Method::Post, path, None);
+        request.set_body(body);
+        request.set_content_type(content_type);
+
+        respond(app, request).await
+    }
+
+    #[async_std::test]
+    async fn due_dates_of_multiple_entries_are_reported_one_by_one() {
+        let (_directory, store, app) = app("");
+        let uuid = seeded(&store);
+        let unchanged = entry("unchanged entry");
+        store.add_entry(unchanged.clone()).unwrap();
+        let invalid = entry("invalid due");
+        store.add_entry(invalid.clone()).unwrap();
+        let invalid = invalid.metadata.uuid;
+        let unknown = Uuid::new_v4();
+
+        let due = chrono::Local::now().naive_local().date() + chrono::Duration::days(7);
+        let body = serde_json::json!({
+            uuid.to_string(): due.to_string(),
+            unchanged.metadata.uuid.to_string(): "",
+            invalid.to_string(): "0225-01-01",
+            unknown.to_string(): due.to_string(),
+            "not a uuid": due.to_string(),
+        });
+
+        let mut response =
+            set_dues(&app, "/api/v1/entries/due", body.to_string(), mime::JSON).await;
+        let results: serde_json::Value = response.body_json().await.unwrap();
+        let status = |uuid: &str| {
+            results["results"][uuid]["status"]
+                .as_str()
+                .unwrap()
+                .to_string()
+        };
+
+        assert_eq!(response.status(), StatusCode::Ok);
+        assert_eq!(status(&uuid.to_string()), "updated");
+        assert_eq!(results["results"][uuid.to_string()]["due"], due.to_string());
+        assert_eq!(status(&unchanged.metadata.uuid.to_string()), "unchanged");
+        assert_eq!(status(&invalid.to_string()), "failed");
+        assert_eq!(status(&unknown.to_string()), "failed");
+        assert_eq!(status("not a uuid"), "failed");
+
+        assert_eq!(
+            store.get_entry_by_uuid(&uuid).unwrap().metadata.due,
+            Some(due)
+        );
+        assert_eq!(
+            store.get_entry_by_uuid(&invalid).unwrap().metadata.due,
+            None
+        );
+    }
+
+    #[async_std::test]
+    async fn due_date_forms_redirect_back_with_the_failures() {
+        let (_directory, store, app) = app("");
+        let uuid = seeded(&store);
+        let due = chrono::Local::now().naive_local().date() + chrono::Duration::days(7);
+
+        let response = set_dues(
+            &app,
+            "/api/v1/entries/due?redirect=%2Fproject%2Fwork%3Fdone%3Dfalse",
+            format!("{}={}&{}={}", uuid, due, Uuid::new_v4(), due),
+            mime::FORM,
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::SeeOther);
+        assert_eq!(
+            response["Location"],
+            "/project/work?done=false&due_errors=1"
+        );
+        assert_eq!(
+            store.get_entry_by_uuid(&uuid).unwrap().metadata.due,
+            Some(due)
+        );
+
+        let response = set_dues(
+            &app,
+            "/api/v1/entries/due",
+            format!("{}=", uuid),
+            mime::FORM,
+        )
+        .await;
+
+        assert_eq!(response.status(), StatusCode::SeeOther);
+        assert_eq!(response["Location"], "/");
+        assert_eq!(store.get_entry_by_uuid(&uuid).unwrap().metadata.due, None);
+    }
+
+    #[async_std::test]
+    async fn changes_through_the_api_run_the_hooks() {
+        let hooks = tempfile::tempdir().unwrap();
+        let hook = hooks::tests::recorder(hooks.path());
+        let (_directory, store, app) = app(&format!(
+            "[hooks]\npost_done = {:?}\npost_move = {:?}",
+            hook, hook
+        ));
+        let uuid = seeded(&store);
+
+        let response = move_entry(&app, uuid, "existing_project=default&new_project=").await;
+        assert_eq!(response.status(), StatusCode::SeeOther);
+
+        let response = respond(
+            &app,
+            request(
+                Method::Get,
+                &format!("/api/v1/entry/mark/done/{}", uuid),
+                None,
+            ),
+        )
+        .await;
+        assert!(response.status().is_redirection(), "{}", response.status());
+
+        drop(app);
+        drop(store);
+
+        let runs = hooks::tests::recorded(hooks.path());
+        let events = runs
+            .iter()
+            .map(|run| run["TODUST_EVENT"].as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(events, vec!["moved", "finished"]);
+        assert_eq!(runs[0]["TODUST_PREVIOUS_PROJECT"], "work");
+        assert_eq!(runs[1]["TODUST_UUID"], uuid.to_string());
+    }
+}
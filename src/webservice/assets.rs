@@ -0,0 +1,201 @@
+use super::{
+    auth,
+    auth::Access,
+    sitemap,
+    WebService,
+};
+use tide::{
+    Body,
+    Request,
+    Response,
+    StatusCode,
+};
+
+pub(super) async fn handler_static_css_main(
+    _request: Request<WebService>,
+) -> Result<Response, tide::Error> {
+    Ok(Response::builder(StatusCode::Ok)
+        .header("Content-Type", "text/css")
+        .body(Body::from(
+            include_bytes!("resources/css/main.css").to_vec(),
+        ))
+        .build())
+}
+
+pub(super) async fn handler_static_css_font_awesome(
+    _request: Request<WebService>,
+) -> Result<Response, tide::Error> {
+    Ok(Response::builder(StatusCode::Ok)
+        .header("Content-Type", "text/css")
+        .body(Body::from(
+            include_bytes!("resources/css/font-awesome.min.css").to_vec(),
+        ))
+        .build())
+}
+
+pub(super) async fn handler_static_fonts_fontawesome_webfont_woff2(
+    _request: Request<WebService>,
+) -> Result<Response, tide::Error> {
+    Ok(Response::builder(StatusCode::Ok)
+        .header("Content-Type", "font/woff2")
+        .body(Body::from(
+            include_bytes!("resources/fonts/fontawesome-webfont.woff2").to_vec(),
+        ))
+        .build())
+}
+
+pub(super) async fn handler_robots_txt(
+    request: Request<WebService>,
+) -> Result<Response, tide::Error> {
+    let state = request.state();
+
+    Ok(Response::builder(StatusCode::Ok)
+        .header("Content-Type", "text/plain")
+        .body(Body::from(sitemap::robots(
+            state.public,
+            &base_url(&request),
+        )))
+        .build())
+}
+
+/// Sitemap with the most recently changed entries the request can read. Only
+/// reads the index.
+pub(super) async fn handler_sitemap_xml(
+    request: Request<WebService>,
+) -> Result<Response, tide::Error> {
+    let state = request.state();
+
+    let mut metadata = state
+        .store
+        .get_all_metadata()?
+        .into_iter()
+        .filter(|metadata| !state.store.is_ignored_project(&metadata.project))
+        .filter(|metadata| auth::scope(&request).allows(&metadata.project, Access::Read))
+        .collect::<Vec<_>>();
+    metadata.sort_by_key(|metadata| std::cmp::Reverse(metadata.last_change));
+    metadata.truncate(state.sitemap_limit);
+
+    let body = sitemap::sitemap(
+        &base_url(&request),
+        metadata
+            .into_iter()
+            .map(|metadata| (metadata.uuid, metadata.last_change)),
+    );
+
+    Ok(Response::builder(StatusCode::Ok)
+        .header("Content-Type", "application/xml")
+        .body(Body::from(body))
+        .build())
+}
+
+/// Url the webservice is reachable under without a trailing slash. Uses the
+/// configured url or the scheme and host the request was sent to.
+pub(super) fn base_url(request: &Request<WebService>) -> String {
+    if let Some(base_url) = &request.state().base_url {
+        return base_url.clone();
+    }
+
+    let url = request.url();
+    let mut base_url = format!(
+        "{}://{}",
+        url.scheme(),
+        url.host_str().unwrap_or("localhost")
+    );
+    if let Some(port) = url.port() {
+        base_url.push_str(&format!(":{}", port));
+    }
+
+    base_url
+}
+
+pub(super) async fn handler_favicon_ico(
+    _request: Request<WebService>,
+) -> Result<Response, tide::Error> {
+    Ok(Response::builder(StatusCode::Ok)
+        .header("Content-Type", "image/x-icon")
+        .body(Body::from(
+            include_bytes!("resources/img/favicon.ico").to_vec(),
+        ))
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        store::tests::entry,
+        webservice::tests::{
+            app,
+            request,
+            respond,
+        },
+    };
+    use http_types::{
+        Method,
+        StatusCode,
+    };
+
+    async fn get(app: &tide::Server<super::WebService>, path: &str) -> (StatusCode, String) {
+        let mut response = respond(app, request(Method::Get, path, None)).await;
+
+        (response.status(), response.body_string().await.unwrap())
+    }
+
+    #[async_std::test]
+    async fn robots_disallows_everything_unless_public() {
+        let (_directory, _store, private) = app(r#"
+            [[web_tokens]]
+            name = "reader"
+            token = "secret"
+            access = ["read"]
+            "#);
+        assert_eq!(
+            get(&private, "/robots.txt").await,
+            (StatusCode::Ok, "User-agent: *\nDisallow: /\n".to_string())
+        );
+
+        let (_directory, _store, public) = app(r#"
+            public = true
+            web_base_url = "https://todo.example.com/"
+            "#);
+        assert_eq!(
+            get(&public, "/robots.txt").await,
+            (
+                StatusCode::Ok,
+                "User-agent: *\nAllow: /\n\nSitemap: https://todo.example.com/sitemap.xml\n"
+                    .to_string()
+            )
+        );
+    }
+
+    #[async_std::test]
+    async fn sitemap_lists_the_most_recent_entries() {
+        let (_directory, store, app) = app(r#"
+            web_base_url = "https://todo.example.com/todust"
+            sitemap_limit = 2
+            "#);
+
+        let mut added = Vec::new();
+        for (minutes, text) in [(1, "oldest"), (2, "middle"), (3, "newest")] {
+            let mut entry = entry(text);
+            entry.metadata.last_change = "2026-10-16T08:00:00Z"
+                .parse::<chrono::DateTime<chrono::Utc>>()
+                .unwrap()
+                + chrono::Duration::minutes(minutes);
+            store.add_entry(entry.clone()).unwrap();
+            added.push(store.get_entry_by_uuid(&entry.metadata.uuid).unwrap());
+        }
+
+        let (status, sitemap) = get(&app, "/sitemap.xml").await;
+
+        assert_eq!(status, StatusCode::Ok);
+        assert_eq!(sitemap.matches("<url>").count(), 2);
+        let position = |entry: &crate::entry::Entry| {
+            sitemap.find(&format!(
+                "<loc>https://todo.example.com/todust/entry/{}</loc>",
+                entry.metadata.uuid
+            ))
+        };
+        assert!(position(&added[2]).unwrap() < position(&added[1]).unwrap());
+        assert_eq!(position(&added[0]), None);
+    }
+}
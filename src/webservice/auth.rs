@@ -0,0 +1,446 @@
+use super::WebService;
+use crate::entry::Entry;
+use http_types::auth::{
+    AuthenticationScheme,
+    Authorization,
+    BasicAuth,
+};
+use log::warn;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::BTreeSet,
+    future::Future,
+    pin::Pin,
+};
+use tide::{
+    utils::async_trait,
+    Middleware,
+    Next,
+    Request,
+    Response,
+    StatusCode,
+};
+
+/// Token that grants access to the webservice.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct WebToken {
+    /// Name of the token. Only used for logging.
+    name: String,
+
+    /// Secret that has to be sent either as a bearer token or as the password
+    /// of basic auth.
+    token: String,
+
+    /// Projects the token can access. All projects when empty.
+    #[serde(default)]
+    projects: Vec<String>,
+
+    /// What the token is allowed to do with the projects.
+    access: BTreeSet<Access>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, Ord, PartialOrd, Eq, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub(super) enum Access {
+    Read,
+    Write,
+
+    /// Read the audit log of the projects.
+    Admin,
+}
+
+/// What the current request is allowed to access. Attached to every request
+/// by the auth middleware.
+#[derive(Debug, Clone)]
+pub(super) struct Scope {
+    name: Option<String>,
+
+    /// Position of the token in the configured tokens. Tells tokens apart
+    /// that share a name.
+    token: Option<usize>,
+
+    projects: Option<Vec<String>>,
+    access: BTreeSet<Access>,
+}
+
+impl Scope {
+    /// Scope used when no tokens are configured.
+    fn unrestricted() -> Self {
+        Self {
+            name: None,
+            token: None,
+            projects: None,
+            access: vec![Access::Read, Access::Write, Access::Admin]
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    fn from_token(index: usize, token: &WebToken) -> Self {
+        let projects = if token.projects.is_empty() {
+            None
+        } else {
+            Some(token.projects.clone())
+        };
+
+        Self {
+            name: Some(token.name.clone()),
+            token: Some(index),
+            projects,
+            access: token.access.clone(),
+        }
+    }
+
+    /// Name of the token the request was sent with. Missing when no tokens
+    /// are configured or the path is public.
+    pub(super) fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Position of the token the request was sent with in the configured
+    /// tokens.
+    pub(super) fn token(&self) -> Option<usize> {
+        self.token
+    }
+
+    /// Check if the scope has the access to at least some projects.
+    pub(super) fn has(&self, access: Access) -> bool {
+        self.access.contains(&access)
+    }
+
+    /// Check if the scope has the access to every project.
+    pub(super) fn has_everywhere(&self, access: Access) -> bool {
+        self.projects.is_none() && self.has(access)
+    }
+
+    /// Check if the scope allows the access to the project.
+    pub(super) fn allows(&self, project: &str, access: Access) -> bool {
+        let project_allowed = match &self.projects {
+            Some(projects) => projects.iter().any(|allowed| allowed == project),
+            None => true,
+        };
+
+        project_allowed && self.access.contains(&access)
+    }
+}
+
+/// Check that the request is allowed to access the project and return 403 if
+/// it is not.
+pub(super) fn authorize(
+    request: &Request<WebService>,
+    project: &str,
+    access: Access,
+) -> Result<(), tide::Error> {
+    let scope = scope(request);
+
+    if scope.allows(project, access) {
+        return Ok(());
+    }
+
+    warn!(
+        "token {} tried to access project {} with {:?} access",
+        scope.name.as_deref().unwrap_or("-"),
+        project,
+        access
+    );
+
+    Err(tide::Error::from_str(
+        StatusCode::Forbidden,
+        format!("403 - no {:?} access to project {}", access, project),
+    ))
+}
+
+/// Route middleware that checks the access a route needs before its handler
+/// runs. Routes with a `:project` parameter need the access to that project
+/// and routes with a `:uuid` parameter to the project of the entry. The entry
+/// is attached to the request and handlers get it with `authorized_entry`.
+/// Handlers only have to check projects that are given in the query or body.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Require(pub(super) Access);
+
+#[async_trait]
+impl Middleware<WebService> for Require {
+    async fn handle(
+        &self,
+        mut request: Request<WebService>,
+        next: Next<'_, WebService>,
+    ) -> tide::Result {
+        let Require(access) = *self;
+        let scope = scope(&request);
+
+        if !scope.has(access) {
+            warn!(
+                "token {} tried to access {} without {:?} access",
+                scope.name.as_deref().unwrap_or("-"),
+                request.url().path(),
+                access
+            );
+
+            return Err(tide::Error::from_str(
+                StatusCode::Forbidden,
+                format!("403 - the request needs {:?} access", access),
+            ));
+        }
+
+        if request.param("project").is_ok() {
+            let project = super::project_param(&request)?;
+            authorize(&request, &project, access)?;
+        }
+
+        if request.param("uuid").is_ok() {
+            let entry = super::entry_param(&request)?;
+            authorize(&request, &entry.metadata.project, access)?;
+            request.set_ext(AuthorizedEntry(entry));
+        }
+
+        Ok(next.run(request).await)
+    }
+}
+
+/// Route middleware for routes that show data of every project like the
+/// store info. Tokens that are limited to some projects get a 403.
+#[derive(Debug, Clone, Copy)]
+pub(super) struct RequireEverywhere(pub(super) Access);
+
+#[async_trait]
+impl Middleware<WebService> for RequireEverywhere {
+    async fn handle(
+        &self,
+        request: Request<WebService>,
+        next: Next<'_, WebService>,
+    ) -> tide::Result {
+        let RequireEverywhere(access) = *self;
+        let scope = scope(&request);
+
+        if !scope.has_everywhere(access) {
+            warn!(
+                "token {} tried to access {} without {:?} access to every project",
+                scope.name.as_deref().unwrap_or("-"),
+                request.url().path(),
+                access
+            );
+
+            return Err(tide::Error::from_str(
+                StatusCode::Forbidden,
+                format!(
+                    "403 - the request needs {:?} access to every project",
+                    access
+                ),
+            ));
+        }
+
+        Ok(next.run(request).await)
+    }
+}
+
+/// Entry of the `:uuid` parameter after `Require` checked the access to it.
+#[derive(Debug, Clone)]
+struct AuthorizedEntry(Entry);
+
+/// Entry of the `:uuid` parameter of the route as checked by `Require`.
+pub(super) fn authorized_entry(request: &Request<WebService>) -> Entry {
+    request
+        .ext::<AuthorizedEntry>()
+        .map(|AuthorizedEntry(entry)| entry.clone())
+        .expect("route with a uuid parameter has no access check")
+}
+
+/// Scope of the request as resolved by the auth middleware.
+pub(super) fn scope(request: &Request<WebService>) -> &Scope {
+    request
+        .ext::<Scope>()
+        .expect("auth middleware did not attach a scope to the request")
+}
+
+/// Middleware that resolves the token sent with the request into a scope. If
+/// no tokens are configured every request gets unrestricted access.
+pub(super) fn middleware<'a>(
+    mut request: Request<WebService>,
+    next: Next<'a, WebService>,
+) -> Pin<Box<dyn Future<Output = tide::Result> + Send + 'a>> {
+    Box::pin(async move {
+        let tokens = &request.state().tokens;
+
+        let scope = if tokens.is_empty() || is_public_path(request.url().path()) {
+            Scope::unrestricted()
+        } else {
+            let secret = match request_secret(&request) {
+                Some(secret) => secret,
+                None => return Ok(unauthorized()),
+            };
+
+            match tokens
+                .iter()
+                .enumerate()
+                .find(|(_, token)| constant_time_eq(token.token.as_bytes(), secret.as_bytes()))
+            {
+                Some((index, token)) => Scope::from_token(index, token),
+                None => return Ok(unauthorized()),
+            }
+        };
+
+        request.set_ext(scope);
+
+        Ok(next.run(request).await)
+    })
+}
+
+/// Paths that can be accessed without a token.
+fn is_public_path(path: &str) -> bool {
+    path == "/_/health"
+        || path == "/favicon.ico"
+        || path == "/robots.txt"
+        || path.starts_with("/static/")
+}
+
+/// Get the secret from either a bearer token or the password of basic auth.
+fn request_secret(request: &Request<WebService>) -> Option<String> {
+    let authorization = Authorization::from_headers(request).ok()??;
+
+    match authorization.scheme() {
+        AuthenticationScheme::Bearer => Some(authorization.credentials().to_string()),
+        AuthenticationScheme::Basic => BasicAuth::from_credentials(authorization.credentials())
+            .ok()
+            .map(|auth| auth.password().to_string()),
+        _ => None,
+    }
+}
+
+fn unauthorized() -> Response {
+    Response::builder(StatusCode::Unauthorized)
+        .header("Content-Type", "text/plain")
+        .header("WWW-Authenticate", r#"Basic realm="todust""#)
+        .body("401 - missing or invalid token")
+        .build()
+}
+
+/// Compare two byte slices in constant time so the comparison does not leak
+/// how much of a token matched.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+
+    a.iter()
+        .zip(b.iter())
+        .fold(0u8, |acc, (a, b)| acc | (a ^ b))
+        == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        store::tests::entry,
+        webservice::tests::{
+            app,
+            request,
+            respond,
+        },
+    };
+    use http_types::{
+        Body,
+        Method,
+    };
+    use serde_json::json;
+
+    const TOKENS: &str = r#"
+        [[web_tokens]]
+        name = "work"
+        token = "work-secret"
+        projects = ["work"]
+        access = ["read", "write"]
+
+        [[web_tokens]]
+        name = "reader"
+        token = "reader-secret"
+        access = ["read"]
+    "#;
+
+    fn add_entry(project: &str, token: &str) -> http_types::Request {
+        let mut request = request(
+            Method::Post,
+            &format!("/api/v1/project/add/entry/{}", project),
+            Some(token),
+        );
+        request.set_body(Body::from_form(&json!({ "text": "new entry" })).unwrap());
+
+        request
+    }
+
+    #[async_std::test]
+    async fn token_scoped_to_another_project_is_rejected() {
+        let (_directory, store, app) = app(TOKENS);
+        let mut home = entry("home entry");
+        home.metadata.project = "home".to_string();
+        store.add_entry(home.clone()).unwrap();
+
+        let response = respond(&app, add_entry("home", "work-secret")).await;
+        assert_eq!(response.status(), StatusCode::Forbidden);
+
+        let path = format!("/api/v1/entry/raw/{}", home.metadata.uuid);
+        let response = respond(&app, request(Method::Get, &path, Some("work-secret"))).await;
+        assert_eq!(response.status(), StatusCode::Forbidden);
+
+        let response = respond(&app, add_entry("work", "work-secret")).await;
+        assert_eq!(response.status(), StatusCode::SeeOther);
+        assert_eq!(store.get_active_entries("home").unwrap().len(), 1);
+        assert_eq!(store.get_active_entries("work").unwrap().len(), 1);
+    }
+
+    #[async_std::test]
+    async fn read_only_token_can_not_write() {
+        let (_directory, store, app) = app(TOKENS);
+
+        let response = respond(&app, add_entry("work", "reader-secret")).await;
+        assert_eq!(response.status(), StatusCode::Forbidden);
+        assert_eq!(store.get_active_entries("work").unwrap().len(), 0);
+
+        let path = "/api/v1/project/entries/work";
+        let response = respond(&app, request(Method::Get, path, Some("reader-secret"))).await;
+        assert_eq!(response.status(), StatusCode::Ok);
+    }
+
+    #[async_std::test]
+    async fn info_needs_access_to_every_project() {
+        let (_directory, _store, app) = app(TOKENS);
+
+        let response = respond(
+            &app,
+            request(Method::Get, "/api/v1/info", Some("work-secret")),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::Forbidden);
+
+        let response = respond(
+            &app,
+            request(Method::Get, "/api/v1/info", Some("reader-secret")),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::Ok);
+    }
+
+    #[async_std::test]
+    async fn missing_or_unknown_token_is_unauthorized() {
+        let (_directory, _store, app) = app(TOKENS);
+
+        for token in [None, Some("work"), Some("work-secret-")] {
+            let response = respond(&app, request(Method::Get, "/api/v1/info", token)).await;
+            assert_eq!(response.status(), StatusCode::Unauthorized);
+        }
+
+        let response = respond(&app, request(Method::Get, "/_/health", None)).await;
+        assert_eq!(response.status(), StatusCode::Ok);
+    }
+
+    #[test]
+    fn compares_tokens_completely() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secret-"));
+        assert!(!constant_time_eq(b"", b"secret"));
+    }
+}
@@ -0,0 +1,303 @@
+use super::{
+    auth,
+    auth::{
+        authorized_entry,
+        Access,
+    },
+    WebService,
+};
+use crate::{
+    checklist,
+    entry::Entry,
+    helper,
+    templating,
+};
+use log::warn;
+use tide::{
+    Body,
+    Request,
+    Response,
+    StatusCode,
+};
+
+/// Page of an entry. The slug in the url is only there for people reading the
+/// link and links with an outdated slug are redirected to the current one.
+pub(super) async fn handler_entry(request: Request<WebService>) -> Result<Response, tide::Error> {
+    let entry = authorized_entry(&request);
+
+    if let Ok(slug) = request.param("slug") {
+        if helper::percent_decode(slug) != Some(helper::slug(&entry.text)) {
+            return Ok(Response::builder(StatusCode::MovedPermanently)
+                .header("Content-Type", "text/plain")
+                .header(
+                    "Location",
+                    templating::entry_path(&entry.metadata.uuid, &entry.text),
+                )
+                .body(Body::from("entry moved to its current permalink"))
+                .build());
+        }
+    }
+
+    request.state().store.record_view(&entry.metadata.uuid);
+
+    // Show the plain text instead of an empty page when asciidoctor fails.
+    let entry_html = request
+        .state()
+        .store
+        .entry_html(&entry, request.state().render_cache)
+        .map_err(|err| warn!("can not render entry {}: {:#}", entry.metadata.uuid, err))
+        .ok();
+
+    let mut template_context = tera::Context::new();
+    template_context.insert("entry", &entry);
+    template_context.insert("entry_html", &entry_html);
+    template_context.insert("render_failed", &entry_html.is_none());
+    template_context.insert("today", &request.state().time.today());
+    template_context.insert("unchecked_count", &checklist::unchecked_count(&entry.text));
+
+    let output = request.state().render("entry.html", &template_context)?;
+
+    Ok(Response::builder(StatusCode::Ok)
+        .header("Content-Type", "text/html")
+        .body(Body::from(output.as_bytes()))
+        .build())
+}
+
+pub(super) async fn handler_entry_edit(
+    request: Request<WebService>,
+) -> Result<Response, tide::Error> {
+    let entry = authorized_entry(&request);
+
+    let mut template_context = tera::Context::new();
+    template_context.insert("entry", &entry);
+
+    let output = request
+        .state()
+        .render("entry_edit.html", &template_context)?;
+
+    Ok(Response::builder(StatusCode::Ok)
+        .header("Content-Type", "text/html")
+        .body(Body::from(output.as_bytes()))
+        .build())
+}
+
+pub(super) async fn handler_entry_move_project(
+    request: Request<WebService>,
+) -> Result<Response, tide::Error> {
+    let entry = authorized_entry(&request);
+
+    render_entry_move_project(&request, &entry, None)
+}
+
+pub(super) async fn handler_entry_due(
+    request: Request<WebService>,
+) -> Result<Response, tide::Error> {
+    let entry = authorized_entry(&request);
+
+    let mut template_context = tera::Context::new();
+    template_context.insert("entry", &entry);
+    template_context.insert("today", &request.state().time.today());
+
+    let output = request
+        .state()
+        .render("entry_due.html", &template_context)?;
+
+    Ok(Response::builder(StatusCode::Ok)
+        .header("Content-Type", "text/html")
+        .body(Body::from(output.as_bytes()))
+        .build())
+}
+
+/// Render the page for moving the entry to another project. With the
+/// project of a move that failed as the entry was changed in the meantime
+/// the current project and text are shown to check before moving again.
+pub(super) fn render_entry_move_project(
+    request: &Request<WebService>,
+    entry: &Entry,
+    conflict_project: Option<&str>,
+) -> Result<Response, tide::Error> {
+    let mut projects = request
+        .state()
+        .store
+        .get_projects()?
+        .into_iter()
+        .filter(|project| project != &entry.metadata.project)
+        .filter(|project| auth::scope(request).allows(project, Access::Write))
+        .collect::<Vec<_>>();
+    projects.sort();
+    projects.dedup();
+
+    let mut template_context = tera::Context::new();
+    template_context.insert("entry", entry);
+    template_context.insert("projects", &projects);
+    if let Some(project) = conflict_project {
+        template_context.insert("conflict_project", project);
+    }
+
+    let output = request
+        .state()
+        .render("entry_move_project.html", &template_context)?;
+
+    let status = if conflict_project.is_some() {
+        StatusCode::Conflict
+    } else {
+        StatusCode::Ok
+    };
+
+    Ok(Response::builder(status)
+        .header("Content-Type", "text/html")
+        .body(Body::from(output.as_bytes()))
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        store::tests::entry,
+        templating,
+        webservice::tests::{
+            app,
+            navigation,
+            request,
+            respond,
+        },
+    };
+    use http_types::{
+        Method,
+        StatusCode,
+    };
+
+    #[async_std::test]
+    async fn failed_render_shows_the_plain_text_with_a_notice() {
+        let (_directory, store, app) = app("");
+        let added = entry("1 < 2 & *bold*");
+        store.add_entry(added.clone()).unwrap();
+        let entry_path = templating::entry_path(&added.metadata.uuid, &added.text);
+
+        let mut response = respond(&app, request(Method::Get, &entry_path, None)).await;
+        assert_eq!(response.status(), StatusCode::Ok);
+        let body = response.body_string().await.unwrap();
+
+        let mut response = respond(&app, request(Method::Get, "/api/v1/info", None)).await;
+        let info: serde_json::Value = response.body_json().await.unwrap();
+
+        let notice = "Markup rendering failed, showing the plain text instead.";
+        if std::process::Command::new("asciidoctor")
+            .arg("--version")
+            .output()
+            .is_err()
+        {
+            assert!(body.contains(notice), "{}", body);
+            assert!(
+                body.contains("<pre>1 &lt; 2 &amp; *bold*</pre>"),
+                "{}",
+                body
+            );
+            assert_eq!(info["render_failures"], 1);
+        } else {
+            assert!(!body.contains(notice), "{}", body);
+            assert!(body.contains("<strong>bold</strong>"), "{}", body);
+            assert_eq!(info["render_failures"], 0);
+        }
+    }
+
+    #[async_std::test]
+    async fn entry_pages_link_back_to_the_project() {
+        let (_directory, store, app) = app("");
+        let mut added = entry("== Call the vendor\n\nabout the invoice");
+        added.metadata.project = "work".to_string();
+        store.add_entry(added.clone()).unwrap();
+        let uuid = added.metadata.uuid;
+        let entry_path = templating::entry_path(&uuid, &added.text);
+
+        assert_eq!(
+            navigation(&app, &entry_path).await,
+            (
+                "<title>Todust - work - Call the vendor about the invoice</title>".to_string(),
+                "<nav class=\"breadcrumb\"> <a href=\"/\">Projects</a> &rsaquo; <a \
+                 href=\"/project/work\">work</a> &rsaquo; Call the vendor about the invoice </nav>"
+                    .to_string()
+            )
+        );
+
+        for (path, crumb) in [
+            (format!("/entry/edit/{}", uuid), "edit"),
+            (format!("/entry/move_project/{}", uuid), "move"),
+            (format!("/entry/due/{}", uuid), "Due"),
+        ] {
+            assert_eq!(
+                navigation(&app, &path).await,
+                (
+                    format!(
+                        "<title>Todust - work - Call the vendor about the invoice - {}</title>",
+                        crumb
+                    ),
+                    format!(
+                        "<nav class=\"breadcrumb\"> <a href=\"/\">Projects</a> &rsaquo; <a \
+                         href=\"/project/work\">work</a> &rsaquo; <a href=\"{}\">Call the vendor \
+                         about the invoice</a> &rsaquo; {} </nav>",
+                        entry_path, crumb
+                    )
+                ),
+                "{}",
+                path
+            );
+        }
+    }
+
+    #[async_std::test]
+    async fn template_syntax_of_the_text_is_shown_as_written() {
+        let (_directory, store, app) = app("");
+        let added = entry("{{ 7*7 }} and {% raw %} in the text");
+        store.add_entry(added.clone()).unwrap();
+        let uuid = added.metadata.uuid;
+
+        for path in [
+            templating::entry_path(&uuid, &added.text),
+            format!("/entry/edit/{}", uuid),
+            "/project/default".to_string(),
+        ] {
+            let mut response = respond(&app, request(Method::Get, &path, None)).await;
+            let body = response.body_string().await.unwrap();
+
+            assert!(
+                body.contains("{{ 7*7 }} and {% raw %}"),
+                "{}: {}",
+                path,
+                body
+            );
+        }
+    }
+
+    #[async_std::test]
+    async fn stale_slugs_redirect_to_the_current_permalink() {
+        let (_directory, store, app) = app("");
+        let added = entry("Café über 東京");
+        store.add_entry(added.clone()).unwrap();
+        let uuid = added.metadata.uuid;
+        let old_path = templating::entry_path(&uuid, &added.text);
+
+        let response = respond(&app, request(Method::Get, &old_path, None)).await;
+        assert_eq!(response.status(), StatusCode::Ok);
+
+        let mut edited = store.get_entry_by_uuid(&uuid).unwrap();
+        edited.text = "Renamed entry".to_string();
+        store.update_entry(edited).unwrap();
+        let current_path = templating::entry_path(&uuid, "Renamed entry");
+
+        for path in [
+            old_path,
+            format!("/entry/{}/something-else", uuid),
+            format!("/entry/{}/%ZZ", uuid),
+        ] {
+            let response = respond(&app, request(Method::Get, &path, None)).await;
+            assert_eq!(response.status(), StatusCode::MovedPermanently, "{}", path);
+            assert_eq!(response["Location"], current_path.as_str(), "{}", path);
+        }
+
+        for path in [current_path, format!("/entry/{}", uuid)] {
+            let response = respond(&app, request(Method::Get, &path, None)).await;
+            assert_eq!(response.status(), StatusCode::Ok, "{}", path);
+        }
+    }
+}
@@ -0,0 +1,364 @@
+use crate::{
+    entry::ProjectCount,
+    helper,
+    store::favorites,
+    time_context::TimeContext,
+};
+use log::warn;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    cmp::{
+        Ordering,
+        Reverse,
+    },
+    convert::TryFrom,
+    str::FromStr,
+};
+
+/// Columns that can be shown for each project on the index page.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum IndexColumn {
+    Active,
+    Done,
+    Total,
+
+    /// Active entries with a due date in the past.
+    Overdue,
+
+    /// Active entries that are due in the next days.
+    DueSoon,
+
+    /// Active entries that were not viewed or changed in a long time.
+    Forgotten,
+
+    /// Day of the most recent change of an entry in the project.
+    LastActivity,
+
+    /// Share of the done entries with a due date that were finished on time.
+    OnTime,
+}
+
+impl FromStr for IndexColumn {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "active" => Ok(IndexColumn::Active),
+            "done" => Ok(IndexColumn::Done),
+            "total" => Ok(IndexColumn::Total),
+            "overdue" => Ok(IndexColumn::Overdue),
+            "due_soon" => Ok(IndexColumn::DueSoon),
+            "forgotten" => Ok(IndexColumn::Forgotten),
+            "last_activity" => Ok(IndexColumn::LastActivity),
+            "on_time" => Ok(IndexColumn::OnTime),
+            _ => Err(format!("unknown index column {}", s)),
+        }
+    }
+}
+
+impl IndexColumn {
+    fn as_str(&self) -> &'static str {
+        match self {
+            IndexColumn::Active => "active",
+            IndexColumn::Done => "done",
+            IndexColumn::Total => "total",
+            IndexColumn::Overdue => "overdue",
+            IndexColumn::DueSoon => "due_soon",
+            IndexColumn::Forgotten => "forgotten",
+            IndexColumn::LastActivity => "last_activity",
+            IndexColumn::OnTime => "on_time",
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            IndexColumn::Active => "Active",
+            IndexColumn::Done => "Done",
+            IndexColumn::Total => "Total",
+            IndexColumn::Overdue => "Overdue",
+            IndexColumn::DueSoon => "Due Soon",
+            IndexColumn::Forgotten => "Forgotten",
+            IndexColumn::LastActivity => "Last Activity",
+            IndexColumn::OnTime => "On Time",
+        }
+    }
+
+    fn value(&self, count: &ProjectCount, time: &TimeContext) -> String {
+        match self {
+            IndexColumn::Active => count.active_count.to_string(),
+            IndexColumn::Done => count.done_count.to_string(),
+            IndexColumn::Total => count.total_count.to_string(),
+            IndexColumn::Overdue => count.overdue_count.to_string(),
+            IndexColumn::DueSoon => count.due_soon_count.to_string(),
+            IndexColumn::Forgotten => count.forgotten_count.to_string(),
+            IndexColumn::LastActivity => count
+                .last_activity
+                .map(|last_activity| time.date(last_activity).to_string())
+                .unwrap_or_else(|| "-".to_string()),
+            IndexColumn::OnTime => helper::format_ratio(count.on_time_ratio()),
+        }
+    }
+}
+
+/// How the projects on the index page are sorted. Written as `project` or the
+/// name of a column.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub(crate) enum IndexSort {
+    /// Projects by name.
+    #[default]
+    Project,
+
+    /// Projects with the highest value of the column first.
+    Column(IndexColumn),
+}
+
+impl FromStr for IndexSort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "project" => Ok(IndexSort::Project),
+            _ => s
+                .parse()
+                .map(IndexSort::Column)
+                .map_err(|_| format!("unknown index sort {}", s)),
+        }
+    }
+}
+
+impl TryFrom<String> for IndexSort {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<IndexSort> for String {
+    fn from(sort: IndexSort) -> Self {
+        match sort {
+            IndexSort::Project => "project".to_string(),
+            IndexSort::Column(column) => column.as_str().to_string(),
+        }
+    }
+}
+
+impl IndexSort {
+    pub(super) fn sort(&self, counts: &mut [ProjectCount]) {
+        counts.sort_by(|a, b| a.project.cmp(&b.project));
+
+        let column = match self {
+            IndexSort::Project => return,
+            IndexSort::Column(column) => column,
+        };
+
+        // The sort is stable so projects with the same value stay sorted by
+        // name.
+        match column {
+            IndexColumn::Active => counts.sort_by_key(|count| Reverse(count.active_count)),
+            IndexColumn::Done => counts.sort_by_key(|count| Reverse(count.done_count)),
+            IndexColumn::Total => counts.sort_by_key(|count| Reverse(count.total_count)),
+            IndexColumn::Overdue => counts.sort_by_key(|count| Reverse(count.overdue_count)),
+            IndexColumn::DueSoon => counts.sort_by_key(|count| Reverse(count.due_soon_count)),
+            IndexColumn::Forgotten => counts.sort_by_key(|count| Reverse(count.forgotten_count)),
+            IndexColumn::LastActivity => counts.sort_by_key(|count| Reverse(count.last_activity)),
+            IndexColumn::OnTime => counts.sort_by(|a, b| {
+                b.on_time_ratio()
+                    .partial_cmp(&a.on_time_ratio())
+                    .unwrap_or(Ordering::Equal)
+            }),
+        }
+    }
+}
+
+/// Defaults for the index page. Can be overwritten per request with the
+/// `columns` and `sort` query parameters.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub(crate) struct IndexPreferences {
+    #[serde(default = "default_columns")]
+    pub(super) columns: Vec<IndexColumn>,
+
+    #[serde(default)]
+    pub(super) sort: IndexSort,
+}
+
+impl Default for IndexPreferences {
+    fn default() -> Self {
+        Self {
+            columns: default_columns(),
+            sort: IndexSort::default(),
+        }
+    }
+}
+
+fn default_columns() -> Vec<IndexColumn> {
+    vec![IndexColumn::Active, IndexColumn::Done, IndexColumn::Total]
+}
+
+impl IndexPreferences {
+    /// Apply the comma separated `columns` and the `sort` from the query.
+    /// Unknown names are ignored with a warning so a typo in a bookmark does
+    /// not break the page.
+    pub(super) fn with_query(&self, columns: Option<&str>, sort: Option<&str>) -> Self {
+        let mut preferences = self.clone();
+
+        if let Some(columns) = columns {
+            preferences.columns = columns
+                .split(',')
+                .map(str::trim)
+                .filter(|name| !name.is_empty())
+                .filter_map(|name| match name.parse() {
+                    Ok(column) => Some(column),
+                    Err(err) => {
+                        warn!("ignoring index column from query: {}", err);
+                        None
+                    }
+                })
+                .collect();
+        }
+
+        if let Some(sort) = sort {
+            match sort.parse() {
+                Ok(sort) => preferences.sort = sort,
+                Err(err) => warn!("ignoring index sort from query: {}", err),
+            }
+        }
+
+        preferences
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct IndexRow {
+    project: String,
+    cells: Vec<String>,
+
+    /// Favorites come first in the order they were configured in.
+    favorite: bool,
+}
+
+/// Labels of the columns and the rows of the index page in the order they are
+/// shown. The favorite projects come first, the others are sorted as
+/// configured.
+pub(super) fn index_table(
+    preferences: &IndexPreferences,
+    mut counts: Vec<ProjectCount>,
+    favorites: &[String],
+    time: &TimeContext,
+) -> (Vec<&'static str>, Vec<IndexRow>) {
+    let favorites = favorites::take_favorites(&mut counts, favorites);
+    preferences.sort.sort(&mut counts);
+
+    let labels = preferences.columns.iter().map(IndexColumn::label).collect();
+
+    let rows = favorites
+        .iter()
+        .map(|count| (count, true))
+        .chain(counts.iter().map(|count| (count, false)))
+        .map(|(count, favorite)| IndexRow {
+            project: count.project.clone(),
+            cells: preferences
+                .columns
+                .iter()
+                .map(|column| column.value(count, time))
+                .collect(),
+            favorite,
+        })
+        .collect();
+
+    (labels, rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{
+        DateTime,
+        Utc,
+    };
+
+    fn at(time: &str) -> Option<DateTime<Utc>> {
+        Some(time.parse().unwrap())
+    }
+
+    /// Projects where every count puts another project first.
+    fn counts() -> Vec<ProjectCount> {
+        vec![
+            ProjectCount {
+                project: "c".to_string(),
+                active_count: 3,
+                total_count: 3,
+                forgotten_count: 1,
+                last_activity: at("2026-10-01T00:00:00Z"),
+                ..ProjectCount::default()
+            },
+            ProjectCount {
+                project: "b".to_string(),
+                done_count: 4,
+                total_count: 5,
+                active_count: 1,
+                overdue_count: 1,
+                on_time_count: 1,
+                late_count: 3,
+                last_activity: at("2026-10-10T00:00:00Z"),
+                ..ProjectCount::default()
+            },
+            ProjectCount {
+                project: "a".to_string(),
+                active_count: 2,
+                done_count: 1,
+                total_count: 3,
+                due_soon_count: 2,
+                on_time_count: 1,
+                ..ProjectCount::default()
+            },
+        ]
+    }
+
+    fn sorted(sort: &str) -> Vec<String> {
+        let mut counts = counts();
+        sort.parse::<IndexSort>().unwrap().sort(&mut counts);
+
+        counts.into_iter().map(|count| count.project).collect()
+    }
+
+    #[test]
+    fn every_sort_key() {
+        for (sort, expected) in &[
+            ("project", ["a", "b", "c"]),
+            ("active", ["c", "a", "b"]),
+            ("done", ["b", "a", "c"]),
+            ("total", ["b", "a", "c"]),
+            ("overdue", ["b", "a", "c"]),
+            ("due_soon", ["a", "b", "c"]),
+            ("forgotten", ["c", "a", "b"]),
+            ("last_activity", ["b", "c", "a"]),
+            ("on_time", ["a", "b", "c"]),
+        ] {
+            assert_eq!(sorted(sort), expected, "{}", sort);
+        }
+    }
+
+    #[test]
+    fn unknown_query_values_are_ignored() {
+        let defaults = IndexPreferences::default();
+
+        let preferences = defaults.with_query(Some("overdue, bogus,,last_activity"), Some("bogus"));
+        assert_eq!(
+            preferences.columns,
+            vec![IndexColumn::Overdue, IndexColumn::LastActivity]
+        );
+        assert_eq!(preferences.sort, IndexSort::Project);
+
+        assert_eq!(defaults.with_query(None, None), defaults);
+        assert_eq!(
+            defaults.with_query(None, Some("due_soon")).sort,
+            IndexSort::Column(IndexColumn::DueSoon)
+        );
+    }
+}
@@ -1,68 +1,315 @@
+mod api;
+mod api_entries;
+mod assets;
+pub(crate) mod auth;
+mod entry_pages;
+pub(crate) mod index_columns;
+mod pages;
+pub(crate) mod preferences;
+pub(crate) mod rate_limit;
+mod sitemap;
+mod sparkline;
+pub(crate) mod webhook;
+
 use crate::{
-    entry::{
-        Entry,
-        Metadata,
+    config::Config,
+    due_date::DueDateLimits,
+    entry::Entry,
+    helper,
+    locale::Translations,
+    project_name::validate_project_name,
+    store::{
+        audit::{
+            self,
+            AuditRecord,
+            AuditWriter,
+        },
+        changes::Changes,
+        quota::QuotaExceeded,
+        Store,
     },
-    store::Store,
     templating,
+    time_context::TimeContext,
+};
+use anyhow::{
+    Context as _,
+    Error,
+};
+use api::{
+    handler_api_v1_audit,
+    handler_api_v1_changes,
+    handler_api_v1_info,
+    handler_api_v1_project_add_entry,
+    handler_api_v1_project_entries,
+    handler_api_v1_quick_add,
+    handler_api_v1_stats_history,
+    handler_api_v1_webhook,
+};
+use api_entries::{
+    handler_api_v1_entries_due,
+    handler_api_v1_entry_due,
+    handler_api_v1_entry_edit,
+    handler_api_v1_entry_move_project,
+    handler_api_v1_entry_raw,
+    handler_api_v1_entry_raw_update,
+    handler_api_v1_mark_entry_active,
+    handler_api_v1_mark_entry_done,
+};
+use assets::{
+    handler_favicon_ico,
+    handler_robots_txt,
+    handler_sitemap_xml,
+    handler_static_css_font_awesome,
+    handler_static_css_main,
+    handler_static_fonts_fontawesome_webfont_woff2,
+};
+use auth::{
+    Access,
+    Require,
+    RequireEverywhere,
+    WebToken,
 };
-use anyhow::Error;
 use chrono::Utc;
+use entry_pages::{
+    handler_entry,
+    handler_entry_due,
+    handler_entry_edit,
+    handler_entry_move_project,
+};
 use http_types::mime;
+use index_columns::IndexPreferences;
+use log::error;
+use pages::{
+    handler_board,
+    handler_digest,
+    handler_index,
+    handler_project,
+    handler_project_add_entry,
+    handler_project_export,
+    handler_project_view,
+    handler_quick_add,
+};
+pub(super) use pages::{
+    render_digest,
+    render_export_bundle,
+};
+use preferences::ViewPreferences;
+use rate_limit::RateLimiter;
 use serde::Deserialize;
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+};
 use tera::Tera;
 use tide::{
     Body,
+    Next,
     Request,
     Response,
     StatusCode,
 };
 use uuid::Uuid;
+use webhook::InboundWebhook;
+
+/// Project that is used when neither the request nor the config names one.
+const DEFAULT_PROJECT: &str = "default";
+
+/// Largest plain text body that is accepted by the api.
+const MAX_TEXT_BODY_SIZE: u64 = 1024 * 1024;
+
+/// How far `server_time` of /api/v1/changes lies before the request so
+/// changes whose index rows are written while the request runs are returned
+/// again by the next request instead of being missed.
+const CHANGES_OVERLAP_SECONDS: i64 = 5;
+
+/// How many weeks of finished entries the index page shows by default.
+const DEFAULT_ACTIVITY_WEEKS: usize = 12;
+
+/// Most weeks of finished entries the index page can show.
+const MAX_ACTIVITY_WEEKS: usize = 104;
+
+/// How many daily statistics snapshots /api/v1/stats/history returns by
+/// default.
+const DEFAULT_HISTORY_DAYS: usize = 30;
+
+/// How often the webservice checks if the statistics snapshot of the day was
+/// recorded, as it can run for days without another command doing it.
+const STATS_SNAPSHOT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60 * 60);
 
 #[derive(Debug, Clone)]
 pub(super) struct WebService {
     store: Store,
     templates: Tera,
+
+    /// Records which token changed which entry.
+    audit: Arc<AuditWriter>,
+
+    tokens: Vec<WebToken>,
+    webhooks: Vec<InboundWebhook>,
+    time: TimeContext,
+    view_defaults: ViewPreferences,
+    index_defaults: IndexPreferences,
+    default_project: String,
+
+    /// Requests that add entries have to name the project.
+    require_explicit_project: bool,
+
+    rate_limiter: RateLimiter,
+
+    /// Cache the html rendered by asciidoctor.
+    render_cache: bool,
+
+    /// Include filesystem paths in /api/v1/info.
+    expose_paths: bool,
+
+    /// Allow crawlers in the robots.txt.
+    public: bool,
+
+    /// Url the webservice is reachable under. Taken from the request if not
+    /// configured.
+    base_url: Option<String>,
+
+    /// Maximum number of entries in the sitemap.
+    sitemap_limit: usize,
+
+    /// Range of due dates that can be set.
+    due_date_limits: DueDateLimits,
+
+    /// Changes the requests would have made in a dry run.
+    changes: Changes,
 }
 
 impl WebService {
-    pub(super) fn open(store: Store) -> Result<Self, Error> {
-        let templates = WebService::open_templates()?;
+    pub(super) fn open(store: Store, config: &Config, render_cache: bool) -> Result<Self, Error> {
+        let translations = Translations::load(store.datadir(), config.locale)?;
+        let templates = WebService::open_templates(translations)?;
+        let audit = Arc::new(store.audit_log().writer());
+
+        Ok(Self {
+            store,
+            templates,
+            audit,
+            tokens: config.web_tokens.clone(),
+            webhooks: config.inbound_webhooks.clone(),
+            time: TimeContext::from_config(config),
+            view_defaults: config.web_view.clone(),
+            index_defaults: config.web_index.clone(),
+            default_project: config
+                .default_project
+                .clone()
+                .unwrap_or_else(|| DEFAULT_PROJECT.to_string()),
+            require_explicit_project: config.require_explicit_project,
+            rate_limiter: RateLimiter::new(&config.web_rate_limit),
+            render_cache,
+            expose_paths: config.expose_paths,
+            public: config.public,
+            base_url: config
+                .web_base_url
+                .as_ref()
+                .map(|url| url.trim_end_matches('/').to_string()),
+            sitemap_limit: config.sitemap_limit,
+            due_date_limits: config.due_date_limits.clone(),
+            changes: config.changes.clone(),
+        })
+    }
 
-        Ok(Self { store, templates })
+    /// Project entries are added to, the one named by the request or the
+    /// default project. Fails if the request has to name the project.
+    fn project_to_add_to(&self, project: Option<String>) -> Result<String, tide::Error> {
+        match project {
+            Some(project) => Ok(project),
+            None if self.require_explicit_project => Err(tide::Error::from_str(
+                StatusCode::BadRequest,
+                "require_explicit_project is set, name the project with ?project=<name>",
+            )),
+            None => Ok(self.default_project.clone()),
+        }
     }
 
-    fn open_templates() -> Result<Tera, Error> {
+    /// Render the template for a response. Tera only names the template in
+    /// its error so the cause is logged before the request fails with a 500.
+    fn render(&self, template: &str, context: &tera::Context) -> Result<String, tide::Error> {
+        self.templates.render(template, context).map_err(|err| {
+            let err = Error::from(err);
+            error!("can not render template {}: {:#}", template, err);
+
+            tide::Error::new(StatusCode::InternalServerError, err)
+        })
+    }
+
+    fn open_templates(translations: Translations) -> Result<Tera, Error> {
         let mut templates = tera::Tera::default();
 
+        let title_raw = include_str!("resources/html/title.html.tera");
+        let breadcrumb_raw = include_str!("resources/html/breadcrumb.html.tera");
+        templates
+            .add_raw_templates(vec![
+                ("title.html", title_raw),
+                ("breadcrumb.html", breadcrumb_raw),
+            ])
+            .context("can not parse templates title.html and breadcrumb.html")?;
+
         let index_raw = include_str!("resources/html/index.html.tera");
-        templates.add_raw_template("index.html", index_raw).unwrap();
+        templates
+            .add_raw_template("index.html", index_raw)
+            .context("can not parse template index.html")?;
 
         let project_raw = include_str!("resources/html/project.html.tera");
         templates
             .add_raw_template("project.html", project_raw)
-            .unwrap();
+            .context("can not parse template project.html")?;
 
         let entry_raw = include_str!("resources/html/entry.html.tera");
-        templates.add_raw_template("entry.html", entry_raw).unwrap();
+        templates
+            .add_raw_template("entry.html", entry_raw)
+            .context("can not parse template entry.html")?;
 
         let entry_edit_raw = include_str!("resources/html/entry_edit.html.tera");
         templates
             .add_raw_template("entry_edit.html", entry_edit_raw)
-            .unwrap();
+            .context("can not parse template entry_edit.html")?;
 
         let entry_move_project_raw = include_str!("resources/html/entry_move_project.html.tera");
         templates
             .add_raw_template("entry_move_project.html", entry_move_project_raw)
-            .unwrap();
+            .context("can not parse template entry_move_project.html")?;
+
+        let entry_due_raw = include_str!("resources/html/entry_due.html.tera");
+        templates
+            .add_raw_template("entry_due.html", entry_due_raw)
+            .context("can not parse template entry_due.html")?;
 
         let project_add_entry_raw = include_str!("resources/html/project_add_entry.html.tera");
         templates
             .add_raw_template("project_add_entry.html", project_add_entry_raw)
-            .unwrap();
+            .context("can not parse template project_add_entry.html")?;
+
+        let board_macros_raw = include_str!("resources/html/board_macros.html.tera");
+        let board_raw = include_str!("resources/html/board.html.tera");
+        templates
+            .add_raw_templates(vec![
+                ("board_macros.html", board_macros_raw),
+                ("board.html", board_raw),
+            ])
+            .context("can not parse templates board_macros.html and board.html")?;
 
-        templates.register_filter("asciidoc_header", templating::asciidoc_header);
-        templates.register_filter("asciidoc_to_html", templating::asciidoc_to_html);
+        let digest_raw = include_str!("resources/html/digest.html.tera");
+        templates
+            .add_raw_template("digest.html", digest_raw)
+            .context("can not parse template digest.html")?;
+
+        let export_bundle_raw = include_str!("resources/html/export_bundle.html.tera");
+        templates
+            .add_raw_template("export_bundle.html", export_bundle_raw)
+            .context("can not parse template export_bundle.html")?;
+
+        templates.register_filter("entry_path", templating::entry_path_filter);
+        templates.register_filter(
+            "format_date",
+            templating::format_date(translations.locale()),
+        );
+        templates.register_filter("format_due", templating::format_due(translations.locale()));
         templates.register_filter("format_duration_since", templating::format_duration_since);
         templates.register_filter("lines", templating::lines);
         templates.register_filter("single_line", templating::single_line);
@@ -70,36 +317,130 @@ impl WebService {
 
         templates.register_tester("some", templating::some);
 
+        templates.register_function("t", templating::translate(translations));
+
         Ok(templates)
     }
 
     pub(super) async fn run(self, binding: std::net::SocketAddr) -> Result<(), Error> {
+        let store = self.store.clone();
+        std::thread::spawn(move || loop {
+            store.record_stats_snapshot();
+            std::thread::sleep(STATS_SNAPSHOT_INTERVAL);
+        });
+
+        self.app().listen(binding).await?;
+
+        Ok(())
+    }
+
+    /// The webservice with all its middleware and routes.
+    fn app(self) -> tide::Server<WebService> {
         let mut app = tide::with_state(self);
 
+        app.with(auth::middleware);
+        app.with(print_planned_changes);
+        app.with(tide::utils::After(client_error_body));
+
         app.at("/").get(handler_index);
         app.at("/_/health").get(handler_health);
         app.at("/_/health").options(handler_health);
 
-        app.at("/project/:project").get(handler_project);
+        app.at("/board/:project")
+            .with(Require(Access::Read))
+            .get(handler_board);
+        app.at("/digest")
+            .with(Require(Access::Read))
+            .get(handler_digest);
+        app.at("/project/:project")
+            .with(Require(Access::Read))
+            .get(handler_project);
+        app.at("/project/:project/view")
+            .with(Require(Access::Read))
+            .get(handler_project_view);
+        app.at("/project/:project/export")
+            .with(Require(Access::Read))
+            .get(handler_project_export);
         app.at("/project/add/entry/:project")
+            .with(Require(Access::Write))
             .get(handler_project_add_entry);
-        app.at("/entry/:uuid").get(handler_entry);
-        app.at("/entry/edit/:uuid").get(handler_entry_edit);
+        app.at("/quick-add")
+            .with(Require(Access::Write))
+            .get(handler_quick_add);
+        app.at("/entry/:uuid")
+            .with(Require(Access::Read))
+            .get(handler_entry);
+        app.at("/entry/:uuid/:slug")
+            .with(Require(Access::Read))
+            .get(handler_entry);
+        app.at("/entry/edit/:uuid")
+            .with(Require(Access::Read))
+            .get(handler_entry_edit);
         app.at("/entry/move_project/:uuid")
+            .with(Require(Access::Read))
             .get(handler_entry_move_project);
 
+        app.at("/entry/due/:uuid")
+            .with(Require(Access::Read))
+            .get(handler_entry_due);
+        app.at("/api/v1/info")
+            .with(RequireEverywhere(Access::Read))
+            .get(handler_api_v1_info);
+        app.at("/api/v1/audit")
+            .with(Require(Access::Admin))
+            .get(handler_api_v1_audit);
         app.at("/api/v1/project/entries/:project")
+            .with(Require(Access::Read))
             .get(handler_api_v1_project_entries);
         app.at("/api/v1/entry/mark/done/:uuid")
+            .with(Require(Access::Write))
+            .with(rate_limit::middleware)
             .get(handler_api_v1_mark_entry_done);
         app.at("/api/v1/entry/mark/active/:uuid")
+            .with(Require(Access::Write))
+            .with(rate_limit::middleware)
             .get(handler_api_v1_mark_entry_active);
         app.at("/api/v1/project/add/entry/:project")
+            .with(Require(Access::Write))
+            .with(rate_limit::middleware)
             .post(handler_api_v1_project_add_entry);
         app.at("/api/v1/entry/edit/:uuid")
+            .with(Require(Access::Write))
+            .with(rate_limit::middleware)
             .post(handler_api_v1_entry_edit);
         app.at("/api/v1/entry/move_project/:uuid")
+            .with(Require(Access::Write))
+            .with(rate_limit::middleware)
             .post(handler_api_v1_entry_move_project);
+        app.at("/api/v1/entry/due/:uuid")
+            .with(Require(Access::Write))
+            .with(rate_limit::middleware)
+            .post(handler_api_v1_entry_due);
+        app.at("/api/v1/entry/raw/:uuid")
+            .with(Require(Access::Read))
+            .get(handler_api_v1_entry_raw);
+        app.at("/api/v1/entry/raw/:uuid")
+            .with(Require(Access::Write))
+            .with(rate_limit::middleware)
+            .put(handler_api_v1_entry_raw_update);
+        app.at("/api/v1/changes")
+            .with(Require(Access::Read))
+            .get(handler_api_v1_changes);
+        app.at("/api/v1/stats/history")
+            .with(Require(Access::Read))
+            .get(handler_api_v1_stats_history);
+        app.at("/api/v1/entries/due")
+            .with(Require(Access::Write))
+            .with(rate_limit::middleware)
+            .post(handler_api_v1_entries_due);
+        app.at("/api/v1/quick-add")
+            .with(Require(Access::Write))
+            .with(rate_limit::middleware)
+            .post(handler_api_v1_quick_add);
+        app.at("/api/v1/webhook/:project")
+            .with(Require(Access::Write))
+            .with(rate_limit::middleware)
+            .post(handler_api_v1_webhook);
 
         app.at("/static/css/main.css").get(handler_static_css_main);
         app.at("/static/css/font-awesome.min.css")
@@ -108,37 +449,59 @@ impl WebService {
             .get(handler_static_fonts_fontawesome_webfont_woff2);
 
         app.at("/favicon.ico").get(handler_favicon_ico);
+        app.at("/robots.txt").get(handler_robots_txt);
+        app.at("/sitemap.xml").get(handler_sitemap_xml);
 
-        app.listen(binding).await?;
-
-        Ok(())
+        app
     }
 }
 
-async fn handler_index(request: Request<WebService>) -> Result<Response, tide::Error> {
-    let mut projects_count = request
-        .state()
-        .store
-        .get_projects_count()
-        .unwrap()
-        .into_iter()
-        .collect::<Vec<_>>();
+/// Print what the request would have changed when the webservice runs with
+/// --dry_run, so the plan does not pile up until the webservice stops.
+fn print_planned_changes<'a>(
+    request: Request<WebService>,
+    next: Next<'a, WebService>,
+) -> Pin<Box<dyn Future<Output = tide::Result> + Send + 'a>> {
+    Box::pin(async move {
+        let changes = request.state().changes.clone();
+        let response = next.run(request).await;
+
+        for change in changes.take_plan() {
+            println!("dry run: would {}", change);
+        }
 
-    projects_count.sort();
+        Ok(response)
+    })
+}
 
-    let mut template_context = tera::Context::new();
-    template_context.insert("projects_count", &projects_count);
+/// Tide does not send the message of errors so clients would not know why a
+/// request was rejected. Only client errors get the message as server errors
+/// can contain internal details. Exceeded quotas are rejected as unprocessable
+/// with the quota, its limit and the count as json.
+async fn client_error_body(mut response: Response) -> tide::Result {
+    let exceeded = response
+        .error()
+        .and_then(|err| err.downcast_ref::<QuotaExceeded>())
+        .cloned();
 
-    let output = request
-        .state()
-        .templates
-        .render("index.html", &template_context)
-        .unwrap();
+    if let Some(exceeded) = exceeded {
+        let mut details = serde_json::to_value(&exceeded)?;
+        details["error"] = exceeded.to_string().into();
 
-    Ok(Response::builder(StatusCode::Ok)
-        .header("Content-Type", "text/html")
-        .body(Body::from(output))
-        .build())
+        response.set_status(StatusCode::UnprocessableEntity);
+        response.set_body(Body::from_json(&details)?);
+
+        return Ok(response);
+    }
+
+    if response.status().is_client_error() && response.is_empty() == Some(true) {
+        if let Some(message) = response.error().map(ToString::to_string) {
+            response.set_content_type(mime::PLAIN);
+            response.set_body(message);
+        }
+    }
+
+    Ok(response)
 }
 
 async fn handler_health(_request: Request<WebService>) -> Result<Response, tide::Error> {
@@ -148,354 +511,298 @@ async fn handler_health(_request: Request<WebService>) -> Result<Response, tide:
         .build())
 }
 
-async fn handler_project(request: Request<WebService>) -> Result<Response, tide::Error> {
-    let project = request.param("project")?;
-
-    // TODO: use request.query() instead
-    let show_done = match request.url().query() {
-        Some(parameters) => parameters
-            .split('&')
-            .map(|key_values| {
-                let mut split = key_values.split('=');
-                (split.next().unwrap_or(""), split.next().unwrap_or(""))
-            })
-            .find(|(key, _)| key == &"show_done")
-            .map(|(_, value)| value.parse().unwrap_or(false))
-            .unwrap_or(false),
-        None => false,
-    };
-
-    let entries_active = request.state().store.get_active_entries(project).unwrap();
-    let entries_done = if show_done {
-        request.state().store.get_done_entries(project).unwrap()
-    } else {
-        crate::entry::Entries::default()
-    };
+/// Location to redirect to after an action. Uses the `redirect` query
+/// parameter if it is a path on this server so pages can link to actions that
+/// return back to them.
+fn redirect_location(
+    request: &Request<WebService>,
+    default: String,
+) -> Result<String, tide::Error> {
+    #[derive(Deserialize, Debug)]
+    struct Query {
+        redirect: Option<String>,
+    }
 
-    let mut template_context = tera::Context::new();
-    template_context.insert("entries_active", &entries_active.into_inner());
-    template_context.insert("entries_done", &entries_done.into_inner());
-    template_context.insert("project", &project);
-    template_context.insert("show_done", &show_done);
+    let query: Query = request.query()?;
 
-    let output = request
-        .state()
-        .templates
-        .render("project.html", &template_context)
-        .unwrap();
+    let location = match query.redirect {
+        Some(redirect) if is_local_path(&redirect) => redirect,
+        _ => default,
+    };
 
-    Ok(Response::builder(StatusCode::Ok)
-        .header("Content-Type", "text/html")
-        .body(Body::from(output))
-        .build())
+    Ok(location)
 }
 
-async fn handler_project_add_entry(request: Request<WebService>) -> Result<Response, tide::Error> {
-    let project = request.param("project").unwrap_or("work");
-
-    let mut template_context = tera::Context::new();
-    template_context.insert("project", &project);
-
-    let output = request
-        .state()
-        .templates
-        .render("project_add_entry.html", &template_context)
-        .unwrap();
-
-    Ok(Response::builder(StatusCode::Ok)
-        .header("Content-Type", "text/html")
-        .body(Body::from(output.as_bytes()))
-        .build())
+/// Check that the location is a path on this server. Browsers treat
+/// backslashes like slashes and drop tabs and newlines, so `/\evil.com` or
+/// `/\t/evil.com` would lead to another host.
+fn is_local_path(location: &str) -> bool {
+    location.starts_with('/')
+        && !location.starts_with("//")
+        && !location
+            .chars()
+            .any(|character| character == '\\' || character.is_control())
 }
 
-async fn handler_entry(request: Request<WebService>) -> Result<Response, tide::Error> {
-    let uuid: uuid::Uuid = match request.param("uuid") {
-        Ok(uuid) => uuid.parse()?,
-        Err(_) => {
-            return Ok(Response::builder(StatusCode::InternalServerError)
-                .header("Content-Type", "text/plain")
-                .body(Body::from("500 - no uuid found"))
-                .build())
-        }
-    };
-
-    let entry = request.state().store.get_entry_by_uuid(&uuid).unwrap();
-
-    let mut template_context = tera::Context::new();
-    template_context.insert("entry", &entry);
-
-    let output = request
-        .state()
-        .templates
-        .render("entry.html", &template_context)
-        .unwrap();
-
-    Ok(Response::builder(StatusCode::Ok)
-        .header("Content-Type", "text/html")
-        .body(Body::from(output.as_bytes()))
-        .build())
+/// Get the project from the request parameters. Fails with a bad request if
+/// the project is not a valid project name.
+fn project_param(request: &Request<WebService>) -> Result<String, tide::Error> {
+    let project = helper::percent_decode(request.param("project")?).ok_or_else(|| {
+        tide::Error::from_str(
+            StatusCode::BadRequest,
+            "project is not percent encoded correctly",
+        )
+    })?;
+
+    valid_project(project)
 }
 
-async fn handler_entry_edit(request: Request<WebService>) -> Result<Response, tide::Error> {
-    let uuid: uuid::Uuid = match request.param("uuid") {
-        Ok(uuid) => uuid.parse()?,
-        Err(_) => {
-            return Ok(Response::builder(StatusCode::InternalServerError)
-                .header("Content-Type", "text/plain")
-                .body(Body::from("500 - no uuid found"))
-                .build())
-        }
-    };
+/// Fails with a bad request if the project is not a valid project name.
+fn valid_project(project: String) -> Result<String, tide::Error> {
+    validate_project_name(&project).map_err(|err| tide::Error::new(StatusCode::BadRequest, err))?;
 
-    let entry = request.state().store.get_entry_by_uuid(&uuid).unwrap();
-
-    let mut template_context = tera::Context::new();
-    template_context.insert("entry", &entry);
-
-    let output = request
-        .state()
-        .templates
-        .render("entry_edit.html", &template_context)
-        .unwrap();
-
-    Ok(Response::builder(StatusCode::Ok)
-        .header("Content-Type", "text/html")
-        .body(Body::from(output.as_bytes()))
-        .build())
+    Ok(project)
 }
 
-async fn handler_entry_move_project(request: Request<WebService>) -> Result<Response, tide::Error> {
-    let uuid: uuid::Uuid = match request.param("uuid") {
-        Ok(uuid) => uuid.parse()?,
-        Err(_) => {
-            return Ok(Response::builder(StatusCode::InternalServerError)
-                .header("Content-Type", "text/plain")
-                .body(Body::from("500 - no uuid found"))
-                .build())
-        }
-    };
-
-    let entry = request.state().store.get_entry_by_uuid(&uuid).unwrap();
-    let mut projects = request.state().store.get_projects().unwrap();
-    projects.sort();
-    projects.dedup();
+/// Record the change in the audit log under the name of the token of the
+/// request. Never fails as the change was already made.
+fn audit(request: &Request<WebService>, action: &str, uuid: Uuid, project: &str) {
+    request.state().audit.record(AuditRecord {
+        timestamp: Utc::now(),
+        principal: auth::scope(request)
+            .name()
+            .unwrap_or(audit::ANONYMOUS)
+            .to_string(),
+        action: action.to_string(),
+        uuid,
+        project: project.to_string(),
+    });
+}
 
-    let mut template_context = tera::Context::new();
-    template_context.insert("entry", &entry);
-    template_context.insert("projects", &projects);
+/// Get the entry with the uuid from the request parameters. Handlers get the
+/// entry through `authorized_entry` after the access to it was checked.
+fn entry_param(request: &Request<WebService>) -> Result<Entry, tide::Error> {
+    let uuid = helper::parse_uuid(request.param("uuid")?)
+        .map_err(|err| tide::Error::new(StatusCode::BadRequest, err))?;
 
-    let output = request
+    let entry = request
         .state()
-        .templates
-        .render("entry_move_project.html", &template_context)
-        .unwrap();
+        .store
+        .find_entry_by_uuid(&uuid)?
+        .ok_or_else(|| {
+            tide::Error::from_str(StatusCode::NotFound, format!("404 - no entry {}", uuid))
+        })?;
 
-    Ok(Response::builder(StatusCode::Ok)
-        .header("Content-Type", "text/html")
-        .body(Body::from(output.as_bytes()))
-        .build())
+    Ok(entry)
 }
 
-async fn handler_api_v1_project_entries(
-    request: Request<WebService>,
-) -> Result<Response, tide::Error> {
-    let project = request.param("project")?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::{
+        self,
+        hooks::HooksConfig,
+        quota::Quotas,
+    };
+    use http_types::{
+        Method,
+        Url,
+    };
+    use rate_limit::RateLimitConfig;
 
-    let entries = request.state().store.get_active_entries(project).unwrap();
+    /// Webservice for a temporary store with the web settings of the toml
+    /// like `web_tokens`, `web_view`, `inbound_webhooks`, `hooks` or
+    /// `quotas`. Without tokens every request is allowed. The store is
+    /// returned so tests can add entries to it.
+    pub(super) fn app(settings: &str) -> (tempfile::TempDir, Store, tide::Server<WebService>) {
+        #[derive(Deserialize)]
+        struct Settings {
+            #[serde(default)]
+            web_tokens: Vec<WebToken>,
 
-    let response = Response::builder(200)
-        .body(Body::from_json(&entries)?)
-        .content_type(mime::JSON)
-        .build();
+            #[serde(default)]
+            web_view: ViewPreferences,
 
-    Ok(response)
-}
+            #[serde(default)]
+            inbound_webhooks: Vec<InboundWebhook>,
 
-async fn handler_api_v1_mark_entry_done(
-    request: Request<WebService>,
-) -> Result<Response, tide::Error> {
-    let uuid: Uuid = request.param("uuid")?.parse()?;
+            #[serde(default)]
+            expose_paths: bool,
 
-    request.state().store.entry_done_by_uuid(uuid).unwrap();
+            #[serde(default)]
+            public: bool,
 
-    let location = format!("/entry/{}", uuid);
+            web_base_url: Option<String>,
 
-    Ok(Response::builder(StatusCode::SeeOther)
-        .header("Content-Type", "text/plain")
-        .header("Location", location)
-        .body(Body::from("entry updated to be done"))
-        .build())
-}
+            sitemap_limit: Option<usize>,
 
-async fn handler_api_v1_mark_entry_active(
-    request: Request<WebService>,
-) -> Result<Response, tide::Error> {
-    let uuid: Uuid = request.param("uuid")?.parse()?;
+            #[serde(default)]
+            hooks: HooksConfig,
 
-    request.state().store.entry_active_by_uuid(uuid).unwrap();
+            #[serde(default)]
+            web_rate_limit: RateLimitConfig,
 
-    let location = format!("/entry/{}", uuid);
+            #[serde(default)]
+            quotas: Quotas,
 
-    Ok(Response::builder(StatusCode::SeeOther)
-        .header("Content-Type", "text/plain")
-        .header("Location", location)
-        .body(Body::from("entry updated to be active"))
-        .build())
-}
+            #[serde(default)]
+            require_explicit_project: bool,
+        }
 
-async fn handler_api_v1_project_add_entry(
-    mut request: Request<WebService>,
-) -> Result<Response, tide::Error> {
-    #[derive(Deserialize, Debug)]
-    struct Message {
-        text: String,
+        let settings = toml::from_str::<Settings>(settings).unwrap();
+
+        let (directory, store) = store::tests::temporary_with(Config {
+            hooks: settings.hooks,
+            quotas: settings.quotas,
+            ..Config::default()
+        });
+        let config = Config {
+            web_tokens: settings.web_tokens,
+            web_view: settings.web_view,
+            inbound_webhooks: settings.inbound_webhooks,
+            expose_paths: settings.expose_paths,
+            public: settings.public,
+            web_base_url: settings.web_base_url,
+            web_rate_limit: settings.web_rate_limit,
+            require_explicit_project: settings.require_explicit_project,
+            ..Config::default()
+        };
+        let config = Config {
+            sitemap_limit: settings.sitemap_limit.unwrap_or(config.sitemap_limit),
+            ..config
+        };
+
+        let service = WebService::open(store.clone(), &config, false).unwrap();
+
+        (directory, store, service.app())
     }
 
-    let project = request.param("project")?.to_owned();
-    let message: Message = request.body_form().await?;
-
-    let entry = Entry {
-        text: message.text.replace("\r", ""),
-        metadata: Metadata {
-            project,
-            ..Metadata::default()
-        },
-    };
+    /// Request for the path that is sent with the token as bearer token.
+    pub(super) fn request(method: Method, path: &str, token: Option<&str>) -> http_types::Request {
+        let url = Url::parse("http://localhost").unwrap().join(path).unwrap();
+        let mut request = http_types::Request::new(method, url);
 
-    let uuid = entry.metadata.uuid;
+        if let Some(token) = token {
+            request.insert_header("Authorization", format!("Bearer {}", token));
+        }
 
-    request.state().store.add_entry(entry).unwrap();
+        request
+    }
 
-    Ok(Response::builder(StatusCode::SeeOther)
-        .header("Content-Type", "text/plain")
-        .header("Location", format!("/entry/{}", uuid))
-        .body(Body::from("entry updated to be done"))
-        .build())
-}
+    pub(super) async fn respond(
+        app: &tide::Server<WebService>,
+        request: http_types::Request,
+    ) -> http_types::Response {
+        app.respond(request).await.unwrap()
+    }
 
-async fn handler_api_v1_entry_edit(
-    mut request: Request<WebService>,
-) -> Result<Response, tide::Error> {
-    #[derive(Deserialize, Debug)]
-    struct Message {
-        text: String,
-        update_time: Option<String>,
+    /// Title and breadcrumb of the page with the whitespace between the
+    /// elements collapsed.
+    pub(super) async fn navigation(app: &tide::Server<WebService>, path: &str) -> (String, String) {
+        let mut response = respond(app, request(Method::Get, path, None)).await;
+        assert_eq!(response.status(), StatusCode::Ok, "{}", path);
+        let body = response.body_string().await.unwrap();
+
+        let element = |start: &str, end: &str| {
+            let from = body.find(start).unwrap();
+            let to = body[from..].find(end).unwrap() + from + end.len();
+
+            body[from..to]
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+
+        (
+            element("<title>", "</title>"),
+            element("<nav class=\"breadcrumb\">", "</nav>"),
+        )
     }
 
-    let uuid: uuid::Uuid = match request.param("uuid") {
-        Ok(uuid) => uuid.parse()?,
-        Err(_) => {
-            return Ok(Response::builder(StatusCode::InternalServerError)
-                .header("Content-Type", "text/plain")
-                .body(Body::from("500 - no uuid found"))
-                .build())
+    /// Overwrite every index file below the folder with rows that can not be
+    /// read.
+    fn corrupt_index(folder: &std::path::Path) {
+        for path in helper::folder_paths(folder).unwrap() {
+            if path.is_dir() {
+                corrupt_index(&path);
+            } else if path.extension().is_some_and(|extension| extension == "csv") {
+                std::fs::write(&path, "not,an\nindex\n").unwrap();
+            }
         }
-    };
-
-    let message: Message = request.body_form().await?;
-
-    let old_entry = request.state().store.get_entry_by_uuid(&uuid).unwrap();
-
-    let text = message.text.replace("\r", "");
+    }
 
-    let new_entry = if message.update_time.is_some() {
-        Entry {
-            text,
-            metadata: Metadata {
-                started: Utc::now(),
-                last_change: Utc::now(),
-                ..old_entry.metadata
-            },
+    #[async_std::test]
+    async fn unknown_entry_is_not_found() {
+        let (_directory, _store, app) = app("");
+        let uuid = Uuid::new_v4();
+
+        for path in &[
+            format!("/entry/{}", uuid),
+            format!("/entry/edit/{}", uuid),
+            format!("/api/v1/entry/raw/{}", uuid),
+        ] {
+            let response = respond(&app, request(Method::Get, path, None)).await;
+            assert_eq!(response.status(), StatusCode::NotFound, "{}", path);
         }
-    } else {
-        Entry { text, ..old_entry }
-    };
+    }
 
-    request.state().store.update_entry(new_entry).unwrap();
+    #[async_std::test]
+    async fn malformed_uuid_is_a_bad_request() {
+        let (_directory, _store, app) = app("");
 
-    Ok(Response::builder(StatusCode::SeeOther)
-        .header("Content-Type", "text/plain")
-        .header("Location", format!("/entry/{}", uuid))
-        .body(Body::from("entry text updated"))
-        .build())
-}
+        let response = respond(&app, request(Method::Get, "/entry/not-a-uuid", None)).await;
 
-async fn handler_api_v1_entry_move_project(
-    mut request: Request<WebService>,
-) -> Result<Response, tide::Error> {
-    #[derive(Deserialize, Debug)]
-    struct Message {
-        new_project: String,
+        assert_eq!(response.status(), StatusCode::BadRequest);
     }
 
-    let message: Message = request.body_form().await?;
-
-    let uuid: uuid::Uuid = match request.param("uuid") {
-        Ok(uuid) => uuid.parse()?,
-        Err(_) => {
-            return Ok(Response::builder(StatusCode::InternalServerError)
-                .header("Content-Type", "text/plain")
-                .body(Body::from("500 - no uuid found"))
-                .build())
+    #[async_std::test]
+    async fn pasted_uuid_forms_are_found() {
+        let (_directory, store, app) = app("");
+        let added = store::tests::entry("text");
+        store.add_entry(added.clone()).unwrap();
+
+        let uuid = added.metadata.uuid;
+        for path in &[
+            format!("/entry/{}", uuid.to_string().to_uppercase()),
+            format!("/entry/{}", uuid.to_simple()),
+            format!("/entry/%7B{}%7D", uuid),
+        ] {
+            let response = respond(&app, request(Method::Get, path, None)).await;
+            assert_eq!(response.status(), StatusCode::Ok, "{}", path);
         }
-    };
-
-    let old_entry = request.state().store.get_entry_by_uuid(&uuid).unwrap();
+    }
 
-    let new_entry = Entry {
-        metadata: Metadata {
-            project: message.new_project,
-            last_change: Utc::now(),
-            ..old_entry.metadata
-        },
-        ..old_entry
-    };
+    #[async_std::test]
+    async fn unreadable_index_is_an_internal_error() {
+        let (_directory, store, app) = app("");
+        let added = store::tests::entry("text");
+        store.add_entry(added.clone()).unwrap();
 
-    request.state().store.update_entry(new_entry).unwrap();
+        let path = format!("/entry/{}", added.metadata.uuid);
+        let response = respond(&app, request(Method::Get, &path, None)).await;
+        assert_eq!(response.status(), StatusCode::Ok);
 
-    Ok(Response::builder(StatusCode::SeeOther)
-        .header("Content-Type", "text/plain")
-        .header("Location", format!("/entry/{}", uuid))
-        .body(Body::from("entry text updated"))
-        .build())
-}
+        corrupt_index(&store.datadir().join("index"));
 
-async fn handler_static_css_main(_request: Request<WebService>) -> Result<Response, tide::Error> {
-    Ok(Response::builder(StatusCode::Ok)
-        .header("Content-Type", "text/css")
-        .body(Body::from(
-            include_bytes!("resources/css/main.css").to_vec(),
-        ))
-        .build())
-}
-
-async fn handler_static_css_font_awesome(
-    _request: Request<WebService>,
-) -> Result<Response, tide::Error> {
-    Ok(Response::builder(StatusCode::Ok)
-        .header("Content-Type", "text/css")
-        .body(Body::from(
-            include_bytes!("resources/css/font-awesome.min.css").to_vec(),
-        ))
-        .build())
-}
+        let response = respond(&app, request(Method::Get, &path, None)).await;
+        assert_eq!(response.status(), StatusCode::InternalServerError);
+    }
 
-async fn handler_static_fonts_fontawesome_webfont_woff2(
-    _request: Request<WebService>,
-) -> Result<Response, tide::Error> {
-    Ok(Response::builder(StatusCode::Ok)
-        .header("Content-Type", "font/woff2")
-        .body(Body::from(
-            include_bytes!("resources/fonts/fontawesome-webfont.woff2").to_vec(),
-        ))
-        .build())
-}
+    #[test]
+    fn local_paths_are_allowed() {
+        assert!(is_local_path("/"));
+        assert!(is_local_path("/project/default"));
+        assert!(is_local_path("/project/default?view=list#entry"));
+    }
 
-async fn handler_favicon_ico(_request: Request<WebService>) -> Result<Response, tide::Error> {
-    Ok(Response::builder(StatusCode::Ok)
-        .header("Content-Type", "image/x-icon")
-        .body(Body::from(
-            include_bytes!("resources/img/favicon.ico").to_vec(),
-        ))
-        .build())
+    #[test]
+    fn other_hosts_are_rejected() {
+        assert!(!is_local_path("https://evil.com"));
+        assert!(!is_local_path("evil.com"));
+        assert!(!is_local_path("//evil.com"));
+        assert!(!is_local_path("/\\evil.com"));
+        assert!(!is_local_path("/\t/evil.com"));
+        assert!(!is_local_path("/\n/evil.com"));
+        assert!(!is_local_path(""));
+    }
 }
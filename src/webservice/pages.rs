@@ -0,0 +1,1121 @@
+use super::{
+    auth,
+    auth::{
+        authorize,
+        Access,
+    },
+    index_columns,
+    preferences,
+    preferences::{
+        SortOrder,
+        ViewOverrides,
+    },
+    project_param,
+    sparkline,
+    valid_project,
+    WebService,
+    DEFAULT_ACTIVITY_WEEKS,
+    DEFAULT_PROJECT,
+    MAX_ACTIVITY_WEEKS,
+};
+use crate::{
+    entry::{
+        Entries,
+        Entry,
+    },
+    helper,
+    locale::Translations,
+    stats::{
+        Board,
+        Digest,
+    },
+    store::Store,
+};
+use anyhow::Error;
+use chrono::NaiveDate;
+use log::warn;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::collections::BTreeMap;
+use tide::{
+    http::Cookie,
+    Body,
+    Request,
+    Response,
+    StatusCode,
+};
+
+pub(super) async fn handler_index(request: Request<WebService>) -> Result<Response, tide::Error> {
+    #[derive(Deserialize, Debug)]
+    struct Query {
+        #[serde(default)]
+        show_ignored: bool,
+        columns: Option<String>,
+        sort: Option<String>,
+        weeks: Option<usize>,
+    }
+
+    let query: Query = request.query()?;
+    let state = request.state();
+
+    let weeks = query
+        .weeks
+        .unwrap_or(DEFAULT_ACTIVITY_WEEKS)
+        .clamp(1, MAX_ACTIVITY_WEEKS);
+
+    let projects_count = state
+        .store
+        .get_projects_count(query.show_ignored, &state.time)?
+        .into_iter()
+        .filter(|count| auth::scope(&request).allows(&count.project, Access::Read))
+        .collect::<Vec<_>>();
+
+    let preferences = state
+        .index_defaults
+        .with_query(query.columns.as_deref(), query.sort.as_deref());
+
+    let finished_per_week = state.store.finished_per_week(weeks, &state.time)?;
+    let sparklines = projects_count
+        .iter()
+        .map(|count| {
+            let values = finished_per_week
+                .get(&count.project)
+                .cloned()
+                .unwrap_or_else(|| vec![0; weeks]);
+
+            (count.project.clone(), sparkline::sparkline(&values))
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    let summaries = state.store.get_summaries(&state.time)?;
+
+    let favorites = state
+        .store
+        .favorite_projects(query.show_ignored)
+        .into_iter()
+        .filter(|project| auth::scope(&request).allows(project, Access::Read))
+        .collect::<Vec<_>>();
+
+    // Flag the default project while entries still land there although they
+    // should not anymore.
+    let untriaged = state.require_explicit_project
+        && projects_count
+            .iter()
+            .any(|count| count.project == DEFAULT_PROJECT && count.active_count > 0);
+
+    let (columns, rows) =
+        index_columns::index_table(&preferences, projects_count, &favorites, &state.time);
+
+    let mut template_context = tera::Context::new();
+    template_context.insert("columns", &columns);
+    template_context.insert("rows", &rows);
+    template_context.insert("weeks", &weeks);
+    template_context.insert("sparklines", &sparklines);
+    template_context.insert("summaries", &summaries);
+    if untriaged {
+        template_context.insert("untriaged_project", DEFAULT_PROJECT);
+    }
+
+    let output = request.state().render("index.html", &template_context)?;
+
+    Ok(Response::builder(StatusCode::Ok)
+        .header("Content-Type", "text/html")
+        .body(Body::from(output))
+        .build())
+}
+
+/// Render the digest as a standalone html page. When `inline_css` is set the
+/// stylesheet is embedded so the page can be viewed without the webservice.
+pub(crate) fn render_digest(
+    digest: &Digest,
+    translations: Translations,
+    inline_css: bool,
+) -> Result<String, Error> {
+    let templates = WebService::open_templates(translations)?;
+
+    let mut template_context = tera::Context::new();
+    template_context.insert("digest", digest);
+
+    if inline_css {
+        template_context.insert("inline_css", include_str!("resources/css/main.css"));
+    }
+
+    let output = templates.render("digest.html", &template_context)?;
+
+    Ok(output)
+}
+
+/// Entry of the html bundle with its text rendered by asciidoctor. `html` is
+/// not set when asciidoctor is not available and the text is shown as it is.
+#[derive(Debug, Serialize)]
+pub(super) struct BundledEntry<'a> {
+    entry: &'a Entry,
+    html: Option<String>,
+}
+
+/// Entries of the html bundle under one heading of the table of contents.
+#[derive(Debug, Serialize)]
+pub(super) struct BundleSection<'a> {
+    /// Locale key of the heading.
+    name: &'static str,
+    entries: Vec<BundledEntry<'a>>,
+}
+
+/// Render the active and done entries as a single html page for archiving
+/// them. The stylesheet is embedded and nothing is loaded from elsewhere so
+/// the page can be viewed offline.
+pub(crate) fn render_export_bundle(
+    store: &Store,
+    title: &str,
+    entries: &Entries,
+    today: NaiveDate,
+    translations: Translations,
+) -> Result<String, Error> {
+    let templates = WebService::open_templates(translations)?;
+
+    // Stop trying after the first failure as asciidoctor is most likely not
+    // installed.
+    let mut renderable = true;
+    let mut bundle = |entry| {
+        let html = if renderable {
+            store
+                .entry_html(entry, true)
+                .map_err(|err| {
+                    warn!(
+                        "can not render entries, showing their text instead: {:#}",
+                        err
+                    );
+                    renderable = false;
+                })
+                .ok()
+        } else {
+            None
+        };
+
+        BundledEntry { entry, html }
+    };
+
+    let (active, done): (Vec<_>, Vec<_>) =
+        entries.entries.iter().partition(|entry| entry.is_active());
+    let sections = vec![
+        BundleSection {
+            name: "active",
+            entries: active.into_iter().map(&mut bundle).collect(),
+        },
+        BundleSection {
+            name: "done",
+            entries: done.into_iter().map(&mut bundle).collect(),
+        },
+    ];
+
+    let mut template_context = tera::Context::new();
+    template_context.insert("title", title);
+    template_context.insert("sections", &sections);
+    template_context.insert("today", &today);
+    template_context.insert("inline_css", include_str!("resources/css/main.css"));
+
+    let output = templates.render("export_bundle.html", &template_context)?;
+
+    Ok(output)
+}
+
+pub(super) async fn handler_digest(request: Request<WebService>) -> Result<Response, tide::Error> {
+    #[derive(Deserialize, Debug)]
+    struct Query {
+        period: Option<String>,
+    }
+
+    let query: Query = request.query()?;
+
+    let period = match query.period {
+        Some(period) => helper::parse_duration(&period)
+            .map_err(|err| tide::Error::from_str(StatusCode::BadRequest, err))?,
+        None => chrono::Duration::weeks(1),
+    };
+
+    let scope = auth::scope(&request);
+    let entries = request
+        .state()
+        .store
+        .get_all_entries(false)?
+        .into_iter()
+        .filter(|entry| scope.allows(&entry.metadata.project, Access::Read))
+        .collect();
+    let digest = Digest::new(entries, &request.state().time.frozen(), period);
+
+    let mut template_context = tera::Context::new();
+    template_context.insert("digest", &digest);
+
+    let output = request.state().render("digest.html", &template_context)?;
+
+    Ok(Response::builder(StatusCode::Ok)
+        .header("Content-Type", "text/html")
+        .body(Body::from(output))
+        .build())
+}
+
+pub(super) async fn handler_board(request: Request<WebService>) -> Result<Response, tide::Error> {
+    let project = &project_param(&request)?;
+
+    let entries = request.state().store.get_entries(project)?;
+    let board = Board::new(entries, &request.state().time.frozen());
+
+    let mut template_context = tera::Context::new();
+    template_context.insert("project", &project);
+    template_context.insert("board", &board);
+    template_context.insert("today", &request.state().time.today());
+
+    let output = request.state().render("board.html", &template_context)?;
+
+    Ok(Response::builder(StatusCode::Ok)
+        .header("Content-Type", "text/html")
+        .body(Body::from(output))
+        .build())
+}
+
+/// Download the document `print` writes for the project either as asciidoc or
+/// rendered to html.
+pub(super) async fn handler_project_export(
+    request: Request<WebService>,
+) -> Result<Response, tide::Error> {
+    #[derive(Deserialize, Debug, Default, Clone, Copy, PartialEq)]
+    #[serde(rename_all = "lowercase")]
+    enum ExportFormat {
+        #[default]
+        Asciidoc,
+        Html,
+    }
+
+    #[derive(Deserialize, Debug)]
+    struct Query {
+        #[serde(default)]
+        format: ExportFormat,
+
+        #[serde(default = "default_export_done")]
+        done: bool,
+    }
+
+    fn default_export_done() -> bool {
+        true
+    }
+
+    let project = &project_param(&request)?;
+
+    let query: Query = request.query()?;
+
+    let mut entries = request.state().store.get_entries(project)?;
+    if !query.done {
+        entries = entries.get_active();
+    }
+
+    let asciidoc = entries.to_asciidoc(&request.state().time)?;
+
+    let (body, content_type, extension) = match query.format {
+        ExportFormat::Asciidoc => (asciidoc, "text/asciidoc; charset=utf-8", "adoc"),
+        ExportFormat::Html => (
+            request.state().store.document_html(
+                &format!("export {}", project),
+                &asciidoc,
+                request.state().render_cache,
+            )?,
+            "text/html; charset=utf-8",
+            "html",
+        ),
+    };
+
+    let filename = format!(
+        "{}-{}.{}",
+        attachment_name(project),
+        request.state().time.today(),
+        extension
+    );
+
+    Ok(Response::builder(StatusCode::Ok)
+        .header("Content-Type", content_type)
+        .header(
+            "Content-Disposition",
+            format!("attachment; filename=\"{}\"", filename),
+        )
+        .body(Body::from(body))
+        .build())
+}
+
+/// Project names can contain anything so only keep characters that are safe
+/// in the quoted filename of a content disposition header.
+pub(super) fn attachment_name(project: &str) -> String {
+    project
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+pub(super) async fn handler_project(request: Request<WebService>) -> Result<Response, tide::Error> {
+    #[derive(Deserialize, Debug)]
+    struct Query {
+        show_done: Option<bool>,
+        sort: Option<SortOrder>,
+        page_size: Option<usize>,
+        page: Option<usize>,
+        due_errors: Option<usize>,
+    }
+
+    let project = &project_param(&request)?;
+
+    let query: Query = request.query()?;
+    let query_view = ViewOverrides {
+        show_done: query.show_done,
+        sort: query.sort,
+        page_size: query.page_size,
+    };
+
+    let cookie_views = cookie_views(&request);
+    let view = preferences::resolve(
+        &query_view,
+        cookie_views.get(project),
+        &request.state().view_defaults,
+    );
+
+    let mut entries_active = request
+        .state()
+        .store
+        .get_active_entry_previews(project)?
+        .into_iter()
+        .collect::<Vec<_>>();
+    view.sort.sort(&mut entries_active);
+
+    let mut entries_done = if view.show_done {
+        request
+            .state()
+            .store
+            .get_done_entry_previews(project)?
+            .into_iter()
+            .collect::<Vec<_>>()
+    } else {
+        Vec::new()
+    };
+    view.sort.sort(&mut entries_done);
+
+    let page_count = match view.page_size {
+        Some(page_size) => entries_active.len().div_ceil(page_size).max(1),
+        None => 1,
+    };
+    let page = query.page.unwrap_or(1).clamp(1, page_count);
+    let first_entry = match view.page_size {
+        Some(page_size) => {
+            let first_entry = (page - 1) * page_size;
+            entries_active = entries_active
+                .into_iter()
+                .skip(first_entry)
+                .take(page_size)
+                .collect();
+
+            first_entry
+        }
+        None => 0,
+    };
+
+    let focus = request.state().store.focused_uuid(project);
+
+    let summary = request
+        .state()
+        .store
+        .get_summaries(&request.state().time)?
+        .remove(project)
+        .unwrap_or_default();
+
+    let mut template_context = tera::Context::new();
+    template_context.insert("entries_active", &entries_active);
+    template_context.insert("summary", &summary);
+    template_context.insert("focus", &focus);
+    template_context.insert("entries_done", &entries_done);
+    template_context.insert("project", &project);
+    template_context.insert("show_done", &view.show_done);
+    template_context.insert("view", &view);
+    template_context.insert("page", &page);
+    template_context.insert("page_count", &page_count);
+    template_context.insert("first_entry", &(first_entry + 1));
+    template_context.insert("due_errors", &query.due_errors.unwrap_or(0));
+
+    let output = request.state().render("project.html", &template_context)?;
+
+    Ok(Response::builder(StatusCode::Ok)
+        .header("Content-Type", "text/html")
+        .body(Body::from(output))
+        .build())
+}
+
+/// Store the view preferences given in the query in the preferences cookie
+/// and redirect back to the project.
+pub(super) async fn handler_project_view(
+    request: Request<WebService>,
+) -> Result<Response, tide::Error> {
+    let project = project_param(&request)?;
+
+    let update: ViewOverrides = request.query()?;
+
+    let mut views = cookie_views(&request);
+    views.entry(project.clone()).or_default().merge(&update);
+
+    let cookie = Cookie::build(
+        preferences::COOKIE_NAME,
+        preferences::serialize_cookie(&views),
+    )
+    .path("/")
+    .max_age(time::Duration::days(365))
+    .finish();
+
+    let mut response = Response::builder(StatusCode::SeeOther)
+        .header("Content-Type", "text/plain")
+        .header("Location", format!("/project/{}", project))
+        .body(Body::from("view preferences updated"))
+        .build();
+    response.insert_cookie(cookie);
+
+    Ok(response)
+}
+
+pub(super) fn cookie_views(request: &Request<WebService>) -> BTreeMap<String, ViewOverrides> {
+    request
+        .cookie(preferences::COOKIE_NAME)
+        .map(|cookie| preferences::parse_cookie(cookie.value()))
+        .unwrap_or_default()
+}
+
+pub(super) async fn handler_project_add_entry(
+    request: Request<WebService>,
+) -> Result<Response, tide::Error> {
+    let project = &project_param(&request)?;
+
+    let mut template_context = tera::Context::new();
+    template_context.insert("project", &project);
+
+    let output = request
+        .state()
+        .render("project_add_entry.html", &template_context)?;
+
+    Ok(Response::builder(StatusCode::Ok)
+        .header("Content-Type", "text/html")
+        .body(Body::from(output.as_bytes()))
+        .build())
+}
+
+/// Prefilled form for adding an entry that can be opened from bookmarklets.
+/// Only renders the form so following the link never creates an entry.
+pub(super) async fn handler_quick_add(
+    request: Request<WebService>,
+) -> Result<Response, tide::Error> {
+    #[derive(Deserialize, Debug)]
+    struct Query {
+        text: Option<String>,
+        project: Option<String>,
+    }
+
+    let query: Query = request.query()?;
+    let project = valid_project(request.state().project_to_add_to(query.project)?)?;
+    authorize(&request, &project, Access::Write)?;
+
+    let mut template_context = tera::Context::new();
+    template_context.insert("project", &project);
+    template_context.insert("text", &query.text.unwrap_or_default());
+
+    let output = request
+        .state()
+        .render("project_add_entry.html", &template_context)?;
+
+    Ok(Response::builder(StatusCode::Ok)
+        .header("Content-Type", "text/html")
+        .body(Body::from(output.as_bytes()))
+        .build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        store::{
+            tests::{
+                entry,
+                temporary,
+            },
+            Store,
+        },
+        time_context::TimeContext,
+        webservice,
+    };
+    use chrono::{
+        DateTime,
+        NaiveDate,
+        Utc,
+    };
+    use http_types::Method;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        time.parse().unwrap()
+    }
+
+    fn seeded(text: &str, project: &str) -> Entry {
+        let mut seeded = entry(text);
+        seeded.metadata.project = project.to_string();
+
+        seeded
+    }
+
+    /// Headings and list items of the page without the indentation. How long
+    /// ago entries were changed is left out as it is measured with the system
+    /// clock.
+    fn structure(html: &str) -> Vec<&str> {
+        html.lines()
+            .map(str::trim)
+            .filter(|line| {
+                ["<h1>", "<h2>", "<h3>", "<li>", "<p>"]
+                    .iter()
+                    .any(|tag| line.starts_with(tag))
+            })
+            .map(|line| match line.find(" (last change") {
+                Some(index) => &line[..index],
+                None => line,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn digest_page_lists_every_section() {
+        let (_directory, store) = temporary();
+
+        let mut shipped = seeded("== Ship the release", "work");
+        shipped.metadata.finished = Some(at("2026-10-14T09:00:00Z"));
+        let mut old = seeded("Clean the garage", "home");
+        old.metadata.finished = Some(at("2026-09-01T09:00:00Z"));
+        let mut renew = seeded("Renew the domain", "home");
+        renew.metadata.due = NaiveDate::from_ymd_opt(2026, 10, 10);
+        let mut call = seeded("Call the vendor", "work");
+        call.metadata.due = NaiveDate::from_ymd_opt(2026, 10, 20);
+        let idea = seeded("Someday idea", "home");
+
+        for seeded in [shipped, old, renew, call, idea] {
+            store.add_entry(seeded).unwrap();
+        }
+
+        let time = TimeContext::new(None).with_now(at("2026-10-16T12:00:00Z"));
+        let digest = Digest::new(
+            store.get_all_entries(false).unwrap(),
+            &time,
+            chrono::Duration::weeks(1),
+        );
+        let html = render_digest(&digest, Translations::default(), true).unwrap();
+
+        assert!(html.contains("<style>"));
+        assert!(!html.contains("/static/css/main.css"));
+        assert_eq!(
+            structure(&html),
+            vec![
+                "<h1>Digest 2026-10-09 - 2026-10-16</h1>",
+                "<h2>Finished</h2>",
+                "<li>Ship the release (work)</li>",
+                "<h2>Overdue</h2>",
+                "<li>Renew the domain (home, Due 2026-10-10)</li>",
+                "<h2>Due until 2026-10-23</h2>",
+                "<li>Call the vendor (work, Due 2026-10-20)</li>",
+                "<h2>Stalest</h2>",
+                "<h3>home</h3>",
+                "<li>Renew the domain",
+                "<li>Someday idea",
+                "<h3>work</h3>",
+                "<li>Call the vendor",
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_digest_page_says_so() {
+        let time = TimeContext::new(None).with_now(at("2026-10-16T12:00:00Z"));
+        let digest = Digest::new(Entries::default(), &time, chrono::Duration::weeks(1));
+        let html = render_digest(&digest, Translations::default(), false).unwrap();
+
+        assert!(html.contains("/static/css/main.css"));
+        assert_eq!(
+            structure(&html),
+            vec![
+                "<h1>Digest 2026-10-09 - 2026-10-16</h1>",
+                "<h2>Finished</h2>",
+                "<p>Nothing was finished.</p>",
+                "<h2>Overdue</h2>",
+                "<p>Nothing is overdue.</p>",
+                "<h2>Due until 2026-10-23</h2>",
+                "<p>Nothing is due.</p>",
+                "<h2>Stalest</h2>",
+            ]
+        );
+    }
+
+    /// Whether the done entry shows up on the project page for the request.
+    async fn shows_done(app: &tide::Server<WebService>, path: &str, cookie: Option<&str>) -> bool {
+        let mut request = webservice::tests::request(Method::Get, path, None);
+        if let Some(cookie) = cookie {
+            request.insert_header("Cookie", format!("{}={}", preferences::COOKIE_NAME, cookie));
+        }
+
+        let mut response = webservice::tests::respond(app, request).await;
+        assert_eq!(response.status(), StatusCode::Ok);
+
+        response
+            .body_string()
+            .await
+            .unwrap()
+            .contains("Finished entry")
+    }
+
+    fn add_done_entry(store: &Store) {
+        let mut done = entry("Finished entry");
+        done.metadata.finished = Some(at("2026-10-14T09:00:00Z"));
+        store.add_entry(done).unwrap();
+        store.add_entry(entry("Active entry")).unwrap();
+    }
+
+    #[async_std::test]
+    async fn query_overrides_cookie_overrides_config() {
+        let (_directory, store, app) = webservice::tests::app("[web_view]\nshow_done = true");
+        add_done_entry(&store);
+
+        assert!(shows_done(&app, "/project/default", None).await);
+        assert!(!shows_done(&app, "/project/default", Some("default:0::")).await);
+        assert!(shows_done(&app, "/project/default?show_done=true", Some("default:0::")).await);
+        assert!(!shows_done(&app, "/project/default?show_done=false", None).await);
+    }
+
+    #[async_std::test]
+    async fn cookie_of_another_project_is_ignored() {
+        let (_directory, store, app) = webservice::tests::app("");
+        add_done_entry(&store);
+
+        assert!(!shows_done(&app, "/project/default", Some("other:1::")).await);
+        assert!(shows_done(&app, "/project/default", Some("other:0::|default:1::")).await);
+    }
+
+    #[async_std::test]
+    async fn view_update_stores_the_cookie() {
+        let (_directory, store, app) = webservice::tests::app("");
+        add_done_entry(&store);
+
+        let mut request =
+            webservice::tests::request(Method::Get, "/project/default/view?show_done=true", None);
+        request.insert_header(
+            "Cookie",
+            format!("{}=other:0:due:", preferences::COOKIE_NAME),
+        );
+        let response = webservice::tests::respond(&app, request).await;
+
+        assert_eq!(response.status(), StatusCode::SeeOther);
+        assert_eq!(response["Location"], "/project/default");
+
+        // The cookie is sent back like a browser would without its attributes.
+        let set_cookie = response["Set-Cookie"].as_str();
+        let (name, value) = set_cookie
+            .split(';')
+            .next()
+            .and_then(|cookie| cookie.split_once('='))
+            .unwrap();
+        assert_eq!(name, preferences::COOKIE_NAME);
+
+        let cookie = Cookie::parse_encoded(set_cookie.to_string()).unwrap();
+        let views = preferences::parse_cookie(cookie.value());
+        assert_eq!(views.len(), 2);
+        assert!(shows_done(&app, "/project/default", Some(value)).await);
+    }
+
+    #[async_std::test]
+    async fn quick_add_form_escapes_the_text() {
+        let (_directory, store, app) = webservice::tests::app("");
+
+        let request = webservice::tests::request(
+            Method::Get,
+            "/quick-add?project=work&text=%3C%2Ftextarea%3E%3Cscript%3Ealert(%22x%22)%3C%2Fscript%\
+             3E",
+            None,
+        );
+        let mut response = webservice::tests::respond(&app, request).await;
+        let body = response.body_string().await.unwrap();
+
+        assert_eq!(response.status(), StatusCode::Ok);
+        assert!(
+            body.contains("&lt;&#x2F;textarea&gt;&lt;script&gt;alert(&quot;x&quot;)"),
+            "{}",
+            body
+        );
+        assert!(!body.contains("<script>alert"));
+        assert_eq!(store.get_active_entries("work").unwrap().len(), 0);
+    }
+
+    #[async_std::test]
+    async fn board_shows_empty_columns() {
+        let (_directory, store, app) = webservice::tests::app("");
+        store.add_entry(entry("Active entry")).unwrap();
+
+        let request = webservice::tests::request(Method::Get, "/board/default", None);
+        let mut response = webservice::tests::respond(&app, request).await;
+        assert_eq!(response.status(), StatusCode::Ok);
+        let body = response.body_string().await.unwrap();
+
+        let columns = body
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("<h2>")?.strip_suffix("</h2>"))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            columns,
+            vec![
+                "Overdue (0)",
+                "Due soon (0)",
+                "Active (1)",
+                "Waiting (0)",
+                "Done (last 7 days) (0)",
+            ]
+        );
+        assert_eq!(body.matches("Nothing here.").count(), 4);
+    }
+
+    /// Body, content type and attachment file name of the export.
+    async fn export(app: &tide::Server<WebService>, path: &str) -> (String, String, String) {
+        let request = webservice::tests::request(Method::Get, path, None);
+        let mut response = webservice::tests::respond(app, request).await;
+        assert_eq!(response.status(), StatusCode::Ok, "{}", path);
+
+        let filename = response["Content-Disposition"]
+            .as_str()
+            .strip_prefix("attachment; filename=\"")
+            .and_then(|filename| filename.strip_suffix('"'))
+            .unwrap()
+            .to_string();
+        let content_type = response["Content-Type"].as_str().to_string();
+
+        (
+            response.body_string().await.unwrap(),
+            content_type,
+            filename,
+        )
+    }
+
+    /// Headings of the asciidoc document without the delimiters of the
+    /// entry texts.
+    fn headings(asciidoc: &str) -> Vec<&str> {
+        asciidoc
+            .lines()
+            .filter(|line| line.starts_with('=') && line.contains(' '))
+            .collect()
+    }
+
+    #[async_std::test]
+    async fn export_downloads_the_asciidoc_of_the_project() {
+        let (_directory, store, app) = webservice::tests::app("");
+        add_done_entry(&store);
+        store.add_entry(seeded("Other project", "work")).unwrap();
+        let today = app.state().time.today();
+
+        let (asciidoc, content_type, filename) = export(&app, "/project/default/export").await;
+        assert_eq!(content_type, "text/asciidoc; charset=utf-8");
+        assert_eq!(filename, format!("default-{}.adoc", today));
+        assert_eq!(
+            headings(&asciidoc),
+            vec![
+                "= Todos",
+                "== Active",
+                "=== default",
+                "==== Active entry",
+                "== Done",
+                "=== default",
+                "==== Finished entry",
+            ]
+        );
+
+        let (asciidoc, ..) = export(&app, "/project/default/export?done=false").await;
+        assert_eq!(
+            headings(&asciidoc),
+            vec!["= Todos", "== Active", "=== default", "==== Active entry"]
+        );
+    }
+
+    #[async_std::test]
+    async fn export_of_a_project_without_entries_is_an_empty_document() {
+        let (_directory, _store, app) = webservice::tests::app("");
+
+        let (asciidoc, _, filename) = export(&app, "/project/empty/export").await;
+        assert!(filename.starts_with("empty-"));
+        assert_eq!(headings(&asciidoc), vec!["= Todos", "== Active"]);
+        assert!(asciidoc.contains("0 active, 0 overdue, 0 done\n"));
+    }
+
+    #[async_std::test]
+    async fn export_renders_the_asciidoc_to_html() {
+        let (_directory, store, app) = webservice::tests::app("");
+        add_done_entry(&store);
+
+        if std::process::Command::new("asciidoctor")
+            .arg("--version")
+            .output()
+            .is_err()
+        {
+            // Nothing is offered for download when the html can not be
+            // rendered.
+            let request = webservice::tests::request(
+                Method::Get,
+                "/project/default/export?format=html",
+                None,
+            );
+            let response = webservice::tests::respond(&app, request).await;
+            assert_eq!(response.status(), StatusCode::InternalServerError);
+            assert!(response.header("Content-Disposition").is_none());
+
+            return;
+        }
+
+        let (html, content_type, filename) =
+            export(&app, "/project/default/export?format=html&done=false").await;
+        assert_eq!(content_type, "text/html; charset=utf-8");
+        assert_eq!(
+            filename,
+            format!("default-{}.html", app.state().time.today())
+        );
+        assert!(html.contains("Active entry"), "{}", html);
+        assert!(!html.contains("Finished entry"), "{}", html);
+    }
+
+    /// Column headers and projects of the index page in the order they are
+    /// shown.
+    async fn index(app: &tide::Server<WebService>, path: &str) -> (Vec<String>, Vec<String>) {
+        let request = webservice::tests::request(Method::Get, path, None);
+        let mut response = webservice::tests::respond(app, request).await;
+        assert_eq!(response.status(), StatusCode::Ok);
+
+        let body = response.body_string().await.unwrap();
+        let lines = body.lines().map(str::trim).collect::<Vec<_>>();
+
+        let columns = lines
+            .iter()
+            .filter_map(|line| line.strip_prefix("<th>")?.strip_suffix("</th>"))
+            .skip(1)
+            .filter(|column| !column.starts_with("Finished"))
+            .map(str::to_string)
+            .collect();
+        let projects = lines
+            .iter()
+            .filter_map(|line| line.strip_prefix("<a href=\"/project/")?.split('"').next())
+            .map(str::to_string)
+            .collect();
+
+        (columns, projects)
+    }
+
+    fn seed_projects(store: &Store) {
+        for (project, active, done) in &[("a", 2, 1), ("b", 1, 3), ("c", 3, 0)] {
+            for _ in 0..*active {
+                store.add_entry(seeded("active", project)).unwrap();
+            }
+
+            for _ in 0..*done {
+                let mut done = seeded("done", project);
+                done.metadata.finished = Some(at("2026-10-14T09:00:00Z"));
+                store.add_entry(done).unwrap();
+            }
+        }
+    }
+
+    #[async_std::test]
+    async fn index_flags_the_default_project_when_a_project_is_required() {
+        let untriaged = "<tr class=\"untriaged\">";
+
+        for (settings, flagged) in &[("require_explicit_project = true", true), ("", false)] {
+            let (_directory, store, app) = webservice::tests::app(settings);
+            store.add_entry(seeded("inbox", "default")).unwrap();
+            store.add_entry(seeded("active", "work")).unwrap();
+
+            let request = webservice::tests::request(Method::Get, "/", None);
+            let mut response = webservice::tests::respond(&app, request).await;
+            let body = response.body_string().await.unwrap();
+
+            assert_eq!(
+                body.matches(untriaged).count(),
+                *flagged as usize,
+                "{}",
+                body
+            );
+            if *flagged {
+                let row = &body[body.find(untriaged).unwrap()..];
+                assert!(row.contains("<a href=\"/project/default\">"), "{}", row);
+            }
+        }
+
+        // Once the default project is empty it is not flagged anymore.
+        let (_directory, store, app) = webservice::tests::app("require_explicit_project = true");
+        let mut done = seeded("done", "default");
+        done.metadata.finished = Some(at("2026-10-14T09:00:00Z"));
+        store.add_entry(done).unwrap();
+
+        let request = webservice::tests::request(Method::Get, "/", None);
+        let mut response = webservice::tests::respond(&app, request).await;
+        let body = response.body_string().await.unwrap();
+        assert!(!body.contains(untriaged), "{}", body);
+    }
+
+    #[async_std::test]
+    async fn index_shows_the_default_columns() {
+        let (_directory, store, app) = webservice::tests::app("");
+        seed_projects(&store);
+
+        let (columns, projects) = index(&app, "/").await;
+
+        assert_eq!(columns, vec!["Active", "Done", "Total"]);
+        assert_eq!(projects, vec!["a", "b", "c"]);
+    }
+
+    #[async_std::test]
+    async fn index_shows_the_columns_of_the_query() {
+        let (_directory, store, app) = webservice::tests::app("");
+        seed_projects(&store);
+
+        let (columns, _) = index(&app, "/?columns=overdue,bogus,last_activity").await;
+
+        assert_eq!(columns, vec!["Overdue", "Last Activity"]);
+    }
+
+    #[async_std::test]
+    async fn index_sorts_by_the_query() {
+        let (_directory, store, app) = webservice::tests::app("");
+        seed_projects(&store);
+
+        for (sort, expected) in &[
+            ("project", ["a", "b", "c"]),
+            ("active", ["c", "a", "b"]),
+            ("done", ["b", "a", "c"]),
+            ("total", ["b", "a", "c"]),
+            ("bogus", ["a", "b", "c"]),
+        ] {
+            let (_, projects) = index(&app, &format!("/?sort={}", sort)).await;
+
+            assert_eq!(projects, expected, "{}", sort);
+        }
+    }
+
+    #[async_std::test]
+    async fn index_sparklines_cover_the_weeks_of_the_query() {
+        let (_directory, store, app) = webservice::tests::app("");
+        store.add_entry(entry("active")).unwrap();
+
+        // A single week is drawn as a flat line with two points.
+        for (path, points) in &[("/", 12), ("/?weeks=4", 4), ("/?weeks=0", 2)] {
+            let request = webservice::tests::request(Method::Get, path, None);
+            let mut response = webservice::tests::respond(&app, request).await;
+            let body = response.body_string().await.unwrap();
+
+            let drawn = body
+                .split("points=\"")
+                .nth(1)
+                .and_then(|drawn| drawn.split('"').next())
+                .unwrap();
+            assert_eq!(drawn.split(' ').count(), *points, "{}", path);
+        }
+    }
+
+    #[async_std::test]
+    async fn project_pages_link_back_to_the_index() {
+        let (_directory, store, app) = webservice::tests::app("");
+        store.add_entry(seeded("first entry", "work")).unwrap();
+
+        assert_eq!(
+            webservice::tests::navigation(&app, "/project/work").await,
+            (
+                "<title>Todust - work</title>".to_string(),
+                "<nav class=\"breadcrumb\"> <a href=\"/\">Projects</a> &rsaquo; work </nav>"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            webservice::tests::navigation(&app, "/project/add/entry/work").await,
+            (
+                "<title>Todust - work - add entry</title>".to_string(),
+                "<nav class=\"breadcrumb\"> <a href=\"/\">Projects</a> &rsaquo; <a \
+                 href=\"/project/work\">work</a> &rsaquo; add entry </nav>"
+                    .to_string()
+            )
+        );
+    }
+
+    /// Headings, table of contents and sections of the bundle. The rendered
+    /// text is left out as it depends on asciidoctor being installed.
+    fn bundle_structure(html: &str) -> Vec<&str> {
+        html.lines()
+            .map(str::trim)
+            .filter(|line| {
+                ["<h1>", "<h2>", "<h3>", "<li>", "<section", "</section>"]
+                    .iter()
+                    .any(|tag| line.starts_with(tag))
+            })
+            .collect()
+    }
+
+    fn render_bundle() -> (Entry, Entry, String) {
+        let (_directory, store) = temporary();
+
+        let active = seeded("Call the vendor\nabout the invoice", "work");
+        let mut done = seeded("Ship the release", "work");
+        done.metadata.finished = Some(at("2026-10-14T09:00:00Z"));
+        store.add_entry(active.clone()).unwrap();
+        store.add_entry(done.clone()).unwrap();
+
+        let entries = store.get_entries("work").unwrap();
+        let html = render_export_bundle(
+            &store,
+            "work",
+            &entries,
+            NaiveDate::from_ymd_opt(2026, 10, 16).unwrap(),
+            Translations::default(),
+        )
+        .unwrap();
+
+        (active, done, html)
+    }
+
+    #[test]
+    fn bundle_has_contents_and_a_section_per_entry() {
+        let (active, done, html) = render_bundle();
+        let (active, done) = (active.metadata.uuid, done.metadata.uuid);
+
+        assert_eq!(
+            bundle_structure(&html),
+            vec![
+                "<h1>work</h1>".to_string(),
+                "<h2>Contents</h2>".to_string(),
+                "<h3>Active</h3>".to_string(),
+                format!(
+                    "<li><a href=\"#{}\">Call the vendor about the invoice</a></li>",
+                    active
+                ),
+                "<h3>Done</h3>".to_string(),
+                format!("<li><a href=\"#{}\">Ship the release</a></li>", done),
+                format!("<section id=\"{}\">", active),
+                "<h2>Call the vendor about the invoice</h2>".to_string(),
+                "</section>".to_string(),
+                format!("<section id=\"{}\">", done),
+                "<h2>Ship the release</h2>".to_string(),
+                "</section>".to_string(),
+            ]
+        );
+        assert!(html.contains("about the invoice"));
+    }
+
+    #[test]
+    fn bundle_loads_nothing_from_elsewhere() {
+        let (_, _, html) = render_bundle();
+
+        assert!(html.contains("<style>"));
+        for reference in ["http://", "https://", "<link", "<script", "src=", "@import"] {
+            assert!(!html.contains(reference), "{} in\n{}", reference, html);
+        }
+    }
+}
@@ -0,0 +1,292 @@
+use crate::{
+    entry::Entry,
+    helper,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::BTreeMap,
+    str::FromStr,
+};
+
+/// Name of the cookie that stores the view preferences per project.
+pub(super) const COOKIE_NAME: &str = "todust_view";
+
+/// Separates the projects in the cookie value.
+const PROJECT_SEPARATOR: char = '|';
+
+/// Separates the fields of a project in the cookie value.
+const FIELD_SEPARATOR: char = ':';
+
+/// How the entries of a project are sorted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum SortOrder {
+    /// Least recently changed entries first.
+    #[default]
+    Changed,
+
+    /// Oldest entries first.
+    Started,
+
+    /// Entries with the nearest due date first. Entries without a due date
+    /// come last.
+    Due,
+}
+
+impl FromStr for SortOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "changed" => Ok(SortOrder::Changed),
+            "started" => Ok(SortOrder::Started),
+            "due" => Ok(SortOrder::Due),
+            _ => Err(format!("unknown sort order {}", s)),
+        }
+    }
+}
+
+impl SortOrder {
+    /// Sort the entries in this order. Entries are expected to be in the
+    /// default order of the store which is the changed order.
+    pub(super) fn sort(&self, entries: &mut [Entry]) {
+        match self {
+            SortOrder::Changed => {}
+            SortOrder::Started => entries.sort_by_key(|entry| entry.metadata.started),
+            SortOrder::Due => {
+                entries.sort_by_key(|entry| (entry.metadata.due.is_none(), entry.metadata.due))
+            }
+        }
+    }
+
+    fn as_str(&self) -> &'static str {
+        match self {
+            SortOrder::Changed => "changed",
+            SortOrder::Started => "started",
+            SortOrder::Due => "due",
+        }
+    }
+}
+
+/// Defaults for how projects are shown in the web ui. Can be overwritten per
+/// project with a cookie or per request with query parameters.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub(crate) struct ViewPreferences {
+    #[serde(default)]
+    pub(super) show_done: bool,
+
+    #[serde(default)]
+    pub(super) sort: SortOrder,
+
+    /// How many entries are shown per page. All entries are shown when not
+    /// set.
+    #[serde(default)]
+    pub(super) page_size: Option<usize>,
+}
+
+/// Preferences that overwrite the defaults. Fields that are not set fall back
+/// to the next source.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub(super) struct ViewOverrides {
+    pub(super) show_done: Option<bool>,
+    pub(super) sort: Option<SortOrder>,
+    pub(super) page_size: Option<usize>,
+}
+
+impl ViewOverrides {
+    /// Overwrite the fields of `self` that are set in `other`.
+    pub(super) fn merge(&mut self, other: &ViewOverrides) {
+        if other.show_done.is_some() {
+            self.show_done = other.show_done;
+        }
+
+        if other.sort.is_some() {
+            self.sort = other.sort;
+        }
+
+        if other.page_size.is_some() {
+            self.page_size = other.page_size;
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == ViewOverrides::default()
+    }
+}
+
+/// Resolve the preferences with the precedence query > cookie > defaults.
+pub(super) fn resolve(
+    query: &ViewOverrides,
+    cookie: Option<&ViewOverrides>,
+    defaults: &ViewPreferences,
+) -> ViewPreferences {
+    let mut overrides = cookie.cloned().unwrap_or_default();
+    overrides.merge(query);
+
+    ViewPreferences {
+        show_done: overrides.show_done.unwrap_or(defaults.show_done),
+        sort: overrides.sort.unwrap_or(defaults.sort),
+        page_size: match overrides.page_size {
+            // A page size of 0 in a query or cookie means showing all entries.
+            Some(0) => None,
+            Some(page_size) => Some(page_size),
+            None => defaults.page_size,
+        },
+    }
+}
+
+/// Parse the cookie value into the overrides per project. Malformed parts are
+/// skipped so a broken cookie never breaks the page.
+pub(super) fn parse_cookie(value: &str) -> BTreeMap<String, ViewOverrides> {
+    value
+        .split(PROJECT_SEPARATOR)
+        .filter_map(|part| {
+            let mut fields = part.split(FIELD_SEPARATOR);
+
+            let project = helper::percent_decode(fields.next()?)?;
+            let show_done = match fields.next()? {
+                "1" => Some(true),
+                "0" => Some(false),
+                _ => None,
+            };
+            let sort = fields.next()?.parse().ok();
+            let page_size = fields.next()?.parse().ok();
+
+            Some((
+                project,
+                ViewOverrides {
+                    show_done,
+                    sort,
+                    page_size,
+                },
+            ))
+        })
+        .collect()
+}
+
+/// Serialize the overrides per project into a cookie value.
+pub(super) fn serialize_cookie(views: &BTreeMap<String, ViewOverrides>) -> String {
+    views
+        .iter()
+        .filter(|(_, view)| !view.is_empty())
+        .map(|(project, view)| {
+            let show_done = match view.show_done {
+                Some(true) => "1",
+                Some(false) => "0",
+                None => "",
+            };
+            let sort = view.sort.map(|sort| sort.as_str()).unwrap_or("");
+            let page_size = view
+                .page_size
+                .map(|page_size| page_size.to_string())
+                .unwrap_or_default();
+
+            format!(
+                "{}{sep}{}{sep}{}{sep}{}",
+                encode(project),
+                show_done,
+                sort,
+                page_size,
+                sep = FIELD_SEPARATOR
+            )
+        })
+        .collect::<Vec<_>>()
+        .join(&PROJECT_SEPARATOR.to_string())
+}
+
+/// Percent encode everything except alphanumeric characters, `-`, `_` and `.`
+/// so the project name can not contain the separators or characters that are
+/// not allowed in cookies.
+fn encode(input: &str) -> String {
+    input
+        .bytes()
+        .map(|byte| match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn overrides(
+        show_done: Option<bool>,
+        sort: Option<SortOrder>,
+        page_size: Option<usize>,
+    ) -> ViewOverrides {
+        ViewOverrides {
+            show_done,
+            sort,
+            page_size,
+        }
+    }
+
+    #[test]
+    fn cookie_round_trip() {
+        let mut views = BTreeMap::new();
+        views.insert(
+            "work".to_string(),
+            overrides(Some(true), Some(SortOrder::Due), Some(20)),
+        );
+        views.insert("a|b:c".to_string(), overrides(Some(false), None, None));
+        views.insert("empty".to_string(), ViewOverrides::default());
+
+        let cookie = serialize_cookie(&views);
+        views.remove("empty");
+
+        assert_eq!(cookie, "a%7Cb%3Ac:0::|work:1:due:20");
+        assert_eq!(parse_cookie(&cookie), views);
+    }
+
+    #[test]
+    fn malformed_cookie_parts_are_skipped() {
+        let views = parse_cookie("work:1:due:20|broken|other:x:unknown:many|%ZZ:1::");
+
+        assert_eq!(views.len(), 2);
+        assert_eq!(
+            views["work"],
+            overrides(Some(true), Some(SortOrder::Due), Some(20))
+        );
+        assert_eq!(views["other"], ViewOverrides::default());
+        assert!(parse_cookie("").is_empty());
+    }
+
+    #[test]
+    fn query_beats_cookie_beats_defaults() {
+        let defaults = ViewPreferences {
+            show_done: true,
+            sort: SortOrder::Started,
+            page_size: Some(50),
+        };
+        let cookie = overrides(Some(false), Some(SortOrder::Due), None);
+        let query = overrides(None, Some(SortOrder::Changed), Some(0));
+
+        assert_eq!(
+            resolve(&ViewOverrides::default(), None, &defaults),
+            defaults
+        );
+        assert_eq!(
+            resolve(&ViewOverrides::default(), Some(&cookie), &defaults),
+            ViewPreferences {
+                show_done: false,
+                sort: SortOrder::Due,
+                page_size: Some(50),
+            }
+        );
+        assert_eq!(
+            resolve(&query, Some(&cookie), &defaults),
+            ViewPreferences {
+                show_done: false,
+                sort: SortOrder::Changed,
+                page_size: None,
+            }
+        );
+    }
+}
@@ -0,0 +1,320 @@
+use super::{
+    auth,
+    WebService,
+};
+use log::warn;
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use std::{
+    collections::HashMap,
+    future::Future,
+    pin::Pin,
+    sync::{
+        Arc,
+        Mutex,
+    },
+    time::{
+        Duration,
+        Instant,
+    },
+};
+use tide::{
+    Next,
+    Request,
+    Response,
+    StatusCode,
+};
+
+/// Requests per minute a client can send to the mutating endpoints by default.
+const DEFAULT_REQUESTS_PER_MINUTE: u32 = 60;
+
+/// Buckets that were not used for this long are full again and are dropped
+/// so the map does not grow with every client that ever sent a request.
+const IDLE_BUCKET_CLEANUP: Duration = Duration::from_secs(10 * 60);
+
+/// Limits for requests that change entries.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) struct RateLimitConfig {
+    /// Requests per minute each client can send to the endpoints that change
+    /// entries. Clients are told apart by their token or, without tokens,
+    /// by their ip address. Set to 0 to disable the limit.
+    #[serde(default = "default_requests_per_minute")]
+    pub(super) requests_per_minute: u32,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            requests_per_minute: default_requests_per_minute(),
+        }
+    }
+}
+
+fn default_requests_per_minute() -> u32 {
+    DEFAULT_REQUESTS_PER_MINUTE
+}
+
+/// Token bucket per client. Every client can send a burst of
+/// `requests_per_minute` requests and gets the tokens back evenly over a
+/// minute.
+#[derive(Debug, Clone)]
+pub(super) struct RateLimiter {
+    requests_per_minute: u32,
+    buckets: Arc<Mutex<HashMap<String, Bucket>>>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl RateLimiter {
+    pub(super) fn new(config: &RateLimitConfig) -> Self {
+        Self {
+            requests_per_minute: config.requests_per_minute,
+            buckets: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Take a token from the bucket of the client. Returns how long the
+    /// client has to wait for the next token if the bucket is empty.
+    pub(super) fn check(&self, client: &str, now: Instant) -> Result<(), Duration> {
+        if self.requests_per_minute == 0 {
+            return Ok(());
+        }
+
+        let capacity = f64::from(self.requests_per_minute);
+        let tokens_per_second = capacity / 60.0;
+
+        let mut buckets = self.buckets.lock().expect("rate limiter lock is poisoned");
+
+        buckets.retain(|_, bucket| {
+            now.saturating_duration_since(bucket.updated) < IDLE_BUCKET_CLEANUP
+        });
+
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket {
+            tokens: capacity,
+            updated: now,
+        });
+
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * tokens_per_second).min(capacity);
+        bucket.updated = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / tokens_per_second,
+            ))
+        }
+    }
+}
+
+/// Route middleware that limits how many requests each client can send to
+/// the endpoints that change entries. It is added to those routes no matter
+/// their method as some of them are GET requests. Has to run after the auth
+/// middleware as clients with a token are told apart by the token. It is
+/// added after the access check of the route so requests that are refused
+/// anyway do not use up the requests of the client.
+pub(super) fn middleware<'a>(
+    request: Request<WebService>,
+    next: Next<'a, WebService>,
+) -> Pin<Box<dyn Future<Output = tide::Result> + Send + 'a>> {
+    Box::pin(async move {
+        let client = client_key(&request);
+
+        if let Err(retry_after) = request.state().rate_limiter.check(&client, Instant::now()) {
+            match auth::scope(&request).name() {
+                Some(name) => warn!("rate limit exceeded by token {}", name),
+                None => warn!("rate limit exceeded by {}", client),
+            }
+            return Ok(too_many_requests(retry_after));
+        }
+
+        Ok(next.run(request).await)
+    })
+}
+
+/// The token the request was sent with or the ip address of the client.
+/// Tokens are told apart by their position as names do not have to be
+/// unique.
+fn client_key(request: &Request<WebService>) -> String {
+    if let Some(token) = auth::scope(request).token() {
+        return format!("token:{}", token);
+    }
+
+    let peer = request.peer_addr().unwrap_or("-");
+
+    // Strip the port so every connection of the same host shares a bucket.
+    let ip = match peer.parse::<std::net::SocketAddr>() {
+        Ok(address) => address.ip().to_string(),
+        Err(_) => peer.to_string(),
+    };
+
+    format!("ip:{}", ip)
+}
+
+fn too_many_requests(retry_after: Duration) -> Response {
+    // Round up so clients that wait the given seconds get a token.
+    let seconds = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+
+    Response::builder(StatusCode::TooManyRequests)
+        .header("Content-Type", "text/plain")
+        .header("Retry-After", seconds.to_string())
+        .body(format!(
+            "429 - too many requests, try again in {} seconds",
+            seconds
+        ))
+        .build()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webservice::tests::{
+        app,
+        request,
+        respond,
+    };
+    use http_types::Method;
+
+    fn limiter(requests_per_minute: u32) -> RateLimiter {
+        RateLimiter::new(&RateLimitConfig {
+            requests_per_minute,
+        })
+    }
+
+    #[test]
+    fn burst_is_limited_to_the_requests_per_minute() {
+        let limiter = limiter(3);
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert_eq!(limiter.check("ip:127.0.0.1", now), Ok(()));
+        }
+
+        // One token comes back every 20 seconds.
+        assert_eq!(
+            limiter.check("ip:127.0.0.1", now),
+            Err(Duration::from_secs(20))
+        );
+    }
+
+    #[test]
+    fn tokens_come_back_over_time() {
+        let limiter = limiter(60);
+        let now = Instant::now();
+
+        for _ in 0..60 {
+            assert_eq!(limiter.check("ip:127.0.0.1", now), Ok(()));
+        }
+        assert!(limiter.check("ip:127.0.0.1", now).is_err());
+
+        // One token per second, so half a second is not enough.
+        let later = now + Duration::from_millis(500);
+        assert_eq!(
+            limiter.check("ip:127.0.0.1", later),
+            Err(Duration::from_millis(500))
+        );
+
+        let later = now + Duration::from_secs(2);
+        assert_eq!(limiter.check("ip:127.0.0.1", later), Ok(()));
+        assert_eq!(limiter.check("ip:127.0.0.1", later), Ok(()));
+        assert!(limiter.check("ip:127.0.0.1", later).is_err());
+
+        // The bucket never holds more than the burst.
+        let much_later = later + Duration::from_secs(5 * 60);
+        for _ in 0..60 {
+            assert_eq!(limiter.check("ip:127.0.0.1", much_later), Ok(()));
+        }
+        assert!(limiter.check("ip:127.0.0.1", much_later).is_err());
+    }
+
+    #[test]
+    fn clients_have_their_own_buckets() {
+        let limiter = limiter(1);
+        let now = Instant::now();
+
+        assert_eq!(limiter.check("token:0", now), Ok(()));
+        assert!(limiter.check("token:0", now).is_err());
+
+        assert_eq!(limiter.check("token:1", now), Ok(()));
+        assert_eq!(limiter.check("ip:127.0.0.1", now), Ok(()));
+        assert!(limiter.check("token:0", now).is_err());
+    }
+
+    #[test]
+    fn zero_disables_the_limit() {
+        let limiter = limiter(0);
+        let now = Instant::now();
+
+        for _ in 0..1000 {
+            assert_eq!(limiter.check("ip:127.0.0.1", now), Ok(()));
+        }
+    }
+
+    #[async_std::test]
+    async fn tokens_with_the_same_name_are_limited_separately() {
+        let (_directory, _store, app) = app(r#"
+            [web_rate_limit]
+            requests_per_minute = 1
+
+            [[web_tokens]]
+            name = "script"
+            token = "first"
+            access = ["read", "write"]
+
+            [[web_tokens]]
+            name = "script"
+            token = "second"
+            access = ["read", "write"]
+            "#);
+
+        let add = |token| {
+            let mut request = request(Method::Post, "/api/v1/quick-add?project=work", Some(token));
+            request.set_body("entry");
+            request
+        };
+
+        let response = respond(&app, add("first")).await;
+        assert!(response.status().is_success(), "{}", response.status());
+
+        let mut response = respond(&app, add("first")).await;
+        assert_eq!(response.status(), StatusCode::TooManyRequests);
+        assert_eq!(response.header("Retry-After").unwrap().as_str(), "60");
+        assert_eq!(
+            response.body_string().await.unwrap(),
+            "429 - too many requests, try again in 60 seconds"
+        );
+
+        let response = respond(&app, add("second")).await;
+        assert!(response.status().is_success(), "{}", response.status());
+    }
+
+    #[async_std::test]
+    async fn refused_requests_do_not_use_up_the_limit() {
+        let (_directory, _store, app) = app(r#"
+            [web_rate_limit]
+            requests_per_minute = 1
+
+            [[web_tokens]]
+            name = "reader"
+            token = "reader"
+            access = ["read"]
+            "#);
+
+        for _ in 0..3 {
+            let response = respond(
+                &app,
+                request(Method::Post, "/api/v1/quick-add", Some("reader")),
+            )
+            .await;
+            assert_eq!(response.status(), StatusCode::Forbidden);
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use chrono::{
+    DateTime,
+    SecondsFormat,
+    Utc,
+};
+use uuid::Uuid;
+
+/// Render a sitemap with the page of every given entry. `base_url` is the
+/// url the webservice is reachable under without a trailing slash.
+pub(super) fn sitemap<I>(base_url: &str, entries: I) -> String
+where
+    I: IntoIterator<Item = (Uuid, DateTime<Utc>)>,
+{
+    let mut out = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+
+    for (uuid, last_change) in entries {
+        out.push_str(&format!(
+            "  <url>\n    <loc>{}/entry/{}</loc>\n    <lastmod>{}</lastmod>\n  </url>\n",
+            escape(base_url),
+            uuid,
+            last_change.to_rfc3339_opts(SecondsFormat::Secs, true)
+        ));
+    }
+
+    out.push_str("</urlset>\n");
+
+    out
+}
+
+/// Render the robots.txt. Crawlers are only allowed on instances that are
+/// marked as public so an instance that is exposed by accident is not
+/// indexed.
+pub(super) fn robots(public: bool, base_url: &str) -> String {
+    if !public {
+        return "User-agent: *\nDisallow: /\n".to_string();
+    }
+
+    format!(
+        "User-agent: *\nAllow: /\n\nSitemap: {}/sitemap.xml\n",
+        base_url
+    )
+}
+
+fn escape(input: &str) -> String {
+    input
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sitemap_lists_every_entry() {
+        let first = Uuid::parse_str("6b3f3f0e-6b0a-4a4e-9c4a-1f0e0c3a2b10").unwrap();
+        let second = Uuid::parse_str("0f8d0a52-3c1e-4f7a-8e0b-7d3b5e9c4a21").unwrap();
+
+        let sitemap = sitemap(
+            "https://todo.example.com/todust",
+            vec![
+                (first, "2026-10-16T08:30:15.123456Z".parse().unwrap()),
+                (second, "2026-01-02T00:00:00Z".parse().unwrap()),
+            ],
+        );
+
+        assert_eq!(
+            sitemap,
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n\
+             \x20 <url>\n\
+             \x20   <loc>https://todo.example.com/todust/entry/6b3f3f0e-6b0a-4a4e-9c4a-1f0e0c3a2b10</loc>\n\
+             \x20   <lastmod>2026-10-16T08:30:15Z</lastmod>\n\
+             \x20 </url>\n\
+             \x20 <url>\n\
+             \x20   <loc>https://todo.example.com/todust/entry/0f8d0a52-3c1e-4f7a-8e0b-7d3b5e9c4a21</loc>\n\
+             \x20   <lastmod>2026-01-02T00:00:00Z</lastmod>\n\
+             \x20 </url>\n\
+             </urlset>\n"
+        );
+    }
+
+    #[test]
+    fn empty_sitemap_is_valid() {
+        assert_eq!(
+            sitemap("http://localhost", Vec::new()),
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n\
+             </urlset>\n"
+        );
+    }
+
+    #[test]
+    fn base_url_is_escaped() {
+        let sitemap = sitemap(
+            "http://localhost/a&b",
+            vec![(Uuid::nil(), "2026-10-16T00:00:00Z".parse().unwrap())],
+        );
+
+        assert!(sitemap.contains("<loc>http://localhost/a&amp;b/entry/"));
+    }
+
+    #[test]
+    fn robots_only_allows_public_instances() {
+        assert_eq!(
+            robots(false, "http://localhost"),
+            "User-agent: *\nDisallow: /\n"
+        );
+        assert_eq!(
+            robots(true, "https://todo.example.com"),
+            "User-agent: *\nAllow: /\n\nSitemap: https://todo.example.com/sitemap.xml\n"
+        );
+    }
+}
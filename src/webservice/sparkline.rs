@@ -0,0 +1,80 @@
+/// Width of the sparkline in pixels.
+const WIDTH: usize = 100;
+
+/// Height of the sparkline in pixels.
+const HEIGHT: usize = 20;
+
+/// Space above and below the line so the stroke is not cut off.
+const PADDING: f64 = 1.0;
+
+/// Render the values as an inline svg line chart. The highest value is drawn
+/// at the top and zero at the bottom of the chart.
+pub(super) fn sparkline(values: &[usize]) -> String {
+    format!(
+        r#"<svg class="sparkline" width="{width}" height="{height}" viewBox="0 0 {width} {height}" xmlns="http://www.w3.org/2000/svg"><polyline fill="none" stroke="currentColor" stroke-width="1" points="{points}"/></svg>"#,
+        width = WIDTH,
+        height = HEIGHT,
+        points = points(values),
+    )
+}
+
+/// Coordinates of the polyline. A single value is drawn as a flat line over
+/// the whole width.
+fn points(values: &[usize]) -> String {
+    let max = values.iter().copied().max().unwrap_or_default();
+
+    let range = HEIGHT as f64 - 2.0 * PADDING;
+    let y = |value: usize| match max {
+        0 => PADDING + range,
+        _ => PADDING + range - (value as f64 / max as f64) * range,
+    };
+
+    match values {
+        [] => String::new(),
+        [value] => format!("0.0,{:.1} {}.0,{:.1}", y(*value), WIDTH, y(*value)),
+        _ => {
+            let step = WIDTH as f64 / (values.len() - 1) as f64;
+
+            values
+                .iter()
+                .enumerate()
+                .map(|(index, value)| format!("{:.1},{:.1}", index as f64 * step, y(*value)))
+                .collect::<Vec<_>>()
+                .join(" ")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn highest_value_is_at_the_top() {
+        assert_eq!(points(&[0, 2, 4]), "0.0,19.0 50.0,10.0 100.0,1.0");
+        assert_eq!(
+            points(&[3, 0, 0, 1]),
+            "0.0,1.0 33.3,19.0 66.7,19.0 100.0,13.0"
+        );
+    }
+
+    #[test]
+    fn zeros_are_drawn_at_the_bottom() {
+        assert_eq!(points(&[0, 0]), "0.0,19.0 100.0,19.0");
+    }
+
+    #[test]
+    fn single_value_is_a_flat_line() {
+        assert_eq!(points(&[3]), "0.0,1.0 100.0,1.0");
+        assert_eq!(points(&[]), "");
+    }
+
+    #[test]
+    fn sparkline_is_an_inline_svg() {
+        let svg = sparkline(&[0, 1]);
+
+        assert!(svg.starts_with(r#"<svg class="sparkline" width="100" height="20""#));
+        assert!(svg.contains(r#"points="0.0,19.0 100.0,1.0""#));
+        assert!(svg.ends_with("</svg>"));
+    }
+}
@@ -0,0 +1,109 @@
+use anyhow::{
+    bail,
+    Error,
+};
+use serde::{
+    Deserialize,
+    Serialize,
+};
+use serde_json::Value;
+use tera::{
+    Context,
+    Tera,
+};
+
+/// Name of the webhook that is used when the request does not name one.
+pub(super) const DEFAULT_WEBHOOK: &str = "default";
+
+/// Field of the payload that is used to avoid creating duplicate entries.
+pub(super) const DEDUP_KEY_FIELD: &str = "dedup_key";
+
+/// How often rendering is retried after filling in a missing variable.
+const MAX_MISSING_VARIABLES: usize = 64;
+
+/// Webhook that turns a json payload into the text of a new entry.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct InboundWebhook {
+    /// Name of the webhook that is selected with the `name` query parameter.
+    pub(super) name: String,
+
+    /// Tera template that gets the fields of the payload as variables.
+    pub(super) template: String,
+}
+
+impl InboundWebhook {
+    /// Render the entry text from the payload. Fields that are used in the
+    /// template but missing in the payload are rendered as empty strings.
+    pub(super) fn render(&self, payload: &Value) -> Result<String, Error> {
+        let mut payload = match payload {
+            Value::Object(_) => payload.clone(),
+            _ => bail!("webhook payload has to be a json object"),
+        };
+
+        let mut tera = Tera::default();
+        tera.add_raw_template(&self.name, &self.template)?;
+
+        for _ in 0..MAX_MISSING_VARIABLES {
+            let context = Context::from_value(payload.clone())?;
+
+            let err = match tera.render(&self.name, &context) {
+                Ok(rendered) => return Ok(rendered),
+                Err(err) => err,
+            };
+
+            match missing_variable(&err) {
+                Some(variable) if insert_blank(&mut payload, &variable) => continue,
+                _ => return Err(err.into()),
+            }
+        }
+
+        bail!("too many missing fields in the webhook payload")
+    }
+}
+
+/// Extract the name of the variable from a tera "not found in context" error.
+fn missing_variable(err: &tera::Error) -> Option<String> {
+    let mut source: Option<&dyn std::error::Error> = Some(err);
+
+    while let Some(err) = source {
+        let message = err.to_string();
+
+        if message.contains("not found in context") {
+            return message.split('`').nth(1).map(str::to_string);
+        }
+
+        source = err.source();
+    }
+
+    None
+}
+
+/// Insert an empty string at the dotted path in the payload. Returns false if
+/// the path runs into a value that is not an object so retrying would not
+/// help.
+fn insert_blank(payload: &mut Value, path: &str) -> bool {
+    let mut current = payload;
+    let mut parts = path.split('.').peekable();
+
+    while let Some(part) = parts.next() {
+        let object = match current {
+            Value::Object(object) => object,
+            _ => return false,
+        };
+
+        if parts.peek().is_none() {
+            if object.contains_key(part) {
+                return false;
+            }
+
+            object.insert(part.to_string(), Value::String(String::new()));
+            return true;
+        }
+
+        current = object
+            .entry(part.to_string())
+            .or_insert_with(|| Value::Object(Default::default()));
+    }
+
+    false
+}
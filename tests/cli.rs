@@ -0,0 +1,295 @@
+use assert_cmd::Command;
+use std::path::Path;
+
+/// Run todust on the datadir with stdin and stdout connected to pipes like in
+/// a script. The home and state directories point into the temporary
+/// directory so the user configuration is not used.
+fn todust(directory: &Path, args: &[&str]) -> (String, String) {
+    todust_with_env(directory, &[], args)
+}
+
+/// Like `todust` with additional environment variables.
+fn todust_with_env(directory: &Path, env: &[(&str, &Path)], args: &[&str]) -> (String, String) {
+    let output = Command::cargo_bin("todust")
+        .unwrap()
+        .env_clear()
+        .env("PATH", std::env::var_os("PATH").unwrap_or_default())
+        .env("HOME", directory)
+        .env("XDG_CONFIG_HOME", directory.join("config"))
+        .env("XDG_STATE_HOME", directory.join("state"))
+        .env("TODUST_DATADIR", directory.join("datadir"))
+        .env("GIT_AUTHOR_NAME", "todust")
+        .env("GIT_AUTHOR_EMAIL", "todust@example.com")
+        .env("GIT_COMMITTER_NAME", "todust")
+        .env("GIT_COMMITTER_EMAIL", "todust@example.com")
+        // A pager would swallow the output so it must not be started.
+        .env("PAGER", "false")
+        .envs(env.iter().copied())
+        .args(args)
+        .write_stdin("")
+        .output()
+        .unwrap();
+
+    (
+        String::from_utf8(output.stdout).unwrap(),
+        String::from_utf8(output.stderr).unwrap(),
+    )
+}
+
+#[test]
+fn done_without_yes_fails_instead_of_waiting_for_an_answer() {
+    let directory = tempfile::tempdir().unwrap();
+    todust(directory.path(), &["add", "first entry"]);
+
+    let (_, stderr) = todust(directory.path(), &["done", "1"]);
+    assert!(
+        stderr.contains("stdin is not a terminal, pass --yes to skip it"),
+        "{}",
+        stderr
+    );
+
+    let (stdout, _) = todust(directory.path(), &["list"]);
+    assert!(stdout.contains("first entry"), "{}", stdout);
+
+    todust(directory.path(), &["done", "--yes", "1"]);
+    let (stdout, _) = todust(directory.path(), &["list"]);
+    assert_eq!(stdout, "no active todos\n");
+}
+
+#[test]
+fn list_in_a_pipe_has_no_colors_and_no_pager() {
+    let directory = tempfile::tempdir().unwrap();
+    todust(directory.path(), &["add", "overdue entry"]);
+    todust(directory.path(), &["due", "1", "2000-01-01"]);
+
+    let (stdout, stderr) = todust(directory.path(), &["list"]);
+    assert!(stdout.contains("overdue entry"), "{}{}", stdout, stderr);
+    assert!(!stdout.contains('\u{1b}'), "{:?}", stdout);
+}
+
+#[cfg(unix)]
+#[test]
+fn edit_is_not_saved_without_confirmation() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let directory = tempfile::tempdir().unwrap();
+    todust(directory.path(), &["add", "original entry"]);
+
+    let editor = directory.path().join("editor");
+    std::fs::write(&editor, "#!/bin/sh\nprintf 'changed entry\\n' > \"$1\"\n").unwrap();
+    std::fs::set_permissions(&editor, std::fs::Permissions::from_mode(0o755)).unwrap();
+    let env = [("EDITOR", editor.as_path())];
+
+    let (stdout, stderr) = todust_with_env(directory.path(), &env, &["edit", "1"]);
+    assert_eq!(
+        stdout,
+        "--- before\n+++ after\n@@ -1 +1 @@\n-original entry\n\\ No newline at end of \
+         file\n+changed entry\n"
+    );
+    assert!(stderr.contains("pass --yes to save them"), "{}", stderr);
+
+    let (stdout, _) = todust(directory.path(), &["list"]);
+    assert!(stdout.contains("original entry"), "{}", stdout);
+
+    todust_with_env(directory.path(), &env, &["edit", "--yes", "1"]);
+    let (stdout, _) = todust(directory.path(), &["list"]);
+    assert!(stdout.contains("changed entry"), "{}", stdout);
+}
+
+/// Add the datadir `personal` in the temporary directory to the stores table
+/// of the config that todust wrote on the first run.
+fn add_personal_store(directory: &Path) -> std::path::PathBuf {
+    let personal = directory.join("personal");
+    let config = directory.join("config").join("todust").join("config.toml");
+    let mut text = std::fs::read_to_string(&config).unwrap();
+    text.push_str(&format!("\n[stores]\npersonal = {:?}\n", personal));
+    std::fs::write(&config, text).unwrap();
+
+    personal
+}
+
+fn git_log(datadir: &Path) -> String {
+    let output = std::process::Command::new("git")
+        .arg("-C")
+        .arg(datadir)
+        .args(["log", "--format=%s"])
+        .output()
+        .unwrap();
+
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn move_to_store_adds_the_entry_there_and_removes_it_here() {
+    let directory = tempfile::tempdir().unwrap();
+    todust(directory.path(), &["add", "personal entry"]);
+    todust(directory.path(), &["add", "work entry"]);
+    let personal = add_personal_store(directory.path());
+    let personal_arg = personal.to_str().unwrap();
+
+    let (stdout, stderr) = todust(
+        directory.path(),
+        &["move", "1", "inbox", "--to_store", "personal", "--yes"],
+    );
+    assert!(stdout.contains("personal: inbox"), "{}{}", stdout, stderr);
+
+    let (stdout, _) = todust(directory.path(), &["list"]);
+    assert!(!stdout.contains("personal entry"), "{}", stdout);
+    assert!(stdout.contains("work entry"), "{}", stdout);
+
+    let (stdout, _) = todust(
+        directory.path(),
+        &["list", "--datadir", personal_arg, "--project", "inbox"],
+    );
+    assert!(stdout.contains("personal entry"), "{}", stdout);
+    assert!(!stdout.contains("work entry"), "{}", stdout);
+
+    assert!(git_log(&personal).starts_with("added entry with id"));
+    let log = git_log(&directory.path().join("datadir"));
+    assert!(log.starts_with("moved entry with id"), "{}", log);
+    assert!(log.lines().next().unwrap().ends_with("to store personal"));
+}
+
+#[cfg(unix)]
+#[test]
+fn move_to_store_that_has_the_entry_only_removes_it_here() {
+    let directory = tempfile::tempdir().unwrap();
+    todust(directory.path(), &["add", "personal entry"]);
+    let personal = add_personal_store(directory.path());
+
+    // The entry was added to the other store before a move failed to remove
+    // it here.
+    let copied = std::process::Command::new("cp")
+        .arg("-r")
+        .arg(directory.path().join("datadir"))
+        .arg(&personal)
+        .status()
+        .unwrap();
+    assert!(copied.success());
+
+    let (stdout, stderr) = todust(
+        directory.path(),
+        &["move", "1", "inbox", "--to_store", "personal", "--yes"],
+    );
+    assert!(
+        stdout.contains("is in store personal already, only removing it from this store"),
+        "{}{}",
+        stdout,
+        stderr
+    );
+
+    let (stdout, _) = todust(directory.path(), &["list"]);
+    assert_eq!(stdout, "no active todos\n");
+
+    let (stdout, _) = todust(
+        directory.path(),
+        &["list", "--datadir", personal.to_str().unwrap()],
+    );
+    assert!(stdout.contains("personal entry"), "{}", stdout);
+    let log = git_log(&personal);
+    assert_eq!(log.lines().count(), 1, "{}", log);
+}
+
+#[test]
+fn add_over_a_quota_names_the_quota() {
+    let directory = tempfile::tempdir().unwrap();
+    todust(directory.path(), &["add", "first entry"]);
+
+    let config = directory
+        .path()
+        .join("config")
+        .join("todust")
+        .join("config.toml");
+    let text = std::fs::read_to_string(&config).unwrap();
+    assert!(text.contains("\n[quotas]\n"), "{}", text);
+    let text = text.replace("\n[quotas]\n", "\n[quotas]\nmax_total_entries = 1\n");
+    std::fs::write(&config, text).unwrap();
+
+    let (stdout, stderr) = todust(directory.path(), &["add", "second entry"]);
+    assert!(
+        stderr.contains("can not add more entries as the store is limited to 1 entries"),
+        "{}{}",
+        stdout,
+        stderr
+    );
+
+    let (stdout, _) = todust(directory.path(), &["list"]);
+    assert!(stdout.contains("first entry"), "{}", stdout);
+    assert!(!stdout.contains("second entry"), "{}", stdout);
+}
+
+#[cfg(unix)]
+#[test]
+fn switched_project_is_below_the_flag_and_the_environment() {
+    let directory = tempfile::tempdir().unwrap();
+    todust(directory.path(), &["add", "first entry"]);
+
+    let (stdout, _) = todust(directory.path(), &["switch", "work"]);
+    assert_eq!(stdout, "this terminal session uses project work now\n");
+
+    let (stdout, _) = todust(directory.path(), &["add", "--yes", "switched entry"]);
+    assert!(stdout.contains("to project work"), "{}", stdout);
+
+    let (stdout, _) = todust(
+        directory.path(),
+        &["add", "--yes", "-p", "flag", "flag entry"],
+    );
+    assert!(stdout.contains("to project flag"), "{}", stdout);
+
+    let env = [("TODUST_PROJECT", Path::new("environment"))];
+    let (stdout, _) = todust_with_env(
+        directory.path(),
+        &env,
+        &["add", "--yes", "environment entry"],
+    );
+    assert!(stdout.contains("to project environment"), "{}", stdout);
+
+    // The first add created the store so it did not use a project yet.
+    let (stdout, _) = todust(directory.path(), &["recent"]);
+    let projects = stdout
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .collect::<Vec<_>>();
+    assert_eq!(projects, vec!["environment", "flag", "work"], "{}", stdout);
+
+    todust(directory.path(), &["switch", "--clear"]);
+    let (stdout, _) = todust(directory.path(), &["add", "default entry"]);
+    assert!(stdout.contains("to project default"), "{}", stdout);
+}
+
+#[test]
+fn add_without_a_project_fails_when_a_project_is_required() {
+    let directory = tempfile::tempdir().unwrap();
+    todust(directory.path(), &["add", "first entry"]);
+
+    let config = directory
+        .path()
+        .join("config")
+        .join("todust")
+        .join("config.toml");
+    let text = std::fs::read_to_string(&config).unwrap();
+    std::fs::write(
+        &config,
+        format!("require_explicit_project = true\n{}", text),
+    )
+    .unwrap();
+
+    let (stdout, stderr) = todust(directory.path(), &["add", "second entry"]);
+    assert!(
+        stderr.contains("no project given and require_explicit_project is set"),
+        "{}{}",
+        stdout,
+        stderr
+    );
+
+    let env = [("TODUST_PROJECT", Path::new("work"))];
+    let (stdout, _) = todust_with_env(directory.path(), &env, &["add", "--yes", "env entry"]);
+    assert!(stdout.contains("to project work"), "{}", stdout);
+
+    let (stdout, _) = todust(directory.path(), &["add", "-p", "work", "flag entry"]);
+    assert!(stdout.contains("to project work"), "{}", stdout);
+
+    let (stdout, _) = todust(directory.path(), &["list"]);
+    assert!(!stdout.contains("second entry"), "{}", stdout);
+}